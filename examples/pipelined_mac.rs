@@ -1,46 +1,57 @@
 ﻿use rust_hls::ir::graph::Graph;
-use rust_hls::passes::pipeline::PipelineScheduler;
+use rust_hls::config::HlsConfig;
 use rust_hls::backend::verilog::generate_verilog_module;
+use rust_hls::backend::testbench::TestbenchRunner;
+use std::collections::HashMap;
+use std::path::Path;
 
 fn main() {
     println!("Rust HLS Pipeline Demo");
     println!("======================");
     
+    // Module name, pipeline, device and output settings
+    let config_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").join("pipelined_mac.toml");
+    let config = HlsConfig::load(&config_path).expect("Failed to load the build configuration");
+    println!("Configuration: {}", config_path.display());
+    
     // Create a pipelined multiplier-accumulator (MAC) example
     let mut graph = create_pipelined_mac();
     
-    // Enable pipelining with II=1, depth=4, unroll=1
-    graph.enable_pipeline(1, 4, 1);
+    // Enable pipelining with the configured II, depth and unroll factor
+    config.apply(&mut graph);
     
-    // Schedule the pipeline
-    let mut scheduler = PipelineScheduler::new();
+    // Schedule the pipeline against the configured device and clock
+    let mut scheduler = config.scheduler().expect("Invalid configuration");
     match scheduler.schedule_pipeline(&mut graph) {
         Ok(()) => {
             println!("Pipeline scheduling successful!");
             
             // Generate pipelined Verilog
-            let verilog = generate_verilog_module(&graph, "pipelined_mac");
+            let verilog = generate_verilog_module(&graph, &config.module_name, Some(&config.emit_options())).expect("Failed to generate Verilog");
             
             // Write to file
-            std::fs::create_dir_all("target/verilog_out").expect("Failed to create directory");
-            std::fs::write("target/verilog_out/pipelined_mac.v", &verilog)
+            let output_path = config.output_path();
+            std::fs::create_dir_all(&config.output_dir).expect("Failed to create directory");
+            std::fs::write(&output_path, &verilog)
                 .expect("Failed to write Verilog file");
                 
-            println!("Generated: target/verilog_out/pipelined_mac.v");
+            println!("Generated: {}", output_path.display());
             println!("Ready for Vivado synthesis!");
             
             // Display file size
-            if let Ok(metadata) = std::fs::metadata("target/verilog_out/pipelined_mac.v") {
+            if let Ok(metadata) = std::fs::metadata(&output_path) {
                 println!("File size: {} bytes", metadata.len());
             }
             
+            report_cycle_counts(&graph, &config);
+            
             // Show simulation instructions
             println!("\nNext Steps for Simulation:");
             println!("1. Open Vivado and create a new project");
-            println!("2. Add target/verilog_out/pipelined_mac.v as source");
+            println!("2. Add {} as source", output_path.display());
             println!("3. Create a testbench to verify MAC functionality");
             println!("4. Run behavioral simulation");
-            println!("5. Synthesize for AMD Alveo U50 target");
+            println!("5. Synthesize for the {} target", scheduler.device.name);
         }
         Err(e) => {
             println!("Pipeline scheduling failed: {}", e);
@@ -90,3 +101,27 @@ fn create_pipelined_mac() -> Graph {
     
     graph
 }
+
+/// Measure latency and streaming throughput on the Verilated model, when Verilator is installed
+fn report_cycle_counts(graph: &Graph, config: &HlsConfig) {
+    println!("\nMeasuring cycle counts with Verilator");
+    let mut runner = TestbenchRunner::from_config(config);
+    let testbench = match runner.prepare(graph).and_then(|()| runner.create_testbench()) {
+        Ok(testbench) => testbench,
+        Err(e) => {
+            println!("Skipping measurement: {}", e);
+            return;
+        }
+    };
+    let inputs: Vec<HashMap<String, u64>> = (1..=16u64)
+        .map(|i| ["a", "b", "c", "d", "e"].iter().map(|port| (port.to_string(), i)).collect())
+        .collect();
+    let ii = graph.pipeline_config.initiation_interval;
+    match testbench.run_test_timed(&inputs[0], graph).and_then(|(_, latency)| Ok((latency, testbench.run_stream(&inputs, ii, graph)?))) {
+        Ok((latency, stream)) => {
+            println!("Latency: {} cycles ({} scheduled stages)", latency, graph.pipeline_stages.len());
+            println!("Throughput: {:.2} results per cycle over {} transactions in {} cycles", stream.throughput(), inputs.len(), stream.total_cycles);
+        }
+        Err(e) => println!("Measurement failed: {}", e),
+    }
+}
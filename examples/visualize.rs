@@ -0,0 +1,42 @@
+use rust_hls::dsl::ast::*;
+use rust_hls::hft::ZeroPlusStrategy;
+use rust_hls::ir::graph::Graph;
+use rust_hls::ir::lower::lower_expr_to_graph;
+use rust_hls::passes::dce::run_dce_pass;
+use rust_hls::passes::pipeline::PipelineScheduler;
+
+fn main() {
+    println!("IR Graph Visualization");
+    println!("======================");
+
+    let adder = lower_expr_to_graph(&output("result", add(input("a", 32), input("b", 32))));
+
+    let mac = add(add(mul(input("a", 16), input("b", 16)), mul(input("c", 16), input("d", 16))), input("e", 16));
+    let mut pipelined_mac = lower_expr_to_graph(&output("result", mac));
+    pipelined_mac.enable_pipeline(1, 4, 1);
+    schedule(&mut pipelined_mac);
+
+    let mut hft = ZeroPlusStrategy::to_fpga_graph();
+    hft.enable_pipeline(1, 3, 1);
+    schedule(&mut hft);
+
+    std::fs::create_dir_all("target/graphs").expect("Failed to create directory");
+    for (module, graph) in [("adder", &adder), ("pipelined_mac", &pipelined_mac), ("hft_zero_plus", &hft)] {
+        let path = format!("target/graphs/{}.dot", module);
+        std::fs::write(&path, graph.to_dot(module)).expect("Failed to write DOT file");
+        println!("Generated: {} ({} nodes, {} stages)", path, graph.nodes.len(), graph.pipeline_stages.len());
+    }
+
+    println!("\nRender with: dot -Tsvg target/graphs/pipelined_mac.dot -o pipelined_mac.svg");
+}
+
+fn schedule(graph: &mut Graph) {
+    let mut scheduler = PipelineScheduler::new();
+    match scheduler.schedule_pipeline(graph) {
+        // Drop the unused pipeline registers the scheduler leaves behind
+        Ok(()) => {
+            run_dce_pass(graph);
+        }
+        Err(e) => println!("Pipeline scheduling failed: {}", e),
+    }
+}
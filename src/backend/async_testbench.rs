@@ -0,0 +1,118 @@
+//! Async access to the Verilated testbench
+//!
+//! Long simulations block the thread they run on, so these wrappers move
+//! each call onto tokio's blocking pool. The model behind the FFI pointer
+//! is not thread-safe; a `tokio::sync::Mutex` gives one test at a time
+//! exclusive use of it, and concurrent tests on one model queue up rather
+//! than interleave their inputs.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::future::join_all;
+use tokio::sync::Mutex;
+use crate::backend::testbench::{simulate_case, TestbenchRunner, VerilatorTestbench};
+use crate::backend::verilog::collect_output_ports;
+use crate::error::HlsError;
+use crate::ir::graph::Graph;
+
+/// `VerilatorTestbench` shared between tasks
+#[derive(Clone)]
+pub struct AsyncVerilatorTestbench {
+    testbench: Arc<Mutex<VerilatorTestbench>>,
+    /// Ports `run_test` reads back
+    outputs: Vec<String>,
+}
+
+impl AsyncVerilatorTestbench {
+    /// Wrap a testbench built from `graph`, whose outputs `run_test` returns
+    pub fn new(testbench: VerilatorTestbench, graph: &Graph) -> Self {
+        Self {
+            testbench: Arc::new(Mutex::new(testbench)),
+            outputs: collect_output_ports(graph).into_iter().map(|port| port.name).collect(),
+        }
+    }
+
+    /// Clock the model until it signals done, off the async runtime
+    pub async fn run_until_done(&self) -> Result<(), HlsError> {
+        let testbench = self.testbench.clone().lock_owned().await;
+        run_blocking(move || testbench.run_until_done()).await
+    }
+
+    /// Run one transaction from reset and return every output by port name
+    ///
+    /// The model stays locked from reset to the last read, so a concurrent
+    /// call waits for this one instead of overwriting its inputs.
+    pub async fn run_test(&self, inputs: HashMap<String, u64>) -> Result<HashMap<String, u64>, HlsError> {
+        let testbench = self.testbench.clone().lock_owned().await;
+        let outputs = self.outputs.clone();
+        run_blocking(move || {
+            testbench.reset()?;
+            for (port, &value) in &inputs {
+                testbench.set_input(port, value)?;
+            }
+            testbench.run_until_done()?;
+            outputs.into_iter()
+                .map(|port| Ok((port.clone(), testbench.get_output(&port)?)))
+                .collect()
+        }).await
+    }
+}
+
+async fn run_blocking<T, F>(work: F) -> Result<T, HlsError>
+where
+    F: FnOnce() -> Result<T, HlsError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work).await
+        .map_err(|e| HlsError::SimulationError(format!("simulation task failed: {}", e)))?
+}
+
+impl TestbenchRunner {
+    /// Run every input set as its own task and return their outputs in order
+    ///
+    /// Like `run_tests`, this falls back to the software `Simulator` when
+    /// the Verilated model is not prepared. Stops at the first failure.
+    pub async fn run_tests_async(&self, test_inputs: Vec<HashMap<String, u64>>, graph: &Graph) -> Result<Vec<HashMap<String, u64>>, HlsError> {
+        println!("🧪 Running {} test cases concurrently", test_inputs.len());
+        let results = match self.create_testbench() {
+            Ok(testbench) => {
+                let testbench = AsyncVerilatorTestbench::new(testbench, graph);
+                join_all(test_inputs.into_iter().map(|inputs| testbench.run_test(inputs))).await
+            }
+            Err(e) => {
+                println!("   ⚠️  FFI testbench unavailable: {}", e);
+                println!("   🔄 Falling back to software simulation");
+                test_inputs.iter().map(|inputs| simulate_case(inputs, graph)).collect()
+            }
+        };
+        results.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_additions_return_their_own_sums() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 32), input("b", 32))));
+        let mut runner = TestbenchRunner::new("test_adder_async");
+        match runner.prepare(&graph) {
+            Ok(()) => {}
+            Err(HlsError::CompilerNotFound(_)) => println!("Verilator not installed - checking the software fallback"),
+            Err(e) => panic!("Unexpected error preparing testbench: {}", e),
+        }
+
+        let test_inputs: Vec<HashMap<String, u64>> = (0..10u64)
+            .map(|i| HashMap::from([("a".to_string(), i * 1000), ("b".to_string(), i + 7)]))
+            .collect();
+        let results = runner.run_tests_async(test_inputs, &graph).await.unwrap();
+
+        assert_eq!(results.len(), 10);
+        for (i, outputs) in (0..10u64).zip(&results) {
+            assert_eq!(outputs["result"], i * 1000 + i + 7);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Concurrent builds of several modules
+//!
+//! Every module generates into its own files under `target/verilog_out` and
+//! builds in its own `target/sim/<module>`, so builds share nothing beyond
+//! those parent directories, which are created once up front.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::backend::testbench::TestbenchRunner;
+use crate::error::HlsError;
+use crate::ir::graph::Graph;
+
+/// Outcome of preparing one module
+pub struct ModuleBuild {
+    pub name: String,
+    /// The prepared runner, or why generation or the Verilator build failed
+    pub result: Result<TestbenchRunner, HlsError>,
+    /// Wall-clock time of the module's generation and build
+    pub elapsed: Duration,
+}
+
+/// `TestbenchRunner::prepare` every module, at most `parallelism` at a time
+///
+/// Verilator builds are memory-hungry, so `parallelism` caps the number of
+/// builds in flight; 0 is treated as 1. A failing module does not stop the
+/// others. Results come back in the order of `modules`.
+pub fn compile_all(modules: Vec<(String, Graph)>, parallelism: usize) -> Vec<ModuleBuild> {
+    let setup = ["verilog_out", "sim"].iter()
+        .try_for_each(|dir| fs::create_dir_all(Path::new("target").join(dir)));
+    if let Err(e) = setup {
+        return modules.into_iter()
+            .map(|(name, _)| ModuleBuild { name, result: Err(io::Error::new(e.kind(), e.to_string()).into()), elapsed: Duration::ZERO })
+            .collect();
+    }
+
+    let workers = parallelism.max(1).min(modules.len());
+    println!("🔧 Building {} modules, {} at a time", modules.len(), workers);
+    let next = AtomicUsize::new(0);
+    let builds: Vec<Mutex<Option<ModuleBuild>>> = modules.iter().map(|_| Mutex::new(None)).collect();
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some((name, graph)) = modules.get(index) else { break };
+                let start = Instant::now();
+                let mut runner = TestbenchRunner::new(name);
+                let result = runner.prepare(graph).map(|()| runner);
+                *builds[index].lock().unwrap() = Some(ModuleBuild { name: name.clone(), result, elapsed: start.elapsed() });
+            });
+        }
+    });
+
+    builds.into_iter()
+        .map(|build| build.into_inner().unwrap().expect("every module is built"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+
+    #[test]
+    fn test_every_module_reports_in_order_whatever_the_others_do() {
+        let modules: Vec<(String, Graph)> = (0..5)
+            .map(|i| (format!("test_batch_{}", i), lower_expr_to_graph(&output("result", add(input("a", 8), const_val(i, 8))))))
+            .collect();
+        let builds = compile_all(modules, 2);
+
+        let names: Vec<&str> = builds.iter().map(|build| build.name.as_str()).collect();
+        assert_eq!(names, ["test_batch_0", "test_batch_1", "test_batch_2", "test_batch_3", "test_batch_4"]);
+        for build in &builds {
+            match &build.result {
+                Ok(runner) => assert!(runner.get_directory_info().sim.ends_with(&build.name)),
+                Err(HlsError::CompilerNotFound(_)) => println!("Skipping {} - Verilator not installed", build.name),
+                Err(e) => panic!("Unexpected error building {}: {}", build.name, e),
+            }
+        }
+    }
+}
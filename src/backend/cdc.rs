@@ -0,0 +1,251 @@
+//! Clock-domain crossing synchronizers
+//!
+//! A register clocked by one domain and sampled by another can go
+//! metastable, so every crossing passes through a chain of flip-flops in the
+//! receiving domain. The flops carry Vivado's `ASYNC_REG` attribute, which
+//! places them together in one slice and keeps synthesis from merging or
+//! retiming them. Multi-bit values cross Gray-coded, so at most one bit is
+//! changing when the receiving clock samples it.
+
+/// Bits needed to count from 0 to `max`
+fn bits_for(max: u32) -> u32 {
+    (u32::BITS - max.leading_zeros()).max(1)
+}
+
+/// `(* ASYNC_REG = "TRUE" *) reg [..] name;` for one synchronizer flip-flop
+fn async_reg(width: u32, name: &str) -> String {
+    let range = if width > 1 { format!("[{}:0] ", width - 1) } else { String::new() };
+    format!("    (* ASYNC_REG = \"TRUE\" *) reg {}{};\n", range, name)
+}
+
+/// Generate an N-flop synchronizer module named `cdc_sync_<width>b_<stages>ff`
+///
+/// A single bit is sampled straight into `stages` `dst_clk` flops, from
+/// `async_in` to `sync_out`. Wider values are registered Gray-coded in the
+/// `src_clk` domain, from `src_data`, and decoded after the last flop onto
+/// `dst_data`; this is only safe for values that change by at most one
+/// between `dst_clk` samples, such as counters. Buses of arbitrary data
+/// cross through `generate_fifo_cdc` instead.
+///
+/// # Panics
+///
+/// If `signal_width` is zero or `stages` is less than two.
+pub fn generate_cdc_synchronizer(signal_width: u32, stages: u32) -> String {
+    assert!(signal_width > 0, "a synchronizer needs at least one bit");
+    assert!(stages >= 2, "a synchronizer needs at least two flip-flops, not {}", stages);
+    let name = format!("cdc_sync_{}b_{}ff", signal_width, stages);
+    let last = format!("sync_{}", stages - 1);
+    let mut verilog = String::new();
+
+    if signal_width == 1 {
+        verilog.push_str(&format!("// {}-flop single-bit synchronizer\n", stages));
+        verilog.push_str(&format!("module {} (\n", name));
+        verilog.push_str("    input  wire                    dst_clk,\n");
+        verilog.push_str("    input  wire                    async_in,\n");
+        verilog.push_str("    output wire                    sync_out\n");
+        verilog.push_str(");\n\n");
+        for stage in 0..stages {
+            verilog.push_str(&async_reg(1, &format!("sync_{}", stage)));
+        }
+        verilog.push_str("\n    always @(posedge dst_clk) begin\n");
+        verilog.push_str("        sync_0 <= async_in;\n");
+        for stage in 1..stages {
+            verilog.push_str(&format!("        sync_{} <= sync_{};\n", stage, stage - 1));
+        }
+        verilog.push_str("    end\n\n");
+        verilog.push_str(&format!("    assign sync_out = {};\n\n", last));
+        verilog.push_str("endmodule\n");
+        return verilog;
+    }
+
+    let msb = signal_width - 1;
+    verilog.push_str(&format!("// {}-flop Gray-code synchronizer for a {}-bit value that steps by at most one\n", stages, signal_width));
+    verilog.push_str(&format!("module {} (\n", name));
+    verilog.push_str("    input  wire                    src_clk,\n");
+    verilog.push_str(&format!("    input  wire [{}:0]  src_data,\n", msb));
+    verilog.push_str("    input  wire                    dst_clk,\n");
+    verilog.push_str(&format!("    output wire [{}:0]  dst_data\n", msb));
+    verilog.push_str(");\n\n");
+    // Registered in the source domain, so no combinational glitch reaches the crossing
+    verilog.push_str(&format!("    reg [{}:0] src_gray;\n", msb));
+    verilog.push_str("    always @(posedge src_clk)\n");
+    verilog.push_str("        src_gray <= src_data ^ (src_data >> 1);\n\n");
+    for stage in 0..stages {
+        verilog.push_str(&async_reg(signal_width, &format!("sync_{}", stage)));
+    }
+    verilog.push_str("\n    always @(posedge dst_clk) begin\n");
+    verilog.push_str("        sync_0 <= src_gray;\n");
+    for stage in 1..stages {
+        verilog.push_str(&format!("        sync_{} <= sync_{};\n", stage, stage - 1));
+    }
+    verilog.push_str("    end\n\n");
+    // Binary bit i is the XOR of Gray bits i and above
+    verilog.push_str("    genvar i;\n");
+    verilog.push_str("    generate\n");
+    verilog.push_str(&format!("        for (i = 0; i < {}; i = i + 1) begin : gray_to_binary\n", signal_width));
+    verilog.push_str(&format!("            assign dst_data[i] = ^({} >> i);\n", last));
+    verilog.push_str("        end\n");
+    verilog.push_str("    endgenerate\n\n");
+    verilog.push_str("endmodule\n");
+    verilog
+}
+
+/// Generate a dual-clock FIFO module named `async_fifo_<data_width>x<depth>`
+///
+/// Writes are taken on `wr_en` unless `full`, in the `wr_clk` domain; reads
+/// on `rd_en` unless `empty`, in the `rd_clk` domain, with `dout` holding the
+/// entry read on the previous `rd_clk` edge as in `fifo::generate_fifo`.
+/// Storage is a block RAM written on one clock and read on the other. Each
+/// side's pointer crosses Gray-coded through a two-flop synchronizer, one
+/// bit wider than the address so a full FIFO can be told from an empty one
+/// (Cummings, "Simulation and Synthesis Techniques for Asynchronous FIFO
+/// Design"). The flags are pessimistic: `full` and `empty` clear a couple of
+/// cycles after the other side frees or fills an entry.
+///
+/// # Panics
+///
+/// If `depth` is not a power of two of at least 4, or `data_width` is zero.
+pub fn generate_fifo_cdc(data_width: u32, depth: u32) -> String {
+    assert!(data_width > 0, "a FIFO needs at least one data bit");
+    assert!(depth >= 4 && depth.is_power_of_two(), "an asynchronous FIFO needs a power-of-two depth of at least 4, not {}", depth);
+    let addr_width = bits_for(depth - 1);
+    let ptr = addr_width; // MSB of the pointers, one above the address
+    let name = format!("async_fifo_{}x{}", data_width, depth);
+    let mut verilog = String::new();
+
+    verilog.push_str(&format!("// Dual-clock FIFO, {} x {} bits in block RAM, Gray-coded pointers\n", depth, data_width));
+    verilog.push_str(&format!("module {} (\n", name));
+    verilog.push_str("    input  wire                    wr_clk,\n");
+    verilog.push_str("    input  wire                    wr_rst_n,\n");
+    verilog.push_str("    input  wire                    wr_en,\n");
+    verilog.push_str(&format!("    input  wire [{}:0]  din,\n", data_width - 1));
+    verilog.push_str("    output reg                     full,\n");
+    verilog.push_str("    \n");
+    verilog.push_str("    input  wire                    rd_clk,\n");
+    verilog.push_str("    input  wire                    rd_rst_n,\n");
+    verilog.push_str("    input  wire                    rd_en,\n");
+    verilog.push_str(&format!("    output reg  [{}:0]  dout,\n", data_width - 1));
+    verilog.push_str("    output reg                     empty\n");
+    verilog.push_str(");\n\n");
+
+    verilog.push_str(&format!("    localparam DEPTH = {};\n\n", depth));
+    verilog.push_str("    (* ram_style = \"block\" *)\n");
+    verilog.push_str(&format!("    reg [{}:0] mem [0:DEPTH-1];\n\n", data_width - 1));
+
+    for pointer in ["wr_bin", "wr_gray", "rd_bin", "rd_gray"] {
+        verilog.push_str(&format!("    reg  [{}:0] {};\n", ptr, pointer));
+    }
+    verilog.push('\n');
+
+    // Write domain
+    verilog.push_str(&async_reg(ptr + 1, "rd_gray_sync_0"));
+    verilog.push_str(&async_reg(ptr + 1, "rd_gray_sync_1"));
+    verilog.push_str("    wire do_write = wr_en & ~full;\n");
+    verilog.push_str(&format!("    wire [{}:0] wr_bin_next = wr_bin + {{{}'d0, do_write}};\n", ptr, ptr));
+    verilog.push_str(&format!("    wire [{}:0] wr_gray_next = wr_bin_next ^ (wr_bin_next >> 1);\n", ptr));
+    // Full when the write pointer has lapped the read pointer: Gray MSBs differ, the rest match
+    let lapped = format!("{{~rd_gray_sync_1[{}:{}], rd_gray_sync_1[{}:0]}}", ptr, ptr - 1, ptr - 2);
+    verilog.push_str(&format!("    wire full_next = (wr_gray_next == {});\n\n", lapped));
+    verilog.push_str("    always @(posedge wr_clk) begin\n");
+    verilog.push_str("        if (do_write)\n");
+    verilog.push_str(&format!("            mem[wr_bin[{}:0]] <= din;\n", addr_width - 1));
+    verilog.push_str("    end\n\n");
+    verilog.push_str("    always @(posedge wr_clk) begin\n");
+    verilog.push_str("        if (!wr_rst_n) begin\n");
+    verilog.push_str(&format!("            wr_bin <= {}'d0;\n", ptr + 1));
+    verilog.push_str(&format!("            wr_gray <= {}'d0;\n", ptr + 1));
+    verilog.push_str(&format!("            rd_gray_sync_0 <= {}'d0;\n", ptr + 1));
+    verilog.push_str(&format!("            rd_gray_sync_1 <= {}'d0;\n", ptr + 1));
+    verilog.push_str("            full <= 1'b0;\n");
+    verilog.push_str("        end else begin\n");
+    verilog.push_str("            wr_bin <= wr_bin_next;\n");
+    verilog.push_str("            wr_gray <= wr_gray_next;\n");
+    verilog.push_str("            rd_gray_sync_0 <= rd_gray;\n");
+    verilog.push_str("            rd_gray_sync_1 <= rd_gray_sync_0;\n");
+    verilog.push_str("            full <= full_next;\n");
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n\n");
+
+    // Read domain
+    verilog.push_str(&async_reg(ptr + 1, "wr_gray_sync_0"));
+    verilog.push_str(&async_reg(ptr + 1, "wr_gray_sync_1"));
+    verilog.push_str("    wire do_read = rd_en & ~empty;\n");
+    verilog.push_str(&format!("    wire [{}:0] rd_bin_next = rd_bin + {{{}'d0, do_read}};\n", ptr, ptr));
+    verilog.push_str(&format!("    wire [{}:0] rd_gray_next = rd_bin_next ^ (rd_bin_next >> 1);\n", ptr));
+    verilog.push_str("    wire empty_next = (rd_gray_next == wr_gray_sync_1);\n\n");
+    verilog.push_str("    always @(posedge rd_clk) begin\n");
+    verilog.push_str("        if (do_read)\n");
+    verilog.push_str(&format!("            dout <= mem[rd_bin[{}:0]];\n", addr_width - 1));
+    verilog.push_str("    end\n\n");
+    verilog.push_str("    always @(posedge rd_clk) begin\n");
+    verilog.push_str("        if (!rd_rst_n) begin\n");
+    verilog.push_str(&format!("            rd_bin <= {}'d0;\n", ptr + 1));
+    verilog.push_str(&format!("            rd_gray <= {}'d0;\n", ptr + 1));
+    verilog.push_str(&format!("            wr_gray_sync_0 <= {}'d0;\n", ptr + 1));
+    verilog.push_str(&format!("            wr_gray_sync_1 <= {}'d0;\n", ptr + 1));
+    verilog.push_str("            empty <= 1'b1;\n");
+    verilog.push_str("        end else begin\n");
+    verilog.push_str("            rd_bin <= rd_bin_next;\n");
+    verilog.push_str("            rd_gray <= rd_gray_next;\n");
+    verilog.push_str("            wr_gray_sync_0 <= wr_gray;\n");
+    verilog.push_str("            wr_gray_sync_1 <= wr_gray_sync_0;\n");
+    verilog.push_str("            empty <= empty_next;\n");
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n\n");
+    verilog.push_str("endmodule\n");
+    verilog
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names of every `reg` whose name marks it as a synchronizer flop, and whether it has `ASYNC_REG`
+    fn sync_registers(verilog: &str) -> Vec<(String, bool)> {
+        verilog.lines()
+            .filter_map(|line| {
+                let declared = line.split(" reg ").nth(1)?;
+                let name = declared.trim_end_matches(';').rsplit(' ').next()?.to_string();
+                name.contains("sync_").then(|| (name, line.contains("(* ASYNC_REG = \"TRUE\" *)")))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_every_synchronizer_flop_is_async_reg() {
+        let single = generate_cdc_synchronizer(1, 3);
+        assert!(single.contains("module cdc_sync_1b_3ff ("));
+        assert_eq!(sync_registers(&single), [("sync_0".to_string(), true), ("sync_1".to_string(), true), ("sync_2".to_string(), true)]);
+        assert!(single.contains("sync_2 <= sync_1;"));
+        assert!(single.contains("assign sync_out = sync_2;"));
+
+        let bus = generate_cdc_synchronizer(8, 2);
+        assert_eq!(sync_registers(&bus).len(), 2);
+        assert!(bus.contains("(* ASYNC_REG = \"TRUE\" *) reg [7:0] sync_1;"));
+        assert!(bus.contains("src_gray <= src_data ^ (src_data >> 1);"));
+        assert!(bus.contains("assign dst_data[i] = ^(sync_1 >> i);"));
+
+        let fifo = generate_fifo_cdc(32, 16);
+        let registers = sync_registers(&fifo);
+        assert_eq!(registers.len(), 4);
+        assert!(registers.iter().all(|(_, async_reg)| *async_reg));
+        // Plain pointers stay free for synthesis to optimize
+        assert!(fifo.contains("    reg  [4:0] wr_gray;\n"));
+    }
+
+    #[test]
+    fn test_async_fifo_compares_gray_pointers_one_bit_wider_than_the_address() {
+        let fifo = generate_fifo_cdc(32, 16);
+        assert!(fifo.contains("module async_fifo_32x16 ("));
+        assert!(fifo.contains("reg [31:0] mem [0:DEPTH-1];"));
+        assert!(fifo.contains("mem[wr_bin[3:0]] <= din;"));
+        assert!(fifo.contains("dout <= mem[rd_bin[3:0]];"));
+        assert!(fifo.contains("wire [4:0] wr_bin_next = wr_bin + {4'd0, do_write};"));
+        assert!(fifo.contains("wire full_next = (wr_gray_next == {~rd_gray_sync_1[4:3], rd_gray_sync_1[2:0]});"));
+        assert!(fifo.contains("wire empty_next = (rd_gray_next == wr_gray_sync_1);"));
+        assert!(fifo.contains("empty <= 1'b1;"));
+
+        assert!(generate_fifo_cdc(8, 4).contains("(wr_gray_next == {~rd_gray_sync_1[2:1], rd_gray_sync_1[0:0]})"));
+        assert!(std::panic::catch_unwind(|| generate_fifo_cdc(8, 12)).is_err());
+    }
+}
@@ -0,0 +1,436 @@
+//! Cycle-accurate model of a scheduled pipeline
+//!
+//! `Simulator` evaluates a graph in one shot. `CycleSimulator` runs the same
+//! evaluation, but moves each set of inputs through `graph.pipeline_stages`
+//! one stage per clock, so latency, back-to-back throughput and the values
+//! each stage holds can be checked without Verilator. A transaction issued
+//! in cycle `c` produces its outputs in cycle `c + depth`, where `depth` is
+//! the number of cycles the schedule spans, as `ap_done` does in the
+//! pipelined Verilog.
+//!
+//! `enable_vcd` also records every port and stage register of the module
+//! the Verilog backend emits for the graph, under the same names, so the
+//! waveform can be opened next to Verilator's trace.
+
+use crate::backend::sim::{as_unsigned, Simulator};
+use crate::backend::verilog::{collect_input_ports, collect_output_ports, scheduled_depth, stage_registers};
+use crate::error::HlsError;
+use crate::ir::graph::{Graph, NodeId, Operation, ValueId};
+use crate::passes::timing::schedule_of;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Half a clock period in the VCD, in its 1 ns timescale
+const VCD_HALF_PERIOD: usize = 5;
+
+/// One set of inputs moving through the pipeline
+#[derive(Clone)]
+struct Transaction {
+    issued: usize,
+    values: HashMap<ValueId, i64>,
+    outputs: HashMap<String, i64>,
+}
+
+/// Clock-by-clock simulator for a scheduled graph
+pub struct CycleSimulator<'g> {
+    graph: &'g Graph,
+    functional: Simulator,
+    schedule: HashMap<NodeId, usize>,
+    depth: usize,
+    cycle: usize,
+    last_issue: Option<usize>,
+    in_flight: VecDeque<Transaction>,
+    completed: VecDeque<HashMap<String, i64>>,
+    vcd: Option<VcdWriter>,
+}
+
+/// What a VCD signal shows
+enum SignalSource {
+    /// Sum of these values, as in a stage register
+    Values(Vec<ValueId>),
+    Output(String),
+}
+
+/// One `$var` of the VCD
+struct VcdSignal {
+    name: String,
+    code: String,
+    width: u32,
+    /// Cycles after issue a transaction's value appears on the signal
+    delay: usize,
+    source: SignalSource,
+    current: Option<u64>,
+}
+
+/// Value changes written out as the simulator steps
+struct VcdWriter {
+    out: BufWriter<File>,
+    signals: Vec<VcdSignal>,
+    /// Transactions still shown on some signal, oldest first
+    history: VecDeque<Transaction>,
+    /// Time of the last `#` line, which the initial values are dumped at
+    time: usize,
+    error: Option<std::io::Error>,
+}
+
+impl<'g> CycleSimulator<'g> {
+    /// An empty pipeline at cycle 0, as after reset
+    ///
+    /// The graph must be scheduled, and cannot be the body of a rolled loop,
+    /// whose iterations its loop controller starts rather than `ap_start`.
+    pub fn new(graph: &'g Graph) -> Result<Self, HlsError> {
+        if graph.pipeline_stages.is_empty() {
+            return Err(HlsError::Unsupported("cycle simulation of an unscheduled graph".to_string()));
+        }
+        if graph.loop_trip_count.is_some() {
+            return Err(HlsError::Unsupported("cycle simulation of a rolled loop".to_string()));
+        }
+        Ok(Self {
+            graph,
+            functional: Simulator::new(),
+            schedule: schedule_of(graph),
+            depth: scheduled_depth(graph),
+            cycle: 0,
+            last_issue: None,
+            in_flight: VecDeque::new(),
+            completed: VecDeque::new(),
+            vcd: None,
+        })
+    }
+
+    /// Clock edges taken so far
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    /// Cycles from issuing a transaction to its outputs
+    pub fn latency(&self) -> usize {
+        self.depth
+    }
+
+    /// Transactions issued whose outputs are not ready yet
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Issue a transaction in the current cycle
+    ///
+    /// Inputs left out keep the value they had in the previous transaction,
+    /// as ports do. Fails if a transaction was issued fewer than II cycles
+    /// ago, including earlier in this cycle.
+    pub fn push_inputs(&mut self, inputs: &HashMap<String, i64>) -> Result<(), HlsError> {
+        let ii = self.graph.pipeline_config.initiation_interval.max(1);
+        if let Some(last) = self.last_issue.filter(|last| self.cycle < last + ii) {
+            return Err(HlsError::SimulationError(format!(
+                "inputs in cycle {} come {} cycles after the last, inside II={}",
+                self.cycle, self.cycle - last, ii
+            )));
+        }
+        for (name, value) in inputs {
+            self.functional.set_input(name, *value, self.graph);
+        }
+        let outputs = self.functional.simulate(self.graph)?;
+        let values = self.graph.nodes.iter()
+            .filter_map(|node| node.output)
+            .filter_map(|value| Some((value, self.functional.value(value)?)))
+            .collect();
+        let transaction = Transaction { issued: self.cycle, values, outputs };
+        if let Some(vcd) = &mut self.vcd {
+            vcd.history.push_back(transaction.clone());
+        }
+        self.in_flight.push_back(transaction);
+        self.last_issue = Some(self.cycle);
+        Ok(())
+    }
+
+    /// Record a VCD of every port and stage register at `path`, from the current cycle on
+    ///
+    /// Must be called before any inputs are issued. Stage registers are named
+    /// and timed as in the module `generate_verilog_module` emits: they show
+    /// the latest transaction to pass them, as a register that holds its value
+    /// or is fed held inputs would, and `x` until the first one does, unless
+    /// the module resets them. Call `finish_vcd` to flush the file.
+    pub fn enable_vcd<P: AsRef<Path>>(&mut self, path: P) -> Result<(), HlsError> {
+        if self.last_issue.is_some() {
+            return Err(HlsError::Unsupported("a VCD starting after inputs were issued".to_string()));
+        }
+        // Code `!` is the clock
+        let mut signals = Vec::new();
+        let mut signal = |name: String, width: Option<u32>, delay: usize, source: SignalSource, reset: bool| {
+            let code = vcd_code(signals.len() + 1);
+            signals.push(VcdSignal { name, code, width: width.unwrap_or(32), delay, source, current: reset.then_some(0) });
+        };
+        for port in collect_input_ports(self.graph) {
+            let load = self.graph.nodes.iter()
+                .find(|node| matches!(&node.op, Operation::Load(name) if *name == port.name))
+                .and_then(|node| node.output)
+                .expect("input ports come from loads");
+            signal(port.name, port.width, 0, SignalSource::Values(vec![load]), false);
+        }
+        for register in stage_registers(self.graph) {
+            signal(register.name, register.width, register.stage + 1, SignalSource::Values(register.terms), register.reset);
+        }
+        for port in collect_output_ports(self.graph) {
+            signal(port.name.clone(), port.width, self.depth, SignalSource::Output(port.name), true);
+        }
+
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "$version rust_hls cycle simulator $end")?;
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module TOP $end")?;
+        writeln!(out, "$var wire 1 {} ap_clk $end", vcd_code(0))?;
+        for signal in &signals {
+            writeln!(out, "$var wire {} {} {} $end", signal.width, signal.code, signal.name)?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+        writeln!(out, "#{}", self.cycle * 2 * VCD_HALF_PERIOD)?;
+        writeln!(out, "$dumpvars")?;
+        writeln!(out, "{}", vcd_value(Some(0), 1, &vcd_code(0)))?;
+        for signal in &signals {
+            writeln!(out, "{}", vcd_value(signal.current, signal.width, &signal.code))?;
+        }
+        writeln!(out, "$end")?;
+        self.vcd = Some(VcdWriter { out, signals, history: VecDeque::new(), time: self.cycle * 2 * VCD_HALF_PERIOD, error: None });
+        Ok(())
+    }
+
+    /// Flush the VCD, reporting any write that failed while stepping
+    pub fn finish_vcd(&mut self) -> Result<(), HlsError> {
+        let Some(vcd) = &mut self.vcd else { return Ok(()) };
+        if let Some(error) = vcd.error.take() {
+            return Err(error.into());
+        }
+        vcd.out.flush()?;
+        Ok(())
+    }
+
+    /// Advance one clock edge
+    pub fn step(&mut self) {
+        if let Some(vcd) = &mut self.vcd {
+            if let Err(error) = vcd.dump(self.cycle, self.depth) {
+                vcd.error.get_or_insert(error);
+            }
+        }
+        self.cycle += 1;
+        while self.in_flight.front().is_some_and(|transaction| self.cycle - transaction.issued >= self.depth) {
+            let transaction = self.in_flight.pop_front().expect("front was just checked");
+            self.completed.push_back(transaction.outputs);
+        }
+    }
+
+    /// Outputs of the oldest finished transaction not yet taken
+    pub fn pop_outputs(&mut self) -> Option<HashMap<String, i64>> {
+        self.completed.pop_front()
+    }
+
+    /// Advance one clock edge and take the outputs that finished on it, if any
+    ///
+    /// At II=1 one transaction can finish per cycle, so nothing is left
+    /// waiting in `pop_outputs` when every cycle is ticked.
+    pub fn tick(&mut self) -> Option<HashMap<String, i64>> {
+        self.step();
+        self.pop_outputs()
+    }
+
+    /// Values computed so far by the transaction in `stage`, if one is there
+    ///
+    /// These are the results of every node scheduled in `stage` or before,
+    /// which the stage registers carry for operations further down.
+    pub fn stage_values(&self, stage: usize) -> Option<HashMap<ValueId, i64>> {
+        let transaction = self.in_flight.iter().find(|transaction| self.cycle - transaction.issued == stage)?;
+        Some(self.graph.nodes.iter()
+            .filter(|node| self.schedule.get(&node.id).is_some_and(|&cycle| cycle <= stage))
+            .filter_map(|node| node.output)
+            .filter_map(|value| Some((value, *transaction.values.get(&value)?)))
+            .collect())
+    }
+}
+
+impl VcdWriter {
+    /// Write the clock edges of `cycle` and every signal that changes in it
+    fn dump(&mut self, cycle: usize, depth: usize) -> std::io::Result<()> {
+        // The oldest transaction is no longer shown once a newer one has reached every signal
+        while self.history.get(1).is_some_and(|next| next.issued + depth <= cycle) {
+            self.history.pop_front();
+        }
+        let time = cycle * 2 * VCD_HALF_PERIOD;
+        if time != self.time {
+            writeln!(self.out, "#{}", time)?;
+        }
+        writeln!(self.out, "{}", vcd_value(Some(1), 1, &vcd_code(0)))?;
+        for signal in &mut self.signals {
+            let shown = self.history.iter().rev().find(|transaction| transaction.issued + signal.delay <= cycle);
+            let value = shown.map(|transaction| match &signal.source {
+                SignalSource::Values(values) => values.iter()
+                    .map(|value| transaction.values.get(value).copied().unwrap_or(0))
+                    .fold(0i64, i64::wrapping_add),
+                SignalSource::Output(name) => transaction.outputs[name],
+            });
+            let value = value.map(|value| as_unsigned(value, Some(signal.width))).or(signal.current);
+            if value != signal.current {
+                signal.current = value;
+                writeln!(self.out, "{}", vcd_value(value, signal.width, &signal.code))?;
+            }
+        }
+        self.time = time + VCD_HALF_PERIOD;
+        writeln!(self.out, "#{}", self.time)?;
+        writeln!(self.out, "{}", vcd_value(Some(0), 1, &vcd_code(0)))
+    }
+}
+
+/// Short VCD identifier for the `index`th signal, in printable ASCII
+fn vcd_code(mut index: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}
+
+/// Value change line: `1!` for single bits, `b1010 "` otherwise; `None` is undefined
+fn vcd_value(value: Option<u64>, width: u32, code: &str) -> String {
+    match (value, width) {
+        (Some(value), 1) => format!("{}{}", value & 1, code),
+        (None, 1) => format!("x{}", code),
+        (Some(value), _) => format!("b{:b} {}", value, code),
+        (None, _) => format!("bx {}", code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::{lower_expr_to_graph, lowered_mac};
+    use crate::passes::pipeline::PipelineScheduler;
+
+    /// `a * b + c * d + e`, with two-cycle multiplies: a 5-cycle pipeline at II=`ii`
+    fn mac(ii: usize) -> Graph {
+        let mut graph = lowered_mac();
+        graph.enable_pipeline(ii, 8, 1);
+        let mut scheduler = PipelineScheduler { mul_latency: Some(2), ..PipelineScheduler::new() };
+        scheduler.schedule_pipeline(&mut graph).unwrap();
+        graph
+    }
+
+    fn inputs(values: [i64; 5]) -> HashMap<String, i64> {
+        ["a", "b", "c", "d", "e"].into_iter().map(str::to_string).zip(values).collect()
+    }
+
+    #[test]
+    fn test_back_to_back_mac_results_leave_one_per_cycle() {
+        let graph = mac(1);
+        let mut sim = CycleSimulator::new(&graph).unwrap();
+        assert_eq!(sim.latency(), 5);
+
+        for (i, set) in [[1, 2, 3, 4, 5], [10, 10, 10, 10, 10], [0, 7, 6, 5, 4]].into_iter().enumerate() {
+            sim.push_inputs(&inputs(set)).unwrap();
+            // One transaction per cycle at II=1
+            assert!(sim.push_inputs(&inputs(set)).is_err());
+            sim.step();
+            assert_eq!(sim.in_flight(), i + 1);
+        }
+        // The first set has reached stage 3: its products are there, the final sum is not
+        let in_stage_3 = sim.stage_values(3).unwrap();
+        assert!(in_stage_3.values().any(|&value| value == 12));
+        assert!(!in_stage_3.values().any(|&value| value == 19));
+
+        let mut results = Vec::new();
+        while sim.cycle() < 10 {
+            sim.step();
+            if let Some(outputs) = sim.pop_outputs() {
+                results.push((sim.cycle(), outputs["result"]));
+            }
+        }
+        assert_eq!(results, vec![(5, 19), (6, 210), (7, 34)]);
+        assert_eq!(sim.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_five_macs_issued_every_tick_finish_every_tick() {
+        let graph = mac(1);
+        let mut sim = CycleSimulator::new(&graph).unwrap();
+        let sets = [[1, 2, 3, 4, 5], [2, 2, 2, 2, 2], [3, 0, 0, 3, 1], [0, 0, 0, 0, 9], [5, 5, 5, 5, 5]];
+
+        let mut finished = Vec::new();
+        for tick in 1..=sets.len() + sim.latency() {
+            if let Some(set) = sets.get(tick - 1) {
+                sim.push_inputs(&inputs(*set)).unwrap();
+            }
+            if let Some(outputs) = sim.tick() {
+                finished.push((tick, outputs["result"]));
+            }
+        }
+        // The first result comes `latency` ticks after the first inputs, then one per tick
+        let ticks: Vec<usize> = finished.iter().map(|(tick, _)| *tick).collect();
+        assert_eq!(ticks, (sim.latency()..sim.latency() + sets.len()).collect::<Vec<_>>());
+        assert_eq!(finished.iter().map(|(_, result)| *result).collect::<Vec<_>>(), vec![19, 10, 1, 9, 55]);
+    }
+
+    #[test]
+    fn test_initiation_interval_spaces_transactions() {
+        let graph = mac(2);
+        let mut sim = CycleSimulator::new(&graph).unwrap();
+        sim.push_inputs(&inputs([1, 1, 1, 1, 1])).unwrap();
+        sim.step();
+        let error = sim.push_inputs(&inputs([2, 2, 2, 2, 2])).unwrap_err();
+        assert!(error.to_string().contains("1 cycles after the last, inside II=2"));
+        sim.step();
+        sim.push_inputs(&inputs([2, 2, 2, 2, 2])).unwrap();
+
+        assert!(CycleSimulator::new(&lower_expr_to_graph(&output("r", input("a", 8)))).is_err());
+    }
+
+    #[test]
+    fn test_vcd_declares_the_ports_and_stage_registers_of_the_module() {
+        let graph = mac(1);
+        let path = std::env::temp_dir().join("rust_hls_cycle_sim_mac.vcd");
+        let mut sim = CycleSimulator::new(&graph).unwrap();
+        sim.enable_vcd(&path).unwrap();
+        for set in [[1, 2, 3, 4, 5], [10, 10, 10, 10, 10]] {
+            sim.push_inputs(&inputs(set)).unwrap();
+            sim.step();
+        }
+        while sim.cycle() < 8 {
+            sim.step();
+        }
+        sim.finish_vcd().unwrap();
+        assert!(sim.enable_vcd(&path).is_err());
+
+        let vcd = std::fs::read_to_string(&path).unwrap();
+        let (header, changes) = vcd.split_once("$enddefinitions $end\n").unwrap();
+        let vars: HashMap<&str, (u32, &str)> = header.lines()
+            .filter_map(|line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["$var", "wire", width, code, name, "$end"] => Some((name, (width.parse().unwrap(), code))),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vars["ap_clk"].0, 1);
+        assert_eq!(vars["a"].0, 16);
+        assert_eq!(vars["e_reg2"].0, 16);
+        assert_eq!(vars["prod1_reg1"].0, 32);
+        assert_eq!(vars["result"].0, vars["result_reg4"].0);
+
+        // Every register is one the Verilog declares
+        let verilog = crate::backend::verilog::generate_verilog_module(&graph, "mac", None).unwrap();
+        for name in vars.keys().filter(|name| name.contains("_reg")) {
+            assert!(verilog.contains(&format!(" {};\n", name)), "{} is not declared", name);
+        }
+        assert_eq!(vars.len(), 1 + 5 + stage_registers(&graph).len() + 1);
+
+        // Both results leave the output register one cycle apart, from cycle 5
+        let result = vars["result"].1;
+        let at = |time: usize| changes.find(&format!("#{}\n", time)).unwrap();
+        let first = changes.find(&format!("b10011 {}\n", result)).unwrap();
+        let second = changes.find(&format!("b{:b} {}\n", 210, result)).unwrap();
+        assert!(at(50) < first && first < at(55));
+        assert!(at(60) < second && second < at(65));
+    }
+}
@@ -0,0 +1,207 @@
+//! Signal processing primitives built on DSP48E2 slices
+//!
+//! The FIR filter is the systolic direct form: each tap's DSP48E2 adds its
+//! product to the partial sum cascaded from the previous tap over
+//! PCOUT/PCIN, the dedicated column routing between neighbouring slices.
+//! The sum gains one register per tap, so the delay line holds two
+//! registers per tap to keep every product lined up with its sample.
+
+use crate::backend::verilog::DSP48E2_TIE_OFFS;
+use crate::ir::graph::{reduction_levels, Graph, Operation};
+
+/// Sample width of `build_fir_graph`
+pub const FIR_GRAPH_DATA_WIDTH: u32 = 16;
+
+/// Coefficient width of `build_fir_graph`, the DSP48E2's B port
+pub const FIR_GRAPH_COEFF_WIDTH: u32 = 18;
+
+/// Bits of a FIR output: a full product plus one bit per doubling of the tap count
+fn accumulator_width(data_width: u32, coeff_width: u32, taps: usize) -> u32 {
+    data_width + coeff_width + reduction_levels(taps) as u32
+}
+
+/// Fully pipelined direct-form FIR filter `name`, one DSP48E2 per coefficient
+///
+/// Samples and coefficients are signed. Every `x_valid` cycle shifts
+/// `x_in` into the delay line and advances the whole filter, so output
+/// y[n] = Σ c_k * x[n - k] leaves on `y_out` with `y_valid` once sample
+/// n + taps has arrived; feed zeros to flush the last outputs. The delay
+/// line and partial sums start from zero after reset.
+///
+/// Panics unless there are coefficients, `data_width` is 1 to 27 bits,
+/// `coeff_width` 1 to 18 bits, each coefficient fits `coeff_width` and the
+/// sum fits the 48-bit accumulator.
+pub fn generate_fir_filter(name: &str, coefficients: &[i32], data_width: u32, coeff_width: u32) -> String {
+    let taps = coefficients.len();
+    assert!(taps > 0, "a FIR filter needs at least one coefficient");
+    assert!((1..=27).contains(&data_width), "FIR sample width must be 1 to 27 bits, not {}", data_width);
+    assert!((1..=18).contains(&coeff_width), "FIR coefficient width must be 1 to 18 bits, not {}", coeff_width);
+    let bound = 1i64 << (coeff_width - 1);
+    for &coefficient in coefficients {
+        assert!((-bound..bound).contains(&i64::from(coefficient)), "coefficient {} does not fit in {} bits", coefficient, coeff_width);
+    }
+    let sum_width = accumulator_width(data_width, coeff_width, taps);
+    assert!(sum_width <= 48, "a {}-tap filter needs a {}-bit accumulator, wider than the DSP48E2's 48", taps, sum_width);
+    let delays = 2 * taps - 1;
+
+    let mut verilog = String::new();
+    verilog.push_str(&format!("// {}-tap FIR filter, coefficients {:?}\n", taps, coefficients));
+    verilog.push_str(&format!("module {} (\n", name));
+    verilog.push_str("    input  wire ap_clk,\n");
+    verilog.push_str("    input  wire ap_rst_n,\n");
+    verilog.push_str("    input  wire x_valid,\n");
+    verilog.push_str(&format!("    input  wire signed [{}:0] x_in,\n", data_width - 1));
+    verilog.push_str("    output reg  y_valid,\n");
+    verilog.push_str(&format!("    output wire signed [{}:0] y_out\n", sum_width - 1));
+    verilog.push_str(");\n\n");
+
+    // Tap i reads x_d{2i}, two registers per partial sum register ahead of it
+    verilog.push_str("    // Delay line\n");
+    for delay in 0..delays {
+        verilog.push_str(&format!("    (* SRL_STYLE = \"register\" *) reg signed [{}:0] x_d{};\n", data_width - 1, delay));
+    }
+    verilog.push_str("    always @(posedge ap_clk) begin\n");
+    verilog.push_str("        if (!ap_rst_n) begin\n");
+    for delay in 0..delays {
+        verilog.push_str(&format!("            x_d{} <= {}'d0;\n", delay, data_width));
+    }
+    verilog.push_str("        end else if (x_valid) begin\n");
+    verilog.push_str("            x_d0 <= x_in;\n");
+    for delay in 1..delays {
+        verilog.push_str(&format!("            x_d{} <= x_d{};\n", delay, delay - 1));
+    }
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n\n");
+
+    verilog.push_str("    // Coefficients\n");
+    for (tap, &coefficient) in coefficients.iter().enumerate() {
+        let sign = if coefficient < 0 { "-" } else { "" };
+        verilog.push_str(&format!(
+            "    wire signed [{}:0] coeff{} = {}{}'sd{};\n",
+            coeff_width - 1, tap, sign, coeff_width, coefficient.unsigned_abs()
+        ));
+    }
+    verilog.push('\n');
+
+    for tap in 0..taps {
+        verilog.push_str(&fir_tap(tap, 2 * tap));
+    }
+    verilog.push_str(&format!("    assign y_out = dsp{}_p[{}:0];\n\n", taps - 1, sum_width - 1));
+
+    // The first output that saw the first sample on every tap leaves taps + 1 samples in
+    let fill_width = usize::BITS - taps.leading_zeros();
+    verilog.push_str(&format!("    reg [{}:0] fill;  // Samples accepted, up to {}\n", fill_width - 1, taps));
+    verilog.push_str("    always @(posedge ap_clk) begin\n");
+    verilog.push_str("        if (!ap_rst_n) begin\n");
+    verilog.push_str("            fill <= 0;\n");
+    verilog.push_str("            y_valid <= 1'b0;\n");
+    verilog.push_str("        end else begin\n");
+    verilog.push_str(&format!("            y_valid <= x_valid && fill == {};\n", taps));
+    verilog.push_str(&format!("            if (x_valid && fill != {}) fill <= fill + 1;\n", taps));
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n\n");
+    verilog.push_str("endmodule\n");
+    verilog
+}
+
+/// DSP48E2 for `tap`: P <= x_d{delay} * coeff{tap} + PCIN, registered once in PREG while `x_valid` holds
+fn fir_tap(tap: usize, delay: usize) -> String {
+    let mut verilog = String::new();
+    // Assigning to the wider port wires sign-extends the signed operands
+    verilog.push_str(&format!("    wire [29:0] dsp{}_a = x_d{};\n", tap, delay));
+    verilog.push_str(&format!("    wire [17:0] dsp{}_b = coeff{};\n", tap, tap));
+    verilog.push_str(&format!("    wire [47:0] dsp{}_p;\n", tap));
+    verilog.push_str(&format!("    wire [47:0] dsp{}_pcout;\n", tap));
+    verilog.push_str("    DSP48E2 #(\n");
+    let parameters = [
+        ".AREG(0)", ".ACASCREG(0)", ".BREG(0)", ".BCASCREG(0)", ".MREG(0)", ".PREG(1)",
+        ".CREG(0)", ".DREG(0)", ".ADREG(0)", ".CARRYINREG(0)", ".CARRYINSELREG(0)",
+        ".INMODEREG(0)", ".OPMODEREG(0)", ".ALUMODEREG(0)",
+        ".AMULTSEL(\"A\")", ".BMULTSEL(\"B\")", ".PREADDINSEL(\"A\")", ".USE_MULT(\"MULTIPLY\")",
+        ".USE_SIMD(\"ONE48\")", ".USE_PATTERN_DETECT(\"NO_PATDET\")",
+    ];
+    verilog.push_str(&parameters.iter().map(|p| format!("        {}", p)).collect::<Vec<_>>().join(",\n"));
+    verilog.push_str(&format!("\n    ) dsp{} (\n", tap));
+    let (opmode, pcin) = if tap == 0 {
+        // X = Y = M, Z = W = 0
+        ("9'b000000101", "48'd0".to_string())
+    } else {
+        // X = Y = M, Z = PCIN, W = 0
+        ("9'b000010101", format!("dsp{}_pcout", tap - 1))
+    };
+    let mut ports = vec![
+        format!(".A(dsp{}_a)", tap),
+        format!(".B(dsp{}_b)", tap),
+        format!(".P(dsp{}_p)", tap),
+        format!(".PCIN({})", pcin),
+        format!(".PCOUT(dsp{}_pcout)", tap),
+        format!(".OPMODE({})", opmode),
+    ];
+    ports.extend(["CEA1", "CEA2", "CEB1", "CEB2", "CEM", "CEP"].iter().map(|port| format!(".{}(x_valid)", port)));
+    ports.extend(DSP48E2_TIE_OFFS.iter()
+        .filter(|port| ![".PCIN(", ".PCOUT(", ".OPMODE("].iter().any(|overridden| port.starts_with(overridden)))
+        .map(|port| port.to_string()));
+    verilog.push_str(&ports.iter().map(|p| format!("        {}", p)).collect::<Vec<_>>().join(",\n"));
+    verilog.push_str("\n    );\n");
+    verilog
+}
+
+/// One sample of the FIR filter `y = Σ c_k * x[n - k]` as a graph, for scheduling and resource estimates
+///
+/// Sample `x` is a signed `FIR_GRAPH_DATA_WIDTH`-bit input and output `y`
+/// has the width `generate_fir_filter` gives it. The delay line is held in
+/// state registers `x_d1`.. `x_d<taps - 1>`, so each simulated or
+/// scheduled transaction is one sample. Coefficients are `Const` nodes of
+/// `FIR_GRAPH_COEFF_WIDTH` bits, and each tap is one `Fma`.
+pub fn build_fir_graph(coefficients: &[i32]) -> Graph {
+    let mut graph = Graph::new();
+    let data_width = Some(FIR_GRAPH_DATA_WIDTH);
+    let sum_width = Some(accumulator_width(FIR_GRAPH_DATA_WIDTH, FIR_GRAPH_COEFF_WIDTH, coefficients.len()));
+
+    let mut samples = vec![graph.add_node_with_output_type(Operation::Load("x".to_string()), data_width, true)];
+    for delay in 1..coefficients.len() {
+        let register = format!("x_d{}", delay);
+        graph.declare_state_register(&register, data_width, 0, false);
+        samples.push(graph.add_node_with_output_type(Operation::RegisterLoad(register), data_width, true));
+    }
+
+    let mut sum = graph.add_node_with_output_type(Operation::Const(0), sum_width, true);
+    for (&sample, &coefficient) in samples.iter().zip(coefficients) {
+        let coefficient = graph.add_node_with_output_type(Operation::Const(i64::from(coefficient)), Some(FIR_GRAPH_COEFF_WIDTH), true);
+        sum = graph.add_node_with_output_type(Operation::Fma(sample, coefficient, sum), sum_width, true);
+    }
+    graph.add_node(Operation::Store("y".to_string(), sum));
+
+    // Shift the delay line for the next sample
+    for delay in 1..coefficients.len() {
+        graph.add_node(Operation::RegisterStore(format!("x_d{}", delay), samples[delay - 1]));
+    }
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fir_taps_cascade_their_partial_sums() {
+        let verilog = generate_fir_filter("fir3", &[3, -2, 1], 16, 12);
+        assert_eq!(verilog.matches("    DSP48E2 #(\n").count(), 3);
+        assert_eq!(verilog.matches("(* SRL_STYLE = \"register\" *) reg signed [15:0] x_d").count(), 5);
+        assert!(verilog.contains("    wire signed [11:0] coeff1 = -12'sd2;\n"));
+        // Tap 2 multiplies the sample four registers back and adds tap 1's sum
+        assert!(verilog.contains("    wire [29:0] dsp2_a = x_d4;\n"));
+        assert!(verilog.contains("        .PCIN(dsp1_pcout),\n"));
+        assert!(verilog.contains("        .PCIN(48'd0),\n        .PCOUT(dsp0_pcout),\n        .OPMODE(9'b000000101),\n"));
+        assert_eq!(verilog.matches(".PCIN(").count(), 3);
+        assert!(verilog.contains("    output wire signed [29:0] y_out\n"));
+        assert!(verilog.contains("    assign y_out = dsp2_p[29:0];\n"));
+        assert!(verilog.contains("            y_valid <= x_valid && fill == 3;\n"));
+    }
+
+    #[test]
+    #[should_panic(expected = "coefficient 2048 does not fit in 12 bits")]
+    fn test_fir_rejects_coefficients_wider_than_declared() {
+        generate_fir_filter("fir", &[1, 2048], 16, 12);
+    }
+}
@@ -0,0 +1,226 @@
+//! Synchronous FIFO generator
+//!
+//! Modules running at different rates, such as an AXI4-Stream input and a
+//! kernel that takes several cycles per beat, are decoupled by a FIFO. Every
+//! style shares one interface: a write is taken on `wr_en` unless `full`, a
+//! read on `rd_en` unless `empty`, and `dout` holds the entry read on the
+//! previous clock edge. Only the storage differs, so the style can follow
+//! the depth without touching the logic around the FIFO.
+
+use std::collections::VecDeque;
+
+/// Storage a generated FIFO is built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FifoStyle {
+    /// Shift register mapped onto SRL16/SRL32 LUTs, for up to 16 or so entries
+    ShiftReg,
+    /// Circular buffer in block RAM, for deep FIFOs
+    Bram,
+    /// Circular buffer in LUT RAM
+    DistributedRam,
+}
+
+impl FifoStyle {
+    /// SRLs up to 16 entries, LUT RAM up to 64 and block RAM beyond
+    pub fn for_depth(depth: u32) -> Self {
+        match depth {
+            0..=16 => FifoStyle::ShiftReg,
+            17..=64 => FifoStyle::DistributedRam,
+            _ => FifoStyle::Bram,
+        }
+    }
+}
+
+/// Bits needed to count from 0 to `max`
+fn bits_for(max: u32) -> u32 {
+    (u32::BITS - max.leading_zeros()).max(1)
+}
+
+/// Generate a `depth`-entry, `data_width`-bit FIFO module named `name`
+///
+/// Ports are `ap_clk`, `ap_rst_n`, `wr_en`, `din`, `full`, `rd_en`, `dout`,
+/// `empty` and `data_count`, the number of entries held. A write while
+/// full is dropped, even if a read frees an entry in the same cycle.
+///
+/// # Panics
+///
+/// If `depth` is zero.
+pub fn generate_fifo(name: &str, data_width: u32, depth: u32, style: FifoStyle) -> String {
+    assert!(depth > 0, "FIFO {} needs at least one entry", name);
+    let count_width = bits_for(depth);
+    let mut verilog = String::new();
+
+    let storage = match style {
+        FifoStyle::ShiftReg => "shift register",
+        FifoStyle::Bram => "block RAM",
+        FifoStyle::DistributedRam => "distributed RAM",
+    };
+    verilog.push_str(&format!("// Synchronous FIFO, {} x {} bits in {}\n", depth, data_width, storage));
+    verilog.push_str(&format!("module {} (\n", name));
+    verilog.push_str("    input  wire                    ap_clk,\n");
+    verilog.push_str("    input  wire                    ap_rst_n,\n");
+    verilog.push_str("    \n");
+    verilog.push_str("    input  wire                    wr_en,\n");
+    verilog.push_str(&format!("    input  wire [{}:0]  din,\n", data_width - 1));
+    verilog.push_str("    output wire                    full,\n");
+    verilog.push_str("    \n");
+    verilog.push_str("    input  wire                    rd_en,\n");
+    verilog.push_str(&format!("    output reg  [{}:0]  dout,\n", data_width - 1));
+    verilog.push_str("    output wire                    empty,\n");
+    verilog.push_str(&format!("    output reg  [{}:0]  data_count\n", count_width - 1));
+    verilog.push_str(");\n\n");
+
+    verilog.push_str(&format!("    localparam DEPTH = {};\n\n", depth));
+    verilog.push_str("    wire do_write = wr_en & ~full;\n");
+    verilog.push_str("    wire do_read = rd_en & ~empty;\n");
+    verilog.push_str(&format!("    assign full = (data_count == {}'d{});\n", count_width, depth));
+    verilog.push_str(&format!("    assign empty = (data_count == {}'d0);\n\n", count_width));
+
+    match style {
+        FifoStyle::ShiftReg => {
+            // New entries shift in at 0, so the oldest sits data_count - 1 places in
+            verilog.push_str("    (* srl_style = \"srl\" *)\n");
+            verilog.push_str(&format!("    reg [{}:0] srl [0:DEPTH-1];\n", data_width - 1));
+            verilog.push_str("    integer i;\n\n");
+            verilog.push_str("    always @(posedge ap_clk) begin\n");
+            verilog.push_str("        if (do_write) begin\n");
+            verilog.push_str("            srl[0] <= din;\n");
+            verilog.push_str("            for (i = 1; i < DEPTH; i = i + 1)\n");
+            verilog.push_str("                srl[i] <= srl[i-1];\n");
+            verilog.push_str("        end\n");
+            verilog.push_str("        if (do_read)\n");
+            verilog.push_str(&format!("            dout <= srl[data_count - {}'d1];\n", count_width));
+            verilog.push_str("    end\n\n");
+        }
+        FifoStyle::Bram | FifoStyle::DistributedRam => {
+            let pointer_width = bits_for(depth - 1);
+            let ram_style = if style == FifoStyle::Bram { "block" } else { "distributed" };
+            verilog.push_str(&format!("    (* ram_style = \"{}\" *)\n", ram_style));
+            verilog.push_str(&format!("    reg [{}:0] mem [0:DEPTH-1];\n", data_width - 1));
+            verilog.push_str(&format!("    reg [{}:0] wr_ptr;\n", pointer_width - 1));
+            verilog.push_str(&format!("    reg [{}:0] rd_ptr;\n\n", pointer_width - 1));
+            verilog.push_str("    always @(posedge ap_clk) begin\n");
+            verilog.push_str("        if (do_write)\n");
+            verilog.push_str("            mem[wr_ptr] <= din;\n");
+            verilog.push_str("        if (do_read)\n");
+            verilog.push_str("            dout <= mem[rd_ptr];\n");
+            verilog.push_str("    end\n\n");
+            let last = format!("{}'d{}", pointer_width, depth - 1);
+            let zero = format!("{}'d0", pointer_width);
+            verilog.push_str("    always @(posedge ap_clk) begin\n");
+            verilog.push_str("        if (!ap_rst_n) begin\n");
+            verilog.push_str(&format!("            wr_ptr <= {};\n", zero));
+            verilog.push_str(&format!("            rd_ptr <= {};\n", zero));
+            verilog.push_str("        end else begin\n");
+            verilog.push_str("            if (do_write)\n");
+            verilog.push_str(&format!("                wr_ptr <= (wr_ptr == {}) ? {} : wr_ptr + {}'d1;\n", last, zero, pointer_width));
+            verilog.push_str("            if (do_read)\n");
+            verilog.push_str(&format!("                rd_ptr <= (rd_ptr == {}) ? {} : rd_ptr + {}'d1;\n", last, zero, pointer_width));
+            verilog.push_str("        end\n");
+            verilog.push_str("    end\n\n");
+        }
+    }
+
+    verilog.push_str("    always @(posedge ap_clk) begin\n");
+    verilog.push_str("        if (!ap_rst_n)\n");
+    verilog.push_str(&format!("            data_count <= {}'d0;\n", count_width));
+    verilog.push_str("        else if (do_write & ~do_read)\n");
+    verilog.push_str(&format!("            data_count <= data_count + {}'d1;\n", count_width));
+    verilog.push_str("        else if (do_read & ~do_write)\n");
+    verilog.push_str(&format!("            data_count <= data_count - {}'d1;\n", count_width));
+    verilog.push_str("    end\n\n");
+    verilog.push_str("endmodule\n");
+    verilog
+}
+
+/// Cycle-accurate software model of a `generate_fifo` module, whatever its style
+#[derive(Debug, Clone)]
+pub struct FifoModel {
+    depth: usize,
+    data_width: u32,
+    entries: VecDeque<u64>,
+    /// Entry read on the last clock edge, as on the `dout` port
+    pub dout: u64,
+}
+
+impl FifoModel {
+    /// An empty FIFO, as after reset
+    pub fn new(data_width: u32, depth: u32) -> Self {
+        Self { depth: depth as usize, data_width, entries: VecDeque::new(), dout: 0 }
+    }
+
+    pub fn full(&self) -> bool {
+        self.entries.len() == self.depth
+    }
+
+    pub fn empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn data_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Advance one clock edge with the given `wr_en`, `din` and `rd_en`
+    pub fn clock(&mut self, wr_en: bool, din: u64, rd_en: bool) {
+        let (write, read) = (wr_en && !self.full(), rd_en && !self.empty());
+        if read {
+            self.dout = self.entries.pop_front().expect("reads only happen when not empty");
+        }
+        if write {
+            let mask = if self.data_width >= 64 { u64::MAX } else { (1 << self.data_width) - 1 };
+            self.entries.push_back(din & mask);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth_four_fifo_reads_back_in_order() {
+        let mut fifo = FifoModel::new(8, 4);
+        for value in [0x11, 0x22, 0x33, 0x144] {
+            assert!(!fifo.full());
+            fifo.clock(true, value, false);
+        }
+        assert!(fifo.full());
+        assert_eq!(fifo.data_count(), 4);
+        // 0x144 was cut to the 8-bit data width
+        // Dropped, as the FIFO is full
+        fifo.clock(true, 0x55, false);
+
+        let mut read = Vec::new();
+        while !fifo.empty() {
+            fifo.clock(false, 0, true);
+            read.push(fifo.dout);
+        }
+        assert_eq!(read, vec![0x11, 0x22, 0x33, 0x44]);
+        // Reading an empty FIFO holds dout
+        fifo.clock(false, 0, true);
+        assert_eq!(fifo.dout, 0x44);
+    }
+
+    #[test]
+    fn test_styles_share_ports_and_pick_their_storage() {
+        for style in [FifoStyle::ShiftReg, FifoStyle::Bram, FifoStyle::DistributedRam] {
+            let verilog = generate_fifo("beats", 32, 4, style);
+            assert!(verilog.contains("module beats ("));
+            assert!(verilog.contains("input  wire [31:0]  din,"));
+            assert!(verilog.contains("output reg  [31:0]  dout,"));
+            assert!(verilog.contains("output reg  [2:0]  data_count\n"));
+            assert!(verilog.contains("assign full = (data_count == 3'd4);"));
+        }
+
+        assert!(generate_fifo("beats", 8, 16, FifoStyle::ShiftReg).contains("dout <= srl[data_count - 5'd1];"));
+        let bram = generate_fifo("beats", 8, 512, FifoStyle::Bram);
+        assert!(bram.contains("(* ram_style = \"block\" *)"));
+        assert!(bram.contains("wr_ptr <= (wr_ptr == 9'd511) ? 9'd0 : wr_ptr + 9'd1;"));
+        assert!(generate_fifo("beats", 8, 32, FifoStyle::DistributedRam).contains("(* ram_style = \"distributed\" *)"));
+        assert_eq!(
+            [4, 16, 17, 64, 65].map(FifoStyle::for_depth),
+            [FifoStyle::ShiftReg, FifoStyle::ShiftReg, FifoStyle::DistributedRam, FifoStyle::DistributedRam, FifoStyle::Bram]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! SystemVerilog Assertions for formal verification
+//!
+//! The properties go in a checker module whose ports are named after the
+//! signals it watches, bound into the generated module with `.*` so the
+//! design itself is left untouched. SymbiYosys or JasperGold read the file
+//! alongside the design.
+
+use crate::backend::sim::as_unsigned;
+use crate::backend::verilog::{collect_output_ports, get_value_reference, signal_type};
+use crate::error::HlsError;
+use crate::ir::graph::{Graph, Operation, ValueId};
+
+/// A property of a generated module to prove
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HlsProperty {
+    /// `value` always fits in `width` bits, two's complement if the value is signed
+    NoOverflow { value: ValueId, width: u32 },
+    /// Every `ap_start` is followed by `ap_done` within `max_cycles` cycles
+    EventuallyDone { max_cycles: u32 },
+    /// Output `port` holds `value` whenever `ap_done` is high
+    OutputEquality { port: String, value: i64 },
+}
+
+/// `<module>_props`, one labelled `assert property` per entry of
+/// `properties`, and the `bind` placing it in `module_name`
+///
+/// Properties are sampled on `ap_clk` and disabled during reset. A
+/// `NoOverflow` value is watched through the signal the Verilog backend
+/// names it by, so it must be one the emitted module declares, e.g. from
+/// an unpipelined graph. Fails with `HlsError::PropertyError` for values
+/// or ports the graph does not have, and for zero widths or cycle counts.
+pub fn generate_sva_properties(graph: &Graph, module_name: &str, properties: &[HlsProperty]) -> Result<String, HlsError> {
+    let outputs = collect_output_ports(graph);
+    let mut watched: Vec<(String, String)> = Vec::new();
+    let mut watch = |name: &str, decl: String| {
+        if !watched.iter().any(|(watched, _)| watched == name) {
+            watched.push((name.to_string(), decl));
+        }
+    };
+    let mut assertions = Vec::with_capacity(properties.len());
+
+    for (index, property) in properties.iter().enumerate() {
+        let (label, body) = match property {
+            HlsProperty::NoOverflow { value, width } => {
+                let producer = graph.producer(*value)
+                    .ok_or_else(|| HlsError::PropertyError(format!("property {}: value {} has no producer", index, value.0)))?;
+                if *width == 0 {
+                    return Err(HlsError::PropertyError(format!("property {}: a value cannot fit in 0 bits", index)));
+                }
+                let signal = get_value_reference(*value, graph);
+                let signed = graph.value_signed(*value);
+                if !matches!(producer.op, Operation::Const(_)) {
+                    watch(&signal, signal_type(graph.value_width(*value), signed));
+                }
+                let body = if signed {
+                    // Every bit from the new sign bit up must match it
+                    format!("($signed({0}) >>> {1}) == 0 || ($signed({0}) >>> {1}) == -1", signal, width - 1)
+                } else {
+                    format!("({} >> {}) == 0", signal, width)
+                };
+                ("no_overflow", body)
+            }
+            HlsProperty::EventuallyDone { max_cycles } => {
+                if *max_cycles == 0 {
+                    return Err(HlsError::PropertyError(format!("property {}: ap_done cannot follow ap_start within 0 cycles", index)));
+                }
+                ("eventually_done", format!("ap_start |-> ##[1:{}] ap_done", max_cycles))
+            }
+            HlsProperty::OutputEquality { port, value } => {
+                let output = outputs.iter().find(|output| &output.name == port)
+                    .ok_or_else(|| HlsError::PropertyError(format!("property {}: no output port {}", index, port)))?;
+                watch(&output.name, output.decl());
+                let width = output.width.unwrap_or(32);
+                ("output_equality", format!("ap_done |-> ({} == {}'h{:x})", port, width, as_unsigned(*value, Some(width))))
+            }
+        };
+        assertions.push(format!(
+            "    {}_{}: assert property (@(posedge ap_clk) disable iff (!ap_rst_n) {});\n",
+            label, index, body
+        ));
+    }
+
+    let checker = format!("{}_props", module_name);
+    let mut ports: Vec<String> = ["ap_clk", "ap_rst_n", "ap_start", "ap_done"].iter()
+        .map(|signal| format!("    input wire {}", signal))
+        .collect();
+    ports.extend(watched.iter().map(|(name, decl)| format!("    input wire {} {}", decl, name)));
+
+    let mut sva = String::new();
+    sva.push_str(&format!("// Formal properties for {}\n", module_name));
+    sva.push_str(&format!("module {} (\n", checker));
+    sva.push_str(&ports.join(",\n"));
+    sva.push_str("\n);\n\n");
+    sva.push_str(&assertions.concat());
+    sva.push_str("\nendmodule\n\n");
+    sva.push_str(&format!("bind {} {} {}_inst (.*);\n", module_name, checker, checker));
+    Ok(sva)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+
+    #[test]
+    fn test_adder_gets_one_assertion_per_property() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        let sum = graph.nodes.iter().find(|node| matches!(node.op, Operation::Add(_, _))).and_then(|node| node.output).unwrap();
+        let properties = [
+            HlsProperty::NoOverflow { value: sum, width: 9 },
+            HlsProperty::EventuallyDone { max_cycles: 4 },
+            HlsProperty::OutputEquality { port: "result".to_string(), value: 300 },
+        ];
+        let sva = generate_sva_properties(&graph, "adder", &properties).unwrap();
+
+        assert_eq!(sva.matches("assert property (@(posedge ap_clk)").count(), properties.len());
+        assert!(sva.contains("    input wire [8:0] node_2,\n"));
+        assert!(sva.contains("no_overflow_0: assert property (@(posedge ap_clk) disable iff (!ap_rst_n) (node_2 >> 9) == 0);"));
+        assert!(sva.contains("eventually_done_1: assert property (@(posedge ap_clk) disable iff (!ap_rst_n) ap_start |-> ##[1:4] ap_done);"));
+        assert!(sva.contains("output_equality_2: assert property (@(posedge ap_clk) disable iff (!ap_rst_n) ap_done |-> (result == 9'h12c));"));
+        assert!(sva.ends_with("bind adder adder_props adder_props_inst (.*);\n"));
+    }
+
+    #[test]
+    fn test_properties_must_name_what_the_graph_has() {
+        let graph = lower_expr_to_graph(&output("result", sub(sinput("a", 8), sinput("b", 8))));
+        let difference = graph.nodes.iter().find(|node| matches!(node.op, Operation::Sub(_, _))).and_then(|node| node.output).unwrap();
+        let sva = generate_sva_properties(&graph, "sub", &[HlsProperty::NoOverflow { value: difference, width: 8 }]).unwrap();
+        assert!(sva.contains("($signed(node_2) >>> 7) == 0 || ($signed(node_2) >>> 7) == -1"));
+
+        let missing = generate_sva_properties(&graph, "sub", &[HlsProperty::OutputEquality { port: "sum".to_string(), value: 0 }]);
+        assert!(matches!(missing, Err(HlsError::PropertyError(message)) if message == "property 0: no output port sum"));
+        let instant = generate_sva_properties(&graph, "sub", &[HlsProperty::EventuallyDone { max_cycles: 0 }]);
+        assert!(matches!(instant, Err(HlsError::PropertyError(_))));
+    }
+}
@@ -3,7 +3,55 @@
 //! This module provides code generation for various target formats.
 
 pub mod verilog;
+pub mod systemverilog;
+pub mod vhdl;
+pub mod axi;
+pub mod wishbone;
+pub mod fifo;
+pub mod cdc;
 pub mod sim;
+pub mod cycle_sim;
 pub mod verilator;
 pub mod testbench;
+pub mod batch;
+#[cfg(feature = "async")]
+pub mod async_testbench;
+pub mod verilog_tb;
+pub mod vivado;
+pub mod formal;
+pub mod perf;
+pub mod nn;
+pub mod dsp;
 pub mod pipeline_integration;
+
+use crate::error::HlsError;
+use crate::ir::graph::Graph;
+
+/// HDL flavour written out for simulation and synthesis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Verilog,
+    SystemVerilog,
+}
+
+impl OutputFormat {
+    /// Source file extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Verilog => "v",
+            OutputFormat::SystemVerilog => "sv",
+        }
+    }
+
+    /// Generate the module source in this format
+    pub fn generate(&self, graph: &Graph, module_name: &str) -> Result<String, HlsError> {
+        match self {
+            OutputFormat::Verilog => verilog::generate_verilog_module(graph, module_name, None),
+            OutputFormat::SystemVerilog => {
+                let options = verilog::VerilogEmitOptions { dialect: verilog::VerilogDialect::SystemVerilog, ..Default::default() };
+                verilog::generate_verilog_module(graph, module_name, Some(&options))
+            }
+        }
+    }
+}
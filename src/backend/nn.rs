@@ -0,0 +1,223 @@
+//! Systolic array templates for neural network inference
+//!
+//! Matrix multiplication maps onto a grid of multiply-accumulate processing
+//! elements. Each PE keeps one weight of B, loaded by shifting it down its
+//! column, and multiplies it with the A value passing through from the left.
+//! Partial sums flow down the columns, so the bottom row's sums are the
+//! results. Every hop is one register, which keeps the wiring short
+//! between neighbouring PEs and lets the grid close timing at any size.
+
+use crate::backend::verilog::DSP48E2_TIE_OFFS;
+use crate::ir::graph::{Graph, Operation};
+
+/// A `rows` x `cols` systolic array of DSP48E2 PEs, `pe_<row>_<col>`, and its `systolic_array` top
+///
+/// Values are signed `data_width`-bit integers, with `2 * data_width`-bit
+/// sums that wrap on overflow. While `b_load` holds, `b_col` shifts one
+/// row of weights into the top of the array: feed the last row of B
+/// first, and after `rows` cycles row r of B sits in PE row r.
+/// Lane r of `a_row` feeds row r and must lag row 0 by r cycles, the
+/// usual systolic skew. Column c of the product then appears on lane c of
+/// `c_out` `rows + c` cycles after its A entered row 0.
+///
+/// Panics unless `data_width` is 1 to 18 bits, the width of the DSP48E2's B port.
+pub fn generate_systolic_array(rows: usize, cols: usize, data_width: u32) -> String {
+    assert!((1..=18).contains(&data_width), "systolic array data width must be 1 to 18 bits, not {}", data_width);
+    let range = |width: u32| format!("[{}:0]", width - 1);
+    let lane = |index: usize, width: u32| format!("[{}:{}]", (index + 1) * width as usize - 1, index * width as usize);
+    let sum_width = 2 * data_width;
+
+    let mut verilog = String::new();
+    verilog.push_str(&format!("// {}x{} systolic array, {}-bit signed operands\n", rows, cols, data_width));
+    for row in 0..rows {
+        for col in 0..cols {
+            verilog.push_str(&pe_module(row, col, data_width));
+            verilog.push('\n');
+        }
+    }
+
+    verilog.push_str("module systolic_array (\n");
+    verilog.push_str("    input  wire ap_clk,\n");
+    verilog.push_str("    input  wire ap_rst_n,\n");
+    verilog.push_str("    input  wire b_load,\n");
+    verilog.push_str(&format!("    input  wire {} a_row,\n", range(data_width * rows as u32)));
+    verilog.push_str(&format!("    input  wire {} b_col,\n", range(data_width * cols as u32)));
+    verilog.push_str(&format!("    output wire {} c_out\n", range(sum_width * cols as u32)));
+    verilog.push_str(");\n\n");
+
+    // a_<r>_<c> enters PE (r, c) from the left, b_<r>_<c> and psum_<r>_<c> from above
+    verilog.push_str("    // Links between neighbouring PEs\n");
+    for row in 0..rows {
+        for col in 0..=cols {
+            verilog.push_str(&format!("    wire signed {} a_{}_{};\n", range(data_width), row, col));
+        }
+    }
+    for row in 0..=rows {
+        for col in 0..cols {
+            verilog.push_str(&format!("    wire signed {} b_{}_{};\n", range(data_width), row, col));
+            verilog.push_str(&format!("    wire signed {} psum_{}_{};\n", range(sum_width), row, col));
+        }
+    }
+    verilog.push('\n');
+
+    verilog.push_str("    // Array edges: A from the left, B and zero partial sums from the top, results from the bottom\n");
+    for row in 0..rows {
+        verilog.push_str(&format!("    assign a_{}_0 = a_row{};\n", row, lane(row, data_width)));
+    }
+    for col in 0..cols {
+        verilog.push_str(&format!("    assign b_0_{} = b_col{};\n", col, lane(col, data_width)));
+        verilog.push_str(&format!("    assign psum_0_{} = {}'d0;\n", col, sum_width));
+        verilog.push_str(&format!("    assign c_out{} = psum_{}_{};\n", lane(col, sum_width), rows, col));
+    }
+    verilog.push('\n');
+
+    for row in 0..rows {
+        for col in 0..cols {
+            let ports = [
+                ".ap_clk(ap_clk)".to_string(),
+                ".ap_rst_n(ap_rst_n)".to_string(),
+                ".b_load(b_load)".to_string(),
+                format!(".a_in(a_{}_{})", row, col),
+                format!(".b_in(b_{}_{})", row, col),
+                format!(".psum_in(psum_{}_{})", row, col),
+                format!(".a_out(a_{}_{})", row, col + 1),
+                format!(".b_out(b_{}_{})", row + 1, col),
+                format!(".psum_out(psum_{}_{})", row + 1, col),
+            ];
+            verilog.push_str(&format!("    pe_{0}_{1} pe_{0}_{1}_inst (\n", row, col));
+            verilog.push_str(&ports.iter().map(|port| format!("        {}", port)).collect::<Vec<_>>().join(",\n"));
+            verilog.push_str("\n    );\n");
+        }
+    }
+    verilog.push_str("\nendmodule\n");
+    verilog
+}
+
+/// PE (row, col): passes A right, holds the weight shifted down from above, and adds A * W to the sum from above
+fn pe_module(row: usize, col: usize, data_width: u32) -> String {
+    let sum_width = 2 * data_width;
+    let mut verilog = String::new();
+    verilog.push_str(&format!("module pe_{}_{} (\n", row, col));
+    verilog.push_str("    input  wire ap_clk,\n");
+    verilog.push_str("    input  wire ap_rst_n,\n");
+    verilog.push_str("    input  wire b_load,\n");
+    verilog.push_str(&format!("    input  wire signed [{}:0] a_in,\n", data_width - 1));
+    verilog.push_str(&format!("    input  wire signed [{}:0] b_in,\n", data_width - 1));
+    verilog.push_str(&format!("    input  wire signed [{}:0] psum_in,\n", sum_width - 1));
+    verilog.push_str(&format!("    output reg  signed [{}:0] a_out,\n", data_width - 1));
+    verilog.push_str(&format!("    output reg  signed [{}:0] b_out,  // Stationary weight\n", data_width - 1));
+    verilog.push_str(&format!("    output wire signed [{}:0] psum_out\n", sum_width - 1));
+    verilog.push_str(");\n\n");
+
+    verilog.push_str("    always @(posedge ap_clk) begin\n");
+    verilog.push_str("        if (!ap_rst_n) begin\n");
+    verilog.push_str("            a_out <= 0;\n");
+    verilog.push_str("            b_out <= 0;\n");
+    verilog.push_str("        end else begin\n");
+    verilog.push_str("            a_out <= a_in;\n");
+    verilog.push_str("            if (b_load) b_out <= b_in;\n");
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n\n");
+
+    // Assigning to the wider port wires sign-extends the signed operands
+    verilog.push_str("    wire [29:0] dsp_a = a_in;\n");
+    verilog.push_str("    wire [17:0] dsp_b = b_out;\n");
+    verilog.push_str("    wire [47:0] dsp_c = psum_in;\n");
+    verilog.push_str("    wire [47:0] dsp_p;\n");
+    verilog.push_str("    // P = A * B + C, registered once in PREG\n");
+    verilog.push_str("    DSP48E2 #(\n");
+    let parameters = [
+        ".AREG(0)", ".ACASCREG(0)", ".BREG(0)", ".BCASCREG(0)", ".MREG(0)", ".PREG(1)",
+        ".CREG(0)", ".DREG(0)", ".ADREG(0)", ".CARRYINREG(0)", ".CARRYINSELREG(0)",
+        ".INMODEREG(0)", ".OPMODEREG(0)", ".ALUMODEREG(0)",
+        ".AMULTSEL(\"A\")", ".BMULTSEL(\"B\")", ".PREADDINSEL(\"A\")", ".USE_MULT(\"MULTIPLY\")",
+        ".USE_SIMD(\"ONE48\")", ".USE_PATTERN_DETECT(\"NO_PATDET\")",
+    ];
+    verilog.push_str(&parameters.iter().map(|p| format!("        {}", p)).collect::<Vec<_>>().join(",\n"));
+    verilog.push_str("\n    ) dsp (\n");
+    let mut ports = vec![
+        ".A(dsp_a)".to_string(),
+        ".B(dsp_b)".to_string(),
+        ".C(dsp_c)".to_string(),
+        ".P(dsp_p)".to_string(),
+        // X = Y = M, Z = C, W = 0
+        ".OPMODE(9'b000110101)".to_string(),
+    ];
+    ports.extend(["CEA1", "CEA2", "CEB1", "CEB2", "CEM", "CEP"].iter().map(|port| format!(".{}(1'b1)", port)));
+    ports.extend(DSP48E2_TIE_OFFS.iter()
+        .filter(|port| !port.starts_with(".C(") && !port.starts_with(".OPMODE("))
+        .map(|port| port.to_string()));
+    verilog.push_str(&ports.iter().map(|p| format!("        {}", p)).collect::<Vec<_>>().join(",\n"));
+    verilog.push_str("\n    );\n");
+    verilog.push_str(&format!("    assign psum_out = dsp_p[{}:0];\n", sum_width - 1));
+    verilog.push_str("endmodule\n");
+    verilog
+}
+
+/// One pass of the systolic array as a graph: a `rows` x `cols` grid of `Fma` nodes
+///
+/// Inputs `a_<r>` are shared along row r and weights are `w_<r>_<c>`.
+/// Each column chains its `Fma`s from row 0 down, and output `c_<c>` is
+/// Σ a_r * w_r_c at `2 * data_width` signed bits, as `c_out` lane c of
+/// `generate_systolic_array` computes it.
+pub fn systolic_array_graph(rows: usize, cols: usize, data_width: u32) -> Graph {
+    let mut graph = Graph::new();
+    let sum_width = Some(2 * data_width);
+    let a: Vec<_> = (0..rows)
+        .map(|row| graph.add_node_with_output_type(Operation::Load(format!("a_{}", row)), Some(data_width), true))
+        .collect();
+    let zero = graph.add_node_with_output_type(Operation::Const(0), sum_width, true);
+    for col in 0..cols {
+        let mut sum = zero;
+        for (row, &a) in a.iter().enumerate() {
+            let weight = graph.add_node_with_output_type(Operation::Load(format!("w_{}_{}", row, col)), Some(data_width), true);
+            sum = graph.add_node_with_output_type(Operation::Fma(a, weight, sum), sum_width, true);
+        }
+        graph.add_node(Operation::Store(format!("c_{}", col), sum));
+    }
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+
+    #[test]
+    fn test_four_by_four_array_has_sixteen_pes() {
+        let verilog = generate_systolic_array(4, 4, 8);
+        let instances: Vec<&str> = verilog.lines().filter(|line| line.trim_start().starts_with("pe_") && line.ends_with("_inst (")).collect();
+        assert_eq!(instances.len(), 16);
+        assert_eq!(verilog.matches("    DSP48E2 #(\n").count(), 16);
+        assert!(verilog.contains("module pe_3_3 (\n"));
+        assert!(verilog.contains("    input  wire [31:0] a_row,\n    input  wire [31:0] b_col,\n    output wire [63:0] c_out\n"));
+        // Row 1 of column 2 takes A from column 1, and the sum and weight from row 0
+        assert!(verilog.contains("        .a_in(a_1_2),\n        .b_in(b_1_2),\n        .psum_in(psum_1_2),\n        .a_out(a_1_3),\n        .b_out(b_2_2),\n        .psum_out(psum_2_2)\n"));
+        assert!(verilog.contains("    assign c_out[31:16] = psum_4_1;\n"));
+        // The PE's own C and OPMODE replace the plain multiplier tie-offs
+        assert_eq!(verilog.matches(".C(48'd0)").count(), 0);
+        assert_eq!(verilog.matches(".OPMODE(").count(), 16);
+    }
+
+    #[test]
+    fn test_array_graph_computes_vector_matrix_product() {
+        let graph = systolic_array_graph(3, 2, 8);
+        assert_eq!(graph.nodes.iter().filter(|node| matches!(node.op, Operation::Fma(_, _, _))).count(), 6);
+        assert!(graph.type_check().is_ok());
+
+        let a = [3, -2, 7];
+        let w = [[1, -4], [5, 6], [-8, 2]];
+        let mut sim = Simulator::new();
+        for (row, weights) in w.iter().enumerate() {
+            sim.set_input(&format!("a_{}", row), a[row], &graph);
+            for (col, &weight) in weights.iter().enumerate() {
+                sim.set_input(&format!("w_{}_{}", row, col), weight, &graph);
+            }
+        }
+        let outputs = sim.simulate(&graph).unwrap();
+        for col in 0..2 {
+            let expected: i64 = a.iter().zip(&w).map(|(a, weights)| a * weights[col]).sum();
+            assert_eq!(outputs[&format!("c_{}", col)], expected);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Hardware performance counters
+//!
+//! Simulation shows what the schedule promises; counters in the fabric show
+//! what the kernel achieves on the board, with real host traffic in front
+//! of it. The counters sit in a submodule of the kernel and are read back
+//! through the AXI4-Lite register file, after the argument registers.
+
+/// Counter outputs of `generate_perf_counters`, in register-map order
+pub(crate) fn perf_counter_names(stages: usize) -> Vec<String> {
+    let mut names: Vec<String> = ["total_starts", "total_dones", "cycles"].iter().map(|name| name.to_string()).collect();
+    names.extend((0..stages).map(|stage| format!("stage{}_valid_cycles", stage)));
+    names
+}
+
+/// Generate `<module>_perf_counters`, 64-bit counters cleared by reset
+///
+/// `total_starts` counts the starts the core accepted (`ap_start` while
+/// `ap_ready`), `total_dones` the cycles `ap_done` was high, and `cycles`
+/// every cycle since reset. With `stages` above 0 the module also takes the
+/// kernel's `stage_valid` bits and counts the cycles each stage held valid
+/// data, so `stage<i>_valid_cycles / cycles` is that stage's occupancy.
+pub fn generate_perf_counters(module_name: &str, stages: usize) -> String {
+    let names = perf_counter_names(stages);
+    let mut verilog = String::new();
+
+    verilog.push_str(&format!("// Performance counters for {}\n", module_name));
+    verilog.push_str(&format!("module {}_perf_counters (\n", module_name));
+    let mut ports: Vec<String> = ["ap_clk", "ap_rst_n", "ap_start", "ap_ready", "ap_done"].iter()
+        .map(|signal| format!("    input  wire        {}", signal))
+        .collect();
+    if stages > 0 {
+        ports.push(format!("    input  wire [{}:0] stage_valid", stages - 1));
+    }
+    ports.extend(names.iter().map(|name| format!("    output reg  [63:0] {}", name)));
+    verilog.push_str(&ports.join(",\n"));
+    verilog.push_str("\n);\n\n");
+
+    verilog.push_str("    always @(posedge ap_clk) begin\n");
+    verilog.push_str("        if (!ap_rst_n) begin\n");
+    for name in &names {
+        verilog.push_str(&format!("            {} <= 64'd0;\n", name));
+    }
+    verilog.push_str("        end else begin\n");
+    verilog.push_str("            cycles <= cycles + 64'd1;\n");
+    verilog.push_str("            if (ap_start & ap_ready) total_starts <= total_starts + 64'd1;\n");
+    verilog.push_str("            if (ap_done) total_dones <= total_dones + 64'd1;\n");
+    for stage in 0..stages {
+        verilog.push_str(&format!("            if (stage_valid[{0}]) stage{0}_valid_cycles <= stage{0}_valid_cycles + 64'd1;\n", stage));
+    }
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n");
+    verilog.push_str("\nendmodule\n");
+    verilog
+}
+
+/// `perf_<counter>` output declarations for the kernel's port list, each followed by a comma
+pub(crate) fn perf_counter_ports(stages: usize) -> String {
+    let mut verilog = String::from("    \n    // Performance counters, read through the AXI4-Lite register file\n");
+    for name in perf_counter_names(stages) {
+        verilog.push_str(&format!("    output wire [63:0]             perf_{},\n", name));
+    }
+    verilog
+}
+
+/// The kernel's `perf_counters` instance, driving its `perf_<counter>` outputs
+pub(crate) fn perf_counter_instance(module_name: &str, stages: usize) -> String {
+    let mut connections: Vec<String> = ["ap_clk", "ap_rst_n", "ap_start", "ap_ready", "ap_done"].iter()
+        .map(|signal| format!("        .{0}({0})", signal))
+        .collect();
+    if stages > 0 {
+        connections.push("        .stage_valid(stage_valid)".to_string());
+    }
+    connections.extend(perf_counter_names(stages).iter().map(|name| format!("        .{0}(perf_{0})", name)));
+
+    let mut verilog = String::from("\n    // Hardware performance counters\n");
+    verilog.push_str(&format!("    {}_perf_counters perf_counters (\n", module_name));
+    verilog.push_str(&connections.join(",\n"));
+    verilog.push_str("\n    );\n");
+    verilog
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::axi::generate_axi_lite_perf_register_map;
+    use crate::backend::verilog::{generate_verilog_module, VerilogEmitOptions};
+    use crate::dsl::ast::*;
+    use crate::ir::graph::Graph;
+    use crate::ir::lower::lower_expr_to_graph;
+    use crate::passes::pipeline::run_pipeline_pass;
+    use std::process::Command;
+
+    fn pipelined_adder() -> Graph {
+        let mut graph = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        graph.enable_pipeline(1, 4, 1);
+        run_pipeline_pass(&mut graph).unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_counters_follow_the_kernel_handshake_and_stages() {
+        let counters = generate_perf_counters("adder", 3);
+        assert!(counters.contains("    input  wire [2:0] stage_valid,\n"));
+        assert!(counters.contains("if (ap_start & ap_ready) total_starts <= total_starts + 64'd1;"));
+        assert!(counters.contains("if (stage_valid[2]) stage2_valid_cycles <= stage2_valid_cycles + 64'd1;"));
+        assert!(!generate_perf_counters("fsm", 0).contains("stage_valid"));
+
+        let options = VerilogEmitOptions { enable_perf_counters: true, ..Default::default() };
+        let fsm = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        let verilog = generate_verilog_module(&fsm, "fsm_adder", Some(&options)).unwrap();
+        assert!(verilog.contains("output wire [63:0]             perf_total_starts,"));
+        assert!(verilog.contains("    fsm_adder_perf_counters perf_counters (\n"));
+        assert!(verilog.ends_with(&generate_perf_counters("fsm_adder", 0)));
+
+        // Counters are mapped after the arguments, the return value at 0x10 and a and b from 0x20
+        let map: serde_json::Value = serde_json::from_str(&generate_axi_lite_perf_register_map(&pipelined_adder(), "adder").unwrap()).unwrap();
+        let counters: Vec<(&str, u64)> = map["registers"].as_array().unwrap().iter()
+            .filter(|register| register["access"] == "counter")
+            .map(|register| (register["name"].as_str().unwrap(), register["offset"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(counters[..2], [("perf_total_starts", 0x30), ("perf_total_dones", 0x3c)]);
+        assert_eq!(counters.len(), 3 + pipelined_adder().pipeline_stages.len());
+    }
+
+    #[test]
+    fn test_total_starts_counts_every_simulated_start() {
+        let graph = pipelined_adder();
+        let options = VerilogEmitOptions { enable_perf_counters: true, ..Default::default() };
+        let dir = std::path::PathBuf::from("target").join("sim").join("perf_adder");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("perf_adder.v"), generate_verilog_module(&graph, "perf_adder", Some(&options)).unwrap()).unwrap();
+        // Five one-cycle starts with growing gaps; the count must step once after each
+        let main = r#"#include "Vperf_adder.h"
+#include "verilated.h"
+#include <cstdio>
+
+int main(int argc, char** argv) {
+    Verilated::commandArgs(argc, argv);
+    Vperf_adder* dut = new Vperf_adder;
+    auto tick = [dut]() {
+        dut->ap_clk = 0;
+        dut->eval();
+        dut->ap_clk = 1;
+        dut->eval();
+    };
+    dut->ap_rst_n = 0;
+    dut->ap_start = 0;
+    for (int i = 0; i < 4; i++) tick();
+    dut->ap_rst_n = 1;
+    int failures = 0;
+    for (int start = 1; start <= 5; start++) {
+        dut->ap_start = 1;
+        tick();
+        dut->ap_start = 0;
+        if (dut->perf_total_starts != (uint64_t) start) {
+            std::printf("after start %d total_starts = %llu\n", start, (unsigned long long) dut->perf_total_starts);
+            failures++;
+        }
+        for (int gap = 0; gap < start; gap++) tick();
+    }
+    for (int i = 0; i < 10; i++) tick();
+    if (dut->perf_total_dones != 5) failures++;
+    delete dut;
+    return failures == 0 ? 0 : 1;
+}
+"#;
+        std::fs::write(dir.join("main.cpp"), main).unwrap();
+
+        let build = Command::new("verilator")
+            .args(["--cc", "--exe", "--build", "-Wno-fatal", "--top-module", "perf_adder", "perf_adder.v", "main.cpp"])
+            .current_dir(&dir)
+            .output();
+        let Ok(build) = build else {
+            println!("Skipping performance counter simulation - Verilator not installed");
+            return;
+        };
+        assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+
+        let run = Command::new(dir.join("obj_dir").join("Vperf_adder")).output().unwrap();
+        assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stdout));
+    }
+}
@@ -3,27 +3,107 @@
 //! This module provides high-level functions to integrate pipeline scheduling
 //! with Verilog generation for a complete HLS flow.
 
+use crate::config::HlsConfig;
+use crate::error::HlsError;
 use crate::ir::graph::Graph;
 use crate::passes::pipeline::run_pipeline_pass;
+use crate::passes::dce::run_dce_pass;
+use crate::passes::const_fold::run_const_fold_pass;
+use crate::passes::strength_reduce::run_strength_reduce_pass;
+use crate::passes::manager::PassManager;
+use crate::passes::timing::{analyze_critical_path, schedule_of, CriticalPathReport};
+use crate::passes::retiming::{run_retiming_pass, RetimingReport};
 use crate::backend::verilog::generate_verilog_module;
+use crate::backend::axi::generate_axi_stream_wrapper;
 
 /// Complete HLS flow: Schedule pipeline and generate Verilog
-pub fn generate_pipelined_hls(mut graph: Graph, module_name: &str, ii: usize, depth: usize) -> Result<String, String> {
-    // Enable pipelining configuration
-    graph.enable_pipeline(ii, depth, 1);
-    
-    // Run pipeline scheduling pass
-    run_pipeline_pass(&mut graph)?;
+pub fn generate_pipelined_hls(graph: Graph, module_name: &str, ii: usize, depth: usize) -> Result<String, HlsError> {
+    let graph = optimize_and_schedule(graph, ii, depth)?;
     
     // Generate Verilog with pipeline support
-    let verilog = generate_verilog_module(&graph, module_name);
-    
-    Ok(verilog)
+    generate_verilog_module(&graph, module_name, None)
+}
+
+/// `generate_pipelined_hls`, also reporting the critical path of the scheduled graph
+pub fn generate_pipelined_hls_with_report(
+    graph: Graph,
+    module_name: &str,
+    ii: usize,
+    depth: usize,
+) -> Result<(String, CriticalPathReport), HlsError> {
+    let graph = optimize_and_schedule(graph, ii, depth)?;
+    let report = analyze_critical_path(&graph, &schedule_of(&graph));
+    println!("{}", report);
+    Ok((generate_verilog_module(&graph, module_name, None)?, report))
+}
+
+/// `generate_pipelined_hls`, retiming the scheduled graph to fit `target_period_ns`
+pub fn generate_pipelined_hls_with_retiming(
+    graph: Graph,
+    module_name: &str,
+    ii: usize,
+    depth: usize,
+    target_period_ns: f64,
+) -> Result<(String, RetimingReport), HlsError> {
+    let mut graph = optimize_and_schedule(graph, ii, depth)?;
+    let report = run_retiming_pass(&mut graph, target_period_ns);
+    println!("{}", report);
+    Ok((generate_verilog_module(&graph, module_name, None)?, report))
+}
+
+/// Complete HLS flow with AXI4-Stream ports: schedule, then wrap the module
+pub fn generate_pipelined_axi_stream(
+    graph: Graph,
+    module_name: &str,
+    ii: usize,
+    depth: usize,
+    data_width: u32,
+) -> Result<String, HlsError> {
+    let graph = optimize_and_schedule(graph, ii, depth)?;
+    generate_axi_stream_wrapper(&graph, module_name, data_width)
+}
+
+/// Complete HLS flow driven by `config`: its pipeline settings, device, clock, interface and dialect
+pub fn generate_pipelined_hls_from_config(graph: Graph, config: &HlsConfig) -> Result<String, HlsError> {
+    let graph = optimize_and_schedule_with_config(graph, config)?;
+    generate_verilog_module(&graph, &config.module_name, Some(&config.emit_options()))
+}
+
+/// The optimized graph `generate_pipelined_hls_from_config` generates from,
+/// e.g. for `TestbenchRunner::from_config`
+pub fn optimize_and_schedule_with_config(mut graph: Graph, config: &HlsConfig) -> Result<Graph, HlsError> {
+    config.apply(&mut graph);
+    let scheduler = config.scheduler()?;
+    pipelined_passes(move |graph: &mut Graph| scheduler.clone().schedule_pipeline(graph)).run(&mut graph)?;
+    Ok(graph)
+}
+
+/// Optimization passes and pipeline scheduling shared by the pipelined flows
+fn optimize_and_schedule(mut graph: Graph, ii: usize, depth: usize) -> Result<Graph, HlsError> {
+    graph.enable_pipeline(ii, depth, 1);
+    pipelined_passes(run_pipeline_pass).run(&mut graph)?;
+    Ok(graph)
+}
+
+/// The pipelined flow's passes in their default order, scheduling with `pipeline`
+fn pipelined_passes(pipeline: impl Fn(&mut Graph) -> Result<(), HlsError> + 'static) -> PassManager {
+    PassManager::new()
+        // Evaluate constant subexpressions before they take up pipeline slots
+        .add_pass("const_fold", run_const_fold_pass)
+        // Turn multiplies by powers of two into shifts to save DSP slices
+        .add_pass("strength_reduce", run_strength_reduce_pass)
+        .add_pass("pipeline", pipeline)
+        // Drop logic that never reaches an output
+        .add_pass("dce", run_dce_pass)
+        .depends_on("strength_reduce", "const_fold")
+        .depends_on("pipeline", "strength_reduce")
+        .depends_on("dce", "pipeline")
 }
 
 /// Generate simple (non-pipelined) HLS
-pub fn generate_simple_hls(graph: Graph, module_name: &str) -> String {
-    generate_verilog_module(&graph, module_name)
+pub fn generate_simple_hls(mut graph: Graph, module_name: &str) -> Result<String, HlsError> {
+    run_dce_pass(&mut graph);
+    generate_verilog_module(&graph, module_name, None)
 }
 
 /// Pipeline configuration presets for common use cases
@@ -49,4 +129,12 @@ impl PipelinePresets {
     pub fn dsp_intensive() -> (usize, usize) {
         (1, 6)  // II=1, depth=6 (allows for DSP48 latency)
     }
+    
+    /// HFT ultra low latency: a decision every cycle through two stages, as (II, depth, target MHz)
+    ///
+    /// Scheduling fails with `HlsError::IINotAchievable` when a state
+    /// register recurrence rules out II=1.
+    pub fn hft_ultra_low_latency() -> (usize, usize, u64) {
+        (1, 2, 500)  // II=1, depth=2, 500 MHz
+    }
 }
@@ -2,38 +2,129 @@
 //! 
 //! This module provides basic simulation capabilities for generated RTL.
 
-use crate::ir::graph::{Graph, Operation};
+use crate::error::HlsError;
+use crate::ir::graph::{saturation_range, Graph, Node, NodeId, Operation, ValueId};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use thiserror::Error;
+
+/// A graph the software model cannot evaluate for the given inputs
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SimError {
+    #[error("node {} divides by zero", .node.0)]
+    DivisionByZero { node: NodeId },
+    /// An operand that is neither set with `set_input` nor computed by an earlier node
+    #[error("node {} reads value {}, which is never set or computed before it", .node.0, .value.0)]
+    UnsetValue { node: NodeId, value: ValueId },
+    #[error("node {} calls '{}', which is not a declared callee", .node.0, .name)]
+    UndeclaredCallee { node: NodeId, name: String },
+}
+
+impl From<SimError> for HlsError {
+    fn from(error: SimError) -> Self {
+        HlsError::SimulationError(error.to_string())
+    }
+}
 
 /// Simple simulation engine for IR graphs
 pub struct Simulator {
     values: HashMap<usize, i64>, // ValueId -> actual value
+    arrays: HashMap<String, Vec<i64>>, // Array name -> contents
+    registers: HashMap<String, i64>, // State register name -> value, once updated
+    instances: HashMap<NodeId, Simulator>, // Call node -> its callee's simulator, keeping the callee's state
+    call_outputs: HashMap<usize, HashMap<String, i64>>, // Call handle -> callee outputs
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Simulator {
     pub fn new() -> Self {
         Self {
             values: HashMap::new(),
+            arrays: HashMap::new(),
+            registers: HashMap::new(),
+            instances: HashMap::new(),
+            call_outputs: HashMap::new(),
         }
     }
     
-    /// Set an input value
+    /// Set an input value, truncated (or sign-extended) to the port's declared width
     pub fn set_input(&mut self, name: &str, value: i64, graph: &Graph) {
         // Find the input node and set its output value
         for node in &graph.nodes {
             if let Operation::Load(input_name) = &node.op {
                 if input_name == name {
                     if let Some(output_id) = node.output {
-                        self.values.insert(output_id.0, value);
+                        self.values.insert(output_id.0, fit_to_width(value, node.output_width, node.signed));
                     }
                 }
             }
         }
     }
     
-    /// Run simulation on the graph
-    pub fn simulate(&mut self, graph: &Graph) -> HashMap<String, i64> {
+    /// Fill a declared array, truncating elements to its width and padding with zeros to its depth
+    pub fn set_array(&mut self, name: &str, values: &[i64], graph: &Graph) {
+        if let Some(array) = graph.array(name) {
+            let mut contents: Vec<i64> = values.iter().map(|value| fit_to_width(*value, array.width, false)).collect();
+            contents.resize(array.depth, 0);
+            self.arrays.insert(name.to_string(), contents);
+        }
+    }
+
+    /// Contents of an array after simulation, if it has been filled or written
+    pub fn array(&self, name: &str) -> Option<&[i64]> {
+        self.arrays.get(name).map(Vec::as_slice)
+    }
+
+    /// Value computed for `value` by the last `simulate`
+    pub(crate) fn value(&self, value: ValueId) -> Option<i64> {
+        self.values.get(&value.0).copied()
+    }
+
+    /// Value of a node's operand, which must already be set or computed
+    fn read(&self, node: &Node, value: ValueId) -> Result<i64, SimError> {
+        self.values.get(&value.0).copied().ok_or(SimError::UnsetValue { node: node.id, value })
+    }
+
+    /// Return every state register to its initial value, as a reset or clear does
+    pub fn clear_state(&mut self) {
+        self.registers.clear();
+        self.instances.values_mut().for_each(Simulator::clear_state);
+    }
+
+    /// Run simulation on the graph, returning the value of every output port
+    ///
+    /// Pipeline registers pass their input straight through; scheduling does
+    /// not change what a graph computes. A graph with a rolled loop is
+    /// evaluated once per iteration, and the outputs are those of the last.
+    /// Each call is one transaction: state registers keep the value it
+    /// stores in them for the next call. An input left unset is an error, not zero.
+    pub fn simulate(&mut self, graph: &Graph) -> Result<HashMap<String, i64>, SimError> {
+        let mut carried = HashMap::new();
+        let mut updates = HashMap::new();
+        let mut outputs = HashMap::new();
+        for iteration in 0..graph.loop_trip_count.unwrap_or(1) {
+            outputs = self.evaluate(graph, iteration, &mut carried, &mut updates)?;
+        }
+        self.registers.extend(updates);
+        Ok(outputs)
+    }
+
+    /// One pass over the graph; `carried` holds the loop-carried registers
+    /// between iterations, and `updates` collects state register stores
+    fn evaluate(
+        &mut self,
+        graph: &Graph,
+        iteration: usize,
+        carried: &mut HashMap<String, i64>,
+        updates: &mut HashMap<String, i64>,
+    ) -> Result<HashMap<String, i64>, SimError> {
         let mut outputs = HashMap::new();
+        let mut next = HashMap::new();
         
         // Process nodes in order (assuming they're already in dependency order)
         for node in &graph.nodes {
@@ -43,37 +134,733 @@ impl Simulator {
                         self.values.insert(output_id.0, *val);
                     }
                 }
-                Operation::Add(left, right) => {
+                Operation::Add(left, right) | Operation::Sub(left, right) | Operation::Mul(left, right) => {
                     if let Some(output_id) = node.output {
-                        let left_val = self.values.get(&left.0).unwrap_or(&0);
-                        let right_val = self.values.get(&right.0).unwrap_or(&0);
-                        self.values.insert(output_id.0, left_val + right_val);
+                        let signed = graph.value_signed(*left) && graph.value_signed(*right);
+                        let left_val = expression_operand(self.read(node, *left)?, *left, signed, graph);
+                        let right_val = expression_operand(self.read(node, *right)?, *right, signed, graph);
+                        let value = match node.op {
+                            Operation::Add(_, _) => left_val.wrapping_add(right_val),
+                            Operation::Sub(_, _) => left_val.wrapping_sub(right_val),
+                            _ => left_val.wrapping_mul(right_val),
+                        };
+                        self.values.insert(output_id.0, value);
                     }
                 }
-                Operation::Sub(left, right) => {
+                Operation::AddSat(left, right, _) | Operation::SubSat(left, right, _) => {
                     if let Some(output_id) = node.output {
-                        let left_val = self.values.get(&left.0).unwrap_or(&0);
-                        let right_val = self.values.get(&right.0).unwrap_or(&0);
-                        self.values.insert(output_id.0, left_val - right_val);
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        self.values.insert(output_id.0, saturate(&node.op, left_val, right_val, graph));
                     }
                 }
-                Operation::Mul(left, right) => {
+                Operation::SAdd(left, right) | Operation::SSub(left, right) | Operation::SMul(left, right) => {
                     if let Some(output_id) = node.output {
-                        let left_val = self.values.get(&left.0).unwrap_or(&0);
-                        let right_val = self.values.get(&right.0).unwrap_or(&0);
-                        self.values.insert(output_id.0, left_val * right_val);
+                        let left_val = as_signed(self.read(node, *left)?, *left, graph);
+                        let right_val = as_signed(self.read(node, *right)?, *right, graph);
+                        let value = match node.op {
+                            Operation::SAdd(_, _) => left_val.wrapping_add(right_val),
+                            Operation::SSub(_, _) => left_val.wrapping_sub(right_val),
+                            _ => left_val.wrapping_mul(right_val),
+                        };
+                        self.values.insert(output_id.0, value);
+                    }
+                }
+                Operation::Div(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        if right_val == 0 {
+                            return Err(SimError::DivisionByZero { node: node.id });
+                        }
+                        // i64::MIN / -1 overflows and is defined as 0
+                        self.values.insert(output_id.0, left_val.checked_div(right_val).unwrap_or(0));
+                    }
+                }
+                Operation::And(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        self.values.insert(output_id.0, left_val & right_val);
+                    }
+                }
+                Operation::Or(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        self.values.insert(output_id.0, left_val | right_val);
+                    }
+                }
+                Operation::Xor(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        self.values.insert(output_id.0, left_val ^ right_val);
+                    }
+                }
+                Operation::Nand(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        self.values.insert(output_id.0, !(left_val & right_val));
+                    }
+                }
+                Operation::Nor(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        self.values.insert(output_id.0, !(left_val | right_val));
+                    }
+                }
+                Operation::Xnor(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        self.values.insert(output_id.0, !(left_val ^ right_val));
+                    }
+                }
+                Operation::Concat(high, low) => {
+                    if let Some(output_id) = node.output {
+                        let high_val = self.read(node, *high)?;
+                        let low_val = self.read(node, *low)?;
+                        let value = concat_bits(high_val, low_val, graph.value_width(*low).unwrap_or(32));
+                        self.values.insert(output_id.0, value);
+                    }
+                }
+                Operation::Slice(value, high, low) => {
+                    if let Some(output_id) = node.output {
+                        let val = self.read(node, *value)?;
+                        self.values.insert(output_id.0, slice_bits(val, *high, *low));
+                    }
+                }
+                Operation::Not(value) => {
+                    if let Some(output_id) = node.output {
+                        let val = self.read(node, *value)?;
+                        self.values.insert(output_id.0, !val);
+                    }
+                }
+                Operation::Shl(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        self.values.insert(output_id.0, shift_left(left_val, right_val));
+                    }
+                }
+                Operation::Shr(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        // Zeros fill in above the operand's own width, signed or not
+                        let left_val = as_unsigned(left_val, graph.value_width(*left)) as i64;
+                        self.values.insert(output_id.0, shift_right_logical(left_val, right_val));
+                    }
+                }
+                Operation::CmpLt(left, right) | Operation::CmpGt(left, right) | Operation::CmpEq(left, right) |
+                Operation::CmpGe(left, right) | Operation::CmpLe(left, right) | Operation::CmpNe(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        let ordering = compare_values(left_val, right_val, *left, *right, graph);
+                        let holds = match node.op {
+                            Operation::CmpLt(_, _) => ordering.is_lt(),
+                            Operation::CmpGt(_, _) => ordering.is_gt(),
+                            Operation::CmpEq(_, _) => ordering.is_eq(),
+                            Operation::CmpGe(_, _) => ordering.is_ge(),
+                            Operation::CmpLe(_, _) => ordering.is_le(),
+                            _ => ordering.is_ne(),
+                        };
+                        self.values.insert(output_id.0, holds as i64);
+                    }
+                }
+                Operation::Min(left, right) | Operation::Max(left, right) => {
+                    if let Some(output_id) = node.output {
+                        let left_val = self.read(node, *left)?;
+                        let right_val = self.read(node, *right)?;
+                        let ordering = compare_values(left_val, right_val, *left, *right, graph);
+                        let pick_left = match node.op {
+                            Operation::Min(_, _) => ordering.is_lt(),
+                            _ => ordering.is_gt(),
+                        };
+                        self.values.insert(output_id.0, if pick_left { left_val } else { right_val });
+                    }
+                }
+                Operation::ReduceAdd(values) => {
+                    if let Some(output_id) = node.output {
+                        let sum = values.iter().try_fold(0i64, |sum, value| Ok::<_, SimError>(sum.wrapping_add(self.read(node, *value)?)))?;
+                        self.values.insert(output_id.0, sum);
+                    }
+                }
+                Operation::ReduceMax(values) | Operation::ReduceMin(values) => {
+                    if let Some(output_id) = node.output {
+                        // Pairwise like the hardware comparators, so mixed signedness compares the same way
+                        let read = values.iter().map(|value| Ok((*value, self.read(node, *value)?))).collect::<Result<Vec<_>, SimError>>()?;
+                        let pick = |best: (ValueId, i64), candidate: (ValueId, i64)| {
+                            let ordering = compare_values(candidate.1, best.1, candidate.0, best.0, graph);
+                            let better = match node.op {
+                                Operation::ReduceMin(_) => ordering.is_lt(),
+                                _ => ordering.is_gt(),
+                            };
+                            if better { candidate } else { best }
+                        };
+                        let value = read.into_iter().reduce(pick).map_or(0, |(_, value)| value);
+                        self.values.insert(output_id.0, value);
+                    }
+                }
+                Operation::Abs(value) => {
+                    if let Some(output_id) = node.output {
+                        // Negated whenever the top bit is set, as in the generated `(a[msb]) ? (~a + 1) : a`
+                        let val = as_signed(self.read(node, *value)?, *value, graph);
+                        self.values.insert(output_id.0, val.wrapping_abs());
+                    }
+                }
+                Operation::Sqrt(value) => {
+                    if let Some(output_id) = node.output {
+                        // The radicand is an unsigned bit pattern, as in the generated `isqrt` function
+                        let val = as_unsigned(self.read(node, *value)?, graph.value_width(*value));
+                        self.values.insert(output_id.0, val.isqrt() as i64);
+                    }
+                }
+                Operation::PipelineRegister(value) => {
+                    if let Some(output_id) = node.output {
+                        let val = self.read(node, *value)?;
+                        self.values.insert(output_id.0, val);
+                    }
+                }
+                Operation::Fma(a, b, c) => {
+                    if let Some(output_id) = node.output {
+                        let signed = [a, b, c].into_iter().all(|operand| graph.value_signed(*operand));
+                        let a_val = expression_operand(self.read(node, *a)?, *a, signed, graph);
+                        let b_val = expression_operand(self.read(node, *b)?, *b, signed, graph);
+                        let c_val = expression_operand(self.read(node, *c)?, *c, signed, graph);
+                        self.values.insert(output_id.0, a_val.wrapping_mul(b_val).wrapping_add(c_val));
+                    }
+                }
+                Operation::Mux(cond, true_val, false_val) => {
+                    if let Some(output_id) = node.output {
+                        let selected = if self.read(node, *cond)? != 0 { true_val } else { false_val };
+                        let value = self.read(node, *selected)?;
+                        self.values.insert(output_id.0, value);
                     }
                 }
                 Operation::Store(name, value_id) => {
-                    let value = self.values.get(&value_id.0).unwrap_or(&0);
-                    outputs.insert(name.clone(), *value);
+                    let value = self.read(node, *value_id)?;
+                    outputs.insert(name.clone(), value);
+                }
+                Operation::ArrayLoad(name, index) => {
+                    if let Some(output_id) = node.output {
+                        // Out-of-range reads return 0
+                        let index = self.read(node, *index)?;
+                        let value = usize::try_from(index).ok()
+                            .and_then(|index| self.arrays.get(name)?.get(index).copied())
+                            .unwrap_or(0);
+                        self.values.insert(output_id.0, value);
+                    }
+                }
+                Operation::ArrayStore(name, index, value) => {
+                    if let Some(array) = graph.array(name) {
+                        let index = self.read(node, *index)?;
+                        let value = fit_to_width(self.read(node, *value)?, array.width, false);
+                        let contents = self.arrays.entry(name.clone()).or_insert_with(|| vec![0; array.depth]);
+                        // Out-of-range writes are dropped
+                        if let Some(slot) = usize::try_from(index).ok().and_then(|index| contents.get_mut(index)) {
+                            *slot = value;
+                        }
+                    }
+                }
+                Operation::LoopIndex => {
+                    if let Some(output_id) = node.output {
+                        self.values.insert(output_id.0, iteration as i64);
+                    }
+                }
+                Operation::LoopCarry(name, init) => {
+                    if let Some(output_id) = node.output {
+                        let value = match carried.get(name) {
+                            Some(value) if iteration > 0 => *value,
+                            _ => self.read(node, *init)?,
+                        };
+                        self.values.insert(output_id.0, value);
+                    }
+                }
+                Operation::LoopNext(name, value) => {
+                    next.insert(name.clone(), self.read(node, *value)?);
+                }
+                Operation::RegisterLoad(name) => {
+                    if let Some(output_id) = node.output {
+                        let init = graph.state_register(name).map_or(0, |register| register.init);
+                        self.values.insert(output_id.0, self.registers.get(name).copied().unwrap_or(init));
+                    }
                 }
-                _ => {
-                    // Handle other operations
+                Operation::RegisterStore(name, value) => {
+                    updates.insert(name.clone(), self.read(node, *value)?);
+                }
+                Operation::Call(name, inputs) => {
+                    let callee = graph.callee(name).ok_or_else(|| SimError::UndeclaredCallee { node: node.id, name: name.clone() })?;
+                    let values = inputs.iter().map(|value| self.read(node, *value)).collect::<Result<Vec<_>, SimError>>()?;
+                    // Every call node is an instance of its own, with its own state registers
+                    let instance = self.instances.entry(node.id).or_default();
+                    for (port, value) in callee.input_names().iter().zip(values) {
+                        instance.set_input(port, value, callee);
+                    }
+                    let results = instance.simulate(callee)?;
+                    if let Some(output_id) = node.output {
+                        self.call_outputs.insert(output_id.0, results);
+                        self.values.insert(output_id.0, 1);
+                    }
+                }
+                Operation::CallResult(call, port) => {
+                    if let Some(output_id) = node.output {
+                        let handle = graph.call_node(*call).and_then(|call| call.output).unwrap_or(*call);
+                        let value = self.call_outputs.get(&handle.0).and_then(|results| results.get(port)).copied()
+                            .ok_or(SimError::UnsetValue { node: node.id, value: *call })?;
+                        self.values.insert(output_id.0, value);
+                    }
+                }
+                // Inputs are set by `set_input`; barriers and no-ops compute nothing
+                Operation::Load(_) | Operation::PipelineBarrier | Operation::Nop => {}
+            }
+            
+            // Truncate to the declared width so software matches the hardware
+            if let Some(output_id) = node.output {
+                if let Some(value) = self.values.get_mut(&output_id.0) {
+                    *value = fit_to_width(*value, node.output_width, node.signed);
                 }
             }
         }
         
-        outputs
+        // Loop-carried registers update at the end of the iteration
+        carried.extend(next);
+        Ok(outputs)
+    }
+}
+
+/// Result of an `AddSat`/`SubSat` on two operand values
+///
+/// As in Verilog, the operands are two's complement only if both are signed,
+/// and otherwise unsigned bit patterns of their own width.
+pub(crate) fn saturate(op: &Operation, left_val: i64, right_val: i64, graph: &Graph) -> i64 {
+    let (Operation::AddSat(left, right, width) | Operation::SubSat(left, right, width)) = op else {
+        unreachable!("only saturating operations are clamped")
+    };
+    let signed = graph.value_signed(*left) && graph.value_signed(*right);
+    let operand = |value: i64, operand: ValueId| {
+        if signed { value as i128 } else { as_unsigned(value, graph.value_width(operand)) as i128 }
+    };
+    let (left_val, right_val) = (operand(left_val, *left), operand(right_val, *right));
+    let exact = if matches!(op, Operation::AddSat(_, _, _)) { left_val + right_val } else { left_val - right_val };
+    let (min, max) = saturation_range(*width, signed);
+    exact.clamp(min, max) as i64
+}
+
+/// Keep only the low `width` bits of a value (unknown widths are left untouched)
+fn mask_to_width(value: i64, width: Option<u32>) -> i64 {
+    match width {
+        Some(width) if width < 64 => value & ((1i64 << width) - 1),
+        _ => value,
+    }
+}
+
+/// Truncate to `width` bits, sign-extending from the top bit for signed values
+pub(crate) fn fit_to_width(value: i64, width: Option<u32>, signed: bool) -> i64 {
+    match width {
+        Some(width) if signed && width > 0 && width < 64 => {
+            let shift = 64 - width;
+            (value << shift) >> shift
+        }
+        _ => mask_to_width(value, width),
+    }
+}
+
+/// Reinterpret an operand as two's complement of its own width, whatever its declared type
+pub(crate) fn as_signed(value: i64, operand: ValueId, graph: &Graph) -> i64 {
+    fit_to_width(value, graph.value_width(operand), true)
+}
+
+/// An operand as Verilog reads it in an expression that is signed only if
+/// all its operands are: unchanged if `signed`, otherwise as an unsigned
+/// bit pattern of its own width
+pub(crate) fn expression_operand(value: i64, operand: ValueId, signed: bool, graph: &Graph) -> i64 {
+    if signed { value } else { as_unsigned(value, graph.value_width(operand)) as i64 }
+}
+
+/// Reinterpret a value as an unsigned bit pattern of the given width
+pub(crate) fn as_unsigned(value: i64, width: Option<u32>) -> u64 {
+    mask_to_width(value, width.or(Some(32))) as u64
+}
+
+/// Compare two operand values the way Verilog does: signed only if both
+/// operands are signed, otherwise as unsigned bit patterns of the wider width
+pub(crate) fn compare_values(left_val: i64, right_val: i64, left: ValueId, right: ValueId, graph: &Graph) -> Ordering {
+    if graph.value_signed(left) && graph.value_signed(right) {
+        left_val.cmp(&right_val)
+    } else {
+        let width = graph.value_width(left).max(graph.value_width(right));
+        as_unsigned(left_val, width).cmp(&as_unsigned(right_val, width))
+    }
+}
+
+/// Left shift; shifting by 64 or more bits (or a negative amount) yields 0
+pub(crate) fn shift_left(value: i64, amount: i64) -> i64 {
+    u32::try_from(amount)
+        .ok()
+        .and_then(|amount| value.checked_shl(amount))
+        .unwrap_or(0)
+}
+
+/// Logical (zero-filling) right shift, matching Verilog's `>>`
+pub(crate) fn shift_right_logical(value: i64, amount: i64) -> i64 {
+    u32::try_from(amount)
+        .ok()
+        .and_then(|amount| (value as u64).checked_shr(amount))
+        .map(|shifted| shifted as i64)
+        .unwrap_or(0)
+}
+
+/// `{high, low}`: `high` shifted above the `low_width` bits of `low`
+pub(crate) fn concat_bits(high: i64, low: i64, low_width: u32) -> i64 {
+    shift_left(high, low_width as i64) | mask_to_width(low, Some(low_width))
+}
+
+/// Bits `high` down to `low` of a value, zero-extended
+pub(crate) fn slice_bits(value: i64, high: u32, low: u32) -> i64 {
+    let width = high.checked_sub(low).map(|bits| bits + 1);
+    mask_to_width(shift_right_logical(value, low as i64), width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::graph::ArrayKind;
+    use crate::ir::lower::*;
+
+    fn run(expr: &Expr, inputs: &[(&str, i64)]) -> HashMap<String, i64> {
+        let graph = lower_expr_to_graph(expr);
+        let mut sim = Simulator::new();
+        for (name, value) in inputs {
+            sim.set_input(name, *value, &graph);
+        }
+        sim.simulate(&graph).unwrap()
+    }
+
+    #[test]
+    fn test_results_masked_to_declared_width() {
+        // 8-bit inputs: the sum gets a carry bit, the difference wraps at 8 bits
+        let sum = output("result", add(input("a", 8), input("b", 8)));
+        assert_eq!(run(&sum, &[("a", 200), ("b", 100)])["result"], 300);
+
+        let diff = output("result", sub(input("a", 8), input("b", 8)));
+        assert_eq!(run(&diff, &[("a", 1), ("b", 2)])["result"], 0xFF);
+
+        // Inputs are truncated to the port width
+        let pass = output("result", add(input("a", 4), const_val(0, 4)));
+        assert_eq!(run(&pass, &[("a", 0x1F)])["result"], 0xF);
+    }
+
+    #[test]
+    fn test_wide_results_wrap_instead_of_overflowing() {
+        // A 96-bit product keeps its low 64 bits
+        let x = 0xFFFF_FFFF_i64;
+        let expr = output("result", mul(mul(input("x", 32), input("y", 32)), input("y", 32)));
+        assert_eq!(run(&expr, &[("x", x), ("y", x)])["result"], x.wrapping_mul(x).wrapping_mul(x));
+
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 64), input("b", 64))));
+        let mut sim = Simulator::new();
+        sim.set_input("a", i64::MAX, &graph);
+        sim.set_input("b", 1, &graph);
+        assert_eq!(sim.simulate(&graph).unwrap()["result"], i64::MIN);
+    }
+
+    #[test]
+    fn test_signed_multiply() {
+        let expr = output("result", mul(signed_input("a", 16), signed_input("b", 16)));
+        assert_eq!(run(&expr, &[("a", -3), ("b", 5)])["result"], -15);
+
+        // Unsigned ports see the same bits as 65533
+        let expr = output("result", mul(input("a", 16), input("b", 16)));
+        assert_eq!(run(&expr, &[("a", -3), ("b", 5)])["result"], 65533 * 5);
+    }
+
+    #[test]
+    fn test_mixed_signedness_arithmetic_is_unsigned() {
+        // As in Verilog, the signed operand is zero-extended: 0xFFFF * 1
+        let expr = output("result", mul(signed_input("a", 16), input("b", 16)));
+        assert_eq!(run(&expr, &[("a", -1), ("b", 1)])["result"], 0x0000_FFFF);
+
+        let expr = output("result", add(signed_input("a", 8), input("b", 8)));
+        assert_eq!(run(&expr, &[("a", -1), ("b", 1)])["result"], 0x100);
+    }
+
+    #[test]
+    fn test_explicitly_signed_arithmetic() {
+        let expr = output("result", smul(sinput("a", 8), const_val(4, 8)));
+        assert_eq!(run(&expr, &[("a", -3)])["result"], -12);
+
+        // The signed operations reinterpret unsigned operands: 0xFD is -3
+        let expr = output("result", smul(input("a", 8), input("b", 8)));
+        assert_eq!(run(&expr, &[("a", 0xFD), ("b", 4)])["result"], -12);
+        assert_eq!(run(&output("r", sadd(input("a", 8), input("b", 8))), &[("a", 0xFF), ("b", 0xFF)])["r"], -2);
+        assert_eq!(run(&output("r", ssub(input("a", 8), input("b", 8))), &[("a", 1), ("b", 3)])["r"], -2);
+
+        let graph = lower_expr_to_graph(&expr);
+        let product = graph.nodes[2].type_annotation().unwrap();
+        assert_eq!((product.signed, product.width), (true, 16));
+    }
+
+    #[test]
+    fn test_signed_comparison() {
+        for (signed, expected) in [(true, 1), (false, 0)] {
+            let mut graph = Graph::new();
+            let a = graph.add_node_with_output_type(Operation::Load("a".to_string()), Some(8), signed);
+            let b = graph.add_node_with_output_type(Operation::Load("b".to_string()), Some(8), signed);
+            let lt = graph.add_node_with_output(Operation::CmpLt(a, b));
+            graph.add_node(Operation::Store("lt".to_string(), lt));
+
+            // -1 < 1 when signed; 0xFF < 1 is false when unsigned
+            let mut sim = Simulator::new();
+            sim.set_input("a", -1, &graph);
+            sim.set_input("b", 1, &graph);
+            assert_eq!(sim.simulate(&graph).unwrap()["lt"], expected);
+        }
+    }
+
+    #[test]
+    fn test_division() {
+        let expr = output("result", div(input("a", 32), input("b", 32)));
+        assert_eq!(run(&expr, &[("a", 100), ("b", 7)])["result"], 14);
+
+        let graph = lower_expr_to_graph(&expr);
+        let mut sim = Simulator::new();
+        sim.set_input("a", 100, &graph);
+        sim.set_input("b", 0, &graph);
+        assert_eq!(sim.simulate(&graph), Err(SimError::DivisionByZero { node: graph.nodes[2].id }));
+    }
+
+    #[test]
+    fn test_square_root_rounds_down() {
+        let expr = output("result", sqrt(input("a", 32)));
+        assert_eq!(run(&expr, &[("a", 144)])["result"], 12);
+        assert_eq!(run(&expr, &[("a", 143)])["result"], 11);
+        // 0xFFFF_FFFF is read as unsigned, and the root fits in 16 bits
+        assert_eq!(run(&expr, &[("a", -1)])["result"], 65535);
+        assert_eq!(lower_expr_to_graph(&expr).nodes[1].output_width, Some(16));
+    }
+
+    #[test]
+    fn test_unset_input_is_an_error() {
+        let graph = lower_expr_to_graph(&output("result", mul(input("a", 8), input("b", 8))));
+        let mut sim = Simulator::new();
+        sim.set_input("a", 3, &graph);
+        let error = sim.simulate(&graph).unwrap_err();
+        assert_eq!(error, SimError::UnsetValue { node: graph.nodes[2].id, value: graph.nodes[1].output.unwrap() });
+        assert_eq!(error.to_string(), "node 2 reads value 1, which is never set or computed before it");
+
+        sim.set_input("b", 5, &graph);
+        assert_eq!(sim.simulate(&graph).unwrap()["result"], 15);
+    }
+
+    #[test]
+    fn test_bitwise_operations() {
+        let a = || input("a", 8);
+        let b = || input("b", 8);
+        assert_eq!(run(&output("r", and(a(), b())), &[("a", 0xF0), ("b", 0x3C)])["r"], 0x30);
+        assert_eq!(run(&output("r", or(a(), b())), &[("a", 0xF0), ("b", 0x0F)])["r"], 0xFF);
+        assert_eq!(run(&output("r", xor(a(), b())), &[("a", 0xFF), ("b", 0x0F)])["r"], 0xF0);
+        assert_eq!(run(&output("r", not(a())), &[("a", 0x0F)])["r"], 0xF0);
+    }
+
+    #[test]
+    fn test_inverted_bitwise_operations() {
+        let a = || input("a", 8);
+        let b = || input("b", 8);
+        let inputs = [("a", 0xAA), ("b", 0x55)];
+        assert_eq!(run(&output("r", xor(a(), b())), &inputs)["r"], 0xFF);
+        assert_eq!(run(&output("r", xnor(a(), b())), &inputs)["r"], 0x00);
+        assert_eq!(run(&output("r", nand(a(), b())), &inputs)["r"], 0xFF);
+        assert_eq!(run(&output("r", nor(a(), b())), &inputs)["r"], 0x00);
+        assert_eq!(run(&output("r", nand(a(), b())), &[("a", 0xF0), ("b", 0x3C)])["r"], 0xCF);
+        assert_eq!(run(&output("r", nor(a(), b())), &[("a", 0xF0), ("b", 0x0C)])["r"], 0x03);
+    }
+
+    #[test]
+    fn test_concat_and_slice() {
+        assert_eq!(run(&output("r", slice(input("a", 8), 7, 4)), &[("a", 0xAB)])["r"], 0xA);
+        assert_eq!(run(&output("r", slice(input("a", 8), 3, 0)), &[("a", 0xAB)])["r"], 0xB);
+
+        let swapped = output("r", concat(slice(input("a", 8), 3, 0), slice(input("a", 8), 7, 4)));
+        assert_eq!(run(&swapped, &[("a", 0xAB)])["r"], 0xBA);
+        // The low half keeps its full width even when its top bits are zero
+        assert_eq!(run(&output("r", concat(input("h", 4), input("l", 8))), &[("h", 0x3), ("l", 0x05)])["r"], 0x305);
+
+        let graph = lower_expr_to_graph(&output("r", concat(input("h", 4), input("l", 8))));
+        assert_eq!(graph.nodes[2].output_width, Some(12));
+    }
+
+    #[test]
+    fn test_comparisons_min_max_and_abs() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output_type(Operation::Load("a".to_string()), Some(8), true);
+        let b = graph.add_node_with_output_type(Operation::Load("b".to_string()), Some(8), true);
+        let ops: [(&str, Operation); 9] = [
+            ("gt", Operation::CmpGt(a, b)),
+            ("ge", Operation::CmpGe(a, b)),
+            ("le", Operation::CmpLe(a, b)),
+            ("ne", Operation::CmpNe(a, b)),
+            ("lt", Operation::CmpLt(a, b)),
+            ("eq", Operation::CmpEq(a, b)),
+            ("min", Operation::Min(a, b)),
+            ("max", Operation::Max(a, b)),
+            ("abs", Operation::Abs(a)),
+        ];
+        for (name, op) in ops {
+            let value = graph.add_node_with_output(op);
+            graph.add_node(Operation::Store(name.to_string(), value));
+        }
+
+        let mut sim = Simulator::new();
+        sim.set_input("a", -5, &graph);
+        sim.set_input("b", 3, &graph);
+        let outputs = sim.simulate(&graph).unwrap();
+        let results: Vec<i64> = ["gt", "ge", "le", "ne", "lt", "eq", "min", "max", "abs"].iter().map(|name| outputs[*name]).collect();
+        assert_eq!(results, vec![0, 0, 1, 1, 1, 0, -5, 3, 5]);
+
+        sim.set_input("b", -5, &graph);
+        let outputs = sim.simulate(&graph).unwrap();
+        assert_eq!((outputs["ge"], outputs["le"], outputs["ne"], outputs["eq"]), (1, 1, 0, 1));
+    }
+
+    #[test]
+    fn test_pipeline_registers_pass_values_through() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output_width(Operation::Load("a".to_string()), 8);
+        let delayed = graph.add_node_with_output(Operation::PipelineRegister(a));
+        let twice = graph.add_node_with_output(Operation::PipelineRegister(delayed));
+        graph.add_node(Operation::PipelineBarrier);
+        graph.add_node(Operation::Store("result".to_string(), twice));
+
+        let mut sim = Simulator::new();
+        sim.set_input("a", 42, &graph);
+        assert_eq!(sim.simulate(&graph).unwrap()["result"], 42);
+    }
+
+    #[test]
+    fn test_state_registers_carry_over_between_transactions() {
+        let mut graph = Graph::new();
+        graph.declare_state_register("count", Some(4), 14, false);
+        let count = graph.add_node_with_output_width(Operation::RegisterLoad("count".to_string()), 4);
+        let one = graph.add_node_with_output_width(Operation::Const(1), 4);
+        let next = graph.add_node_with_output_width(Operation::Add(count, one), 4);
+        graph.add_node(Operation::RegisterStore("count".to_string(), next));
+        graph.add_node(Operation::Store("count_out".to_string(), count));
+
+        // 14, 15, then the 4-bit register wraps
+        let mut sim = Simulator::new();
+        let counts: Vec<i64> = (0..3).map(|_| sim.simulate(&graph).unwrap()["count_out"]).collect();
+        assert_eq!(counts, vec![14, 15, 0]);
+        sim.clear_state();
+        assert_eq!(sim.simulate(&graph).unwrap()["count_out"], 14);
+    }
+
+    #[test]
+    fn test_saturating_arithmetic_clamps_at_the_bounds() {
+        for width in [8, 16, 32] {
+            let max = (1i64 << width) - 1;
+            let sum = output("r", add_sat(input("a", width), input("b", width), width));
+            let difference = output("r", sub_sat(input("a", width), input("b", width), width));
+            assert_eq!(run(&sum, &[("a", max - 1), ("b", 0)])["r"], max - 1);
+            assert_eq!(run(&sum, &[("a", max - 1), ("b", 1)])["r"], max);
+            assert_eq!(run(&sum, &[("a", max), ("b", max)])["r"], max);
+            assert_eq!(run(&difference, &[("a", 1), ("b", 1)])["r"], 0);
+            assert_eq!(run(&difference, &[("a", 0), ("b", max)])["r"], 0);
+
+            let (min, max) = (-(1i64 << (width - 1)), (1i64 << (width - 1)) - 1);
+            let sum = output("r", add_sat(sinput("a", width), sinput("b", width), width));
+            let difference = output("r", sub_sat(sinput("a", width), sinput("b", width), width));
+            assert_eq!(run(&sum, &[("a", max - 1), ("b", 1)])["r"], max);
+            assert_eq!(run(&sum, &[("a", max), ("b", 1)])["r"], max);
+            assert_eq!(run(&sum, &[("a", min), ("b", -1)])["r"], min);
+            assert_eq!(run(&difference, &[("a", min + 1), ("b", 1)])["r"], min);
+            assert_eq!(run(&difference, &[("a", min), ("b", max)])["r"], min);
+            assert_eq!(run(&difference, &[("a", max), ("b", -1)])["r"], max);
+        }
+    }
+
+    #[test]
+    fn test_overflow_flag_stays_set_until_cleared() {
+        let mut graph = lower_expr_to_graph(&output("position", add_sat(input("a", 8), input("b", 8), 8)));
+        let saturated = graph.nodes.iter()
+            .find_map(|node| match node.op {
+                Operation::AddSat(_, _, _) => node.output,
+                _ => None,
+            })
+            .unwrap();
+        graph.add_overflow_flag(saturated, "overflow");
+
+        let mut sim = Simulator::new();
+        let step = |a: i64, b: i64, sim: &mut Simulator| {
+            sim.set_input("a", a, &graph);
+            sim.set_input("b", b, &graph);
+            let outputs = sim.simulate(&graph).unwrap();
+            (outputs["position"], outputs["overflow"])
+        };
+        assert_eq!(step(100, 155, &mut sim), (255, 0));
+        assert_eq!(step(100, 156, &mut sim), (255, 1));
+        assert_eq!(step(1, 2, &mut sim), (3, 1));
+        sim.clear_state();
+        assert_eq!(step(1, 2, &mut sim), (3, 0));
+    }
+
+    #[test]
+    fn test_shift_left() {
+        let expr = output("result", shl(input("a", 32), input("b", 32)));
+        let outputs = run(&expr, &[("a", 3), ("b", 4)]);
+        assert_eq!(outputs["result"], 48);
+    }
+
+    #[test]
+    fn test_shift_right_is_logical() {
+        let expr = output("result", shr(input("a", 32), const_val(4, 32)));
+        assert_eq!(run(&expr, &[("a", 48)])["result"], 3);
+
+        // -16 as a 32-bit port is 0xFFFF_FFF0; zeros shift in from the top
+        let outputs = run(&expr, &[("a", -16)]);
+        assert_eq!(outputs["result"], 0x0FFF_FFFF);
+    }
+
+    #[test]
+    fn test_select_on_comparisons() {
+        let max = output("result", select(gt(input("a", 8), input("b", 8)), input("a", 8), input("b", 8)));
+        assert_eq!(run(&max, &[("a", 9), ("b", 4)])["result"], 9);
+        assert_eq!(run(&max, &[("a", 2), ("b", 4)])["result"], 4);
+
+        let pick = output("result", select(eq(input("a", 8), const_val(3, 8)), const_val(10, 8), input("b", 8)));
+        assert_eq!(run(&pick, &[("a", 3), ("b", 1)])["result"], 10);
+        assert_eq!(run(&pick, &[("a", 5), ("b", 1)])["result"], 1);
+
+        // Any nonzero condition selects the first operand
+        let masked = output("result", select(and(input("a", 8), const_val(6, 8)), const_val(1, 8), const_val(2, 8)));
+        assert_eq!(run(&masked, &[("a", 4)])["result"], 1);
+    }
+
+    #[test]
+    fn test_array_load_and_store() {
+        let mut graph = Graph::new();
+        graph.declare_array("coeffs", 4, Some(8), ArrayKind::Input);
+        graph.declare_array("scaled", 4, Some(16), ArrayKind::Output);
+        let i = graph.add_node_with_output_width(Operation::Load("i".to_string()), 2);
+        let x = graph.add_node_with_output(Operation::ArrayLoad("coeffs".to_string(), i));
+        let two = graph.add_node_with_output_width(Operation::Const(2), 8);
+        let doubled = graph.add_node_with_output(Operation::Mul(x, two));
+        graph.add_node(Operation::ArrayStore("scaled".to_string(), i, doubled));
+        graph.add_node(Operation::Store("x".to_string(), x));
+
+        let mut sim = Simulator::new();
+        sim.set_array("coeffs", &[5, 300, 7], &graph);
+        sim.set_input("i", 1, &graph);
+        let outputs = sim.simulate(&graph).unwrap();
+
+        // 300 is truncated to the 8-bit element width
+        assert_eq!(outputs["x"], 44);
+        assert_eq!(sim.array("scaled"), Some(&[0, 88, 0, 0][..]));
+        assert_eq!(sim.array("coeffs"), Some(&[5, 44, 7, 0][..]));
     }
 }
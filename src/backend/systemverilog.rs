@@ -0,0 +1,225 @@
+//! SystemVerilog HDL code generation
+//!
+//! The Verilog backend writes SystemVerilog modules itself: under
+//! `VerilogDialect::SystemVerilog` its Verilog-2001 text is rewritten here,
+//! line by line, with `logic` signals, `always_ff` for clocked logic and
+//! `always_comb` for combinational processes. Signals without a tracked width
+//! use the packed `data_t`/`sdata_t` typedefs, and SVA assertions check the
+//! `ap_done` handshake in simulation. The module interface, datapath and
+//! latency stay those of the Verilog-2001 module.
+
+use crate::backend::verilog::{generate_verilog_module, Port, VerilogDialect, VerilogEmitOptions};
+use crate::error::HlsError;
+use crate::ir::graph::Graph;
+
+/// Generate a SystemVerilog module from IR graph
+///
+/// Shorthand for `generate_verilog_module` with the SystemVerilog dialect and
+/// otherwise default options.
+pub fn generate_systemverilog_module(graph: &Graph, module_name: &str) -> Result<String, HlsError> {
+    let options = VerilogEmitOptions { dialect: VerilogDialect::SystemVerilog, ..Default::default() };
+    generate_verilog_module(graph, module_name, Some(&options))
+}
+
+/// Rewrite a generated Verilog-2001 module, and the modules appended to it, as SystemVerilog
+///
+/// The typedefs and assertions go into the first module, whose data outputs
+/// are `outputs`; `fsm` adds the check that `ap_done` follows the `COMPUTE`
+/// state of the control state machine.
+pub(crate) fn rewrite_as_systemverilog(verilog: &str, outputs: &[Port], fsm: bool) -> String {
+    let mut sv = String::with_capacity(verilog.len() + 1024);
+    let mut in_header = true;
+    let mut in_first_module = true;
+    for line in verilog.lines() {
+        if in_first_module && line == "endmodule" {
+            sv.push_str(&handshake_assertions(outputs, fsm));
+            in_first_module = false;
+        }
+        for rewritten in rewrite_line(line, in_first_module && !in_header) {
+            sv.push_str(&rewritten);
+            sv.push('\n');
+        }
+        if in_header && line == ");" {
+            sv.push_str("    typedef logic [DATA_WIDTH-1:0] data_t;\n");
+            sv.push_str("    typedef logic signed [DATA_WIDTH-1:0] sdata_t;\n");
+            in_header = false;
+        }
+    }
+    sv
+}
+
+/// One line of Verilog-2001 as SystemVerilog lines
+///
+/// A net declaration with an assignment becomes a `logic` declaration and an
+/// `assign`, as a `logic` initializer would only set the starting value.
+fn rewrite_line(line: &str, typedefs: bool) -> Vec<String> {
+    let body = line.trim_start();
+    let indent = &line[..line.len() - body.len()];
+
+    for direction in ["input ", "output "] {
+        if let Some(rest) = body.strip_prefix(direction) {
+            let spaces = rest.len() - rest.trim_start().len();
+            if let Some(declaration) = logic_declaration(rest.trim_start()) {
+                return vec![format!("{}{}{}{}", indent, direction, " ".repeat(spaces), declaration)];
+            }
+        }
+    }
+    if let Some(rest) = body.strip_prefix("parameter integer ") {
+        return vec![format!("{}parameter int {}", indent, rest)];
+    }
+    if let Some(rest) = body.strip_prefix("always @(*)") {
+        return vec![format!("{}always_comb{}", indent, rest)];
+    }
+    if let Some(rest) = body.strip_prefix("always @(") {
+        return vec![format!("{}always_ff @({}", indent, rest)];
+    }
+
+    let attribute = if body.starts_with("(*") { body.find("*) ").map_or("", |end| &body[..end + 3]) } else { "" };
+    let Some(mut declaration) = logic_declaration(&body[attribute.len()..]) else {
+        return vec![line.to_string()];
+    };
+    if typedefs {
+        declaration = declaration
+            .replace("logic signed [DATA_WIDTH-1:0]", "sdata_t")
+            .replace("logic [DATA_WIDTH-1:0]", "data_t");
+    }
+    match declaration.split_once(" = ").filter(|(signal, _)| !signal.contains(';')) {
+        Some((signal, assignment)) => {
+            let name = signal.rsplit(' ').next().unwrap_or(signal);
+            vec![
+                format!("{}{}{};", indent, attribute, signal),
+                format!("{}assign {} = {}", indent, name, assignment),
+            ]
+        }
+        None => vec![format!("{}{}{}", indent, attribute, declaration)],
+    }
+}
+
+/// A `wire` or `reg` declaration as a `logic` one, keeping the column of what follows
+fn logic_declaration(declaration: &str) -> Option<String> {
+    let keyword = ["wire", "reg"].into_iter().find(|keyword| {
+        declaration.strip_prefix(keyword).is_some_and(|rest| rest.starts_with(' '))
+    })?;
+    let rest = &declaration[keyword.len()..];
+    let spaces = rest.len() - rest.trim_start().len();
+    let padding = (keyword.len() + spaces).saturating_sub("logic".len()).max(1);
+    Some(format!("logic{}{}", " ".repeat(padding), rest.trim_start()))
+}
+
+/// SVA checks on the `ap_done` handshake, skipped during synthesis
+fn handshake_assertions(outputs: &[Port], fsm: bool) -> String {
+    let mut sv = String::new();
+    sv.push_str("    // Handshake assertions\n");
+    sv.push_str("    // synthesis translate_off\n");
+    if !outputs.is_empty() {
+        let names: Vec<&str> = outputs.iter().map(|port| port.name.as_str()).collect();
+        sv.push_str("    a_outputs_known: assert property (@(posedge ap_clk) disable iff (!ap_rst_n)\n");
+        sv.push_str(&format!("        ap_done |-> !$isunknown({{{}}}))\n", names.join(", ")));
+        sv.push_str("        else $error(\"ap_done with unknown outputs\");\n");
+    }
+    if fsm {
+        sv.push_str("    a_done_after_compute: assert property (@(posedge ap_clk) disable iff (!ap_rst_n)\n");
+        sv.push_str("        ap_done |-> $past(state) == COMPUTE)\n");
+        sv.push_str("        else $error(\"ap_done without a computation\");\n");
+    }
+    sv.push_str("    // synthesis translate_on\n\n");
+    sv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::verilog::BackpressureMode;
+    use crate::dsl::ast::*;
+    use crate::ir::graph::Operation;
+    use crate::ir::lower::{lower_expr_to_graph, lowered_mac};
+    use crate::passes::pipeline::run_pipeline_pass;
+
+    fn systemverilog(graph: &Graph, options: VerilogEmitOptions) -> String {
+        let options = VerilogEmitOptions { dialect: VerilogDialect::SystemVerilog, ..options };
+        generate_verilog_module(graph, "kernel", Some(&options)).unwrap()
+    }
+
+    #[test]
+    fn test_simple_module_uses_systemverilog_constructs() {
+        let expr = output("result", select(lt(input("a", 8), input("b", 8)), input("a", 8), input("b", 8)));
+        let sv = generate_systemverilog_module(&lower_expr_to_graph(&expr), "min8").unwrap();
+
+        assert!(sv.contains("    parameter int DATA_WIDTH = 32,\n"));
+        assert!(sv.contains("    input  logic                   ap_clk,\n"));
+        assert!(sv.contains("    output logic                   ap_done,\n"));
+        assert!(sv.contains("    input  logic [7:0]  a,\n"));
+        assert!(sv.contains("    output logic [7:0]  result\n);\n    typedef logic [DATA_WIDTH-1:0] data_t;\n"));
+        assert!(sv.contains("(* DONT_TOUCH = \"yes\" *) logic [1:0] state;"));
+        assert!(sv.contains("    always_ff @(posedge ap_clk) begin\n"));
+        assert!(!sv.contains(" wire ") && !sv.contains(" reg ") && !sv.contains("always @"));
+        assert!(sv.contains("ap_done |-> !$isunknown({result}))"));
+        assert!(sv.contains("ap_done |-> $past(state) == COMPUTE)"));
+    }
+
+    #[test]
+    fn test_registers_and_latency_match_the_verilog_module() {
+        let mut graph = lowered_mac();
+        graph.enable_pipeline(1, 4, 1);
+        run_pipeline_pass(&mut graph).unwrap();
+        let verilog = generate_verilog_module(&graph, "kernel", None).unwrap();
+        let sv = systemverilog(&graph, VerilogEmitOptions::default());
+
+        // Every register transfer, and so every stage, is the Verilog one
+        let transfers = |hdl: &str| hdl.lines().filter(|line| line.contains("<=")).map(str::to_string).collect::<Vec<_>>();
+        assert!(!transfers(&verilog).is_empty());
+        assert_eq!(transfers(&sv), transfers(&verilog));
+        assert_eq!(sv.matches("always_ff @(posedge ap_clk)").count(), verilog.matches("always @(posedge ap_clk)").count());
+        assert!(!sv.contains("$past(state)"));
+    }
+
+    #[test]
+    fn test_net_assignments_become_continuous_assignments() {
+        let mut graph = crate::hft::ZeroPlusStrategy::to_fpga_graph();
+        graph.enable_pipeline(1, 3, 1);
+        run_pipeline_pass(&mut graph).unwrap();
+        let sv = systemverilog(&graph, VerilogEmitOptions { backpressure: BackpressureMode::ReadyValid, ..Default::default() });
+
+        assert!(sv.contains("    logic enable;\n    assign enable = m_axis_tready;\n"));
+        assert!(sv.contains("    output logic                   m_axis_tvalid,\n"));
+        assert!(sv.contains("    logic [31:0] best_bid_price_r0;\n"));
+    }
+
+    #[test]
+    fn test_verilog_options_apply_to_systemverilog() {
+        // Explicit DSP48E2 instances
+        let graph = lower_expr_to_graph(&output("result", mul(signed_input("a", 16), signed_input("b", 20))));
+        let sv = systemverilog(&graph, VerilogEmitOptions { explicit_dsp: true, ..Default::default() });
+        assert!(sv.contains("    logic [29:0] dsp2_a;\n    assign dsp2_a = $signed(b);\n"));
+        assert!(sv.contains("DSP48E2 #("));
+
+        // Block RAM arrays
+        let mut function = crate::dsl::hls::HLSFunction::new("lookup");
+        let lut = function.array_input("lut", 256);
+        let copy = function.array_output("copy", 256);
+        let index = function.input("index").value;
+        let entry = function.array_load(&lut, index).value;
+        function.array_store(&copy, index, entry);
+        let sv = systemverilog(&function.graph, VerilogEmitOptions::default());
+        // Unsized signals use the typedefs, except on the ports
+        assert!(sv.contains("    data_t node_1;\n"));
+        assert!(sv.contains("(* RAM_STYLE = \"block\" *) data_t lut [0:255];"));
+        assert!(sv.contains("    output logic [DATA_WIDTH-1:0]  copy_rdata\n"));
+
+        // State registers and performance counters, whose module is rewritten too
+        let mut graph = Graph::new();
+        graph.declare_state_register("acc", Some(32), 0, true);
+        let x = graph.add_node_with_output_width(Operation::Load("x".to_string()), 16);
+        let acc = graph.add_node_with_output_width(Operation::RegisterLoad("acc".to_string()), 32);
+        let sum = graph.add_node_with_output_width(Operation::Add(acc, x), 32);
+        graph.add_node(Operation::RegisterStore("acc".to_string(), sum));
+        graph.add_node(Operation::Store("sum".to_string(), sum));
+        let sv = systemverilog(&graph, VerilogEmitOptions { enable_perf_counters: true, ..Default::default() });
+        assert!(sv.contains("    logic [31:0] acc_state;"));
+        assert!(sv.contains("    input  logic                   acc_clear,"));
+        assert!(sv.contains("module kernel_perf_counters (") && !sv.contains(" wire ") && !sv.contains(" reg "));
+        // The assertions belong to the kernel
+        assert_eq!(sv.matches("// Handshake assertions").count(), 1);
+        assert!(sv.find("// Handshake assertions").unwrap() < sv.find("module kernel_perf_counters").unwrap());
+    }
+}
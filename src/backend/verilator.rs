@@ -3,23 +3,519 @@
 //! This module provides integration with Verilator to compile generated Verilog
 //! into C++ simulation models and run testbenches from Rust.
 
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
 use std::path::{Path, PathBuf};
 use std::fs;
-use crate::backend::verilog::generate_verilog_module;
+use std::collections::HashMap;
+use std::io::Read;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::backend::axi::{generate_axi_lite_register_map, generate_axi_lite_wrapper};
+use crate::backend::testbench::{LCG_INCREMENT, LCG_MULTIPLIER};
+use crate::backend::verilog::{
+    collect_input_ports, collect_output_ports, generate_verilog_module, BackpressureMode, Port, VerilogDialect, VerilogEmitOptions,
+};
+use crate::backend::verilog_tb::generate_verilog_testbench;
+use crate::backend::vivado::{generate_vivado_tcl, generate_vivado_tcl_for_source, generate_xdc, ClockConstraint, ALVEO_U50_PART};
+use crate::backend::OutputFormat;
+use crate::error::HlsError;
 use crate::ir::graph::Graph;
 
+/// C type of a port in the FFI contract: the narrowest of 8, 16, 32 and 64 bits that holds it
+pub(crate) fn ffi_width(width: u32) -> u32 {
+    width.next_power_of_two().clamp(8, 64)
+}
+
+/// Per-port accessor methods for the `{module}Sim` class and their C exports
+///
+/// Every input gets `set_<port>_sim(void*, uintN_t)` and every output
+/// `get_<port>_sim(void*) -> uintN_t`, N being one of 8, 16, 32 or 64.
+/// `set_input_by_name`, `get_output_by_name` and `port_width_by_name`
+/// dispatch on the port name for callers that only have a string; the
+/// first two return 0 and the last returns N, or -1 and 0 for a port the
+/// module does not have. `output_port_count` and `output_port_name` list
+/// the outputs. Inputs narrower than N are masked, as Verilator
+/// expects the unused bits clear. Ports wider than 64 bits become `VlWide`
+/// arrays and are left out.
+fn port_accessors(graph: &Graph, module_name: &str) -> (String, String) {
+    let fits = |port: &&Port| port.width.unwrap_or(32) <= 64;
+    let inputs: Vec<Port> = collect_input_ports(graph).into_iter().filter(|port| fits(&port)).collect();
+    let outputs: Vec<Port> = collect_output_ports(graph).into_iter().filter(|port| fits(&port)).collect();
+    let mut methods = String::new();
+    let mut exports = String::new();
+
+    for port in &inputs {
+        let width = port.width.unwrap_or(32);
+        let c_width = ffi_width(width);
+        let value = if width < c_width { format!("value & 0x{:x}ULL", (1u64 << width) - 1) } else { "value".to_string() };
+        methods.push_str(&format!("    void set_{0}(uint{1}_t value) {{\n        dut->{0} = {2};\n    }}\n    \n", port.name, c_width, value));
+        exports.push_str(&format!(
+            "    \n    void set_{0}_sim(void* sim, uint{1}_t value) {{\n        static_cast<{2}Sim*>(sim)->set_{0}(value);\n    }}\n",
+            port.name, c_width, module_name
+        ));
+    }
+    for port in &outputs {
+        let c_width = ffi_width(port.width.unwrap_or(32));
+        methods.push_str(&format!("    uint{1}_t get_{0}() {{\n        return dut->{0};\n    }}\n    \n", port.name, c_width));
+        exports.push_str(&format!(
+            "    \n    uint{1}_t get_{0}_sim(void* sim) {{\n        return static_cast<{2}Sim*>(sim)->get_{0}();\n    }}\n",
+            port.name, c_width, module_name
+        ));
+    }
+
+    exports.push_str("    \n    int set_input_by_name(void* sim, const char* port, uint64_t value) {\n");
+    for port in &inputs {
+        exports.push_str(&format!(
+            "        if (strcmp(port, \"{0}\") == 0) {{\n            set_{0}_sim(sim, static_cast<uint{1}_t>(value));\n            return 0;\n        }}\n",
+            port.name, ffi_width(port.width.unwrap_or(32))
+        ));
+    }
+    exports.push_str("        return -1;\n    }\n");
+    exports.push_str("    \n    int get_output_by_name(void* sim, const char* port, uint64_t* value) {\n");
+    for port in &outputs {
+        exports.push_str(&format!(
+            "        if (strcmp(port, \"{0}\") == 0) {{\n            *value = get_{0}_sim(sim);\n            return 0;\n        }}\n",
+            port.name
+        ));
+    }
+    exports.push_str("        return -1;\n    }\n");
+    exports.push_str("    \n    int port_width_by_name(const char* port) {\n");
+    for port in inputs.iter().chain(&outputs) {
+        exports.push_str(&format!(
+            "        if (strcmp(port, \"{}\") == 0) return {};\n",
+            port.name, ffi_width(port.width.unwrap_or(32))
+        ));
+    }
+    exports.push_str("        return 0;\n    }\n");
+    exports.push_str(&format!("    \n    int output_port_count() {{\n        return {};\n    }}\n", outputs.len()));
+    exports.push_str("    \n    const char* output_port_name(int index) {\n");
+    if !outputs.is_empty() {
+        let names: Vec<String> = outputs.iter().map(|port| format!("\"{}\"", port.name)).collect();
+        exports.push_str(&format!("        static const char* const names[] = {{{}}};\n", names.join(", ")));
+        exports.push_str(&format!("        if (index >= 0 && index < {}) return names[index];\n", outputs.len()));
+    }
+    exports.push_str("        return nullptr;\n    }\n");
+    (methods, exports)
+}
+
+/// `check_stall` method for ready/valid modules: once a result is valid,
+/// `m_axis_tready` is held low and every output must keep its value
+fn stall_check(graph: &Graph) -> String {
+    let outputs: Vec<Port> = collect_output_ports(graph).into_iter().filter(|port| port.width.unwrap_or(32) <= 64).collect();
+    let mut cpp = String::new();
+    cpp.push_str("    \n");
+    cpp.push_str("    // Backpressure: stall the consumer for `cycles` cycles and check nothing moves\n");
+    cpp.push_str("    bool check_stall(int cycles = 3) {\n");
+    cpp.push_str("        dut->ap_start = 1;\n");
+    cpp.push_str("        for (int i = 0; i < 1000 && !dut->m_axis_tvalid; i++) {\n");
+    cpp.push_str("            clock_tick();\n");
+    cpp.push_str("        }\n");
+    cpp.push_str("        dut->ap_start = 0;\n");
+    for port in &outputs {
+        cpp.push_str(&format!("        uint64_t held_{0} = dut->{0};\n", port.name));
+    }
+    cpp.push_str("        dut->m_axis_tready = 0;\n");
+    cpp.push_str("        bool held = dut->m_axis_tvalid;\n");
+    cpp.push_str("        for (int i = 0; i < cycles; i++) {\n");
+    cpp.push_str("            clock_tick();\n");
+    cpp.push_str("            held = held && dut->m_axis_tvalid;\n");
+    for port in &outputs {
+        cpp.push_str(&format!("            held = held && dut->{0} == held_{0};\n", port.name));
+    }
+    cpp.push_str("        }\n");
+    cpp.push_str("        dut->m_axis_tready = 1;\n");
+    cpp.push_str("        return held;\n");
+    cpp.push_str("    }\n");
+    cpp
+}
+
+/// One transaction of a standalone testbench, as port bit patterns
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestVector {
+    pub inputs: Vec<(String, u64)>,
+    pub expected_outputs: Vec<(String, u64)>,
+}
+
+impl TestVector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn input(mut self, port: &str, value: u64) -> Self {
+        self.inputs.push((port.to_string(), value));
+        self
+    }
+    
+    pub fn expect(mut self, port: &str, value: u64) -> Self {
+        self.expected_outputs.push((port.to_string(), value));
+        self
+    }
+}
+
+/// `main` for a testbench that checks `vectors` itself, appended to the FFI testbench
+///
+/// Each vector is one `std::tuple` of the input values followed by the
+/// expected outputs, in port order. Inputs a vector leaves out are driven
+/// to 0, but it must expect every output. Every vector runs from reset and
+/// the process exits with 1 if any output differs, 0 otherwise.
+fn standalone_main(graph: &Graph, module_name: &str, vectors: &[TestVector]) -> Result<String, HlsError> {
+    let fits = |port: &Port| port.width.unwrap_or(32) <= 64;
+    let inputs: Vec<Port> = collect_input_ports(graph).into_iter().filter(fits).collect();
+    let outputs: Vec<Port> = collect_output_ports(graph).into_iter().filter(fits).collect();
+    
+    let mut rows = Vec::with_capacity(vectors.len());
+    for (index, vector) in vectors.iter().enumerate() {
+        let unknown = vector.inputs.iter().map(|(port, _)| port)
+            .find(|port| !inputs.iter().any(|input| &input.name == *port))
+            .or_else(|| vector.expected_outputs.iter().map(|(port, _)| port).find(|port| !outputs.iter().any(|output| &output.name == *port)));
+        if let Some(port) = unknown {
+            return Err(HlsError::SimulationError(format!("test vector {} names unknown port {}", index, port)));
+        }
+        let mut values = Vec::with_capacity(inputs.len() + outputs.len());
+        for port in &inputs {
+            values.push(vector.inputs.iter().find(|(name, _)| name == &port.name).map_or(0, |&(_, value)| value));
+        }
+        for port in &outputs {
+            let Some(&(_, value)) = vector.expected_outputs.iter().find(|(name, _)| name == &port.name) else {
+                return Err(HlsError::SimulationError(format!("test vector {} has no expected value for {}", index, port.name)));
+            };
+            values.push(value);
+        }
+        let values: Vec<String> = values.iter().map(|value| format!("0x{:x}ULL", value)).collect();
+        rows.push(format!("        {{{}}},\n", values.join(", ")));
+    }
+    
+    let columns = vec!["uint64_t"; inputs.len() + outputs.len()].join(", ");
+    let mut cpp = String::new();
+    cpp.push_str("\n// Standalone test: every vector from reset, exit status 1 on any mismatch\n");
+    cpp.push_str("#include <tuple>\n#include <vector>\n\n");
+    cpp.push_str("int main(int argc, char** argv) {\n");
+    cpp.push_str("    Verilated::commandArgs(argc, argv);\n");
+    cpp.push_str(&format!("    const std::vector<std::tuple<{}>> vectors = {{\n", columns));
+    cpp.push_str(&rows.concat());
+    cpp.push_str("    };\n");
+    cpp.push_str(&format!("    {}Sim sim;\n", module_name));
+    cpp.push_str("    int failures = 0;\n");
+    cpp.push_str("    for (size_t i = 0; i < vectors.size(); i++) {\n");
+    cpp.push_str("        const auto& vector = vectors[i];\n");
+    cpp.push_str("        sim.reset();\n");
+    for (column, port) in inputs.iter().enumerate() {
+        cpp.push_str(&format!(
+            "        sim.set_{}(static_cast<uint{}_t>(std::get<{}>(vector)));\n",
+            port.name, ffi_width(port.width.unwrap_or(32)), column
+        ));
+    }
+    cpp.push_str("        sim.run_until_done();\n");
+    cpp.push_str("        bool passed = true;\n");
+    for (offset, port) in outputs.iter().enumerate() {
+        let column = inputs.len() + offset;
+        cpp.push_str(&format!("        if (static_cast<uint64_t>(sim.get_{0}()) != std::get<{1}>(vector)) {{\n", port.name, column));
+        cpp.push_str(&format!(
+            "            std::cerr << \"vector \" << i << \": {0} = \" << static_cast<uint64_t>(sim.get_{0}()) << \", expected \" << std::get<{1}>(vector) << std::endl;\n",
+            port.name, column
+        ));
+        cpp.push_str("            passed = false;\n");
+        cpp.push_str("        }\n");
+    }
+    cpp.push_str("        failures += passed ? 0 : 1;\n");
+    cpp.push_str("    }\n");
+    cpp.push_str("    std::cout << vectors.size() - failures << \"/\" << vectors.size() << \" vectors passed\" << std::endl;\n");
+    cpp.push_str("    return failures == 0 ? 0 : 1;\n");
+    cpp.push_str("}\n");
+    Ok(cpp)
+}
+
+/// `main` for a testbench that drives `count` random vectors from `seed` and exits with 1 on a bad one
+///
+/// The vectors are those of `RandomTestbenchGenerator::for_graph(graph, seed)`,
+/// from the same LCG, so a failing index can be replayed from Rust. With no
+/// reference model in C++, each vector runs twice from reset and fails if
+/// either run never raises `ap_done` or the two disagree on an output.
+fn random_self_test_main(graph: &Graph, module_name: &str, seed: u64, count: usize) -> String {
+    let fits = |port: &Port| port.width.unwrap_or(32) <= 64;
+    let mut inputs: Vec<Port> = collect_input_ports(graph).into_iter().filter(fits).collect();
+    inputs.sort_by(|a, b| a.name.cmp(&b.name));
+    let outputs: Vec<Port> = collect_output_ports(graph).into_iter().filter(fits).collect();
+    let mask = |port: &Port| match port.width.unwrap_or(32) {
+        64 => u64::MAX,
+        width => (1u64 << width) - 1,
+    };
+    
+    let mut cpp = String::new();
+    cpp.push_str(&format!("\n// Random self-test: {} vectors from seed {}, exit status 1 if one hangs or is not reproducible\n", count, seed));
+    cpp.push_str(&format!("static uint64_t lcg_state = 0x{:x}ULL;\n\n", seed));
+    cpp.push_str("static uint64_t lcg_next32() {\n");
+    cpp.push_str(&format!("    lcg_state = lcg_state * 0x{:x}ULL + 0x{:x}ULL;\n", LCG_MULTIPLIER, LCG_INCREMENT));
+    cpp.push_str("    return lcg_state >> 32;\n");
+    cpp.push_str("}\n\n");
+    cpp.push_str("// A value in 0..=max, as RandomTestbenchGenerator draws it\n");
+    cpp.push_str("static uint64_t lcg_draw(uint64_t max) {\n");
+    cpp.push_str("    uint64_t high = lcg_next32();\n");
+    cpp.push_str("    uint64_t value = (high << 32) | lcg_next32();\n");
+    cpp.push_str("    return max == UINT64_MAX ? value : value % (max + 1);\n");
+    cpp.push_str("}\n\n");
+    cpp.push_str("int main(int argc, char** argv) {\n");
+    cpp.push_str("    Verilated::commandArgs(argc, argv);\n");
+    cpp.push_str(&format!("    {}Sim sim;\n", module_name));
+    cpp.push_str("    int failures = 0;\n");
+    cpp.push_str(&format!("    for (int i = 0; i < {}; i++) {{\n", count));
+    for port in &inputs {
+        cpp.push_str(&format!("        const uint64_t in_{} = lcg_draw(0x{:x}ULL);\n", port.name, mask(port)));
+    }
+    cpp.push_str(&format!("        uint64_t first[{}] = {{}};\n", outputs.len().max(1)));
+    cpp.push_str("        bool passed = true;\n");
+    cpp.push_str("        for (int run = 0; run < 2 && passed; run++) {\n");
+    cpp.push_str("            sim.reset();\n");
+    for port in &inputs {
+        cpp.push_str(&format!("            sim.set_{0}(static_cast<uint{1}_t>(in_{0}));\n", port.name, ffi_width(port.width.unwrap_or(32))));
+    }
+    cpp.push_str("            if (sim.measure_latency() < 0) {\n");
+    cpp.push_str("                std::cerr << \"vector \" << i << \": no ap_done\" << std::endl;\n");
+    cpp.push_str("                passed = false;\n");
+    cpp.push_str("                break;\n");
+    cpp.push_str("            }\n");
+    for (index, port) in outputs.iter().enumerate() {
+        cpp.push_str(&format!("            uint64_t out_{} = sim.get_{}();\n", index, port.name));
+        cpp.push_str(&format!("            if (run == 0) first[{0}] = out_{0};\n", index));
+        cpp.push_str(&format!("            else if (out_{0} != first[{0}]) {{\n", index));
+        cpp.push_str(&format!(
+            "                std::cerr << \"vector \" << i << \": {} = \" << first[{1}] << \", then \" << out_{1} << std::endl;\n",
+            port.name, index
+        ));
+        cpp.push_str("                passed = false;\n");
+        cpp.push_str("            }\n");
+    }
+    cpp.push_str("        }\n");
+    cpp.push_str("        failures += passed ? 0 : 1;\n");
+    cpp.push_str("    }\n");
+    cpp.push_str(&format!("    std::cout << {} - failures << \"/{}\" << \" random vectors passed\" << std::endl;\n", count, count));
+    cpp.push_str("    return failures == 0 ? 0 : 1;\n");
+    cpp.push_str("}\n");
+    cpp
+}
+
+/// Waveform format the Verilated model traces to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraceFormat {
+    /// `--trace`, written to `<module>.vcd`
+    #[default]
+    Vcd,
+    /// `--trace-fst`, written to `<module>.fst`; `create_shared_library` only links the VCD writer
+    Fst,
+}
+
+impl TraceFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            TraceFormat::Vcd => "vcd",
+            TraceFormat::Fst => "fst",
+        }
+    }
+}
+
+/// How `VerilatorSim` invokes Verilator
+///
+/// The defaults are the flags it has always passed: VCD tracing, `-Wall`
+/// with unused, undriven and truncation warnings disabled, all other
+/// warnings fatal, Verilator's own optimization level and no time limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerilatorOptions {
+    /// Waveform tracing, or `None` to build without it
+    pub trace: Option<TraceFormat>,
+    /// `-O0` to `-O3`; `None` leaves Verilator's default
+    pub optimization: Option<u8>,
+    /// Warning categories disabled as `-Wno-<category>`
+    pub disabled_warnings: Vec<String>,
+    /// Fail on any remaining warning; `false` passes `-Wno-fatal`
+    pub warnings_as_errors: bool,
+    /// Passed after the other flags, e.g. `--threads 4`, `--x-assign fast` or `-I<dir>`
+    pub extra_args: Vec<String>,
+    /// Stop Verilator, and fail, if it runs longer than this
+    pub timeout: Option<Duration>,
+}
+
+impl Default for VerilatorOptions {
+    fn default() -> Self {
+        Self {
+            trace: Some(TraceFormat::Vcd),
+            optimization: None,
+            disabled_warnings: ["UNUSED", "UNDRIVEN", "WIDTHTRUNC"].map(String::from).to_vec(),
+            warnings_as_errors: true,
+            extra_args: Vec::new(),
+            timeout: None,
+        }
+    }
+}
+
+impl VerilatorOptions {
+    /// Flags passed between `--build` and `--top-module`
+    fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        match self.trace {
+            Some(TraceFormat::Vcd) => args.push("--trace".to_string()),
+            Some(TraceFormat::Fst) => args.push("--trace-fst".to_string()),
+            None => {}
+        }
+        args.push("-Wall".to_string());
+        args.extend(self.disabled_warnings.iter().map(|category| format!("-Wno-{}", category)));
+        if !self.warnings_as_errors {
+            args.push("-Wno-fatal".to_string());
+        }
+        if let Some(level) = self.optimization {
+            args.push(format!("-O{}", level));
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+}
+
+/// Severity of a Verilator diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    Warning,
+    Error,
+}
+
+/// One `%Warning-<CATEGORY>: file:line:column: message` or `%Error: ...` report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerilatorDiagnostic {
+    pub severity: DiagnosticSeverity,
+    /// Warning category such as `WIDTHEXPAND`; most errors have none
+    pub category: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl std::fmt::Display for VerilatorDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+            for number in [self.line, self.column].into_iter().flatten() {
+                write!(f, "{}:", number)?;
+            }
+            write!(f, " ")?;
+        }
+        let severity = match self.severity {
+            DiagnosticSeverity::Warning => "warning",
+            DiagnosticSeverity::Error => "error",
+        };
+        match &self.category {
+            Some(category) => write!(f, "{}[{}]: {}", severity, category, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// Diagnostics in Verilator's output, in order
+///
+/// Source excerpts and other continuation lines are skipped, as is the
+/// closing `Exiting due to ...` summary.
+pub fn parse_verilator_diagnostics(output: &str) -> Vec<VerilatorDiagnostic> {
+    output.lines().filter_map(|line| {
+        let (severity, rest) = if let Some(rest) = line.strip_prefix("%Warning") {
+            (DiagnosticSeverity::Warning, rest)
+        } else {
+            (DiagnosticSeverity::Error, line.strip_prefix("%Error")?)
+        };
+        let (category, rest) = rest.split_once(": ")?;
+        let category = category.strip_prefix('-').map(str::to_string);
+        if rest.starts_with("Exiting due to") {
+            return None;
+        }
+
+        let mut diagnostic = VerilatorDiagnostic { severity, category, file: None, line: None, column: None, message: rest.to_string() };
+        let fields: Vec<&str> = rest.splitn(4, ':').collect();
+        if let [file, line, tail @ ..] = fields.as_slice() {
+            if let Ok(line) = line.parse() {
+                diagnostic.file = Some(file.to_string());
+                diagnostic.line = Some(line);
+                diagnostic.message = tail.join(":").trim().to_string();
+                if let [column, message] = tail {
+                    if let Ok(column) = column.parse() {
+                        diagnostic.column = Some(column);
+                        diagnostic.message = message.trim().to_string();
+                    }
+                }
+            }
+        }
+        Some(diagnostic)
+    }).collect()
+}
+
+/// Run `cmd` to completion, or kill it and return `None` once `timeout` passes
+fn output_within(cmd: &mut Command, timeout: Option<Duration>) -> std::io::Result<Option<Output>> {
+    let Some(timeout) = timeout else { return cmd.output().map(Some) };
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    // Drain both pipes while waiting, so a chatty run cannot block on a full pipe
+    fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut bytes);
+            }
+            bytes
+        })
+    }
+    let (stdout, stderr) = (drain(child.stdout.take()), drain(child.stderr.take()));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    Ok(Some(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    }))
+}
+
+/// Waveform pieces of the C++ testbench, empty when tracing is off
+#[derive(Default)]
+struct TraceCode {
+    include: String,
+    member: String,
+    open: String,
+    close: String,
+    dump: String,
+}
+
 /// Verilator simulation wrapper
 pub struct VerilatorSim {
     module_name: String,
     verilog_out_dir: PathBuf,
     sim_dir: PathBuf,
     verilated_executable: Option<PathBuf>,
+    test_vectors: Option<Vec<HashMap<String, i64>>>,
+    axi_lite: bool,
+    backpressure: BackpressureMode,
+    parameters: Vec<(String, i64)>,
+    options: VerilatorOptions,
+    force_rebuild: bool,
+    last_build_cached: bool,
+    /// Vectors the C++ testbench checks in its own `main`, while building a standalone executable
+    standalone_vectors: Option<Vec<TestVector>>,
+    /// Seed and vector count of the random self-test `main`, if the testbench has one
+    random_self_test: Option<(u64, usize)>,
 }
 
 impl VerilatorSim {
     /// Create a new Verilator simulation with organized directory structure
     pub fn new(module_name: &str) -> Self {
+        Self::new_with_options(module_name, VerilatorOptions::default())
+    }
+    
+    /// `new`, invoking Verilator with `options` instead of the default flags
+    pub fn new_with_options(module_name: &str, options: VerilatorOptions) -> Self {
         let base_dir = PathBuf::from("target");
         let verilog_out_dir = base_dir.join("verilog_out");
         let sim_dir = base_dir.join("sim").join(module_name);
@@ -29,85 +525,304 @@ impl VerilatorSim {
             verilog_out_dir,
             sim_dir,
             verilated_executable: None,
+            test_vectors: None,
+            axi_lite: false,
+            backpressure: BackpressureMode::None,
+            parameters: Vec::new(),
+            options,
+            force_rebuild: false,
+            last_build_cached: false,
+            standalone_vectors: None,
+            random_self_test: None,
         }
     }
     
-    /// Generate Verilog and compile with Verilator
-    pub fn compile_from_graph(&mut self, graph: &Graph) -> Result<(), String> {
-        // Create directories
-        fs::create_dir_all(&self.verilog_out_dir)
-            .map_err(|e| format!("Failed to create verilog_out directory: {}", e))?;
-        fs::create_dir_all(&self.sim_dir)
-            .map_err(|e| format!("Failed to create sim directory: {}", e))?;
-        
-        // Generate Verilog to verilog_out/
-        let verilog_code = generate_verilog_module(graph, &self.module_name);
-        let verilog_path = self.verilog_out_dir.join(format!("{}.v", self.module_name));
-        
-        fs::write(&verilog_path, verilog_code)
-            .map_err(|e| format!("Failed to write Verilog file: {}", e))?;
-        
-        println!("Generated Verilog: {}", verilog_path.display());
+    /// Write the generated HDL to `dir` instead of `target/verilog_out`
+    pub fn with_verilog_out_dir(mut self, dir: &Path) -> Self {
+        self.verilog_out_dir = dir.to_path_buf();
+        self
+    }
+    
+    /// Also write a self-checking `<module>_tb.v` for these vectors on the next compile
+    pub fn with_verilog_testbench(mut self, test_vectors: Vec<HashMap<String, i64>>) -> Self {
+        self.test_vectors = Some(test_vectors);
+        self
+    }
+    
+    /// Also write the `<module>_axilite.v` control wrapper and its `<module>_axilite.json` register map
+    pub fn with_axi_lite_wrapper(mut self) -> Self {
+        self.axi_lite = true;
+        self
+    }
+    
+    /// Emit the module with the given flow control; `ReadyValid` also adds
+    /// `check_stall`/`check_stall_sim` to the C++ testbench
+    pub fn with_backpressure(mut self, mode: BackpressureMode) -> Self {
+        self.backpressure = mode;
+        self
+    }
+    
+    /// Override a parameter of the top module when Verilating, as `-G<name>=<value>`
+    pub fn with_parameter(mut self, name: &str, value: i64) -> Self {
+        self.parameters.push((name.to_string(), value));
+        self
+    }
+    
+    /// Generate HDL in the given format and compile with Verilator
+    ///
+    /// The build is skipped when the generated sources and Verilator flags
+    /// hash to the `.build_hash` stored in the simulation directory by the
+    /// last successful build and its executable is still there, unless
+    /// `with_force_rebuild` was set.
+    pub fn compile_from_graph(&mut self, graph: &Graph, format: OutputFormat) -> Result<(), HlsError> {
+        let sources = self.generated_sources(graph, format)?;
+        let hash = self.build_hash(&sources, format);
+        let hash_path = self.sim_dir.join(".build_hash");
+        let executable = self.get_obj_dir().join(format!("V{}", self.module_name));
+        let cached = !self.force_rebuild
+            && executable.exists()
+            && fs::read_to_string(&hash_path).is_ok_and(|stored| stored.trim() == hash);
+        self.last_build_cached = cached;
+        if cached {
+            println!("Verilator cache hit for {} ({}): sources and flags unchanged, skipping the build", self.module_name, hash);
+            self.set_executable_path();
+            return Ok(());
+        }
+        println!("Verilator cache miss for {} ({}): rebuilding", self.module_name, hash);
         
-        // Generate C++ testbench to sim/
-        self.generate_cpp_testbench()?;
+        // Create directories
+        fs::create_dir_all(&self.verilog_out_dir)?;
+        fs::create_dir_all(&self.sim_dir)?;
+        // A failed build must not leave a matching hash behind
+        if hash_path.exists() {
+            fs::remove_file(&hash_path)?;
+        }
+        for (path, contents) in &sources {
+            fs::write(path, contents)?;
+            println!("Generated {}", path.display());
+        }
         
         // Run Verilator (output goes to sim/)
+        let verilog_path = self.verilog_out_dir.join(format!("{}.{}", self.module_name, format.extension()));
         self.run_verilator(&verilog_path)?;
         
         // Compile the generated C++
         self.compile_cpp()?;
         
+        fs::write(&hash_path, &hash)?;
+        Ok(())
+    }
+    
+    /// Build `V<module>` as an executable that runs `test_cases` itself and
+    /// reports through its exit status, and return its path
+    ///
+    /// The vectors are compiled into the C++ testbench, so running the
+    /// result needs no FFI; see `TestbenchRunner::run_standalone`.
+    pub fn compile_executable_testbench(&mut self, graph: &Graph, test_cases: &[TestVector]) -> Result<PathBuf, HlsError> {
+        self.standalone_vectors = Some(test_cases.to_vec());
+        let built = self.compile_from_graph(graph, OutputFormat::Verilog);
+        self.standalone_vectors = None;
+        built?;
+        Ok(self.get_obj_dir().join(format!("V{}", self.module_name)))
+    }
+    
+    /// Give the C++ testbench a `main` that runs `count` random vectors from
+    /// `seed`, so `V<module>` checks itself when run
+    ///
+    /// See `random_self_test_main` for what is checked; the vectors of
+    /// `compile_executable_testbench` take precedence while it builds.
+    pub fn with_random_self_test(mut self, seed: u64, count: usize) -> Self {
+        self.random_self_test = Some((seed, count));
+        self
+    }
+    
+    /// Rebuild on every `compile_from_graph`, even when the cached build is current
+    pub fn with_force_rebuild(mut self) -> Self {
+        self.force_rebuild = true;
+        self
+    }
+    
+    /// Whether the last `compile_from_graph` reused the cached build
+    pub fn last_build_cached(&self) -> bool {
+        self.last_build_cached
+    }
+    
+    /// Every file `compile_from_graph` writes, with its contents: the HDL,
+    /// the optional testbench and AXI4-Lite wrapper, and the C++ testbench
+    /// with the standalone or random self-test `main` when it has one
+    fn generated_sources(&self, graph: &Graph, format: OutputFormat) -> Result<Vec<(PathBuf, String)>, HlsError> {
+        let verilog_code = match self.backpressure {
+            BackpressureMode::None => format.generate(graph, &self.module_name)?,
+            backpressure => {
+                let dialect = match format {
+                    OutputFormat::Verilog => VerilogDialect::Verilog2001,
+                    OutputFormat::SystemVerilog => VerilogDialect::SystemVerilog,
+                };
+                let options = VerilogEmitOptions { dialect, backpressure, ..Default::default() };
+                generate_verilog_module(graph, &self.module_name, Some(&options))?
+            }
+        };
+        let out = |suffix: &str| self.verilog_out_dir.join(format!("{}{}", self.module_name, suffix));
+        let mut sources = vec![(out(&format!(".{}", format.extension())), verilog_code)];
+        
+        if let Some(test_vectors) = &self.test_vectors {
+            sources.push((out("_tb.v"), generate_verilog_testbench(graph, &self.module_name, test_vectors)?));
+        }
+        if self.axi_lite {
+            sources.push((out("_axilite.v"), generate_axi_lite_wrapper(graph, &self.module_name)?));
+            sources.push((out("_axilite.json"), generate_axi_lite_register_map(graph, &self.module_name)?));
+        }
+        let mut testbench = self.generate_cpp_testbench(graph);
+        if let Some(vectors) = &self.standalone_vectors {
+            testbench.push_str(&standalone_main(graph, &self.module_name, vectors)?);
+        } else if let Some((seed, count)) = self.random_self_test {
+            testbench.push_str(&random_self_test_main(graph, &self.module_name, seed, count));
+        }
+        sources.push((self.sim_dir.join("testbench.cpp"), testbench));
+        Ok(sources)
+    }
+    
+    /// 64-bit FNV-1a hash, in hex, of the sources and the flags they are Verilated with
+    fn build_hash(&self, sources: &[(PathBuf, String)], format: OutputFormat) -> String {
+        let mut flags = self.options.args();
+        flags.push(format.extension().to_string());
+        flags.extend(self.parameters.iter().map(|(name, value)| format!("-G{}={}", name, value)));
+        
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            // Length prefixes keep ("ab", "c") and ("a", "bc") apart
+            for byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+                hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        for (path, contents) in sources {
+            feed(path.to_string_lossy().as_bytes());
+            feed(contents.as_bytes());
+        }
+        for flag in &flags {
+            feed(flag.as_bytes());
+        }
+        format!("{:016x}", hash)
+    }
+    
+    /// Write `target/vivado/<module>/build.tcl` and its XDC for the sources already in verilog_out
+    ///
+    /// Picks up every HDL file generated for this module (`<module>.v`,
+    /// `<module>_tb.v`, `<module>_axilite.v`, ...). `part` defaults to the
+    /// Alveo U50. Returns the script's path.
+    pub fn write_vivado_project(&self, clock_period_ns: f64, part: Option<&str>) -> Result<PathBuf, HlsError> {
+        let base_dir = self.verilog_out_dir.parent().unwrap_or(Path::new("."));
+        let project_dir = base_dir.join("vivado").join(&self.module_name);
+        fs::create_dir_all(&project_dir)?;
+        
+        let clock = ClockConstraint { period_ns: clock_period_ns, ..Default::default() };
+        let xdc_path = project_dir.join(format!("{}.xdc", self.module_name));
+        fs::write(&xdc_path, generate_xdc(&self.module_name, &clock))?;
+        
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.verilog_out_dir)? {
+            let path = entry?.path();
+            let is_hdl = path.extension().is_some_and(|ext| ext == "v" || ext == "sv" || ext == "vhd");
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            let ours = name.strip_prefix(self.module_name.as_str()).is_some_and(|rest| rest.starts_with(['.', '_']));
+            if is_hdl && ours {
+                files.push(fs::canonicalize(&path)?);
+            }
+        }
+        files.sort();
+        
+        let tcl = generate_vivado_tcl(&self.module_name, part.unwrap_or(ALVEO_U50_PART), &files, &[fs::canonicalize(&xdc_path)?]);
+        let tcl_path = project_dir.join("build.tcl");
+        fs::write(&tcl_path, tcl)?;
+        println!("Generated Vivado script: {}", tcl_path.display());
+        Ok(tcl_path)
+    }
+    
+    /// Write a Vivado synthesis script for the generated `<module>.v` (or `.sv`) to `path`
+    ///
+    /// Targets the Alveo U50; see `vivado::generate_vivado_tcl_for_source`.
+    pub fn write_vivado_tcl(&self, path: &Path) -> Result<(), HlsError> {
+        let verilog = self.verilog_out_dir.join(format!("{}.v", self.module_name));
+        let source = if verilog.exists() { verilog } else { self.verilog_out_dir.join(format!("{}.sv", self.module_name)) };
+        let tcl = generate_vivado_tcl_for_source(&self.module_name, &fs::canonicalize(&source)?, ALVEO_U50_PART);
+        fs::write(path, tcl)?;
+        println!("Generated Vivado script: {}", path.display());
         Ok(())
     }
     
+    /// Compile a graph cached with `Graph::to_json`, skipping the DSL and scheduling passes
+    pub fn compile_from_json(&mut self, json_path: &Path, format: OutputFormat) -> Result<(), HlsError> {
+        let text = fs::read_to_string(json_path)?;
+        let value: serde_json::Value = serde_json::from_str(&text)?;
+        let graph = Graph::from_json(&value)?;
+        self.compile_from_graph(&graph, format)
+    }
+    
     /// Generate C++ testbench for the Verilated module
-    fn generate_cpp_testbench(&self) -> Result<(), String> {
-        let cpp_code = format!(r#"
+    ///
+    /// Ports are reached through the per-port exports of `port_accessors`.
+    fn generate_cpp_testbench(&self, graph: &Graph) -> String {
+        let (port_methods, port_exports) = port_accessors(graph, &self.module_name);
+        let ready_valid = self.backpressure == BackpressureMode::ReadyValid;
+        let tready_init = if ready_valid { "        dut->m_axis_tready = 1;\n" } else { "" };
+        let stall_method = if ready_valid { stall_check(graph) } else { String::new() };
+        let stall_export = if ready_valid {
+            format!(
+                "    \n    int check_stall_sim(void* sim, int cycles) {{\n        return static_cast<{}Sim*>(sim)->check_stall(cycles) ? 1 : 0;\n    }}\n",
+                self.module_name
+            )
+        } else {
+            String::new()
+        };
+        let trace = self.trace_code();
+        format!(r#"
 // Generated C++ testbench wrapper for {}
 #include "V{}.h"
 #include "verilated.h"
-#include "verilated_vcd_c.h"
-#include <iostream>
+{trace_include}#include <iostream>
 #include <memory>
+#include <cstring>
 
 class {}Sim {{
 private:
     std::unique_ptr<V{}> dut;
-    std::unique_ptr<VerilatedVcdC> trace;
-    uint64_t sim_time;
+{trace_member}    uint64_t sim_time;
+    // Cycles from the clock edge sampling ap_start to the one raising ap_done
+    uint64_t latency_cycles;
+    bool timing;
     
 public:
-    {}Sim() : sim_time(0) {{
+    {}Sim() : sim_time(0), latency_cycles(0), timing(false) {{
         dut = std::make_unique<V{}>();
         
-        // Initialize trace
-        Verilated::traceEverOn(true);
-        trace = std::make_unique<VerilatedVcdC>();
-        dut->trace(trace.get(), 99);
-        trace->open("{}.vcd");
-        
-        // Initialize signals
+{trace_open}        // Initialize signals
         dut->ap_rst_n = 0;
         dut->ap_clk = 0;
         dut->ap_start = 0;
-    }}
+{}    }}
     
     ~{}Sim() {{
-        if (trace) {{
-            trace->close();
-        }}
-        dut->final();
+{trace_close}        dut->final();
     }}
     
     void clock_tick() {{
+        bool starting = dut->ap_start;
         dut->ap_clk = 0;
         dut->eval();
-        trace->dump(sim_time++);
+        {trace_dump}
         
         dut->ap_clk = 1;
         dut->eval();
-        trace->dump(sim_time++);
+        {trace_dump}
+        
+        if (starting && !timing) {{
+            timing = true;
+            latency_cycles = 0;
+        }}
+        if (timing) {{
+            latency_cycles++;
+            timing = !dut->ap_done;
+        }}
     }}
     
     void reset() {{
@@ -117,6 +832,8 @@ public:
         }}
         dut->ap_rst_n = 1;
         clock_tick();
+        latency_cycles = 0;
+        timing = false;
     }}
     
     void start_computation() {{
@@ -133,32 +850,49 @@ public:
         return dut->ap_idle;
     }}
     
-    // Input setters (these will be generated based on actual inputs)
-    void set_input_a(uint32_t value) {{
-        dut->a = value;
+    void set_start(bool start) {{
+        dut->ap_start = start;
     }}
     
-    void set_input_b(uint32_t value) {{
-        dut->b = value;
+    // 0 until a computation started by ap_start has raised ap_done
+    uint64_t get_latency_cycles() {{
+        return timing ? 0 : latency_cycles;
     }}
     
-    // Output getters (these will be generated based on actual outputs)
-    uint32_t get_output_result() {{
-        return dut->result;
+    // Advances twice per clock_tick, once per edge
+    uint64_t get_sim_time() {{
+        return sim_time;
     }}
     
-    void run_until_done() {{
+    // Port access, generated from the graph's inputs and outputs
+{}    void run_until_done() {{
+        uint64_t started = sim_time;
         start_computation();
         while (!is_done()) {{
             clock_tick();
-            if (sim_time > 1000) {{ // Timeout protection
+            if (sim_time - started > 1000) {{ // Timeout protection
                 std::cerr << "Simulation timeout!" << std::endl;
                 break;
             }}
         }}
         clock_tick(); // One more cycle to see the done signal
     }}
-}};
+    
+    // Clock edges from the one sampling ap_start to the one raising ap_done
+    int measure_latency() {{
+        dut->ap_start = 1;
+        clock_tick();
+        dut->ap_start = 0;
+        int cycles = 1;
+        while (!is_done()) {{
+            clock_tick();
+            if (++cycles > 1000) {{ // Timeout protection
+                return -1;
+            }}
+        }}
+        return cycles;
+    }}
+{}}};
 
 // C interface for Rust FFI
 extern "C" {{
@@ -174,26 +908,34 @@ extern "C" {{
         static_cast<{}Sim*>(sim)->reset();
     }}
     
-    void set_input_a_sim(void* sim, uint32_t value) {{
-        static_cast<{}Sim*>(sim)->set_input_a(value);
+    void run_until_done_sim(void* sim) {{
+        static_cast<{}Sim*>(sim)->run_until_done();
     }}
     
-    void set_input_b_sim(void* sim, uint32_t value) {{
-        static_cast<{}Sim*>(sim)->set_input_b(value);
+    int is_done_sim(void* sim) {{
+        return static_cast<{}Sim*>(sim)->is_done() ? 1 : 0;
     }}
     
-    uint32_t get_output_result_sim(void* sim) {{
-        return static_cast<{}Sim*>(sim)->get_output_result();
+    int measure_latency_sim(void* sim) {{
+        return static_cast<{}Sim*>(sim)->measure_latency();
     }}
     
-    void run_until_done_sim(void* sim) {{
-        static_cast<{}Sim*>(sim)->run_until_done();
+    void clock_tick_sim(void* sim) {{
+        static_cast<{}Sim*>(sim)->clock_tick();
     }}
     
-    int is_done_sim(void* sim) {{
-        return static_cast<{}Sim*>(sim)->is_done() ? 1 : 0;
+    void set_start_sim(void* sim, int start) {{
+        static_cast<{}Sim*>(sim)->set_start(start != 0);
     }}
-}}
+    
+    uint64_t get_sim_time_sim(void* sim) {{
+        return static_cast<{}Sim*>(sim)->get_sim_time();
+    }}
+    
+    uint64_t get_latency_cycles_sim(void* sim) {{
+        return static_cast<{}Sim*>(sim)->get_latency_cycles();
+    }}
+{}{}}}
 "#,
             self.module_name, // V{}.h include
             self.module_name, // V{} class
@@ -201,36 +943,58 @@ extern "C" {{
             self.module_name, // V{} member
             self.module_name, // {}Sim constructor
             self.module_name, // V{} constructor
-            self.module_name, // VCD filename
+            tready_init,      // m_axis_tready starts high
             self.module_name, // ~{}Sim destructor
+            port_methods,     // set_<port>/get_<port>
+            stall_method,     // check_stall for ready/valid modules
             self.module_name, // create_sim return
             self.module_name, // destroy_sim cast
             self.module_name, // reset_sim cast
-            self.module_name, // set_input_a_sim cast
-            self.module_name, // set_input_b_sim cast
-            self.module_name, // get_output_result_sim cast
             self.module_name, // run_until_done_sim cast
             self.module_name, // is_done_sim cast
-        );
-        
-        let cpp_path = self.sim_dir.join("testbench.cpp");
-        fs::write(cpp_path, cpp_code)
-            .map_err(|e| format!("Failed to write C++ testbench: {}", e))?;
-        
-        println!("Generated C++ testbench: {}", self.sim_dir.join("testbench.cpp").display());
-        
-        Ok(())
+            self.module_name, // measure_latency_sim cast
+            self.module_name, // clock_tick_sim cast
+            self.module_name, // set_start_sim cast
+            self.module_name, // get_sim_time_sim cast
+            self.module_name, // get_latency_cycles_sim cast
+            port_exports,     // set_<port>_sim/get_<port>_sim and the by-name dispatchers
+            stall_export,     // check_stall_sim
+            trace_include = trace.include,
+            trace_member = trace.member,
+            trace_open = trace.open,
+            trace_close = trace.close,
+            trace_dump = trace.dump,
+        )
+    }
+    
+    /// Testbench lines that open, dump and close the waveform of `options.trace`
+    fn trace_code(&self) -> TraceCode {
+        let Some(format) = self.options.trace else {
+            return TraceCode { dump: "sim_time++;".to_string(), ..Default::default() };
+        };
+        let (header, class) = match format {
+            TraceFormat::Vcd => ("verilated_vcd_c.h", "VerilatedVcdC"),
+            TraceFormat::Fst => ("verilated_fst_c.h", "VerilatedFstC"),
+        };
+        TraceCode {
+            include: format!("#include \"{}\"\n", header),
+            member: format!("    std::unique_ptr<{}> trace;\n", class),
+            open: format!(
+                "        // Initialize trace\n        Verilated::traceEverOn(true);\n        trace = std::make_unique<{}>();\n        dut->trace(trace.get(), 99);\n        trace->open(\"{}.{}\");\n        \n",
+                class, self.module_name, format.extension()
+            ),
+            close: "        if (trace) {\n            trace->close();\n        }\n".to_string(),
+            dump: "trace->dump(sim_time++);".to_string(),
+        }
     }
     
     /// Run Verilator to generate C++ from Verilog
-    fn run_verilator(&mut self, verilog_path: &Path) -> Result<(), String> {
+    fn run_verilator(&mut self, verilog_path: &Path) -> Result<(), HlsError> {
         // Get the absolute path, but handle Windows UNC path issues
         let abs_verilog_path = if verilog_path.is_absolute() {
             verilog_path.to_path_buf()
         } else {
-            std::env::current_dir()
-                .map_err(|e| format!("Failed to get current directory: {}", e))?
-                .join(verilog_path)
+            std::env::current_dir()?.join(verilog_path)
         };
         
         // Convert to string and normalize path separators for Verilator
@@ -248,30 +1012,34 @@ extern "C" {{
             println!("Setting VERILATOR_ROOT to: {}", root);
         }
         
+        // `.sv` sources are parsed as SystemVerilog from their extension
         cmd.arg("--cc")                    // Generate C++
             .arg("--exe")                   // Generate executable
             .arg("--build")                 // Build the executable
-            .arg("--trace")                 // Enable VCD tracing
-            .arg("-Wall")                   // Enable warnings
-            .arg("-Wno-UNUSED")            // Disable unused warnings
-            .arg("-Wno-UNDRIVEN")          // Disable undriven warnings
-            .arg("-Wno-WIDTHTRUNC")        // Disable width truncation warnings
+            .args(self.options.args())      // Tracing, warnings, optimization and extra flags
             .arg("--top-module")
-            .arg(&self.module_name)
-            .arg("testbench.cpp")          // Use our testbench file
+            .arg(&self.module_name);
+        for (name, value) in &self.parameters {
+            cmd.arg(format!("-G{}={}", name, value));
+        }
+        cmd.arg("testbench.cpp")           // Use our testbench file
             .arg(&verilog_path_str)        // Use normalized absolute path
             .current_dir(&self.sim_dir);   // Work in sim directory
         
         println!("Running Verilator with Verilog file: {}", verilog_path_str);
         
-        let output = cmd.output();
+        let output = output_within(&mut cmd, self.options.timeout);
         
         match output {
-            Ok(result) => {
+            Ok(None) => Err(HlsError::VerilatorTimeout(self.options.timeout.unwrap_or_default())),
+            Ok(Some(result)) => {
                 if !result.status.success() {
-                    let stderr = String::from_utf8_lossy(&result.stderr);
-                    let stdout = String::from_utf8_lossy(&result.stdout);
-                    return Err(format!("Verilator failed:\nstdout: {}\nstderr: {}", stdout, stderr));
+                    let stderr = String::from_utf8_lossy(&result.stderr).into_owned();
+                    return Err(HlsError::VerilatorError {
+                        stdout: String::from_utf8_lossy(&result.stdout).into_owned(),
+                        diagnostics: parse_verilator_diagnostics(&stderr),
+                        stderr,
+                    });
                 }
                 
                 // Set the executable path
@@ -281,12 +1049,12 @@ extern "C" {{
                 println!("Verilated files in: {}", self.sim_dir.join("obj_dir").display());
                 Ok(())
             }
-            Err(e) => Err(format!("Failed to run Verilator: {}. Make sure Verilator is installed.", e))
+            Err(e) => Err(HlsError::CompilerNotFound(format!("verilator ({}). Make sure Verilator is installed.", e)))
         }
     }
     
     /// Find Verilator root directory
-    fn find_verilator_root(&self) -> Result<String, String> {
+    fn find_verilator_root(&self) -> Result<String, HlsError> {
         // Try to get VERILATOR_ROOT from verilator itself
         if let Ok(output) = Command::new("verilator").arg("--getenv").arg("VERILATOR_ROOT").output() {
             if output.status.success() {
@@ -322,11 +1090,11 @@ extern "C" {{
             }
         }
         
-        Err("Could not find Verilator root directory".to_string())
+        Err(HlsError::CompilerNotFound("Verilator root directory".to_string()))
     }
     
     /// Compile the generated C++ with proper linking
-    fn compile_cpp(&self) -> Result<(), String> {
+    fn compile_cpp(&self) -> Result<(), HlsError> {
         // The --build flag in Verilator should handle this,
         // but we can add additional compilation steps here if needed
         Ok(())
@@ -364,7 +1132,7 @@ extern "C" {{
 }
 
 /// Create a dynamic library for FFI with Rust
-pub fn create_shared_library(module_name: &str, sim_dir: &Path) -> Result<PathBuf, String> {
+pub fn create_shared_library(module_name: &str, sim_dir: &Path) -> Result<PathBuf, HlsError> {
     // Determine the library filename based on platform
     let lib_filename = if cfg!(target_os = "windows") {
         format!("{}_sim.dll", module_name)
@@ -380,7 +1148,7 @@ pub fn create_shared_library(module_name: &str, sim_dir: &Path) -> Result<PathBu
     // Check if Verilator generated the necessary files
     let verilated_cpp = obj_dir.join(format!("V{}.cpp", module_name));
     if !verilated_cpp.exists() {
-        return Err(format!("Verilated C++ file not found: {}", verilated_cpp.display()));
+        return Err(HlsError::CompilerNotFound(format!("Verilated C++ file {}", verilated_cpp.display())));
     }
     
     // Determine compiler and flags based on platform
@@ -462,17 +1230,18 @@ pub fn create_shared_library(module_name: &str, sim_dir: &Path) -> Result<PathBu
                 println!("Created shared library: {}", lib_path.display());
                 Ok(lib_path)
             } else {
-                let stderr = String::from_utf8_lossy(&result.stderr);
-                let stdout = String::from_utf8_lossy(&result.stdout);
-                Err(format!("Failed to create shared library:\nStderr: {}\nStdout: {}", stderr, stdout))
+                Err(HlsError::CompileError {
+                    stdout: String::from_utf8_lossy(&result.stdout).into_owned(),
+                    stderr: String::from_utf8_lossy(&result.stderr).into_owned(),
+                })
             }
         }
-        Err(e) => Err(format!("Failed to run {}: {}", compiler, e))
+        Err(e) => Err(HlsError::CompilerNotFound(format!("{} ({})", compiler, e)))
     }
 }
 
 /// Get Verilator include directory (platform-specific)
-fn get_verilator_include_dir() -> Result<String, String> {
+fn get_verilator_include_dir() -> Result<String, HlsError> {
     if cfg!(target_os = "windows") {
         // On Windows, Verilator might be installed via MSYS2, WSL, or Chocolatey
         let possible_paths = vec![
@@ -487,7 +1256,7 @@ fn get_verilator_include_dir() -> Result<String, String> {
             }
         }
         
-        Err("Verilator include directory not found. Please install Verilator via MSYS2, WSL, or manually set the path.".to_string())
+        Err(HlsError::CompilerNotFound("Verilator include directory. Please install Verilator via MSYS2, WSL, or manually set the path.".to_string()))
     } else if cfg!(target_os = "macos") {
         // On macOS, check Homebrew location
         let possible_paths = vec![
@@ -501,7 +1270,7 @@ fn get_verilator_include_dir() -> Result<String, String> {
             }
         }
         
-        Err("Verilator include directory not found. Please install with: brew install verilator".to_string())
+        Err(HlsError::CompilerNotFound("Verilator include directory. Please install with: brew install verilator".to_string()))
     } else {
         // Linux
         let possible_paths = vec![
@@ -515,7 +1284,7 @@ fn get_verilator_include_dir() -> Result<String, String> {
             }
         }
         
-        Err("Verilator include directory not found. Please install with: sudo apt install verilator".to_string())
+        Err(HlsError::CompilerNotFound("Verilator include directory. Please install with: sudo apt install verilator".to_string()))
     }
 }
 
@@ -538,15 +1307,334 @@ mod tests {
         let mut verilator_sim = VerilatorSim::new("test_adder");
         
         // This test will only pass if Verilator is installed
-        if let Err(e) = verilator_sim.compile_from_graph(&graph) {
-            if e.contains("Failed to run Verilator") {
+        match verilator_sim.compile_from_graph(&graph, OutputFormat::Verilog) {
+            Ok(()) => {}
+            Err(e @ HlsError::CompilerNotFound(_)) => {
                 println!("Skipping Verilator test - Verilator not installed: {}", e);
                 return; // Skip test if Verilator is not available
-            } else {
-                panic!("Unexpected error: {}", e);
             }
+            Err(e) => panic!("Unexpected error: {}", e),
         }
         
+        // The simple module registers `result` when it leaves COMPUTE
+        let lib_path = create_shared_library("test_adder", verilator_sim.get_sim_dir())
+            .expect("failed to build the adder simulation library");
+        let testbench = crate::backend::testbench::VerilatorTestbench::new(&lib_path)
+            .expect("failed to load the adder simulation library");
+        assert_eq!(testbench.run_test(5, 10).unwrap(), 15);
+        
         println!("Verilator compilation test passed!");
     }
+    
+    #[test]
+    fn test_compile_from_cached_json() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 32), input("b", 32))));
+        let cache = std::env::temp_dir().join("rust_hls_cached_adder.json");
+        fs::write(&cache, graph.to_json().to_string()).unwrap();
+        
+        let mut verilator_sim = VerilatorSim::new("test_cached_adder");
+        match verilator_sim.compile_from_json(&cache, OutputFormat::Verilog) {
+            Ok(()) => {}
+            Err(HlsError::CompilerNotFound(_)) => {
+                // The cached graph was still parsed and emitted
+                let verilog = fs::read_to_string(verilator_sim.get_verilog_out_dir().join("test_cached_adder.v")).unwrap();
+                assert!(verilog.contains("result_reg <= node_2;"));
+            }
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        
+        let missing = verilator_sim.compile_from_json(Path::new("no/such/graph.json"), OutputFormat::Verilog);
+        assert!(matches!(missing, Err(HlsError::IoError(_))));
+    }
+    
+    #[test]
+    fn test_data_width_overridden_when_verilating() {
+        let mut function = crate::dsl::hls::HLSFunction::new("test_width16_adder");
+        let a = function.input("a").value;
+        let b = function.input("b").value;
+        let sum = function.graph.add_node_with_output(crate::ir::graph::Operation::Add(a, b));
+        function.graph.add_node(crate::ir::graph::Operation::Store("sum".to_string(), sum));
+        let parameterized = function.generate_verilog_parameterized(&crate::backend::verilog::GenerationParams::default()).unwrap();
+        
+        let mut verilator_sim = VerilatorSim::new("test_width16_adder").with_parameter("DATA_WIDTH", 16);
+        match verilator_sim.compile_from_graph(&function.graph, OutputFormat::Verilog) {
+            Ok(()) => {}
+            Err(HlsError::CompilerNotFound(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        let verilog = fs::read_to_string(verilator_sim.get_verilog_out_dir().join("test_width16_adder.v")).unwrap();
+        assert_eq!(verilog, parameterized);
+        assert!(verilog.contains("input  wire [DATA_WIDTH-1:0]  a,"));
+    }
+    
+    #[test]
+    fn test_compile_writes_optional_verilog_testbench() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        let vectors = vec![HashMap::from([("a".to_string(), 3), ("b".to_string(), 4)])];
+        
+        let mut verilator_sim = VerilatorSim::new("test_tb_adder").with_verilog_testbench(vectors);
+        match verilator_sim.compile_from_graph(&graph, OutputFormat::Verilog) {
+            Ok(()) => {}
+            Err(HlsError::CompilerNotFound(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        
+        let testbench = fs::read_to_string(verilator_sim.get_verilog_out_dir().join("test_tb_adder_tb.v")).unwrap();
+        assert!(testbench.contains("module test_tb_adder_tb;"));
+        assert!(testbench.contains("result_expected[0] = 9'h7;"));
+        
+        let tcl = fs::read_to_string(verilator_sim.write_vivado_project(5.0, Some("xcu280-fsvh2892-2L-e")).unwrap()).unwrap();
+        assert!(tcl.contains("-part xcu280-fsvh2892-2L-e"));
+        assert!(tcl.contains("verilog_out/test_tb_adder.v}"));
+        assert!(tcl.contains("add_files -fileset sim_1 -norecurse {\n    {"));
+        let xdc = fs::read_to_string(Path::new("target/vivado/test_tb_adder/test_tb_adder.xdc")).unwrap();
+        assert!(xdc.contains("create_clock -period 5.000 -name ap_clk [get_ports ap_clk]"));
+        
+        let tcl_path = Path::new("target/vivado/test_tb_adder/single.tcl");
+        verilator_sim.write_vivado_tcl(tcl_path).unwrap();
+        let tcl = fs::read_to_string(tcl_path).unwrap();
+        assert!(tcl.contains("create_project -force test_tb_adder") && tcl.contains("-part xcu50-fsvh2104-2-e"));
+        assert!(tcl.contains("vivado/test_tb_adder/test_tb_adder.xdc}"));
+        assert!(!tcl.contains("sim_1"));
+    }
+    
+    #[test]
+    fn test_compile_writes_optional_axi_lite_wrapper() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        
+        let mut verilator_sim = VerilatorSim::new("test_axil_adder").with_axi_lite_wrapper();
+        match verilator_sim.compile_from_graph(&graph, OutputFormat::Verilog) {
+            Ok(()) => {}
+            Err(HlsError::CompilerNotFound(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        
+        let out_dir = verilator_sim.get_verilog_out_dir();
+        let wrapper = fs::read_to_string(out_dir.join("test_axil_adder_axilite.v")).unwrap();
+        assert!(wrapper.contains("module test_axil_adder_axilite ("));
+        assert!(!wrapper.contains("module test_axil_adder ("));
+        let map: serde_json::Value = serde_json::from_str(&fs::read_to_string(out_dir.join("test_axil_adder_axilite.json")).unwrap()).unwrap();
+        assert_eq!(map["registers"][0]["name"], "result");
+    }
+    
+    #[test]
+    fn test_backpressure_testbench_holds_tready_low() {
+        let mut graph = lower_expr_to_graph(&output("result", add(mul(input("a", 8), input("b", 8)), input("c", 16))));
+        graph.enable_pipeline(1, 4, 1);
+        crate::passes::pipeline::run_pipeline_pass(&mut graph).unwrap();
+        
+        let mut verilator_sim = VerilatorSim::new("test_stall_mac").with_backpressure(BackpressureMode::ReadyValid);
+        match verilator_sim.compile_from_graph(&graph, OutputFormat::Verilog) {
+            Ok(()) => {}
+            Err(HlsError::CompilerNotFound(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        
+        let verilog = fs::read_to_string(verilator_sim.get_verilog_out_dir().join("test_stall_mac.v")).unwrap();
+        assert!(verilog.contains("end else if (enable & stage_valid[0]) begin"));
+        let cpp = fs::read_to_string(verilator_sim.get_sim_dir().join("testbench.cpp")).unwrap();
+        assert!(cpp.contains("bool check_stall(int cycles = 3) {"));
+        assert!(cpp.contains("dut->m_axis_tready = 0;"));
+        assert!(cpp.contains("held = held && dut->result == held_result;"));
+        assert!(cpp.contains("int check_stall_sim(void* sim, int cycles) {"));
+    }
+    
+    #[test]
+    fn test_testbench_exports_one_typed_accessor_per_port() {
+        let mac = add(add(mul(input("a", 8), input("b", 8)), mul(input("c", 16), input("d", 12))), input("e", 32));
+        let graph = lower_expr_to_graph(&output("result", mac));
+        
+        let mut verilator_sim = VerilatorSim::new("test_ports_mac");
+        match verilator_sim.compile_from_graph(&graph, OutputFormat::Verilog) {
+            Ok(()) => {}
+            Err(HlsError::CompilerNotFound(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        
+        let cpp = fs::read_to_string(verilator_sim.get_sim_dir().join("testbench.cpp")).unwrap();
+        assert!(cpp.contains("void set_a_sim(void* sim, uint8_t value) {"));
+        assert!(cpp.contains("void set_e_sim(void* sim, uint32_t value) {"));
+        // 12 bits travel as a uint16_t with the top bits cleared
+        assert!(cpp.contains("void set_d(uint16_t value) {\n        dut->d = value & 0xfffULL;"));
+        assert!(cpp.contains("uint64_t get_result_sim(void* sim) {"));
+        assert!(cpp.contains("if (strcmp(port, \"d\") == 0) {\n            set_d_sim(sim, static_cast<uint16_t>(value));"));
+        assert!(cpp.contains("if (strcmp(port, \"result\") == 0) {\n            *value = get_result_sim(sim);"));
+        assert!(cpp.contains("if (strcmp(port, \"d\") == 0) return 16;"));
+        assert!(cpp.contains("int output_port_count() {\n        return 1;"));
+        assert!(cpp.contains("static const char* const names[] = {\"result\"};"));
+        assert!(!cpp.contains("set_input_sim("));
+        assert!(cpp.contains("uint64_t get_sim_time_sim(void* sim) {\n        return static_cast<test_ports_macSim*>(sim)->get_sim_time();"));
+        assert!(cpp.contains("uint64_t get_latency_cycles_sim(void* sim) {\n        return static_cast<test_ports_macSim*>(sim)->get_latency_cycles();"));
+    }
+    
+    #[test]
+    fn test_options_default_to_the_original_flags() {
+        let flags = ["--trace", "-Wall", "-Wno-UNUSED", "-Wno-UNDRIVEN", "-Wno-WIDTHTRUNC"];
+        assert_eq!(VerilatorOptions::default().args(), flags);
+        
+        let options = VerilatorOptions {
+            trace: Some(TraceFormat::Fst),
+            optimization: Some(3),
+            disabled_warnings: vec!["WIDTHEXPAND".to_string()],
+            warnings_as_errors: false,
+            extra_args: ["--x-assign", "fast", "--threads", "4"].map(String::from).to_vec(),
+            timeout: None,
+        };
+        assert_eq!(options.args(), ["--trace-fst", "-Wall", "-Wno-WIDTHEXPAND", "-Wno-fatal", "-O3", "--x-assign", "fast", "--threads", "4"]);
+        
+        // The testbench follows the trace format, or leaves tracing out
+        let sim = VerilatorSim::new_with_options("fst_adder", options);
+        let fst = sim.trace_code();
+        assert_eq!(fst.include, "#include \"verilated_fst_c.h\"\n");
+        assert!(fst.open.contains("trace->open(\"fst_adder.fst\");"));
+        let untraced = VerilatorSim::new_with_options("adder", VerilatorOptions { trace: None, ..Default::default() }).trace_code();
+        assert_eq!((untraced.include.as_str(), untraced.dump.as_str()), ("", "sim_time++;"));
+    }
+    
+    #[test]
+    fn test_verilator_output_is_parsed_into_diagnostics() {
+        let stderr = "\
+%Warning-WIDTHEXPAND: /tmp/adder.v:12:20: Operator ADD expects 9 bits on the RHS, but RHS's VARREF 'b' generates 8 bits.
+                                        : ... In instance adder
+   12 |     assign node_2 = a + b;
+      |                    ^
+                      ... For warning description see https://verilator.org/warn/WIDTHEXPAND?v=5.020
+%Error: adder.v:3: syntax error, unexpected always
+%Error-PINNOTFOUND: top.v:7:5: Pin not found: 'clk'
+%Error: Exiting due to 2 error(s)
+";
+        let diagnostics = parse_verilator_diagnostics(stderr);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0], VerilatorDiagnostic {
+            severity: DiagnosticSeverity::Warning,
+            category: Some("WIDTHEXPAND".to_string()),
+            file: Some("/tmp/adder.v".to_string()),
+            line: Some(12),
+            column: Some(20),
+            message: "Operator ADD expects 9 bits on the RHS, but RHS's VARREF 'b' generates 8 bits.".to_string(),
+        });
+        assert_eq!((diagnostics[1].line, diagnostics[1].column), (Some(3), None));
+        assert_eq!(diagnostics[1].to_string(), "adder.v:3: error: syntax error, unexpected always");
+        assert_eq!(diagnostics[2].to_string(), "top.v:7:5: error[PINNOTFOUND]: Pin not found: 'clk'");
+        
+        let error = HlsError::VerilatorError { stdout: String::new(), stderr: stderr.to_string(), diagnostics };
+        assert!(error.to_string().starts_with("Verilator failed:\n/tmp/adder.v:12:20: warning[WIDTHEXPAND]: Operator ADD"));
+    }
+    
+    #[test]
+    fn test_standalone_vectors_are_baked_into_the_testbench_main() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        let vectors = [TestVector::new().input("a", 5).input("b", 10).expect("result", 15), TestVector::new().expect("result", 0)];
+        let main = standalone_main(&graph, "adder", &vectors).unwrap();
+        assert!(main.contains("const std::vector<std::tuple<uint64_t, uint64_t, uint64_t>> vectors = {\n        {0x5ULL, 0xaULL, 0xfULL},\n        {0x0ULL, 0x0ULL, 0x0ULL},\n    };"));
+        assert!(main.contains("sim.set_b(static_cast<uint8_t>(std::get<1>(vector)));"));
+        assert!(main.contains("if (static_cast<uint64_t>(sim.get_result()) != std::get<2>(vector)) {"));
+        assert!(main.contains("return failures == 0 ? 0 : 1;"));
+        
+        let unchecked = standalone_main(&graph, "adder", &[TestVector::new().input("a", 1)]);
+        assert!(matches!(unchecked, Err(HlsError::SimulationError(message)) if message == "test vector 0 has no expected value for result"));
+        let misnamed = standalone_main(&graph, "adder", &[TestVector::new().input("c", 1).expect("result", 1)]);
+        assert!(matches!(misnamed, Err(HlsError::SimulationError(message)) if message == "test vector 0 names unknown port c"));
+    }
+    
+    #[test]
+    fn test_random_self_test_draws_the_generator_sequence() {
+        use crate::backend::testbench::RandomTestbenchGenerator;
+        let graph = lower_expr_to_graph(&output("result", add(input("b", 8), input("a", 1))));
+        let main = random_self_test_main(&graph, "random_adder", 7, 100);
+        assert!(main.contains("static uint64_t lcg_state = 0x7ULL;"));
+        // Ports in name order, each over its width, as the generator draws them
+        let a = main.find("const uint64_t in_a = lcg_draw(0x1ULL);").unwrap();
+        assert!(main[a..].contains("const uint64_t in_b = lcg_draw(0xffULL);"));
+        assert!(main.contains("for (int i = 0; i < 100; i++) {"));
+        assert!(main.contains("else if (out_0 != first[0]) {"));
+        assert!(main.contains("return failures == 0 ? 0 : 1;"));
+        let ports: Vec<String> = RandomTestbenchGenerator::for_graph(&graph, 7).generate_test_vectors(1)[0].inputs.iter().map(|(port, _)| port.clone()).collect();
+        assert_eq!(ports, ["a", "b"]);
+        
+        let sim = VerilatorSim::new("random_adder").with_random_self_test(7, 100);
+        let sources = sim.generated_sources(&graph, OutputFormat::Verilog).unwrap();
+        assert!(sources.last().unwrap().1.ends_with(&main));
+    }
+    
+    #[test]
+    fn test_unchanged_build_is_reused_without_running_verilator() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        let mut first = VerilatorSim::new("cached_adder").with_force_rebuild();
+        match first.compile_from_graph(&graph, OutputFormat::Verilog) {
+            Ok(()) => {}
+            Err(HlsError::CompilerNotFound(_)) => {
+                // Stand in for the build Verilator would have left behind
+                let sources = first.generated_sources(&graph, OutputFormat::Verilog).unwrap();
+                fs::create_dir_all(first.get_obj_dir()).unwrap();
+                fs::write(first.get_obj_dir().join("Vcached_adder"), "").unwrap();
+                fs::write(first.get_sim_dir().join(".build_hash"), first.build_hash(&sources, OutputFormat::Verilog)).unwrap();
+            }
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        assert!(!first.last_build_cached());
+        
+        // Without Verilator on this machine, only a cache hit can succeed
+        let mut second = VerilatorSim::new("cached_adder");
+        second.compile_from_graph(&graph, OutputFormat::Verilog).unwrap();
+        assert!(second.last_build_cached());
+        
+        // New flags, a new graph or a forced rebuild all miss
+        let optimized = VerilatorOptions { optimization: Some(3), ..Default::default() };
+        let mut flagged = VerilatorSim::new_with_options("cached_adder", optimized);
+        let sources = flagged.generated_sources(&graph, OutputFormat::Verilog).unwrap();
+        assert_ne!(flagged.build_hash(&sources, OutputFormat::Verilog), second.build_hash(&sources, OutputFormat::Verilog));
+        let wider = lower_expr_to_graph(&output("result", add(input("a", 16), input("b", 16))));
+        let changed = second.generated_sources(&wider, OutputFormat::Verilog).unwrap();
+        assert_ne!(second.build_hash(&changed, OutputFormat::Verilog), second.build_hash(&sources, OutputFormat::Verilog));
+        let _ = flagged.compile_from_graph(&graph, OutputFormat::Verilog);
+        assert!(!flagged.last_build_cached());
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_runs_past_the_timeout_are_stopped() {
+        let started = Instant::now();
+        let output = output_within(Command::new("sleep").arg("5"), Some(Duration::from_millis(100))).unwrap();
+        assert!(output.is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        
+        let output = output_within(Command::new("echo").arg("done"), Some(Duration::from_secs(5))).unwrap().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+    }
+    
+    #[test]
+    fn test_verilator_rejection_carries_its_output() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        
+        // `always` is a Verilog keyword, so the module header does not parse
+        let mut verilator_sim = VerilatorSim::new("always");
+        match verilator_sim.compile_from_graph(&graph, OutputFormat::Verilog) {
+            Err(HlsError::CompilerNotFound(_)) => println!("Skipping Verilator rejection test - Verilator not installed"),
+            err => assert!(matches!(err, Err(HlsError::VerilatorError { .. })), "{:?}", err),
+        }
+    }
+    
+    #[test]
+    fn test_adder_compiles_in_both_formats() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 32), input("b", 32))));
+        
+        for format in [OutputFormat::Verilog, OutputFormat::SystemVerilog] {
+            let module_name = format!("test_adder_{}", format.extension());
+            let mut verilator_sim = VerilatorSim::new(&module_name);
+            
+            // Any Verilator error fails the compile, so Ok means a clean run
+            match verilator_sim.compile_from_graph(&graph, format) {
+                Ok(()) => {
+                    let source = verilator_sim.get_verilog_out_dir().join(format!("{}.{}", module_name, format.extension()));
+                    assert!(source.exists());
+                }
+                Err(HlsError::CompilerNotFound(_)) => {
+                    println!("Skipping {:?} Verilator test - Verilator not installed", format);
+                    return;
+                }
+                Err(e) => panic!("{:?} backend failed Verilator: {}", format, e),
+            }
+        }
+    }
 }
@@ -2,84 +2,484 @@
 //! 
 //! This module generates clean, maintainable Verilog RTL code optimized for AMD FPGAs.
 
-use crate::ir::graph::{Graph, Operation};
-// Removed unused HashMap import
+use crate::backend::axi::generate_axi_stream_kernel;
+use crate::backend::perf::{generate_perf_counters, perf_counter_instance, perf_counter_ports};
+use crate::backend::sim::slice_bits;
+use crate::backend::systemverilog::rewrite_as_systemverilog;
+use crate::dsl::types::FixedPointType;
+use crate::error::HlsError;
+use crate::ir::graph::{reduction_levels, saturation_range, ArrayKind, FunctionalUnit, Graph, Operation, ValueId, DEFAULT_WIDTH};
+use crate::passes::binding::signed_multiply;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// HDL language revision written by `generate_verilog_module`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerilogDialect {
+    #[default]
+    Verilog2001,
+    /// `logic`, `always_ff`/`always_comb`, packed typedefs and SVA handshake assertions
+    ///
+    /// The Verilog-2001 module rewritten by `systemverilog::rewrite_as_systemverilog`,
+    /// so every emit option applies as it does to Verilog.
+    SystemVerilog,
+}
+
+/// Ports the generated kernel exposes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterfaceStyle {
+    /// Bare data ports with the `ap_start`/`ap_done`/`ap_idle`/`ap_ready` handshake
+    #[default]
+    ApCtrl,
+    /// Inputs and outputs packed into `s_axis`/`m_axis` beats; see `axi::generate_axi_stream_kernel`
+    AxiStream,
+}
+
+/// Flow control of a pipelined `ApCtrl` kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackpressureMode {
+    /// Results leave the pipeline every cycle, whether or not they are consumed
+    #[default]
+    None,
+    /// `m_axis_tvalid` marks results; while the consumer holds `m_axis_tready`
+    /// low, every stage register and valid bit keeps its value
+    ReadyValid,
+}
+
+/// Options controlling how the Verilog backend maps operations to primitives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerilogEmitOptions {
+    /// Instantiate a `DSP48E2` per multiply instead of relying on inference
+    pub explicit_dsp: bool,
+    /// Pipeline registers in each DSP48E2 (1-3): PREG, then AREG/BREG, then MREG
+    pub dsp_pipeline_regs: u32,
+    /// Language revision of the `ApCtrl` modules
+    pub dialect: VerilogDialect,
+    /// Kernel interface; AXI4-Stream kernels are always written as Verilog-2001
+    pub interface: InterfaceStyle,
+    /// Stall support of pipelined modules
+    pub backpressure: BackpressureMode,
+    /// Instantiate `perf::generate_perf_counters` in `ApCtrl`
+    /// modules and drive its counts out on `perf_*` ports
+    pub enable_perf_counters: bool,
+}
+
+impl Default for VerilogEmitOptions {
+    fn default() -> Self {
+        Self {
+            explicit_dsp: false,
+            dsp_pipeline_regs: 3,
+            dialect: VerilogDialect::Verilog2001,
+            interface: InterfaceStyle::ApCtrl,
+            backpressure: BackpressureMode::None,
+            enable_perf_counters: false,
+        }
+    }
+}
+
+impl VerilogEmitOptions {
+    /// Cycles from DSP48E2 inputs to product, for `PipelineScheduler::mul_latency`
+    pub fn mul_latency(&self) -> usize {
+        self.dsp_pipeline_regs.clamp(1, 3) as usize
+    }
+}
 
 /// Generate Xilinx-compatible Verilog module from IR graph
-pub fn generate_verilog_module(graph: &Graph, module_name: &str) -> String {
-    if graph.pipeline_config.enable && !graph.pipeline_stages.is_empty() {
-        generate_clean_pipelined_module(graph, module_name)
+///
+/// `None` uses `VerilogEmitOptions::default()`, which leaves multiplies to
+/// synthesis inference.
+pub fn generate_verilog_module(graph: &Graph, module_name: &str, options: Option<&VerilogEmitOptions>) -> Result<String, HlsError> {
+    graph.validate()?;
+    let options = options.copied().unwrap_or_default();
+
+    for array in graph.arrays.iter().filter(|array| array.kind == ArrayKind::Interface) {
+        let accesses = |store: bool| graph.nodes.iter().filter(|node| match &node.op {
+            Operation::ArrayLoad(name, _) => !store && *name == array.name,
+            Operation::ArrayStore(name, _, _) => store && *name == array.name,
+            _ => false,
+        }).count();
+        if accesses(false) > 1 || accesses(true) > 1 {
+            return Err(HlsError::Unsupported(format!("more than one read or write of interface array {}", array.name)));
+        }
+    }
+
+    let rolled = graph.loop_trip_count.is_some();
+    let stateful = !graph.state_registers.is_empty();
+    if stateful && (rolled || options.interface == InterfaceStyle::AxiStream ||
+                    options.backpressure != BackpressureMode::None) {
+        return Err(HlsError::Unsupported("state registers outside the ap_ctrl modules, or with a rolled loop".to_string()));
+    }
+    if rolled && (options.interface == InterfaceStyle::AxiStream || options.backpressure != BackpressureMode::None) {
+        return Err(HlsError::Unsupported("rolled loops outside the ap_ctrl FSM".to_string()));
+    }
+    if options.enable_perf_counters && (options.interface == InterfaceStyle::AxiStream) {
+        return Err(HlsError::Unsupported("performance counters outside the ap_ctrl modules".to_string()));
+    }
+    // A rolled loop reuses one copy of its body, run by the control FSM unless it is modulo-scheduled
+    let pipelined = graph.pipeline_config.enable && !graph.pipeline_stages.is_empty();
+    if rolled && pipelined {
+        check_loop_carries(graph, &options)?;
+    }
+    if graph.nodes.iter().any(|node| matches!(node.op, Operation::Call(_, _))) {
+        if !pipelined || rolled || options.interface != InterfaceStyle::ApCtrl ||
+           options.backpressure != BackpressureMode::None {
+            return Err(HlsError::Unsupported("calls outside pipelined ap_ctrl modules".to_string()));
+        }
+        let callees = generate_callee_modules(graph, module_name, &options)?;
+        return Ok(generate_clean_pipelined_module(graph, module_name, &options, StallControl::None) + &callees);
+    }
+    if options.interface == InterfaceStyle::AxiStream {
+        return generate_axi_stream_kernel(graph, module_name, &options);
+    }
+    if options.backpressure == BackpressureMode::ReadyValid {
+        if !pipelined || !graph.arrays.is_empty() {
+            return Err(HlsError::Unsupported(
+                "ready/valid backpressure outside pipelined modules without arrays".to_string(),
+            ));
+        }
+        return Ok(generate_clean_pipelined_module(graph, module_name, &options, StallControl::ReadyValid));
+    }
+    if pipelined {
+        Ok(generate_clean_pipelined_module(graph, module_name, &options, StallControl::None))
     } else {
-        generate_simple_module(graph, module_name)
+        Ok(generate_simple_module(graph, module_name, &options))
+    }
+}
+
+/// Width of a class of signals in a generated module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParameterValue {
+    /// A fixed number of bits, written out in every range
+    Literal(u32),
+    /// A module parameter of this name, so the width can be overridden at elaboration
+    Parameter(String),
+}
+
+/// Widths `generate_verilog_parameterized` gives signals the graph leaves unsized
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationParams {
+    pub data_width: ParameterValue,
+    pub addr_width: ParameterValue,
+}
+
+impl Default for GenerationParams {
+    /// The `DATA_WIDTH` and `ADDR_WIDTH` parameters `generate_verilog_module` declares
+    fn default() -> Self {
+        Self {
+            data_width: ParameterValue::Parameter("DATA_WIDTH".to_string()),
+            addr_width: ParameterValue::Parameter("ADDR_WIDTH".to_string()),
+        }
+    }
+}
+
+/// Generate a module whose unknown widths follow `params` instead of the DATA_WIDTH default
+///
+/// A `Literal` data width sizes the unsized leaves of the graph (its ports,
+/// constants, arrays and state registers) before emission, then infers the
+/// other unsized values from them in graph order, as if the widths had been
+/// given: ranges read `[15:0]`, and the sum of two such values `[16:0]`. A `Parameter`
+/// renames the module parameter unsized signals fall back to. Either way the
+/// parameter defaults in the header match.
+pub fn generate_verilog_parameterized(graph: &Graph, module_name: &str, options: Option<&VerilogEmitOptions>, params: &GenerationParams) -> Result<String, HlsError> {
+    let mut verilog = match params.data_width {
+        ParameterValue::Literal(width) => {
+            if width == 0 {
+                return Err(HlsError::Unsupported("a data width of zero bits".to_string()));
+            }
+            let mut sized = graph.clone();
+            for array in sized.arrays.iter_mut().filter(|array| array.width.is_none()) {
+                array.width = Some(width);
+            }
+            for register in sized.state_registers.iter_mut().filter(|register| register.width.is_none()) {
+                register.width = Some(width);
+            }
+            // Graph order is topological, so every operand is sized by the time it is read
+            for index in 0..sized.nodes.len() {
+                let node = &sized.nodes[index];
+                if node.output.is_some() && node.output_width.is_none() {
+                    let inferred = sized.infer_width(&node.op).unwrap_or(width);
+                    sized.nodes[index].output_width = Some(inferred);
+                }
+            }
+            let verilog = generate_verilog_module(&sized, module_name, options)?;
+            verilog.replacen("DATA_WIDTH = 32", &format!("DATA_WIDTH = {}", width), 1)
+        }
+        ParameterValue::Parameter(ref name) => rename_identifier(&generate_verilog_module(graph, module_name, options)?, "DATA_WIDTH", name),
+    };
+    verilog = match params.addr_width {
+        ParameterValue::Literal(width) => verilog.replacen("ADDR_WIDTH = 16", &format!("ADDR_WIDTH = {}", width), 1),
+        ParameterValue::Parameter(ref name) => rename_identifier(&verilog, "ADDR_WIDTH", name),
+    };
+    Ok(verilog)
+}
+
+/// Replace whole-word occurrences of the identifier `from` with `to`
+fn rename_identifier(verilog: &str, from: &str, to: &str) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut renamed = String::with_capacity(verilog.len());
+    let mut rest = verilog;
+    while let Some(at) = rest.find(from) {
+        let (before, after) = (&rest[..at], &rest[at + from.len()..]);
+        let bounded = !renamed.chars().chain(before.chars()).next_back().is_some_and(is_ident) &&
+            !after.chars().next().is_some_and(is_ident);
+        renamed.push_str(before);
+        renamed.push_str(if bounded { to } else { from });
+        rest = after;
+    }
+    renamed.push_str(rest);
+    renamed
+}
+
+/// How a pipelined module stalls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StallControl {
+    /// Every stage advances on every clock
+    None,
+    /// An `ap_ce` input, driven by the AXI4-Stream wrapper
+    ClockEnable,
+    /// `m_axis_tready`/`m_axis_tvalid` ports, see `BackpressureMode::ReadyValid`
+    ReadyValid,
+}
+
+impl StallControl {
+    /// Signal that is high while the pipeline may advance
+    fn enable(self) -> Option<&'static str> {
+        match self {
+            StallControl::None => None,
+            StallControl::ClockEnable => Some("ap_ce"),
+            StallControl::ReadyValid => Some("enable"),
+        }
     }
 }
 
 /// Generate a clean, logical pipelined Verilog module
-fn generate_clean_pipelined_module(graph: &Graph, module_name: &str) -> String {
+///
+/// Under a `stall` every stage register, valid bit and DSP48E2 holds its
+/// value while the enable signal is low. Array logic is not gated, so
+/// stallable modules must not use arrays.
+pub(crate) fn generate_clean_pipelined_module(graph: &Graph, module_name: &str, options: &VerilogEmitOptions, stall: StallControl) -> String {
     let mut verilog = String::new();
-    
-    // Analyze the graph to understand the computation pattern
-    let analysis = analyze_computation_pattern(graph);
+    let analysis = pipeline_analysis(graph, options, stall);
     
     // Generate header
-    verilog.push_str(&format!("// Generated for AMD Alveo U50 - PIPELINED VERSION (CLEAN)\n"));
+    verilog.push_str("// Generated for AMD Alveo U50 - PIPELINED VERSION (CLEAN)\n");
     verilog.push_str(&format!("// Pipeline: {}-stage {} implementation\n", 
                             analysis.logical_stages, analysis.description));
-    verilog.push_str(&format!("// synthesis translate_off\n"));
-    verilog.push_str(&format!("`timescale 1ns / 1ps\n"));
-    verilog.push_str(&format!("// synthesis translate_on\n\n"));
+    verilog.push_str("// synthesis translate_off\n");
+    verilog.push_str("`timescale 1ns / 1ps\n");
+    verilog.push_str("// synthesis translate_on\n\n");
     
     // Module header
-    verilog.push_str(&generate_module_header(graph, module_name));
+    let perf_stages = options.enable_perf_counters.then(|| valid_stages(graph, &analysis));
+    verilog.push_str(&generate_module_header(graph, module_name, stall, perf_stages));
+    if stall == StallControl::ReadyValid {
+        verilog.push_str("    // Backpressure: the pipeline only advances while the consumer is ready\n");
+        verilog.push_str("    wire enable = m_axis_tready;\n");
+        verilog.push_str("    assign m_axis_tvalid = ap_done;\n");
+        verilog.push_str("    \n");
+    }
+    if graph.loop_trip_count.is_none() {
+        generate_issue_control(&mut verilog, initiation_interval(graph), stall, !shared_units(graph, options).is_empty());
+    }
     
     // Generate pipeline based on computation pattern
     match analysis.pattern {
-        ComputationPattern::MAC => generate_mac_pipeline(&mut verilog, &analysis),
+        ComputationPattern::Mac => {
+            let mac = analysis.mac.as_ref().expect("MAC pattern without a MAC structure");
+            generate_mac_pipeline(&mut verilog, graph, mac, stall)
+        }
         ComputationPattern::SimpleArithmetic => generate_arithmetic_pipeline(&mut verilog, &analysis),
-        ComputationPattern::Complex => generate_generic_pipeline(&mut verilog, graph),
+        ComputationPattern::Complex => generate_generic_pipeline(&mut verilog, graph, options, stall),
     }
     
+    push_perf_counters(&mut verilog, module_name, perf_stages);
+    in_dialect(verilog, graph, options, false)
+}
+
+/// Stages `generate_verilog_module` gives the performance counters of `graph`, 0 for the control FSM
+pub(crate) fn perf_counter_stages(graph: &Graph, options: &VerilogEmitOptions) -> usize {
+    let pipelined = graph.pipeline_config.enable && !graph.pipeline_stages.is_empty();
+    if !pipelined {
+        return 0;
+    }
+    let options = VerilogEmitOptions { enable_perf_counters: true, ..*options };
+    valid_stages(graph, &pipeline_analysis(graph, &options, StallControl::None))
+}
+
+/// Bits of the `stage_valid` register the pipeline template declares
+fn valid_stages(graph: &Graph, analysis: &ComputationAnalysis) -> usize {
+    match analysis.pattern {
+        ComputationPattern::Mac => scheduled_depth(graph).max(MAC_STAGES),
+        _ => analysis.logical_stages,
+    }
+}
+
+/// Close the module, instantiating and appending its performance counters when `perf_stages` is set
+fn push_perf_counters(verilog: &mut String, module_name: &str, perf_stages: Option<usize>) {
+    if let Some(stages) = perf_stages {
+        verilog.push_str(&perf_counter_instance(module_name, stages));
+    }
     verilog.push_str("\nendmodule\n");
-    verilog
+    if let Some(stages) = perf_stages {
+        verilog.push('\n');
+        verilog.push_str(&generate_perf_counters(module_name, stages));
+    }
+}
+
+/// `verilog` written in the dialect of `options`; `fsm` marks a module run by the control state machine
+fn in_dialect(verilog: String, graph: &Graph, options: &VerilogEmitOptions, fsm: bool) -> String {
+    match options.dialect {
+        VerilogDialect::Verilog2001 => verilog,
+        VerilogDialect::SystemVerilog => rewrite_as_systemverilog(&verilog, &collect_output_ports(graph), fsm),
+    }
+}
+
+/// Template `generate_clean_pipelined_module` builds a scheduled graph from
+fn pipeline_analysis(graph: &Graph, options: &VerilogEmitOptions, stall: StallControl) -> ComputationAnalysis {
+    // Analyze the graph to understand the computation pattern
+    let mut analysis = analyze_computation_pattern(graph);
+    if options.explicit_dsp {
+        // The MAC and arithmetic templates infer their multipliers; build from the graph instead
+        analysis.pattern = ComputationPattern::Complex;
+        analysis.description = "complex logic".to_string();
+    }
+    // The arithmetic template has no valid bits for the counters to watch either
+    let stalls = stall != StallControl::None || initiation_interval(graph) > 1 || options.enable_perf_counters;
+    let depth_differs = scheduled_depth(graph) != analysis.logical_stages;
+    if (stalls || depth_differs) && matches!(analysis.pattern, ComputationPattern::SimpleArithmetic) {
+        // The arithmetic template has no stall or II support and a fixed depth; the generic pipeline covers it
+        analysis.pattern = ComputationPattern::Complex;
+        analysis.description = "complex logic".to_string();
+    }
+    if matches!(analysis.pattern, ComputationPattern::Mac) && (1..MAC_STAGES).contains(&scheduled_depth(graph)) {
+        // Schedules shorter than the MAC template are built as scheduled
+        analysis.pattern = ComputationPattern::Complex;
+        analysis.description = "complex logic".to_string();
+    }
+    if !shared_units(graph, options).is_empty() {
+        // Only the generic pipeline emits shared multipliers
+        analysis.pattern = ComputationPattern::Complex;
+        analysis.description = "complex logic".to_string();
+    }
+    if graph.loop_trip_count.is_some() {
+        // Only the generic pipeline overlaps the iterations of a rolled loop
+        analysis.pattern = ComputationPattern::Complex;
+        analysis.description = "modulo-scheduled loop".to_string();
+    }
+    if let ComputationPattern::Complex = analysis.pattern {
+        analysis.logical_stages = StagePlan::new(graph, options).depth;
+    }
+    analysis
+}
+
+/// A data register between two stages of the pipelined module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StageRegister {
+    pub(crate) name: String,
+    pub(crate) width: Option<u32>,
+    /// Stage whose results it latches, so it holds those of the transaction issued `stage + 1` cycles ago
+    pub(crate) stage: usize,
+    /// Values whose sum it holds; a single value for all but the MAC template's sum of products
+    pub(crate) terms: Vec<ValueId>,
+    /// Cleared by `ap_rst_n` rather than undefined until first written
+    pub(crate) reset: bool,
+}
+
+/// Stage registers `generate_verilog_module` declares for a scheduled graph, by default options
+///
+/// The MAC template registers `<port>_reg0`, `prod<i>_reg1`, `products_reg2`,
+/// `result_reg3` and `<output>_reg<k>`; the generic pipeline registers every
+/// value a later stage reads as `<signal>_r<stage>`.
+pub(crate) fn stage_registers(graph: &Graph) -> Vec<StageRegister> {
+    let options = VerilogEmitOptions::default();
+    let analysis = pipeline_analysis(graph, &options, StallControl::None);
+    let register = |name: String, width: Option<u32>, stage: usize, terms: Vec<ValueId>, reset: bool| {
+        StageRegister { name, width, stage, terms, reset }
+    };
+    match analysis.pattern {
+        ComputationPattern::Mac => {
+            let mac = analysis.mac.as_ref().expect("MAC pattern without a MAC structure");
+            let port_value = |name: &str| graph.nodes.iter()
+                .find(|node| matches!(&node.op, Operation::Load(load) if load == name))
+                .and_then(|node| node.output)
+                .expect("input ports come from loads");
+            let inputs = collect_input_ports(graph);
+            let addend_ports: Vec<&Port> = inputs.iter()
+                .filter(|port| mac.addends.iter().any(|value_id| get_value_reference(*value_id, graph) == port.name))
+                .collect();
+
+            let mut registers: Vec<StageRegister> = inputs.iter()
+                .map(|port| register(format!("{}_reg0", port.name), port.width, 0, vec![port_value(&port.name)], true))
+                .collect();
+            for (i, (&value_id, (_, _, width))) in mac.product_values.iter().zip(&mac.products).enumerate() {
+                registers.push(register(format!("prod{}_reg1", i), *width, 1, vec![value_id], true));
+            }
+            for stage in 1..=2 {
+                registers.extend(addend_ports.iter().map(|port| {
+                    register(format!("{}_reg{}", port.name, stage), port.width, stage, vec![port_value(&port.name)], true)
+                }));
+            }
+            registers.push(register("products_reg2".to_string(), mac.width, 2, mac.product_values.clone(), true));
+            registers.push(register("result_reg3".to_string(), mac.width, 3, vec![mac.root], true));
+            for stage in 4..scheduled_depth(graph).max(MAC_STAGES) {
+                registers.push(register(format!("{}_reg{}", mac.output.name, stage), mac.output.width, stage, vec![mac.root], true));
+            }
+            registers
+        }
+        // The fixed arithmetic template holds no data registers
+        ComputationPattern::SimpleArithmetic => Vec::new(),
+        ComputationPattern::Complex => {
+            let plan = StagePlan::new(graph, &options);
+            plan.lifetimes.iter()
+                .flat_map(|(&value_id, &(ready, last_use))| (ready..last_use).map(move |stage| (value_id, stage)))
+                .map(|(value_id, stage)| {
+                    register(format!("{}_r{}", plan.base(value_id, graph), stage), graph.value_width(value_id), stage, vec![value_id], false)
+                })
+                .collect()
+        }
+    }
 }
 
 /// Analyze the computation to determine the optimal pipeline structure
 fn analyze_computation_pattern(graph: &Graph) -> ComputationAnalysis {
-    let mut mul_count = 0;
-    let mut add_count = 0;
     let mut complex_ops = 0;
-    let mut inputs = Vec::new();
-    let mut outputs = Vec::new();
     
-    for (_node_id, node) in graph.nodes.iter().enumerate() {
+    for node in &graph.nodes {
         match &node.op {
-            Operation::Mul(_, _) => mul_count += 1,
-            Operation::Add(_, _) => add_count += 1,
-            Operation::Load(name) => inputs.push(name.clone()),
-            Operation::Store(name, _) => outputs.push(name.clone()),
             // Count complex operations that require custom logic
             Operation::CmpLt(_, _) | Operation::CmpGt(_, _) | Operation::CmpEq(_, _) | 
             Operation::CmpGe(_, _) | Operation::CmpLe(_, _) | Operation::CmpNe(_, _) |
             Operation::And(_, _) | Operation::Or(_, _) | Operation::Not(_) |
             Operation::Mux(_, _, _) | Operation::Abs(_) | Operation::Min(_, _) | 
             Operation::Max(_, _) | Operation::Shl(_, _) | Operation::Shr(_, _) | 
-            Operation::Xor(_, _) => complex_ops += 1,
+            Operation::Xor(_, _) | Operation::Nand(_, _) | Operation::Nor(_, _) |
+            Operation::SAdd(_, _) | Operation::SSub(_, _) | Operation::SMul(_, _) |
+            Operation::AddSat(_, _, _) | Operation::SubSat(_, _, _) |
+            Operation::Xnor(_, _) | Operation::Concat(_, _) | Operation::Slice(_, _, _) |
+            Operation::Fma(_, _, _) | Operation::ReduceAdd(_) | Operation::ReduceMax(_) | Operation::ReduceMin(_) |
+            Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) |
+            Operation::RegisterLoad(_) | Operation::RegisterStore(_, _) |
+            Operation::Call(_, _) | Operation::CallResult(_, _) => complex_ops += 1,
             _ => {}
         }
     }
     
-    // Determine pattern - if we have complex operations, use Complex pattern
-    let pattern = if complex_ops > 0 {
+    // Sums of products get the MAC template; other complex operations the generic pipeline
+    let mac = mac_structure(graph);
+    let pattern = if mac.is_some() {
+        ComputationPattern::Mac
+    } else if complex_ops > 0 {
         ComputationPattern::Complex
-    } else if mul_count >= 2 && add_count >= 2 {
-        ComputationPattern::MAC
     } else {
         ComputationPattern::SimpleArithmetic
     };
     
     let (logical_stages, description) = match pattern {
-        ComputationPattern::MAC => (5, "MAC"),
+        ComputationPattern::Mac => (scheduled_depth(graph).max(MAC_STAGES), "MAC"),
         ComputationPattern::SimpleArithmetic => (3, "arithmetic"),
+        // Replaced by the scheduled depth once the stages are legalized
         ComputationPattern::Complex => (3, "complex logic"),
     };
     
@@ -87,179 +487,346 @@ fn analyze_computation_pattern(graph: &Graph) -> ComputationAnalysis {
         pattern,
         logical_stages,
         description: description.to_string(),
-        inputs,
-        outputs,
+        mac,
+    }
+}
+
+/// Stages of the MAC template before any delay to match the schedule
+const MAC_STAGES: usize = 5;
+
+/// Cycles the schedule claims from `ap_start` to `ap_done`; 0 for unscheduled graphs
+pub(crate) fn scheduled_depth(graph: &Graph) -> usize {
+    graph.pipeline_stages.iter().map(|stage| stage.cycle + 1).max().unwrap_or(0)
+}
+
+/// Sum-of-products shape of a MAC graph: `output = Σ x_i * y_i + Σ addends`
+#[derive(Debug)]
+struct MacStructure {
+    /// Operand pairs of each multiply, with the product's width
+    products: Vec<(ValueId, ValueId, Option<u32>)>,
+    /// Results of those multiplies, in the same order
+    product_values: Vec<ValueId>,
+    /// Values added to the sum of products
+    addends: Vec<ValueId>,
+    /// The final sum, and its width
+    root: ValueId,
+    width: Option<u32>,
+    output: Port,
+}
+
+/// Match a graph whose single output is an adder tree over 1-4 products of ports or constants
+///
+/// Anything else, including extra logic next to the adder tree, returns `None`
+/// and is built by the generic pipeline instead.
+fn mac_structure(graph: &Graph) -> Option<MacStructure> {
+    let outputs = collect_output_ports(graph);
+    let [output] = outputs.as_slice() else { return None };
+    let root = graph.nodes.iter().find_map(|node| match &node.op {
+        Operation::Store(_, value_id) => Some(*value_id),
+        _ => None,
+    })?;
+    if !matches!(graph.producer(root)?.op, Operation::Add(_, _)) {
+        return None;
+    }
+
+    let is_leaf = |value_id: ValueId| {
+        matches!(graph.producer(resolve(value_id, graph)).map(|node| &node.op), Some(Operation::Load(_) | Operation::Const(_)))
+    };
+    let mut products = Vec::new();
+    let mut product_values = Vec::new();
+    let mut addends = Vec::new();
+    let mut covered = 1; // the store
+    let mut pending = vec![root];
+    while let Some(value_id) = pending.pop() {
+        let value_id = resolve(value_id, graph);
+        let node = graph.producer(value_id)?;
+        match node.op {
+            Operation::Add(a_id, b_id) => {
+                covered += 1;
+                // Visit the left operand first so terms keep their source order
+                pending.push(b_id);
+                pending.push(a_id);
+            }
+            Operation::Mul(a_id, b_id) if is_leaf(a_id) && is_leaf(b_id) => {
+                covered += 1;
+                products.push((resolve(a_id, graph), resolve(b_id, graph), node.output_width));
+                product_values.push(value_id);
+            }
+            _ if is_leaf(value_id) => addends.push(value_id),
+            _ => return None,
+        }
+    }
+
+    // Scheduler bookkeeping nodes carry no logic of their own
+    let logic = graph.nodes.iter().filter(|node| !matches!(
+        node.op,
+        Operation::Load(_) | Operation::Const(_) | Operation::PipelineRegister(_) | Operation::PipelineBarrier | Operation::Nop
+    )).count();
+    if products.is_empty() || products.len() > 4 || logic != covered {
+        return None;
     }
+    let root = resolve(root, graph);
+    Some(MacStructure { products, product_values, addends, root, width: graph.value_width(root), output: output.clone() })
 }
 
-/// Generate MAC-specific pipeline (like our fixed version)
-fn generate_mac_pipeline(verilog: &mut String, analysis: &ComputationAnalysis) {
+/// Five-stage MAC: register inputs, multiply, sum the products, add the addends, register the output
+///
+/// A schedule longer than five cycles delays the output by as many extra registers.
+fn generate_mac_pipeline(verilog: &mut String, graph: &Graph, mac: &MacStructure, stall: StallControl) {
+    let depth = scheduled_depth(graph).max(MAC_STAGES);
+    let ce = stall.enable().map_or(String::new(), |enable| format!("{} & ", enable));
+    let start = issue_signal(graph);
+    let enable = |stage: usize| if stage == 0 { format!("{}{}", ce, start) } else { format!("{}stage_valid[{}]", ce, stage - 1) };
+    let inputs = collect_input_ports(graph);
+    let addend_ports: Vec<&Port> = inputs.iter()
+        .filter(|port| mac.addends.iter().any(|value_id| get_value_reference(*value_id, graph) == port.name))
+        .collect();
+    // Ports are registered in stage 0, constants are used as literals
+    let operand = |value_id: ValueId, stage: usize| match graph.producer(value_id).map(|node| &node.op) {
+        Some(Operation::Load(name)) => format!("{}_reg{}", name, stage),
+        _ => get_value_reference(value_id, graph),
+    };
+
     verilog.push_str("    // Pipeline control signals\n");
-    verilog.push_str(&format!("    reg [{}:0] pipeline_valid;  // {}-stage pipeline\n", 
-                             analysis.logical_stages - 1, analysis.logical_stages));
-    verilog.push_str("    reg [3:0] pipeline_counter;\n");
+    verilog.push_str(&format!("    reg [{}:0] stage_valid;  // {}-stage pipeline\n", depth - 1, depth));
     verilog.push_str("    \n");
-    
-    // Generate meaningful register names for MAC pipeline
+
     verilog.push_str("    // Pipeline registers for Stage 0 (Input Registration)\n");
-    for (_i, input) in analysis.inputs.iter().enumerate() {
-        verilog.push_str(&format!("    reg [DATA_WIDTH-1:0] {}_reg0;\n", input));
+    for port in &inputs {
+        verilog.push_str(&format!("    reg {} {}_reg0;\n", port.decl(), port.name));
     }
     verilog.push_str("    \n");
-    
+
     verilog.push_str("    // Pipeline registers for Stage 1 (Multiplication)\n");
-    verilog.push_str("    reg [DATA_WIDTH-1:0] mult_ab_reg1, mult_cd_reg1;\n");
-    for input in &analysis.inputs[4..] { // Pass-through registers
-        verilog.push_str(&format!("    reg [DATA_WIDTH-1:0] {}_reg1;\n", input));
+    for (i, (_, _, width)) in mac.products.iter().enumerate() {
+        verilog.push_str(&format!("    reg {} prod{}_reg1;\n", width_range(*width), i));
     }
-    verilog.push_str("    \n");
-    
-    verilog.push_str("    // Pipeline registers for Stage 2 (First Addition)\n");
-    verilog.push_str("    reg [DATA_WIDTH-1:0] add_mult_reg2;\n");
-    for input in &analysis.inputs[4..] { // Pass-through registers
-        verilog.push_str(&format!("    reg [DATA_WIDTH-1:0] {}_reg2;\n", input));
+    for port in &addend_ports { // Pass-through registers
+        verilog.push_str(&format!("    reg {} {}_reg1;\n", port.decl(), port.name));
     }
     verilog.push_str("    \n");
-    
-    verilog.push_str("    // Pipeline registers for Stage 3 (Final Addition)\n");
-    verilog.push_str("    reg [DATA_WIDTH-1:0] result_reg3;\n");
+
+    verilog.push_str("    // Pipeline registers for Stage 2 (Sum of Products)\n");
+    verilog.push_str(&format!("    reg {} products_reg2;\n", width_range(mac.width)));
+    for port in &addend_ports {
+        verilog.push_str(&format!("    reg {} {}_reg2;\n", port.decl(), port.name));
+    }
     verilog.push_str("    \n");
-    
-    // Control logic
-    verilog.push_str("    // Control logic\n");
-    verilog.push_str(&format!("    assign ap_idle = (pipeline_counter == 0);\n"));
-    verilog.push_str(&format!("    assign ap_ready = (pipeline_counter < {});  // Can accept new input when not full\n", 
-                             analysis.logical_stages));
+
+    verilog.push_str("    // Pipeline registers for Stages 3-4 (Accumulation and Output)\n");
+    verilog.push_str(&format!("    reg {} result_reg3;\n", width_range(mac.width)));
+    for index in 4..depth {
+        verilog.push_str(&format!("    reg {} {}_reg{};\n", mac.output.decl(), mac.output.name, index));
+    }
     verilog.push_str("    \n");
-    
-    // Pipeline control
-    generate_pipeline_control(verilog, analysis.logical_stages);
-    
-    // Generate pipeline stages
-    generate_mac_stage_0(verilog, &analysis.inputs);
-    generate_mac_stage_1(verilog, &analysis.inputs);
-    generate_mac_stage_2(verilog, &analysis.inputs);
-    generate_mac_stage_3(verilog);
-    generate_mac_stage_4(verilog, &analysis.outputs);
-}
 
-/// Generate pipeline control logic
-fn generate_pipeline_control(verilog: &mut String, stages: usize) {
-    verilog.push_str("    // Pipeline control logic\n");
-    verilog.push_str("    always @(posedge ap_clk) begin\n");
-    verilog.push_str("        if (!ap_rst_n) begin\n");
-    verilog.push_str(&format!("            pipeline_valid <= {}'b{};\n", stages, "0".repeat(stages)));
-    verilog.push_str("            pipeline_counter <= 4'b0000;\n");
-    verilog.push_str("            ap_done <= 1'b0;\n");
-    verilog.push_str("        end else begin\n");
-    verilog.push_str("            // Shift pipeline valid bits\n");
-    verilog.push_str(&format!("            pipeline_valid <= {{pipeline_valid[{}:0], ap_start && ap_ready}};\n", 
-                             stages - 2));
-    verilog.push_str("            \n");
-    verilog.push_str("            // Update counter\n");
-    verilog.push_str("            if (ap_start && ap_ready) begin\n");
-    verilog.push_str(&format!("                if (pipeline_counter < {}) begin\n", stages));
-    verilog.push_str("                    pipeline_counter <= pipeline_counter + 1;\n");
-    verilog.push_str("                end\n");
-    verilog.push_str(&format!("            end else if (pipeline_counter > 0 && pipeline_valid[{}]) begin\n", 
-                             stages - 1));
-    verilog.push_str("                pipeline_counter <= pipeline_counter - 1;\n");
-    verilog.push_str("            end\n");
-    verilog.push_str("            \n");
-    verilog.push_str("            // Output done signal when result emerges from pipeline\n");
-    verilog.push_str(&format!("            ap_done <= pipeline_valid[{}];\n", stages - 1));
-    verilog.push_str("        end\n");
-    verilog.push_str("    end\n");
+    let mut stage = |comment: &str, registers: Vec<(String, String, String)>, index: usize| {
+        verilog.push_str(&format!("    // Pipeline Stage {}: {}\n", index, comment));
+        verilog.push_str("    always @(posedge ap_clk) begin\n");
+        verilog.push_str("        if (!ap_rst_n) begin\n");
+        for (target, _, zero) in &registers {
+            verilog.push_str(&format!("            {} <= {};\n", target, zero));
+        }
+        verilog.push_str(&format!("        end else if ({}) begin\n", enable(index)));
+        for (target, value, _) in &registers {
+            verilog.push_str(&format!("            {} <= {};\n", target, value));
+        }
+        verilog.push_str("        end\n");
+        verilog.push_str("    end\n");
+        verilog.push_str("    \n");
+    };
+
+    stage("Input Registration", inputs.iter().map(|port| {
+        (format!("{}_reg0", port.name), port.name.clone(), zero_literal(port.width))
+    }).collect(), 0);
+
+    let mut multiplies: Vec<_> = mac.products.iter().enumerate().map(|(i, (a_id, b_id, width))| {
+        (format!("prod{}_reg1", i), format!("{} * {}", operand(*a_id, 0), operand(*b_id, 0)), zero_literal(*width))
+    }).collect();
+    multiplies.extend(addend_ports.iter().map(|port| (format!("{}_reg1", port.name), format!("{}_reg0", port.name), zero_literal(port.width))));
+    stage("Parallel Multiplications (DSP48E2 inferred)", multiplies, 1);
+
+    let product_sum: Vec<String> = (0..mac.products.len()).map(|i| format!("prod{}_reg1", i)).collect();
+    let mut sums = vec![("products_reg2".to_string(), product_sum.join(" + "), zero_literal(mac.width))];
+    sums.extend(addend_ports.iter().map(|port| (format!("{}_reg2", port.name), format!("{}_reg1", port.name), zero_literal(port.width))));
+    stage("Sum of Products", sums, 2);
+
+    let mut accumulate = vec!["products_reg2".to_string()];
+    accumulate.extend(mac.addends.iter().map(|value_id| operand(*value_id, 2)));
+    stage("Accumulation", vec![("result_reg3".to_string(), accumulate.join(" + "), zero_literal(mac.width))], 3);
+
+    stage("Output Register", vec![(format!("{}_reg4", mac.output.name), "result_reg3".to_string(), zero_literal(mac.output.width))], 4);
+    for index in MAC_STAGES..depth {
+        let register = (format!("{}_reg{}", mac.output.name, index), format!("{}_reg{}", mac.output.name, index - 1), zero_literal(mac.output.width));
+        stage("Output Delay, to the scheduled latency", vec![register], index);
+    }
+
+    verilog.push_str(&format!("    assign {0} = {0}_reg{1};\n", mac.output.name, depth - 1));
     verilog.push_str("    \n");
+    generate_valid_chain(verilog, depth, initiation_interval(graph), stall);
 }
 
-/// Generate MAC Stage 0: Input Registration
-fn generate_mac_stage_0(verilog: &mut String, inputs: &[String]) {
-    verilog.push_str("    // Pipeline Stage 0: Input Registration\n");
+/// `stage_valid` shift register fed by `ap_start`, with `ap_done` raised as results leave the last stage
+///
+/// Under a `stall` the valid bits, like the data, only move while the enable
+/// is high. With an II above 1 only the starts `generate_issue_control`
+/// accepts enter the pipeline.
+fn generate_valid_chain(verilog: &mut String, depth: usize, ii: usize, stall: StallControl) {
+    let start = if ii > 1 { "ap_accept" } else { "ap_start" };
+    verilog.push_str("    // Valid bits travel alongside the data\n");
     verilog.push_str("    always @(posedge ap_clk) begin\n");
     verilog.push_str("        if (!ap_rst_n) begin\n");
-    for input in inputs {
-        verilog.push_str(&format!("            {}_reg0 <= {{DATA_WIDTH{{1'b0}}}};\n", input));
+    verilog.push_str(&format!("            stage_valid <= {}'b0;\n", depth));
+    verilog.push_str("            ap_done <= 1'b0;\n");
+    match stall.enable() {
+        Some(enable) => verilog.push_str(&format!("        end else if ({}) begin\n", enable)),
+        None => verilog.push_str("        end else begin\n"),
     }
-    verilog.push_str("        end else if (pipeline_valid[0]) begin\n");
-    for input in inputs {
-        verilog.push_str(&format!("            {}_reg0 <= {};\n", input, input));
+    if depth == 1 {
+        verilog.push_str(&format!("            stage_valid <= {};\n", start));
+        verilog.push_str(&format!("            ap_done <= {};\n", start));
+    } else {
+        verilog.push_str(&format!("            stage_valid <= {{stage_valid[{}:0], {}}};\n", depth - 2, start));
+        verilog.push_str(&format!("            ap_done <= stage_valid[{}];\n", depth - 2));
     }
     verilog.push_str("        end\n");
     verilog.push_str("    end\n");
-    verilog.push_str("    \n");
+    verilog.push('\n');
+    verilog.push_str("    assign ap_idle = ~|stage_valid;\n");
+    match (stall == StallControl::ReadyValid, ii > 1) {
+        (true, true) => verilog.push_str("    assign ap_ready = enable & (ii_count == 0);\n"),
+        (true, false) => verilog.push_str("    assign ap_ready = enable;  // Inputs are only accepted while the pipeline moves\n"),
+        (false, true) => verilog.push_str(&format!("    assign ap_ready = (ii_count == 0);  // A new input every {} cycles\n", ii)),
+        (false, false) => verilog.push_str("    assign ap_ready = 1'b1;  // A new input is accepted every cycle\n"),
+    }
 }
 
-/// Generate MAC Stage 1: Parallel Multiplications
-fn generate_mac_stage_1(verilog: &mut String, inputs: &[String]) {
-    verilog.push_str("    // Pipeline Stage 1: Parallel Multiplications (DSP48E2 optimized for AU50)\n");
-    verilog.push_str("    always @(posedge ap_clk) begin\n");
-    verilog.push_str("        if (!ap_rst_n) begin\n");
-    verilog.push_str("            mult_ab_reg1 <= {DATA_WIDTH{1'b0}};\n");
-    verilog.push_str("            mult_cd_reg1 <= {DATA_WIDTH{1'b0}};\n");
-    for input in &inputs[4..] {
-        verilog.push_str(&format!("            {}_reg1 <= {{DATA_WIDTH{{1'b0}}}};\n", input));
-    }
-    verilog.push_str("        end else if (pipeline_valid[1]) begin\n");
-    verilog.push_str("            // Force DSP48E2 usage for AU50 optimization\n");
-    verilog.push_str("            (* USE_DSP = \"yes\", DSP_A_INPUT = \"DIRECT\", DSP_B_INPUT = \"DIRECT\" *) \n");
-    verilog.push_str(&format!("            mult_ab_reg1 <= {}_reg0 * {}_reg0;\n", inputs[0], inputs[1]));
-    verilog.push_str("            (* USE_DSP = \"yes\", DSP_A_INPUT = \"DIRECT\", DSP_B_INPUT = \"DIRECT\" *) \n");
-    verilog.push_str(&format!("            mult_cd_reg1 <= {}_reg0 * {}_reg0;\n", inputs[2], inputs[3]));
-    for input in &inputs[4..] {
-        verilog.push_str(&format!("            {}_reg1 <= {}_reg0;  // Pass through\n", input, input));
+/// Requested initiation interval of a graph, at least 1
+fn initiation_interval(graph: &Graph) -> usize {
+    graph.pipeline_config.initiation_interval.max(1)
+}
+
+/// Signal that starts an iteration: `ap_start`, `ap_accept` when the II limits the starts,
+/// or the loop controller's `loop_issue` in a rolled loop
+fn issue_signal(graph: &Graph) -> &'static str {
+    if graph.loop_trip_count.is_some() {
+        "loop_issue"
+    } else if initiation_interval(graph) > 1 {
+        "ap_accept"
+    } else {
+        "ap_start"
     }
-    verilog.push_str("        end\n");
-    verilog.push_str("    end\n");
-    verilog.push_str("    \n");
 }
 
-/// Generate MAC Stage 2: First Addition
-fn generate_mac_stage_2(verilog: &mut String, inputs: &[String]) {
-    verilog.push_str("    // Pipeline Stage 2: First Addition (mult_ab + mult_cd)\n");
+/// `ii_count`, counting down the cycles until the next start may be accepted
+///
+/// Nothing is emitted at II=1. Stalls freeze the count along with the stages.
+/// When `aligned`, the count instead runs up through every slot of the II
+/// and starts are only accepted in slot 0, so stage `s` of every iteration
+/// runs while `ii_count` is `s mod II`, as shared units select on.
+fn generate_issue_control(verilog: &mut String, ii: usize, stall: StallControl, aligned: bool) {
+    if ii <= 1 {
+        return;
+    }
+    let width = ii_count_width(ii);
+    if aligned {
+        verilog.push_str(&format!("    // II={}: iterations start in slot 0 of every {} cycles\n", ii, ii));
+    } else {
+        verilog.push_str(&format!("    // II={}: a new iteration starts at most every {} cycles\n", ii, ii));
+    }
+    verilog.push_str(&format!("    reg [{}:0] ii_count;\n", width - 1));
+    verilog.push_str("    wire ap_accept = ap_start & (ii_count == 0);\n");
     verilog.push_str("    always @(posedge ap_clk) begin\n");
     verilog.push_str("        if (!ap_rst_n) begin\n");
-    verilog.push_str("            add_mult_reg2 <= {DATA_WIDTH{1'b0}};\n");
-    for input in &inputs[4..] {
-        verilog.push_str(&format!("            {}_reg2 <= {{DATA_WIDTH{{1'b0}}}};\n", input));
+    verilog.push_str(&format!("            ii_count <= {}'d0;\n", width));
+    match stall.enable() {
+        Some(enable) => verilog.push_str(&format!("        end else if ({}) begin\n", enable)),
+        None => verilog.push_str("        end else begin\n"),
     }
-    verilog.push_str("        end else if (pipeline_valid[2]) begin\n");
-    verilog.push_str("            add_mult_reg2 <= mult_ab_reg1 + mult_cd_reg1;\n");
-    for input in &inputs[4..] {
-        verilog.push_str(&format!("            {}_reg2 <= {}_reg1;  // Pass through\n", input, input));
+    if aligned {
+        verilog.push_str(&format!("            if (ii_count == {}'d{})\n", width, ii - 1));
+        verilog.push_str(&format!("                ii_count <= {}'d0;\n", width));
+        verilog.push_str("            else\n");
+        verilog.push_str(&format!("                ii_count <= ii_count + {}'d1;\n", width));
+    } else {
+        verilog.push_str("            if (ap_accept)\n");
+        verilog.push_str(&format!("                ii_count <= {}'d{};\n", width, ii - 1));
+        verilog.push_str("            else if (ii_count != 0)\n");
+        verilog.push_str(&format!("                ii_count <= ii_count - {}'d1;\n", width));
     }
     verilog.push_str("        end\n");
     verilog.push_str("    end\n");
     verilog.push_str("    \n");
 }
 
-/// Generate MAC Stage 3: Final Addition
-fn generate_mac_stage_3(verilog: &mut String) {
-    verilog.push_str("    // Pipeline Stage 3: Final Addition (result = (a*b + c*d) + e)\n");
-    verilog.push_str("    always @(posedge ap_clk) begin\n");
-    verilog.push_str("        if (!ap_rst_n) begin\n");
-    verilog.push_str("            result_reg3 <= {DATA_WIDTH{1'b0}};\n");
-    verilog.push_str("        end else if (pipeline_valid[3]) begin\n");
-    verilog.push_str("            result_reg3 <= add_mult_reg2 + e_reg2;\n");
-    verilog.push_str("        end\n");
-    verilog.push_str("    end\n");
-    verilog.push_str("    \n");
+/// Bits of `ii_count` at `ii`
+fn ii_count_width(ii: usize) -> u32 {
+    (usize::BITS - (ii - 1).leading_zeros()).max(1)
 }
 
-/// Generate MAC Stage 4: Output Assignment
-fn generate_mac_stage_4(verilog: &mut String, outputs: &[String]) {
-    verilog.push_str("    // Pipeline Stage 4: Output Assignment\n");
-    verilog.push_str("    always @(posedge ap_clk) begin\n");
-    verilog.push_str("        if (!ap_rst_n) begin\n");
-    for output in outputs {
-        verilog.push_str(&format!("            {} <= {{DATA_WIDTH{{1'b0}}}};\n", output));
+/// Functional units of `graph` that time-multiplex more than one multiply
+///
+/// Explicit DSP48E2 instances are never shared, and at II=1 no unit can be.
+fn shared_units<'g>(graph: &'g Graph, options: &VerilogEmitOptions) -> Vec<&'g FunctionalUnit> {
+    if options.explicit_dsp || initiation_interval(graph) <= 1 {
+        return Vec::new();
+    }
+    graph.functional_units.iter().filter(|unit| unit.is_shared()).collect()
+}
+
+/// One multiplier for the multiplies of `unit`, fed through input muxes on `ii_count`
+///
+/// Each multiply reads the unit's product in its own stage, which only
+/// ever runs in its slot. Returns `None` if two of them compute in the same
+/// slot after all, leaving them a multiplier each.
+fn shared_multiplier(index: usize, unit: &FunctionalUnit, graph: &Graph, plan: &StagePlan) -> Option<String> {
+    let ii = initiation_interval(graph);
+    let mut operations = Vec::new();
+    for node_id in &unit.operations {
+        let position = graph.nodes.iter().position(|node| node.id == *node_id)?;
+        let (Operation::Mul(a_id, b_id) | Operation::SMul(a_id, b_id)) = graph.nodes[position].op else { return None };
+        let stage = plan.stages[position]?;
+        if operations.iter().any(|&(_, other, _, _)| other % ii == stage % ii) {
+            return None;
+        }
+        operations.push((position, stage, a_id, b_id));
+    }
+    let signed = signed_multiply(graph, &graph.nodes[operations[0].0].op);
+    let width = |value_id: ValueId| graph.value_width(value_id).unwrap_or(DEFAULT_WIDTH);
+    let a_width = operations.iter().map(|&(_, _, a_id, _)| width(a_id)).max()?;
+    let b_width = operations.iter().map(|&(_, _, _, b_id)| width(b_id)).max()?;
+    let operand = |value_id: ValueId, stage: usize| {
+        let reference = plan.reference(value_id, stage, graph);
+        if signed { format!("$signed({})", reference) } else { reference }
+    };
+
+    let name = format!("mul_unit{}", index);
+    let count_width = ii_count_width(ii);
+    let mut verilog = format!(
+        "    // Multiplier unit {}: {} multiplies on one multiplier, through a {}:1 input mux on ii_count\n",
+        index, operations.len(), operations.len()
+    );
+    verilog.push_str(&format!("    reg {} {}_a;\n", signal_type(Some(a_width), signed), name));
+    verilog.push_str(&format!("    reg {} {}_b;\n", signal_type(Some(b_width), signed), name));
+    verilog.push_str(&format!("    wire {} {}_p = {}_a * {}_b;\n", signal_type(Some(a_width + b_width), signed), name, name, name));
+    verilog.push_str("    always @(*) begin\n");
+    verilog.push_str("        case (ii_count)\n");
+    for &(position, stage, a_id, b_id) in &operations {
+        verilog.push_str(&format!(
+            "            {}'d{}: begin {}_a = {}; {}_b = {}; end  // node_{}, stage {}\n",
+            count_width, stage % ii, name, operand(a_id, stage), name, operand(b_id, stage), position, stage
+        ));
     }
-    verilog.push_str("        end else if (pipeline_valid[4]) begin\n");
-    for output in outputs {
-        verilog.push_str(&format!("            {} <= result_reg3;\n", output));
+    if operations.len() < ii {
+        verilog.push_str(&format!(
+            "            default: begin {}_a = {}; {}_b = {}; end\n",
+            name, zero_literal(Some(a_width)), name, zero_literal(Some(b_width))
+        ));
     }
-    verilog.push_str("        end\n");
+    verilog.push_str("        endcase\n");
     verilog.push_str("    end\n");
+    verilog.push('\n');
+    Some(verilog)
 }
 
 /// Generate simple arithmetic pipeline
@@ -271,126 +838,853 @@ fn generate_arithmetic_pipeline(verilog: &mut String, analysis: &ComputationAnal
     // Add simple pipeline logic here...
 }
 
-/// Fallback to generic pipeline for complex patterns
-fn generate_generic_pipeline(verilog: &mut String, graph: &Graph) {
-    verilog.push_str("    // Complex computation pipeline\n");
-    
-    // Generate the actual combinational logic
-    generate_combinational_logic(verilog, graph);
-    
-    // Add simple pipeline control
-    verilog.push_str("    // Pipeline control\n");
-    verilog.push_str("    always @(posedge ap_clk) begin\n");
-    verilog.push_str("        if (!ap_rst_n) begin\n");
-    verilog.push_str("            pipeline_valid <= 3'b000;\n");
-    verilog.push_str("            pipeline_counter <= 3'b000;\n");
-    verilog.push_str("            ap_done <= 1'b0;\n");
-    verilog.push_str("        end else if (ap_start) begin\n");
-    verilog.push_str("            pipeline_valid <= {pipeline_valid[1:0], 1'b1};\n");
-    verilog.push_str("            pipeline_counter <= pipeline_counter + 1;\n");
-    verilog.push_str("            ap_done <= pipeline_valid[2];\n");
-    verilog.push_str("        end\n");
-    verilog.push_str("    end\n");
-    verilog.push_str("\n");
-    verilog.push_str("    // Control signal assignments\n");
-    verilog.push_str("    assign ap_idle = ~pipeline_valid[0];\n");
-    verilog.push_str("    assign ap_ready = ~pipeline_valid[0];\n");
-}
+/// Register-per-stage pipeline built from the scheduler's `pipeline_stages`
+///
+/// Each stage computes its operations combinationally from the previous
+/// stage's registers, then registers every value a later stage still needs.
+/// Outputs are driven from the registers after the final stage, so results
+/// appear `depth` cycles after `ap_start` and a new input is accepted every II cycles.
+fn generate_generic_pipeline(verilog: &mut String, graph: &Graph, options: &VerilogEmitOptions, stall: StallControl) {
+    let plan = StagePlan::new(graph, options);
+    let depth = plan.depth;
+    let valid = |stage: usize| if stage == 0 { issue_signal(graph).to_string() } else { format!("stage_valid[{}]", stage - 1) };
 
-/// Generate a simple (non-pipelined) Verilog module  
-fn generate_simple_module(graph: &Graph, module_name: &str) -> String {
-    let mut verilog = String::new();
-    
-    verilog.push_str(&format!("// Generated for AMD Alveo U50 - SIMPLE VERSION\n"));
-    verilog.push_str(&format!("// synthesis translate_off\n"));
-    verilog.push_str(&format!("`timescale 1ns / 1ps\n"));
-    verilog.push_str(&format!("// synthesis translate_on\n\n"));
-    
-    verilog.push_str(&generate_module_header(graph, module_name));
-    
-    // Simple combinational logic
-    verilog.push_str("    // Simple control state machine\n");
-    verilog.push_str("    (* DONT_TOUCH = \"yes\" *) reg [1:0] state;\n");
-    verilog.push_str("    localparam IDLE = 2'b00, COMPUTE = 2'b01, DONE = 2'b10;\n");
-    verilog.push_str("    \n");
-    
-    // Generate combinational logic for all operations
-    generate_combinational_logic(&mut verilog, graph);
-    
-    // Add simple implementation logic...
-    verilog.push_str("    assign ap_idle = (state == IDLE);\n");
-    verilog.push_str("    assign ap_ready = (state == IDLE);\n");
-    
-    verilog.push_str("\nendmodule\n");
-    verilog
-}
+    verilog.push_str(&format!("    // {}-stage pipeline from the schedule (II={})\n", depth, initiation_interval(graph)));
+    verilog.push_str(&format!("    reg [{}:0] stage_valid;\n", depth - 1));
+    verilog.push('\n');
+    if let Some(trip_count) = graph.loop_trip_count {
+        generate_loop_control(verilog, graph, trip_count, depth);
+    }
 
-/// Generate module header with I/O ports
-fn generate_module_header(graph: &Graph, module_name: &str) -> String {
-    let mut verilog = String::new();
-    
-    verilog.push_str(&format!("module {} #(\n", module_name));
-    verilog.push_str("    parameter integer DATA_WIDTH = 32,\n");
-    verilog.push_str("    parameter integer ADDR_WIDTH = 16\n");
-    verilog.push_str(") (\n");
-    
-    verilog.push_str("    // Clock and Reset\n");
-    verilog.push_str("    input  wire                    ap_clk,\n");
-    verilog.push_str("    input  wire                    ap_rst_n,\n");
-    verilog.push_str("    \n");
-    verilog.push_str("    // Control signals (HLS-style)\n");
-    verilog.push_str("    input  wire                    ap_start,\n");
-    verilog.push_str("    output reg                     ap_done,\n");
-    verilog.push_str("    output wire                    ap_idle,\n");
-    verilog.push_str("    output wire                    ap_ready,\n");
-    
-    // Collect inputs and outputs
-    let mut inputs = Vec::new();
-    let mut outputs = Vec::new();
-    
-    for node in &graph.nodes {
-        match &node.op {
-            Operation::Load(name) => {
-                if !inputs.contains(name) {
-                    inputs.push(name.clone());
-                }
-            }
-            Operation::Store(name, _) => {
-                if !outputs.contains(name) {
-                    outputs.push(name.clone());
-                }
-            }
-            _ => {}
+    verilog.push_str("    // Stage results\n");
+    for (node_id, node) in graph.nodes.iter().enumerate() {
+        if node.output.is_some() && (operation_expression(node, graph).is_some() || matches!(node.op, Operation::ArrayLoad(_, _) | Operation::CallResult(_, _))) {
+            let attribute = if matches!(node.op, Operation::Fma(_, _, _)) { "(* USE_DSP = \"yes\" *) " } else { "" };
+            let kind = if matches!(node.op, Operation::ArrayLoad(_, _)) { "reg " } else { "wire" };
+            verilog.push_str(&format!(
+                "    {}{} {} node_{};{}\n",
+                attribute, kind, signal_type(node.output_width, node.signed), node_id, fixed_point_comment(node.fixed_point)
+            ));
         }
     }
-    
-    // Add data interface
-    if !inputs.is_empty() {
-        verilog.push_str("    \n    // Data inputs");
-        if inputs.len() == 5 && inputs.contains(&"a".to_string()) && inputs.contains(&"e".to_string()) {
-            verilog.push_str(" - MAC: result = (a * b) + (c * d) + e\n");
-        } else {
-            verilog.push_str("\n");
-        }
-        for input in &inputs {
-            verilog.push_str(&format!("    input  wire [DATA_WIDTH-1:0]  {},\n", input));
+    verilog.push_str(&state_register_declarations(graph));
+    verilog.push('\n');
+
+    verilog.push_str("    // Stage boundary registers\n");
+    for (&value_id, &(ready, last_use)) in &plan.lifetimes {
+        for stage in ready..last_use {
+            verilog.push_str(&format!(
+                "    reg {} {}_r{};{}\n",
+                signal_type(graph.value_width(value_id), graph.value_signed(value_id)), plan.base(value_id, graph), stage,
+                fixed_point_comment(graph.value_fixed_point(value_id))
+            ));
         }
     }
-    
-    if !outputs.is_empty() {
-        verilog.push_str("    \n    // Data outputs\n");
-        for (i, output) in outputs.iter().enumerate() {
-            let comma = if i == outputs.len() - 1 { "" } else { "," };
-            verilog.push_str(&format!("    output wire [DATA_WIDTH-1:0]  {}{}\n", output, comma));
-        }
+    verilog.push('\n');
+
+    if options.explicit_dsp && graph.nodes.iter().any(|node| dsp_operands(node, graph).is_some()) {
+        generate_dsp_parameters(verilog, options);
+    }
+
+    // Multiplies bound to a shared unit read its product instead of multiplying
+    let mut unit_of: HashMap<usize, usize> = HashMap::new();
+    let mut units = String::new();
+    for (index, unit) in shared_units(graph, options).into_iter().enumerate() {
+        if let Some(multiplier) = shared_multiplier(index, unit, graph, &plan) {
+            units.push_str(&multiplier);
+            for node_id in &unit.operations {
+                unit_of.extend(graph.nodes.iter().position(|node| node.id == *node_id).map(|position| (position, index)));
+            }
+        }
+    }
+    verilog.push_str(&units);
+
+    let ii = initiation_interval(graph);
+    for stage in 0..depth {
+        verilog.push_str(&format!("    // Stage {}\n", stage));
+        if ii > 1 {
+            // Iterations overlap, so this stage runs alongside those congruent to it mod II
+            verilog.push_str(&format!("    // Kernel stage {} (mod II={}), slot {}\n", stage / ii, ii, stage % ii));
+        }
+        for (node_id, node) in graph.nodes.iter().enumerate() {
+            if plan.stages[node_id] != Some(stage) {
+                continue;
+            }
+            if let Some(unit) = unit_of.get(&node_id) {
+                verilog.push_str(&format!("    assign node_{} = mul_unit{}_p;  // Multiplication on shared unit {}\n", node_id, unit, unit));
+                continue;
+            }
+            let reference = |value_id| plan.reference(value_id, stage, graph);
+            if let Operation::LoopCarry(name, init_id) = &node.op {
+                // The first iteration of a modulo-scheduled loop takes the initial value
+                let first = if stage == 0 { "(loop_index == 0)".to_string() } else { format!("stage_first[{}]", stage - 1) };
+                verilog.push_str(&format!(
+                    "    assign node_{} = {} ? {} : {}_carry;  // Loop-carried value\n",
+                    node_id, first, signed_reference(*init_id, graph, &reference), name
+                ));
+                continue;
+            }
+            match dsp_operands(node, graph).filter(|_| options.explicit_dsp) {
+                Some((a_id, b_id)) => {
+                    let a = signed_reference(a_id, graph, &reference);
+                    let b = signed_reference(b_id, graph, &reference);
+                    verilog.push_str(&dsp48e2_instance(node_id, node, &a, &b, stall.enable().unwrap_or("1'b1")));
+                }
+                None if is_reduction(&node.op) => {
+                    generate_reduction_tree(verilog, node_id, node, graph, &reference, Some(stall));
+                }
+                None if matches!(node.op, Operation::Call(_, _)) => {
+                    verilog.push_str(&call_instance(node_id, graph, options, &valid(stage), &reference));
+                }
+                None => {
+                    if let Some((expression, description)) = operation_expression_with(node, graph, &reference) {
+                        verilog.push_str(&format!("    assign node_{} = {};  // {}\n", node_id, expression, description));
+                    }
+                }
+            }
+        }
+
+        let registers: Vec<_> = plan.lifetimes.iter()
+            .filter(|(_, &(ready, last_use))| ready <= stage && stage < last_use)
+            .collect();
+        if !registers.is_empty() {
+            let indent = if stall.enable().is_some() { "            " } else { "        " };
+            verilog.push_str("    always @(posedge ap_clk) begin\n");
+            if let Some(enable) = stall.enable() {
+                verilog.push_str(&format!("        if ({}) begin\n", enable));
+            }
+            for (&value_id, _) in registers {
+                verilog.push_str(&format!(
+                    "{}{}_r{} <= {};\n",
+                    indent, plan.base(value_id, graph), stage, plan.reference(value_id, stage, graph)
+                ));
+            }
+            if stall.enable().is_some() {
+                verilog.push_str("        end\n");
+            }
+            verilog.push_str("    end\n");
+        }
+        verilog.push('\n');
+    }
+
+    // BRAM reads and stores happen in their scheduled stage, while that stage holds valid data
+    generate_array_logic(
+        verilog,
+        graph,
+        &|node_id, value_id| plan.reference(value_id, plan.stages[node_id].unwrap_or(0), graph),
+        &|node_id| valid(plan.stages[node_id].unwrap_or(0)),
+        &|node_id| valid(plan.stages[node_id].unwrap_or(0)),
+    );
+    // State registers commit in the stage their new value is computed in
+    generate_state_registers(
+        verilog,
+        graph,
+        &|node_id, value_id| plan.reference(value_id, plan.stages[node_id].unwrap_or(0), graph),
+        &|node_id| valid(plan.stages[node_id].unwrap_or(0)),
+    );
+
+    if graph.loop_trip_count.is_some() {
+        generate_loop_results(verilog, graph, &plan, &valid);
+        return;
+    }
+    verilog.push_str("    // Outputs come from the registers after the final stage\n");
+    for node in &graph.nodes {
+        if let Operation::Store(name, value_id) = &node.op {
+            verilog.push_str(&format!("    assign {} = {};\n", name, plan.reference(*value_id, depth, graph)));
+        }
+    }
+    verilog.push('\n');
+
+    generate_valid_chain(verilog, depth, initiation_interval(graph), stall);
+}
+
+/// Counter-based controller of a modulo-scheduled loop, starting an iteration every II cycles
+///
+/// `ap_start` sets the loop running; `loop_issue` then starts iterations 0
+/// to `trip_count - 1` in slot 0 of `ii_count`, which runs freely so stage
+/// `s` of every iteration runs while it is `s mod II`. The first and last
+/// iterations are marked by `stage_first` and `stage_last`, which travel
+/// alongside `stage_valid`.
+fn generate_loop_control(verilog: &mut String, graph: &Graph, trip_count: usize, depth: usize) {
+    let ii = initiation_interval(graph);
+    let bits = address_bits(trip_count);
+    verilog.push_str(&format!("    // Rolled loop: {} iterations, modulo-scheduled at II={}\n", trip_count, ii));
+    verilog.push_str("    reg loop_running;\n");
+    verilog.push_str(&format!("    reg [{}:0] loop_index;  // Next iteration to start, 0 to {}\n", bits - 1, trip_count.saturating_sub(1)));
+    let issue = if ii > 1 {
+        verilog.push_str(&format!("    reg [{}:0] ii_count;\n", ii_count_width(ii) - 1));
+        "loop_running & (ii_count == 0)"
+    } else {
+        "loop_running"
+    };
+    verilog.push_str(&format!("    wire loop_issue = {};\n", issue));
+    verilog.push_str(&format!("    wire loop_last = (loop_index == {}'d{});\n", bits, trip_count.saturating_sub(1)));
+    verilog.push_str(&format!("    reg [{}:0] stage_first;\n", depth - 1));
+    verilog.push_str(&format!("    reg [{}:0] stage_last;\n", depth - 1));
+    for node in &graph.nodes {
+        if let Operation::LoopCarry(name, _) = &node.op {
+            verilog.push_str(&format!("    reg {} {}_carry;  // Loop-carried register\n", signal_type(node.output_width, node.signed), name));
+        }
+    }
+    verilog.push_str("    always @(posedge ap_clk) begin\n");
+    verilog.push_str("        if (!ap_rst_n) begin\n");
+    verilog.push_str("            loop_running <= 1'b0;\n");
+    verilog.push_str(&format!("            loop_index <= {}'d0;\n", bits));
+    if ii > 1 {
+        verilog.push_str(&format!("            ii_count <= {}'d0;\n", ii_count_width(ii)));
+    }
+    verilog.push_str("        end else begin\n");
+    if ii > 1 {
+        let width = ii_count_width(ii);
+        verilog.push_str(&format!("            ii_count <= (ii_count == {0}'d{1}) ? {0}'d0 : ii_count + {0}'d1;\n", width, ii - 1));
+    }
+    verilog.push_str("            if (!loop_running) begin\n");
+    verilog.push_str("                loop_running <= ap_start;\n");
+    verilog.push_str(&format!("                loop_index <= {}'d0;\n", bits));
+    verilog.push_str("            end else if (loop_issue) begin\n");
+    verilog.push_str("                if (loop_last)\n");
+    verilog.push_str("                    loop_running <= 1'b0;\n");
+    verilog.push_str("                else\n");
+    verilog.push_str(&format!("                    loop_index <= loop_index + {}'d1;\n", bits));
+    verilog.push_str("            end\n");
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n");
+    verilog.push('\n');
+}
+
+/// Carried registers, valid chains and outputs of a modulo-scheduled loop
+///
+/// A carried register is written in the stage its next value is computed
+/// in, by the iteration in that stage. Outputs capture the results of the
+/// last iteration as it leaves the final stage, raising `ap_done`.
+fn generate_loop_results(verilog: &mut String, graph: &Graph, plan: &StagePlan, valid: &dyn Fn(usize) -> String) {
+    let depth = plan.depth;
+    verilog.push_str("    // Loop-carried registers, written by the iteration computing the next value\n");
+    verilog.push_str("    always @(posedge ap_clk) begin\n");
+    for (node_id, node) in graph.nodes.iter().enumerate() {
+        if let Operation::LoopNext(name, value_id) = &node.op {
+            let stage = plan.stages[node_id].unwrap_or(0);
+            verilog.push_str(&format!("        if ({}) {}_carry <= {};\n", valid(stage), name, plan.reference(*value_id, stage, graph)));
+        }
+    }
+    verilog.push_str("    end\n");
+    verilog.push('\n');
+
+    let outputs = collect_output_ports(graph);
+    let exits = format!("stage_valid[{0}] & stage_last[{0}]", depth - 1);
+    if !outputs.is_empty() {
+        verilog.push_str("    // Output registers, holding the results of the last iteration\n");
+        for port in &outputs {
+            verilog.push_str(&format!("    reg {} {}_reg;{}\n", port.decl(), port.name, fixed_point_comment(port.fixed_point)));
+        }
+        verilog.push_str("    always @(posedge ap_clk) begin\n");
+        verilog.push_str("        if (!ap_rst_n) begin\n");
+        for port in &outputs {
+            verilog.push_str(&format!("            {}_reg <= {};\n", port.name, zero_literal(port.width)));
+        }
+        verilog.push_str(&format!("        end else if ({}) begin\n", exits));
+        for node in &graph.nodes {
+            if let Operation::Store(name, value_id) = &node.op {
+                verilog.push_str(&format!("            {}_reg <= {};\n", name, plan.reference(*value_id, depth, graph)));
+            }
+        }
+        verilog.push_str("        end\n");
+        verilog.push_str("    end\n");
+        for port in &outputs {
+            verilog.push_str(&format!("    assign {0} = {0}_reg;\n", port.name));
+        }
+        verilog.push('\n');
+    }
+
+    let shift = |chain: &str, input: &str| if depth == 1 {
+        input.to_string()
+    } else {
+        format!("{{{}[{}:0], {}}}", chain, depth - 2, input)
+    };
+    verilog.push_str("    // Valid bits, and the first and last iterations, travel alongside the data\n");
+    verilog.push_str("    always @(posedge ap_clk) begin\n");
+    verilog.push_str("        if (!ap_rst_n) begin\n");
+    for chain in ["stage_valid", "stage_first", "stage_last"] {
+        verilog.push_str(&format!("            {} <= {}'b0;\n", chain, depth));
+    }
+    verilog.push_str("            ap_done <= 1'b0;\n");
+    verilog.push_str("        end else begin\n");
+    verilog.push_str(&format!("            stage_valid <= {};\n", shift("stage_valid", "loop_issue")));
+    verilog.push_str(&format!("            stage_first <= {};\n", shift("stage_first", "loop_issue & (loop_index == 0)")));
+    verilog.push_str(&format!("            stage_last <= {};\n", shift("stage_last", "loop_issue & loop_last")));
+    verilog.push_str(&format!("            ap_done <= {};\n", exits));
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n");
+    verilog.push('\n');
+    verilog.push_str("    assign ap_idle = ~loop_running & ~|stage_valid;\n");
+    verilog.push_str("    assign ap_ready = loop_issue & loop_last;  // Inputs are read until the last iteration starts\n");
+}
+
+/// Check that every carried value of a modulo-scheduled loop is written before the next iteration reads it
+///
+/// The next iteration reads a carry II cycles after this one, so the stage
+/// computing its next value must come less than II stages after the read.
+fn check_loop_carries(graph: &Graph, options: &VerilogEmitOptions) -> Result<(), HlsError> {
+    let plan = StagePlan::new(graph, options);
+    let ii = initiation_interval(graph);
+    let stage_of = |matches: &dyn Fn(&Operation) -> bool| graph.nodes.iter().enumerate()
+        .filter(|(_, node)| matches(&node.op))
+        .filter_map(|(node_id, _)| plan.stages[node_id])
+        .collect::<Vec<_>>();
+    for node in &graph.nodes {
+        let Operation::LoopNext(name, _) = &node.op else { continue };
+        let write = stage_of(&|op| matches!(op, Operation::LoopNext(written, _) if written == name)).into_iter().max().unwrap_or(0);
+        let read = stage_of(&|op| matches!(op, Operation::LoopCarry(carried, _) if carried == name)).into_iter().min().unwrap_or(0);
+        if read + ii <= write {
+            return Err(HlsError::Unsupported(format!(
+                "loop-carried {} written in stage {}, after the next iteration reads it in stage {}", name, write, read + ii
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Modules of the graphs `graph` calls, once each and in the order they are first called
+///
+/// A callee must be a pipelined graph without arrays or calls of its own,
+/// accepting inputs at least as often as the caller issues them, since the
+/// caller starts it in the call's stage of every transaction and takes its
+/// results the cycle they appear. It is emitted with the caller's options.
+fn generate_callee_modules(graph: &Graph, module_name: &str, options: &VerilogEmitOptions) -> Result<String, HlsError> {
+    let mut emitted: Vec<&str> = Vec::new();
+    let mut modules = String::new();
+    for node in &graph.nodes {
+        let Operation::Call(name, _) = &node.op else { continue };
+        if emitted.contains(&name.as_str()) {
+            continue;
+        }
+        emitted.push(name);
+        let callee = graph.callee(name).expect("validated calls have a callee");
+        callee.validate()?;
+        let pipelined = callee.pipeline_config.enable && !callee.pipeline_stages.is_empty() && callee.loop_trip_count.is_none();
+        let calls = callee.nodes.iter().any(|node| matches!(node.op, Operation::Call(_, _)));
+        if name == module_name || !pipelined || calls || !callee.arrays.is_empty() || initiation_interval(callee) > initiation_interval(graph) {
+            return Err(HlsError::Unsupported(format!(
+                "call of {}: callees must be pipelined, no slower than the caller and without arrays or calls",
+                name
+            )));
+        }
+        let callee_options = callee_options(options);
+        if matches!(pipeline_analysis(callee, &callee_options, StallControl::None).pattern, ComputationPattern::SimpleArithmetic) {
+            return Err(HlsError::Unsupported(format!("call of {}, built by the fixed arithmetic template", name)));
+        }
+        modules.push('\n');
+        modules.push_str(&generate_clean_pipelined_module(callee, name, &callee_options, StallControl::None));
+    }
+    Ok(modules)
+}
+
+/// Options a callee module is built with: the caller's, without performance counters
+fn callee_options(options: &VerilogEmitOptions) -> VerilogEmitOptions {
+    VerilogEmitOptions { enable_perf_counters: false, ..*options }
+}
+
+/// Cycles from a callee module's `ap_start` to its results
+fn callee_depth(callee: &Graph, options: &VerilogEmitOptions) -> usize {
+    let options = callee_options(options);
+    valid_stages(callee, &pipeline_analysis(callee, &options, StallControl::None))
+}
+
+/// Instance of the callee of call node `node_id`, started by `start` and driving its results' `node_N` wires
+///
+/// Instances are named `inst_<k>` for the k-th call in graph order.
+/// Outputs without a `CallResult` are left open.
+fn call_instance(node_id: usize, graph: &Graph, options: &VerilogEmitOptions, start: &str, reference: &dyn Fn(ValueId) -> String) -> String {
+    let node = &graph.nodes[node_id];
+    let Operation::Call(name, inputs) = &node.op else { unreachable!("only calls are instantiated") };
+    let callee = graph.callee(name).expect("validated calls have a callee");
+    let instance = graph.nodes[..node_id].iter().filter(|node| matches!(node.op, Operation::Call(_, _))).count();
+
+    let mut connections = vec![
+        ".ap_clk(ap_clk)".to_string(),
+        ".ap_rst_n(ap_rst_n)".to_string(),
+        format!(".ap_start({})", start),
+        ".ap_done()".to_string(),
+        ".ap_idle()".to_string(),
+        ".ap_ready()".to_string(),
+    ];
+    for register in callee.state_registers.iter().filter(|register| register.clear) {
+        connections.push(format!(".{}_clear(1'b0)", register.name));
+    }
+    for (port, value_id) in callee.input_names().iter().zip(inputs) {
+        connections.push(format!(".{}({})", port, reference(*value_id)));
+    }
+    for port in callee.output_names() {
+        let result = graph.nodes.iter().position(|result| matches!(&result.op, Operation::CallResult(call, name)
+            if *name == port && graph.call_node(*call).is_some_and(|call| call.id == node.id)));
+        connections.push(format!(".{}({})", port, result.map_or(String::new(), |result| format!("node_{}", result))));
+    }
+
+    let mut verilog = format!(
+        "    // {} instance, results {} cycles later\n    {} inst_{} (\n",
+        name, callee_depth(callee, options), name, instance
+    );
+    verilog.push_str(&connections.iter().map(|connection| format!("        {}", connection)).collect::<Vec<_>>().join(",\n"));
+    verilog.push_str("\n    );\n");
+    verilog
+}
+
+/// Stage assignment and register lifetimes for `generate_generic_pipeline`
+///
+/// Stages are the scheduler's cycles, legalized in graph order: a node never
+/// computes before its operands are available, inputs are sampled in stage 0
+/// alongside `ap_start`, and pipeline registers inserted by the scheduler are
+/// looked through, since every stage boundary is registered here anyway.
+///
+/// A multi-cycle operation such as a 3-cycle multiply or an 18-cycle divide
+/// is computed in the stage it starts in and its result is carried through
+/// one register per cycle the scheduler reserved for it, for synthesis to
+/// retime into the operator. The module therefore takes exactly as many
+/// cycles as the schedule has stages.
+struct StagePlan {
+    /// Stage each node computes in, indexed like `graph.nodes`
+    stages: Vec<Option<usize>>,
+    /// First stage a value is available in, and the stage after its last use
+    lifetimes: std::collections::BTreeMap<ValueId, (usize, usize)>,
+    /// Number of stages, i.e. cycles from `ap_start` to `ap_done`
+    depth: usize,
+}
+
+impl StagePlan {
+    fn new(graph: &Graph, options: &VerilogEmitOptions) -> Self {
+        let scheduled = crate::passes::timing::schedule_of(graph);
+
+        let mut stages = vec![None; graph.nodes.len()];
+        let mut ready: HashMap<ValueId, usize> = HashMap::new();
+        let mut depth = scheduled_depth(graph).max(1);
+        for (node_id, node) in graph.nodes.iter().enumerate() {
+            let operands_ready = node.op.operands().into_iter()
+                .filter_map(|value_id| ready.get(&resolve(value_id, graph)).copied())
+                .max()
+                .unwrap_or(0);
+            let stage = match node.op {
+                // The loop index is that of the iteration being issued
+                Operation::Load(_) | Operation::Const(_) | Operation::LoopIndex => 0,
+                // Stores are driven after the final stage; registers are implicit at every boundary
+                Operation::Store(_, _) | Operation::PipelineRegister(_) | Operation::PipelineBarrier | Operation::Nop => continue,
+                // Instance outputs are only wired up in the stage they appear in
+                Operation::CallResult(_, _) => operands_ready,
+                _ => scheduled.get(&node.id).copied().unwrap_or(0).max(operands_ready),
+            };
+            let latency = match node.op {
+                Operation::ArrayLoad(_, _) => 1,
+                Operation::Mul(_, _) if options.explicit_dsp && dsp_operands(node, graph).is_some() => options.mul_latency(),
+                // Registered between tree levels
+                Operation::ReduceAdd(ref values) | Operation::ReduceMax(ref values) | Operation::ReduceMin(ref values) => {
+                    reduction_levels(values.len()).saturating_sub(1)
+                }
+                Operation::Call(ref name, _) => graph.callee(name).map_or(0, |callee| callee_depth(callee, options)),
+                _ => 0,
+            };
+            stages[node_id] = Some(stage);
+            if let Some(output) = node.output {
+                ready.insert(output, stage + latency);
+                depth = depth.max(stage + latency + 1);
+            } else {
+                depth = depth.max(stage + 1);
+            }
+        }
+
+        let mut lifetimes = std::collections::BTreeMap::new();
+        for (node_id, node) in graph.nodes.iter().enumerate() {
+            let use_stage = match node.op {
+                Operation::Store(_, _) => depth,
+                _ => match stages[node_id] {
+                    Some(stage) => stage,
+                    None => continue,
+                },
+            };
+            for value_id in node.op.operands() {
+                let value_id = resolve(value_id, graph);
+                if matches!(graph.producer(value_id).map(|producer| &producer.op), Some(Operation::Const(_))) {
+                    continue;
+                }
+                if let Some(&first) = ready.get(&value_id) {
+                    let lifetime = lifetimes.entry(value_id).or_insert((first, first));
+                    lifetime.1 = lifetime.1.max(use_stage);
+                }
+            }
+        }
+
+        Self { stages, lifetimes, depth }
+    }
+
+    /// Signal name a value's registers are derived from: its port or `node_N`
+    fn base(&self, value_id: ValueId, graph: &Graph) -> String {
+        get_value_reference(value_id, graph)
+    }
+
+    /// Reference to a value from an operation computed in `stage`
+    fn reference(&self, value_id: ValueId, stage: usize, graph: &Graph) -> String {
+        let value_id = resolve(value_id, graph);
+        match self.lifetimes.get(&value_id) {
+            Some(&(ready, _)) if stage > ready => format!("{}_r{}", self.base(value_id, graph), stage - 1),
+            // Constants, and values produced in this very stage
+            _ => get_value_reference(value_id, graph),
+        }
+    }
+}
+
+/// Follow scheduler-inserted pipeline registers back to the value they carry
+fn resolve(value_id: ValueId, graph: &Graph) -> ValueId {
+    match graph.producer(value_id).map(|producer| &producer.op) {
+        Some(Operation::PipelineRegister(inner)) => resolve(*inner, graph),
+        _ => value_id,
+    }
+}
+
+/// Generate a simple (non-pipelined) Verilog module  
+fn generate_simple_module(graph: &Graph, module_name: &str, options: &VerilogEmitOptions) -> String {
+    let mut verilog = String::new();
+    
+    verilog.push_str("// Generated for AMD Alveo U50 - SIMPLE VERSION\n");
+    verilog.push_str("// synthesis translate_off\n");
+    verilog.push_str("`timescale 1ns / 1ps\n");
+    verilog.push_str("// synthesis translate_on\n\n");
+    
+    let perf_stages = options.enable_perf_counters.then_some(0);
+    verilog.push_str(&generate_module_header(graph, module_name, StallControl::None, perf_stages));
+    
+    // Simple combinational logic
+    verilog.push_str("    // Simple control state machine\n");
+    verilog.push_str("    (* DONT_TOUCH = \"yes\" *) reg [1:0] state;\n");
+    verilog.push_str("    localparam IDLE = 2'b00, COMPUTE = 2'b01, DONE = 2'b10;\n");
+    let compute_wait = datapath_latency(graph, options);
+    let wait_width = (usize::BITS - compute_wait.leading_zeros()).max(1);
+    if compute_wait > 0 {
+        verilog.push_str(&format!("    reg [{}:0] compute_wait;  // Cycles left until clocked DSP48E2/BRAM results are valid\n", wait_width - 1));
+    }
+    let index_bits = graph.loop_trip_count.map(address_bits);
+    let carries: Vec<(&String, ValueId)> = graph.nodes.iter().filter_map(|node| match &node.op {
+        Operation::LoopNext(name, value_id) => Some((name, *value_id)),
+        _ => None,
+    }).collect();
+    if let (Some(trip_count), Some(bits)) = (graph.loop_trip_count, index_bits) {
+        // An iteration starts once the previous one has committed its carried registers
+        verilog.push_str(&format!("    // Rolled loop: {} iterations, II={}\n", trip_count, compute_wait + 1));
+        verilog.push_str(&format!("    reg [{}:0] loop_index;  // Iteration of the rolled loop, 0 to {}\n", bits - 1, trip_count.saturating_sub(1)));
+        for node in &graph.nodes {
+            if let Operation::LoopCarry(name, _) = &node.op {
+                verilog.push_str(&format!("    reg {} {}_carry;  // Loop-carried register\n", signal_type(node.output_width, node.signed), name));
+            }
+        }
+    }
+    verilog.push_str(&state_register_declarations(graph));
+    verilog.push_str("    \n");
+    
+    // Generate combinational logic for all operations
+    generate_combinational_logic(&mut verilog, graph, options);
+    let write_enable = if compute_wait > 0 { "state == COMPUTE && compute_wait == 0" } else { "state == COMPUTE" };
+    generate_array_logic(
+        &mut verilog,
+        graph,
+        &|_, value_id| get_value_reference(value_id, graph),
+        &|_| "state == COMPUTE".to_string(),
+        &|_| write_enable.to_string(),
+    );
+    generate_state_registers(&mut verilog, graph, &|_, value_id| get_value_reference(value_id, graph), &|_| write_enable.to_string());
+    
+    // Outputs hold the result captured on the COMPUTE -> DONE transition
+    let outputs = collect_output_ports(graph);
+    let stores: Vec<(&String, String)> = graph.nodes.iter().filter_map(|node| match &node.op {
+        Operation::Store(name, value_id) => Some((name, get_value_reference(*value_id, graph))),
+        _ => None,
+    }).collect();
+    if !outputs.is_empty() {
+        verilog.push_str("    // Output registers\n");
+        for port in &outputs {
+            verilog.push_str(&format!("    reg {} {}_reg;{}\n", port.decl(), port.name, fixed_point_comment(port.fixed_point)));
+        }
+        verilog.push_str("    \n");
+    }
+    
+    // The datapath settles while in COMPUTE, so ap_done follows one cycle after the start
+    verilog.push_str("    always @(posedge ap_clk) begin\n");
+    verilog.push_str("        if (!ap_rst_n) begin\n");
+    verilog.push_str("            state <= IDLE;\n");
+    verilog.push_str("            ap_done <= 1'b0;\n");
+    for port in &outputs {
+        verilog.push_str(&format!("            {}_reg <= {};\n", port.name, zero_literal(port.width)));
+    }
+    verilog.push_str("        end else begin\n");
+    verilog.push_str("            case (state)\n");
+    verilog.push_str("                IDLE: begin\n");
+    verilog.push_str("                    ap_done <= 1'b0;\n");
+    if compute_wait > 0 {
+        verilog.push_str(&format!("                    compute_wait <= {}'d{};\n", wait_width, compute_wait));
+    }
+    if graph.loop_trip_count.is_some() {
+        verilog.push_str("                    loop_index <= 0;\n");
+    }
+    verilog.push_str("                    if (ap_start) state <= COMPUTE;\n");
+    verilog.push_str("                end\n");
+    // The last (or only) iteration finishes; earlier ones update the carried registers and advance
+    let mut finish = vec!["state <= DONE;".to_string(), "ap_done <= 1'b1;".to_string()];
+    finish.extend(stores.iter().map(|(name, value)| format!("{}_reg <= {};", name, value)));
+    let commit = match (graph.loop_trip_count, index_bits) {
+        (Some(trip_count), Some(bits)) => {
+            let mut commit: Vec<String> = carries.iter()
+                .map(|(name, value_id)| format!("{}_carry <= {};", name, get_value_reference(*value_id, graph)))
+                .collect();
+            commit.push(format!("if (loop_index == {}'d{}) begin", bits, trip_count.saturating_sub(1)));
+            commit.extend(finish.iter().map(|line| format!("    {}", line)));
+            commit.push("end else begin".to_string());
+            commit.push("    loop_index <= loop_index + 1;".to_string());
+            if compute_wait > 0 {
+                commit.push(format!("    compute_wait <= {}'d{};", wait_width, compute_wait));
+            }
+            commit.push("end".to_string());
+            commit
+        }
+        _ => finish,
+    };
+    verilog.push_str("                COMPUTE: begin\n");
+    if compute_wait > 0 {
+        verilog.push_str("                    if (compute_wait == 0) begin\n");
+        for line in &commit {
+            verilog.push_str(&format!("                        {}\n", line));
+        }
+        verilog.push_str("                    end else begin\n");
+        verilog.push_str("                        compute_wait <= compute_wait - 1;\n");
+        verilog.push_str("                    end\n");
+    } else {
+        for line in &commit {
+            verilog.push_str(&format!("                    {}\n", line));
+        }
+    }
+    verilog.push_str("                end\n");
+    verilog.push_str("                default: begin\n");
+    verilog.push_str("                    state <= IDLE;\n");
+    verilog.push_str("                    ap_done <= 1'b0;\n");
+    verilog.push_str("                end\n");
+    verilog.push_str("            endcase\n");
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n");
+    verilog.push_str("    \n");
+    verilog.push_str("    assign ap_idle = (state == IDLE);\n");
+    verilog.push_str("    assign ap_ready = (state == IDLE);\n");
+    for port in &outputs {
+        verilog.push_str(&format!("    assign {0} = {0}_reg;\n", port.name));
+    }
+    
+    push_perf_counters(&mut verilog, module_name, perf_stages);
+    in_dialect(verilog, graph, options, true)
+}
+
+/// Generate module header with I/O ports
+///
+/// `perf_stages` adds the `perf_*` counter outputs for a pipeline of that many stages
+fn generate_module_header(graph: &Graph, module_name: &str, stall: StallControl, perf_stages: Option<usize>) -> String {
+    let mut verilog = String::new();
+    
+    verilog.push_str(&format!("module {} #(\n", module_name));
+    verilog.push_str("    parameter integer DATA_WIDTH = 32,\n");
+    verilog.push_str("    parameter integer ADDR_WIDTH = 16\n");
+    verilog.push_str(") (\n");
+    
+    verilog.push_str("    // Clock and Reset\n");
+    verilog.push_str("    input  wire                    ap_clk,\n");
+    verilog.push_str("    input  wire                    ap_rst_n,\n");
+    verilog.push_str("    \n");
+    verilog.push_str("    // Control signals (HLS-style)\n");
+    verilog.push_str("    input  wire                    ap_start,\n");
+    match stall {
+        StallControl::None => {}
+        StallControl::ClockEnable => verilog.push_str("    input  wire                    ap_ce,  // Stage registers hold while low\n"),
+        StallControl::ReadyValid => {
+            verilog.push_str("    input  wire                    m_axis_tready,  // Stage registers hold while low\n");
+            verilog.push_str("    output wire                    m_axis_tvalid,\n");
+        }
+    }
+    verilog.push_str("    output reg                     ap_done,\n");
+    verilog.push_str("    output wire                    ap_idle,\n");
+    verilog.push_str("    output wire                    ap_ready,\n");
+    for register in graph.state_registers.iter().filter(|register| register.clear) {
+        verilog.push_str(&format!("    input  wire                    {0}_clear,  // Reloads {0}_state with its initial value\n", register.name));
+    }
+    if let Some(stages) = perf_stages {
+        verilog.push_str(&perf_counter_ports(stages));
+    }
+    
+    // Collect inputs and outputs
+    let inputs = collect_input_ports(graph);
+    let outputs = collect_output_ports(graph);
+    
+    // Add data interface
+    if !inputs.is_empty() {
+        verilog.push_str("    \n    // Data inputs");
+        let names: Vec<&str> = inputs.iter().map(|port| port.name.as_str()).collect();
+        if names.len() == 5 && names.contains(&"a") && names.contains(&"e") {
+            verilog.push_str(" - MAC: result = (a * b) + (c * d) + e\n");
+        } else {
+            verilog.push('\n');
+        }
+        for port in &inputs {
+            verilog.push_str(&format!("    input  wire {}  {},{}\n", port.decl(), port.name, fixed_point_comment(port.fixed_point)));
+        }
+    }
+    
+    if !outputs.is_empty() {
+        verilog.push_str("    \n    // Data outputs\n");
+        for (i, port) in outputs.iter().enumerate() {
+            let comma = if i == outputs.len() - 1 && graph.arrays.is_empty() { "" } else { "," };
+            verilog.push_str(&format!("    output wire {}  {}{}{}\n", port.decl(), port.name, comma, fixed_point_comment(port.fixed_point)));
+        }
+    }
+    
+    if !graph.arrays.is_empty() {
+        verilog.push_str("    \n    // Array ports (host side of the block RAMs, or external memory ports)\n");
+        let mut ports = Vec::new();
+        for array in &graph.arrays {
+            let address = format!("[{}:0]", address_bits(array.depth) - 1);
+            let data = width_range(array.width);
+            match array.kind {
+                ArrayKind::Input => {
+                    ports.push(format!("    input  wire                    {}_we", array.name));
+                    ports.push(format!("    input  wire {}  {}_waddr", address, array.name));
+                    ports.push(format!("    input  wire {}  {}_wdata", data, array.name));
+                }
+                ArrayKind::Output => {
+                    ports.push(format!("    input  wire {}  {}_raddr", address, array.name));
+                    ports.push(format!("    output reg  {}  {}_rdata", data, array.name));
+                }
+                ArrayKind::Interface => {
+                    if graph.nodes.iter().any(|node| matches!(&node.op, Operation::ArrayLoad(name, _) if *name == array.name)) {
+                        ports.push(format!("    output wire {}  {}_addr", address, array.name));
+                        ports.push(format!("    output wire                    {}_en", array.name));
+                        ports.push(format!("    input  wire {}  {}_dout", data, array.name));
+                    }
+                    if graph.nodes.iter().any(|node| matches!(&node.op, Operation::ArrayStore(name, _, _) if *name == array.name)) {
+                        ports.push(format!("    output wire {}  {}_waddr", address, array.name));
+                        ports.push(format!("    output wire                    {}_we", array.name));
+                        ports.push(format!("    output wire {}  {}_din", data, array.name));
+                    }
+                }
+            }
+        }
+        verilog.push_str(&ports.join(",\n"));
+        verilog.push('\n');
     }
     
     verilog.push_str(");\n\n");
+    verilog.push_str(&sqrt_functions(graph));
     verilog
 }
 
+/// A data port of the generated module
+#[derive(Debug, Clone)]
+pub(crate) struct Port {
+    pub(crate) name: String,
+    pub(crate) width: Option<u32>,
+    pub(crate) signed: bool,
+    pub(crate) fixed_point: Option<FixedPointType>,
+}
+
+impl Port {
+    /// Declaration type, e.g. `signed [15:0]`
+    pub(crate) fn decl(&self) -> String {
+        signal_type(self.width, self.signed)
+    }
+}
+
+/// Collect the module's input ports (Load nodes), in `sort_ports` order
+pub(crate) fn collect_input_ports(graph: &Graph) -> Vec<Port> {
+    let mut inputs: Vec<Port> = Vec::new();
+    for node in &graph.nodes {
+        if let Operation::Load(name) = &node.op {
+            if !inputs.iter().any(|port| &port.name == name) {
+                inputs.push(Port { name: name.clone(), width: node.output_width, signed: node.signed, fixed_point: node.fixed_point });
+            }
+        }
+    }
+    sort_ports(&mut inputs, graph);
+    inputs
+}
+
+/// Collect the module's output ports (Store nodes) typed like the stored values, in `sort_ports` order
+pub(crate) fn collect_output_ports(graph: &Graph) -> Vec<Port> {
+    let mut outputs: Vec<Port> = Vec::new();
+    for node in &graph.nodes {
+        if let Operation::Store(name, value) = &node.op {
+            if !outputs.iter().any(|port| &port.name == name) {
+                outputs.push(Port {
+                    name: name.clone(),
+                    width: graph.value_width(*value),
+                    signed: graph.value_signed(*value),
+                    fixed_point: graph.value_fixed_point(*value),
+                });
+            }
+        }
+    }
+    sort_ports(&mut outputs, graph);
+    outputs
+}
+
+/// `graph.port_order` first, then the remaining ports by name, so equal graphs declare equal ports however they were built
+fn sort_ports(ports: &mut [Port], graph: &Graph) {
+    ports.sort_by_cached_key(|port| graph.port_key(&port.name));
+}
+
+/// Verilog bit range for a signal, falling back to the DATA_WIDTH parameter
+fn width_range(width: Option<u32>) -> String {
+    match width {
+        Some(width) => format!("[{}:0]", width.saturating_sub(1)),
+        None => "[DATA_WIDTH-1:0]".to_string(),
+    }
+}
+
+/// `  // fixed-point: Q8.8` after the declaration of a fixed-point signal
+fn fixed_point_comment(ty: Option<FixedPointType>) -> String {
+    ty.map_or(String::new(), |ty| format!("  // fixed-point: {}", ty))
+}
+
+/// Bit range prefixed with `signed` for two's-complement signals
+pub(crate) fn signal_type(width: Option<u32>, signed: bool) -> String {
+    if signed {
+        format!("signed {}", width_range(width))
+    } else {
+        width_range(width)
+    }
+}
+
+/// All-zeros reset value for a signal of the given width
+pub(crate) fn zero_literal(width: Option<u32>) -> String {
+    match width {
+        Some(width) => format!("{}'d0", width),
+        None => "{DATA_WIDTH{1'b0}}".to_string(),
+    }
+}
+
+/// Sized decimal literal; unknown widths default to 32 bits
+fn sized_literal(value: i64, width: Option<u32>) -> String {
+    format_literal(value, width, false)
+}
+
+fn format_literal(value: i64, width: Option<u32>, signed: bool) -> String {
+    let width = width.unwrap_or(32);
+    let base = if signed { "sd" } else { "d" };
+    if value < 0 {
+        format!("-{}'{}{}", width, base, value.unsigned_abs())
+    } else {
+        format!("{}'{}{}", width, base, value)
+    }
+}
+
+/// Most significant bit index of a signal, for sign tests
+fn msb_index(width: Option<u32>) -> String {
+    match width {
+        Some(width) => width.saturating_sub(1).to_string(),
+        None => "DATA_WIDTH-1".to_string(),
+    }
+}
+
 /// Generate combinational logic for all operations in the graph
-fn generate_combinational_logic(verilog: &mut String, graph: &Graph) {
+fn generate_combinational_logic(verilog: &mut String, graph: &Graph, options: &VerilogEmitOptions) {
     // Generate wire declarations for intermediate values
     verilog.push_str("    // Intermediate computation wires\n");
     for (node_id, node) in graph.nodes.iter().enumerate() {
@@ -398,227 +1692,617 @@ fn generate_combinational_logic(verilog: &mut String, graph: &Graph) {
             Operation::Load(_) | Operation::Store(_, _) | Operation::Const(_) => {
                 // Inputs, outputs, and constants don't need wire declarations
             }
-            _ => {
-                verilog.push_str(&format!("    wire [DATA_WIDTH-1:0] node_{};\n", node_id));
+            _ => {
+                if node.output.is_some() {
+                    // Keep fused multiply-adds on the DSP48E2 post-adder path
+                    let attribute = if matches!(node.op, Operation::Fma(_, _, _)) { "(* USE_DSP = \"yes\" *) " } else { "" };
+                    let kind = if matches!(node.op, Operation::ArrayLoad(_, _)) { "reg " } else { "wire" };
+                    verilog.push_str(&format!(
+                        "    {}{} {} node_{};{}\n",
+                        attribute, kind, signal_type(node.output_width, node.signed), node_id, fixed_point_comment(node.fixed_point)
+                    ));
+                }
+            }
+        }
+    }
+    verilog.push('\n');
+    
+    if options.explicit_dsp && graph.nodes.iter().any(|node| dsp_operands(node, graph).is_some()) {
+        generate_dsp_parameters(verilog, options);
+    }
+    
+    // Generate assign statements for each operation
+    verilog.push_str("    // Combinational logic for all operations\n");
+    for (node_id, node) in graph.nodes.iter().enumerate() {
+        match dsp_operands(node, graph).filter(|_| options.explicit_dsp) {
+            Some((a_id, b_id)) => verilog.push_str(&dsp48e2_instance(node_id, node, &operand_reference(a_id, graph), &operand_reference(b_id, graph), "1'b1")),
+            None => generate_operation_verilog(verilog, node_id, node, graph),
+        }
+    }
+    verilog.push('\n');
+}
+
+/// AREG/BREG/MREG/PREG settings shared by every DSP48E2 instance
+fn generate_dsp_parameters(verilog: &mut String, options: &VerilogEmitOptions) {
+    let (areg, breg, mreg, preg) = match options.mul_latency() {
+        1 => (0, 0, 0, 1),
+        2 => (1, 1, 0, 1),
+        _ => (1, 1, 1, 1),
+    };
+    verilog.push_str("    // DSP48E2 pipeline registers\n");
+    verilog.push_str(&format!("    localparam AREG = {};\n", areg));
+    verilog.push_str(&format!("    localparam BREG = {};\n", breg));
+    verilog.push_str(&format!("    localparam MREG = {};\n", mreg));
+    verilog.push_str(&format!("    localparam PREG = {};\n", preg));
+    verilog.push('\n');
+}
+
+/// Block RAM declarations plus synchronous read and write ports for every array
+///
+/// Each `ArrayLoad` is a registered read, available one cycle after its index;
+/// `ArrayStore`s write while their `write_enable` holds. `reference` renders an
+/// operand of the node at the given index. Input arrays are filled through
+/// `<name>_we`/`_waddr`/`_wdata`, output arrays read back through
+/// `<name>_raddr`/`_rdata`. Interface arrays live outside the module: their
+/// single read is driven onto `<name>_addr`/`_en` while `read_enable` holds
+/// and returns on `<name>_dout` a cycle later, and their single store drives
+/// `<name>_waddr`/`_we`/`_din`.
+fn generate_array_logic(
+    verilog: &mut String,
+    graph: &Graph,
+    reference: &dyn Fn(usize, ValueId) -> String,
+    read_enable: &dyn Fn(usize) -> String,
+    write_enable: &dyn Fn(usize) -> String,
+) {
+    if graph.arrays.is_empty() {
+        return;
+    }
+    let interface = |name: &str| graph.array(name).is_some_and(|array| array.kind == ArrayKind::Interface);
+
+    let internal: Vec<_> = graph.arrays.iter().filter(|array| array.kind != ArrayKind::Interface).collect();
+    if !internal.is_empty() {
+        verilog.push_str("    // Block RAM arrays\n");
+        for array in &internal {
+            verilog.push_str(&format!(
+                "    (* RAM_STYLE = \"block\" *) reg {} {} [0:{}];\n",
+                width_range(array.width), array.name, array.depth.saturating_sub(1)
+            ));
+        }
+        verilog.push('\n');
+    }
+
+    for (node_id, node) in graph.nodes.iter().enumerate() {
+        match &node.op {
+            Operation::ArrayLoad(name, index) if interface(name) => {
+                verilog.push_str(&format!("    assign {}_addr = {};\n", name, reference(node_id, *index)));
+                verilog.push_str(&format!("    assign {}_en = {};\n", name, read_enable(node_id)));
+                verilog.push_str(&format!("    always @(*) node_{} = {}_dout;  // External memory read\n\n", node_id, name));
+            }
+            Operation::ArrayStore(name, index, value) if interface(name) => {
+                verilog.push_str(&format!("    assign {}_waddr = {};\n", name, reference(node_id, *index)));
+                verilog.push_str(&format!("    assign {}_we = {};\n", name, write_enable(node_id)));
+                verilog.push_str(&format!("    assign {}_din = {};  // External memory write\n\n", name, reference(node_id, *value)));
+            }
+            Operation::ArrayLoad(name, index) => {
+                verilog.push_str("    always @(posedge ap_clk) begin\n");
+                verilog.push_str(&format!("        node_{} <= {}[{}];  // Synchronous block RAM read\n", node_id, name, reference(node_id, *index)));
+                verilog.push_str("    end\n\n");
+            }
+            _ => {}
+        }
+    }
+
+    for array in internal {
+        let stores: Vec<String> = graph.nodes.iter().enumerate().filter_map(|(node_id, node)| match &node.op {
+            Operation::ArrayStore(name, index, value) if *name == array.name => Some(format!(
+                "        if ({}) {}[{}] <= {};\n",
+                write_enable(node_id), name, reference(node_id, *index), reference(node_id, *value)
+            )),
+            _ => None,
+        }).collect();
+
+        if array.kind == ArrayKind::Input || !stores.is_empty() {
+            verilog.push_str("    always @(posedge ap_clk) begin\n");
+            if array.kind == ArrayKind::Input {
+                verilog.push_str(&format!("        if ({0}_we) {0}[{0}_waddr] <= {0}_wdata;  // Host write port\n", array.name));
+            }
+            for store in stores {
+                verilog.push_str(&store);
             }
+            verilog.push_str("    end\n\n");
+        }
+        if array.kind == ArrayKind::Output {
+            verilog.push_str("    always @(posedge ap_clk) begin\n");
+            verilog.push_str(&format!("        {0}_rdata <= {0}[{0}_raddr];  // Host read port\n", array.name));
+            verilog.push_str("    end\n\n");
         }
     }
-    verilog.push_str("\n");
-    
-    // Generate assign statements for each operation
-    verilog.push_str("    // Combinational logic for all operations\n");
-    for (node_id, node) in graph.nodes.iter().enumerate() {
-        generate_operation_verilog(verilog, node_id, &node.op, graph);
+}
+
+/// `reg` declarations for the graph's state registers
+fn state_register_declarations(graph: &Graph) -> String {
+    graph.state_registers.iter()
+        .map(|register| format!("    reg {} {}_state;  // Held across transactions\n", width_range(register.width), register.name))
+        .collect()
+}
+
+/// Reset, clear and update logic for every state register
+///
+/// A register starts from its initial value after reset and while its
+/// `<name>_clear` input is high, and otherwise takes the value of its
+/// `RegisterStore` when `commit` holds for that node. `reference` renders an
+/// operand of the node at the given index.
+fn generate_state_registers(
+    verilog: &mut String,
+    graph: &Graph,
+    reference: &dyn Fn(usize, ValueId) -> String,
+    commit: &dyn Fn(usize) -> String,
+) {
+    for register in &graph.state_registers {
+        let reset = if register.clear { format!("!ap_rst_n || {}_clear", register.name) } else { "!ap_rst_n".to_string() };
+        verilog.push_str(&format!("    // State register {}\n", register.name));
+        verilog.push_str("    always @(posedge ap_clk) begin\n");
+        verilog.push_str(&format!("        if ({}) begin\n", reset));
+        verilog.push_str(&format!("            {}_state <= {};\n", register.name, sized_literal(register.init, register.width)));
+        let store = graph.nodes.iter().enumerate().find_map(|(node_id, node)| match &node.op {
+            Operation::RegisterStore(name, value_id) if *name == register.name => Some((node_id, *value_id)),
+            _ => None,
+        });
+        if let Some((node_id, value_id)) = store {
+            verilog.push_str(&format!("        end else if ({}) begin\n", commit(node_id)));
+            verilog.push_str(&format!("            {}_state <= {};\n", register.name, reference(node_id, value_id)));
+        }
+        verilog.push_str("        end\n");
+        verilog.push_str("    end\n\n");
+    }
+}
+
+/// Address bits needed to index `depth` entries
+fn address_bits(depth: usize) -> u32 {
+    usize::BITS - (depth.max(2) - 1).leading_zeros()
+}
+
+/// Operands of a `Mul` that fits one DSP48E2 multiplier, ordered as (A, B)
+///
+/// The multiplier is 27x18 two's complement, so unsigned operands need a spare
+/// bit. Multiplies that do not fit keep the inferred `*`.
+fn dsp_operands(node: &crate::ir::graph::Node, graph: &Graph) -> Option<(crate::ir::graph::ValueId, crate::ir::graph::ValueId)> {
+    let Operation::Mul(a_id, b_id) = node.op else { return None };
+    let bits = |value_id| graph.value_width(value_id).unwrap_or(32) + u32::from(!graph.value_signed(value_id));
+    let (a_id, b_id) = if bits(a_id) >= bits(b_id) { (a_id, b_id) } else { (b_id, a_id) };
+    (bits(a_id) <= 27 && bits(b_id) <= 18).then_some((a_id, b_id))
+}
+
+/// Cycles the simple FSM must wait for chained DSP48E2 products and BRAM reads to become valid
+fn datapath_latency(graph: &Graph, options: &VerilogEmitOptions) -> usize {
+    // Graph order is topological, so one forward sweep finds the deepest chain
+    let mut ready: HashMap<crate::ir::graph::ValueId, usize> = HashMap::new();
+    let mut latency = 0;
+    for node in &graph.nodes {
+        let start = node.op.operands().iter().filter_map(|value| ready.get(value)).copied().max().unwrap_or(0);
+        let registers = match node.op {
+            Operation::ArrayLoad(_, _) => 1,
+            Operation::Mul(_, _) if options.explicit_dsp && dsp_operands(node, graph).is_some() => options.mul_latency(),
+            _ => 0,
+        };
+        let finish = start + registers;
+        if let Some(output) = node.output {
+            ready.insert(output, finish);
+        }
+        latency = latency.max(finish);
     }
-    verilog.push_str("\n");
+    latency
+}
+
+/// DSP48E2 port connections that do not depend on the multiply being mapped
+pub(crate) const DSP48E2_TIE_OFFS: [&str; 41] = [
+    ".C(48'd0)", ".D(27'd0)", ".CARRYIN(1'b0)",
+    // Control: X = Y = M, W = Z = 0, ALU adds
+    ".OPMODE(9'b000000101)", ".ALUMODE(4'b0000)", ".INMODE(5'b00000)", ".CARRYINSEL(3'b000)",
+    ".CLK(ap_clk)",
+    // Clock enables of the unused registers
+    ".CEC(1'b0)", ".CED(1'b0)", ".CEAD(1'b0)", ".CECARRYIN(1'b0)", ".CECTRL(1'b0)",
+    ".CEINMODE(1'b0)", ".CEALUMODE(1'b0)",
+    // Resets
+    ".RSTA(~ap_rst_n)", ".RSTB(~ap_rst_n)", ".RSTM(~ap_rst_n)", ".RSTP(~ap_rst_n)",
+    ".RSTC(1'b0)", ".RSTD(1'b0)", ".RSTALLCARRYIN(1'b0)", ".RSTALUMODE(1'b0)", ".RSTCTRL(1'b0)",
+    ".RSTINMODE(1'b0)",
+    // Cascade and status
+    ".ACIN(30'd0)", ".BCIN(18'd0)", ".PCIN(48'd0)", ".CARRYCASCIN(1'b0)", ".MULTSIGNIN(1'b0)",
+    ".ACOUT()", ".BCOUT()", ".PCOUT()", ".CARRYCASCOUT()", ".MULTSIGNOUT()", ".CARRYOUT()",
+    ".XOROUT()", ".OVERFLOW()", ".UNDERFLOW()", ".PATTERNDETECT()", ".PATTERNBDETECT()",
+];
+
+/// `DSP48E2` configured as a plain multiplier (P = A * B), with unused ports tied off
+///
+/// `a` and `b` are rendered operand references, as from `operand_reference`;
+/// `clock_enable` drives the A, B, M and P register enables.
+fn dsp48e2_instance(node_id: usize, node: &crate::ir::graph::Node, a: &str, b: &str, clock_enable: &str) -> String {
+    let mut verilog = String::new();
+    // Assigning to the wider port wires sign-extends `$signed()` operands
+    verilog.push_str(&format!("    wire [29:0] dsp{}_a = {};\n", node_id, a));
+    verilog.push_str(&format!("    wire [17:0] dsp{}_b = {};\n", node_id, b));
+    verilog.push_str(&format!("    wire [47:0] dsp{}_p;\n", node_id));
+    verilog.push_str("    DSP48E2 #(\n");
+    let parameters = [
+        ".AREG(AREG)", ".ACASCREG(AREG)", ".BREG(BREG)", ".BCASCREG(BREG)", ".MREG(MREG)", ".PREG(PREG)",
+        ".CREG(0)", ".DREG(0)", ".ADREG(0)", ".CARRYINREG(0)", ".CARRYINSELREG(0)",
+        ".INMODEREG(0)", ".OPMODEREG(0)", ".ALUMODEREG(0)",
+        ".AMULTSEL(\"A\")", ".BMULTSEL(\"B\")", ".PREADDINSEL(\"A\")", ".USE_MULT(\"MULTIPLY\")",
+        ".USE_SIMD(\"ONE48\")", ".USE_PATTERN_DETECT(\"NO_PATDET\")",
+    ];
+    verilog.push_str(&parameters.iter().map(|p| format!("        {}", p)).collect::<Vec<_>>().join(",\n"));
+    verilog.push_str(&format!("\n    ) dsp{} (\n", node_id));
+    let mut ports = vec![
+        format!(".A(dsp{}_a)", node_id),
+        format!(".B(dsp{}_b)", node_id),
+        format!(".P(dsp{}_p)", node_id),
+    ];
+    ports.extend(["CEA1", "CEA2", "CEB1", "CEB2", "CEM", "CEP"].iter().map(|port| format!(".{}({})", port, clock_enable)));
+    ports.extend(DSP48E2_TIE_OFFS.iter().map(|port| port.to_string()));
+    verilog.push_str(&ports.iter().map(|p| format!("        {}", p)).collect::<Vec<_>>().join(",\n"));
+    verilog.push_str("\n    );\n");
+    verilog.push_str(&format!(
+        "    assign node_{} = dsp{}_p[{}:0];  // Multiplication on DSP48E2\n",
+        node_id, node_id, msb_index(node.output_width)
+    ));
+    verilog
 }
 
 /// Generate Verilog for a specific operation
-fn generate_operation_verilog(verilog: &mut String, node_id: usize, op: &Operation, graph: &Graph) {
+///
+/// Stores are registered by the control FSM, so they produce no assign here.
+fn generate_operation_verilog(verilog: &mut String, node_id: usize, node: &crate::ir::graph::Node, graph: &Graph) {
+    if is_reduction(&node.op) {
+        generate_reduction_tree(verilog, node_id, node, graph, &|value_id| get_value_reference(value_id, graph), None);
+    } else if let Some((expression, description)) = operation_expression(node, graph) {
+        verilog.push_str(&format!(
+            "    assign node_{} = {};  // {}\n",
+            node_id, expression, description
+        ));
+    }
+}
+
+fn is_reduction(op: &Operation) -> bool {
+    matches!(op, Operation::ReduceAdd(_) | Operation::ReduceMax(_) | Operation::ReduceMin(_))
+}
+
+fn reduction_description(op: &Operation) -> &'static str {
     match op {
-        // Arithmetic operations
-        Operation::Add(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = {} + {};  // Addition\n",
-                node_id, a_val, b_val
-            ));
-        }
-        
-        Operation::Sub(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = {} - {};  // Subtraction\n",
-                node_id, a_val, b_val
-            ));
-        }
-        
-        Operation::Mul(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = {} * {};  // Multiplication\n",
-                node_id, a_val, b_val
-            ));
+        Operation::ReduceMax(_) => "Reduction maximum",
+        Operation::ReduceMin(_) => "Reduction minimum",
+        _ => "Reduction add",
+    }
+}
+
+/// One two-input adder or compare-and-select of a reduction tree
+fn reduction_step(op: &Operation, a: &str, b: &str) -> String {
+    match op {
+        Operation::ReduceMax(_) => format!("({0} > {1}) ? {0} : {1}", a, b),
+        Operation::ReduceMin(_) => format!("({0} < {1}) ? {0} : {1}", a, b),
+        _ => format!("{} + {}", a, b),
+    }
+}
+
+/// Combine neighbouring `terms` level by level, carrying an odd one out up unchanged
+fn reduction_level(op: &Operation, terms: &[String]) -> Vec<String> {
+    terms.chunks(2).map(|pair| match pair {
+        [a, b] => reduction_step(op, a, b),
+        _ => pair[0].clone(),
+    }).collect()
+}
+
+/// A reduction tree as one parenthesized expression, `empty` for no terms
+fn balanced_expression(op: &Operation, mut terms: Vec<String>, empty: &str) -> String {
+    while terms.len() > 1 {
+        let paired = terms.len() / 2;
+        terms = reduction_level(op, &terms).into_iter().enumerate()
+            .map(|(index, term)| if index < paired { format!("({})", term) } else { term })
+            .collect();
+    }
+    terms.pop().unwrap_or_else(|| empty.to_string())
+}
+
+/// Balanced tree of adders or comparators for a `ReduceAdd`/`ReduceMax`/`ReduceMin` node
+///
+/// Every level but the last drives `node_N_l<level>_<k>` signals, and the
+/// last drives `node_N`. With `clocking` the levels are registered, so an
+/// N-input tree spends `ceil(log2(N))` cycles in total, the last of them in
+/// the stage boundary register after `node_N`.
+fn generate_reduction_tree(
+    verilog: &mut String,
+    node_id: usize,
+    node: &crate::ir::graph::Node,
+    graph: &Graph,
+    reference: &dyn Fn(ValueId) -> String,
+    clocking: Option<StallControl>,
+) {
+    let values = node.op.operands();
+    let levels = reduction_levels(values.len());
+    let kind = if matches!(node.op, Operation::ReduceAdd(_)) { "adder" } else { "comparator" };
+    verilog.push_str(&format!(
+        "    // node_{}: {}-level {} tree over {} values{}\n",
+        node_id, levels, kind, values.len(), if clocking.is_some() { ", registered between levels" } else { "" }
+    ));
+
+    let mut terms: Vec<String> = values.iter().map(|value_id| signed_reference(*value_id, graph, reference)).collect();
+    for level in 1..levels {
+        // Sums grow a carry bit per level; comparators keep the input width
+        let width = match node.op {
+            Operation::ReduceAdd(_) => node.output_width.map(|width| width.saturating_sub((levels - level) as u32).max(1)),
+            _ => node.output_width,
+        };
+        let results = reduction_level(&node.op, &terms);
+        let names: Vec<String> = (0..results.len()).map(|index| format!("node_{}_l{}_{}", node_id, level, index)).collect();
+        let signal = if clocking.is_some() { "reg " } else { "wire" };
+        for name in &names {
+            verilog.push_str(&format!("    {} {} {};\n", signal, signal_type(width, node.signed), name));
         }
-        
-        Operation::Div(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = {} / {};  // Division\n",
-                node_id, a_val, b_val
-            ));
+        match clocking {
+            None => {
+                for (name, result) in names.iter().zip(&results) {
+                    verilog.push_str(&format!("    assign {} = {};\n", name, result));
+                }
+            }
+            Some(stall) => {
+                let indent = if stall.enable().is_some() { "            " } else { "        " };
+                verilog.push_str("    always @(posedge ap_clk) begin\n");
+                if let Some(enable) = stall.enable() {
+                    verilog.push_str(&format!("        if ({}) begin\n", enable));
+                }
+                for (name, result) in names.iter().zip(&results) {
+                    verilog.push_str(&format!("{}{} <= {};\n", indent, name, result));
+                }
+                if stall.enable().is_some() {
+                    verilog.push_str("        end\n");
+                }
+                verilog.push_str("    end\n");
+            }
         }
+        terms = names;
+    }
+
+    // At most two terms are left for the final level
+    let result = reduction_level(&node.op, &terms).pop().unwrap_or_else(|| sized_literal(0, node.output_width));
+    verilog.push_str(&format!("    assign node_{} = {};  // {}\n", node_id, result, reduction_description(&node.op)));
+}
+
+/// Right-hand side expression and a short description for a node's operation
+///
+/// Returns `None` for nodes that produce no logic: ports, constants, stores
+/// and pipeline bookkeeping.
+pub(crate) fn operation_expression(node: &crate::ir::graph::Node, graph: &Graph) -> Option<(String, &'static str)> {
+    operation_expression_with(node, graph, &|value_id| get_value_reference(value_id, graph))
+}
+
+/// `operation_expression` with operands named by `reference`, e.g. a pipeline stage's registers
+fn operation_expression_with(
+    node: &crate::ir::graph::Node,
+    graph: &Graph,
+    reference: &dyn Fn(ValueId) -> String,
+) -> Option<(String, &'static str)> {
+    let one = sized_literal(1, node.output_width);
+    let zero = sized_literal(0, node.output_width);
+    let r = |value_id: &ValueId| signed_reference(*value_id, graph, reference);
+    let s = |value_id: &ValueId| format!("$signed({})", reference(*value_id));
+    let compare = |a_id, b_id, op: &str| format!("({} {} {}) ? {} : {}", r(a_id), op, r(b_id), one, zero);
+    
+    let expression = match &node.op {
+        // Arithmetic operations
+        Operation::Add(a_id, b_id) => (format!("{} + {}", r(a_id), r(b_id)), "Addition"),
+        Operation::Sub(a_id, b_id) => (format!("{} - {}", r(a_id), r(b_id)), "Subtraction"),
+        Operation::Mul(a_id, b_id) => (format!("{} * {}", r(a_id), r(b_id)), "Multiplication"),
+        Operation::Fma(a_id, b_id, c_id) => (format!("{} * {} + {}", r(a_id), r(b_id), r(c_id)), "Fused multiply-add"),
+        Operation::Div(a_id, b_id) => (format!("{} / {}", r(a_id), r(b_id)), "Division"),
+        Operation::SAdd(a_id, b_id) => (format!("{} + {}", s(a_id), s(b_id)), "Signed addition"),
+        Operation::SSub(a_id, b_id) => (format!("{} - {}", s(a_id), s(b_id)), "Signed subtraction"),
+        Operation::SMul(a_id, b_id) => (format!("{} * {}", s(a_id), s(b_id)), "Signed multiplication"),
+        Operation::AddSat(_, _, _) => (saturating_expression(node, graph, reference), "Saturating addition"),
+        Operation::SubSat(_, _, _) => (saturating_expression(node, graph, reference), "Saturating subtraction"),
         
         // Comparison operations
-        Operation::CmpLt(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} < {}) ? 32'd1 : 32'd0;  // Less than\n",
-                node_id, a_val, b_val
-            ));
-        }
-        
-        Operation::CmpGt(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} > {}) ? 32'd1 : 32'd0;  // Greater than\n",
-                node_id, a_val, b_val
-            ));
-        }
-        
-        Operation::CmpEq(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} == {}) ? 32'd1 : 32'd0;  // Equality\n",
-                node_id, a_val, b_val
-            ));
-        }
-        
-        Operation::CmpGe(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} >= {}) ? 32'd1 : 32'd0;  // Greater than or equal\n",
-                node_id, a_val, b_val
-            ));
-        }
-        
-        Operation::CmpLe(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} <= {}) ? 32'd1 : 32'd0;  // Less than or equal\n",
-                node_id, a_val, b_val
-            ));
-        }
-        
-        Operation::CmpNe(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} != {}) ? 32'd1 : 32'd0;  // Not equal\n",
-                node_id, a_val, b_val
-            ));
-        }
-        
-        // Logical operations
-        Operation::And(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} != 0) && ({} != 0) ? 32'd1 : 32'd0;  // Logical AND\n",
-                node_id, a_val, b_val
-            ));
-        }
-        
-        Operation::Or(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} != 0) || ({} != 0) ? 32'd1 : 32'd0;  // Logical OR\n",
-                node_id, a_val, b_val
-            ));
-        }
+        Operation::CmpLt(a_id, b_id) => (compare(a_id, b_id, "<"), "Less than"),
+        Operation::CmpGt(a_id, b_id) => (compare(a_id, b_id, ">"), "Greater than"),
+        Operation::CmpEq(a_id, b_id) => (compare(a_id, b_id, "=="), "Equality"),
+        Operation::CmpGe(a_id, b_id) => (compare(a_id, b_id, ">="), "Greater than or equal"),
+        Operation::CmpLe(a_id, b_id) => (compare(a_id, b_id, "<="), "Less than or equal"),
+        Operation::CmpNe(a_id, b_id) => (compare(a_id, b_id, "!="), "Not equal"),
         
-        Operation::Not(a_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} == 0) ? 32'd1 : 32'd0;  // Logical NOT\n",
-                node_id, a_val
-            ));
-        }
+        // Bitwise operations
+        Operation::And(a_id, b_id) => (format!("{} & {}", r(a_id), r(b_id)), "Bitwise AND"),
+        Operation::Or(a_id, b_id) => (format!("{} | {}", r(a_id), r(b_id)), "Bitwise OR"),
+        Operation::Not(a_id) => (format!("~{}", r(a_id)), "Bitwise NOT"),
+        Operation::Xor(a_id, b_id) => (format!("{} ^ {}", r(a_id), r(b_id)), "Bitwise XOR"),
+        Operation::Nand(a_id, b_id) => (format!("~({} & {})", r(a_id), r(b_id)), "Bitwise NAND"),
+        Operation::Nor(a_id, b_id) => (format!("~({} | {})", r(a_id), r(b_id)), "Bitwise NOR"),
+        Operation::Xnor(a_id, b_id) => (format!("{} ~^ {}", r(a_id), r(b_id)), "Bitwise XNOR"),
         
-        Operation::Xor(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = {} ^ {};  // Bitwise XOR\n",
-                node_id, a_val, b_val
-            ));
+        // Bit fields (part-selects only apply to named signals, so constants are cut here)
+        Operation::Concat(high_id, low_id) => {
+            (format!("{{{}, {}}}", reference(*high_id), reference(*low_id)), "Concatenation")
         }
+        Operation::Slice(a_id, high, low) => match graph.producer(*a_id).map(|producer| &producer.op) {
+            Some(Operation::Const(val)) => (sized_literal(slice_bits(*val, *high, *low), node.output_width), "Bit slice"),
+            _ => (format!("{}[{}:{}]", reference(*a_id), high, low), "Bit slice"),
+        },
         
         // Conditional and utility operations  
         Operation::Mux(cond_id, true_id, false_id) => {
-            let cond_val = get_value_reference(*cond_id, graph);
-            let true_val = get_value_reference(*true_id, graph);
-            let false_val = get_value_reference(*false_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} != 0) ? {} : {};  // Multiplexer\n",
-                node_id, cond_val, true_val, false_val
-            ));
+            (format!("({} != 0) ? {} : {}", r(cond_id), r(true_id), r(false_id)), "Multiplexer")
         }
-        
         Operation::Abs(a_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({}[31]) ? (~{} + 1) : {};  // Absolute value\n",
-                node_id, a_val, a_val, a_val
-            ));
+            let a_val = reference(*a_id);
+            // Only untyped values are DATA_WIDTH wide; an unknown one has no width at all
+            debug_assert!(graph.producer(*a_id).is_some(), "value {} has no producer", a_id.0);
+            let msb = msb_index(graph.value_width(*a_id));
+            (format!("({}[{}]) ? (~{} + 1) : {}", a_val, msb, a_val, a_val), "Absolute value")
         }
-        
         Operation::Min(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} < {}) ? {} : {};  // Minimum\n",
-                node_id, a_val, b_val, a_val, b_val
-            ));
+            (format!("({} < {}) ? {} : {}", r(a_id), r(b_id), r(a_id), r(b_id)), "Minimum")
         }
-        
         Operation::Max(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = ({} > {}) ? {} : {};  // Maximum\n",
-                node_id, a_val, b_val, a_val, b_val
-            ));
+            (format!("({} > {}) ? {} : {}", r(a_id), r(b_id), r(a_id), r(b_id)), "Maximum")
         }
-        
-        // Shift operations
-        Operation::Shl(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = {} << {};  // Left shift\n",
-                node_id, a_val, b_val
-            ));
+        Operation::Sqrt(a_id) => {
+            (format!("{}({})", sqrt_function_name(graph.value_width(*a_id)), reference(*a_id)), "Square root")
         }
         
-        Operation::Shr(a_id, b_id) => {
-            let a_val = get_value_reference(*a_id, graph);
-            let b_val = get_value_reference(*b_id, graph);
-            verilog.push_str(&format!(
-                "    assign node_{} = {} >> {};  // Right shift\n",
-                node_id, a_val, b_val
-            ));
+        // Shift operations (Verilog always treats the shift amount as unsigned)
+        Operation::Shl(a_id, b_id) => (format!("{} << {}", r(a_id), r(b_id)), "Left shift"),
+        Operation::Shr(a_id, b_id) => (format!("{} >> {}", r(a_id), r(b_id)), "Right shift"),
+        
+        // Balanced trees; the Verilog paths name and register the levels with `generate_reduction_tree`
+        Operation::ReduceAdd(values) | Operation::ReduceMax(values) | Operation::ReduceMin(values) => {
+            let terms = values.iter().map(r).collect();
+            (balanced_expression(&node.op, terms, &zero), reduction_description(&node.op))
         }
         
-        // Output assignment
-        Operation::Store(name, value_id) => {
-            let val = get_value_reference(*value_id, graph);
-            verilog.push_str(&format!(
-                "    assign {} = {};  // Output assignment\n",
-                name, val
-            ));
+        // Rolled loops: the counter and carried registers belong to the control FSM or loop controller
+        Operation::LoopIndex => ("loop_index".to_string(), "Loop index"),
+        Operation::LoopCarry(name, init_id) => {
+            (format!("(loop_index == 0) ? {} : {}_carry", r(init_id), name), "Loop-carried value")
         }
+        Operation::LoopNext(_, _) => return None,
+        Operation::RegisterLoad(name) => (format!("{}_state", name), "State register"),
+        // The update is clocked, emitted by `generate_state_registers`
+        Operation::RegisterStore(_, _) => return None,
+        
+        // Inputs, constants and outputs don't generate logic of their own
+        Operation::Load(_) | Operation::Const(_) | Operation::Store(_, _) => return None,
         
-        // Input and constants don't generate logic
-        Operation::Load(_) | Operation::Const(_) => {}
+        // Array accesses are clocked BRAM ports, emitted by `generate_array_logic`
+        Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) => return None,
+
+        // Instances and the wires out of their ports, emitted by `generate_generic_pipeline`
+        Operation::Call(_, _) | Operation::CallResult(_, _) => return None,
         
         // Pipeline operations don't generate logic in combinational version
-        Operation::PipelineRegister(_) | Operation::PipelineBarrier | Operation::Nop => {}
+        Operation::PipelineRegister(_) | Operation::PipelineBarrier | Operation::Nop => return None,
+    };
+    
+    Some(expression)
+}
+
+/// Compare-and-clamp expression of an `AddSat`/`SubSat` node
+///
+/// The bound comparisons are sized one bit wider than the operands and the
+/// result, so the carry or borrow deciding the clamp is kept. Unsigned
+/// subtractions clamp to zero first, as their wrapped difference is large.
+fn saturating_expression(node: &crate::ir::graph::Node, graph: &Graph, reference: &dyn Fn(ValueId) -> String) -> String {
+    let (Operation::AddSat(a_id, b_id, width) | Operation::SubSat(a_id, b_id, width)) = node.op else {
+        unreachable!("only saturating operations are clamped")
+    };
+    let subtract = matches!(node.op, Operation::SubSat(_, _, _));
+    let signed = node.signed;
+    let operand_width = |value_id| graph.value_width(value_id).unwrap_or(32);
+    let exact_width = operand_width(a_id).max(operand_width(b_id)).max(width) + 1;
+    let operand = |value_id| if signed { format!("$signed({})", reference(value_id)) } else { reference(value_id) };
+    let exact = format!("{} {} {}", operand(a_id), if subtract { "-" } else { "+" }, operand(b_id));
+
+    let literal = |value: i128, width: u32| match (value < 0, signed) {
+        (true, _) => format!("-{}'sd{}", width, value.unsigned_abs()),
+        (false, true) => format!("{}'sd{}", width, value),
+        (false, false) => format!("{}'d{}", width, value),
+    };
+    let (min, max) = saturation_range(width, signed);
+    let upper = format!("({} > {}) ? {}", exact, literal(max, exact_width), literal(max, width));
+    match (signed, subtract) {
+        (true, _) => format!("{} : ({} < {}) ? {} : {}", upper, exact, literal(min, exact_width), literal(min, width), exact),
+        (false, false) => format!("{} : {}", upper, exact),
+        // The difference is at most `a`, so only wider minuends can exceed the result
+        (false, true) if operand_width(a_id) <= width => {
+            format!("({} < {}) ? {} : {}", reference(a_id), reference(b_id), literal(0, width), exact)
+        }
+        (false, true) => format!("({} < {}) ? {} : {} : {}", reference(a_id), reference(b_id), literal(0, width), upper, exact),
+    }
+}
+
+/// `isqrt` function declared by `sqrt_functions` for a radicand of `width` bits
+fn sqrt_function_name(width: Option<u32>) -> String {
+    match width {
+        Some(width) => format!("isqrt_{}", width),
+        None => "isqrt".to_string(),
+    }
+}
+
+/// One non-restoring square root function per radicand width the graph's `Sqrt` nodes use
+///
+/// Each result bit takes one add or subtract of the partial remainder, so
+/// the function is combinational; the registers the scheduler reserves after
+/// it, as for `Div`, let synthesis retime it into a pipeline.
+pub(crate) fn sqrt_functions(graph: &Graph) -> String {
+    let widths: BTreeSet<Option<u32>> = graph.nodes.iter()
+        .filter_map(|node| match node.op {
+            Operation::Sqrt(a_id) => Some(graph.value_width(a_id)),
+            _ => None,
+        })
+        .collect();
+
+    let mut verilog = String::new();
+    for width in widths {
+        // Bit `root + offset` of the result, with `root` the result width
+        let bit = |offset: i64| match width {
+            Some(width) => (width.div_ceil(2) as i64 + offset).to_string(),
+            None => format!("(DATA_WIDTH+1)/2{:+}", offset),
+        };
+        let (radicand_msb, padded_msb) = match width {
+            Some(width) => ((width - 1).to_string(), (2 * width.div_ceil(2) - 1).to_string()),
+            None => ("DATA_WIDTH-1".to_string(), "2*((DATA_WIDTH+1)/2)-1".to_string()),
+        };
+        let name = sqrt_function_name(width);
+        // Prefixed locals never hide a port or signal of the module
+        verilog.push_str("    // Non-restoring square root, one add or subtract per result bit\n");
+        verilog.push_str(&format!("    function [{}:0] {};\n", bit(-1), name));
+        verilog.push_str(&format!("        input [{}:0] isqrt_value;\n", radicand_msb));
+        verilog.push_str(&format!("        reg [{}:0] isqrt_radicand;\n", padded_msb));
+        verilog.push_str(&format!("        reg [{}:0] isqrt_remainder;  // Two's complement\n", bit(1)));
+        verilog.push_str(&format!("        reg [{}:0] isqrt_root;\n", bit(-1)));
+        verilog.push_str("        integer isqrt_i;\n");
+        verilog.push_str("        begin\n");
+        verilog.push_str("            isqrt_radicand = 0;\n");
+        verilog.push_str(&format!("            isqrt_radicand[{}:0] = isqrt_value;\n", radicand_msb));
+        verilog.push_str("            isqrt_remainder = 0;\n");
+        verilog.push_str("            isqrt_root = 0;\n");
+        verilog.push_str(&format!("            for (isqrt_i = {}; isqrt_i >= 0; isqrt_i = isqrt_i - 1) begin\n", bit(-1)));
+        verilog.push_str("                // Shift in the next two radicand bits, then subtract 4*root+1 or add 4*root+3\n");
+        verilog.push_str(&format!("                if (!isqrt_remainder[{}])\n", bit(1)));
+        verilog.push_str(&format!(
+            "                    isqrt_remainder = {{isqrt_remainder[{}:0], isqrt_radicand[2*isqrt_i+1 -: 2]}} - {{isqrt_root, 2'b01}};\n",
+            bit(-1)
+        ));
+        verilog.push_str("                else\n");
+        verilog.push_str(&format!(
+            "                    isqrt_remainder = {{isqrt_remainder[{}:0], isqrt_radicand[2*isqrt_i+1 -: 2]}} + {{isqrt_root, 2'b11}};\n",
+            bit(-1)
+        ));
+        verilog.push_str("                isqrt_root = isqrt_root << 1;\n");
+        verilog.push_str(&format!("                isqrt_root[0] = ~isqrt_remainder[{}];\n", bit(1)));
+        verilog.push_str("            end\n");
+        verilog.push_str(&format!("            {} = isqrt_root;\n", name));
+        verilog.push_str("        end\n");
+        verilog.push_str("    endfunction\n");
+        verilog.push_str("    \n");
+    }
+    verilog
+}
+
+/// Reference a value as an operand, casting two's-complement values with `$signed()`
+pub(crate) fn operand_reference(value_id: crate::ir::graph::ValueId, graph: &Graph) -> String {
+    signed_reference(value_id, graph, &|value_id| get_value_reference(value_id, graph))
+}
+
+/// `operand_reference` with the signal named by `reference`
+fn signed_reference(value_id: ValueId, graph: &Graph, reference: &dyn Fn(ValueId) -> String) -> String {
+    let reference = reference(value_id);
+    if graph.value_signed(value_id) {
+        format!("$signed({})", reference)
+    } else {
+        reference
     }
 }
 
 /// Get the Verilog reference for a value (input, constant, or intermediate result)
-fn get_value_reference(value_id: crate::ir::graph::ValueId, graph: &Graph) -> String {
+pub(crate) fn get_value_reference(value_id: crate::ir::graph::ValueId, graph: &Graph) -> String {
     // Find the node that produces this value
     for (node_id, node) in graph.nodes.iter().enumerate() {
         match &node.op {
@@ -629,7 +2313,7 @@ fn get_value_reference(value_id: crate::ir::graph::ValueId, graph: &Graph) -> St
             }
             Operation::Const(val) => {
                 if node.output == Some(value_id) {
-                    return format!("32'd{}", val);
+                    return format_literal(*val, node.output_width, node.signed);
                 }
             }
             _ => {
@@ -639,15 +2323,16 @@ fn get_value_reference(value_id: crate::ir::graph::ValueId, graph: &Graph) -> St
             }
         }
     }
-    
-    // Fallback
-    format!("32'd0")
+
+    // `validate` rejects graphs that use a value no node produces
+    debug_assert!(false, "value {} has no producer", value_id.0);
+    "32'd0".to_string()
 }
 
 // Supporting data structures
 #[derive(Debug)]
 enum ComputationPattern {
-    MAC,              // Multiply-accumulate pattern
+    Mac,              // Multiply-accumulate pattern
     SimpleArithmetic, // Simple adds/subs
     Complex,          // Complex patterns
 }
@@ -657,6 +2342,537 @@ struct ComputationAnalysis {
     pattern: ComputationPattern,
     logical_stages: usize,
     description: String,
-    inputs: Vec<String>,
-    outputs: Vec<String>,
+    mac: Option<MacStructure>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::*;
+
+    #[test]
+    fn test_mac_uses_tracked_widths() {
+        // result = (a * b) + (c * d) + e with 16-bit inputs
+        let a = input("a", 16);
+        let b = input("b", 16);
+        let c = input("c", 16);
+        let d = input("d", 16);
+        let e = input("e", 16);
+        let mac = add(add(mul(a, b), mul(c, d)), e);
+        let graph = lower_expr_to_graph(&output("result", mac));
+        assert!(graph.type_check().is_ok());
+
+        let verilog = generate_verilog_module(&graph, "mac16", None).unwrap();
+        assert!(verilog.contains("input  wire [15:0]  a,"));
+        assert!(verilog.contains("wire [31:0] node_5;"), "product should be 32 bits:\n{}", verilog);
+        // 32-bit products, plus a carry bit for each of the two additions
+        assert!(verilog.contains("output wire [33:0]  result"));
+        assert!(!verilog.contains("[DATA_WIDTH-1:0]"));
+    }
+
+    #[test]
+    fn test_simple_module_emits_bitwise_operators() {
+        let a = || input("a", 8);
+        let b = || input("b", 8);
+        let expr = output("result", xor(and(a(), b()), or(shl(a(), b()), not(shr(a(), b())))));
+        let verilog = generate_verilog_module(&lower_expr_to_graph(&expr), "bits", None).unwrap();
+
+        for operator in ["a & b", " | node_", " ^ node_", "~node_", "a << b", "a >> b"] {
+            assert!(verilog.contains(operator), "missing `{}` in:\n{}", operator, verilog);
+        }
+
+        let expr = output("result", xnor(nand(a(), b()), nor(a(), b())));
+        let verilog = generate_verilog_module(&lower_expr_to_graph(&expr), "inverted", None).unwrap();
+        for operator in ["~(a & b)", "~(a | b)", "node_2 ~^ node_3"] {
+            assert!(verilog.contains(operator), "missing `{}` in:\n{}", operator, verilog);
+        }
+
+        let expr = output("result", concat(slice(a(), 3, 0), slice(const_val(0xAB, 8), 7, 4)));
+        let verilog = generate_verilog_module(&lower_expr_to_graph(&expr), "fields", None).unwrap();
+        for operator in ["a[3:0]", "4'd10", "{node_1, node_3}", "wire [7:0] node_4;"] {
+            assert!(verilog.contains(operator), "missing `{}` in:\n{}", operator, verilog);
+        }
+    }
+
+    #[test]
+    fn test_signed_declarations_and_casts() {
+        let expr = output("result", mul(signed_input("a", 16), signed_const(-3, 16)));
+        let verilog = generate_verilog_module(&lower_expr_to_graph(&expr), "smul", None).unwrap();
+
+        assert!(verilog.contains("input  wire signed [15:0]  a,"));
+        assert!(verilog.contains("wire signed [31:0] node_2;"));
+        assert!(verilog.contains("= $signed(a) * $signed(-16'sd3);"), "{}", verilog);
+        assert!(verilog.contains("output wire signed [31:0]  result"));
+
+        let expr = output("result", smul(input("a", 8), input("b", 8)));
+        let verilog = generate_verilog_module(&lower_expr_to_graph(&expr), "smul", None).unwrap();
+        assert!(verilog.contains("input  wire [7:0]  a,"));
+        assert!(verilog.contains("wire signed [15:0] node_2;"));
+        assert!(verilog.contains("= $signed(a) * $signed(b);"), "{}", verilog);
+    }
+
+    #[test]
+    fn test_simple_module_emits_divide() {
+        let expr = output("result", div(input("a", 32), input("b", 32)));
+        let graph = lower_expr_to_graph(&expr);
+        let verilog = generate_verilog_module(&graph, "divider", None).unwrap();
+
+        assert!(verilog.contains("= a / b;"));
+        assert!(verilog.contains("                    result_reg <= node_2;"));
+        assert!(verilog.contains("assign result = result_reg;"));
+    }
+
+    #[test]
+    fn test_simple_adder_registers_its_sum_when_leaving_compute() {
+        // The adder of test_verilator_compilation, checked without Verilator
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 32), input("b", 32))));
+        let verilog = generate_verilog_module(&graph, "adder", None).unwrap();
+
+        assert!(verilog.contains("    wire [32:0] node_2;\n"));
+        assert!(verilog.contains("    assign node_2 = a + b;  // Addition\n"));
+        assert!(verilog.contains(concat!(
+            "                COMPUTE: begin\n",
+            "                    state <= DONE;\n",
+            "                    ap_done <= 1'b1;\n",
+            "                    result_reg <= node_2;\n",
+            "                end\n",
+        )));
+        assert!(verilog.contains("    assign result = result_reg;\n"));
+
+        let mut sim = crate::backend::sim::Simulator::new();
+        sim.set_input("a", 5, &graph);
+        sim.set_input("b", 10, &graph);
+        assert_eq!(sim.simulate(&graph).unwrap()["result"], 15);
+    }
+
+    #[test]
+    fn test_fixed_point_signals_are_annotated() {
+        use crate::dsl::types::FixedPointType;
+
+        let q8_8 = FixedPointType::unsigned(8, 8);
+        let graph = lower_expr_to_graph(&output("result", mul(fixed_input("a", q8_8), fixed_input("b", q8_8))));
+        let verilog = generate_verilog_module(&graph, "qmul", None).unwrap();
+
+        assert!(verilog.contains("input  wire [15:0]  a,  // fixed-point: Q8.8"));
+        assert!(verilog.contains("wire [31:0] node_2;  // fixed-point: Q16.16"));
+        assert!(verilog.contains("wire [23:0] node_4;  // fixed-point: Q16.8"));
+        assert!(verilog.contains("assign node_4 = node_2 >> 4'd8;"));
+        assert!(verilog.contains("output wire [23:0]  result  // fixed-point: Q16.8"));
+    }
+
+    #[test]
+    fn test_select_emits_ternary() {
+        let expr = output("result", select(lt(input("a", 8), input("b", 8)), input("a", 8), input("b", 8)));
+        let verilog = generate_verilog_module(&lower_expr_to_graph(&expr), "min8", None).unwrap();
+
+        assert!(verilog.contains("= (a < b) ?"));
+        assert!(verilog.contains("assign node_3 = (node_2 != 0) ? a : b;"), "{}", verilog);
+    }
+
+    #[test]
+    fn test_invalid_graph_is_rejected() {
+        let mut graph = lower_expr_to_graph(&output("result", input("a", 8)));
+        graph.add_node(Operation::Store("result".to_string(), crate::ir::graph::ValueId(99)));
+
+        let error = generate_verilog_module(&graph, "broken", None).unwrap_err().to_string();
+        assert!(error.contains("uses value 99 which no node produces"), "{}", error);
+        assert!(error.contains("output 'result' is stored more than once"), "{}", error);
+    }
+
+    #[test]
+    fn test_explicit_dsp_instantiates_dsp48e2() {
+        // The wider operand goes to the 27-bit A port
+        let expr = output("result", mul(signed_input("a", 16), signed_input("b", 20)));
+        let graph = lower_expr_to_graph(&expr);
+        let options = VerilogEmitOptions { explicit_dsp: true, dsp_pipeline_regs: 2, ..Default::default() };
+        let verilog = generate_verilog_module(&graph, "dsp_mul", Some(&options)).unwrap();
+
+        assert!(verilog.contains("    localparam AREG = 1;\n    localparam BREG = 1;\n    localparam MREG = 0;\n    localparam PREG = 1;"));
+        assert!(verilog.contains("    wire [29:0] dsp2_a = $signed(b);\n    wire [17:0] dsp2_b = $signed(a);\n    wire [47:0] dsp2_p;\n    DSP48E2 #(\n        .AREG(AREG),"));
+        assert!(verilog.contains("        .PREG(PREG),"));
+        assert!(verilog.contains("    ) dsp2 (\n        .A(dsp2_a),\n        .B(dsp2_b),\n        .P(dsp2_p),"));
+        assert!(verilog.contains(".CLK(ap_clk)"));
+        assert!(verilog.contains("    assign node_2 = dsp2_p[35:0];  // Multiplication on DSP48E2"));
+        assert!(!verilog.contains(" * "));
+        // Two register stages before the product is valid
+        assert!(verilog.contains("compute_wait <= 2'd2;"));
+
+        let inferred = generate_verilog_module(&graph, "dsp_mul", None).unwrap();
+        assert!(!inferred.contains("DSP48E2") && !inferred.contains("compute_wait"));
+    }
+
+    #[test]
+    fn test_explicit_dsp_keeps_oversized_multiplies_inferred() {
+        let graph = lower_expr_to_graph(&output("result", mul(input("a", 32), input("b", 32))));
+        let options = VerilogEmitOptions { explicit_dsp: true, ..Default::default() };
+        let verilog = generate_verilog_module(&graph, "wide_mul", Some(&options)).unwrap();
+
+        assert!(!verilog.contains("DSP48E2"));
+        assert!(verilog.contains("= a * b;"));
+    }
+
+    #[test]
+    fn test_arrays_infer_block_ram() {
+        let mut function = crate::dsl::hls::HLSFunction::new("lookup");
+        let lut = function.array_input("lut", 256);
+        let copy = function.array_output("copy", 256);
+        let index = function.input("index").value;
+        let entry = function.array_load(&lut, index).value;
+        function.array_store(&copy, index, entry);
+        let verilog = function.generate_verilog().unwrap();
+
+        assert!(verilog.contains("(* RAM_STYLE = \"block\" *) reg [DATA_WIDTH-1:0] lut [0:255];"));
+        assert!(verilog.contains("(* RAM_STYLE = \"block\" *) reg [DATA_WIDTH-1:0] copy [0:255];"));
+        assert!(verilog.contains("input  wire [7:0]  lut_waddr,"));
+        assert!(verilog.contains("output reg  [DATA_WIDTH-1:0]  copy_rdata\n);"));
+        assert!(verilog.contains("node_1 <= lut[index];  // Synchronous block RAM read"));
+        assert!(verilog.contains("if (lut_we) lut[lut_waddr] <= lut_wdata;"));
+        // The store commits once the one-cycle read has completed
+        assert!(verilog.contains("if (state == COMPUTE && compute_wait == 0) copy[index] <= node_1;"));
+        assert!(verilog.contains("copy_rdata <= copy[copy_raddr];"));
+    }
+
+    #[test]
+    fn test_generic_pipeline_registers_stage_boundaries() {
+        let mut graph = crate::hft::ZeroPlusStrategy::to_fpga_graph();
+        crate::passes::cse::run_cse_pass(&mut graph);
+        graph.enable_pipeline(1, 3, 1);
+        crate::passes::pipeline::run_pipeline_pass(&mut graph).unwrap();
+        let depth = StagePlan::new(&graph, &VerilogEmitOptions::default()).depth;
+        let verilog = generate_verilog_module(&graph, "hft_zero_plus", None).unwrap();
+
+        assert!(depth > 1);
+        assert!(verilog.contains(&format!("// Pipeline: {}-stage complex logic", depth)));
+        assert!(!verilog.contains("pipeline_counter"));
+        // One register block per stage, plus the valid chain
+        assert_eq!(verilog.matches("always @(posedge ap_clk)").count(), depth + 1);
+        // Inputs are sampled with ap_start and carried forward
+        assert!(verilog.contains("best_bid_price_r0 <= best_bid_price;"));
+        assert!(verilog.contains("best_bid_price_r1 <= best_bid_price_r0;"));
+        for output in ["action", "price", "quantity"] {
+            let assign = verilog.lines().find(|line| line.starts_with(&format!("    assign {} = ", output))).unwrap();
+            assert!(assign.ends_with(&format!("_r{};", depth - 1)), "{}", assign);
+        }
+        assert!(verilog.contains(&format!("ap_done <= stage_valid[{}];", depth - 2)));
+        assert!(verilog.contains("assign ap_ready = 1'b1;"));
+
+        // Every stage result is declared and driven exactly once
+        for (node_id, node) in graph.nodes.iter().enumerate() {
+            if operation_expression(node, &graph).is_some() {
+                assert_eq!(verilog.matches(&format!("assign node_{} = ", node_id)).count(), 1);
+            }
+        }
+    }
+
+    /// `sum = acc += x`, with `acc` kept between transactions
+    fn running_sum(clear: bool) -> Graph {
+        let mut graph = Graph::new();
+        graph.declare_state_register("acc", Some(32), 0, clear);
+        let x = graph.add_node_with_output_width(Operation::Load("x".to_string()), 16);
+        let acc = graph.add_node_with_output_width(Operation::RegisterLoad("acc".to_string()), 32);
+        let sum = graph.add_node_with_output_width(Operation::Add(acc, x), 32);
+        graph.add_node(Operation::RegisterStore("acc".to_string(), sum));
+        graph.add_node(Operation::Store("sum".to_string(), sum));
+        graph
+    }
+
+    #[test]
+    fn test_saturating_arithmetic_compares_and_clamps() {
+        let verilog_of = |expr: Expr| generate_verilog_module(&lower_expr_to_graph(&expr), "sat", None).unwrap();
+        let verilog = verilog_of(output("r", add_sat(input("a", 8), input("b", 8), 8)));
+        assert!(verilog.contains("assign node_2 = (a + b > 9'd255) ? 8'd255 : a + b;  // Saturating addition"), "{}", verilog);
+        let verilog = verilog_of(output("r", sub_sat(input("a", 16), input("b", 8), 8)));
+        assert!(verilog.contains("assign node_2 = (a < b) ? 8'd0 : (a - b > 17'd255) ? 8'd255 : a - b;"), "{}", verilog);
+        let verilog = verilog_of(output("r", add_sat(sinput("a", 32), sinput("b", 32), 32)));
+        assert!(verilog.contains(
+            "($signed(a) + $signed(b) > 33'sd2147483647) ? 32'sd2147483647 : ($signed(a) + $signed(b) < -33'sd2147483648) ? -32'sd2147483648"
+        ), "{}", verilog);
+
+        // The sticky flag is a state register behind an extra output port
+        let mut graph = lower_expr_to_graph(&output("r", add_sat(input("a", 8), input("b", 8), 8)));
+        let saturated = graph.nodes.iter()
+            .find_map(|node| match node.op {
+                Operation::AddSat(_, _, _) => node.output,
+                _ => None,
+            })
+            .unwrap();
+        graph.add_overflow_flag(saturated, "overflow");
+        let verilog = generate_verilog_module(&graph, "sat", None).unwrap();
+        assert!(verilog.contains("output wire [0:0]  overflow"));
+        assert!(verilog.contains("assign node_4 = a + b;"));
+        assert!(verilog.contains("assign node_5 = (node_2 != node_4) ? 1'd1 : 1'd0;"));
+        assert!(verilog.contains("overflow_state <= node_7;"));
+    }
+
+    #[test]
+    fn test_state_register_is_updated_once_per_transaction() {
+        let graph = running_sum(true);
+        let verilog = generate_verilog_module(&graph, "running_sum", None).unwrap();
+        assert!(verilog.contains("input  wire                    acc_clear,  // Reloads acc_state with its initial value"));
+        assert!(verilog.contains("reg [31:0] acc_state;  // Held across transactions"));
+        assert!(verilog.contains("assign node_1 = acc_state;  // State register"));
+        assert!(verilog.contains("        if (!ap_rst_n || acc_clear) begin\n            acc_state <= 32'd0;\n        end else if (state == COMPUTE) begin\n            acc_state <= node_2;\n"));
+
+        let mut pipelined = running_sum(false);
+        pipelined.enable_pipeline(1, 4, 1);
+        crate::passes::pipeline::run_pipeline_pass(&mut pipelined).unwrap();
+        let verilog = generate_verilog_module(&pipelined, "running_sum", None).unwrap();
+        assert!(!verilog.contains("acc_clear"));
+        // Read and updated in the stage of the add, so the next input sees the new value
+        assert!(verilog.contains("    // Stage 2\n    assign node_1 = acc_state;"));
+        assert!(verilog.contains("end else if (stage_valid[1]) begin\n            acc_state <= node_2;"));
+        assert!(verilog.contains("assign ap_ready = 1'b1;"));
+
+        let axi = VerilogEmitOptions { interface: InterfaceStyle::AxiStream, ..Default::default() };
+        assert!(matches!(generate_verilog_module(&graph, "running_sum", Some(&axi)), Err(HlsError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_reduce_add_is_a_registered_balanced_tree() {
+        let inputs: Vec<Expr> = (0..8).map(|i| input(format!("x{}", i), 8)).collect();
+        let mut graph = lower_expr_to_graph(&output("total", sum(inputs)));
+        let mut sim = crate::backend::sim::Simulator::new();
+        for i in 0..8 {
+            sim.set_input(&format!("x{}", i), 200 + i, &graph);
+        }
+        assert_eq!(sim.simulate(&graph).unwrap()["total"], (200..208).sum::<i64>());
+
+        // Unregistered, the three levels are plain wires
+        let verilog = generate_verilog_module(&graph, "sum8", None).unwrap();
+        assert!(verilog.contains("// node_8: 3-level adder tree over 8 values\n"));
+        assert!(verilog.contains("assign node_8_l1_3 = x6 + x7;"));
+        assert!(verilog.contains("assign node_8_l2_1 = node_8_l1_2 + node_8_l1_3;"));
+        assert!(verilog.contains("assign node_8 = node_8_l2_0 + node_8_l2_1;"));
+        assert!(!verilog.contains("node_8_l3_"));
+
+        // Pipelined, the tree takes log2(8) cycles: two level registers and the stage boundary
+        graph.enable_pipeline(1, 8, 1);
+        crate::passes::pipeline::run_pipeline_pass(&mut graph).unwrap();
+        assert_eq!(graph.get_operation_latency(&graph.nodes[8].op), 3);
+        assert_eq!(graph.pipeline_stages.len(), 6);
+        let verilog = generate_verilog_module(&graph, "sum8", None).unwrap();
+        assert!(verilog.contains("reg  [8:0] node_8_l1_0;"));
+        assert!(verilog.contains("node_8_l1_0 <= x0_r1 + x1_r1;"));
+        assert!(verilog.contains("reg  [9:0] node_8_l2_1;"));
+        assert!(verilog.contains("node_8_l2_1 <= node_8_l1_2 + node_8_l1_3;"));
+        assert!(verilog.contains("assign node_8 = node_8_l2_0 + node_8_l2_1;  // Reduction add"));
+        assert!(verilog.contains("node_8_r4 <= node_8;"));
+        assert!(verilog.contains("assign total = node_8_r5;"));
+    }
+
+    #[test]
+    fn test_divide_is_registered_for_every_cycle_the_schedule_reserves() {
+        let mut graph = lower_expr_to_graph(&output("quotient", div(input("a", 16), input("b", 8))));
+        graph.enable_pipeline(1, 24, 1);
+        crate::passes::pipeline::run_pipeline_pass(&mut graph).unwrap();
+        let verilog = generate_verilog_module(&graph, "div16", None).unwrap();
+
+        // Loads in cycles 0-1, the divide in 2-19 and the store chained in 20
+        assert_eq!(graph.pipeline_stages.len(), 21);
+        assert!(verilog.contains("// Pipeline: 21-stage complex logic"));
+        assert!(verilog.contains("a_r1 <= a_r0;"));
+        assert!(verilog.contains("assign node_2 = a_r1 / b_r1;"));
+        assert!(verilog.contains("node_2_r2 <= node_2;"));
+        assert!(verilog.contains("node_2_r19 <= node_2_r18;"));
+        assert!(verilog.contains("assign quotient = node_2_r20;"));
+        assert!(verilog.contains("ap_done <= stage_valid[19];"));
+    }
+
+    #[test]
+    fn test_square_root_is_a_function_registered_for_its_scheduled_latency() {
+        let mut graph = lower_expr_to_graph(&output("root", add(sqrt(input("a", 32)), sqrt(input("b", 32)))));
+        graph.enable_pipeline(1, 16, 1);
+        let mut scheduler = crate::passes::pipeline::PipelineScheduler { sqrt_latency: Some(8), ..Default::default() };
+        scheduler.schedule_pipeline(&mut graph).unwrap();
+        let verilog = generate_verilog_module(&graph, "isqrt32", None).unwrap();
+
+        // Both roots share one 32-bit function
+        assert_eq!(verilog.matches("function [15:0] isqrt_32;").count(), 1);
+        assert!(verilog.contains("reg [17:0] isqrt_remainder;"));
+        assert!(verilog.contains("isqrt_remainder = {isqrt_remainder[15:0], isqrt_radicand[2*isqrt_i+1 -: 2]} - {isqrt_root, 2'b01};"));
+        assert!(verilog.contains("assign node_1 = isqrt_32(a_r1);"));
+        assert!(verilog.contains("node_1_r9 <= node_1_r8;"));
+        assert!(!verilog.contains("node_1_r10"));
+    }
+
+    #[test]
+    fn test_mac_pipeline_follows_graph_structure() {
+        // A single product plus an accumulate operand
+        let mut graph = lower_expr_to_graph(&output("acc", add(mul(input("x", 8), input("y", 8)), input("z", 16))));
+        graph.enable_pipeline(1, 4, 1);
+        crate::passes::pipeline::run_pipeline_pass(&mut graph).unwrap();
+        let verilog = generate_verilog_module(&graph, "mac1", None).unwrap();
+
+        // Loads take two cycles and the multiply three, one more than the template
+        assert!(verilog.contains("// Pipeline: 6-stage MAC implementation"));
+        assert!(verilog.contains("prod0_reg1 <= x_reg0 * y_reg0;"));
+        assert!(!verilog.contains("prod1_reg1"));
+        assert!(verilog.contains("products_reg2 <= prod0_reg1;"));
+        assert!(verilog.contains("z_reg2 <= z_reg1;"));
+        assert!(verilog.contains("result_reg3 <= products_reg2 + z_reg2;"));
+        assert!(verilog.contains("acc_reg5 <= acc_reg4;"));
+        assert!(verilog.contains("assign acc = acc_reg5;"));
+        assert!(verilog.contains("ap_done <= stage_valid[4];"));
+    }
+
+    #[test]
+    fn test_mac_pipeline_uses_real_port_names() {
+        let p = |i: usize| input(format!("p{}", i), 16);
+        let mac = add(add(mul(p(0), p(1)), mul(p(2), p(3))), p(4));
+        let mut graph = lower_expr_to_graph(&output("out", mac));
+        graph.enable_pipeline(1, 4, 1);
+        crate::passes::pipeline::run_pipeline_pass(&mut graph).unwrap();
+        let verilog = generate_verilog_module(&graph, "mac_p", None).unwrap();
+
+        assert!(verilog.contains("prod0_reg1 <= p0_reg0 * p1_reg0;"));
+        assert!(verilog.contains("prod1_reg1 <= p2_reg0 * p3_reg0;"));
+        assert!(verilog.contains("products_reg2 <= prod0_reg1 + prod1_reg1;"));
+        assert!(verilog.contains("result_reg3 <= products_reg2 + p4_reg2;"));
+        assert!(verilog.contains("reg [33:0] result_reg3;"));
+        assert!(!verilog.contains("e_reg") && !verilog.contains("mult_ab"));
+        // Every register read is declared
+        for register in ["p4_reg1", "p4_reg2", "out_reg4"] {
+            assert!(verilog.contains(&format!(" {};", register)), "{} is not declared", register);
+        }
+    }
+
+    #[test]
+    fn test_mac_built_in_reverse_order_emits_identical_verilog() {
+        // result = (a * b) + (c * d) + e, with the loads and products added in opposite orders
+        let build = |loads: [&str; 5], ab_first: bool| {
+            let mut graph = Graph::new();
+            let mut port = std::collections::HashMap::new();
+            for name in loads {
+                port.insert(name, graph.add_node_with_output_type(Operation::Load(name.to_string()), Some(16), false));
+            }
+            let mut multiply = |x: &str, y: &str| graph.add_node_with_output(Operation::Mul(port[x], port[y]));
+            let (ab, cd) = if ab_first {
+                let ab = multiply("a", "b");
+                (ab, multiply("c", "d"))
+            } else {
+                let cd = multiply("c", "d");
+                (multiply("a", "b"), cd)
+            };
+            let products = graph.add_node_with_output(Operation::Add(ab, cd));
+            let result = graph.add_node_with_output(Operation::Add(products, port["e"]));
+            graph.add_node(Operation::Store("result".to_string(), result));
+            graph.enable_pipeline(1, 4, 1);
+            crate::passes::pipeline::PipelineScheduler::new().schedule_pipeline(&mut graph).unwrap();
+            graph
+        };
+        let forward = build(["a", "b", "c", "d", "e"], true);
+        let reversed = build(["e", "d", "c", "b", "a"], false);
+        assert_eq!(
+            generate_verilog_module(&forward, "mac", None).unwrap(),
+            generate_verilog_module(&reversed, "mac", None).unwrap()
+        );
+        assert!(reversed.pipeline_stages.iter().all(|stage| stage.operations.is_sorted()));
+
+        let mut ordered = reversed;
+        ordered.set_port_order(&["e", "c"]);
+        let names: Vec<String> = collect_input_ports(&ordered).into_iter().map(|port| port.name).collect();
+        assert_eq!(names, ["e", "c", "a", "b", "d"]);
+    }
+
+    #[test]
+    fn test_ready_valid_backpressure_stalls_every_stage() {
+        let mut graph = crate::hft::ZeroPlusStrategy::to_fpga_graph();
+        graph.enable_pipeline(1, 3, 1);
+        crate::passes::pipeline::run_pipeline_pass(&mut graph).unwrap();
+        let options = VerilogEmitOptions { backpressure: BackpressureMode::ReadyValid, ..Default::default() };
+        let verilog = generate_verilog_module(&graph, "hft_stall", Some(&options)).unwrap();
+
+        assert!(verilog.contains("input  wire                    m_axis_tready,"));
+        assert!(verilog.contains("output wire                    m_axis_tvalid,"));
+        assert!(verilog.contains("wire enable = m_axis_tready;"));
+        assert!(verilog.contains("assign m_axis_tvalid = ap_done;"));
+        assert!(verilog.contains("end else if (enable) begin\n            stage_valid <="));
+        assert!(verilog.contains("assign ap_ready = enable;"));
+        // Every stage register block holds while stalled
+        let stages = verilog.matches("always @(posedge ap_clk) begin\n        if (enable) begin").count();
+        assert_eq!(stages + 1, verilog.matches("always @(posedge ap_clk)").count());
+
+        // Without a pipeline there is nothing to stall
+        let combinational = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        assert!(matches!(generate_verilog_module(&combinational, "adder", Some(&options)), Err(HlsError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_bound_multiplies_share_one_multiplier_behind_a_slot_mux() {
+        let product = |a: &str, b: &str| mul(input(a, 16), input(b, 16));
+        let sum = add(add(add(product("a", "b"), product("c", "d")), product("e", "f")), product("g", "h"));
+        let mut graph = lower_expr_to_graph(&output("result", sum));
+        graph.enable_pipeline(4, 24, 1);
+        let mut scheduler = crate::passes::pipeline::PipelineScheduler::new();
+        scheduler.resource_constraints.insert("multiplier".to_string(), 1);
+        scheduler.schedule_pipeline(&mut graph).unwrap();
+        let unshared = generate_verilog_module(&graph, "four_products", None).unwrap();
+        assert_eq!(crate::passes::binding::run_binding_pass(&mut graph), 3);
+        let verilog = generate_verilog_module(&graph, "four_products", None).unwrap();
+
+        assert_eq!(unshared.matches(" * ").count(), 4);
+        assert_eq!(verilog.matches(" * ").count(), 1);
+        assert!(verilog.contains("wire [31:0] mul_unit0_p = mul_unit0_a * mul_unit0_b;"));
+        assert!(verilog.contains("4 multiplies on one multiplier, through a 4:1 input mux on ii_count"));
+        for slot in 0..4 {
+            assert!(verilog.contains(&format!("            2'd{}: begin mul_unit0_a = ", slot)));
+        }
+        assert_eq!(verilog.matches("= mul_unit0_p;  // Multiplication on shared unit 0").count(), 4);
+        // The slot count runs freely, so starts land in slot 0
+        assert!(verilog.contains("ii_count <= ii_count + 2'd1;"));
+        assert!(verilog.contains("wire ap_accept = ap_start & (ii_count == 0);"));
+        assert!(!unshared.contains("ii_count <= ii_count + 2'd1;"));
+    }
+
+    #[test]
+    fn test_initiation_interval_limits_ap_ready() {
+        let mut graph = crate::hft::ZeroPlusStrategy::to_fpga_graph();
+        graph.enable_pipeline(2, 3, 1);
+        crate::passes::pipeline::run_pipeline_pass(&mut graph).unwrap();
+        let verilog = generate_verilog_module(&graph, "hft_ii2", None).unwrap();
+
+        assert!(verilog.contains("pipeline from the schedule (II=2)"));
+        assert!(verilog.contains("reg [0:0] ii_count;"));
+        assert!(verilog.contains("wire ap_accept = ap_start & (ii_count == 0);"));
+        assert!(verilog.contains("ii_count <= 1'd1;"));
+        assert!(verilog.contains("assign ap_ready = (ii_count == 0);"));
+        assert!(verilog.contains("stage_valid <= {stage_valid[") && verilog.contains(", ap_accept};"));
+    }
+
+    #[test]
+    fn test_unsized_values_follow_the_generation_params() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let b = graph.add_node_with_output(Operation::Load("b".to_string()));
+        let sum = graph.add_node_with_output(Operation::Add(a, b));
+        graph.add_node(Operation::Store("sum".to_string(), sum));
+
+        let parameter = generate_verilog_parameterized(&graph, "adder", None, &GenerationParams::default()).unwrap();
+        assert_eq!(parameter, generate_verilog_module(&graph, "adder", None).unwrap());
+        assert!(parameter.contains("input  wire [DATA_WIDTH-1:0]  a,"));
+
+        let literal = GenerationParams { data_width: ParameterValue::Literal(16), addr_width: ParameterValue::Literal(10) };
+        let verilog = generate_verilog_parameterized(&graph, "adder", None, &literal).unwrap();
+        assert!(verilog.contains("parameter integer DATA_WIDTH = 16,\n    parameter integer ADDR_WIDTH = 10\n"));
+        assert!(verilog.contains("input  wire [15:0]  a,"));
+        // The sum keeps its carry, as if the inputs had been declared 16 bits wide
+        assert!(verilog.contains("output wire [16:0]  sum"));
+        assert!(!verilog.contains("DATA_WIDTH-1"));
+
+        let renamed = GenerationParams { data_width: ParameterValue::Parameter("WORD".to_string()), ..GenerationParams::default() };
+        let verilog = generate_verilog_parameterized(&graph, "adder", None, &renamed).unwrap();
+        assert!(verilog.contains("parameter integer WORD = 32,\n    parameter integer ADDR_WIDTH = 16\n"));
+        assert!(verilog.contains("input  wire [WORD-1:0]  a,"));
+        assert!(!verilog.contains("DATA_WIDTH"));
+        assert_eq!(rename_identifier("DATA_WIDTH FIFO_DATA_WIDTH DATA_WIDTHS", "DATA_WIDTH", "W"), "W FIFO_DATA_WIDTH DATA_WIDTHS");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "value 42 has no producer")]
+    fn test_unknown_value_reference_is_a_bug() {
+        let graph = lower_expr_to_graph(&output("result", input("a", 8)));
+        get_value_reference(crate::ir::graph::ValueId(42), &graph);
+    }
 }
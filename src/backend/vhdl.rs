@@ -0,0 +1,427 @@
+//! VHDL code generation
+//!
+//! Emits a synthesizable entity/architecture pair with the same HLS-style control
+//! interface as the Verilog backend. Internally every value is a `numeric_std`
+//! `unsigned`/`signed` signal so arithmetic matches the Verilog widening rules;
+//! ports stay `std_logic_vector` and are converted at the boundary.
+
+use crate::backend::sim::slice_bits;
+use crate::backend::verilog::{collect_input_ports, collect_output_ports, Port};
+use crate::ir::graph::{Graph, Node, Operation, ValueId};
+use std::collections::HashMap;
+
+/// Generate a VHDL entity and architecture from IR graph
+pub fn generate_vhdl_module(graph: &Graph, module_name: &str) -> String {
+    let inputs = collect_input_ports(graph);
+    let outputs = collect_output_ports(graph);
+    let latency = register_depth(graph) + 1;
+    let mut vhdl = String::new();
+
+    vhdl.push_str("-- Generated for AMD Alveo U50 - VHDL VERSION\n");
+    vhdl.push_str("library ieee;\n");
+    vhdl.push_str("use ieee.std_logic_1164.all;\n");
+    vhdl.push_str("use ieee.numeric_std.all;\n\n");
+
+    generate_entity(&mut vhdl, module_name, &inputs, &outputs);
+
+    vhdl.push_str(&format!("architecture rtl of {} is\n", module_name));
+    vhdl.push_str("    type state_t is (IDLE, COMPUTE, DONE);\n");
+    vhdl.push_str("    signal state : state_t := IDLE;\n");
+    vhdl.push_str(&format!("    signal cycles : natural range 0 to {} := 0;\n", latency));
+    vhdl.push('\n');
+    vhdl.push_str("    -- Port values as numeric types\n");
+    for port in &inputs {
+        vhdl.push_str(&format!("    signal {}_in : {};\n", port.name, numeric_type(port.width, port.signed)));
+    }
+    vhdl.push_str("    -- Intermediate computation signals\n");
+    for (node_id, node) in graph.nodes.iter().enumerate() {
+        if has_signal(node) {
+            vhdl.push_str(&format!("    signal node_{} : {};\n", node_id, numeric_type(node.output_width, node.signed)));
+        }
+    }
+    vhdl.push_str("begin\n\n");
+
+    vhdl.push_str("    -- Input conversion\n");
+    for port in &inputs {
+        let cast = if port.signed { "signed" } else { "unsigned" };
+        vhdl.push_str(&format!("    {}_in <= {}({});\n", port.name, cast, port.name));
+    }
+    vhdl.push('\n');
+
+    vhdl.push_str("    -- Combinational logic for all operations\n");
+    for (node_id, node) in graph.nodes.iter().enumerate() {
+        generate_operation_vhdl(&mut vhdl, node_id, node, graph);
+    }
+    vhdl.push('\n');
+
+    generate_control(&mut vhdl, latency);
+
+    vhdl.push_str("end architecture rtl;\n");
+    vhdl
+}
+
+/// Entity with `std_logic` control ports and `std_logic_vector` data ports
+fn generate_entity(vhdl: &mut String, module_name: &str, inputs: &[Port], outputs: &[Port]) {
+    vhdl.push_str(&format!("entity {} is\n", module_name));
+    vhdl.push_str("    generic (\n");
+    vhdl.push_str("        DATA_WIDTH : integer := 32;\n");
+    vhdl.push_str("        ADDR_WIDTH : integer := 16\n");
+    vhdl.push_str("    );\n");
+    vhdl.push_str("    port (\n");
+    vhdl.push_str("        -- Clock and Reset\n");
+    vhdl.push_str("        ap_clk   : in  std_logic;\n");
+    vhdl.push_str("        ap_rst_n : in  std_logic;\n");
+    vhdl.push_str("        -- Control signals (HLS-style)\n");
+    vhdl.push_str("        ap_start : in  std_logic;\n");
+    vhdl.push_str("        ap_done  : out std_logic;\n");
+    vhdl.push_str("        ap_idle  : out std_logic;\n");
+    vhdl.push_str("        ap_ready : out std_logic");
+
+    let ports: Vec<(&Port, &str)> = inputs.iter().map(|p| (p, "in "))
+        .chain(outputs.iter().map(|p| (p, "out")))
+        .collect();
+    for (port, direction) in ports {
+        vhdl.push_str(&format!(";\n        {} : {} {}", port.name, direction, vector_type(port.width)));
+    }
+    vhdl.push_str("\n    );\n");
+    vhdl.push_str(&format!("end entity {};\n\n", module_name));
+}
+
+/// Handshake FSM: `ap_done` rises once the deepest pipeline register has been filled
+fn generate_control(vhdl: &mut String, latency: usize) {
+    vhdl.push_str("    -- Control state machine\n");
+    vhdl.push_str("    control : process(ap_clk)\n");
+    vhdl.push_str("    begin\n");
+    vhdl.push_str("        if rising_edge(ap_clk) then\n");
+    vhdl.push_str("            if ap_rst_n = '0' then\n");
+    vhdl.push_str("                state <= IDLE;\n");
+    vhdl.push_str("                cycles <= 0;\n");
+    vhdl.push_str("            else\n");
+    vhdl.push_str("                case state is\n");
+    vhdl.push_str("                    when IDLE =>\n");
+    vhdl.push_str("                        if ap_start = '1' then\n");
+    vhdl.push_str("                            state <= COMPUTE;\n");
+    vhdl.push_str("                            cycles <= 1;\n");
+    vhdl.push_str("                        end if;\n");
+    vhdl.push_str("                    when COMPUTE =>\n");
+    vhdl.push_str(&format!("                        if cycles = {} then\n", latency));
+    vhdl.push_str("                            state <= DONE;\n");
+    vhdl.push_str("                        else\n");
+    vhdl.push_str("                            cycles <= cycles + 1;\n");
+    vhdl.push_str("                        end if;\n");
+    vhdl.push_str("                    when DONE =>\n");
+    vhdl.push_str("                        state <= IDLE;\n");
+    vhdl.push_str("                end case;\n");
+    vhdl.push_str("            end if;\n");
+    vhdl.push_str("        end if;\n");
+    vhdl.push_str("    end process control;\n\n");
+    vhdl.push_str("    ap_done <= '1' when state = DONE else '0';\n");
+    vhdl.push_str("    ap_idle <= '1' when state = IDLE else '0';\n");
+    vhdl.push_str("    ap_ready <= '1' when state = IDLE else '0';\n\n");
+}
+
+/// Generate VHDL for a specific operation
+fn generate_operation_vhdl(vhdl: &mut String, node_id: usize, node: &Node, graph: &Graph) {
+    let width = node.output_width;
+    let signed = node.signed;
+    let r = |value: &ValueId| operand(*value, width, signed, graph);
+    let cast = |value: &ValueId| cast_to(*value, signed, graph);
+    let flag = |a: &ValueId, b: &ValueId, op: &str| {
+        let (a_val, b_val) = comparison_operands(*a, *b, graph);
+        format!("\"1\" when {} {} {} else \"0\"", a_val, op, b_val)
+    };
+
+    let expression = match &node.op {
+        // Arithmetic operations
+        // The signed variants produce a signed node, so `r`/`cast` already reinterpret their operands
+        Operation::Add(a, b) | Operation::SAdd(a, b) => format!("{} + {}", r(a), r(b)),
+        Operation::Sub(a, b) | Operation::SSub(a, b) => format!("{} - {}", r(a), r(b)),
+        Operation::Mul(a, b) | Operation::SMul(a, b) => format!("resize({} * {}, {})", cast(a), cast(b), length(width)),
+        Operation::Fma(a, b, c) => format!("resize({} * {}, {}) + {}", cast(a), cast(b), length(width), r(c)),
+        Operation::Div(a, b) => format!("resize({} / {}, {})", cast(a), cast(b), length(width)),
+        Operation::AddSat(_, _, _) | Operation::SubSat(_, _, _) => saturating_expression(node, graph),
+
+        // Comparison operations
+        Operation::CmpLt(a, b) => flag(a, b, "<"),
+        Operation::CmpGt(a, b) => flag(a, b, ">"),
+        Operation::CmpEq(a, b) => flag(a, b, "="),
+        Operation::CmpGe(a, b) => flag(a, b, ">="),
+        Operation::CmpLe(a, b) => flag(a, b, "<="),
+        Operation::CmpNe(a, b) => flag(a, b, "/="),
+
+        // Bitwise operations
+        Operation::And(a, b) => format!("{} and {}", r(a), r(b)),
+        Operation::Or(a, b) => format!("{} or {}", r(a), r(b)),
+        Operation::Xor(a, b) => format!("{} xor {}", r(a), r(b)),
+        Operation::Nand(a, b) => format!("{} nand {}", r(a), r(b)),
+        Operation::Nor(a, b) => format!("{} nor {}", r(a), r(b)),
+        Operation::Xnor(a, b) => format!("{} xnor {}", r(a), r(b)),
+        Operation::Not(a) => format!("not {}", r(a)),
+
+        // Bit fields: plain unsigned vectors, with constant slices folded
+        Operation::Concat(high, low) => format!("{} & {}", cast_to(*high, false, graph), cast_to(*low, false, graph)),
+        Operation::Slice(a, high, low) => match graph.producer(*a).map(|producer| &producer.op) {
+            Some(Operation::Const(val)) => format!("to_unsigned({}, {})", slice_bits(*val, *high, *low), length(width)),
+            _ => format!("unsigned({}({} downto {}))", value_reference(*a, graph), high, low),
+        },
+
+        // Conditional and utility operations
+        Operation::Mux(cond, a, b) => {
+            format!("{} when {} /= 0 else {}", r(a), value_reference(*cond, graph), r(b))
+        }
+        Operation::Abs(a) => {
+            let as_signed = format!("signed({})", value_reference(*a, graph));
+            let abs = format!("abs({})", if graph.value_signed(*a) { value_reference(*a, graph) } else { as_signed });
+            let abs = if signed { abs } else { format!("unsigned({})", abs) };
+            format!("resize({}, {})", abs, length(width))
+        }
+        Operation::Min(a, b) | Operation::Max(a, b) => {
+            let (a_val, b_val) = comparison_operands(*a, *b, graph);
+            let op = if matches!(node.op, Operation::Min(_, _)) { "<" } else { ">" };
+            format!("{} when {} {} {} else {}", r(a), a_val, op, b_val, r(b))
+        }
+
+        // Shifts: the amount is unsigned and `>>` is always logical
+        Operation::Shl(a, b) => format!("shift_left({}, {})", r(a), shift_amount(*b, graph)),
+        Operation::Shr(a, b) if signed => {
+            format!("signed(shift_right(unsigned({}), {}))", r(a), shift_amount(*b, graph))
+        }
+        Operation::Shr(a, b) => format!("shift_right({}, {})", r(a), shift_amount(*b, graph)),
+
+        // Output assignment
+        Operation::Store(name, value) => {
+            let port_width = graph.value_width(*value);
+            let port_value = operand(*value, port_width, graph.value_signed(*value), graph);
+            vhdl.push_str(&format!("    {} <= std_logic_vector({});  -- Output assignment\n", name, port_value));
+            return;
+        }
+
+        // Pipeline registers are clocked with a synchronous reset
+        Operation::PipelineRegister(a) => {
+            vhdl.push_str(&format!("    pipeline_reg_{} : process(ap_clk)\n", node_id));
+            vhdl.push_str("    begin\n");
+            vhdl.push_str("        if rising_edge(ap_clk) then\n");
+            vhdl.push_str("            if ap_rst_n = '0' then\n");
+            vhdl.push_str(&format!("                node_{} <= (others => '0');\n", node_id));
+            vhdl.push_str("            else\n");
+            vhdl.push_str(&format!("                node_{} <= {};\n", node_id, r(a)));
+            vhdl.push_str("            end if;\n");
+            vhdl.push_str("        end if;\n");
+            vhdl.push_str(&format!("    end process pipeline_reg_{};\n", node_id));
+            return;
+        }
+
+        // Balanced adder tree, resized to the result like the binary adds
+        Operation::ReduceAdd(values) => {
+            let mut terms: Vec<String> = values.iter().map(r).collect();
+            while terms.len() > 1 {
+                terms = terms.chunks(2).map(|pair| match pair {
+                    [a, b] => format!("({} + {})", a, b),
+                    _ => pair[0].clone(),
+                }).collect();
+            }
+            terms.pop().unwrap_or_else(|| format!("to_unsigned(0, {})", length(width)))
+        }
+        Operation::LoopIndex | Operation::LoopCarry(_, _) | Operation::LoopNext(_, _) => {
+            vhdl.push_str(&format!("    -- node_{}: rolled loops are only supported by the Verilog backend\n", node_id));
+            return;
+        }
+        Operation::RegisterLoad(_) | Operation::RegisterStore(_, _) => {
+            vhdl.push_str(&format!("    -- node_{}: state registers are only supported by the Verilog backend\n", node_id));
+            return;
+        }
+        Operation::ReduceMax(_) | Operation::ReduceMin(_) => {
+            vhdl.push_str(&format!("    -- node_{}: comparator trees are only supported by the Verilog backend\n", node_id));
+            return;
+        }
+        Operation::Sqrt(_) => {
+            vhdl.push_str(&format!("    -- node_{}: square roots are only supported by the Verilog backend\n", node_id));
+            return;
+        }
+        Operation::Call(_, _) | Operation::CallResult(_, _) => {
+            vhdl.push_str(&format!("    -- node_{}: module instances are only supported by the Verilog backend\n", node_id));
+            return;
+        }
+
+        Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) => {
+            vhdl.push_str(&format!("    -- node_{}: array accesses are only supported by the Verilog backend\n", node_id));
+            return;
+        }
+
+        // Inputs and constants are referenced directly
+        Operation::Load(_) | Operation::Const(_) => return,
+        Operation::PipelineBarrier | Operation::Nop => return,
+    };
+
+    vhdl.push_str(&format!("    node_{} <= {};\n", node_id, expression));
+}
+
+/// Clamped `AddSat`/`SubSat` result
+///
+/// The exact result is kept one bit wider than the operands and the result;
+/// it saturated when it does not survive a round trip through the result
+/// width. The sign of `b` then tells an overflow from an underflow, and
+/// unsigned subtractions clamp to zero whenever `a < b`.
+fn saturating_expression(node: &Node, graph: &Graph) -> String {
+    let (Operation::AddSat(a, b, width) | Operation::SubSat(a, b, width)) = node.op else {
+        unreachable!("only saturating operations are clamped")
+    };
+    let subtract = matches!(node.op, Operation::SubSat(_, _, _));
+    let operand_width = |value| graph.value_width(value).unwrap_or(32);
+    let exact_width = Some(operand_width(a).max(operand_width(b)).max(width) + 1);
+    let exact = format!(
+        "({} {} {})",
+        operand(a, exact_width, node.signed, graph),
+        if subtract { "-" } else { "+" },
+        operand(b, exact_width, node.signed, graph)
+    );
+    let result = format!("resize({}, {})", exact, width);
+    let fits = format!("resize({}, {}) = {}", result, length(exact_width), exact);
+    let (max, min) = if node.signed {
+        (format!("({} => '0', others => '1')", width - 1), format!("({} => '1', others => '0')", width - 1))
+    } else {
+        ("(others => '1')".to_string(), "(others => '0')".to_string())
+    };
+    let (a_val, b_val) = (cast_to(a, node.signed, graph), cast_to(b, node.signed, graph));
+    match (node.signed, subtract) {
+        (true, false) => format!("{} when {} else {} when {} >= 0 else {}", result, fits, max, b_val, min),
+        (true, true) => format!("{} when {} else {} when {} < 0 else {}", result, fits, max, b_val, min),
+        (false, false) => format!("{} when {} else {}", result, fits, max),
+        (false, true) => format!("{} when {} < {} else {} when {} else {}", min, a_val, b_val, result, fits, max),
+    }
+}
+
+/// Nodes that get an internal signal (ports and constants are referenced inline)
+fn has_signal(node: &Node) -> bool {
+    node.output.is_some() && !matches!(node.op, Operation::Load(_) | Operation::Const(_))
+}
+
+/// Reference to a value in its own numeric type
+fn value_reference(value: ValueId, graph: &Graph) -> String {
+    for (node_id, node) in graph.nodes.iter().enumerate() {
+        if node.output != Some(value) {
+            continue;
+        }
+        return match &node.op {
+            Operation::Load(name) => format!("{}_in", name),
+            Operation::Const(val) => {
+                let conversion = if node.signed { "to_signed" } else { "to_unsigned" };
+                format!("{}({}, {})", conversion, val, length(node.output_width))
+            }
+            _ => format!("node_{}", node_id),
+        };
+    }
+
+    // Fallback
+    "to_unsigned(0, DATA_WIDTH)".to_string()
+}
+
+/// Reinterpret a value as the requested signedness, keeping its length
+fn cast_to(value: ValueId, signed: bool, graph: &Graph) -> String {
+    let reference = value_reference(value, graph);
+    match (graph.value_signed(value), signed) {
+        (false, true) => format!("signed({})", reference),
+        (true, false) => format!("unsigned({})", reference),
+        _ => reference,
+    }
+}
+
+/// Operand converted to the consumer's signedness and resized to its width
+///
+/// Casting happens before resizing, so a signed value feeding an unsigned
+/// operation is zero-extended exactly like a mixed Verilog expression.
+fn operand(value: ValueId, width: Option<u32>, signed: bool, graph: &Graph) -> String {
+    let cast = cast_to(value, signed, graph);
+    // Unknown widths on both sides are both DATA_WIDTH
+    if graph.value_width(value) == width {
+        cast
+    } else {
+        format!("resize({}, {})", cast, length(width))
+    }
+}
+
+/// Operands of a comparison: signed only if both sides are signed
+fn comparison_operands(a: ValueId, b: ValueId, graph: &Graph) -> (String, String) {
+    let signed = graph.value_signed(a) && graph.value_signed(b);
+    (cast_to(a, signed, graph), cast_to(b, signed, graph))
+}
+
+/// Shift amount as a natural number
+fn shift_amount(value: ValueId, graph: &Graph) -> String {
+    format!("to_integer({})", cast_to(value, false, graph))
+}
+
+/// `unsigned(7 downto 0)` / `signed(...)` for internal signals
+fn numeric_type(width: Option<u32>, signed: bool) -> String {
+    format!("{}({} downto 0)", if signed { "signed" } else { "unsigned" }, msb(width))
+}
+
+/// `std_logic_vector(7 downto 0)` for ports, falling back to the DATA_WIDTH generic
+fn vector_type(width: Option<u32>) -> String {
+    format!("std_logic_vector({} downto 0)", msb(width))
+}
+
+fn msb(width: Option<u32>) -> String {
+    match width {
+        Some(width) => width.saturating_sub(1).to_string(),
+        None => "DATA_WIDTH-1".to_string(),
+    }
+}
+
+fn length(width: Option<u32>) -> String {
+    match width {
+        Some(width) => width.to_string(),
+        None => "DATA_WIDTH".to_string(),
+    }
+}
+
+/// Longest chain of pipeline registers from any input to any output
+fn register_depth(graph: &Graph) -> usize {
+    let mut depth: HashMap<ValueId, usize> = HashMap::new();
+    let mut deepest = 0;
+    for node in &graph.nodes {
+        let operand_depth = node.op.operands().iter()
+            .map(|value| depth.get(value).copied().unwrap_or(0))
+            .max()
+            .unwrap_or(0);
+        let node_depth = operand_depth + matches!(node.op, Operation::PipelineRegister(_)) as usize;
+        if let Some(output) = node.output {
+            depth.insert(output, node_depth);
+        }
+        deepest = deepest.max(node_depth);
+    }
+    deepest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+
+    #[test]
+    fn test_vhdl_ports_and_arithmetic() {
+        let expr = output("result", add(mul(input("a", 8), input("b", 8)), const_val(3, 8)));
+        let vhdl = generate_vhdl_module(&lower_expr_to_graph(&expr), "mac8");
+
+        assert!(vhdl.contains("ap_clk   : in  std_logic;"));
+        assert!(vhdl.contains("a : in  std_logic_vector(7 downto 0)"));
+        assert!(vhdl.contains("result : out std_logic_vector(16 downto 0)"));
+        assert!(vhdl.contains("node_2 <= resize(a_in * b_in, 16);"));
+        assert!(vhdl.contains("node_4 <= resize(node_2, 17) + resize(to_unsigned(3, 8), 17);"));
+        assert!(vhdl.contains("result <= std_logic_vector(node_4);"));
+    }
+
+    #[test]
+    fn test_vhdl_pipeline_register_process() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output_width(Operation::Load("a".to_string()), 8);
+        let reg = graph.insert_pipeline_register(a);
+        graph.add_node(Operation::Store("result".to_string(), reg));
+        let vhdl = generate_vhdl_module(&graph, "delay");
+
+        assert!(vhdl.contains("pipeline_reg_1 : process(ap_clk)"));
+        assert!(vhdl.contains("if ap_rst_n = '0' then\n                node_1 <= (others => '0');"));
+        assert!(vhdl.contains("if cycles = 2 then"));
+    }
+}
@@ -0,0 +1,535 @@
+//! Vivado project scripts
+//!
+//! Emits a batch-mode TCL script that creates a project for the generated
+//! sources, constrains the kernel clock and runs synthesis through to the
+//! utilization and timing reports:
+//!
+//! ```text
+//! cd target/vivado/adder && vivado -mode batch -source build.tcl
+//! ```
+//!
+//! Kernels can also be packaged as IP for a block design: a directory with
+//! the Verilog under `hdl/` and an IP-XACT (IEEE 1685-2009) `component.xml`,
+//! which Vivado picks up once the directory is added as an IP repository.
+
+use crate::backend::axi::{axi_lite_address_width, axi_lite_top_ports, axi_stream_kernel_ports, generate_axilite_top};
+use crate::backend::verilog::{
+    collect_input_ports, collect_output_ports, generate_verilog_module, get_value_reference, InterfaceStyle, Port, VerilogEmitOptions,
+};
+use crate::error::HlsError;
+use crate::ir::graph::{Graph, Operation};
+use crate::passes::timing::{schedule_of, ROUTING_OVERHEAD};
+use std::path::{Path, PathBuf};
+
+/// Alveo U50 part, used unless another is given
+pub const ALVEO_U50_PART: &str = "xcu50-fsvh2104-2-e";
+
+/// Clock constraint for the generated XDC
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockConstraint {
+    pub port: String,
+    pub period_ns: f64,
+}
+
+impl Default for ClockConstraint {
+    /// `ap_clk` at the 300 MHz Alveo kernel clock
+    fn default() -> Self {
+        Self { port: "ap_clk".to_string(), period_ns: 3.333 }
+    }
+}
+
+/// XDC constraining the kernel clock
+pub fn generate_xdc(module_name: &str, clock: &ClockConstraint) -> String {
+    let mut xdc = String::new();
+    xdc.push_str(&format!("# Timing constraints for {}\n", module_name));
+    xdc.push_str(&format!("create_clock -period {:.3} -name {} [get_ports {}]\n", clock.period_ns, clock.port, clock.port));
+    xdc
+}
+
+/// XDC for a scheduled graph: the clock, I/O delays and multicycle paths
+///
+/// The clock on `clock_port` is always named `ap_clk`, and ports are named as
+/// the Verilog backend emits them. Logic outside the kernel is assumed to use
+/// the routing share of one stage period, so that is the input and output
+/// delay. A value read directly by an operation two or more stages after it
+/// is produced, with no `PipelineRegister` in between, gets a multicycle
+/// path; the scheduler registers such reads itself, so these come from
+/// hand-built schedules.
+pub fn generate_timing_xdc(graph: &Graph, module_name: &str, target_freq_mhz: f64, clock_port: &str) -> String {
+    let period_ns = 1000.0 / target_freq_mhz;
+    let io_delay_ns = period_ns * ROUTING_OVERHEAD;
+    let mut xdc = format!("# Timing constraints for {}\n", module_name);
+    xdc.push_str(&format!("create_clock -period {:.3} -name ap_clk [get_ports {}]\n", period_ns, clock_port));
+
+    let inputs = collect_input_ports(graph);
+    let outputs = collect_output_ports(graph);
+    if !inputs.is_empty() || !outputs.is_empty() {
+        xdc.push_str("\n# I/O delays: one stage period less the kernel's share\n");
+    }
+    for port in &inputs {
+        xdc.push_str(&format!("set_input_delay -clock ap_clk {:.3} {}\n", io_delay_ns, port_pattern(port)));
+    }
+    for port in &outputs {
+        xdc.push_str(&format!("set_output_delay -clock ap_clk {:.3} {}\n", io_delay_ns, port_pattern(port)));
+    }
+
+    let schedule = schedule_of(graph);
+    let registered: Vec<_> = graph.nodes.iter()
+        .filter_map(|node| match node.op {
+            Operation::PipelineRegister(value) => Some(value),
+            _ => None,
+        })
+        .collect();
+    let mut multicycle = Vec::new();
+    for node in &graph.nodes {
+        let Some(&consumed) = schedule.get(&node.id) else { continue };
+        if matches!(node.op, Operation::PipelineRegister(_)) {
+            continue;
+        }
+        for operand in node.op.operands() {
+            let Some(producer) = graph.producer(operand) else { continue };
+            if matches!(producer.op, Operation::Const(_) | Operation::Load(_) | Operation::PipelineRegister(_)) || registered.contains(&operand) {
+                continue;
+            }
+            let Some(&produced) = schedule.get(&producer.id) else { continue };
+            if consumed > produced + 1 {
+                multicycle.push((get_value_reference(operand, graph), consumed - produced));
+            }
+        }
+    }
+    multicycle.sort();
+    multicycle.dedup();
+    if !multicycle.is_empty() {
+        xdc.push_str("\n# Values read without a pipeline register, several stages after they are produced\n");
+    }
+    for (signal, cycles) in multicycle {
+        let cells = format!("[get_cells -hierarchical -filter {{NAME =~ *{}*}}]", signal);
+        xdc.push_str(&format!("set_multicycle_path -setup {} -from {}\n", cycles, cells));
+        xdc.push_str(&format!("set_multicycle_path -hold {} -from {}\n", cycles - 1, cells));
+    }
+    xdc
+}
+
+/// `[get_ports a]`, or `[get_ports {a[*]}]` for buses
+fn port_pattern(port: &Port) -> String {
+    match port.width {
+        Some(1) => format!("[get_ports {}]", port.name),
+        _ => format!("[get_ports {{{}[*]}}]", port.name),
+    }
+}
+
+/// Batch-mode synthesis script for `module_name` on `part`
+///
+/// Files named `*_tb.*` go into the simulation fileset; every other file in
+/// `files` is a design source. `constraints` are XDC files.
+pub fn generate_vivado_tcl(module_name: &str, part: &str, files: &[PathBuf], constraints: &[PathBuf]) -> String {
+    let (testbenches, sources): (Vec<&PathBuf>, Vec<&PathBuf>) = files.iter().partition(|file| is_testbench(file));
+    let mut tcl = String::new();
+
+    tcl.push_str(&format!("# Vivado synthesis script for {}\n", module_name));
+    tcl.push_str("# Run with: vivado -mode batch -source build.tcl\n\n");
+    tcl.push_str(&format!("create_project -force {0} ./{0}_project -part {1}\n\n", module_name, part));
+
+    push_add_files(&mut tcl, "sources_1", &sources);
+    push_add_files(&mut tcl, "sim_1", &testbenches);
+    push_add_files(&mut tcl, "constrs_1", &constraints.iter().collect::<Vec<_>>());
+    tcl.push_str(&format!("set_property top {} [get_filesets sources_1]\n", module_name));
+    tcl.push_str("update_compile_order -fileset sources_1\n\n");
+
+    tcl.push_str(&format!("synth_design -top {} -part {}\n", module_name, part));
+    tcl.push_str("opt_design\n");
+    tcl.push_str(&format!("report_utilization -file {}_utilization.rpt\n", module_name));
+    tcl.push_str(&format!("report_timing -max_paths 10 -file {}_timing.rpt\n", module_name));
+    tcl
+}
+
+/// Synthesis script for one generated source, e.g. `target/verilog_out/adder.v`
+///
+/// A `<module>.xdc` next to the source, or in the `target/vivado/<module>`
+/// project directory that `VerilatorSim::write_vivado_project` fills, is
+/// added as the clock constraint when it exists.
+pub fn generate_vivado_tcl_for_source(module_name: &str, verilog_path: &Path, part: &str) -> String {
+    let xdc = format!("{}.xdc", module_name);
+    let mut candidates = vec![verilog_path.with_file_name(&xdc)];
+    if let Some(base_dir) = verilog_path.parent().and_then(Path::parent) {
+        candidates.push(base_dir.join("vivado").join(module_name).join(&xdc));
+    }
+    let constraints: Vec<PathBuf> = candidates.into_iter().filter(|path| path.exists()).take(1).collect();
+    generate_vivado_tcl(module_name, part, &[verilog_path.to_path_buf()], &constraints)
+}
+
+/// `add_files` for one fileset, one path per line; nothing when `files` is empty
+fn push_add_files(tcl: &mut String, fileset: &str, files: &[&PathBuf]) {
+    if files.is_empty() {
+        return;
+    }
+    tcl.push_str(&format!("add_files -fileset {} -norecurse {{\n", fileset));
+    for file in files {
+        tcl.push_str(&format!("    {{{}}}\n", file.display()));
+    }
+    tcl.push_str("}\n");
+}
+
+fn is_testbench(file: &Path) -> bool {
+    file.file_stem().and_then(|stem| stem.to_str()).is_some_and(|stem| stem.ends_with("_tb"))
+}
+
+/// Vendor and library of packaged IP, as in `rust_hls:hls:<module>:1.0`
+const IP_VENDOR: &str = "rust_hls";
+const IP_LIBRARY: &str = "hls";
+const IP_VERSION: &str = "1.0";
+
+const MASTER: &str = "<spirit:master/>";
+const SLAVE: &str = "<spirit:slave/>";
+
+/// Package the AXI4-Stream kernel for `graph` as Vivado IP in `output_dir`
+///
+/// Writes the `generate_axi_stream_kernel` top level to `hdl/<module>.v` and
+/// a `component.xml` declaring its `s_axis` slave, `m_axis` master, clock
+/// and active-low reset, so the kernel can be added to a block design from
+/// an IP repository pointing at `output_dir`.
+pub fn generate_xilinx_ip_package(module_name: &str, graph: &Graph, output_dir: &Path) -> Result<(), HlsError> {
+    let options = VerilogEmitOptions { interface: InterfaceStyle::AxiStream, ..Default::default() };
+    let verilog = generate_verilog_module(graph, module_name, Some(&options))?;
+    let buses = [
+        axi_stream_bus("s_axis", false),
+        axi_stream_bus("m_axis", true),
+        clock_bus("s_axis:m_axis"),
+        reset_bus(),
+    ];
+    write_ip_package(module_name, &verilog, &axi_stream_kernel_ports(graph), &buses, None, output_dir)
+}
+
+/// Package the `{module}_axilite` top level, controlled over `s_axi_control`
+///
+/// Arguments and the `ap_*` handshake are registers in the Vivado HLS
+/// layout described in `backend::axi`; the bus gets a memory map covering
+/// the register file.
+pub fn generate_xilinx_ip_package_with_control(module_name: &str, graph: &Graph, output_dir: &Path) -> Result<(), HlsError> {
+    let top = format!("{}_axilite", module_name);
+    let verilog = generate_axilite_top(graph, module_name)?;
+    let ports = axi_lite_top_ports(graph);
+    let buses = [axi_lite_bus(&ports), clock_bus("s_axi_control"), reset_bus()];
+    write_ip_package(&top, &verilog, &ports, &buses, Some(1 << axi_lite_address_width(graph)), output_dir)
+}
+
+/// Write `hdl/<top>.v` and its `component.xml`
+///
+/// `register_bytes` adds the `s_axi_control` memory map.
+fn write_ip_package(
+    top: &str, verilog: &str, ports: &[(String, bool, u32)], buses: &[String], register_bytes: Option<u32>, output_dir: &Path,
+) -> Result<(), HlsError> {
+    let source = format!("hdl/{}.v", top);
+    std::fs::create_dir_all(output_dir.join("hdl"))?;
+    std::fs::write(output_dir.join(&source), verilog)?;
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<spirit:component xmlns:xilinx=\"http://www.xilinx.com\" xmlns:spirit=\"http://www.spiritconsortium.org/XMLSchema/SPIRIT/1685-2009\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n");
+    xml.push_str(&format!("  <spirit:vendor>{}</spirit:vendor>\n", IP_VENDOR));
+    xml.push_str(&format!("  <spirit:library>{}</spirit:library>\n", IP_LIBRARY));
+    xml.push_str(&format!("  <spirit:name>{}</spirit:name>\n", top));
+    xml.push_str(&format!("  <spirit:version>{}</spirit:version>\n", IP_VERSION));
+
+    xml.push_str("  <spirit:busInterfaces>\n");
+    for bus in buses {
+        xml.push_str(bus);
+    }
+    xml.push_str("  </spirit:busInterfaces>\n");
+    if let Some(bytes) = register_bytes {
+        xml.push_str("  <spirit:memoryMaps>\n");
+        xml.push_str("    <spirit:memoryMap>\n");
+        xml.push_str("      <spirit:name>s_axi_control</spirit:name>\n");
+        xml.push_str("      <spirit:addressBlock>\n");
+        xml.push_str("        <spirit:name>Reg</spirit:name>\n");
+        xml.push_str("        <spirit:baseAddress spirit:format=\"long\">0</spirit:baseAddress>\n");
+        xml.push_str(&format!("        <spirit:range spirit:format=\"long\">{}</spirit:range>\n", bytes));
+        xml.push_str("        <spirit:width spirit:format=\"long\">32</spirit:width>\n");
+        xml.push_str("        <spirit:usage>register</spirit:usage>\n");
+        xml.push_str("      </spirit:addressBlock>\n");
+        xml.push_str("    </spirit:memoryMap>\n");
+        xml.push_str("  </spirit:memoryMaps>\n");
+    }
+
+    xml.push_str("  <spirit:model>\n");
+    xml.push_str("    <spirit:views>\n");
+    xml.push_str("      <spirit:view>\n");
+    xml.push_str("        <spirit:name>xilinx_verilogsynthesis</spirit:name>\n");
+    xml.push_str("        <spirit:displayName>Verilog Synthesis</spirit:displayName>\n");
+    xml.push_str("        <spirit:envIdentifier>verilogSource:vivado.xilinx.com:synthesis</spirit:envIdentifier>\n");
+    xml.push_str("        <spirit:language>verilog</spirit:language>\n");
+    xml.push_str(&format!("        <spirit:modelName>{}</spirit:modelName>\n", top));
+    xml.push_str("        <spirit:fileSetRef>\n");
+    xml.push_str("          <spirit:localName>xilinx_verilogsynthesis_view_fileset</spirit:localName>\n");
+    xml.push_str("        </spirit:fileSetRef>\n");
+    xml.push_str("      </spirit:view>\n");
+    xml.push_str("    </spirit:views>\n");
+    xml.push_str("    <spirit:ports>\n");
+    for (name, input, width) in ports {
+        xml.push_str("      <spirit:port>\n");
+        xml.push_str(&format!("        <spirit:name>{}</spirit:name>\n", name));
+        xml.push_str("        <spirit:wire>\n");
+        xml.push_str(&format!("          <spirit:direction>{}</spirit:direction>\n", if *input { "in" } else { "out" }));
+        if *width > 1 {
+            xml.push_str(&format!(
+                "          <spirit:vector>\n            <spirit:left spirit:format=\"long\">{}</spirit:left>\n            <spirit:right spirit:format=\"long\">0</spirit:right>\n          </spirit:vector>\n",
+                width - 1
+            ));
+        }
+        xml.push_str("        </spirit:wire>\n");
+        xml.push_str("      </spirit:port>\n");
+    }
+    xml.push_str("    </spirit:ports>\n");
+    xml.push_str("  </spirit:model>\n");
+
+    xml.push_str("  <spirit:fileSets>\n");
+    xml.push_str("    <spirit:fileSet>\n");
+    xml.push_str("      <spirit:name>xilinx_verilogsynthesis_view_fileset</spirit:name>\n");
+    xml.push_str("      <spirit:file>\n");
+    xml.push_str(&format!("        <spirit:name>{}</spirit:name>\n", source));
+    xml.push_str("        <spirit:fileType>verilogSource</spirit:fileType>\n");
+    xml.push_str("      </spirit:file>\n");
+    xml.push_str("    </spirit:fileSet>\n");
+    xml.push_str("  </spirit:fileSets>\n");
+
+    xml.push_str(&format!("  <spirit:description>{} generated by rust_hls</spirit:description>\n", top));
+    xml.push_str("  <spirit:parameters>\n");
+    xml.push_str("    <spirit:parameter>\n");
+    xml.push_str("      <spirit:name>Component_Name</spirit:name>\n");
+    xml.push_str(&format!(
+        "      <spirit:value spirit:resolve=\"user\" spirit:id=\"PARAM_VALUE.Component_Name\" spirit:order=\"1\">{}_v1_0</spirit:value>\n",
+        top
+    ));
+    xml.push_str("    </spirit:parameter>\n");
+    xml.push_str("  </spirit:parameters>\n");
+    xml.push_str("  <spirit:vendorExtensions>\n");
+    xml.push_str("    <xilinx:coreExtensions>\n");
+    xml.push_str("      <xilinx:supportedFamilies>\n");
+    xml.push_str("        <xilinx:family xilinx:lifeCycle=\"Production\">virtexuplusHBM</xilinx:family>\n");
+    xml.push_str("      </xilinx:supportedFamilies>\n");
+    xml.push_str("      <xilinx:taxonomies>\n");
+    xml.push_str("        <xilinx:taxonomy>/UserIP</xilinx:taxonomy>\n");
+    xml.push_str("      </xilinx:taxonomies>\n");
+    xml.push_str(&format!("      <xilinx:displayName>{}</xilinx:displayName>\n", top));
+    xml.push_str("    </xilinx:coreExtensions>\n");
+    xml.push_str("    <xilinx:packagingInfo>\n");
+    xml.push_str("      <xilinx:xilinxVersion>2023.2</xilinx:xilinxVersion>\n");
+    xml.push_str("    </xilinx:packagingInfo>\n");
+    xml.push_str("  </spirit:vendorExtensions>\n");
+    xml.push_str("</spirit:component>\n");
+
+    std::fs::write(output_dir.join("component.xml"), xml)?;
+    Ok(())
+}
+
+/// `<spirit:busInterface>` of a Xilinx interface type, mapping logical to physical ports
+///
+/// `role` is the `<spirit:master/>` or `<spirit:slave>` element.
+fn bus_interface(name: &str, kind: (&str, &str), role: &str, ports: &[(&str, String)], parameters: &[(&str, &str)]) -> String {
+    let mut xml = String::new();
+    xml.push_str("    <spirit:busInterface>\n");
+    xml.push_str(&format!("      <spirit:name>{}</spirit:name>\n", name));
+    xml.push_str(&format!(
+        "      <spirit:busType spirit:vendor=\"xilinx.com\" spirit:library=\"interface\" spirit:name=\"{}\" spirit:version=\"1.0\"/>\n",
+        kind.0
+    ));
+    xml.push_str(&format!(
+        "      <spirit:abstractionType spirit:vendor=\"xilinx.com\" spirit:library=\"interface\" spirit:name=\"{}\" spirit:version=\"1.0\"/>\n",
+        kind.1
+    ));
+    xml.push_str(&format!("      {}\n", role));
+    xml.push_str("      <spirit:portMaps>\n");
+    for (logical, physical) in ports {
+        xml.push_str("        <spirit:portMap>\n");
+        xml.push_str(&format!("          <spirit:logicalPort>\n            <spirit:name>{}</spirit:name>\n          </spirit:logicalPort>\n", logical));
+        xml.push_str(&format!("          <spirit:physicalPort>\n            <spirit:name>{}</spirit:name>\n          </spirit:physicalPort>\n", physical));
+        xml.push_str("        </spirit:portMap>\n");
+    }
+    xml.push_str("      </spirit:portMaps>\n");
+    if !parameters.is_empty() {
+        xml.push_str("      <spirit:parameters>\n");
+        for (parameter, value) in parameters {
+            xml.push_str(&format!(
+                "        <spirit:parameter>\n          <spirit:name>{}</spirit:name>\n          <spirit:value>{}</spirit:value>\n        </spirit:parameter>\n",
+                parameter, value
+            ));
+        }
+        xml.push_str("      </spirit:parameters>\n");
+    }
+    xml.push_str("    </spirit:busInterface>\n");
+    xml
+}
+
+fn axi_stream_bus(name: &str, master: bool) -> String {
+    let ports: Vec<(&str, String)> = ["TDATA", "TVALID", "TREADY", "TLAST"].iter()
+        .map(|signal| (*signal, format!("{}_{}", name, signal.to_lowercase())))
+        .collect();
+    bus_interface(name, ("axis", "axis_rtl"), if master { MASTER } else { SLAVE }, &ports, &[])
+}
+
+/// `s_axi_control`, mapped onto the register file's memory map
+fn axi_lite_bus(ports: &[(String, bool, u32)]) -> String {
+    let ports: Vec<(&str, String)> = ports.iter()
+        .filter_map(|(name, _, _)| Some((name.strip_prefix("s_axi_control_")?, name.clone())))
+        .collect();
+    let role = "<spirit:slave>\n        <spirit:memoryMapRef spirit:memoryMapRef=\"s_axi_control\"/>\n      </spirit:slave>";
+    bus_interface("s_axi_control", ("aximm", "aximm_rtl"), role, &ports, &[("PROTOCOL", "AXI4LITE")])
+}
+
+/// `ap_clk`, clocking the `:`-separated `buses`
+fn clock_bus(buses: &str) -> String {
+    bus_interface("ap_clk", ("clock", "clock_rtl"), SLAVE, &[("CLK", "ap_clk".to_string())], &[("ASSOCIATED_BUSIF", buses), ("ASSOCIATED_RESET", "ap_rst_n")])
+}
+
+fn reset_bus() -> String {
+    bus_interface("ap_rst_n", ("reset", "reset_rtl"), SLAVE, &[("RST", "ap_rst_n".to_string())], &[("POLARITY", "ACTIVE_LOW")])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::graph::{NodeId, PipelineStage};
+
+    #[test]
+    fn test_tcl_for_sample_module() {
+        let files = [PathBuf::from("/work/verilog_out/adder.v"), PathBuf::from("/work/verilog_out/adder_tb.v")];
+        let constraints = [PathBuf::from("/work/vivado/adder/adder.xdc")];
+        let tcl = generate_vivado_tcl("adder", ALVEO_U50_PART, &files, &constraints);
+
+        assert_eq!(tcl, "\
+# Vivado synthesis script for adder
+# Run with: vivado -mode batch -source build.tcl
+
+create_project -force adder ./adder_project -part xcu50-fsvh2104-2-e
+
+add_files -fileset sources_1 -norecurse {
+    {/work/verilog_out/adder.v}
+}
+add_files -fileset sim_1 -norecurse {
+    {/work/verilog_out/adder_tb.v}
+}
+add_files -fileset constrs_1 -norecurse {
+    {/work/vivado/adder/adder.xdc}
+}
+set_property top adder [get_filesets sources_1]
+update_compile_order -fileset sources_1
+
+synth_design -top adder -part xcu50-fsvh2104-2-e
+opt_design
+report_utilization -file adder_utilization.rpt
+report_timing -max_paths 10 -file adder_timing.rpt
+");
+
+        let xdc = generate_xdc("adder", &ClockConstraint { period_ns: 4.0, ..Default::default() });
+        assert_eq!(xdc, "# Timing constraints for adder\ncreate_clock -period 4.000 -name ap_clk [get_ports ap_clk]\n");
+    }
+
+    #[test]
+    fn test_timing_xdc_at_500_mhz() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output_width(Operation::Load("a".to_string()), 16);
+        let enable = graph.add_node_with_output_width(Operation::Load("en".to_string()), 1);
+        let doubled = graph.add_node_with_output(Operation::Add(a, a));
+        let masked = graph.add_node_with_output(Operation::Mux(enable, doubled, a));
+        graph.add_node(Operation::Store("result".to_string(), masked));
+        // Hand-placed: the sum is computed in stage 0 and read in stage 3
+        for (cycle, nodes) in [(0, vec![0, 1, 2]), (3, vec![3, 4])] {
+            graph.pipeline_stages.push(PipelineStage {
+                stage: cycle,
+                cycle,
+                operations: nodes.into_iter().map(NodeId).collect(),
+            });
+        }
+
+        let xdc = generate_timing_xdc(&graph, "kernel", 500.0, "clk");
+        assert!(xdc.contains("create_clock -period 2.000 -name ap_clk [get_ports clk]"));
+        assert!(xdc.contains("set_input_delay -clock ap_clk 0.600 [get_ports {a[*]}]"));
+        assert!(xdc.contains("set_input_delay -clock ap_clk 0.600 [get_ports en]"));
+        assert!(xdc.contains("set_output_delay -clock ap_clk 0.600 [get_ports {result[*]}]"));
+        assert!(xdc.contains("set_multicycle_path -setup 3 -from [get_cells -hierarchical -filter {NAME =~ *node_2*}]"));
+        assert!(xdc.contains("set_multicycle_path -hold 2 -from"));
+
+        // Registering the sum, as the scheduler does, removes the multicycle path
+        graph.insert_pipeline_register(doubled);
+        assert!(!generate_timing_xdc(&graph, "kernel", 500.0, "ap_clk").contains("set_multicycle_path"));
+    }
+
+    #[test]
+    fn test_tcl_for_a_single_source_picks_up_its_xdc() {
+        let out_dir = PathBuf::from("target").join("vivado_tcl_test");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let source = out_dir.join("blinky.v");
+        let xdc = out_dir.join("blinky.xdc");
+        let _ = std::fs::remove_file(&xdc);
+
+        let tcl = generate_vivado_tcl_for_source("blinky", &source, ALVEO_U50_PART);
+        assert!(tcl.contains("create_project -force blinky ./blinky_project -part xcu50-fsvh2104-2-e"));
+        assert!(tcl.contains("set_property top blinky [get_filesets sources_1]"));
+        assert!(tcl.contains(&format!("    {{{}}}", source.display())));
+        assert!(!tcl.contains("constrs_1"));
+
+        std::fs::write(&xdc, generate_xdc("blinky", &ClockConstraint::default())).unwrap();
+        let tcl = generate_vivado_tcl_for_source("blinky", &source, "xcvu9p-flga2104-2L-e");
+        assert!(tcl.contains("synth_design -top blinky -part xcvu9p-flga2104-2L-e"));
+        assert!(tcl.contains(&format!("add_files -fileset constrs_1 -norecurse {{\n    {{{}}}\n}}", xdc.display())));
+    }
+
+    /// Qualified names of every element in `xml`, and the text of each `spirit:name`
+    fn element_names(xml: &str) -> (Vec<String>, Vec<String>) {
+        use quick_xml::events::Event;
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let (mut elements, mut names) = (Vec::new(), Vec::new());
+        let mut in_name = false;
+        loop {
+            match reader.read_event().expect("component.xml is well-formed") {
+                Event::Start(element) | Event::Empty(element) => {
+                    let name = element.name().as_ref().to_string();
+                    in_name = name == "spirit:name";
+                    elements.push(name);
+                }
+                Event::Text(text) if in_name => names.push(text.xml10_content().into_owned()),
+                Event::End(_) => in_name = false,
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        (elements, names)
+    }
+
+    #[test]
+    fn test_ip_package_describes_the_stream_kernel() {
+        use crate::dsl::ast::*;
+        use crate::ir::lower::lower_expr_to_graph;
+
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 16), input("b", 16))));
+        let ip_dir = PathBuf::from("target").join("ip_package_test");
+        generate_xilinx_ip_package("adder", &graph, &ip_dir).unwrap();
+
+        let verilog = std::fs::read_to_string(ip_dir.join("hdl").join("adder.v")).unwrap();
+        assert!(verilog.contains("module adder ("));
+        let xml = std::fs::read_to_string(ip_dir.join("component.xml")).unwrap();
+        let (elements, names) = element_names(&xml);
+        for required in ["spirit:component", "spirit:busInterfaces", "spirit:fileSets", "spirit:ports", "spirit:parameters"] {
+            assert!(elements.iter().any(|element| element == required), "missing {}", required);
+        }
+        assert_eq!(elements.iter().filter(|element| *element == "spirit:busInterface").count(), 4);
+        for name in ["adder", "s_axis", "m_axis", "ap_clk", "ap_rst_n", "TDATA", "m_axis_tlast", "hdl/adder.v", "Component_Name"] {
+            assert!(names.iter().any(|text| text == name), "no spirit:name {}", name);
+        }
+        // Two 16-bit inputs make one 32-bit beat
+        assert!(xml.contains("<spirit:name>s_axis_tdata</spirit:name>\n        <spirit:wire>\n          <spirit:direction>in</spirit:direction>\n          <spirit:vector>\n            <spirit:left spirit:format=\"long\">31</spirit:left>"));
+        assert!(xml.contains("<spirit:value>s_axis:m_axis</spirit:value>"));
+        assert!(!elements.iter().any(|element| element == "spirit:memoryMaps"));
+
+        let control_dir = PathBuf::from("target").join("ip_package_control_test");
+        generate_xilinx_ip_package_with_control("adder", &graph, &control_dir).unwrap();
+        let xml = std::fs::read_to_string(control_dir.join("component.xml")).unwrap();
+        let (elements, names) = element_names(&xml);
+        assert!(elements.iter().any(|element| element == "spirit:memoryMapRef"));
+        for name in ["adder_axilite", "s_axi_control", "AWADDR", "s_axi_control_RREADY", "hdl/adder_axilite.v"] {
+            assert!(names.iter().any(|text| text == name), "no spirit:name {}", name);
+        }
+        assert!(xml.contains("<spirit:range spirit:format=\"long\">64</spirit:range>"));
+        assert!(control_dir.join("hdl").join("adder_axilite.v").exists());
+    }
+}
@@ -0,0 +1,323 @@
+//! Wishbone B4 register slave
+//!
+//! Open-source flows (Yosys and nextpnr, OpenFPGA, LiteX SoCs) attach
+//! peripherals over Wishbone rather than AXI. `generate_wishbone_slave`
+//! emits the same kind of control register file as the AXI4-Lite one, on a
+//! 32-bit Wishbone B4 slave port with byte addresses:
+//!
+//! | Offset | Register                                                   |
+//! |--------|------------------------------------------------------------|
+//! | 0x00   | control: bit 0 `ap_start`, 1 `ap_done`, 2 `ap_idle`, 3 `ap_ready` |
+//! | 0x04+  | inputs, then outputs, one 32-bit word per 32 bits of width |
+//!
+//! `ap_start` clears itself once the core reports `ap_ready`, `ap_done` is
+//! cleared when the control register is read, and outputs are captured on
+//! `ap_done`. `wb_rst_i` is synchronous and active high.
+
+/// Bus cycle variant of the slave
+///
+/// Classic cycles hold `wb_stb_i` until `wb_ack_o`, and the slave acks one
+/// cycle after the strobe is seen. Pipelined cycles issue a new request in
+/// every cycle `wb_stall_o` is low, and each is acked one cycle after it is
+/// issued. Exactly one of the two is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WishboneConfig {
+    pub classic_mode: bool,
+    pub pipeline_mode: bool,
+}
+
+impl WishboneConfig {
+    pub fn classic() -> Self {
+        Self { classic_mode: true, pipeline_mode: false }
+    }
+
+    pub fn pipelined() -> Self {
+        Self { classic_mode: false, pipeline_mode: true }
+    }
+}
+
+impl Default for WishboneConfig {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+/// Generate the `{name}_wb_slave` register file with a classic Wishbone B4 slave port
+///
+/// `inputs` and `outputs` are (name, width) pairs; see the module docs for the
+/// address map.
+pub fn generate_wishbone_slave(module_name: &str, inputs: &[(&str, u32)], outputs: &[(&str, u32)]) -> String {
+    generate_wishbone_slave_with_config(module_name, inputs, outputs, &WishboneConfig::default())
+}
+
+/// `generate_wishbone_slave` with the bus cycle variant chosen by `config`
+pub fn generate_wishbone_slave_with_config(module_name: &str, inputs: &[(&str, u32)], outputs: &[(&str, u32)], config: &WishboneConfig) -> String {
+    assert!(config.classic_mode != config.pipeline_mode, "a Wishbone slave is either classic or pipelined");
+    let registers = register_map(inputs, outputs);
+    let mut verilog = String::new();
+
+    verilog.push_str(&format!("// Wishbone B4 {} register slave for {}\n", if config.pipeline_mode { "pipelined" } else { "classic" }, module_name));
+    verilog.push_str(&format!("module {}_wb_slave (\n", module_name));
+    verilog.push_str("    input  wire        wb_clk_i,\n");
+    verilog.push_str("    input  wire        wb_rst_i,\n");
+    verilog.push_str("    \n");
+    verilog.push_str("    // Wishbone B4 slave\n");
+    verilog.push_str("    input  wire        wb_cyc_i,\n");
+    verilog.push_str("    input  wire        wb_stb_i,\n");
+    verilog.push_str("    input  wire        wb_we_i,\n");
+    verilog.push_str("    input  wire [31:0] wb_adr_i,\n");
+    verilog.push_str("    input  wire [31:0] wb_dat_i,\n");
+    verilog.push_str("    output reg  [31:0] wb_dat_o,\n");
+    verilog.push_str("    output reg         wb_ack_o,\n");
+    if config.pipeline_mode {
+        verilog.push_str("    output wire        wb_stall_o,\n");
+    }
+    verilog.push_str("    \n");
+    verilog.push_str("    // Core handshake\n");
+    verilog.push_str("    output wire        ap_start,\n");
+    verilog.push_str("    input  wire        ap_done,\n");
+    verilog.push_str("    input  wire        ap_idle,\n");
+    let mut core_ports = vec!["    input  wire        ap_ready".to_string()];
+    for register in &registers {
+        let direction = if register.writable { "output" } else { "input " };
+        core_ports.push(format!("    {} wire [{}:0]  {}", direction, register.width - 1, register.name));
+    }
+    verilog.push_str(&core_ports.join(",\n"));
+    verilog.push_str("\n);\n\n");
+
+    verilog.push_str("    // Register map (byte addresses)\n");
+    verilog.push_str("    localparam ADDR_AP_CTRL = 32'h00;\n");
+    for register in &registers {
+        for word in 0..register.words() {
+            verilog.push_str(&format!("    localparam {} = 32'h{:02x};\n", register.word_address_name(word), register.offset + 4 * word));
+        }
+    }
+    verilog.push('\n');
+
+    verilog.push_str("    reg         int_ap_start;\n");
+    verilog.push_str("    reg         int_ap_done;\n");
+    for register in &registers {
+        verilog.push_str(&format!("    reg  [{}:0] int_{};\n", register.words() * 32 - 1, register.name));
+    }
+    verilog.push('\n');
+    if config.pipeline_mode {
+        verilog.push_str("    // Every register answers in one cycle, so a request can issue every cycle\n");
+        verilog.push_str("    assign wb_stall_o = 1'b0;\n");
+        verilog.push_str("    wire        request = wb_cyc_i & wb_stb_i & ~wb_stall_o;\n");
+    } else {
+        verilog.push_str("    // One access per strobe: the cycle after it is acked the master drops wb_stb_i\n");
+        verilog.push_str("    wire        request = wb_cyc_i & wb_stb_i & ~wb_ack_o;\n");
+    }
+    verilog.push_str("    wire        read    = request & ~wb_we_i;\n");
+    verilog.push_str("    wire        write   = request &  wb_we_i;\n\n");
+
+    verilog.push_str("    // Acknowledge one cycle after the request\n");
+    verilog.push_str("    always @(posedge wb_clk_i) begin\n");
+    verilog.push_str("        if (wb_rst_i) begin\n");
+    verilog.push_str("            wb_ack_o <= 1'b0;\n");
+    verilog.push_str("        end else begin\n");
+    verilog.push_str("            wb_ack_o <= request;\n");
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n\n");
+    verilog.push_str("    always @(posedge wb_clk_i) begin\n");
+    verilog.push_str("        if (read) begin\n");
+    verilog.push_str("            case (wb_adr_i)\n");
+    verilog.push_str("                ADDR_AP_CTRL: wb_dat_o <= {28'd0, ap_ready, ap_idle, int_ap_done, int_ap_start};\n");
+    for register in &registers {
+        for word in 0..register.words() {
+            verilog.push_str(&format!("                {}: wb_dat_o <= int_{}[{}:{}];\n",
+                register.word_address_name(word), register.name, 32 * word + 31, 32 * word));
+        }
+    }
+    verilog.push_str("                default: wb_dat_o <= 32'd0;\n");
+    verilog.push_str("            endcase\n");
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n\n");
+
+    verilog.push_str("    // Control register\n");
+    verilog.push_str("    assign ap_start = int_ap_start;\n\n");
+    verilog.push_str("    always @(posedge wb_clk_i) begin\n");
+    verilog.push_str("        if (wb_rst_i) begin\n");
+    verilog.push_str("            int_ap_start <= 1'b0;\n");
+    verilog.push_str("        end else if (write && wb_adr_i == ADDR_AP_CTRL && wb_dat_i[0]) begin\n");
+    verilog.push_str("            int_ap_start <= 1'b1;\n");
+    verilog.push_str("        end else if (ap_ready) begin\n");
+    verilog.push_str("            int_ap_start <= 1'b0;  // Self-clearing once the core accepts the start\n");
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n\n");
+    verilog.push_str("    always @(posedge wb_clk_i) begin\n");
+    verilog.push_str("        if (wb_rst_i) begin\n");
+    verilog.push_str("            int_ap_done <= 1'b0;\n");
+    verilog.push_str("        end else if (ap_done) begin\n");
+    verilog.push_str("            int_ap_done <= 1'b1;\n");
+    verilog.push_str("        end else if (read && wb_adr_i == ADDR_AP_CTRL) begin\n");
+    verilog.push_str("            int_ap_done <= 1'b0;  // Clear on read\n");
+    verilog.push_str("        end\n");
+    verilog.push_str("    end\n");
+
+    for register in &registers {
+        let padded = register.words() * 32;
+        verilog.push('\n');
+        verilog.push_str("    always @(posedge wb_clk_i) begin\n");
+        verilog.push_str("        if (wb_rst_i) begin\n");
+        verilog.push_str(&format!("            int_{} <= {}'d0;\n", register.name, padded));
+        if register.writable {
+            for word in 0..register.words() {
+                verilog.push_str(&format!("        end else if (write && wb_adr_i == {}) begin\n", register.word_address_name(word)));
+                verilog.push_str(&format!("            int_{}[{}:{}] <= wb_dat_i;\n", register.name, 32 * word + 31, 32 * word));
+            }
+        } else {
+            let value = if padded > register.width {
+                format!("{{{}'d0, {}}}", padded - register.width, register.name)
+            } else {
+                register.name.clone()
+            };
+            verilog.push_str("        end else if (ap_done) begin\n");
+            verilog.push_str(&format!("            int_{} <= {};\n", register.name, value));
+        }
+        verilog.push_str("        end\n");
+        verilog.push_str("    end\n");
+        if register.writable {
+            verilog.push_str(&format!("    assign {0} = int_{0}[{1}:0];\n", register.name, register.width - 1));
+        }
+    }
+
+    verilog.push_str("\nendmodule\n");
+    verilog
+}
+
+/// One argument register of the slave
+#[derive(Debug, Clone, PartialEq)]
+struct Register {
+    name: String,
+    width: u32,
+    offset: u32,
+    /// Inputs are written by the master; outputs are read-only
+    writable: bool,
+}
+
+impl Register {
+    fn words(&self) -> u32 {
+        self.width.div_ceil(32)
+    }
+
+    fn word_address_name(&self, word: u32) -> String {
+        format!("ADDR_{}_DATA_{}", self.name.to_uppercase(), word)
+    }
+}
+
+/// Inputs from 0x04, then outputs, each word 4 bytes after the last
+fn register_map(inputs: &[(&str, u32)], outputs: &[(&str, u32)]) -> Vec<Register> {
+    let arguments = inputs.iter().map(|arg| (arg, true)).chain(outputs.iter().map(|arg| (arg, false)));
+    let mut offset = 0x04;
+    let mut registers = Vec::new();
+    for (&(name, width), writable) in arguments {
+        let register = Register { name: name.to_string(), width, offset, writable };
+        offset += 4 * register.words();
+        registers.push(register);
+    }
+    registers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registers_follow_the_control_word_at_4_byte_intervals() {
+        let registers = register_map(&[("a", 16), ("b", 64)], &[("result", 17)]);
+        let offsets: Vec<(&str, u32)> = registers.iter().map(|r| (r.name.as_str(), r.offset)).collect();
+        assert_eq!(offsets, vec![("a", 0x04), ("b", 0x08), ("result", 0x10)]);
+
+        let classic = generate_wishbone_slave("k", &[("a", 16), ("b", 64)], &[("result", 17)]);
+        assert!(classic.contains("module k_wb_slave ("));
+        assert!(classic.contains("    input  wire [31:0] wb_adr_i,\n"));
+        assert!(classic.contains("localparam ADDR_B_DATA_1 = 32'h0c;"));
+        assert!(classic.contains("ADDR_RESULT_DATA_0: wb_dat_o <= int_result[31:0];"));
+        assert!(classic.contains("int_result <= {15'd0, result};"));
+        assert!(classic.contains("assign a = int_a[15:0];"));
+        assert!(classic.contains("wire        request = wb_cyc_i & wb_stb_i & ~wb_ack_o;"));
+        assert!(!classic.contains("wb_stall_o"));
+
+        let pipelined = generate_wishbone_slave_with_config("k", &[("a", 16)], &[("result", 17)], &WishboneConfig::pipelined());
+        assert!(pipelined.contains("    output wire        wb_stall_o,\n"));
+        assert!(pipelined.contains("wire        request = wb_cyc_i & wb_stb_i & ~wb_stall_o;"));
+    }
+
+    #[test]
+    fn test_classic_read_is_acked_in_the_next_cycle() {
+        use std::process::Command;
+
+        let dir = std::path::PathBuf::from("target").join("sim").join("wb_adder");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("wb_adder_wb_slave.v"), generate_wishbone_slave("wb_adder", &[("a", 16), ("b", 16)], &[("result", 17)])).unwrap();
+        // Inputs change after the rising edge, so each strobe is seen on the next one
+        let main = r#"#include "Vwb_adder_wb_slave.h"
+#include "verilated.h"
+#include <cstdio>
+
+static Vwb_adder_wb_slave* dut;
+
+static void tick() {
+    dut->wb_clk_i = 0;
+    dut->eval();
+    dut->wb_clk_i = 1;
+    dut->eval();
+}
+
+// Cycles from the strobe to wb_ack_o, and wb_ack_o once the strobe is dropped
+static void access(unsigned addr, bool write, unsigned data) {
+    dut->wb_adr_i = addr;
+    dut->wb_we_i = write;
+    dut->wb_dat_i = data;
+    dut->wb_cyc_i = 1;
+    dut->wb_stb_i = 1;
+    int cycles = 0;
+    do {
+        tick();
+        cycles++;
+    } while (!dut->wb_ack_o && cycles < 8);
+    unsigned read = dut->wb_dat_o;
+    dut->wb_cyc_i = 0;
+    dut->wb_stb_i = 0;
+    tick();
+    if (write) std::printf("write acked after %d, then %u\n", cycles, dut->wb_ack_o);
+    else std::printf("read %u acked after %d, then %u\n", read, cycles, dut->wb_ack_o);
+}
+
+int main(int argc, char** argv) {
+    Verilated::commandArgs(argc, argv);
+    dut = new Vwb_adder_wb_slave;
+    dut->wb_rst_i = 1;
+    dut->ap_idle = 1;
+    for (int cycle = 0; cycle < 4; cycle++) tick();
+    dut->wb_rst_i = 0;
+    tick();
+
+    access(0x04, true, 1234);
+    access(0x04, false, 0);
+    access(0x00, false, 0);
+    delete dut;
+    return 0;
+}
+"#;
+        std::fs::write(dir.join("main.cpp"), main).unwrap();
+
+        let build = Command::new("verilator")
+            .args(["--cc", "--exe", "--build", "-Wno-fatal", "--top-module", "wb_adder_wb_slave", "wb_adder_wb_slave.v", "main.cpp"])
+            .current_dir(&dir)
+            .output();
+        let Ok(build) = build else {
+            println!("Skipping Wishbone simulation - Verilator not installed");
+            return;
+        };
+        assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+
+        let run = Command::new(dir.join("obj_dir").join("Vwb_adder_wb_slave")).output().unwrap();
+        let stdout = String::from_utf8_lossy(&run.stdout);
+        assert!(run.status.success(), "{}", stdout);
+        let lines: Vec<&str> = stdout.lines().collect();
+        // ap_idle is the only control bit set
+        assert_eq!(lines, vec!["write acked after 1, then 0", "read 1234 acked after 1, then 0", "read 4 acked after 1, then 0"]);
+    }
+}
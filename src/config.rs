@@ -0,0 +1,264 @@
+//! Build configuration for the HLS flow
+//!
+//! An `HlsConfig` gathers what the examples used to hard-code: the module
+//! name, the pipeline's II, depth and unroll factor, the target device and
+//! clock, where the HDL goes and which interface and dialect it uses. It
+//! loads from TOML or JSON; keys it does not know are an error, so a typo
+//! does not silently fall back to a default.
+//!
+//! ```toml
+//! module_name = "pipelined_mac"
+//! device = "alveo_u50"
+//! clock_period_ns = 4.0
+//! output_dir = "target/verilog_out"
+//! interface = "ap_ctrl"
+//! dialect = "verilog2001"
+//!
+//! [pipeline]
+//! ii = 1
+//! depth = 4
+//! ```
+
+use crate::backend::verilog::{InterfaceStyle, VerilogDialect, VerilogEmitOptions};
+use crate::backend::OutputFormat;
+use crate::error::HlsError;
+use crate::ir::graph::{Graph, PipelineConfig};
+use crate::passes::device::DeviceProfile;
+use crate::passes::pipeline::PipelineScheduler;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Device of `HlsConfig::default()`
+pub const DEFAULT_DEVICE: &str = "alveo_u50";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HlsConfig {
+    pub module_name: String,
+    /// One of `DeviceProfile::PRESETS`
+    pub device: String,
+    /// Largest share of the device the kernel may use, in (0, 1]
+    pub kernel_fraction: f64,
+    /// Target clock period; `None` uses the device's default clock
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock_period_ns: Option<f64>,
+    /// Directory the generated HDL is written to
+    pub output_dir: PathBuf,
+    pub interface: InterfaceStyle,
+    pub dialect: VerilogDialect,
+    pub pipeline: PipelineSettings,
+}
+
+/// The `[pipeline]` table, applied to the graph's `PipelineConfig`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineSettings {
+    pub enable: bool,
+    pub ii: usize,
+    pub depth: usize,
+    pub unroll: usize,
+}
+
+impl Default for PipelineSettings {
+    fn default() -> Self {
+        Self { enable: true, ii: 1, depth: 4, unroll: 1 }
+    }
+}
+
+impl HlsConfig {
+    /// A II=1 kernel called `kernel` on the whole of `device`, at its default clock
+    pub fn default_for(device: &str) -> Result<Self, HlsError> {
+        let config = Self {
+            module_name: "kernel".to_string(),
+            device: device.to_string(),
+            kernel_fraction: 1.0,
+            clock_period_ns: None,
+            output_dir: PathBuf::from("target").join("verilog_out"),
+            interface: InterfaceStyle::default(),
+            dialect: VerilogDialect::default(),
+            pipeline: PipelineSettings::default(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_toml_str(toml: &str) -> Result<Self, HlsError> {
+        let config: Self = toml::from_str(toml).map_err(|e| HlsError::ConfigError(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Self, HlsError> {
+        let config: Self = serde_json::from_str(json).map_err(|e| HlsError::ConfigError(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read a `.json` file as JSON and anything else as TOML
+    pub fn load(path: &Path) -> Result<Self, HlsError> {
+        let text = std::fs::read_to_string(path)?;
+        let config = if path.extension().is_some_and(|extension| extension == "json") {
+            Self::from_json_str(&text)
+        } else {
+            Self::from_toml_str(&text)
+        };
+        config.map_err(|e| HlsError::ConfigError(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, HlsError> {
+        toml::to_string(self).map_err(|e| HlsError::ConfigError(e.to_string()))
+    }
+
+    pub fn to_json_string(&self) -> Result<String, HlsError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Error unless the device exists and every number is in range
+    pub fn validate(&self) -> Result<(), HlsError> {
+        let invalid = |message: String| Err(HlsError::ConfigError(message));
+        if self.module_name.is_empty() {
+            return invalid("module_name is empty".to_string());
+        }
+        if DeviceProfile::preset(&self.device).is_none() {
+            return invalid(format!("unknown device '{}', expected one of {}", self.device, DeviceProfile::PRESETS.join(", ")));
+        }
+        if !(self.kernel_fraction > 0.0 && self.kernel_fraction <= 1.0) {
+            return invalid(format!("kernel_fraction {} is not in (0, 1]", self.kernel_fraction));
+        }
+        if self.clock_period_ns.is_some_and(|period| period <= 0.0) {
+            return invalid(format!("clock_period_ns {} is not positive", self.clock_period_ns.unwrap_or_default()));
+        }
+        let PipelineSettings { ii, depth, unroll, .. } = self.pipeline;
+        if [ii, depth, unroll].contains(&0) {
+            return invalid(format!("pipeline ii, depth and unroll must be at least 1, not {}, {} and {}", ii, depth, unroll));
+        }
+        Ok(())
+    }
+
+    /// The `device` preset, limited to `kernel_fraction`
+    pub fn device_profile(&self) -> Result<DeviceProfile, HlsError> {
+        self.validate()?;
+        let device = DeviceProfile::preset(&self.device).expect("validated devices are presets");
+        Ok(device.with_kernel_fraction(self.kernel_fraction))
+    }
+
+    /// `clock_period_ns`, or the device's default clock as a period
+    pub fn target_period_ns(&self) -> Result<f64, HlsError> {
+        Ok(match self.clock_period_ns {
+            Some(period) => period,
+            None => self.device_profile()?.clock_period_ns(),
+        })
+    }
+
+    /// Set `graph.pipeline_config` from the `[pipeline]` table
+    pub fn apply(&self, graph: &mut Graph) {
+        graph.pipeline_config = PipelineConfig {
+            enable: self.pipeline.enable,
+            initiation_interval: self.pipeline.ii,
+            pipeline_depth: self.pipeline.depth,
+            unroll_factor: self.pipeline.unroll,
+        };
+    }
+
+    /// Scheduler with the device's limits, chaining to the target clock
+    pub fn scheduler(&self) -> Result<PipelineScheduler, HlsError> {
+        Ok(PipelineScheduler { clock_period_ns: self.target_period_ns()?, ..PipelineScheduler::with_device(self.device_profile()?) })
+    }
+
+    pub fn emit_options(&self) -> VerilogEmitOptions {
+        VerilogEmitOptions { dialect: self.dialect, interface: self.interface, ..Default::default() }
+    }
+
+    /// HDL flavour the dialect is written in
+    pub fn output_format(&self) -> OutputFormat {
+        match self.dialect {
+            VerilogDialect::Verilog2001 => OutputFormat::Verilog,
+            VerilogDialect::SystemVerilog => OutputFormat::SystemVerilog,
+        }
+    }
+
+    /// `<output_dir>/<module_name>.v`, or `.sv` for SystemVerilog
+    pub fn output_path(&self) -> PathBuf {
+        self.output_dir.join(format!("{}.{}", self.module_name, self.output_format().extension()))
+    }
+}
+
+impl Default for HlsConfig {
+    fn default() -> Self {
+        Self::default_for(DEFAULT_DEVICE).expect("the default device is a preset")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mac_config() -> HlsConfig {
+        HlsConfig {
+            module_name: "mac".to_string(),
+            device: "artix7_100t".to_string(),
+            kernel_fraction: 0.5,
+            clock_period_ns: Some(8.0),
+            output_dir: PathBuf::from("out"),
+            interface: InterfaceStyle::AxiStream,
+            dialect: VerilogDialect::SystemVerilog,
+            pipeline: PipelineSettings { enable: true, ii: 2, depth: 6, unroll: 4 },
+        }
+    }
+
+    #[test]
+    fn test_config_round_trips_through_toml_and_json() {
+        for config in [mac_config(), HlsConfig::default()] {
+            assert_eq!(HlsConfig::from_toml_str(&config.to_toml_string().unwrap()).unwrap(), config);
+            assert_eq!(HlsConfig::from_json_str(&config.to_json_string().unwrap()).unwrap(), config);
+        }
+        let toml = mac_config().to_toml_string().unwrap();
+        assert!(toml.contains("interface = \"axi_stream\""));
+        assert!(toml.contains("dialect = \"system_verilog\""));
+        assert!(toml.contains("[pipeline]\nenable = true\nii = 2\n"));
+
+        // Keys left out take their defaults
+        let partial = HlsConfig::from_toml_str("module_name = \"mac\"\n[pipeline]\nii = 3\n").unwrap();
+        assert_eq!(partial, HlsConfig { module_name: "mac".to_string(), pipeline: PipelineSettings { ii: 3, ..Default::default() }, ..Default::default() });
+    }
+
+    #[test]
+    fn test_unknown_keys_and_devices_are_errors() {
+        let unknown = HlsConfig::from_toml_str("module_name = \"mac\"\nclock_mhz = 300\n").unwrap_err();
+        assert!(matches!(&unknown, HlsError::ConfigError(message) if message.contains("clock_mhz")), "{}", unknown);
+        assert!(HlsConfig::from_toml_str("[pipeline]\ninitiation_interval = 2\n").is_err());
+        assert!(HlsConfig::from_json_str("{\"modul_name\": \"mac\"}").is_err());
+
+        let device = HlsConfig::default_for("stratix").unwrap_err();
+        assert!(device.to_string().contains("unknown device 'stratix', expected one of alveo_u50"));
+        assert!(HlsConfig::from_toml_str("[pipeline]\nii = 0\n").is_err());
+        assert!(HlsConfig::from_toml_str("kernel_fraction = 1.5\n").is_err());
+    }
+
+    #[test]
+    fn test_config_drives_the_graph_scheduler_and_outputs() {
+        let config = mac_config();
+        let mut graph = Graph::new();
+        config.apply(&mut graph);
+        let pipeline = &graph.pipeline_config;
+        assert_eq!((pipeline.enable, pipeline.initiation_interval, pipeline.pipeline_depth, pipeline.unroll_factor), (true, 2, 6, 4));
+
+        let scheduler = config.scheduler().unwrap();
+        assert_eq!(scheduler.resource_constraints["multiplier"], 120);
+        assert_eq!(scheduler.clock_period_ns, 8.0);
+        assert_eq!(scheduler.device.name, "Artix-7 100T");
+        assert_eq!(config.output_path(), Path::new("out").join("mac.sv"));
+        assert_eq!(config.emit_options().interface, InterfaceStyle::AxiStream);
+
+        // Without a clock the device's is used
+        assert_eq!(HlsConfig { clock_period_ns: None, ..config }.target_period_ns().unwrap(), 10.0);
+    }
+
+    #[test]
+    fn test_example_config_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").join("pipelined_mac.toml");
+        let config = HlsConfig::load(&path).unwrap();
+        assert_eq!(config.module_name, "pipelined_mac");
+        assert_eq!((config.pipeline.ii, config.pipeline.depth), (1, 4));
+    }
+}
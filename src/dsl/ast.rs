@@ -1,20 +1,79 @@
+use crate::dsl::types::FixedPointType;
+
 #[derive(Clone, Debug)]
 pub enum Expr {
-    Input { name: String, width: u32 },
-    Const { value: i32, width: u32 },
+    Input { name: String, width: u32, signed: bool },
+    /// Input port holding fixed-point values of format `ty`
+    FixedPointInput { name: String, ty: FixedPointType },
+    Const { value: i32, width: u32, signed: bool },
     Add(Box<Expr>, Box<Expr>),
     Sub(Box<Expr>, Box<Expr>),
     Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    SAdd(Box<Expr>, Box<Expr>),
+    SSub(Box<Expr>, Box<Expr>),
+    SMul(Box<Expr>, Box<Expr>),
+    /// Sum clamped to the range of a `width`-bit result
+    AddSat(Box<Expr>, Box<Expr>, u32),
+    /// Difference clamped to the range of a `width`-bit result
+    SubSat(Box<Expr>, Box<Expr>, u32),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+    Nand(Box<Expr>, Box<Expr>),
+    Nor(Box<Expr>, Box<Expr>),
+    Xnor(Box<Expr>, Box<Expr>),
+    Concat(Box<Expr>, Box<Expr>),
+    Slice { expr: Box<Expr>, high: u32, low: u32 },
+    Not(Box<Expr>),
+    /// Unsigned integer square root, rounded down
+    Sqrt(Box<Expr>),
+    Shl(Box<Expr>, Box<Expr>),
+    Shr(Box<Expr>, Box<Expr>),
+    Lt(Box<Expr>, Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>),
+    Gt(Box<Expr>, Box<Expr>),
+    Select { cond: Box<Expr>, then: Box<Expr>, els: Box<Expr> },
+    /// Sum of all values, built as a balanced adder tree
+    Sum(Vec<Expr>),
+    /// Largest of all values, built as a balanced comparator tree
+    MaxOf(Vec<Expr>),
+    /// Smallest of all values, built as a balanced comparator tree
+    MinOf(Vec<Expr>),
+    /// `array[index]`, read from a block RAM input array of `depth` `width`-bit elements
+    Index { array: String, depth: usize, width: u32, index: Box<Expr> },
+    Let { name: String, value: Box<Expr>, body: Box<Expr> },
+    Var(String),
     Output { name: String, expr: Box<Expr> },
 }
 
 // DSL constructor helpers
 pub fn input<T: Into<String>>(name: T, width: u32) -> Expr {
-    Expr::Input { name: name.into(), width }
+    Expr::Input { name: name.into(), width, signed: false }
+}
+
+/// Two's-complement input port
+pub fn signed_input<T: Into<String>>(name: T, width: u32) -> Expr {
+    Expr::Input { name: name.into(), width, signed: true }
+}
+
+/// Shorthand for `signed_input`
+pub fn sinput<T: Into<String>>(name: T, width: u32) -> Expr {
+    signed_input(name, width)
+}
+
+/// Fixed-point input port; products and sums with it keep the binary point aligned
+pub fn fixed_input<T: Into<String>>(name: T, ty: FixedPointType) -> Expr {
+    Expr::FixedPointInput { name: name.into(), ty }
 }
 
 pub fn const_val(value: i32, width: u32) -> Expr {
-    Expr::Const { value, width }
+    Expr::Const { value, width, signed: false }
+}
+
+/// Two's-complement constant, e.g. a negative filter coefficient
+pub fn signed_const(value: i32, width: u32) -> Expr {
+    Expr::Const { value, width, signed: true }
 }
 
 pub fn add(lhs: Expr, rhs: Expr) -> Expr {
@@ -29,6 +88,143 @@ pub fn mul(lhs: Expr, rhs: Expr) -> Expr {
     Expr::Mul(Box::new(lhs), Box::new(rhs))
 }
 
+/// Two's-complement addition, treating both operands as signed
+pub fn sadd(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::SAdd(Box::new(lhs), Box::new(rhs))
+}
+
+/// Two's-complement subtraction, treating both operands as signed
+pub fn ssub(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::SSub(Box::new(lhs), Box::new(rhs))
+}
+
+/// Two's-complement multiplication, treating both operands as signed
+pub fn smul(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::SMul(Box::new(lhs), Box::new(rhs))
+}
+
+/// `lhs + rhs`, clamped to the largest or smallest `width`-bit value instead of wrapping
+pub fn add_sat(lhs: Expr, rhs: Expr, width: u32) -> Expr {
+    Expr::AddSat(Box::new(lhs), Box::new(rhs), width)
+}
+
+/// `lhs - rhs`, clamped to the largest or smallest `width`-bit value instead of wrapping
+pub fn sub_sat(lhs: Expr, rhs: Expr, width: u32) -> Expr {
+    Expr::SubSat(Box::new(lhs), Box::new(rhs), width)
+}
+
+/// Integer division; dividing by zero yields 0 in simulation
+pub fn div(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Div(Box::new(lhs), Box::new(rhs))
+}
+
+/// Bitwise AND
+pub fn and(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::And(Box::new(lhs), Box::new(rhs))
+}
+
+/// Bitwise OR
+pub fn or(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Or(Box::new(lhs), Box::new(rhs))
+}
+
+/// Bitwise XOR
+pub fn xor(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Xor(Box::new(lhs), Box::new(rhs))
+}
+
+/// Bitwise NAND
+pub fn nand(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Nand(Box::new(lhs), Box::new(rhs))
+}
+
+/// Bitwise NOR
+pub fn nor(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Nor(Box::new(lhs), Box::new(rhs))
+}
+
+/// Bitwise XNOR
+pub fn xnor(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Xnor(Box::new(lhs), Box::new(rhs))
+}
+
+/// `{high, low}`: `high` ends up in the most significant bits
+pub fn concat(high: Expr, low: Expr) -> Expr {
+    Expr::Concat(Box::new(high), Box::new(low))
+}
+
+/// Bits `expr[hi:lo]`, inclusive on both ends
+pub fn slice(expr: Expr, hi: u32, lo: u32) -> Expr {
+    Expr::Slice { expr: Box::new(expr), high: hi, low: lo }
+}
+
+/// Bitwise NOT
+pub fn not(expr: Expr) -> Expr {
+    Expr::Not(Box::new(expr))
+}
+
+/// Integer square root, rounded down; the operand is read as unsigned
+pub fn sqrt(expr: Expr) -> Expr {
+    Expr::Sqrt(Box::new(expr))
+}
+
+pub fn shl(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Shl(Box::new(lhs), Box::new(rhs))
+}
+
+/// Logical right shift
+pub fn shr(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Shr(Box::new(lhs), Box::new(rhs))
+}
+
+/// `lhs < rhs` as a 1-bit value
+pub fn lt(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Lt(Box::new(lhs), Box::new(rhs))
+}
+
+/// `lhs == rhs` as a 1-bit value
+pub fn eq(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Eq(Box::new(lhs), Box::new(rhs))
+}
+
+/// `lhs > rhs` as a 1-bit value
+pub fn gt(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Gt(Box::new(lhs), Box::new(rhs))
+}
+
+/// `cond ? a : b`; any nonzero condition selects `a`
+pub fn select(cond: Expr, a: Expr, b: Expr) -> Expr {
+    Expr::Select { cond: Box::new(cond), then: Box::new(a), els: Box::new(b) }
+}
+
+/// Bind `value` to `name` within `body`; every `var(name)` reuses one lowered copy
+/// `values[0] + values[1] + ...` as one balanced reduction tree
+pub fn sum(values: Vec<Expr>) -> Expr {
+    Expr::Sum(values)
+}
+
+pub fn max_of(values: Vec<Expr>) -> Expr {
+    Expr::MaxOf(values)
+}
+
+pub fn min_of(values: Vec<Expr>) -> Expr {
+    Expr::MinOf(values)
+}
+
+/// Read element `index` of the block RAM input array `array`
+pub fn index<T: Into<String>>(array: T, depth: usize, width: u32, index: Expr) -> Expr {
+    Expr::Index { array: array.into(), depth, width, index: Box::new(index) }
+}
+
+pub fn let_in<T: Into<String>>(name: T, value: Expr, body: Expr) -> Expr {
+    Expr::Let { name: name.into(), value: Box::new(value), body: Box::new(body) }
+}
+
+/// Reference a name bound by an enclosing `let_in`
+pub fn var<T: Into<String>>(name: T) -> Expr {
+    Expr::Var(name.into())
+}
+
 pub fn output<T: Into<String>>(name: T, expr: Expr) -> Expr {
     Expr::Output { name: name.into(), expr: Box::new(expr) }
 }
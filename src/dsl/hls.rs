@@ -3,7 +3,10 @@
 //! This module provides a more user-friendly interface for creating
 //! pipelined hardware descriptions in Rust.
 
-use crate::ir::graph::{Graph, Operation};
+use crate::backend::verilog::GenerationParams;
+use crate::error::HlsError;
+use crate::ir::graph::{ArrayKind, Graph, Operation, ValueId};
+use std::collections::HashSet;
 
 /// HLS function builder with pipeline support
 pub struct HLSFunction {
@@ -32,7 +35,7 @@ impl HLSFunction {
     }
 
     /// Add input port
-    pub fn input(&mut self, name: &str) -> HLSValue {
+    pub fn input(&mut self, name: &str) -> HLSValue<'_> {
         let value = self.graph.add_node_with_output(Operation::Load(name.to_string()));
         HLSValue { value, function: self }
     }
@@ -42,18 +45,157 @@ impl HLSFunction {
         self.graph.add_node(Operation::Store(name.to_string(), value.value));
     }
 
+    /// Add an input array of `depth` elements, held in block RAM
+    pub fn array_input(&mut self, name: &str, depth: usize) -> HLSArray {
+        self.graph.declare_array(name, depth, None, ArrayKind::Input);
+        HLSArray { name: name.to_string(), depth }
+    }
+
+    /// Add an output array of `depth` elements, held in block RAM
+    pub fn array_output(&mut self, name: &str, depth: usize) -> HLSArray {
+        self.graph.declare_array(name, depth, None, ArrayKind::Output);
+        HLSArray { name: name.to_string(), depth }
+    }
+
+    /// Add an array of `depth` elements held outside the kernel, reached through memory ports
+    pub fn array_interface(&mut self, name: &str, depth: usize) -> HLSArray {
+        self.graph.declare_array(name, depth, None, ArrayKind::Interface);
+        HLSArray { name: name.to_string(), depth }
+    }
+
+    /// Read `array[index]`
+    pub fn array_load(&mut self, array: &HLSArray, index: ValueId) -> HLSValue<'_> {
+        let value = self.graph.add_node_with_output(Operation::ArrayLoad(array.name.clone(), index));
+        HLSValue { value, function: self }
+    }
+
+    /// Write `array[index] = value`
+    pub fn array_store(&mut self, array: &HLSArray, index: ValueId, value: ValueId) {
+        self.graph.add_node(Operation::ArrayStore(array.name.clone(), index, value));
+    }
+
+    /// Loop `trip_count` times, folding `body` over a carried value that starts as `init`
+    ///
+    /// `body` gets the function, the iteration index and the carried value,
+    /// and returns the next carried value; the loop's result is the last one.
+    /// When the pipeline's unroll factor covers the trip count the body is
+    /// replicated with constant indices, and a body that only adds a term to
+    /// the carried value becomes one balanced adder tree over the terms.
+    ///
+    /// Otherwise the loop is rolled: the whole function becomes the loop body,
+    /// run once per iteration by a counter with the carried value held in a
+    /// register. A pipelined function's body is modulo-scheduled, so a new
+    /// iteration starts every II cycles; otherwise the control FSM runs the
+    /// iterations one after another. A function can therefore hold only one rolled loop.
+    pub fn for_loop<F>(&mut self, trip_count: usize, init: ValueId, mut body: F) -> Result<HLSValue<'_>, HlsError>
+    where
+        F: FnMut(&mut HLSFunction, ValueId, ValueId) -> ValueId,
+    {
+        let index_bits = usize::BITS - trip_count.saturating_sub(1).max(1).leading_zeros();
+        if self.graph.pipeline_config.unroll_factor >= trip_count {
+            let value = self.unrolled_loop(trip_count, index_bits, init, &mut body);
+            return Ok(HLSValue { value, function: self });
+        }
+
+        if self.graph.loop_trip_count.is_some() {
+            return Err(HlsError::Unsupported("more than one rolled loop in a function".to_string()));
+        }
+        self.graph.loop_trip_count = Some(trip_count);
+        let index = self.graph.add_node_with_output_width(Operation::LoopIndex, index_bits);
+        let carried = self.graph.add_node_with_output(Operation::LoopCarry("acc".to_string(), init));
+        let next = body(self, index, carried);
+        self.graph.add_node(Operation::LoopNext("acc".to_string(), next));
+        Ok(HLSValue { value: next, function: self })
+    }
+
+    /// Replicate `body` once per iteration, turning an accumulating chain of adds into a `ReduceAdd`
+    fn unrolled_loop<F>(&mut self, trip_count: usize, index_bits: u32, init: ValueId, body: &mut F) -> ValueId
+    where
+        F: FnMut(&mut HLSFunction, ValueId, ValueId) -> ValueId,
+    {
+        let mut carried = init;
+        // Terms added to the carried value, and the adds doing it, while every iteration is `carried + term`
+        let mut sum: Option<(Vec<ValueId>, Vec<ValueId>)> = Some((Vec::new(), Vec::new()));
+        for iteration in 0..trip_count {
+            let index = self.graph.add_node_with_output_type(Operation::Const(iteration as i64), Some(index_bits), false);
+            let next = body(self, index, carried);
+            sum = sum.and_then(|(mut terms, mut adds)| {
+                let term = match self.graph.producer(next)?.op {
+                    Operation::Add(a, b) if a == carried => b,
+                    Operation::Add(a, b) if b == carried => a,
+                    _ => return None,
+                };
+                terms.push(term);
+                adds.push(next);
+                Some((terms, adds))
+            });
+            carried = next;
+        }
+
+        let Some((terms, adds)) = sum.filter(|(terms, _)| terms.len() > 1) else { return carried };
+        // Partial sums read by anything but the next add must stay
+        let chain: HashSet<ValueId> = adds.iter().copied().collect();
+        let escapes = self.graph.nodes.iter()
+            .filter(|node| !node.output.is_some_and(|output| chain.contains(&output)))
+            .any(|node| node.op.operands().iter().any(|operand| chain.contains(operand)));
+        if escapes {
+            return carried;
+        }
+        self.graph.nodes.retain(|node| !node.output.is_some_and(|output| chain.contains(&output)));
+        self.graph.value_map.retain(|value, _| !chain.contains(value));
+
+        let starts_at_zero = matches!(self.graph.producer(init).map(|node| &node.op), Some(Operation::Const(0)));
+        let leaves = if starts_at_zero { terms } else { std::iter::once(init).chain(terms).collect() };
+        self.graph.add_node_with_output(Operation::ReduceAdd(leaves))
+    }
+
+    /// Instantiate `sub` with `inputs` in its input port order, returning its outputs in output port order
+    ///
+    /// Ports are ordered as the generated modules declare them, by
+    /// `Graph::input_names` and `Graph::output_names`. A pipelined `sub` is
+    /// scheduled first, so the call takes as many cycles as its module does.
+    /// Results are plain values, since an `HLSValue` borrows the function.
+    pub fn call(&mut self, sub: &HLSFunction, inputs: &[ValueId]) -> Result<Vec<ValueId>, HlsError> {
+        let mut callee = sub.graph.clone();
+        if callee.pipeline_config.enable && callee.loop_trip_count.is_none() && callee.pipeline_stages.is_empty() {
+            crate::passes::pipeline::PipelineScheduler::new().schedule_pipeline(&mut callee)?;
+        }
+        let outputs = callee.output_names();
+        self.graph.declare_callee(&sub.name, callee);
+
+        let handle = self.graph.add_node_with_output(Operation::Call(sub.name.clone(), inputs.to_vec()));
+        Ok(outputs.into_iter().map(|port| self.graph.add_node_with_output(Operation::CallResult(handle, port))).collect())
+    }
+
     /// Generate Verilog with pipeline scheduling
-    pub fn generate_verilog(&mut self) -> Result<String, String> {
-        // Apply pipeline scheduling if enabled
+    pub fn generate_verilog(&mut self) -> Result<String, crate::error::HlsError> {
+        self.schedule_if_pipelined()?;
+        crate::backend::verilog::generate_verilog_module(&self.graph, &self.name, None)
+    }
+
+    /// Generate Verilog like `generate_verilog`, sizing unknown widths from `params`
+    pub fn generate_verilog_parameterized(&mut self, params: &GenerationParams) -> Result<String, HlsError> {
+        self.schedule_if_pipelined()?;
+        crate::backend::verilog::generate_verilog_parameterized(&self.graph, &self.name, None, params)
+    }
+
+    /// Apply pipeline scheduling if enabled
+    fn schedule_if_pipelined(&mut self) -> Result<(), HlsError> {
         if self.graph.pipeline_config.enable {
             let mut scheduler = crate::passes::pipeline::PipelineScheduler::new();
             scheduler.schedule_pipeline(&mut self.graph)?;
         }
-
-        Ok(crate::backend::verilog::generate_verilog_module(&self.graph, &self.name))
+        Ok(())
     }
 }
 
+/// Handle to an array declared with `array_input` or `array_output`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HLSArray {
+    pub name: String,
+    pub depth: usize,
+}
+
 /// Represents a value in the HLS function
 pub struct HLSValue<'a> {
     pub value: crate::ir::graph::ValueId,
@@ -62,12 +204,14 @@ pub struct HLSValue<'a> {
 
 impl<'a> HLSValue<'a> {
     /// Add two values with automatic pipeline register insertion
+    #[allow(clippy::should_implement_trait)]
     pub fn add(self, other: HLSValue) -> HLSValue<'a> {
         let result = self.function.graph.add_node_with_output(Operation::Add(self.value, other.value));
         HLSValue { value: result, function: self.function }
     }
 
     /// Multiply two values (uses DSP slices)
+    #[allow(clippy::should_implement_trait)]
     pub fn mul(self, other: HLSValue) -> HLSValue<'a> {
         let result = self.function.graph.add_node_with_output(Operation::Mul(self.value, other.value));
         HLSValue { value: result, function: self.function }
@@ -103,3 +247,172 @@ macro_rules! pipeline {
         |f: &mut HLSFunction| f.pipeline($ii)
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+
+    /// `dot = Σ a[i] * b[i]` over two 8-element arrays, built with the given unroll factor
+    fn dot_product(unroll: usize) -> HLSFunction {
+        let mut function = HLSFunction::new("dot8");
+        // Eight reads of each array share its two block RAM ports
+        function.pipeline_advanced(4, 8, unroll);
+        let a = function.array_input("a", 8);
+        let b = function.array_input("b", 8);
+        let zero = HLSValue::constant(&mut function, 0).value;
+        let dot = function.for_loop(8, zero, |f, i, acc| {
+            let x = f.array_load(&a, i).value;
+            let y = f.array_load(&b, i).value;
+            let product = f.graph.add_node_with_output(Operation::Mul(x, y));
+            f.graph.add_node_with_output(Operation::Add(acc, product))
+        }).unwrap().value;
+        function.graph.add_node(Operation::Store("dot".to_string(), dot));
+        function
+    }
+
+    fn simulate_dot(function: &HLSFunction) -> i64 {
+        let mut sim = Simulator::new();
+        sim.set_array("a", &[1, 2, 3, 4, 5, 6, 7, 8], &function.graph);
+        sim.set_array("b", &[8, 7, 6, 5, 4, 3, 2, 1], &function.graph);
+        sim.simulate(&function.graph).unwrap()["dot"]
+    }
+
+    fn count(function: &HLSFunction, matches: fn(&Operation) -> bool) -> usize {
+        function.graph.nodes.iter().filter(|node| matches(&node.op)).count()
+    }
+
+    #[test]
+    fn test_unrolled_dot_product_feeds_an_adder_tree() {
+        let mut function = dot_product(8);
+        assert_eq!(simulate_dot(&function), 120);
+        assert_eq!(count(&function, |op| matches!(op, Operation::Mul(_, _))), 8);
+        assert_eq!(count(&function, |op| matches!(op, Operation::Add(_, _))), 0);
+        assert!(function.graph.nodes.iter().any(|node| matches!(&node.op, Operation::ReduceAdd(terms) if terms.len() == 8)));
+        assert_eq!(function.graph.loop_trip_count, None);
+
+        let verilog = function.generate_verilog().unwrap();
+        assert_eq!(verilog.matches(" * ").count(), 8);
+        assert!(verilog.contains("3-level adder tree over 8 values"));
+    }
+
+    #[test]
+    fn test_rolled_dot_product_reuses_one_mac() {
+        let mut function = dot_product(1);
+        assert_eq!(simulate_dot(&function), 120);
+        assert_eq!(count(&function, |op| matches!(op, Operation::Mul(_, _))), 1);
+        assert_eq!(function.graph.loop_trip_count, Some(8));
+
+        // Unpipelined, the control FSM runs one iteration after another
+        function.graph.pipeline_config.enable = false;
+        let verilog = function.generate_verilog().unwrap();
+        assert_eq!(verilog.matches(" * ").count(), 1);
+        assert!(verilog.contains("// Rolled loop: 8 iterations, II=2\n"));
+        assert!(verilog.contains("reg [2:0] loop_index;"));
+        assert!(verilog.contains("reg [DATA_WIDTH-1:0] acc_carry;"));
+        assert!(verilog.contains("(loop_index == 0) ? 32'd0 : acc_carry;"));
+        assert!(verilog.contains("if (loop_index == 3'd7) begin"));
+        assert!(verilog.contains("loop_index <= loop_index + 1;"));
+        // Each iteration waits a cycle for its block RAM reads
+        assert!(verilog.contains("compute_wait <= 1'd1;"));
+        assert!(verilog.contains("node_3 <= a[node_1];  // Synchronous block RAM read"));
+        assert!(verilog.contains("acc_carry <= node_6;"));
+    }
+
+    #[test]
+    fn test_pipelined_rolled_loop_is_modulo_scheduled() {
+        let mut function = dot_product(1);
+        let verilog = function.generate_verilog().unwrap();
+        assert_eq!(simulate_dot(&function), 120);
+
+        assert_eq!(verilog.matches(" * ").count(), 1);
+        assert!(verilog.contains("    // Rolled loop: 8 iterations, modulo-scheduled at II=4\n"));
+        assert!(verilog.contains("    wire loop_issue = loop_running & (ii_count == 0);\n"));
+        // Iteration i + 1 reads the carry in stage 1, after iteration i writes it in stage 4
+        assert!(verilog.contains("assign node_2 = stage_first[0] ? 32'd0 : acc_carry;"));
+        assert!(verilog.contains("if (stage_valid[3]) acc_carry <= node_9;"));
+        assert!(verilog.contains("end else if (stage_valid[5] & stage_last[5]) begin\n            dot_reg <= node_9_r5;"));
+        assert!(verilog.contains("ap_done <= stage_valid[5] & stage_last[5];"));
+        assert!(!verilog.contains("compute_wait"));
+    }
+
+    #[test]
+    fn test_sum_of_sixteen_constants() {
+        for unroll in [1, 16] {
+            let mut function = HLSFunction::new("sum16");
+            function.pipeline_advanced(1, 8, unroll);
+            let zero = HLSValue::constant(&mut function, 0).value;
+            let total = function.for_loop(16, zero, |f, _, acc| {
+                let term = f.graph.add_node_with_output(Operation::Const(7));
+                f.graph.add_node_with_output(Operation::Add(acc, term))
+            }).unwrap().value;
+            function.graph.add_node(Operation::Store("total".to_string(), total));
+            assert_eq!(Simulator::new().simulate(&function.graph).unwrap()["total"], 112, "unroll {}", unroll);
+
+            if unroll == 1 {
+                // One adder, starting an iteration every cycle
+                assert_eq!(count(&function, |op| matches!(op, Operation::Add(_, _))), 1);
+                let verilog = function.generate_verilog().unwrap();
+                assert!(verilog.contains("    // Rolled loop: 16 iterations, modulo-scheduled at II=1\n"));
+                assert!(verilog.contains("    wire loop_issue = loop_running;\n"));
+                assert!(verilog.contains("wire loop_last = (loop_index == 4'd15);"));
+                assert!(verilog.contains("if (loop_issue) acc_carry <= node_4;"));
+                // The scheduled body still sums to the same total
+                assert_eq!(Simulator::new().simulate(&function.graph).unwrap()["total"], 112);
+            }
+        }
+    }
+
+    #[test]
+    fn test_vector_add_through_external_memory_ports() {
+        let mut function = HLSFunction::new("vadd16");
+        let a = function.array_interface("a", 16);
+        let b = function.array_interface("b", 16);
+        let c = function.array_interface("c", 16);
+        let zero = HLSValue::constant(&mut function, 0).value;
+        function.for_loop(16, zero, |f, i, acc| {
+            let x = f.array_load(&a, i).value;
+            let y = f.array_load(&b, i).value;
+            let sum = f.graph.add_node_with_output(Operation::Add(x, y));
+            f.array_store(&c, i, sum);
+            acc
+        }).unwrap();
+
+        let mut sim = Simulator::new();
+        sim.set_array("a", &(0..16).collect::<Vec<_>>(), &function.graph);
+        sim.set_array("b", &(0..16).map(|i| 100 * i).collect::<Vec<_>>(), &function.graph);
+        sim.simulate(&function.graph).unwrap();
+        assert_eq!(sim.array("c").unwrap(), (0..16).map(|i| 101 * i).collect::<Vec<_>>().as_slice());
+
+        let verilog = function.generate_verilog().unwrap();
+        assert!(verilog.contains("output wire [3:0]  a_addr,"));
+        assert!(verilog.contains("input  wire [DATA_WIDTH-1:0]  b_dout,"));
+        assert!(verilog.contains("output wire [DATA_WIDTH-1:0]  c_din\n"));
+        assert!(!verilog.contains("c_dout"));
+        assert!(!verilog.contains("RAM_STYLE"));
+        assert!(verilog.contains("assign a_addr = node_1;"));
+        assert!(verilog.contains("assign a_en = state == COMPUTE;"));
+        assert!(verilog.contains("always @(*) node_3 = a_dout;  // External memory read"));
+        assert!(verilog.contains("assign c_we = state == COMPUTE && compute_wait == 0;"));
+        assert!(verilog.contains("assign c_din = node_5;  // External memory write"));
+
+        // Each interface array has a single read port to drive
+        let mut twice = HLSFunction::new("twice");
+        let a = twice.array_interface("a", 4);
+        let zero = HLSValue::constant(&mut twice, 0).value;
+        let first = twice.array_load(&a, zero).value;
+        let second = twice.array_load(&a, zero).value;
+        let sum = twice.graph.add_node_with_output(Operation::Add(first, second));
+        twice.graph.add_node(Operation::Store("sum".to_string(), sum));
+        assert!(matches!(twice.generate_verilog(), Err(HlsError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_only_one_rolled_loop_per_function() {
+        let mut function = HLSFunction::new("twice");
+        let zero = HLSValue::constant(&mut function, 0).value;
+        function.for_loop(4, zero, |f, i, acc| f.graph.add_node_with_output(Operation::Add(acc, i))).unwrap();
+        let second = function.for_loop(4, zero, |_, _, acc| acc);
+        assert!(matches!(second, Err(HlsError::Unsupported(_))));
+    }
+}
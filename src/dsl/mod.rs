@@ -1,2 +1,3 @@
 pub mod ast;
 pub mod hls; // Add HLS DSL module
+pub mod types;
@@ -0,0 +1,53 @@
+//! Value types of the DSL beyond plain integers
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Fixed-point format with the binary point tracked at the type level
+///
+/// A value is `integer_bits + fractional_bits` wide and stores `x * 2^fractional_bits`;
+/// for signed formats the sign bit counts towards `integer_bits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FixedPointType {
+    pub integer_bits: u32,
+    pub fractional_bits: u32,
+    pub signed: bool,
+}
+
+impl FixedPointType {
+    pub fn new(integer_bits: u32, fractional_bits: u32, signed: bool) -> Self {
+        Self { integer_bits, fractional_bits, signed }
+    }
+
+    /// Unsigned `Q<integer_bits>.<fractional_bits>`
+    pub fn unsigned(integer_bits: u32, fractional_bits: u32) -> Self {
+        Self::new(integer_bits, fractional_bits, false)
+    }
+
+    /// Two's-complement `Q<integer_bits>.<fractional_bits>`
+    pub fn signed(integer_bits: u32, fractional_bits: u32) -> Self {
+        Self::new(integer_bits, fractional_bits, true)
+    }
+
+    /// Total bit width
+    pub fn width(&self) -> u32 {
+        self.integer_bits + self.fractional_bits
+    }
+
+    /// Raw bit pattern for `value`, rounded to the nearest step: 1.5 in Q8.8 is 384
+    pub fn encode(&self, value: f64) -> i64 {
+        (value * (1u64 << self.fractional_bits) as f64).round() as i64
+    }
+
+    /// Real value of a raw bit pattern
+    pub fn decode(&self, raw: i64) -> f64 {
+        raw as f64 / (1u64 << self.fractional_bits) as f64
+    }
+}
+
+impl fmt::Display for FixedPointType {
+    /// `Q8.8`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Q{}.{}", self.integer_bits, self.fractional_bits)
+    }
+}
@@ -0,0 +1,87 @@
+//! Error type shared by the compiler passes, backends and simulation flow
+
+use crate::backend::testbench::PortMismatch;
+use crate::backend::verilator::VerilatorDiagnostic;
+use crate::ir::graph::{describe_errors, GraphError, NodeId};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Everything that can go wrong between a graph and a running simulation
+#[derive(Debug, Error)]
+pub enum HlsError {
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+    /// The scheduler could not place the graph's operations
+    #[error("pipeline scheduling failed: {0}")]
+    SchedulingError(String),
+    /// A recurrence through a state register takes longer than the requested initiation interval
+    #[error("II={requested} is not achievable: the recurrence through nodes {} needs II={achievable}", describe_path(.bottleneck_path))]
+    IINotAchievable { requested: usize, achievable: usize, bottleneck_path: Vec<NodeId> },
+    /// Verilator ran but rejected the design; `diagnostics` are the reports parsed from `stderr`
+    #[error("Verilator failed:\n{}", describe_verilator_failure(.stdout, .stderr, .diagnostics))]
+    VerilatorError { stdout: String, stderr: String, diagnostics: Vec<VerilatorDiagnostic> },
+    /// Verilator ran past `VerilatorOptions::timeout` and was stopped
+    #[error("Verilator did not finish within {0:?}")]
+    VerilatorTimeout(Duration),
+    #[error("{}", describe_errors(.0))]
+    ValidationError(Vec<GraphError>),
+    #[error("failed to load simulation library: {0}")]
+    LibraryLoadError(#[from] libloading::Error),
+    /// Verilator, its include directory, or the C++ compiler is missing
+    #[error("compiler not found: {0}")]
+    CompilerNotFound(String),
+    /// The C++ compiler failed to build the simulation library
+    #[error("C++ compilation failed:\nstdout: {stdout}\nstderr: {stderr}")]
+    CompileError { stdout: String, stderr: String },
+    #[error("failed to parse graph JSON: {0}")]
+    JsonError(#[from] serde_json::Error),
+    /// An optimization pass found the graph cannot be computed, e.g. a constant division by zero
+    #[error("optimization failed: {0}")]
+    OptimizationError(String),
+    /// Pass dependencies name an unregistered pass or form a cycle
+    #[error("invalid pass order: {0}")]
+    PassOrderError(String),
+    /// A formal property names a value or port the graph does not have, or cannot hold
+    #[error("invalid property: {0}")]
+    PropertyError(String),
+    /// A graph transformation would leave the graph inconsistent, e.g. extracting one of its ports
+    #[error("invalid graph transformation: {0}")]
+    TransformError(String),
+    /// A build configuration does not parse or names something that does not exist
+    #[error("invalid configuration: {0}")]
+    ConfigError(String),
+    /// The graph uses a feature this backend does not generate
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    /// The simulation ran but misbehaved
+    #[error("simulation failed: {0}")]
+    SimulationError(String),
+    /// Test case `test` (counted from 1) produced the wrong value on one or more outputs
+    #[error("test {test} failed: {}", describe_mismatches(.mismatches))]
+    SimulationMismatch { test: usize, mismatches: Vec<PortMismatch> },
+}
+
+/// `3 -> 4 -> 6`
+fn describe_path(path: &[NodeId]) -> String {
+    path.iter().map(|node_id| node_id.0.to_string()).collect::<Vec<_>>().join(" -> ")
+}
+
+/// One diagnostic per line, or the raw output when none could be parsed
+fn describe_verilator_failure(stdout: &str, stderr: &str, diagnostics: &[VerilatorDiagnostic]) -> String {
+    if diagnostics.is_empty() {
+        format!("stdout: {}\nstderr: {}", stdout, stderr)
+    } else {
+        diagnostics.iter().map(VerilatorDiagnostic::to_string).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// `result expected 6, got 5, carry expected 0, got 1`
+fn describe_mismatches(mismatches: &[PortMismatch]) -> String {
+    mismatches.iter().map(PortMismatch::to_string).collect::<Vec<_>>().join(", ")
+}
+
+impl From<Vec<GraphError>> for HlsError {
+    fn from(errors: Vec<GraphError>) -> Self {
+        HlsError::ValidationError(errors)
+    }
+}
@@ -0,0 +1,136 @@
+//! IR graph for the 0+ trading decision
+//!
+//! Hardware counterpart of `fpga_trading_decision`, built directly on the IR
+//! so it can be scheduled and emitted as a single-cycle decision pipeline.
+
+use crate::hft::ZeroPlusStrategy;
+use crate::ir::graph::{Graph, Operation, ValueId};
+
+impl ZeroPlusStrategy {
+    /// `fpga_trading_decision` lowered into the IR, operation for operation
+    ///
+    /// Inputs are the function's arguments under the same names, typed like
+    /// them: 32-bit prices and quantities, 1-bit queue flags, a 32-bit signed
+    /// position and an 8-bit fill side. Outputs are the 8-bit `action` and
+    /// the 32-bit `price` and `quantity`. The scratch path is included, so the
+    /// two match for every input.
+    pub fn to_fpga_graph() -> Graph {
+        let mut graph = Graph::new();
+        let mut port = |name: &str, width: u32, signed: bool| {
+            graph.add_node_with_output_type(Operation::Load(name.to_string()), Some(width), signed)
+        };
+        let best_bid_price = port("best_bid_price", 32, false);
+        let best_ask_price = port("best_ask_price", 32, false);
+        let best_bid_qty = port("best_bid_qty", 32, false);
+        let best_ask_qty = port("best_ask_qty", 32, false);
+        let bid_queue_strong = port("bid_queue_strong", 1, false);
+        let ask_queue_strong = port("ask_queue_strong", 1, false);
+        let current_position = port("current_position", 32, true);
+        let last_fill_price = port("last_fill_price", 32, false);
+        let last_fill_side = port("last_fill_side", 8, false);
+        let constant = |graph: &mut Graph, value: i64, width: u32| {
+            graph.add_node_with_output_type(Operation::Const(value), Some(width), false)
+        };
+        let op = |graph: &mut Graph, op: Operation| graph.add_node_with_output(op);
+        let select = |graph: &mut Graph, condition: ValueId, a: ValueId, b: ValueId, width: u32| {
+            graph.add_node_with_output_type(Operation::Mux(condition, a, b), Some(width), false)
+        };
+
+        let spread = op(&mut graph, Operation::SubSat(best_ask_price, best_bid_price, 32));
+
+        // Scratch a position whose queue weakened or whose price moved against the fill
+        let [zero, one, two] = [0, 1, 2].map(|value| constant(&mut graph, value, 8));
+        let zero_position = constant(&mut graph, 0, 32);
+        let holding = op(&mut graph, Operation::CmpNe(current_position, zero_position));
+        let was_buy = op(&mut graph, Operation::CmpEq(last_fill_side, one));
+        let was_sell = op(&mut graph, Operation::CmpEq(last_fill_side, two));
+        let bid_weak = op(&mut graph, Operation::Not(bid_queue_strong));
+        let bid_fell = op(&mut graph, Operation::CmpLt(best_bid_price, last_fill_price));
+        let buy_broken = op(&mut graph, Operation::Or(bid_weak, bid_fell));
+        let scratch_buy = op(&mut graph, Operation::And(was_buy, buy_broken));
+        let ask_weak = op(&mut graph, Operation::Not(ask_queue_strong));
+        let ask_rose = op(&mut graph, Operation::CmpGt(best_ask_price, last_fill_price));
+        let sell_broken = op(&mut graph, Operation::Or(ask_weak, ask_rose));
+        let scratch_sell = op(&mut graph, Operation::And(was_sell, sell_broken));
+        let either_broken = op(&mut graph, Operation::Or(scratch_buy, scratch_sell));
+        let scratch = op(&mut graph, Operation::And(holding, either_broken));
+        // The opposite side of the fill, at that side's price, for the whole position
+        let scratch_action = select(&mut graph, was_buy, two, one, 8);
+        let scratch_price = select(&mut graph, was_buy, best_bid_price, best_ask_price, 32);
+        let scratch_quantity = graph.add_node_with_output_type(Operation::Abs(current_position), Some(32), false);
+
+        // Join a strong queue when flat and the spread is one tick
+        let flat = op(&mut graph, Operation::CmpEq(current_position, zero_position));
+        let one_tick = constant(&mut graph, 1, 32);
+        let spread_optimal = op(&mut graph, Operation::CmpEq(spread, one_tick));
+        let tradable = op(&mut graph, Operation::And(flat, spread_optimal));
+        let min_queue = constant(&mut graph, 100, 32);
+        let bid_deep = op(&mut graph, Operation::CmpGe(best_bid_qty, min_queue));
+        let bid_opportunity = op(&mut graph, Operation::And(bid_queue_strong, bid_deep));
+        let buy = op(&mut graph, Operation::And(tradable, bid_opportunity));
+        let ask_deep = op(&mut graph, Operation::CmpGe(best_ask_qty, min_queue));
+        let ask_opportunity = op(&mut graph, Operation::And(ask_queue_strong, ask_deep));
+        let sell = op(&mut graph, Operation::And(tradable, ask_opportunity));
+        let trades = op(&mut graph, Operation::Or(buy, sell));
+
+        // Scratch first, then buy, then sell, else hold
+        let sell_or_hold = select(&mut graph, sell, two, zero, 8);
+        let entry_action = select(&mut graph, buy, one, sell_or_hold, 8);
+        let action = select(&mut graph, scratch, scratch_action, entry_action, 8);
+        let ask_or_zero = select(&mut graph, sell, best_ask_price, zero_position, 32);
+        let entry_price = select(&mut graph, buy, best_bid_price, ask_or_zero, 32);
+        let price = select(&mut graph, scratch, scratch_price, entry_price, 32);
+        let lot = constant(&mut graph, 50, 32);
+        let entry_quantity = select(&mut graph, trades, lot, zero_position, 32);
+        let quantity = select(&mut graph, scratch, scratch_quantity, entry_quantity, 32);
+
+        graph.add_node(Operation::Store("action".to_string(), action));
+        graph.add_node(Operation::Store("price".to_string(), price));
+        graph.add_node(Operation::Store("quantity".to_string(), quantity));
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+    use crate::hft::fpga_trading_decision;
+
+    #[test]
+    fn test_strategy_graph_matches_software_decision_including_scratches() {
+        let graph = ZeroPlusStrategy::to_fpga_graph();
+        assert!(graph.type_check().is_ok());
+        let mut sim = Simulator::new();
+        for (bid, ask) in [(80300u32, 80301u32), (80300, 80302), (80300, 80299), (0, 1)] {
+            for (bid_qty, ask_qty) in [(99u32, 100u32), (100, 99), (150, 150)] {
+                for (bid_strong, ask_strong) in [(false, false), (true, false), (false, true), (true, true)] {
+                    for position in [0, 5, -7, i32::MIN] {
+                        for (last_fill_price, last_fill_side) in [(80299u32, 1u8), (80301, 1), (80300, 2), (80302, 2), (80300, 0), (0, 3)] {
+                            let inputs = [
+                                ("best_bid_price", i64::from(bid)), ("best_ask_price", i64::from(ask)),
+                                ("best_bid_qty", i64::from(bid_qty)), ("best_ask_qty", i64::from(ask_qty)),
+                                ("bid_queue_strong", bid_strong as i64), ("ask_queue_strong", ask_strong as i64),
+                                ("current_position", i64::from(position)),
+                                ("last_fill_price", i64::from(last_fill_price)), ("last_fill_side", i64::from(last_fill_side)),
+                            ];
+                            for (name, value) in inputs {
+                                sim.set_input(name, value, &graph);
+                            }
+                            let outputs = sim.simulate(&graph).unwrap();
+
+                            let (action, price, quantity) = fpga_trading_decision(
+                                bid, ask, bid_qty, ask_qty, bid_strong, ask_strong, position, last_fill_price, last_fill_side,
+                            );
+                            assert_eq!(
+                                (outputs["action"], outputs["price"], outputs["quantity"]),
+                                (i64::from(action), i64::from(price), i64::from(quantity)),
+                                "{:?}", inputs
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
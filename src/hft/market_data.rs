@@ -1,6 +1,10 @@
+use crate::ir::graph::{Graph, Operation};
 use std::collections::VecDeque;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Fractional bits of `MarketSnapshot::pressure_metric`, so 1.0 is 65536
+pub const PRESSURE_FRACTIONAL_BITS: u32 = 16;
+
 /// Represents a single order in the order book
 #[derive(Debug, Clone)]
 pub struct Order {
@@ -135,8 +139,8 @@ impl MarketDataSimulator {
         }
 
         // Sort queues by price
-        self.bid_queues.sort_by(|a, b| b.price.cmp(&a.price)); // Descending for bids
-        self.ask_queues.sort_by(|a, b| a.price.cmp(&b.price)); // Ascending for asks
+        self.bid_queues.sort_by_key(|q| std::cmp::Reverse(q.price)); // Descending for bids
+        self.ask_queues.sort_by_key(|q| q.price); // Ascending for asks
     }
 
     pub fn advance_time(&mut self, microseconds: u64) {
@@ -178,7 +182,7 @@ impl MarketDataSimulator {
                     let mut new_queue = OrderQueue::new(price);
                     new_queue.add_order(order);
                     self.bid_queues.push(new_queue);
-                    self.bid_queues.sort_by(|a, b| b.price.cmp(&a.price));
+                    self.bid_queues.sort_by_key(|q| std::cmp::Reverse(q.price));
                 }
             }
             OrderSide::Sell => {
@@ -188,7 +192,7 @@ impl MarketDataSimulator {
                     let mut new_queue = OrderQueue::new(price);
                     new_queue.add_order(order);
                     self.ask_queues.push(new_queue);
-                    self.ask_queues.sort_by(|a, b| a.price.cmp(&b.price));
+                    self.ask_queues.sort_by_key(|q| q.price);
                 }
             }
         }
@@ -292,7 +296,7 @@ impl MarketDataSimulator {
         println!("Time: {} μs", self.current_time);
         
         println!("\nASKS (Sell Orders):");
-        for (_i, queue) in self.ask_queues.iter().take(5).enumerate() {
+        for queue in self.ask_queues.iter().take(5) {
             let strength = if queue.is_strong() { "STRONG" } else if queue.is_weak() { "WEAK" } else { "MEDIUM" };
             println!("  ${:.2} | Qty: {:3} | Orders: {} | {}", 
                 queue.price as f64 / 100.0, queue.total_quantity, queue.orders.len(), strength);
@@ -303,7 +307,7 @@ impl MarketDataSimulator {
         }
         
         println!("BIDS (Buy Orders):");
-        for (_i, queue) in self.bid_queues.iter().take(5).enumerate() {
+        for queue in self.bid_queues.iter().take(5) {
             let strength = if queue.is_strong() { "STRONG" } else if queue.is_weak() { "WEAK" } else { "MEDIUM" };
             println!("  ${:.2} | Qty: {:3} | Orders: {} | {}", 
                 queue.price as f64 / 100.0, queue.total_quantity, queue.orders.len(), strength);
@@ -323,3 +327,96 @@ pub struct MarketSnapshot {
     pub ask_queue_strength: bool,
     pub spread: u32,
 }
+
+impl MarketSnapshot {
+    /// Order book pressure `(bid_qty - ask_qty) / (bid_qty + ask_qty)` with
+    /// `PRESSURE_FRACTIONAL_BITS` fractional bits, rounded towards zero
+    ///
+    /// Positive when the bid outweighs the ask; 0 for an empty book.
+    pub fn pressure_metric(&self) -> i32 {
+        let (bid, ask) = (i64::from(self.best_bid_qty), i64::from(self.best_ask_qty));
+        let total = bid + ask;
+        if total == 0 {
+            return 0;
+        }
+        (((bid - ask) << PRESSURE_FRACTIONAL_BITS) / total) as i32
+    }
+}
+
+/// Build the order book pressure graph: `best_bid_qty` and `best_ask_qty`
+/// in, the signed `pressure` out with `fractional_bits` fractional bits
+///
+/// The hardware counterpart of `MarketSnapshot::pressure_metric` when
+/// `fractional_bits` is `PRESSURE_FRACTIONAL_BITS`. The graph is not
+/// pipelined, so it emits as combinational logic.
+///
+/// # Panics
+///
+/// If `output_width` cannot hold ±1.0, i.e. is less than `fractional_bits + 2`, or is over 32 bits.
+pub fn build_pressure_graph(output_width: u32, fractional_bits: u32) -> Graph {
+    assert!(
+        fractional_bits + 2 <= output_width && output_width <= 32,
+        "a {}-bit pressure cannot hold ±1.0 with {} fractional bits", output_width, fractional_bits
+    );
+    let mut graph = Graph::new();
+    let bid = graph.add_node_with_output_width(Operation::Load("best_bid_qty".to_string()), 32);
+    let ask = graph.add_node_with_output_width(Operation::Load("best_ask_qty".to_string()), 32);
+
+    // Signed difference and total, one bit wider than the quantities
+    let imbalance = graph.add_node_with_output_type(Operation::Sub(bid, ask), Some(33), true);
+    let total = graph.add_node_with_output_type(Operation::Add(bid, ask), Some(34), true);
+    let shift = graph.add_node_with_output_width(Operation::Const(i64::from(fractional_bits)), 5);
+    let scaled = graph.add_node_with_output_type(Operation::Shl(imbalance, shift), Some(33 + fractional_bits), true);
+
+    // An empty book divides 0 by 1
+    let zero = graph.add_node_with_output_type(Operation::Const(0), Some(34), true);
+    let one = graph.add_node_with_output_type(Operation::Const(1), Some(34), true);
+    let empty = graph.add_node_with_output(Operation::CmpEq(total, zero));
+    let divisor = graph.add_node_with_output_type(Operation::Mux(empty, one, total), Some(34), true);
+    let pressure = graph.add_node_with_output_type(Operation::Div(scaled, divisor), Some(output_width), true);
+    graph.add_node(Operation::Store("pressure".to_string(), pressure));
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+    use crate::backend::verilog::generate_verilog_module;
+
+    fn snapshot(best_bid_qty: u32, best_ask_qty: u32) -> MarketSnapshot {
+        MarketSnapshot {
+            timestamp: 0,
+            best_bid_price: 802,
+            best_ask_price: 803,
+            best_bid_qty,
+            best_ask_qty,
+            bid_queue_strength: false,
+            ask_queue_strength: false,
+            spread: 1,
+        }
+    }
+
+    #[test]
+    fn test_pressure_graph_matches_the_software_metric() {
+        let graph = build_pressure_graph(32, PRESSURE_FRACTIONAL_BITS);
+        assert!(graph.validate().is_ok());
+        // 150 against 50 is a pressure of 0.5
+        assert_eq!(snapshot(150, 50).pressure_metric(), 1 << 15);
+
+        for (bid, ask) in [(150, 50), (50, 150), (0, 0), (7, 3), (u32::MAX, 0), (1, u32::MAX)] {
+            let mut sim = Simulator::new();
+            sim.set_input("best_bid_qty", i64::from(bid), &graph);
+            sim.set_input("best_ask_qty", i64::from(ask), &graph);
+            let pressure = sim.simulate(&graph).unwrap()["pressure"];
+            assert_eq!(pressure, i64::from(snapshot(bid, ask).pressure_metric()), "bid {} ask {}", bid, ask);
+        }
+
+        let verilog = generate_verilog_module(&graph, "order_book_pressure", None).unwrap();
+        assert!(verilog.contains("input  wire [31:0]  best_bid_qty"));
+        assert!(verilog.contains("output wire signed [31:0]  pressure"));
+        // One combinational divide, with no pipeline stages around it
+        assert!(verilog.matches("  // Division").count() == 1);
+        assert!(!verilog.contains("stage_valid"));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod market_data;
 pub mod zero_plus;
+pub mod fpga_graph;
 
-pub use market_data::{MarketDataSimulator, MarketSnapshot, Order, OrderSide, OrderQueue};
+pub use market_data::{build_pressure_graph, MarketDataSimulator, MarketSnapshot, Order, OrderSide, OrderQueue, PRESSURE_FRACTIONAL_BITS};
 pub use zero_plus::{ZeroPlusStrategy, TradingSignal, TradingAction, SignalUrgency, StrategyStats, fpga_trading_decision};
@@ -48,6 +48,12 @@ pub enum SignalUrgency {
     Normal,      // Can execute within 100 microseconds
 }
 
+impl Default for ZeroPlusStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ZeroPlusStrategy {
     pub fn new() -> Self {
         Self {
@@ -120,7 +126,7 @@ impl ZeroPlusStrategy {
         TradingSignal {
             action,
             price,
-            quantity: self.position.abs() as u32,
+            quantity: self.position.unsigned_abs(),
             urgency: SignalUrgency::Immediate,
         }
     }
@@ -265,6 +271,7 @@ impl StrategyStats {
 
 /// FPGA-optimized decision logic for ultra-low latency
 /// This represents the core logic that would be implemented in Verilog
+#[allow(clippy::too_many_arguments)] // Mirrors the flat FPGA port list
 pub fn fpga_trading_decision(
     // Market data inputs (32-bit for FPGA efficiency)
     best_bid_price: u32,
@@ -282,11 +289,7 @@ pub fn fpga_trading_decision(
 ) -> (u8, u32, u32) { // Returns: (action, price, quantity)
     // Action codes: 0 = Hold, 1 = Buy, 2 = Sell, 3 = Scratch
     
-    let spread = if best_ask_price > best_bid_price {
-        best_ask_price - best_bid_price
-    } else {
-        0
-    };
+    let spread = best_ask_price.saturating_sub(best_bid_price);
 
     // Check if we need to scratch first
     if current_position != 0 {
@@ -299,7 +302,7 @@ pub fn fpga_trading_decision(
         if should_scratch {
             let scratch_action = if last_fill_side == 1 { 2 } else { 1 }; // Opposite side
             let scratch_price = if scratch_action == 2 { best_bid_price } else { best_ask_price };
-            return (scratch_action, scratch_price, current_position.abs() as u32);
+            return (scratch_action, scratch_price, current_position.unsigned_abs());
         }
     }
 
@@ -0,0 +1,192 @@
+//! Graphviz export of IR graphs
+//!
+//! `Graph::to_dot` renders one vertex per node and one edge per dataflow use.
+//! Scheduled graphs group their nodes into a coloured cluster per pipeline
+//! stage, which makes it easy to check what the scheduler put in which cycle:
+//!
+//! ```text
+//! dot -Tsvg target/graphs/mac.dot -o mac.svg
+//! ```
+
+use crate::ir::graph::{Graph, Node, Operation, ValueId};
+use std::collections::HashSet;
+
+/// Cluster backgrounds, cycled through by stage
+const STAGE_COLORS: [&str; 6] = ["aliceblue", "honeydew", "lavenderblush", "lightyellow", "mintcream", "mistyrose"];
+
+impl Graph {
+    /// Render the graph as a Graphviz `digraph` named after the module
+    pub fn to_dot(&self, module_name: &str) -> String {
+        let mut dot = String::new();
+        dot.push_str(&format!("digraph \"{}\" {{\n", module_name));
+        dot.push_str("    rankdir=TB;\n");
+        dot.push_str("    node [shape=ellipse, fontname=\"monospace\"];\n\n");
+
+        let mut clustered = HashSet::new();
+        // Cycles only reserved for operations in flight have nothing to draw
+        for stage in self.pipeline_stages.iter().filter(|stage| !stage.operations.is_empty()) {
+            dot.push_str(&format!("    subgraph cluster_stage_{} {{\n", stage.stage));
+            dot.push_str(&format!("        label=\"stage {} (cycle {})\";\n", stage.stage, stage.cycle));
+            dot.push_str(&format!("        style=\"filled,dashed\";\n        fillcolor=\"{}\";\n", STAGE_COLORS[stage.stage % STAGE_COLORS.len()]));
+            for node in self.nodes.iter().filter(|node| stage.operations.contains(&node.id)) {
+                dot.push_str(&format!("        {}\n", node_statement(node)));
+                clustered.insert(node.id);
+            }
+            dot.push_str("    }\n");
+        }
+        for node in self.nodes.iter().filter(|node| !clustered.contains(&node.id)) {
+            dot.push_str(&format!("    {}\n", node_statement(node)));
+        }
+        dot.push('\n');
+
+        for node in &self.nodes {
+            for value in node.op.operands() {
+                if let Some(producer) = self.producer(value) {
+                    dot.push_str(&format!("    n{} -> n{};\n", producer.id.0, node.id.0));
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Node declaration with its label and a shape by operation kind
+fn node_statement(node: &Node) -> String {
+    let style = match node.op {
+        Operation::Const(_) => ", shape=diamond",
+        Operation::Load(_) | Operation::ArrayLoad(_, _) => ", shape=box",
+        Operation::Store(_, _) | Operation::ArrayStore(_, _, _) => ", shape=doublecircle",
+        Operation::PipelineRegister(_) => ", shape=box3d",
+        _ => "",
+    };
+    format!("n{} [label=\"n{}: {}\"{}];", node.id.0, node.id.0, node_label(node), style)
+}
+
+/// `%out = op %operands`, with the result type when it is known
+fn node_label(node: &Node) -> String {
+    let operands: Vec<String> = node.op.operands().into_iter().map(value_name).collect();
+    let operation = match &node.op {
+        Operation::Load(name) => format!("load {}", name),
+        Operation::Store(name, _) => format!("store {}, {}", name, operands.join(", ")),
+        Operation::Const(value) => format!("const {}", value),
+        Operation::ArrayLoad(name, _) => format!("load {}[{}]", name, operands[0]),
+        Operation::ArrayStore(name, _, _) => format!("store {}[{}], {}", name, operands[0], operands[1]),
+        Operation::Call(name, _) => format!("call {}({})", name, operands.join(", ")),
+        Operation::CallResult(_, port) => format!("result {}, {}", operands[0], port),
+        op => format!("{} {}", mnemonic(op), operands.join(", ")).trim_end().to_string(),
+    };
+
+    match (node.output, node.output_width) {
+        (Some(output), Some(width)) => {
+            let sign = if node.signed { "i" } else { "u" };
+            format!("{} = {} : {}{}", value_name(output), operation, sign, width)
+        }
+        (Some(output), None) => format!("{} = {}", value_name(output), operation),
+        (None, _) => operation,
+    }
+}
+
+fn value_name(value: ValueId) -> String {
+    format!("%{}", value.0)
+}
+
+fn mnemonic(op: &Operation) -> &'static str {
+    match op {
+        Operation::Add(_, _) => "add",
+        Operation::Sub(_, _) => "sub",
+        Operation::Mul(_, _) => "mul",
+        Operation::SAdd(_, _) => "sadd",
+        Operation::SSub(_, _) => "ssub",
+        Operation::SMul(_, _) => "smul",
+        Operation::AddSat(_, _, _) => "add.sat",
+        Operation::SubSat(_, _, _) => "sub.sat",
+        Operation::Div(_, _) => "div",
+        Operation::And(_, _) => "and",
+        Operation::Or(_, _) => "or",
+        Operation::Not(_) => "not",
+        Operation::Xor(_, _) => "xor",
+        Operation::Nand(_, _) => "nand",
+        Operation::Nor(_, _) => "nor",
+        Operation::Xnor(_, _) => "xnor",
+        Operation::Concat(_, _) => "concat",
+        Operation::Slice(_, _, _) => "slice",
+        Operation::CmpLt(_, _) => "lt",
+        Operation::CmpEq(_, _) => "eq",
+        Operation::CmpGt(_, _) => "gt",
+        Operation::CmpGe(_, _) => "ge",
+        Operation::CmpLe(_, _) => "le",
+        Operation::CmpNe(_, _) => "ne",
+        Operation::Mux(_, _, _) => "mux",
+        Operation::Abs(_) => "abs",
+        Operation::Sqrt(_) => "sqrt",
+        Operation::Min(_, _) => "min",
+        Operation::Max(_, _) => "max",
+        Operation::Shl(_, _) => "shl",
+        Operation::Shr(_, _) => "shr",
+        Operation::Fma(_, _, _) => "fma",
+        Operation::ReduceAdd(_) => "radd",
+        Operation::ReduceMax(_) => "rmax",
+        Operation::ReduceMin(_) => "rmin",
+        Operation::LoopIndex => "index",
+        Operation::LoopCarry(_, _) => "carry",
+        Operation::LoopNext(_, _) => "next",
+        Operation::RegisterLoad(_) => "state",
+        Operation::RegisterStore(_, _) => "update",
+        Operation::PipelineRegister(_) => "reg",
+        Operation::PipelineBarrier => "barrier",
+        Operation::Nop => "nop",
+        Operation::Load(_) => "load",
+        Operation::Store(_, _) => "store",
+        Operation::Const(_) => "const",
+        Operation::ArrayLoad(_, _) => "aload",
+        Operation::ArrayStore(_, _, _) => "astore",
+        Operation::Call(_, _) => "call",
+        Operation::CallResult(_, _) => "result",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+    use crate::passes::dce::run_dce_pass;
+    use crate::passes::pipeline::run_pipeline_pass;
+
+    #[test]
+    fn test_dot_for_simple_adder() {
+        let graph = lower_expr_to_graph(&output("result", add(input("a", 8), input("b", 8))));
+        let dot = graph.to_dot("adder");
+
+        assert!(dot.starts_with("digraph \"adder\" {"));
+        assert!(dot.contains("n0 [label=\"n0: %0 = load a : u8\", shape=box];"));
+        assert!(dot.contains("n1 [label=\"n1: %1 = load b : u8\", shape=box];"));
+        assert!(dot.contains("n2 [label=\"n2: %2 = add %0, %1 : u9\"];"));
+        assert!(dot.contains("n3 [label=\"n3: store result, %2\", shape=doublecircle];"));
+        assert!(dot.contains("n0 -> n2;") && dot.contains("n1 -> n2;") && dot.contains("n2 -> n3;"));
+        assert!(!dot.contains("cluster"));
+
+        let constant = lower_expr_to_graph(&output("result", add(input("a", 8), const_val(1, 8))));
+        assert!(constant.to_dot("inc").contains("[label=\"n1: %1 = const 1 : u8\", shape=diamond];"));
+    }
+
+    #[test]
+    fn test_pipelined_mac_has_stage_clusters() {
+        let mut graph = crate::ir::lower::lowered_mac();
+        graph.enable_pipeline(1, 4, 1);
+        run_pipeline_pass(&mut graph).unwrap();
+        run_dce_pass(&mut graph);
+        let dot = graph.to_dot("mac");
+
+        // Loads and multiplies get their own cycles and the adds and the store chain
+        // into one; `e` is registered through the three multiply cycles
+        assert_eq!(dot.matches("subgraph cluster_stage_").count(), 5);
+        assert_eq!(dot.matches("shape=box3d").count(), 3);
+        assert!(dot.contains("subgraph cluster_stage_2 {\n        label=\"stage 2 (cycle 2)\";"));
+        // Stages are told apart by colour
+        assert!(dot.contains("fillcolor=\"aliceblue\"") && dot.contains("fillcolor=\"lavenderblush\""));
+        // Two operands into each mul and add, plus the store and the register chain
+        assert_eq!(dot.matches(" -> ").count(), 12);
+    }
+}
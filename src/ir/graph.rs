@@ -1,13 +1,23 @@
-use std::collections::HashMap;
+use crate::dsl::types::FixedPointType;
+use crate::error::HlsError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Cycles of a pipelined square root, one per result bit of a 32-bit radicand
+pub const SQRT_LATENCY: usize = 16;
+
+/// Bits of a value of unknown width, the `DATA_WIDTH` default of the generated modules
+pub const DEFAULT_WIDTH: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ValueId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct NodeId(pub usize);
 
 /// Pipeline configuration for operations
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PipelineConfig {
     pub enable: bool,
     pub initiation_interval: usize, // II - cycles between new inputs
@@ -27,14 +37,14 @@ impl Default for PipelineConfig {
 }
 
 /// Pipeline stage information for scheduling
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PipelineStage {
     pub stage: usize,
     pub cycle: usize,
     pub operations: Vec<NodeId>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Operation {
     Add(ValueId, ValueId),
     Sub(ValueId, ValueId),
@@ -54,11 +64,35 @@ pub enum Operation {
     Const(i64),
     Mux(ValueId, ValueId, ValueId), // Conditional select (condition, true_val, false_val)
     Abs(ValueId),                   // Absolute value
+    Sqrt(ValueId),                  // Unsigned integer square root, floor(sqrt(a))
     Min(ValueId, ValueId),          // Minimum of two values
     Max(ValueId, ValueId),          // Maximum of two values
     Shl(ValueId, ValueId),          // Left shift
-    Shr(ValueId, ValueId),          // Right shift  
+    Shr(ValueId, ValueId),          // Logical right shift
     Xor(ValueId, ValueId),          // Bitwise XOR
+    Nand(ValueId, ValueId),         // Bitwise NAND
+    Nor(ValueId, ValueId),          // Bitwise NOR
+    Xnor(ValueId, ValueId),         // Bitwise XNOR
+    SAdd(ValueId, ValueId),         // Two's-complement add, whatever the operand types
+    SSub(ValueId, ValueId),         // Two's-complement subtract
+    SMul(ValueId, ValueId),         // Two's-complement multiply
+    AddSat(ValueId, ValueId, u32),  // a + b clamped to the range of a u32-bit result
+    SubSat(ValueId, ValueId, u32),  // a - b clamped to the range of a u32-bit result
+    Concat(ValueId, ValueId),       // Bit concatenation {high, low}
+    Slice(ValueId, u32, u32),       // Bit extraction value[high:low]
+    Fma(ValueId, ValueId, ValueId), // Fused multiply-add a*b + c (one DSP48E2)
+    ArrayLoad(String, ValueId),     // Read array[index] (one-cycle BRAM read)
+    ArrayStore(String, ValueId, ValueId), // Write array[index] = value
+    ReduceAdd(Vec<ValueId>),        // Sum of all values, as a balanced adder tree
+    ReduceMax(Vec<ValueId>),        // Largest value, as a balanced comparator tree
+    ReduceMin(Vec<ValueId>),        // Smallest value, as a balanced comparator tree
+    LoopIndex,                      // Iteration number of the graph's rolled loop
+    LoopCarry(String, ValueId),     // Loop-carried register: the operand on the first iteration, then the last LoopNext
+    LoopNext(String, ValueId),      // Value of a loop-carried register for the next iteration
+    RegisterLoad(String),           // Value a state register held when the transaction started
+    RegisterStore(String, ValueId), // Value a state register holds for the next transaction
+    Call(String, Vec<ValueId>),     // Instance of a declared callee, fed its inputs in port order; yields a 1-bit handle
+    CallResult(ValueId, String),    // Output port of the call whose handle is the operand
     
     // Pipeline-specific operations
     PipelineRegister(ValueId),     // Insert pipeline register
@@ -66,16 +100,296 @@ pub enum Operation {
     Nop,
 }
 
+impl Operation {
+    /// Variant name, e.g. `"Add"`, as `DelayTable::overrides` keys it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Add(..) => "Add",
+            Operation::Sub(..) => "Sub",
+            Operation::Mul(..) => "Mul",
+            Operation::Div(..) => "Div",
+            Operation::And(..) => "And",
+            Operation::Or(..) => "Or",
+            Operation::Not(..) => "Not",
+            Operation::CmpLt(..) => "CmpLt",
+            Operation::CmpEq(..) => "CmpEq",
+            Operation::CmpGt(..) => "CmpGt",
+            Operation::CmpGe(..) => "CmpGe",
+            Operation::CmpLe(..) => "CmpLe",
+            Operation::CmpNe(..) => "CmpNe",
+            Operation::Load(..) => "Load",
+            Operation::Store(..) => "Store",
+            Operation::Const(..) => "Const",
+            Operation::Mux(..) => "Mux",
+            Operation::Abs(..) => "Abs",
+            Operation::Sqrt(..) => "Sqrt",
+            Operation::Min(..) => "Min",
+            Operation::Max(..) => "Max",
+            Operation::Shl(..) => "Shl",
+            Operation::Shr(..) => "Shr",
+            Operation::Xor(..) => "Xor",
+            Operation::Nand(..) => "Nand",
+            Operation::Nor(..) => "Nor",
+            Operation::Xnor(..) => "Xnor",
+            Operation::SAdd(..) => "SAdd",
+            Operation::SSub(..) => "SSub",
+            Operation::SMul(..) => "SMul",
+            Operation::AddSat(..) => "AddSat",
+            Operation::SubSat(..) => "SubSat",
+            Operation::Concat(..) => "Concat",
+            Operation::Slice(..) => "Slice",
+            Operation::Fma(..) => "Fma",
+            Operation::ArrayLoad(..) => "ArrayLoad",
+            Operation::ArrayStore(..) => "ArrayStore",
+            Operation::ReduceAdd(..) => "ReduceAdd",
+            Operation::ReduceMax(..) => "ReduceMax",
+            Operation::ReduceMin(..) => "ReduceMin",
+            Operation::LoopIndex => "LoopIndex",
+            Operation::LoopCarry(..) => "LoopCarry",
+            Operation::LoopNext(..) => "LoopNext",
+            Operation::RegisterLoad(..) => "RegisterLoad",
+            Operation::RegisterStore(..) => "RegisterStore",
+            Operation::Call(..) => "Call",
+            Operation::CallResult(..) => "CallResult",
+            Operation::PipelineRegister(..) => "PipelineRegister",
+            Operation::PipelineBarrier => "PipelineBarrier",
+            Operation::Nop => "Nop",
+        }
+    }
+
+    /// Values read by this operation, in operand order
+    pub fn operands(&self) -> Vec<ValueId> {
+        match self {
+            Operation::Add(a, b) | Operation::Sub(a, b) | Operation::Mul(a, b) |
+            Operation::SAdd(a, b) | Operation::SSub(a, b) | Operation::SMul(a, b) |
+            Operation::Div(a, b) | Operation::And(a, b) | Operation::Or(a, b) |
+            Operation::CmpLt(a, b) | Operation::CmpEq(a, b) | Operation::CmpGt(a, b) |
+            Operation::CmpGe(a, b) | Operation::CmpLe(a, b) | Operation::CmpNe(a, b) |
+            Operation::Min(a, b) | Operation::Max(a, b) | Operation::Shl(a, b) |
+            Operation::Shr(a, b) | Operation::Xor(a, b) | Operation::Nand(a, b) |
+            Operation::Nor(a, b) | Operation::Xnor(a, b) | Operation::Concat(a, b) |
+            Operation::AddSat(a, b, _) | Operation::SubSat(a, b, _) => vec![*a, *b],
+            Operation::Not(a) | Operation::Abs(a) | Operation::Sqrt(a) | Operation::PipelineRegister(a) |
+            Operation::Slice(a, _, _) |
+            Operation::Store(_, a) | Operation::ArrayLoad(_, a) |
+            Operation::LoopCarry(_, a) | Operation::LoopNext(_, a) | Operation::RegisterStore(_, a) |
+            Operation::CallResult(a, _) => vec![*a],
+            Operation::ArrayStore(_, index, value) => vec![*index, *value],
+            Operation::Mux(sel, a, b) | Operation::Fma(sel, a, b) => vec![*sel, *a, *b],
+            Operation::ReduceAdd(values) | Operation::ReduceMax(values) | Operation::ReduceMin(values) |
+            Operation::Call(_, values) => values.clone(),
+            Operation::Load(_) | Operation::Const(_) | Operation::LoopIndex | Operation::RegisterLoad(_) |
+            Operation::PipelineBarrier | Operation::Nop => vec![],
+        }
+    }
+
+    /// Mutable references to the operand values, for rewriting uses
+    pub fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match self {
+            Operation::Add(a, b) | Operation::Sub(a, b) | Operation::Mul(a, b) |
+            Operation::SAdd(a, b) | Operation::SSub(a, b) | Operation::SMul(a, b) |
+            Operation::Div(a, b) | Operation::And(a, b) | Operation::Or(a, b) |
+            Operation::CmpLt(a, b) | Operation::CmpEq(a, b) | Operation::CmpGt(a, b) |
+            Operation::CmpGe(a, b) | Operation::CmpLe(a, b) | Operation::CmpNe(a, b) |
+            Operation::Min(a, b) | Operation::Max(a, b) | Operation::Shl(a, b) |
+            Operation::Shr(a, b) | Operation::Xor(a, b) | Operation::Nand(a, b) |
+            Operation::Nor(a, b) | Operation::Xnor(a, b) | Operation::Concat(a, b) |
+            Operation::AddSat(a, b, _) | Operation::SubSat(a, b, _) => vec![a, b],
+            Operation::Not(a) | Operation::Abs(a) | Operation::Sqrt(a) | Operation::PipelineRegister(a) |
+            Operation::Slice(a, _, _) |
+            Operation::Store(_, a) | Operation::ArrayLoad(_, a) |
+            Operation::LoopCarry(_, a) | Operation::LoopNext(_, a) | Operation::RegisterStore(_, a) |
+            Operation::CallResult(a, _) => vec![a],
+            Operation::ArrayStore(_, index, value) => vec![index, value],
+            Operation::Mux(sel, a, b) | Operation::Fma(sel, a, b) => vec![sel, a, b],
+            Operation::ReduceAdd(values) | Operation::ReduceMax(values) | Operation::ReduceMin(values) |
+            Operation::Call(_, values) => values.iter_mut().collect(),
+            Operation::Load(_) | Operation::Const(_) | Operation::LoopIndex | Operation::RegisterLoad(_) |
+            Operation::PipelineBarrier | Operation::Nop => vec![],
+        }
+    }
+}
+
+/// A structural problem found by `Graph::validate`
+///
+/// `validate` returns these as the graph's validation errors. An unknown
+/// value is `UndefinedValue`, which also names the node using it; a stage
+/// entry without a node is `DanglingReference`, with its stage; a
+/// detected cycle is `CombinationalCycle`, with the nodes along it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// An operand refers to a value that no node produces
+    UndefinedValue { node: NodeId, value: ValueId },
+    /// `value_map` and the nodes' `output` fields disagree about a value
+    InconsistentValueMap { value: ValueId },
+    /// Nodes that depend on each other without a pipeline register in between
+    CombinationalCycle { nodes: Vec<NodeId> },
+    /// More than one `Store` writes the same output port
+    DuplicateOutput(String),
+    /// An array access names an array that was never declared
+    UndeclaredArray { node: NodeId, name: String },
+    /// A state register access names a register that was never declared
+    UndeclaredRegister { node: NodeId, name: String },
+    /// More than one `RegisterStore` updates the same state register
+    DuplicateRegisterStore(String),
+    /// A pipeline stage lists a node that is not in the graph
+    DanglingReference { stage: usize, node: NodeId },
+    /// A call names a callee that was never declared
+    UndeclaredCallee { node: NodeId, name: String },
+    /// A call passes a different number of inputs than its callee has
+    CallArity { node: NodeId, expected: usize, found: usize },
+    /// A call result reads a port the called graph does not store, or no call at all
+    UnknownCallResult { node: NodeId, port: String },
+    /// A functional unit lists a node that is not a scheduled multiply
+    InvalidBinding { unit: usize, node: NodeId },
+    /// Two operations of one functional unit start in the same slot of the initiation interval
+    SlotConflict { unit: usize, nodes: (NodeId, NodeId) },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::UndefinedValue { node, value } => {
+                write!(f, "node {} uses value {} which no node produces", node.0, value.0)
+            }
+            GraphError::InconsistentValueMap { value } => {
+                write!(f, "value {} is mapped to a node that does not produce it", value.0)
+            }
+            GraphError::CombinationalCycle { nodes } => {
+                let ids: Vec<String> = nodes.iter().map(|id| id.0.to_string()).collect();
+                write!(f, "combinational cycle through nodes {}", ids.join(" -> "))
+            }
+            GraphError::DuplicateOutput(name) => write!(f, "output '{}' is stored more than once", name),
+            GraphError::UndeclaredArray { node, name } => {
+                write!(f, "node {} accesses array '{}' which is not declared", node.0, name)
+            }
+            GraphError::UndeclaredRegister { node, name } => {
+                write!(f, "node {} accesses state register '{}' which is not declared", node.0, name)
+            }
+            GraphError::DuplicateRegisterStore(name) => write!(f, "state register '{}' is updated more than once", name),
+            GraphError::DanglingReference { stage, node } => {
+                write!(f, "pipeline stage {} schedules node {} which is not in the graph", stage, node.0)
+            }
+            GraphError::UndeclaredCallee { node, name } => {
+                write!(f, "node {} calls '{}' which is not declared", node.0, name)
+            }
+            GraphError::CallArity { node, expected, found } => {
+                write!(f, "node {} passes {} inputs to a callee with {}", node.0, found, expected)
+            }
+            GraphError::UnknownCallResult { node, port } => {
+                write!(f, "node {} reads result '{}' which its call does not produce", node.0, port)
+            }
+            GraphError::InvalidBinding { unit, node } => {
+                write!(f, "functional unit {} is bound to node {} which is not a scheduled multiply", unit, node.0)
+            }
+            GraphError::SlotConflict { unit, nodes: (first, second) } => {
+                write!(f, "functional unit {} runs nodes {} and {} in the same slot", unit, first.0, second.0)
+            }
+        }
+    }
+}
+
+/// Join validation errors into a single message
+pub fn describe_errors(errors: &[GraphError]) -> String {
+    let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    format!("invalid graph: {}", messages.join("; "))
+}
+
+/// Smallest and largest value of a `width`-bit result, the bounds `AddSat`/`SubSat` clamp to
+pub fn saturation_range(width: u32, signed: bool) -> (i128, i128) {
+    if signed {
+        (-(1i128 << (width - 1)), (1i128 << (width - 1)) - 1)
+    } else {
+        (0, (1i128 << width) - 1)
+    }
+}
+
+/// Levels of a balanced binary tree over `inputs` values, `ceil(log2(inputs))`
+pub fn reduction_levels(inputs: usize) -> usize {
+    (usize::BITS - inputs.saturating_sub(1).leading_zeros()) as usize
+}
+
+/// Which side of the module fills an array
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArrayKind {
+    /// Written by the host, read by the datapath
+    Input,
+    /// Written by the datapath, read back by the host
+    Output,
+    /// Held outside the kernel and reached through a read port
+    /// (`addr`/`en`/`dout`) and a write port (`waddr`/`we`/`din`)
+    Interface,
+}
+
+/// An array accessed with `ArrayLoad`/`ArrayStore`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArrayDecl {
+    pub name: String,
+    pub depth: usize,
+    pub width: Option<u32>, // Element width; `None` uses DATA_WIDTH
+    pub kind: ArrayKind,
+}
+
+/// A register that keeps its value from one transaction to the next
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateRegister {
+    pub name: String,
+    pub width: Option<u32>, // `None` uses DATA_WIDTH
+    /// Value after reset, and after a clear
+    pub init: i64,
+    /// The module gets a `<name>_clear` input that reloads `init`
+    pub clear: bool,
+}
+
+/// One physical multiplier and the multiplies taking turns on it, one per slot of the initiation interval
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionalUnit {
+    /// `Mul` and `SMul` nodes, in slot order
+    pub operations: Vec<NodeId>,
+}
+
+impl FunctionalUnit {
+    /// Whether more than one operation uses the unit, so it needs input muxes
+    pub fn is_shared(&self) -> bool {
+        self.operations.len() > 1
+    }
+}
+
+/// A graph instantiated by `Call` nodes, emitted as a module of its own
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Callee {
+    pub name: String,
+    pub graph: Graph,
+}
+
 /// An IR node in the graph
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
     pub id: NodeId,
     pub op: Operation,
     pub output: Option<ValueId>,
+    pub output_width: Option<u32>, // Bit width of `output`, if known
+    pub signed: bool,              // `output` is two's complement
+    #[serde(default)]
+    pub fixed_point: Option<FixedPointType>, // Binary point of `output`, for fixed-point values
+}
+
+/// Bit width and signedness of a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeAnnotation {
+    pub signed: bool,
+    pub width: u32,
+}
+
+impl Node {
+    /// Type of `output`, once its width is known
+    pub fn type_annotation(&self) -> Option<TypeAnnotation> {
+        self.output_width.map(|width| TypeAnnotation { signed: self.signed, width })
+    }
 }
 
 /// Main IR container
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Graph {
     pub nodes: Vec<Node>,
     pub next_value: usize,
@@ -83,6 +397,28 @@ pub struct Graph {
     pub value_map: HashMap<ValueId, NodeId>, // who produces what
     pub pipeline_config: PipelineConfig,     // Pipeline configuration
     pub pipeline_stages: Vec<PipelineStage>, // Scheduled pipeline stages
+    pub arrays: Vec<ArrayDecl>,              // Arrays mapped to block RAM
+    /// Iterations per `ap_start` when the whole graph is the body of a rolled loop
+    #[serde(default)]
+    pub loop_trip_count: Option<usize>,
+    /// Registers read with `RegisterLoad` and updated with `RegisterStore`
+    #[serde(default)]
+    pub state_registers: Vec<StateRegister>,
+    /// Ports the backends declare first, in this order; the rest follow alphabetically
+    #[serde(default)]
+    pub port_order: Vec<String>,
+    /// Graphs `Call` nodes instantiate, by module name
+    #[serde(default)]
+    pub callees: Vec<Callee>,
+    /// Physical units the scheduled multiplies are bound to, from `run_binding_pass`; cleared by rescheduling
+    #[serde(default)]
+    pub functional_units: Vec<FunctionalUnit>,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Graph {
@@ -94,9 +430,20 @@ impl Graph {
             value_map: HashMap::new(),
             pipeline_config: PipelineConfig::default(),
             pipeline_stages: Vec::new(),
+            arrays: Vec::new(),
+            loop_trip_count: None,
+            state_registers: Vec::new(),
+            port_order: Vec::new(),
+            callees: Vec::new(),
+            functional_units: Vec::new(),
         }
     }
 
+    /// Declare the ports named in `names` first, in that order, instead of alphabetically
+    pub fn set_port_order(&mut self, names: &[&str]) {
+        self.port_order = names.iter().map(|name| name.to_string()).collect();
+    }
+
     /// Create a new value ID
     pub fn new_value(&mut self) -> ValueId {
         let id = ValueId(self.next_value);
@@ -104,13 +451,28 @@ impl Graph {
         id
     }
 
-    /// Add a node with output value
+    /// Add a node with output value, inferring its width and signedness from the operands
     pub fn add_node_with_output(&mut self, op: Operation) -> ValueId {
+        let width = self.infer_width(&op);
+        let signed = self.infer_signed(&op);
+        self.add_node_with_output_type(op, width, signed)
+    }
+
+    /// Add an unsigned node with output value of an explicitly declared width
+    pub fn add_node_with_output_width(&mut self, op: Operation, width: u32) -> ValueId {
+        self.add_node_with_output_type(op, Some(width), false)
+    }
+
+    /// Add a node with output value of an explicitly declared width and signedness
+    pub fn add_node_with_output_type(&mut self, op: Operation, output_width: Option<u32>, signed: bool) -> ValueId {
         let output_value = self.new_value();
         let node = Node {
             id: NodeId(self.next_node),
             op,
             output: Some(output_value),
+            output_width,
+            signed,
+            fixed_point: None,
         };
         
         self.next_node += 1;
@@ -126,6 +488,9 @@ impl Graph {
             id: NodeId(self.next_node),
             op,
             output: None,
+            output_width: None,
+            signed: false,
+            fixed_point: None,
         };
         
         let node_id = node.id;
@@ -146,40 +511,1132 @@ impl Graph {
     }
 
     /// Insert a pipeline register for the given value
+    ///
+    /// The register goes right after the value's producer, so graph order
+    /// stays topological for consumers rewired to read it.
     pub fn insert_pipeline_register(&mut self, value: ValueId) -> ValueId {
         let reg_value = self.new_value();
         let reg_node = Node {
             id: NodeId(self.next_node),
             op: Operation::PipelineRegister(value),
             output: Some(reg_value),
+            output_width: self.value_width(value),
+            signed: self.value_signed(value),
+            fixed_point: self.value_fixed_point(value),
         };
         
         self.next_node += 1;
         self.value_map.insert(reg_value, reg_node.id);
-        self.nodes.push(reg_node);
+        let producer = self.value_map.get(&value).and_then(|node_id| self.nodes.iter().position(|node| node.id == *node_id));
+        match producer {
+            Some(index) => self.nodes.insert(index + 1, reg_node),
+            None => self.nodes.push(reg_node),
+        }
         
         reg_value
     }
 
+    /// Declare an array for `ArrayLoad`/`ArrayStore` nodes to access
+    pub fn declare_array(&mut self, name: &str, depth: usize, width: Option<u32>, kind: ArrayKind) {
+        self.arrays.retain(|array| array.name != name);
+        self.arrays.push(ArrayDecl { name: name.to_string(), depth, width, kind });
+    }
+
+    /// Look up a declared array by name
+    pub fn array(&self, name: &str) -> Option<&ArrayDecl> {
+        self.arrays.iter().find(|array| array.name == name)
+    }
+
+    /// Declare a state register for `RegisterLoad`/`RegisterStore` nodes to access
+    pub fn declare_state_register(&mut self, name: &str, width: Option<u32>, init: i64, clear: bool) {
+        self.state_registers.retain(|register| register.name != name);
+        self.state_registers.push(StateRegister { name: name.to_string(), width, init, clear });
+    }
+
+    /// Look up a declared state register by name
+    pub fn state_register(&self, name: &str) -> Option<&StateRegister> {
+        self.state_registers.iter().find(|register| register.name == name)
+    }
+
+    /// Declare `graph` as callee `name`, for `Call` nodes to instantiate
+    pub fn declare_callee(&mut self, name: &str, graph: Graph) {
+        self.callees.retain(|callee| callee.name != name);
+        self.callees.push(Callee { name: name.to_string(), graph });
+    }
+
+    /// Look up a declared callee by name
+    pub fn callee(&self, name: &str) -> Option<&Graph> {
+        self.callees.iter().find(|callee| callee.name == name).map(|callee| &callee.graph)
+    }
+
+    /// The callee the call with handle `call` instantiates
+    pub fn called(&self, call: ValueId) -> Option<&Graph> {
+        match &self.call_node(call)?.op {
+            Operation::Call(name, _) => self.callee(name),
+            _ => None,
+        }
+    }
+
+    /// The `Call` node behind handle `call`, looking through pipeline registers the scheduler put in between
+    pub(crate) fn call_node(&self, mut call: ValueId) -> Option<&Node> {
+        loop {
+            let producer = self.producer(call)?;
+            match producer.op {
+                Operation::PipelineRegister(value) => call = value,
+                Operation::Call(_, _) => return Some(producer),
+                _ => return None,
+            }
+        }
+    }
+
+    /// Cycles from the first to the last scheduled stage, 1 for an unscheduled graph
+    pub fn scheduled_latency(&self) -> usize {
+        self.pipeline_stages.iter().map(|stage| stage.cycle + 1).max().unwrap_or(0).max(1)
+    }
+
+    /// Expose a sticky output `port` that goes high once the saturating
+    /// operation producing `saturated` clamps, and stays high until reset
+    ///
+    /// The flag compares the clamped result with the exact one, and is held
+    /// in a 1-bit state register named `port`.
+    ///
+    /// # Panics
+    ///
+    /// If `saturated` is not produced by an `AddSat` or `SubSat`.
+    pub fn add_overflow_flag(&mut self, saturated: ValueId, port: &str) -> ValueId {
+        let (a, b, width, subtract) = match self.producer(saturated).map(|node| &node.op) {
+            Some(Operation::AddSat(a, b, width)) => (*a, *b, *width, false),
+            Some(Operation::SubSat(a, b, width)) => (*a, *b, *width, true),
+            _ => panic!("overflow flag {} needs a saturating add or subtract", port),
+        };
+        let signed = self.value_signed(saturated);
+        let exact_width = [self.value_width(a), self.value_width(b), Some(width)].iter()
+            .map(|width| width.unwrap_or(DEFAULT_WIDTH))
+            .max()
+            .unwrap_or(width) + 1;
+        let exact = match (subtract, signed) {
+            (false, false) => Operation::Add(a, b),
+            (true, false) => Operation::Sub(a, b),
+            (false, true) => Operation::SAdd(a, b),
+            (true, true) => Operation::SSub(a, b),
+        };
+        let exact = self.add_node_with_output_type(exact, Some(exact_width), signed);
+        let clamped = self.add_node_with_output(Operation::CmpNe(saturated, exact));
+
+        self.declare_state_register(port, Some(1), 0, false);
+        let seen = self.add_node_with_output_width(Operation::RegisterLoad(port.to_string()), 1);
+        let sticky = self.add_node_with_output(Operation::Or(seen, clamped));
+        self.add_node(Operation::RegisterStore(port.to_string(), sticky));
+        self.add_node(Operation::Store(port.to_string(), sticky));
+        sticky
+    }
+
+    /// Combine two graphs into one module, keeping `other`'s nodes after this graph's
+    ///
+    /// `other`'s node and value ids are shifted past this graph's, so values
+    /// from it must be looked up again in the result, e.g. with
+    /// `input_value`. Ports keep their names: an input both graphs read
+    /// becomes one port, and no output may be stored by both. `other`'s
+    /// ports follow this graph's in the port order. Arrays, state registers
+    /// and callees declared in both keep this graph's declaration.
+    /// Pipelining stays enabled if either graph asks for it, at the smaller
+    /// II and the larger depth; the schedule is dropped, as neither graph's
+    /// stages describe the result.
+    pub fn merge(mut self, other: Graph) -> Graph {
+        let (node_offset, value_offset) = (self.next_node, self.next_value);
+        for mut node in other.nodes {
+            node.id.0 += node_offset;
+            if let Some(output) = &mut node.output {
+                output.0 += value_offset;
+            }
+            for operand in node.op.operands_mut() {
+                operand.0 += value_offset;
+            }
+            self.nodes.push(node);
+        }
+        self.value_map.extend(other.value_map.into_iter()
+            .map(|(value, node_id)| (ValueId(value.0 + value_offset), NodeId(node_id.0 + node_offset))));
+        self.next_node += other.next_node;
+        self.next_value += other.next_value;
+
+        for array in other.arrays {
+            if self.array(&array.name).is_none() {
+                self.arrays.push(array);
+            }
+        }
+        for register in other.state_registers {
+            if self.state_register(&register.name).is_none() {
+                self.state_registers.push(register);
+            }
+        }
+        for callee in other.callees {
+            if self.callee(&callee.name).is_none() {
+                self.callees.push(callee);
+            }
+        }
+        for name in other.port_order {
+            if !self.port_order.contains(&name) {
+                self.port_order.push(name);
+            }
+        }
+        let config = match (self.pipeline_config.enable, other.pipeline_config.enable) {
+            (true, true) => PipelineConfig {
+                enable: true,
+                initiation_interval: self.pipeline_config.initiation_interval.min(other.pipeline_config.initiation_interval),
+                pipeline_depth: self.pipeline_config.pipeline_depth.max(other.pipeline_config.pipeline_depth),
+                unroll_factor: self.pipeline_config.unroll_factor.max(other.pipeline_config.unroll_factor),
+            },
+            (false, true) => other.pipeline_config,
+            _ => self.pipeline_config,
+        };
+        self.pipeline_config = config;
+        self.pipeline_stages.clear();
+        self.loop_trip_count = self.loop_trip_count.or(other.loop_trip_count);
+        self
+    }
+
+    /// Copy the nodes in `nodes` into a graph of their own, for `extract_module`
+    ///
+    /// Also returns the cut inputs, values produced outside `nodes` but read
+    /// inside, in the order they are first read, and the cut outputs, values
+    /// produced inside and read outside, in the order they are produced. Each
+    /// cut input becomes a `Load` and each cut output a `Store`, and the
+    /// ports are declared in that order. A cut input read from a port keeps
+    /// the port's name; the other ports are named `in_<value>` and
+    /// `out_<value>` after the value's id in this graph. The subgraph keeps
+    /// the pipeline configuration and the declarations its nodes use, but
+    /// not the schedule.
+    pub fn clone_subgraph(&self, nodes: &[NodeId]) -> (Graph, Vec<ValueId>, Vec<ValueId>) {
+        let selected: HashSet<NodeId> = nodes.iter().copied().collect();
+        let inside = |value: &ValueId| self.value_map.get(value).is_some_and(|producer| selected.contains(producer));
+        let (mut cut_inputs, mut cut_outputs) = (Vec::new(), Vec::new());
+        for node in &self.nodes {
+            let (cut, crosses) = if selected.contains(&node.id) { (&mut cut_inputs, false) } else { (&mut cut_outputs, true) };
+            for operand in node.op.operands() {
+                if inside(&operand) == crosses && !cut.contains(&operand) {
+                    cut.push(operand);
+                }
+            }
+        }
+        let position = |value: &ValueId| self.nodes.iter().position(|node| node.output == Some(*value));
+        cut_outputs.sort_by_key(position);
+
+        let mut sub = Graph::new();
+        sub.pipeline_config = self.pipeline_config.clone();
+        let mut renamed: HashMap<ValueId, ValueId> = HashMap::new();
+        let mut ports: Vec<String> = Vec::new();
+        for &value in &cut_inputs {
+            let name = match self.producer(value).map(|node| &node.op) {
+                Some(Operation::Load(port)) if !ports.contains(port) => port.clone(),
+                _ => format!("in_{}", value.0),
+            };
+            let load = sub.add_node_with_output_type(Operation::Load(name.clone()), self.value_width(value), self.value_signed(value));
+            if let Some(ty) = self.value_fixed_point(value) {
+                sub.set_fixed_point(load, ty);
+            }
+            renamed.insert(value, load);
+            ports.push(name);
+        }
+        for node in self.nodes.iter().filter(|node| selected.contains(&node.id)) {
+            let mut op = node.op.clone();
+            for operand in op.operands_mut() {
+                *operand = renamed[&*operand];
+            }
+            match &op {
+                Operation::ArrayLoad(name, _) | Operation::ArrayStore(name, _, _) => {
+                    if let Some(array) = self.array(name) {
+                        sub.declare_array(name, array.depth, array.width, array.kind);
+                    }
+                }
+                Operation::RegisterLoad(name) | Operation::RegisterStore(name, _) => {
+                    if let Some(register) = self.state_register(name) {
+                        sub.declare_state_register(name, register.width, register.init, register.clear);
+                    }
+                }
+                Operation::Call(name, _) => {
+                    if let Some(callee) = self.callee(name) {
+                        sub.declare_callee(name, callee.clone());
+                    }
+                }
+                _ => {}
+            }
+            match node.output {
+                Some(output) => {
+                    let value = sub.add_node_with_output_type(op, node.output_width, node.signed);
+                    if let Some(ty) = node.fixed_point {
+                        sub.set_fixed_point(value, ty);
+                    }
+                    renamed.insert(output, value);
+                }
+                None => {
+                    sub.add_node(op);
+                }
+            }
+        }
+        for &value in &cut_outputs {
+            let name = format!("out_{}", value.0);
+            sub.add_node(Operation::Store(name.clone(), renamed[&value]));
+            ports.push(name);
+        }
+        sub.port_order = ports;
+        (sub, cut_inputs, cut_outputs)
+    }
+
+    /// Move the nodes in `nodes` into callee `module_name` and instantiate it in their place
+    ///
+    /// The callee is the `clone_subgraph` of `nodes`. A `Call` feeds it the
+    /// cut inputs and a `CallResult` per cut output takes over that value's
+    /// readers; the results are returned in cut output order. The schedule is
+    /// dropped, and the callee is declared unscheduled, so a pipelined caller
+    /// schedules it before generating Verilog, as `HLSFunction::call` does.
+    ///
+    /// # Errors
+    ///
+    /// `TransformError`, leaving the graph unchanged, if `nodes` includes a
+    /// `Load` or `Store`, since the ports stay with this graph, or if a node
+    /// outside `nodes` reads one of them and feeds another, since the
+    /// instance would then depend on its own results.
+    pub fn extract_module(&mut self, nodes: &[NodeId], module_name: &str) -> Result<Vec<ValueId>, HlsError> {
+        let selected: HashSet<NodeId> = nodes.iter().copied().collect();
+        // Values computed from the selection, in or outside it
+        let mut downstream: HashSet<ValueId> = HashSet::new();
+        for node in &self.nodes {
+            let operands = node.op.operands();
+            if selected.contains(&node.id) {
+                if matches!(node.op, Operation::Load(_) | Operation::Store(_, _)) {
+                    return Err(HlsError::TransformError(format!("node {} is a port, which cannot be extracted", node.id.0)));
+                }
+                let reenters = operands.iter()
+                    .any(|operand| downstream.contains(operand) && !self.value_map.get(operand).is_some_and(|producer| selected.contains(producer)));
+                if reenters {
+                    return Err(HlsError::TransformError(format!(
+                        "node {} reads a value computed outside the extracted nodes from their results", node.id.0
+                    )));
+                }
+            } else if !operands.iter().any(|operand| downstream.contains(operand)) {
+                continue;
+            }
+            downstream.extend(node.output);
+        }
+
+        let (callee, inputs, outputs) = self.clone_subgraph(nodes);
+        let fixed_points: Vec<Option<FixedPointType>> = outputs.iter().map(|&value| self.value_fixed_point(value)).collect();
+        let ports = callee.output_names();
+        let position = self.nodes.iter().position(|node| selected.contains(&node.id)).unwrap_or(self.nodes.len());
+        self.nodes.retain(|node| !selected.contains(&node.id));
+        self.value_map.retain(|_, producer| !selected.contains(producer));
+        self.declare_callee(module_name, callee);
+
+        let first = self.nodes.len();
+        let handle = self.add_node_with_output(Operation::Call(module_name.to_string(), inputs));
+        let results: Vec<ValueId> = ports.into_iter().zip(fixed_points)
+            .map(|(port, fixed_point)| {
+                let result = self.add_node_with_output(Operation::CallResult(handle, port));
+                if let Some(ty) = fixed_point {
+                    self.set_fixed_point(result, ty);
+                }
+                result
+            })
+            .collect();
+        let instance: Vec<Node> = self.nodes.drain(first..).collect();
+        self.nodes.splice(position..position, instance);
+
+        let rewired: HashMap<ValueId, ValueId> = outputs.into_iter().zip(results.iter().copied()).collect();
+        for node in &mut self.nodes {
+            for operand in node.op.operands_mut() {
+                if let Some(&result) = rewired.get(operand) {
+                    *operand = result;
+                }
+            }
+        }
+        // A cut input produced after the first extracted node moves the instance down
+        self.sort_topologically();
+        self.pipeline_stages.clear();
+        self.functional_units.clear();
+        Ok(results)
+    }
+
+    /// Value read from input port `name`, the first one if it is loaded more than once
+    pub fn input_value(&self, name: &str) -> Option<ValueId> {
+        self.nodes.iter().find_map(|node| match &node.op {
+            Operation::Load(port) if port == name => node.output,
+            _ => None,
+        })
+    }
+
+    /// Value stored to output port `name`
+    pub fn output_value(&self, name: &str) -> Option<ValueId> {
+        self.nodes.iter().find_map(|node| match &node.op {
+            Operation::Store(port, value) if port == name => Some(*value),
+            _ => None,
+        })
+    }
+
+    /// Input port names in the order the backends declare them and `Call` passes inputs
+    pub fn input_names(&self) -> Vec<String> {
+        self.ordered_ports(|op| match op {
+            Operation::Load(name) => Some(name),
+            _ => None,
+        })
+    }
+
+    /// Output port names in the order the backends declare them
+    pub fn output_names(&self) -> Vec<String> {
+        self.ordered_ports(|op| match op {
+            Operation::Store(name, _) => Some(name),
+            _ => None,
+        })
+    }
+
+    fn ordered_ports(&self, port: impl Fn(&Operation) -> Option<&String>) -> Vec<String> {
+        let mut names: Vec<String> = self.nodes.iter().filter_map(|node| port(&node.op)).cloned().collect();
+        names.sort_by_cached_key(|name| self.port_key(name));
+        names.dedup();
+        names
+    }
+
+    /// Sort key of a port: `port_order` first, then the remaining ports by name
+    pub(crate) fn port_key(&self, name: &str) -> (usize, String) {
+        let position = self.port_order.iter().position(|port| port == name).unwrap_or(usize::MAX);
+        (position, name.to_string())
+    }
+
+    /// Reorder nodes so every value is produced before it is read, keeping
+    /// graph order where it already holds
+    ///
+    /// Nodes caught in a cycle are left at the end, for `validate` to report.
+    fn sort_topologically(&mut self) {
+        let position: HashMap<NodeId, usize> = self.nodes.iter().enumerate().map(|(index, node)| (node.id, index)).collect();
+        let out_of_order = self.nodes.iter().enumerate().any(|(index, node)| {
+            node.op.operands().iter().any(|operand| self.value_map.get(operand).is_some_and(|producer| position[producer] > index))
+        });
+        if !out_of_order {
+            return;
+        }
+
+        let mut pending = std::mem::take(&mut self.nodes);
+        let mut placed: HashSet<NodeId> = HashSet::new();
+        while !pending.is_empty() {
+            let ready = pending.iter().position(|node| {
+                node.op.operands().iter().all(|operand| self.value_map.get(operand).is_none_or(|producer| placed.contains(producer)))
+            });
+            let Some(index) = ready else { break };
+            let node = pending.remove(index);
+            placed.insert(node.id);
+            self.nodes.push(node);
+        }
+        self.nodes.extend(pending);
+    }
+
+    /// Look up the node that produces a value
+    pub fn producer(&self, value: ValueId) -> Option<&Node> {
+        let node_id = self.value_map.get(&value)?;
+        self.nodes.iter().find(|node| node.id == *node_id)
+    }
+
+    /// Bit width of a value, if its producer declared or inferred one
+    pub fn value_width(&self, value: ValueId) -> Option<u32> {
+        self.producer(value).and_then(|node| node.output_width)
+    }
+
+    /// Whether a value is two's complement
+    pub fn value_signed(&self, value: ValueId) -> bool {
+        self.producer(value).is_some_and(|node| node.signed)
+    }
+
+    /// Fixed-point format of a value; `None` for plain integers
+    pub fn value_fixed_point(&self, value: ValueId) -> Option<FixedPointType> {
+        self.producer(value).and_then(|node| node.fixed_point)
+    }
+
+    /// Record that `value` holds fixed-point numbers of format `ty`
+    pub fn set_fixed_point(&mut self, value: ValueId, ty: FixedPointType) {
+        if let Some(&node_id) = self.value_map.get(&value) {
+            if let Some(node) = self.nodes.iter_mut().find(|node| node.id == node_id) {
+                node.fixed_point = Some(ty);
+            }
+        }
+    }
+
+    /// Infer signedness Verilog-style: a result is signed only if every data
+    /// operand is signed. Comparisons always produce an unsigned flag, and the
+    /// explicitly signed SAdd/SSub/SMul always a signed result.
+    pub fn infer_signed(&self, op: &Operation) -> bool {
+        let s = |v: &ValueId| self.value_signed(*v);
+        match op {
+            Operation::CmpLt(_, _) | Operation::CmpEq(_, _) | Operation::CmpGt(_, _) |
+            Operation::CmpGe(_, _) | Operation::CmpLe(_, _) | Operation::CmpNe(_, _) => false,
+            Operation::Shl(a, _) | Operation::Shr(a, _) => s(a),
+            Operation::Sqrt(_) => false,
+            Operation::Mux(_, a, b) => s(a) && s(b),
+            Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) => false,
+            Operation::SAdd(_, _) | Operation::SSub(_, _) | Operation::SMul(_, _) => true,
+            // Bit fields are plain vectors, as with Verilog concatenations and part-selects
+            Operation::Concat(_, _) | Operation::Slice(_, _, _) => false,
+            Operation::Call(_, _) => false,
+            Operation::CallResult(call, port) => {
+                self.called(*call).is_some_and(|callee| callee.output_value(port).is_some_and(|value| callee.value_signed(value)))
+            }
+            _ => {
+                let operands = op.operands();
+                !operands.is_empty() && operands.iter().all(s)
+            }
+        }
+    }
+
+    /// Infer the output width of an operation from its operand widths
+    ///
+    /// Follows Verilog-style widening: Add takes the widest operand plus a carry
+    /// bit, Sub/logic ops the widest operand, Mul the sum of both, Fma the wider
+    /// of product and addend plus a carry bit, Concat the sum of its parts, Slice
+    /// `high - low + 1`, saturating operations their declared width, Sqrt half
+    /// its operand rounded up, and comparisons are a single bit. ReduceAdd takes the
+    /// widest input plus a carry bit per tree level, ReduceMax/ReduceMin the
+    /// widest input. Returns `None` if an operand width is unknown.
+    pub fn infer_width(&self, op: &Operation) -> Option<u32> {
+        let w = |v: &ValueId| self.value_width(*v);
+        match op {
+            Operation::Add(a, b) | Operation::SAdd(a, b) => Some(w(a)?.max(w(b)?) + 1),
+            Operation::Sub(a, b) | Operation::SSub(a, b) | Operation::And(a, b) |
+            Operation::Or(a, b) | Operation::Xor(a, b) | Operation::Nand(a, b) |
+            Operation::Nor(a, b) | Operation::Xnor(a, b) | Operation::Min(a, b) |
+            Operation::Max(a, b) => Some(w(a)?.max(w(b)?)),
+            Operation::Mul(a, b) | Operation::SMul(a, b) | Operation::Concat(a, b) => Some(w(a)? + w(b)?),
+            Operation::Slice(_, high, low) => Some(high.checked_sub(*low)? + 1),
+            Operation::AddSat(_, _, width) | Operation::SubSat(_, _, width) => Some(*width),
+            Operation::Div(a, _) | Operation::Shl(a, _) | Operation::Shr(a, _) => w(a),
+            Operation::Sqrt(a) => Some(w(a)?.div_ceil(2)),
+            Operation::CmpLt(_, _) | Operation::CmpEq(_, _) | Operation::CmpGt(_, _) |
+            Operation::CmpGe(_, _) | Operation::CmpLe(_, _) | Operation::CmpNe(_, _) => Some(1),
+            Operation::Not(a) | Operation::Abs(a) | Operation::PipelineRegister(a) |
+            Operation::LoopCarry(_, a) => w(a),
+            Operation::Mux(_, a, b) => Some(w(a)?.max(w(b)?)),
+            Operation::Fma(a, b, c) => Some((w(a)? + w(b)?).max(w(c)?) + 1),
+            Operation::ReduceAdd(values) => {
+                let widest = values.iter().map(w).collect::<Option<Vec<_>>>()?.into_iter().max()?;
+                Some(widest + reduction_levels(values.len()) as u32)
+            }
+            Operation::ReduceMax(values) | Operation::ReduceMin(values) => {
+                values.iter().map(w).collect::<Option<Vec<_>>>()?.into_iter().max()
+            }
+            Operation::ArrayLoad(name, _) => self.array(name)?.width,
+            Operation::RegisterLoad(name) => self.state_register(name)?.width,
+            Operation::Call(_, _) => Some(1),
+            Operation::CallResult(call, port) => {
+                let callee = self.called(*call)?;
+                callee.value_width(callee.output_value(port)?)
+            }
+            Operation::Load(_) | Operation::Store(_, _) | Operation::Const(_) | Operation::ArrayStore(_, _, _) |
+            Operation::LoopIndex | Operation::LoopNext(_, _) | Operation::RegisterStore(_, _) |
+            Operation::PipelineBarrier | Operation::Nop => None,
+        }
+    }
+
+    /// Check that every value consumed by an operation has a known width
+    pub fn type_check(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        for node in &self.nodes {
+            for operand in node.op.operands() {
+                if self.value_width(operand).is_none() {
+                    errors.push(format!(
+                        "node {} ({:?}) uses value {} of unknown width",
+                        node.id.0, node.op, operand.0
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Check the graph's structure before scheduling or code generation
+    ///
+    /// Reports every undefined operand, `value_map` entry that disagrees with
+    /// the nodes, combinational cycle, duplicated output name and pipeline
+    /// stage entry for a node that no longer exists.
+    pub fn validate(&self) -> Result<(), Vec<GraphError>> {
+        let mut errors = Vec::new();
+
+        for node in &self.nodes {
+            for value in node.op.operands() {
+                if !self.value_map.contains_key(&value) {
+                    errors.push(GraphError::UndefinedValue { node: node.id, value });
+                }
+            }
+        }
+
+        let mut inconsistent: Vec<ValueId> = self.value_map.iter()
+            .filter(|(value, node_id)| {
+                !self.nodes.iter().any(|node| node.id == **node_id && node.output == Some(**value))
+            })
+            .map(|(value, _)| *value)
+            .collect();
+        for node in &self.nodes {
+            if let Some(output) = node.output {
+                if self.value_map.get(&output) != Some(&node.id) && !inconsistent.contains(&output) {
+                    inconsistent.push(output);
+                }
+            }
+        }
+        inconsistent.sort_by_key(|value| value.0);
+        errors.extend(inconsistent.into_iter().map(|value| GraphError::InconsistentValueMap { value }));
+
+        errors.extend(self.find_combinational_cycles());
+
+        for node in &self.nodes {
+            if let Operation::ArrayLoad(name, _) | Operation::ArrayStore(name, _, _) = &node.op {
+                if self.array(name).is_none() {
+                    errors.push(GraphError::UndeclaredArray { node: node.id, name: name.clone() });
+                }
+            }
+        }
+
+        for node in &self.nodes {
+            match &node.op {
+                Operation::Call(name, inputs) => match self.callee(name) {
+                    None => errors.push(GraphError::UndeclaredCallee { node: node.id, name: name.clone() }),
+                    Some(callee) => {
+                        let expected = callee.input_names().len();
+                        if inputs.len() != expected {
+                            errors.push(GraphError::CallArity { node: node.id, expected, found: inputs.len() });
+                        }
+                    }
+                },
+                Operation::CallResult(call, port) if self.called(*call).is_none_or(|callee| callee.output_value(port).is_none()) => {
+                    errors.push(GraphError::UnknownCallResult { node: node.id, port: port.clone() });
+                }
+                _ => {}
+            }
+        }
+
+        let mut outputs = HashSet::new();
+        let mut updated = HashSet::new();
+        for node in &self.nodes {
+            match &node.op {
+                Operation::Store(name, _) if !outputs.insert(name) => errors.push(GraphError::DuplicateOutput(name.clone())),
+                Operation::RegisterLoad(name) | Operation::RegisterStore(name, _) if self.state_register(name).is_none() => {
+                    errors.push(GraphError::UndeclaredRegister { node: node.id, name: name.clone() });
+                }
+                Operation::RegisterStore(name, _) if !updated.insert(name) => {
+                    errors.push(GraphError::DuplicateRegisterStore(name.clone()));
+                }
+                _ => {}
+            }
+        }
+
+        for stage in &self.pipeline_stages {
+            for node_id in &stage.operations {
+                if !self.nodes.iter().any(|node| node.id == *node_id) {
+                    errors.push(GraphError::DanglingReference { stage: stage.stage, node: *node_id });
+                }
+            }
+        }
+
+        let ii = self.pipeline_config.initiation_interval.max(1);
+        let cycle = |node_id: NodeId| self.pipeline_stages.iter().find(|stage| stage.operations.contains(&node_id)).map(|stage| stage.cycle);
+        for (unit, functional_unit) in self.functional_units.iter().enumerate() {
+            let mut slots: HashMap<usize, NodeId> = HashMap::new();
+            for &node_id in &functional_unit.operations {
+                let multiply = self.nodes.iter().any(|node| node.id == node_id && matches!(node.op, Operation::Mul(_, _) | Operation::SMul(_, _)));
+                match cycle(node_id).filter(|_| multiply) {
+                    None => errors.push(GraphError::InvalidBinding { unit, node: node_id }),
+                    Some(cycle) => {
+                        if let Some(&first) = slots.get(&(cycle % ii)) {
+                            errors.push(GraphError::SlotConflict { unit, nodes: (first, node_id) });
+                        }
+                        slots.insert(cycle % ii, node_id);
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Depth-first search for dependency cycles; pipeline registers break combinational paths
+    fn find_combinational_cycles(&self) -> Vec<GraphError> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark { Visiting, Done }
+
+        fn visit(graph: &Graph, node: &Node, marks: &mut HashMap<NodeId, Mark>, path: &mut Vec<NodeId>, errors: &mut Vec<GraphError>) {
+            match marks.get(&node.id) {
+                Some(Mark::Done) => return,
+                Some(Mark::Visiting) => {
+                    let start = path.iter().position(|id| *id == node.id).unwrap_or(0);
+                    let mut nodes = path[start..].to_vec();
+                    nodes.push(node.id);
+                    errors.push(GraphError::CombinationalCycle { nodes });
+                    return;
+                }
+                None => {}
+            }
+
+            marks.insert(node.id, Mark::Visiting);
+            path.push(node.id);
+            if !matches!(node.op, Operation::PipelineRegister(_)) {
+                for value in node.op.operands() {
+                    if let Some(producer) = graph.producer(value) {
+                        visit(graph, producer, marks, path, errors);
+                    }
+                }
+            }
+            path.pop();
+            marks.insert(node.id, Mark::Done);
+        }
+
+        let mut marks = HashMap::new();
+        let mut errors = Vec::new();
+        for node in &self.nodes {
+            visit(self, node, &mut marks, &mut Vec::new(), &mut errors);
+        }
+        errors
+    }
+
     /// Get operation latency for scheduling
     pub fn get_operation_latency(&self, op: &Operation) -> usize {
         match op {
-            Operation::Add(_, _) | Operation::Sub(_, _) => 1,
-            Operation::Mul(_, _) => 3, // DSP48 multiplier latency
+            Operation::Add(_, _) | Operation::Sub(_, _) | Operation::SAdd(_, _) | Operation::SSub(_, _) => 1,
+            Operation::AddSat(_, _, _) | Operation::SubSat(_, _, _) => 1, // Add, compare and clamp
+            Operation::Mul(_, _) | Operation::SMul(_, _) => 3, // DSP48 multiplier latency
+            Operation::Fma(_, _, _) => 3, // Post-adder is inside the DSP48
             Operation::Div(_, _) => 18, // Division latency
-            Operation::And(_, _) | Operation::Or(_, _) | Operation::Not(_) | Operation::Xor(_, _) => 1,
+            Operation::Sqrt(_) => SQRT_LATENCY,
+            Operation::And(_, _) | Operation::Or(_, _) | Operation::Not(_) | Operation::Xor(_, _) |
+            Operation::Nand(_, _) | Operation::Nor(_, _) | Operation::Xnor(_, _) => 1,
+            Operation::Concat(_, _) | Operation::Slice(_, _, _) => 1, // Wiring, registered like other logic
             Operation::CmpLt(_, _) | Operation::CmpEq(_, _) | Operation::CmpGt(_, _) | 
             Operation::CmpGe(_, _) | Operation::CmpLe(_, _) | Operation::CmpNe(_, _) => 1,
             Operation::Load(_) => 2, // Memory access latency
             Operation::Store(_, _) => 1,
+            Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) => 1, // Synchronous BRAM port
             Operation::Const(_) => 0,
             Operation::Mux(_, _, _) => 1,
             Operation::Abs(_) => 1,  // Conditional negate + add
             Operation::Min(_, _) | Operation::Max(_, _) => 1, // Compare + mux
+            // One cycle per level of the balanced tree
+            Operation::ReduceAdd(values) | Operation::ReduceMax(values) | Operation::ReduceMin(values) => {
+                reduction_levels(values.len()).max(1)
+            }
             Operation::Shl(_, _) | Operation::Shr(_, _) => 1, // Shift operations
             Operation::PipelineRegister(_) => 1,
+            // The loop counter and carried registers are read directly; the update is registered
+            Operation::LoopIndex | Operation::LoopCarry(_, _) => 0,
+            Operation::LoopNext(_, _) => 1,
+            // Read directly at the start of the cycle; the update is registered
+            Operation::RegisterLoad(_) => 0,
+            Operation::RegisterStore(_, _) => 1,
+            // A black box taking the callee's pipeline depth; its results are wires out of the instance
+            Operation::Call(name, _) => self.callee(name).map_or(1, Graph::scheduled_latency),
+            Operation::CallResult(_, _) => 0,
             Operation::PipelineBarrier => 0,
             Operation::Nop => 0,
         }
     }
+
+    /// Rough combinational delay on UltraScale+ at `width` bits, excluding routing
+    ///
+    /// Used for critical path analysis and for chaining operations into one
+    /// cycle. These are the `DelayTable` defaults.
+    pub fn get_operation_delay_ns(&self, op: &Operation, width: u32) -> f64 {
+        DelayTable::default().delay_ns(op, width)
+    }
+}
+
+/// Per-operation combinational delay estimates, excluding routing
+///
+/// The defaults are rough UltraScale+ figures. Calibrate them against a
+/// device by changing the per-class fields, or pin single operations
+/// through `overrides`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DelayTable {
+    /// Fully pipelined DSP48E2 multiply, about 1 ns per register stage
+    pub multiply_ns: f64,
+    pub multiply_add_ns: f64,
+    /// Divide, per result bit
+    pub divide_ns_per_bit: f64,
+    /// Adder and comparator carry chains take `carry_base_ns + carry_ns_per_bit * width`
+    pub carry_base_ns: f64,
+    pub carry_ns_per_bit: f64,
+    /// A mux, or the select after a compare in `Min`, `Max` and the saturating ops
+    pub select_ns: f64,
+    /// One LUT level of bitwise logic
+    pub logic_ns: f64,
+    /// Each level of a barrel shifter, one per bit of the width
+    pub shift_level_ns: f64,
+    /// BRAM clock-to-output
+    pub memory_ns: f64,
+    /// Fixed delays by operation name, e.g. `"Add"` or `"CmpLt"`, in place of the estimates above
+    #[serde(default)]
+    pub overrides: BTreeMap<String, f64>,
+}
+
+impl Default for DelayTable {
+    fn default() -> Self {
+        Self {
+            multiply_ns: 3.0,
+            multiply_add_ns: 3.3,
+            divide_ns_per_bit: 1.2,
+            carry_base_ns: 0.3,
+            carry_ns_per_bit: 0.02,
+            select_ns: 0.35,
+            logic_ns: 0.3,
+            shift_level_ns: 0.3,
+            memory_ns: 1.2,
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl DelayTable {
+    /// Delay of `op` at `width` bits
+    pub fn delay_ns(&self, op: &Operation, width: u32) -> f64 {
+        if !self.overrides.is_empty() {
+            if let Some(&delay) = self.overrides.get(op.name()) {
+                return delay;
+            }
+        }
+        let carry_chain = self.carry_base_ns + self.carry_ns_per_bit * width as f64;
+        let log2 = u32::BITS.saturating_sub(width.leading_zeros()) as f64;
+        match op {
+            Operation::Mul(_, _) | Operation::SMul(_, _) => self.multiply_ns,
+            Operation::Fma(_, _, _) => self.multiply_add_ns,
+            Operation::Div(_, _) => self.divide_ns_per_bit * width as f64,
+            // One add or subtract per result bit
+            Operation::Sqrt(_) => carry_chain * width as f64,
+            Operation::Add(_, _) | Operation::Sub(_, _) | Operation::SAdd(_, _) | Operation::SSub(_, _) |
+            Operation::CmpLt(_, _) | Operation::CmpGt(_, _) | Operation::CmpEq(_, _) |
+            Operation::CmpGe(_, _) | Operation::CmpLe(_, _) | Operation::CmpNe(_, _) => carry_chain,
+            // Compare, then select or negate
+            Operation::Min(_, _) | Operation::Max(_, _) | Operation::Abs(_) |
+            Operation::AddSat(_, _, _) | Operation::SubSat(_, _, _) => carry_chain + self.select_ns,
+            Operation::Mux(_, _, _) | Operation::LoopCarry(_, _) => self.select_ns,
+            Operation::ReduceAdd(values) => carry_chain * reduction_levels(values.len()) as f64,
+            Operation::ReduceMax(values) | Operation::ReduceMin(values) => {
+                (carry_chain + self.select_ns) * reduction_levels(values.len()) as f64
+            }
+            Operation::And(_, _) | Operation::Or(_, _) | Operation::Xor(_, _) | Operation::Not(_) |
+            Operation::Nand(_, _) | Operation::Nor(_, _) | Operation::Xnor(_, _) => self.logic_ns,
+            Operation::Shl(_, _) | Operation::Shr(_, _) => self.shift_level_ns * log2,
+            Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) => self.memory_ns,
+            Operation::Concat(_, _) | Operation::Slice(_, _, _) | Operation::Load(_) | Operation::Store(_, _) |
+            Operation::Const(_) | Operation::PipelineRegister(_) | Operation::LoopIndex | Operation::LoopNext(_, _) |
+            Operation::RegisterLoad(_) | Operation::RegisterStore(_, _) | Operation::PipelineBarrier | Operation::Nop => 0.0,
+            // The callee registers its own work, and a result is a wire from its port
+            Operation::Call(_, _) | Operation::CallResult(_, _) => 0.0,
+        }
+    }
+}
+
+/// Feed `producer_graph_output` into every reader of `consumer_graph_input` in a merged graph
+///
+/// The `Load` producing `consumer_graph_input` is removed, so its port
+/// disappears from the module, and its readers see the producer's value at
+/// the producer's width. The producer's `Store` stays, keeping the
+/// intermediate result observable. Nodes are reordered if the producer
+/// came after its new readers.
+///
+/// # Errors
+///
+/// `TransformError`, leaving the graph unchanged, if `consumer_graph_input`
+/// is not produced by a `Load`.
+pub fn connect(producer_graph_output: ValueId, consumer_graph_input: ValueId, merged: &mut Graph) -> Result<(), HlsError> {
+    let index = merged.nodes.iter()
+        .position(|node| node.output == Some(consumer_graph_input) && matches!(node.op, Operation::Load(_)))
+        .ok_or_else(|| HlsError::TransformError(format!("value {} is not read from an input port", consumer_graph_input.0)))?;
+    merged.nodes.remove(index);
+    merged.value_map.remove(&consumer_graph_input);
+    for node in &mut merged.nodes {
+        for operand in node.op.operands_mut() {
+            if *operand == consumer_graph_input {
+                *operand = producer_graph_output;
+            }
+        }
+    }
+    merged.sort_topologically();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_width_inference() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output_width(Operation::Load("a".to_string()), 16);
+        let b = graph.add_node_with_output_width(Operation::Load("b".to_string()), 8);
+        let sum = graph.add_node_with_output(Operation::Add(a, b));
+        let product = graph.add_node_with_output(Operation::Mul(a, b));
+        let lt = graph.add_node_with_output(Operation::CmpLt(a, b));
+
+        assert_eq!(graph.value_width(sum), Some(17)); // carry bit
+        assert_eq!(graph.value_width(product), Some(24));
+        assert_eq!(graph.value_width(lt), Some(1));
+        assert!(graph.type_check().is_ok());
+    }
+
+    #[test]
+    fn test_type_check_reports_unknown_width() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let b = graph.add_node_with_output_width(Operation::Load("b".to_string()), 32);
+        let sum = graph.add_node_with_output(Operation::Add(a, b));
+        graph.add_node(Operation::Store("result".to_string(), sum));
+
+        assert_eq!(graph.value_width(sum), None);
+        let errors = graph.type_check().unwrap_err();
+        // `a` feeds the Add and `sum` feeds the Store
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_validate_accepts_well_formed_graph() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let reg = graph.insert_pipeline_register(a);
+        graph.add_node(Operation::Store("result".to_string(), reg));
+
+        assert_eq!(graph.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_reports_undefined_operand() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let store = graph.add_node(Operation::Store("result".to_string(), ValueId(42)));
+        graph.add_node(Operation::Store("other".to_string(), a));
+
+        assert_eq!(graph.validate(), Err(vec![GraphError::UndefinedValue { node: store, value: ValueId(42) }]));
+    }
+
+    #[test]
+    fn test_validate_reports_inconsistent_value_map() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let b = graph.add_node_with_output(Operation::Load("b".to_string()));
+        graph.value_map.insert(b, NodeId(0)); // node 0 produces `a`, not `b`
+        graph.add_node(Operation::Store("result".to_string(), a));
+
+        assert_eq!(graph.validate(), Err(vec![GraphError::InconsistentValueMap { value: b }]));
+    }
+
+    #[test]
+    fn test_validate_reports_combinational_cycle() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let sum = graph.add_node_with_output(Operation::Add(a, a));
+        // Feed the sum back into itself
+        graph.nodes[1].op = Operation::Add(a, sum);
+        graph.add_node(Operation::Store("result".to_string(), sum));
+
+        let errors = graph.validate().unwrap_err();
+        assert_eq!(errors, vec![GraphError::CombinationalCycle { nodes: vec![NodeId(1), NodeId(1)] }]);
+
+        // The same loop through a pipeline register is sequential, not combinational
+        let reg = graph.insert_pipeline_register(sum);
+        graph.nodes[1].op = Operation::Add(a, reg);
+        assert_eq!(graph.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_reports_duplicate_output() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        graph.add_node(Operation::Store("result".to_string(), a));
+        graph.add_node(Operation::Store("result".to_string(), a));
+
+        let errors = graph.validate().unwrap_err();
+        assert_eq!(errors, vec![GraphError::DuplicateOutput("result".to_string())]);
+        assert!(describe_errors(&errors).contains("output 'result' is stored more than once"));
+    }
+
+    #[test]
+    fn test_validate_reports_undeclared_array() {
+        let mut graph = Graph::new();
+        let index = graph.add_node_with_output(Operation::Load("index".to_string()));
+        let entry = graph.add_node_with_output(Operation::ArrayLoad("lut".to_string(), index));
+        graph.add_node(Operation::Store("result".to_string(), entry));
+
+        assert_eq!(graph.validate(), Err(vec![GraphError::UndeclaredArray { node: NodeId(1), name: "lut".to_string() }]));
+
+        graph.declare_array("lut", 16, None, ArrayKind::Input);
+        assert_eq!(graph.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_reports_malformed_calls() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let call = graph.add_node_with_output(Operation::Call("double".to_string(), vec![a, a]));
+        let result = graph.add_node_with_output(Operation::CallResult(call, "y".to_string()));
+        graph.add_node(Operation::Store("result".to_string(), result));
+        assert_eq!(graph.validate(), Err(vec![
+            GraphError::UndeclaredCallee { node: NodeId(1), name: "double".to_string() },
+            GraphError::UnknownCallResult { node: NodeId(2), port: "y".to_string() },
+        ]));
+
+        let mut double = Graph::new();
+        let x = double.add_node_with_output(Operation::Load("x".to_string()));
+        let doubled = double.add_node_with_output(Operation::Add(x, x));
+        double.add_node(Operation::Store("y".to_string(), doubled));
+        graph.declare_callee("double", double);
+        assert_eq!(graph.validate(), Err(vec![GraphError::CallArity { node: NodeId(1), expected: 1, found: 2 }]));
+        assert_eq!(graph.value_width(result), None);
+    }
+
+    #[test]
+    fn test_validate_reports_dangling_stage_entry() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        graph.add_node(Operation::Store("result".to_string(), a));
+        graph.pipeline_stages.push(PipelineStage { stage: 0, cycle: 0, operations: vec![NodeId(0), NodeId(7)] });
+
+        let errors = graph.validate().unwrap_err();
+        assert_eq!(errors, vec![GraphError::DanglingReference { stage: 0, node: NodeId(7) }]);
+        assert!(describe_errors(&errors).contains("pipeline stage 0 schedules node 7"));
+    }
+
+    /// `normalized = x - 128`
+    fn normalization_graph() -> Graph {
+        let mut graph = Graph::new();
+        let x = graph.add_node_with_output_width(Operation::Load("x".to_string()), 16);
+        let offset = graph.add_node_with_output_width(Operation::Const(128), 16);
+        let centered = graph.add_node_with_output(Operation::Sub(x, offset));
+        graph.add_node(Operation::Store("normalized".to_string(), centered));
+        graph
+    }
+
+    /// `result = n * w + bias`
+    fn mac_graph() -> Graph {
+        let mut graph = Graph::new();
+        let n = graph.add_node_with_output_width(Operation::Load("n".to_string()), 16);
+        let w = graph.add_node_with_output_width(Operation::Load("w".to_string()), 16);
+        let bias = graph.add_node_with_output_width(Operation::Load("bias".to_string()), 32);
+        let product = graph.add_node_with_output(Operation::Mul(n, w));
+        let sum = graph.add_node_with_output(Operation::Add(product, bias));
+        graph.add_node(Operation::Store("result".to_string(), sum));
+        graph
+    }
+
+    fn simulate(graph: &Graph, inputs: &[(&str, i64)]) -> HashMap<String, i64> {
+        let mut sim = crate::backend::sim::Simulator::new();
+        for (name, value) in inputs {
+            sim.set_input(name, *value, graph);
+        }
+        sim.simulate(graph).unwrap()
+    }
+
+    #[test]
+    fn test_merged_graphs_compose_through_connect() {
+        let (norm, mut mac) = (normalization_graph(), mac_graph());
+        mac.enable_pipeline(2, 4, 1);
+        let (norm_nodes, mac_nodes) = (norm.nodes.len(), mac.nodes.len());
+
+        let mut merged = norm.merge(mac);
+        assert_eq!(merged.nodes.len(), norm_nodes + mac_nodes);
+        assert_eq!(merged.validate(), Ok(()));
+        assert_eq!((merged.pipeline_config.enable, merged.pipeline_config.initiation_interval), (true, 2));
+
+        let (normalized, n) = (merged.output_value("normalized").unwrap(), merged.input_value("n").unwrap());
+        connect(normalized, n, &mut merged).unwrap();
+        assert_eq!(merged.nodes.len(), norm_nodes + mac_nodes - 1);
+        assert_eq!(merged.input_value("n"), None);
+        assert_eq!(merged.validate(), Ok(()));
+
+        let outputs = simulate(&merged, &[("x", 200), ("w", 3), ("bias", 10)]);
+        assert_eq!((outputs["normalized"], outputs["result"]), (72, 72 * 3 + 10));
+    }
+
+    #[test]
+    fn test_merge_keeps_the_callees_and_port_order_of_both_graphs() {
+        let mut norm = normalization_graph();
+        norm.set_port_order(&["x", "normalized"]);
+        let mut hierarchical = mac_graph();
+        let (product, sum) = (hierarchical.nodes[3].id, hierarchical.nodes[4].id);
+        hierarchical.extract_module(&[product, sum], "mac_core").unwrap();
+        hierarchical.set_port_order(&["n", "w", "bias", "result"]);
+
+        let merged = norm.merge(hierarchical);
+        assert_eq!(merged.validate(), Ok(()));
+        assert_eq!(merged.callees.iter().map(|callee| callee.name.as_str()).collect::<Vec<_>>(), ["mac_core"]);
+        assert_eq!(merged.port_order, ["x", "normalized", "n", "w", "bias", "result"]);
+        let outputs = simulate(&merged, &[("x", 200), ("n", 7), ("w", 3), ("bias", 10)]);
+        assert_eq!((outputs["normalized"], outputs["result"]), (72, 7 * 3 + 10));
+
+        // A callee both graphs declare is kept once
+        let twice = merged.clone().merge(merged);
+        assert_eq!(twice.callees.len(), 1);
+    }
+
+    #[test]
+    fn test_connect_reorders_a_producer_merged_after_its_readers() {
+        let mut merged = mac_graph().merge(normalization_graph());
+        let (normalized, n) = (merged.output_value("normalized").unwrap(), merged.input_value("n").unwrap());
+        connect(normalized, n, &mut merged).unwrap();
+
+        assert_eq!(merged.validate(), Ok(()));
+        let position = |value| merged.nodes.iter().position(|node| node.output == Some(value)).unwrap();
+        let product = merged.nodes.iter().find(|node| matches!(node.op, Operation::Mul(_, _))).unwrap().output.unwrap();
+        assert!(position(normalized) < position(product));
+        assert_eq!(simulate(&merged, &[("x", 130), ("w", 5), ("bias", 1)])["result"], 11);
+    }
+
+    #[test]
+    fn test_extracted_module_simulates_like_the_flat_graph() {
+        let flat = mac_graph();
+        let [n, w] = ["n", "w"].map(|name| flat.input_value(name).unwrap());
+        let (product, sum) = (flat.nodes[3].id, flat.nodes[4].id);
+
+        let (core, inputs, outputs) = flat.clone_subgraph(&[product]);
+        assert_eq!((inputs, outputs), (vec![n, w], vec![ValueId(3)]));
+        assert_eq!((core.input_names(), core.output_names()), (vec!["n".to_string(), "w".to_string()], vec!["out_3".to_string()]));
+        assert_eq!(core.value_width(core.output_value("out_3").unwrap()), Some(32));
+        assert_eq!(simulate(&core, &[("n", 7), ("w", 9)])["out_3"], 63);
+
+        let mut hierarchical = flat.clone();
+        let results = hierarchical.extract_module(&[product, sum], "mac_core").unwrap();
+        assert_eq!(hierarchical.validate(), Ok(()));
+        assert_eq!(hierarchical.callee("mac_core").unwrap().input_names(), ["n", "w", "bias"]);
+        assert_eq!((results.len(), hierarchical.value_width(results[0])), (1, Some(33)));
+        assert_eq!(hierarchical.output_value("result"), Some(results[0]));
+        assert!(!hierarchical.nodes.iter().any(|node| matches!(node.op, Operation::Mul(_, _))));
+        let inputs = [("n", 300), ("w", -4), ("bias", 12)];
+        assert_eq!(simulate(&hierarchical, &inputs), simulate(&flat, &inputs));
+    }
+
+    #[test]
+    fn test_extracting_around_an_outside_node_is_an_error() {
+        // The add reads the product through the register, which stays behind
+        let mut graph = mac_graph();
+        let product = graph.nodes[3].output.unwrap();
+        let register = graph.insert_pipeline_register(product);
+        graph.nodes[5].op = Operation::Add(register, graph.input_value("bias").unwrap());
+        let (mul, add) = (graph.nodes[3].id, graph.nodes[5].id);
+        let error = graph.extract_module(&[mul, add], "mac_core").unwrap_err();
+        assert!(matches!(error, HlsError::TransformError(_)));
+        assert!(error.to_string().contains("reads a value computed outside the extracted nodes"));
+        assert_eq!((graph.nodes.len(), graph.callees.len()), (mac_graph().nodes.len() + 1, 0));
+
+        // Nor can the ports move into the callee
+        let bias = graph.nodes.iter().find(|node| node.op == Operation::Load("bias".to_string())).unwrap().id;
+        assert!(matches!(graph.extract_module(&[bias], "bias_core"), Err(HlsError::TransformError(_))));
+    }
+
+    #[test]
+    fn test_connecting_a_computed_value_is_an_error() {
+        let mut merged = normalization_graph().merge(mac_graph());
+        let (normalized, result) = (merged.output_value("normalized").unwrap(), merged.output_value("result").unwrap());
+        let error = connect(normalized, result, &mut merged).unwrap_err();
+        assert!(error.to_string().contains("is not read from an input port"));
+        assert_eq!(merged.nodes.len(), normalization_graph().nodes.len() + mac_graph().nodes.len());
+    }
 }
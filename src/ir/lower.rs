@@ -1,53 +1,141 @@
 use crate::dsl::ast::*;
-use crate::ir::graph::{Graph, Operation, ValueId};
+use crate::dsl::types::FixedPointType;
+use crate::ir::graph::{ArrayKind, Graph, Operation, ValueId};
 use std::collections::HashMap;
 
+/// Names visible while lowering
+#[derive(Default)]
+struct Env {
+    inputs: HashMap<String, ValueId>,   // Input ports, shared across the whole expression
+    bindings: HashMap<String, ValueId>, // `let` names currently in scope
+}
+
 /// Lower a single expression to IR graph
+///
+/// Panics if a `Var` refers to a name with no enclosing `Let`.
 pub fn lower_expr_to_graph(expr: &Expr) -> Graph {
     let mut graph = Graph::new();
-    let mut env: HashMap<String, ValueId> = HashMap::new();
+    let mut env = Env::default();
     
     let _result = lower_expr(expr, &mut graph, &mut env);
     graph
 }
 
+/// `result = a * b + c * d + e` on 16-bit inputs, the multiply-accumulate the unit tests schedule
+#[cfg(test)]
+pub(crate) fn lowered_mac() -> Graph {
+    let mac = add(add(mul(input("a", 16), input("b", 16)), mul(input("c", 16), input("d", 16))), input("e", 16));
+    lower_expr_to_graph(&output("result", mac))
+}
+
 /// Lower an expression recursively, building the IR graph
-fn lower_expr(expr: &Expr, graph: &mut Graph, env: &mut HashMap<String, ValueId>) -> ValueId {
+fn lower_expr(expr: &Expr, graph: &mut Graph, env: &mut Env) -> ValueId {
     match expr {
-        Expr::Const { value, width: _ } => {
-            graph.add_node_with_output(Operation::Const(*value as i64))
+        Expr::Const { value, width, signed } => {
+            graph.add_node_with_output_type(Operation::Const(*value as i64), Some(*width), *signed)
         }
         
-        Expr::Input { name, width: _ } => {
+        Expr::Input { name, width, signed } => {
             // Check if we already have this input in our environment
-            if let Some(&existing_val) = env.get(name) {
+            if let Some(&existing_val) = env.inputs.get(name) {
                 existing_val
             } else {
                 // Create a new input (load operation)
-                let val_id = graph.add_node_with_output(Operation::Load(name.clone()));
-                env.insert(name.clone(), val_id);
+                let val_id = graph.add_node_with_output_type(Operation::Load(name.clone()), Some(*width), *signed);
+                env.inputs.insert(name.clone(), val_id);
                 val_id
             }
         }
         
-        Expr::Add(left, right) => {
-            let l = lower_expr(left, graph, env);
-            let r = lower_expr(right, graph, env);
-            graph.add_node_with_output(Operation::Add(l, r))
+        Expr::FixedPointInput { name, ty } => {
+            if let Some(&existing_val) = env.inputs.get(name) {
+                existing_val
+            } else {
+                let val_id = graph.add_node_with_output_type(Operation::Load(name.clone()), Some(ty.width()), ty.signed);
+                graph.set_fixed_point(val_id, *ty);
+                env.inputs.insert(name.clone(), val_id);
+                val_id
+            }
         }
         
-        Expr::Sub(left, right) => {
+        Expr::Add(left, right) => lower_binary(left, right, graph, env, Operation::Add),
+        Expr::Sub(left, right) => lower_binary(left, right, graph, env, Operation::Sub),
+        Expr::Mul(left, right) => lower_binary(left, right, graph, env, Operation::Mul),
+        Expr::Div(left, right) => lower_binary(left, right, graph, env, Operation::Div),
+        Expr::SAdd(left, right) => lower_binary(left, right, graph, env, Operation::SAdd),
+        Expr::SSub(left, right) => lower_binary(left, right, graph, env, Operation::SSub),
+        Expr::SMul(left, right) => lower_binary(left, right, graph, env, Operation::SMul),
+        // Saturation bounds are integer ones, so fixed-point operands are not aligned
+        Expr::AddSat(left, right, width) | Expr::SubSat(left, right, width) => {
             let l = lower_expr(left, graph, env);
             let r = lower_expr(right, graph, env);
-            graph.add_node_with_output(Operation::Sub(l, r))
+            let op = if matches!(expr, Expr::AddSat(_, _, _)) { Operation::AddSat(l, r, *width) } else { Operation::SubSat(l, r, *width) };
+            graph.add_node_with_output(op)
         }
+        Expr::And(left, right) => lower_binary(left, right, graph, env, Operation::And),
+        Expr::Or(left, right) => lower_binary(left, right, graph, env, Operation::Or),
+        Expr::Xor(left, right) => lower_binary(left, right, graph, env, Operation::Xor),
+        Expr::Nand(left, right) => lower_binary(left, right, graph, env, Operation::Nand),
+        Expr::Nor(left, right) => lower_binary(left, right, graph, env, Operation::Nor),
+        Expr::Xnor(left, right) => lower_binary(left, right, graph, env, Operation::Xnor),
+        Expr::Concat(high, low) => lower_binary(high, low, graph, env, Operation::Concat),
+        Expr::Shl(left, right) => lower_binary(left, right, graph, env, Operation::Shl),
+        Expr::Shr(left, right) => lower_binary(left, right, graph, env, Operation::Shr),
+        Expr::Lt(left, right) => lower_binary(left, right, graph, env, Operation::CmpLt),
+        Expr::Eq(left, right) => lower_binary(left, right, graph, env, Operation::CmpEq),
+        // a > b is lowered as b < a so only one comparator kind is needed
+        Expr::Gt(left, right) => lower_binary(left, right, graph, env, |l, r| Operation::CmpLt(r, l)),
         
-        Expr::Mul(left, right) => {
-            let l = lower_expr(left, graph, env);
-            let r = lower_expr(right, graph, env);
-            graph.add_node_with_output(Operation::Mul(l, r))
+        Expr::Not(inner) => {
+            let v = lower_expr(inner, graph, env);
+            graph.add_node_with_output(Operation::Not(v))
+        }
+
+        Expr::Sqrt(inner) => {
+            let v = lower_expr(inner, graph, env);
+            graph.add_node_with_output(Operation::Sqrt(v))
         }
         
+        Expr::Slice { expr, high, low } => {
+            let v = lower_expr(expr, graph, env);
+            graph.add_node_with_output(Operation::Slice(v, *high, *low))
+        }
+        
+        Expr::Select { cond, then, els } => {
+            let c = lower_expr(cond, graph, env);
+            let t = lower_expr(then, graph, env);
+            let f = lower_expr(els, graph, env);
+            graph.add_node_with_output(Operation::Mux(c, t, f))
+        }
+        
+        Expr::Sum(values) => lower_reduction(values, graph, env, Operation::ReduceAdd),
+        Expr::MaxOf(values) => lower_reduction(values, graph, env, Operation::ReduceMax),
+        Expr::MinOf(values) => lower_reduction(values, graph, env, Operation::ReduceMin),
+        
+        Expr::Index { array, depth, width, index } => {
+            // The first read of an array declares it
+            if graph.array(array).is_none() {
+                graph.declare_array(array, *depth, Some(*width), ArrayKind::Input);
+            }
+            let index = lower_expr(index, graph, env);
+            graph.add_node_with_output(Operation::ArrayLoad(array.clone(), index))
+        }
+        
+        Expr::Let { name, value, body } => {
+            // Lower the bound value once; the body may shadow an outer binding
+            let val = lower_expr(value, graph, env);
+            let shadowed = env.bindings.insert(name.clone(), val);
+            let result = lower_expr(body, graph, env);
+            match shadowed {
+                Some(outer) => env.bindings.insert(name.clone(), outer),
+                None => env.bindings.remove(name),
+            };
+            result
+        }
+        
+        Expr::Var(name) => *env.bindings.get(name)
+            .unwrap_or_else(|| panic!("Unbound variable '{}' in expression", name)),
+        
         Expr::Output { name, expr } => {
             let val = lower_expr(expr, graph, env);
             graph.add_node(Operation::Store(name.clone(), val));
@@ -55,3 +143,153 @@ fn lower_expr(expr: &Expr, graph: &mut Graph, env: &mut HashMap<String, ValueId>
         }
     }
 }
+
+/// Lower every input of a reduction and combine them into one tree node
+fn lower_reduction(values: &[Expr], graph: &mut Graph, env: &mut Env, op: fn(Vec<ValueId>) -> Operation) -> ValueId {
+    let values = values.iter().map(|value| lower_expr(value, graph, env)).collect();
+    graph.add_node_with_output(op(values))
+}
+
+/// Lower both operands of a binary expression and combine them with `op`
+fn lower_binary(
+    left: &Expr,
+    right: &Expr,
+    graph: &mut Graph,
+    env: &mut Env,
+    op: fn(ValueId, ValueId) -> Operation,
+) -> ValueId {
+    let l = lower_expr(left, graph, env);
+    let r = lower_expr(right, graph, env);
+    if graph.value_fixed_point(l).is_some() || graph.value_fixed_point(r).is_some() {
+        return lower_fixed_point(l, r, graph, op);
+    }
+    graph.add_node_with_output(op(l, r))
+}
+
+/// Combine values of which at least one is fixed-point, keeping the binary point in place
+///
+/// Sums first shift the operand with fewer fractional bits left to line the
+/// binary points up. A product of Qa.b and Qc.d is Q(a+c).(b+d) and is
+/// shifted right by min(b, d), so two Q8.8 factors give a Q16.8 result.
+/// Plain integers count as having no fractional bits; other operations
+/// treat the raw bits as integers.
+fn lower_fixed_point(l: ValueId, r: ValueId, graph: &mut Graph, op: fn(ValueId, ValueId) -> Operation) -> ValueId {
+    let format = |graph: &Graph, value: ValueId| graph.value_fixed_point(value).unwrap_or_else(|| {
+        FixedPointType::new(graph.value_width(value).unwrap_or(32), 0, graph.value_signed(value))
+    });
+    let (lf, rf) = (format(graph, l), format(graph, r));
+
+    match op(l, r) {
+        Operation::Mul(_, _) | Operation::SMul(_, _) => {
+            let product = graph.add_node_with_output(op(l, r));
+            let signed = graph.value_signed(product);
+            let full = FixedPointType::new(lf.integer_bits + rf.integer_bits, lf.fractional_bits + rf.fractional_bits, signed);
+            graph.set_fixed_point(product, full);
+            let shift = lf.fractional_bits.min(rf.fractional_bits);
+            if shift == 0 {
+                return product;
+            }
+            // The bits shifted out of the top are dropped, so signed products stay correct
+            let amount = graph.add_node_with_output_width(Operation::Const(shift as i64), u32::BITS - shift.leading_zeros());
+            let corrected = FixedPointType { fractional_bits: full.fractional_bits - shift, ..full };
+            let result = graph.add_node_with_output_type(Operation::Shr(product, amount), Some(corrected.width()), signed);
+            graph.set_fixed_point(result, corrected);
+            result
+        }
+        Operation::Add(_, _) | Operation::Sub(_, _) | Operation::SAdd(_, _) | Operation::SSub(_, _) => {
+            let fractional_bits = lf.fractional_bits.max(rf.fractional_bits);
+            let align = |graph: &mut Graph, value: ValueId, ty: FixedPointType| {
+                let shift = fractional_bits - ty.fractional_bits;
+                if shift == 0 {
+                    return value;
+                }
+                let amount = graph.add_node_with_output_width(Operation::Const(shift as i64), u32::BITS - shift.leading_zeros());
+                let aligned = FixedPointType { fractional_bits, ..ty };
+                let shifted = graph.add_node_with_output_type(Operation::Shl(value, amount), Some(aligned.width()), ty.signed);
+                graph.set_fixed_point(shifted, aligned);
+                shifted
+            };
+            let (l, r) = (align(graph, l, lf), align(graph, r, rf));
+            let result = graph.add_node_with_output(op(l, r));
+            let integer_bits = lf.integer_bits.max(rf.integer_bits) + 1;
+            graph.set_fixed_point(result, FixedPointType::new(integer_bits, fractional_bits, graph.value_signed(result)));
+            result
+        }
+        operation => graph.add_node_with_output(operation),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_let_binding_shares_product() {
+        let expr = output("result", let_in(
+            "p",
+            mul(input("a", 16), input("b", 16)),
+            add(var("p"), var("p")),
+        ));
+        let graph = lower_expr_to_graph(&expr);
+
+        let muls = graph.nodes.iter().filter(|n| matches!(n.op, Operation::Mul(_, _))).count();
+        assert_eq!(muls, 1);
+    }
+
+    #[test]
+    fn test_indexed_reads_share_one_array() {
+        let i = input("i", 4);
+        let expr = output("c", add(index("a", 16, 8, i.clone()), index("a", 16, 8, add(i, const_val(1, 4)))));
+        let graph = lower_expr_to_graph(&expr);
+        assert_eq!(graph.arrays.len(), 1);
+
+        let mut sim = crate::backend::sim::Simulator::new();
+        sim.set_array("a", &(0..16).map(|x| 10 * x).collect::<Vec<_>>(), &graph);
+        sim.set_input("i", 3, &graph);
+        assert_eq!(sim.simulate(&graph).unwrap()["c"], 70);
+    }
+
+    #[test]
+    fn test_fixed_point_product_is_shifted_back() {
+        // 1.5 * 2.0 in Q8.8
+        let q8_8 = FixedPointType::unsigned(8, 8);
+        let graph = lower_expr_to_graph(&output("result", mul(fixed_input("a", q8_8), fixed_input("b", q8_8))));
+        let shr = graph.nodes.iter().find(|node| matches!(node.op, Operation::Shr(_, _))).unwrap();
+        assert_eq!(shr.fixed_point, Some(FixedPointType::unsigned(16, 8)));
+
+        let mut sim = crate::backend::sim::Simulator::new();
+        sim.set_input("a", q8_8.encode(1.5), &graph);
+        sim.set_input("b", q8_8.encode(2.0), &graph);
+        let result = sim.simulate(&graph).unwrap()["result"];
+        assert_eq!(result, 3 << 8);
+        assert_eq!(q8_8.decode(result), 3.0);
+    }
+
+    #[test]
+    fn test_fixed_point_sum_aligns_the_binary_point() {
+        // 1.5 in Q8.8 plus 0.25 in signed Q4.4
+        let expr = output("result", add(fixed_input("a", FixedPointType::unsigned(8, 8)), fixed_input("b", FixedPointType::signed(4, 4))));
+        let graph = lower_expr_to_graph(&expr);
+        let sum = graph.nodes.iter().find(|node| matches!(node.op, Operation::Add(_, _))).unwrap();
+        assert_eq!(sum.fixed_point, Some(FixedPointType::unsigned(9, 8)));
+
+        let mut sim = crate::backend::sim::Simulator::new();
+        sim.set_input("a", 384, &graph);
+        sim.set_input("b", 4, &graph);
+        assert_eq!(sim.simulate(&graph).unwrap()["result"], 448);
+    }
+
+    #[test]
+    fn test_let_binding_is_scoped() {
+        // The inner `x` shadows the outer one only inside its body
+        let expr = output("result", let_in(
+            "x",
+            const_val(1, 8),
+            add(let_in("x", const_val(2, 8), var("x")), var("x")),
+        ));
+        let graph = lower_expr_to_graph(&expr);
+
+        let mut sim = crate::backend::sim::Simulator::new();
+        assert_eq!(sim.simulate(&graph).unwrap()["result"], 3);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod graph;
-pub mod lower;
\ No newline at end of file
+pub mod lower;
+pub mod export;
+pub mod serde;
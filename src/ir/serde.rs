@@ -0,0 +1,111 @@
+//! JSON serialization of IR graphs
+//!
+//! `Graph::to_json` captures every field, including the pipeline schedule, so
+//! an expensive scheduling run can be cached on disk and reloaded with
+//! `Graph::from_json`. Operations are externally tagged, e.g. `{"Add": [0, 1]}`.
+
+use crate::error::HlsError;
+use crate::ir::graph::Graph;
+use serde_json::Value;
+
+impl Graph {
+    /// Serialize the graph, including its schedule, to a JSON value
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("graphs always serialize to JSON")
+    }
+
+    /// Rebuild a graph written by `to_json`, rejecting malformed or invalid graphs
+    pub fn from_json(value: &Value) -> Result<Graph, HlsError> {
+        let graph: Graph = serde_json::from_value(value.clone())?;
+        graph.validate()?;
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::sim::Simulator;
+    use crate::error::HlsError;
+    use crate::ir::graph::{Graph, GraphError, NodeId, Operation, ValueId};
+    use crate::passes::pipeline::run_pipeline_pass;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    const INPUTS: [&str; 3] = ["a", "b", "c"];
+
+    /// A small graph over three 8-bit inputs: `ops` picks each operation and its operands
+    fn build_graph(ops: &[(u8, usize, usize, usize)], pipelined: bool) -> Graph {
+        let mut graph = Graph::new();
+        let mut values: Vec<ValueId> = INPUTS.iter()
+            .map(|name| graph.add_node_with_output_width(Operation::Load(name.to_string()), 8))
+            .collect();
+        for &(kind, a, b, c) in ops {
+            let (a, b, c) = (values[a % values.len()], values[b % values.len()], values[c % values.len()]);
+            let op = match kind % 9 {
+                0 => Operation::Add(a, b),
+                1 => Operation::Sub(a, b),
+                2 => Operation::Mul(a, b),
+                3 => Operation::And(a, b),
+                4 => Operation::Or(a, b),
+                5 => Operation::Xor(a, b),
+                6 => Operation::CmpLt(a, b),
+                7 => Operation::Not(a),
+                _ => Operation::Mux(a, b, c),
+            };
+            values.push(graph.add_node_with_output(op));
+        }
+        graph.add_node(Operation::Store("result".to_string(), *values.last().unwrap()));
+        if pipelined {
+            graph.enable_pipeline(1, 3, 1);
+            run_pipeline_pass(&mut graph).unwrap();
+        }
+        graph
+    }
+
+    #[test]
+    fn test_operations_are_externally_tagged() {
+        let graph = build_graph(&[(0, 0, 1, 0)], false);
+        let json = graph.to_json();
+
+        assert_eq!(json["nodes"][3]["op"], json!({"Add": [0, 1]}));
+        assert_eq!(json["nodes"][0]["op"], json!({"Load": "a"}));
+        assert_eq!(json["value_map"]["3"], json!(3));
+    }
+
+    #[test]
+    fn test_from_json_rejects_invalid_graphs() {
+        let mut json = build_graph(&[(0, 0, 1, 0)], false).to_json();
+        json["nodes"][4]["op"] = json!({"Store": ["result", 42]});
+
+        match Graph::from_json(&json) {
+            Err(HlsError::ValidationError(errors)) => {
+                assert_eq!(errors, vec![GraphError::UndefinedValue { node: NodeId(4), value: ValueId(42) }]);
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        assert!(matches!(Graph::from_json(&json!({"nodes": 3})), Err(HlsError::JsonError(_))));
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip_preserves_graph_and_results(
+            ops in prop::collection::vec((any::<u8>(), 0..16usize, 0..16usize, 0..16usize), 1..12),
+            pipelined in any::<bool>(),
+            inputs in prop::array::uniform3(0..256i64),
+        ) {
+            let graph = build_graph(&ops, pipelined);
+            let restored = Graph::from_json(&graph.to_json()).unwrap();
+
+            prop_assert_eq!(restored.to_json(), graph.to_json());
+            prop_assert_eq!(restored.pipeline_stages.len(), graph.pipeline_stages.len());
+
+            let mut before = Simulator::new();
+            let mut after = Simulator::new();
+            for (name, value) in INPUTS.iter().zip(inputs) {
+                before.set_input(name, value, &graph);
+                after.set_input(name, value, &restored);
+            }
+            prop_assert_eq!(before.simulate(&graph).unwrap(), after.simulate(&restored).unwrap());
+        }
+    }
+}
@@ -2,4 +2,8 @@ pub mod dsl;
 pub mod ir;
 pub mod backend;
 pub mod passes;
-pub mod hft;
\ No newline at end of file
+pub mod hft;
+pub mod config;
+pub mod error;
+
+pub use error::HlsError;
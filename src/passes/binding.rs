@@ -0,0 +1,138 @@
+//! Binding of scheduled multiplies to shared multipliers
+//!
+//! A pipelined kernel starts an iteration at most every II cycles, so a
+//! multiply starting in cycle `c` of the schedule only ever needs its
+//! multiplier in the cycles congruent to `c` mod II. Multiplies in different
+//! slots can therefore take turns on one DSP, fed through input muxes that
+//! select on the slot. The pass packs the multiplies of a scheduled graph
+//! into as few units as their slots allow and records the units on the
+//! graph, for the Verilog backend and the resource report.
+
+use crate::ir::graph::{FunctionalUnit, Graph, NodeId, Operation, DEFAULT_WIDTH};
+use crate::passes::timing::schedule_of;
+
+/// Bind the scheduled `Mul` and `SMul` nodes of `graph` to `graph.functional_units`
+///
+/// Units never mix signed and unsigned multiplies, and the widest
+/// multiplies open units first so narrow ones share the DSPs already paid
+/// for. At II=1 every multiply gets a unit of its own. Unscheduled graphs
+/// are left without units. Returns the number of multipliers saved.
+pub fn run_binding_pass(graph: &mut Graph) -> usize {
+    let schedule = schedule_of(graph);
+    let ii = graph.pipeline_config.initiation_interval.max(1);
+    let mut multiplies: Vec<(bool, u32, usize, NodeId)> = graph.nodes.iter()
+        .filter(|node| matches!(node.op, Operation::Mul(_, _) | Operation::SMul(_, _)))
+        .filter_map(|node| {
+            let cycle = *schedule.get(&node.id)?;
+            Some((signed_multiply(graph, &node.op), operand_width(graph, &node.op), cycle, node.id))
+        })
+        .collect();
+    multiplies.sort_by_key(|&(signed, width, cycle, node_id)| (signed, std::cmp::Reverse(width), cycle, node_id.0));
+
+    // (signedness, slots taken, unit)
+    let mut units: Vec<(bool, Vec<usize>, FunctionalUnit)> = Vec::new();
+    for (signed, _, cycle, node_id) in multiplies {
+        let slot = cycle % ii;
+        match units.iter_mut().find(|(unit_signed, slots, _)| *unit_signed == signed && !slots.contains(&slot)) {
+            Some((_, slots, unit)) => {
+                slots.push(slot);
+                unit.operations.push(node_id);
+            }
+            None => units.push((signed, vec![slot], FunctionalUnit { operations: vec![node_id] })),
+        }
+    }
+
+    let bound: usize = units.iter().map(|(_, slots, _)| slots.len()).sum();
+    graph.functional_units = units.into_iter()
+        .map(|(_, _, mut unit)| {
+            unit.operations.sort_by_key(|node_id| schedule[node_id] % ii);
+            unit
+        })
+        .collect();
+    bound - graph.functional_units.len()
+}
+
+/// Whether the Verilog backend emits `op` as a two's-complement multiply
+pub(crate) fn signed_multiply(graph: &Graph, op: &Operation) -> bool {
+    match op {
+        Operation::SMul(_, _) => true,
+        Operation::Mul(a, b) => graph.value_signed(*a) && graph.value_signed(*b),
+        _ => false,
+    }
+}
+
+fn operand_width(graph: &Graph, op: &Operation) -> u32 {
+    op.operands().into_iter().map(|value| graph.value_width(value).unwrap_or(DEFAULT_WIDTH)).max().unwrap_or(DEFAULT_WIDTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+    use crate::dsl::ast::*;
+    use crate::ir::graph::GraphError;
+    use crate::ir::lower::lower_expr_to_graph;
+    use crate::passes::pipeline::PipelineScheduler;
+    use crate::passes::report::build_report;
+
+    /// `a*b + c*d + e*f + g*h` at II=4 with a single multiplier to schedule on
+    fn four_products() -> Graph {
+        let product = |a: &str, b: &str| mul(input(a, 16), input(b, 16));
+        let sum = add(add(add(product("a", "b"), product("c", "d")), product("e", "f")), product("g", "h"));
+        let mut graph = lower_expr_to_graph(&output("result", sum));
+        graph.enable_pipeline(4, 24, 1);
+        let mut scheduler = PipelineScheduler::new();
+        scheduler.resource_constraints.insert("multiplier".to_string(), 1);
+        scheduler.schedule_pipeline(&mut graph).unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_four_multiplies_at_ii_4_share_one_unit() {
+        let mut graph = four_products();
+        let mut sim = Simulator::new();
+        for (port, value) in ["a", "b", "c", "d", "e", "f", "g", "h"].iter().zip(1..) {
+            sim.set_input(port, value, &graph);
+        }
+        let before = sim.simulate(&graph).unwrap();
+        let unbound = build_report(&graph);
+
+        assert_eq!(run_binding_pass(&mut graph), 3);
+        assert_eq!(graph.functional_units.len(), 1);
+        assert_eq!(graph.functional_units[0].operations.len(), 4);
+        assert!(graph.validate().is_ok());
+        assert_eq!(sim.simulate(&graph).unwrap(), before);
+
+        // The report counts the one DSP, and the muxes in front of it
+        let bound = build_report(&graph);
+        assert_eq!((unbound.multipliers, unbound.resources.dsps), (4, 4));
+        assert_eq!((bound.multipliers, bound.resources.dsps), (1, 1));
+        assert!(bound.resources.luts > unbound.resources.luts);
+
+        // A multiply bound twice clashes with itself, and an add is no multiply
+        let mut clash = graph.clone();
+        let first = clash.functional_units[0].operations[0];
+        clash.functional_units[0].operations.push(first);
+        let sum = clash.nodes.iter().find(|node| matches!(node.op, Operation::Add(_, _))).unwrap().id;
+        clash.functional_units.push(FunctionalUnit { operations: vec![sum] });
+        let errors = clash.validate().unwrap_err();
+        assert!(errors.contains(&GraphError::SlotConflict { unit: 0, nodes: (first, first) }));
+        assert!(errors.contains(&GraphError::InvalidBinding { unit: 1, node: sum }));
+    }
+
+    #[test]
+    fn test_multiplies_in_one_slot_need_units_of_their_own() {
+        // Unconstrained, both products start together
+        let product = |a: &str, b: &str| mul(input(a, 16), input(b, 16));
+        let mut graph = lower_expr_to_graph(&output("result", add(product("a", "b"), product("c", "d"))));
+        graph.enable_pipeline(4, 24, 1);
+        PipelineScheduler::new().schedule_pipeline(&mut graph).unwrap();
+        assert_eq!(run_binding_pass(&mut graph), 0);
+        assert_eq!(graph.functional_units.len(), 2);
+        assert!(!graph.functional_units.iter().any(FunctionalUnit::is_shared));
+
+        // Rescheduling drops the binding
+        PipelineScheduler::new().schedule_pipeline(&mut graph).unwrap();
+        assert!(graph.functional_units.is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+//! Constant folding for HLS graphs
+//!
+//! Operations whose operands are all `Const` nodes are evaluated at compile
+//! time and replaced by a single `Const`, so literal values passed through
+//! multi-stage computations cost no logic. The operands left behind are
+//! removed by the DCE pass.
+
+use crate::backend::sim::{as_signed, as_unsigned, compare_values, concat_bits, expression_operand, fit_to_width, saturate, shift_left, shift_right_logical, slice_bits};
+use crate::error::HlsError;
+use crate::ir::graph::{Graph, Operation, ValueId};
+
+/// Fold constant operations until a fixed point is reached
+///
+/// Results are truncated to the node's declared width, exactly as the
+/// simulator would compute them. Returns the number of folded nodes, or an
+/// error if a constant division by zero is found.
+pub fn run_const_fold_pass(graph: &mut Graph) -> Result<usize, HlsError> {
+    let mut folded = 0;
+
+    loop {
+        let mut changed = false;
+
+        for index in 0..graph.nodes.len() {
+            let node = &graph.nodes[index];
+            if matches!(node.op, Operation::Const(_)) {
+                continue;
+            }
+
+            if let Some(value) = evaluate(&node.op, graph)? {
+                let node = &mut graph.nodes[index];
+                node.op = Operation::Const(fit_to_width(value, node.output_width, node.signed));
+                folded += 1;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return Ok(folded);
+        }
+    }
+}
+
+/// Value of a constant operand, if it is produced by a `Const` node
+fn const_value(graph: &Graph, value: ValueId) -> Option<i64> {
+    match graph.producer(value)?.op {
+        Operation::Const(val) => Some(val),
+        _ => None,
+    }
+}
+
+/// Operands of a two-input arithmetic operation, read as the simulator does
+fn binary_operands(a: i64, b: i64, left: ValueId, right: ValueId, graph: &Graph) -> (i64, i64) {
+    let signed = graph.value_signed(left) && graph.value_signed(right);
+    (expression_operand(a, left, signed, graph), expression_operand(b, right, signed, graph))
+}
+
+/// Evaluate an operation whose operands are all constants
+fn evaluate(op: &Operation, graph: &Graph) -> Result<Option<i64>, HlsError> {
+    let operands: Option<Vec<i64>> = op.operands().into_iter()
+        .map(|value| const_value(graph, value))
+        .collect();
+    let Some(operands) = operands else { return Ok(None) };
+
+    let result = match (op, operands.as_slice()) {
+        (Operation::Add(left, right), &[a, b]) => { let (a, b) = binary_operands(a, b, *left, *right, graph); a.wrapping_add(b) }
+        (Operation::Sub(left, right), &[a, b]) => { let (a, b) = binary_operands(a, b, *left, *right, graph); a.wrapping_sub(b) }
+        (Operation::Mul(left, right), &[a, b]) => { let (a, b) = binary_operands(a, b, *left, *right, graph); a.wrapping_mul(b) }
+        (Operation::SAdd(left, right), &[a, b]) => as_signed(a, *left, graph).wrapping_add(as_signed(b, *right, graph)),
+        (Operation::SSub(left, right), &[a, b]) => as_signed(a, *left, graph).wrapping_sub(as_signed(b, *right, graph)),
+        (Operation::SMul(left, right), &[a, b]) => as_signed(a, *left, graph).wrapping_mul(as_signed(b, *right, graph)),
+        (Operation::AddSat(_, _, _) | Operation::SubSat(_, _, _), &[a, b]) => saturate(op, a, b, graph),
+        (Operation::Div(_, _), &[_, 0]) => return Err(HlsError::OptimizationError("constant division by zero".to_string())),
+        (Operation::Div(_, _), &[a, b]) => a.checked_div(b).unwrap_or(0),
+        (Operation::And(_, _), &[a, b]) => a & b,
+        (Operation::Or(_, _), &[a, b]) => a | b,
+        (Operation::Xor(_, _), &[a, b]) => a ^ b,
+        (Operation::Nand(_, _), &[a, b]) => !(a & b),
+        (Operation::Nor(_, _), &[a, b]) => !(a | b),
+        (Operation::Xnor(_, _), &[a, b]) => !(a ^ b),
+        (Operation::Not(_), &[a]) => !a,
+        (Operation::Sqrt(value), &[a]) => as_unsigned(a, graph.value_width(*value)).isqrt() as i64,
+        (Operation::Concat(_, low), &[a, b]) => concat_bits(a, b, graph.value_width(*low).unwrap_or(32)),
+        (Operation::Slice(_, high, low), &[a]) => slice_bits(a, *high, *low),
+        (Operation::Shl(_, _), &[a, b]) => shift_left(a, b),
+        (Operation::Shr(value, _), &[a, b]) => shift_right_logical(as_unsigned(a, graph.value_width(*value)) as i64, b),
+        (Operation::CmpLt(left, right), &[a, b]) => compare_values(a, b, *left, *right, graph).is_lt() as i64,
+        (Operation::CmpEq(left, right), &[a, b]) => compare_values(a, b, *left, *right, graph).is_eq() as i64,
+        (Operation::Fma(x, y, z), &[a, b, c]) => {
+            let signed = [x, y, z].into_iter().all(|operand| graph.value_signed(*operand));
+            expression_operand(a, *x, signed, graph).wrapping_mul(expression_operand(b, *y, signed, graph))
+                .wrapping_add(expression_operand(c, *z, signed, graph))
+        }
+        (Operation::Mux(_, _, _), &[cond, a, b]) => if cond != 0 { a } else { b },
+        (Operation::ReduceAdd(_), values) => values.iter().copied().fold(0, i64::wrapping_add),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+    use crate::passes::dce::run_dce_pass;
+
+    #[test]
+    fn test_constant_mac_folds_to_single_const() {
+        let mac = add(
+            mul(const_val(3, 16), const_val(5, 16)),
+            mul(const_val(7, 16), const_val(2, 16)),
+        );
+        let mut graph = lower_expr_to_graph(&output("result", mac));
+
+        assert_eq!(run_const_fold_pass(&mut graph).unwrap(), 3);
+        run_dce_pass(&mut graph);
+
+        let computed: Vec<_> = graph.nodes.iter()
+            .filter(|n| !matches!(n.op, Operation::Store(_, _)))
+            .collect();
+        assert_eq!(computed.len(), 1);
+        assert_eq!(computed[0].op, Operation::Const(29));
+    }
+
+    #[test]
+    fn test_constant_division_by_zero_is_an_error() {
+        let mut graph = lower_expr_to_graph(&output("result", div(const_val(8, 8), const_val(0, 8))));
+        assert!(matches!(run_const_fold_pass(&mut graph), Err(HlsError::OptimizationError(_))));
+    }
+
+    #[test]
+    fn test_signed_shift_right_folds_to_the_zero_filled_value() {
+        // 0xFFF0 >> 4 on 16 bits, as the RTL computes it
+        let mut graph = lower_expr_to_graph(&output("result", shr(signed_const(-16, 16), const_val(4, 8))));
+        assert_eq!(run_const_fold_pass(&mut graph).unwrap(), 1);
+        assert!(graph.nodes.iter().any(|node| node.op == Operation::Const(0x0FFF)));
+    }
+}
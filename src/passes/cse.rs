@@ -0,0 +1,139 @@
+//! Common subexpression elimination for HLS graphs
+//!
+//! Identical operations on identical operands are computed once, so repeated
+//! products such as `a*b` share a single DSP48E2 slice instead of instantiating
+//! one multiplier per occurrence.
+
+use crate::ir::graph::{Graph, NodeId, Operation, ValueId};
+use std::collections::{HashMap, HashSet};
+
+/// Merge duplicate operations, rewriting every use to the first occurrence
+///
+/// Nodes are visited in graph order, which is topological for graphs built
+/// through the `Graph` API. Returns the number of eliminated nodes.
+pub fn run_cse_pass(graph: &mut Graph) -> usize {
+    let mut seen: HashMap<(Operation, Option<u32>, bool), ValueId> = HashMap::new();
+    let mut replacements: HashMap<ValueId, ValueId> = HashMap::new();
+    let mut removed: HashSet<NodeId> = HashSet::new();
+
+    for node in &mut graph.nodes {
+        // Uses of earlier duplicates now refer to the surviving value
+        for operand in node.op.operands_mut() {
+            if let Some(&replacement) = replacements.get(operand) {
+                *operand = replacement;
+            }
+        }
+
+        let Some(output) = node.output else { continue };
+        if !is_mergeable(&node.op) {
+            continue;
+        }
+
+        // Loads are keyed by port name, so only reads of the same port merge
+        let key = (node.op.clone(), node.output_width, node.signed);
+        match seen.get(&key) {
+            Some(&existing) => {
+                replacements.insert(output, existing);
+                removed.insert(node.id);
+            }
+            None => {
+                seen.insert(key, output);
+            }
+        }
+    }
+
+    graph.nodes.retain(|node| !removed.contains(&node.id));
+    graph.value_map.retain(|value, _| !replacements.contains_key(value));
+    for stage in &mut graph.pipeline_stages {
+        stage.operations.retain(|node_id| !removed.contains(node_id));
+    }
+
+    removed.len()
+}
+
+/// Side-effecting and structural nodes are never merged
+fn is_mergeable(op: &Operation) -> bool {
+    !matches!(
+        op,
+        Operation::Store(_, _) | Operation::PipelineRegister(_) | Operation::PipelineBarrier | Operation::Nop |
+        // An intervening store can change what an array read returns
+        Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) |
+        // Each call is its own instance, whose state registers the other does not share
+        Operation::Call(_, _)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+    use crate::hft::ZeroPlusStrategy;
+
+    #[test]
+    fn test_cse_merges_repeated_products() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let b = graph.add_node_with_output(Operation::Load("b".to_string()));
+        let p1 = graph.add_node_with_output(Operation::Mul(a, b));
+        let p2 = graph.add_node_with_output(Operation::Mul(a, b));
+        let p3 = graph.add_node_with_output(Operation::Mul(a, b));
+        let s1 = graph.add_node_with_output(Operation::Add(p1, p2));
+        let s2 = graph.add_node_with_output(Operation::Add(s1, p3));
+        graph.add_node(Operation::Store("result".to_string(), s2));
+
+        let eliminated = run_cse_pass(&mut graph);
+
+        assert_eq!(eliminated, 2);
+        let muls = graph.nodes.iter().filter(|n| matches!(n.op, Operation::Mul(_, _))).count();
+        assert_eq!(muls, 1);
+
+        let mut sim = Simulator::new();
+        sim.set_input("a", 3, &graph);
+        sim.set_input("b", 7, &graph);
+        assert_eq!(sim.simulate(&graph).unwrap()["result"], 63);
+    }
+
+    #[test]
+    fn test_cse_keeps_stores_and_distinct_loads() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let b = graph.add_node_with_output(Operation::Load("b".to_string()));
+        graph.add_node(Operation::Store("x".to_string(), a));
+        graph.add_node(Operation::Store("y".to_string(), b));
+
+        assert_eq!(run_cse_pass(&mut graph), 0);
+        assert_eq!(graph.nodes.len(), 4);
+    }
+
+    #[test]
+    fn test_cse_finds_nothing_to_share_in_the_strategy_graph() {
+        let original = ZeroPlusStrategy::to_fpga_graph();
+        let mut optimized = original.clone();
+
+        // Constants of one width and the flat && spread_optimal condition are already built once
+        assert_eq!(run_cse_pass(&mut optimized), 0);
+        assert_eq!(optimized.nodes.len(), original.nodes.len());
+
+        let markets = [
+            // bid, ask, bid_qty, ask_qty, bid_strong, ask_strong, position, last fill price and side
+            [80300, 80301, 500, 50, 1, 0, 0, 0, 0],      // buy
+            [80300, 80301, 50, 500, 0, 1, 0, 0, 0],      // sell
+            [80300, 80302, 500, 500, 1, 1, 0, 0, 0],     // spread too wide
+            [80300, 80301, 500, 500, 1, 1, 5, 0, 0],     // not flat
+            [80300, 80301, 500, 500, 0, 1, 5, 80300, 1], // scratch a weakened buy
+        ];
+        let names = [
+            "best_bid_price", "best_ask_price", "best_bid_qty", "best_ask_qty",
+            "bid_queue_strong", "ask_queue_strong", "current_position", "last_fill_price", "last_fill_side",
+        ];
+        for market in markets {
+            let mut before = Simulator::new();
+            let mut after = Simulator::new();
+            for (name, value) in names.iter().zip(market) {
+                before.set_input(name, value, &original);
+                after.set_input(name, value, &optimized);
+            }
+            assert_eq!(before.simulate(&original).unwrap(), after.simulate(&optimized).unwrap());
+        }
+    }
+}
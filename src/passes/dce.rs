@@ -0,0 +1,90 @@
+//! Dead-code elimination for HLS graphs
+//!
+//! Removes nodes whose results never reach a `Store`, `ArrayStore`,
+//! `LoopNext` or `RegisterStore`, such as dangling arithmetic, unused
+//! constants, disconnected pipeline registers and `Nop`s. `PipelineBarrier` nodes are synchronization points and are always kept.
+
+use crate::ir::graph::{Graph, NodeId, Operation};
+use std::collections::HashSet;
+
+/// Remove every node not reachable (backwards) from a `Store`, `ArrayStore`, `LoopNext` or `RegisterStore`
+///
+/// Returns the number of removed nodes.
+pub fn run_dce_pass(graph: &mut Graph) -> usize {
+    let live = find_live_nodes(graph);
+    let before = graph.nodes.len();
+
+    graph.nodes.retain(|node| live.contains(&node.id));
+    graph.value_map.retain(|_, producer| live.contains(producer));
+    for stage in &mut graph.pipeline_stages {
+        stage.operations.retain(|node_id| live.contains(node_id));
+    }
+
+    before - graph.nodes.len()
+}
+
+/// Reachability analysis from the graph's roots (stores and barriers)
+fn find_live_nodes(graph: &Graph) -> HashSet<NodeId> {
+    let mut live = HashSet::new();
+    let mut worklist: Vec<NodeId> = graph.nodes.iter()
+        .filter(|node| matches!(node.op, Operation::Store(_, _) | Operation::ArrayStore(_, _, _) | Operation::LoopNext(_, _) |
+                                    Operation::RegisterStore(_, _) | Operation::PipelineBarrier))
+        .map(|node| node.id)
+        .collect();
+
+    while let Some(node_id) = worklist.pop() {
+        if !live.insert(node_id) {
+            continue;
+        }
+
+        if let Some(node) = graph.nodes.iter().find(|n| n.id == node_id) {
+            for operand in node.op.operands() {
+                if let Some(&producer) = graph.value_map.get(&operand) {
+                    if !live.contains(&producer) {
+                        worklist.push(producer);
+                    }
+                }
+            }
+        }
+    }
+
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dce_removes_dangling_multiply() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let b = graph.add_node_with_output(Operation::Load("b".to_string()));
+        let sum = graph.add_node_with_output(Operation::Add(a, b));
+        let product = graph.add_node_with_output(Operation::Mul(a, b));
+        graph.add_node(Operation::Nop);
+        graph.add_node(Operation::PipelineBarrier);
+        graph.add_node(Operation::Store("result".to_string(), sum));
+
+        let removed = run_dce_pass(&mut graph);
+
+        assert_eq!(removed, 2); // the Mul and the Nop
+        assert!(!graph.value_map.contains_key(&product));
+        assert!(!graph.nodes.iter().any(|n| matches!(n.op, Operation::Mul(_, _) | Operation::Nop)));
+        assert!(graph.nodes.iter().any(|n| matches!(n.op, Operation::Add(_, _))));
+        assert!(graph.nodes.iter().any(|n| matches!(n.op, Operation::Store(_, _))));
+        assert!(graph.nodes.iter().any(|n| matches!(n.op, Operation::PipelineBarrier)));
+    }
+
+    #[test]
+    fn test_dce_keeps_fully_live_graph() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let one = graph.add_node_with_output(Operation::Const(1));
+        let sum = graph.add_node_with_output(Operation::Add(a, one));
+        graph.add_node(Operation::Store("result".to_string(), sum));
+
+        assert_eq!(run_dce_pass(&mut graph), 0);
+        assert_eq!(graph.nodes.len(), 4);
+    }
+}
@@ -0,0 +1,133 @@
+//! Target device resource profiles
+//!
+//! A `DeviceProfile` holds what one FPGA offers a kernel: its LUT, FF, DSP,
+//! BRAM and URAM capacity and the clock kernels usually close at. The
+//! scheduler derives its resource limits from a profile and the resource
+//! report expresses utilization against it. A profile can also cap the
+//! fraction of the device a single kernel may take, leaving room for the
+//! shell and for other kernels.
+
+use crate::passes::resource_estimate::{ResourceEstimate, U50_CAPACITY};
+use serde::Serialize;
+
+/// LUTs of a 32-bit adder, as `resource_estimate` costs it
+const ADDER_LUTS: u32 = 32 / 6;
+
+/// LUTs of a 32-bit array divider, as `resource_estimate` costs it
+const DIVIDER_LUTS: u32 = 32 * 32 / 6;
+
+/// Read and write ports of one block RAM
+const MEMORY_PORTS: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceProfile {
+    pub name: String,
+    pub capacity: ResourceEstimate,
+    /// Kernel clock the device usually closes timing at
+    pub default_clock_mhz: f64,
+    /// Largest fraction of `capacity` a single kernel may use, in (0, 1]
+    pub kernel_fraction: f64,
+}
+
+impl DeviceProfile {
+    /// A device with the given capacity, which kernels may use entirely
+    pub fn custom(name: &str, capacity: ResourceEstimate, default_clock_mhz: f64) -> Self {
+        Self { name: name.to_string(), capacity, default_clock_mhz, kernel_fraction: 1.0 }
+    }
+
+    /// AMD Alveo U50 (XCU50) data center card
+    pub fn alveo_u50() -> Self {
+        Self::custom("Alveo U50", U50_CAPACITY, 300.0)
+    }
+
+    /// AMD Alveo U250 (XCU250) data center card
+    pub fn alveo_u250() -> Self {
+        let capacity = ResourceEstimate { luts: 1_728_000, ffs: 3_456_000, dsps: 12_288, brams: 2_688, uram_blocks: 1_280 };
+        Self::custom("Alveo U250", capacity, 300.0)
+    }
+
+    /// Zynq UltraScale+ ZU9EG, as on the ZCU102 board
+    pub fn zynq_zu9eg() -> Self {
+        let capacity = ResourceEstimate { luts: 274_080, ffs: 548_160, dsps: 2_520, brams: 912, uram_blocks: 0 };
+        Self::custom("Zynq UltraScale+ ZU9EG", capacity, 250.0)
+    }
+
+    /// Artix-7 100T, whose DSP48E1 slices count as DSPs
+    pub fn artix7_100t() -> Self {
+        let capacity = ResourceEstimate { luts: 63_400, ffs: 126_800, dsps: 240, brams: 135, uram_blocks: 0 };
+        Self::custom("Artix-7 100T", capacity, 100.0)
+    }
+
+    /// Names `preset` accepts
+    pub const PRESETS: [&'static str; 4] = ["alveo_u50", "alveo_u250", "zynq_zu9eg", "artix7_100t"];
+
+    /// The built-in profile called `name`, one of `PRESETS`
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "alveo_u50" => Some(Self::alveo_u50()),
+            "alveo_u250" => Some(Self::alveo_u250()),
+            "zynq_zu9eg" => Some(Self::zynq_zu9eg()),
+            "artix7_100t" => Some(Self::artix7_100t()),
+            _ => None,
+        }
+    }
+
+    /// Let a single kernel use at most `fraction` of every resource
+    pub fn with_kernel_fraction(mut self, fraction: f64) -> Self {
+        assert!(fraction > 0.0 && fraction <= 1.0, "a kernel fraction is in (0, 1], not {}", fraction);
+        self.kernel_fraction = fraction;
+        self
+    }
+
+    /// The resources a single kernel may use, `capacity` scaled by `kernel_fraction`
+    pub fn budget(&self) -> ResourceEstimate {
+        let scale = |available: u32| (available as f64 * self.kernel_fraction).floor() as u32;
+        ResourceEstimate {
+            luts: scale(self.capacity.luts),
+            ffs: scale(self.capacity.ffs),
+            dsps: scale(self.capacity.dsps),
+            brams: scale(self.capacity.brams),
+            uram_blocks: scale(self.capacity.uram_blocks),
+        }
+    }
+
+    /// Scheduler limits per resource type within `budget`: a DSP per
+    /// multiplier and the 32-bit adders and dividers that fit in the LUTs
+    pub fn resource_constraints(&self) -> Vec<(&'static str, usize)> {
+        let budget = self.budget();
+        vec![
+            ("adder", (budget.luts / ADDER_LUTS) as usize),
+            ("multiplier", budget.dsps as usize),
+            ("divider", (budget.luts / DIVIDER_LUTS) as usize),
+            ("memory", MEMORY_PORTS),
+        ]
+    }
+
+    /// `default_clock_mhz` as a period
+    pub fn clock_period_ns(&self) -> f64 {
+        1000.0 / self.default_clock_mhz
+    }
+}
+
+impl Default for DeviceProfile {
+    fn default() -> Self {
+        Self::alveo_u50()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_fraction_scales_the_budget_and_the_limits() {
+        let u50 = DeviceProfile::alveo_u50();
+        assert_eq!(u50.budget(), U50_CAPACITY);
+        assert!(u50.resource_constraints().contains(&("multiplier", 5_952)));
+
+        let quarter = DeviceProfile::artix7_100t().with_kernel_fraction(0.25);
+        assert_eq!((quarter.budget().dsps, quarter.budget().brams), (60, 33));
+        assert_eq!(quarter.resource_constraints(), [("adder", 3_170), ("multiplier", 60), ("divider", 93), ("memory", 2)]);
+        assert!((quarter.clock_period_ns() - 10.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,132 @@
+//! Multiply-add fusion for DSP48E2 slices
+//!
+//! The DSP48E2 computes `P = A*B + C` natively, so an `Add` fed by a `Mul`
+//! that nothing else reads is folded into one `Fma` node and mapped onto a
+//! single slice, post-adder included.
+
+use crate::ir::graph::{Graph, NodeId, Operation, ValueId};
+use std::collections::{HashMap, HashSet};
+
+/// Fuse `Add(Mul(a, b), c)` (either operand order) into `Fma(a, b, c)`
+///
+/// Only products with exactly one consumer and full (untruncated) width are
+/// fused, so the result is bit-identical. Returns the number of fused pairs.
+pub fn run_dsp_fusion_pass(graph: &mut Graph) -> usize {
+    let mut uses: HashMap<ValueId, usize> = HashMap::new();
+    for node in &graph.nodes {
+        for operand in node.op.operands() {
+            *uses.entry(operand).or_default() += 1;
+        }
+    }
+
+    let mut removed: HashSet<NodeId> = HashSet::new();
+    for index in 0..graph.nodes.len() {
+        let Operation::Add(left, right) = graph.nodes[index].op else { continue };
+        let signed = graph.nodes[index].signed;
+
+        // Prefer the left product; an already-fused product is no longer a Mul
+        let fusion = [(left, right), (right, left)].into_iter().find_map(|(product, addend)| {
+            let (a, b) = fusable_product(graph, product, signed, &uses, &removed)?;
+            Some((product, a, b, addend))
+        });
+
+        if let Some((product, a, b, addend)) = fusion {
+            removed.insert(graph.value_map[&product]);
+            graph.nodes[index].op = Operation::Fma(a, b, addend);
+        }
+    }
+
+    graph.nodes.retain(|node| !removed.contains(&node.id));
+    graph.value_map.retain(|_, producer| !removed.contains(producer));
+    for stage in &mut graph.pipeline_stages {
+        stage.operations.retain(|node_id| !removed.contains(node_id));
+    }
+
+    removed.len()
+}
+
+/// Operands of `value` if it is a single-use, full-width `Mul` of matching signedness
+fn fusable_product(
+    graph: &Graph,
+    value: ValueId,
+    signed: bool,
+    uses: &HashMap<ValueId, usize>,
+    removed: &HashSet<NodeId>,
+) -> Option<(ValueId, ValueId)> {
+    let node = graph.producer(value)?;
+    let Operation::Mul(a, b) = node.op else { return None };
+
+    let single_use = uses.get(&value) == Some(&1) && !removed.contains(&node.id);
+    let full_width = node.output_width == graph.infer_width(&node.op);
+    (single_use && full_width && node.signed == signed).then_some((a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+    use crate::backend::verilog::generate_verilog_module;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+    use crate::passes::pipeline::PipelineScheduler;
+
+    fn count(graph: &Graph, pred: fn(&Operation) -> bool) -> usize {
+        graph.nodes.iter().filter(|n| pred(&n.op)).count()
+    }
+
+    fn simulate(graph: &Graph) -> i64 {
+        let mut sim = Simulator::new();
+        for (name, value) in [("a", 3), ("b", 5), ("c", 7), ("d", 11), ("e", 13)] {
+            sim.set_input(name, value, graph);
+        }
+        sim.simulate(graph).unwrap()["result"]
+    }
+
+    #[test]
+    fn test_mac_chain_fuses_into_dsp_slices() {
+        let [a, b, c, d, e] = ["a", "b", "c", "d", "e"].map(|name| input(name, 16));
+        let mut graph = lower_expr_to_graph(&output("result", add(mul(a, b), add(mul(c, d), e))));
+        let scheduler = PipelineScheduler::new();
+        let before = scheduler.estimate_resources(&graph);
+
+        assert_eq!(run_dsp_fusion_pass(&mut graph), 2);
+
+        assert_eq!(count(&graph, |op| matches!(op, Operation::Fma(_, _, _))), 2);
+        assert_eq!(count(&graph, |op| matches!(op, Operation::Mul(_, _) | Operation::Add(_, _))), 0);
+        let after = scheduler.estimate_resources(&graph);
+        assert_eq!(after.get("multiplier"), before.get("multiplier"));
+        assert_eq!(after.get("adder"), None);
+        assert_eq!(simulate(&graph), 3 * 5 + 7 * 11 + 13);
+    }
+
+    #[test]
+    fn test_left_associated_mac_keeps_one_add() {
+        let [a, b, c, d, e] = ["a", "b", "c", "d", "e"].map(|name| input(name, 16));
+        let mut graph = lower_expr_to_graph(&output("result", add(add(mul(a, b), mul(c, d)), e)));
+
+        assert_eq!(run_dsp_fusion_pass(&mut graph), 1);
+
+        assert_eq!(count(&graph, |op| matches!(op, Operation::Fma(_, _, _))), 1);
+        assert_eq!(count(&graph, |op| matches!(op, Operation::Add(_, _))), 1);
+        assert_eq!(simulate(&graph), 3 * 5 + 7 * 11 + 13);
+    }
+
+    #[test]
+    fn test_shared_product_is_not_fused() {
+        let product = mul(input("a", 8), input("b", 8));
+        let expr = output("result", let_in("p", product, add(add(var("p"), input("c", 8)), var("p"))));
+        let mut graph = lower_expr_to_graph(&expr);
+
+        assert_eq!(run_dsp_fusion_pass(&mut graph), 0);
+    }
+
+    #[test]
+    fn test_fma_emits_dsp_attribute() {
+        let mut graph = lower_expr_to_graph(&output("result", add(mul(input("a", 16), input("b", 16)), input("c", 32))));
+        run_dsp_fusion_pass(&mut graph);
+        let verilog = generate_verilog_module(&graph, "fma", None).unwrap();
+
+        assert!(verilog.contains("(* USE_DSP = \"yes\" *) wire [32:0] node_"));
+        assert!(verilog.contains("= a * b + c;"));
+    }
+}
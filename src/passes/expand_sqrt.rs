@@ -0,0 +1,130 @@
+//! Square root expansion for HLS graphs
+//!
+//! A `Sqrt` node becomes a single `isqrt` function in the Verilog backends,
+//! which synthesis has to retime on its own. Expanding it into the
+//! shift-and-subtract iteration instead makes every step a plain graph node,
+//! so the scheduler places, chains and registers them like any other logic.
+//! Newton-Raphson would need a divider per step, so it is not used.
+
+use crate::ir::graph::{Graph, Node, NodeId, Operation, ValueId};
+
+/// Replace every `Sqrt` with one compare-and-subtract step per result bit
+///
+/// Step `i` brings down the next two radicand bits into the remainder and
+/// subtracts `4 * root + 1` from it when that fits, shifting a one into the
+/// root if it did and a zero otherwise. The expansion computes the same
+/// floor square root over the unsigned radicand, and the last step keeps
+/// the `Sqrt` node's output value. Run it before scheduling. Returns the
+/// number of expanded square roots.
+pub fn run_expand_sqrt_pass(graph: &mut Graph) -> usize {
+    let mut expanded = 0;
+    let mut index = 0;
+
+    while index < graph.nodes.len() {
+        if let Operation::Sqrt(mut radicand) = graph.nodes[index].op {
+            let radicand_width = graph.value_width(radicand).unwrap_or(32);
+            let root_width = radicand_width.div_ceil(2);
+            let remainder_width = root_width + 2;
+            // New nodes go in front of the square root, keeping graph order topological
+            let at = &mut index;
+            if graph.value_signed(radicand) {
+                // Read the bits as unsigned, as `Sqrt` does
+                radicand = insert_before(graph, at, Operation::Slice(radicand, radicand_width - 1, 0), radicand_width);
+            }
+            let [zero, one, two, three] = [0, 1, 2, 3].map(|value| insert_const_before(graph, at, value));
+
+            let (mut remainder, mut root) = (zero, zero);
+            for step in (0..root_width).rev() {
+                let shift = insert_const_before(graph, at, 2 * step as i64);
+                let shifted = insert_before(graph, at, Operation::Shr(radicand, shift), radicand_width);
+                let pair = insert_before(graph, at, Operation::And(shifted, three), 2);
+                let brought_down = insert_before(graph, at, Operation::Shl(remainder, two), remainder_width);
+                let current = insert_before(graph, at, Operation::Or(brought_down, pair), remainder_width);
+                let scaled_root = insert_before(graph, at, Operation::Shl(root, two), remainder_width);
+                let trial = insert_before(graph, at, Operation::Or(scaled_root, one), remainder_width);
+                let fits = insert_before(graph, at, Operation::CmpGe(current, trial), 1);
+                let reduced = insert_before(graph, at, Operation::Sub(current, trial), remainder_width);
+                remainder = insert_before(graph, at, Operation::Mux(fits, reduced, current), remainder_width);
+
+                let doubled = insert_before(graph, at, Operation::Shl(root, one), root_width);
+                if step == 0 {
+                    // The square root node itself takes the last root bit
+                    let node = &mut graph.nodes[*at];
+                    node.op = Operation::Or(doubled, fits);
+                    node.output_width = Some(root_width);
+                    node.signed = false;
+                } else {
+                    root = insert_before(graph, at, Operation::Or(doubled, fits), root_width);
+                }
+            }
+            expanded += 1;
+        }
+        index += 1;
+    }
+
+    expanded
+}
+
+/// Insert an unsigned node of `width` bits at `index`, then step past it
+fn insert_before(graph: &mut Graph, index: &mut usize, op: Operation, width: u32) -> ValueId {
+    let output = graph.new_value();
+    let node = Node {
+        id: NodeId(graph.next_node),
+        op,
+        output: Some(output),
+        output_width: Some(width),
+        signed: false,
+        fixed_point: None,
+    };
+
+    graph.next_node += 1;
+    graph.value_map.insert(output, node.id);
+    graph.nodes.insert(*index, node);
+    *index += 1;
+    output
+}
+
+/// `insert_before` for a minimally sized unsigned constant
+fn insert_const_before(graph: &mut Graph, index: &mut usize, value: i64) -> ValueId {
+    insert_before(graph, index, Operation::Const(value), (64 - value.leading_zeros()).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+    use crate::backend::verilog::generate_verilog_module;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+
+    #[test]
+    fn test_expanded_square_root_matches_the_operation() {
+        for (width, signed) in [(8, false), (9, false), (9, true), (32, false)] {
+            let radicand = if signed { sinput("a", width) } else { input("a", width) };
+            let reference = lower_expr_to_graph(&output("root", sqrt(radicand)));
+            let mut expanded = reference.clone();
+            assert_eq!(run_expand_sqrt_pass(&mut expanded), 1);
+            assert!(!expanded.nodes.iter().any(|node| matches!(node.op, Operation::Sqrt(_))));
+            assert!(expanded.type_check().is_ok());
+
+            let max = (1i64 << width) - 1;
+            for value in [0, 1, 2, 3, 4, 99, 100, 143, 144, 255, max - 1, max] {
+                let run = |graph: &Graph| {
+                    let mut sim = Simulator::new();
+                    sim.set_input("a", value, graph);
+                    sim.simulate(graph).unwrap()["root"]
+                };
+                assert_eq!(run(&expanded), run(&reference), "sqrt({}) at {} bits", value & max, width);
+            }
+        }
+    }
+
+    #[test]
+    fn test_expanded_square_root_needs_no_function() {
+        let mut graph = lower_expr_to_graph(&output("root", sqrt(input("a", 16))));
+        run_expand_sqrt_pass(&mut graph);
+        let verilog = generate_verilog_module(&graph, "isqrt16", None).unwrap();
+        assert!(!verilog.contains("isqrt_16"));
+        assert!(verilog.contains("output wire [7:0]  root"));
+    }
+}
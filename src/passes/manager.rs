@@ -0,0 +1,216 @@
+//! Ordered execution of optimization passes
+//!
+//! Passes are registered by name, with `depends_on` constraints between
+//! them, and run in dependency order. Passes that no constraint orders run
+//! in the order they were added, so a flow reads top to bottom and only the
+//! orderings that matter need spelling out.
+
+use crate::error::HlsError;
+use crate::ir::graph::{Graph, Operation};
+use serde::Serialize;
+use std::fmt;
+
+/// What a pass returns: the number of changes it made, possibly behind a `Result`
+pub trait PassOutcome {
+    fn into_changes(self) -> Result<usize, HlsError>;
+}
+
+impl PassOutcome for usize {
+    fn into_changes(self) -> Result<usize, HlsError> {
+        Ok(self)
+    }
+}
+
+impl PassOutcome for Result<usize, HlsError> {
+    fn into_changes(self) -> Result<usize, HlsError> {
+        self
+    }
+}
+
+/// Passes such as scheduling that report no count
+impl PassOutcome for Result<(), HlsError> {
+    fn into_changes(self) -> Result<usize, HlsError> {
+        self.map(|()| 0)
+    }
+}
+
+type PassFn = Box<dyn Fn(&mut Graph) -> Result<usize, HlsError>>;
+
+/// Named passes and the order constraints between them
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<(String, PassFn)>,
+    /// (pass, dependency): `dependency` runs before `pass`
+    dependencies: Vec<(String, String)>,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `pass` as `name`, e.g. `add_pass("dce", run_dce_pass)`
+    pub fn add_pass<F, R>(mut self, name: &str, pass: F) -> Self
+    where
+        F: Fn(&mut Graph) -> R + 'static,
+        R: PassOutcome,
+    {
+        self.passes.push((name.to_string(), Box::new(move |graph| pass(graph).into_changes())));
+        self
+    }
+
+    /// Run `dependency` before `pass`
+    pub fn depends_on(mut self, pass: &str, dependency: &str) -> Self {
+        self.dependencies.push((pass.to_string(), dependency.to_string()));
+        self
+    }
+
+    /// Pass names in the order `run` executes them
+    ///
+    /// Among the passes whose dependencies have all run, the first added
+    /// goes next. Fails with `HlsError::PassOrderError` when a dependency
+    /// names an unregistered pass or the dependencies form a cycle.
+    pub fn order(&self) -> Result<Vec<&str>, HlsError> {
+        let index_of = |name: &str| {
+            self.passes.iter().position(|(pass, _)| pass == name)
+                .ok_or_else(|| HlsError::PassOrderError(format!("no pass named {}", name)))
+        };
+        let edges = self.dependencies.iter()
+            .map(|(pass, dependency)| Ok((index_of(pass)?, index_of(dependency)?)))
+            .collect::<Result<Vec<_>, HlsError>>()?;
+
+        let mut done = vec![false; self.passes.len()];
+        let mut order = Vec::with_capacity(self.passes.len());
+        while order.len() < self.passes.len() {
+            let ready = (0..self.passes.len()).find(|&pass| {
+                !done[pass] && edges.iter().all(|&(later, earlier)| later != pass || done[earlier])
+            });
+            let Some(pass) = ready else {
+                let waiting: Vec<&str> = (0..self.passes.len()).filter(|&pass| !done[pass]).map(|pass| self.passes[pass].0.as_str()).collect();
+                return Err(HlsError::PassOrderError(format!("dependency cycle among {}", waiting.join(", "))));
+            };
+            done[pass] = true;
+            order.push(self.passes[pass].0.as_str());
+        }
+        Ok(order)
+    }
+
+    /// Run every pass in `order`, stopping at the first that fails
+    pub fn run(&self, graph: &mut Graph) -> Result<PassReport, HlsError> {
+        let mut report = PassReport::default();
+        for name in self.order()? {
+            let (_, pass) = self.passes.iter().find(|(pass, _)| pass == name).expect("ordered passes are registered");
+            let (nodes_before, registers_before) = (graph.nodes.len(), pipeline_registers(graph));
+            let changes = pass(graph)?;
+            report.passes.push(PassMetrics {
+                name: name.to_string(),
+                changes,
+                nodes_before,
+                nodes_after: graph.nodes.len(),
+                registers_inserted: pipeline_registers(graph).saturating_sub(registers_before),
+            });
+        }
+        Ok(report)
+    }
+}
+
+fn pipeline_registers(graph: &Graph) -> usize {
+    graph.nodes.iter().filter(|node| matches!(node.op, Operation::PipelineRegister(_))).count()
+}
+
+/// Effect of one pass on the graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PassMetrics {
+    pub name: String,
+    /// The count the pass returned, e.g. folded constants or removed nodes
+    pub changes: usize,
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+    /// `PipelineRegister` nodes added
+    pub registers_inserted: usize,
+}
+
+impl PassMetrics {
+    pub fn nodes_eliminated(&self) -> usize {
+        self.nodes_before.saturating_sub(self.nodes_after)
+    }
+}
+
+/// Metrics of every pass `PassManager::run` executed, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PassReport {
+    pub passes: Vec<PassMetrics>,
+}
+
+impl PassReport {
+    pub fn get(&self, name: &str) -> Option<&PassMetrics> {
+        self.passes.iter().find(|metrics| metrics.name == name)
+    }
+}
+
+impl fmt::Display for PassReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<16} {:>8} {:>8} {:>8} {:>10}", "Pass", "Changes", "Nodes", "Removed", "Registers")?;
+        for metrics in &self.passes {
+            writeln!(
+                f,
+                "{:<16} {:>8} {:>8} {:>8} {:>10}",
+                metrics.name, metrics.changes, metrics.nodes_after, metrics.nodes_eliminated(), metrics.registers_inserted
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+    use crate::passes::const_fold::run_const_fold_pass;
+    use crate::passes::cse::run_cse_pass;
+    use crate::passes::dce::run_dce_pass;
+
+    #[test]
+    fn test_dependencies_override_the_order_passes_were_added_in() {
+        // Added in opposite orders, the constraints still put const_fold before dce and dce before cse
+        let forward = PassManager::new()
+            .add_pass("const_fold", run_const_fold_pass)
+            .add_pass("dce", run_dce_pass)
+            .add_pass("cse", run_cse_pass)
+            .depends_on("dce", "const_fold")
+            .depends_on("cse", "dce");
+        let reversed = PassManager::new()
+            .add_pass("cse", run_cse_pass)
+            .add_pass("dce", run_dce_pass)
+            .add_pass("const_fold", run_const_fold_pass)
+            .depends_on("cse", "dce")
+            .depends_on("dce", "const_fold");
+        assert_eq!(forward.order().unwrap(), ["const_fold", "dce", "cse"]);
+        assert_eq!(reversed.order().unwrap(), ["const_fold", "dce", "cse"]);
+
+        let cyclic = forward.depends_on("const_fold", "cse");
+        assert!(matches!(cyclic.order(), Err(HlsError::PassOrderError(message)) if message == "dependency cycle among const_fold, dce, cse"));
+        let unknown = PassManager::new().add_pass("dce", run_dce_pass).depends_on("dce", "fold");
+        assert!(matches!(unknown.order(), Err(HlsError::PassOrderError(message)) if message == "no pass named fold"));
+    }
+
+    #[test]
+    fn test_run_reports_what_each_pass_changed() {
+        // 3 * 5 folds to one constant, leaving both operands for dce
+        let mut graph = lower_expr_to_graph(&output("result", add(mul(const_val(3, 8), const_val(5, 8)), input("a", 8))));
+        let report = PassManager::new()
+            .add_pass("dce", run_dce_pass)
+            .add_pass("const_fold", run_const_fold_pass)
+            .depends_on("dce", "const_fold")
+            .run(&mut graph)
+            .unwrap();
+
+        let names: Vec<&str> = report.passes.iter().map(|metrics| metrics.name.as_str()).collect();
+        assert_eq!(names, ["const_fold", "dce"]);
+        assert_eq!(report.get("const_fold").unwrap().changes, 1);
+        let dce = report.get("dce").unwrap();
+        assert_eq!((dce.nodes_eliminated(), dce.nodes_after), (2, 4));
+        assert!(report.to_string().starts_with("Pass "));
+    }
+}
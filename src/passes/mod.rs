@@ -1 +1,15 @@
-pub mod pipeline;
\ No newline at end of file
+pub mod pipeline;
+pub mod dce;
+pub mod cse;
+pub mod const_fold;
+pub mod strength_reduce;
+pub mod expand_sqrt;
+pub mod dsp_fusion;
+pub mod resource_estimate;
+pub mod device;
+pub mod timing;
+pub mod retiming;
+pub mod binding;
+pub mod modulo_sched;
+pub mod report;
+pub mod manager;
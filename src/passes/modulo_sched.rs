@@ -0,0 +1,371 @@
+//! Iterative modulo scheduling (software pipelining)
+//!
+//! A pipelined loop kernel starts a new iteration every II cycles, so an
+//! operation scheduled in cycle t of one iteration runs alongside cycle
+//! t + II of the previous one. Rau's iterative modulo scheduler places every
+//! operation into a modulo reservation table (MRT) indexed by `cycle mod II`,
+//! so no resource is ever used by more overlapping iterations than it has
+//! units. When an operation finds every slot of its resource taken, it
+//! evicts an operation already placed there, and that one is rescheduled.
+//!
+//! Loop-carried values (state registers and rolled-loop carries) are
+//! recurrences: the next iteration reads them II cycles later, so the update
+//! must be committed by then. The schedule is the kernel: after a prologue
+//! fills the pipeline, every II cycles repeat it with each stage working on
+//! a different iteration, and an epilogue drains it after the last start.
+
+use crate::error::HlsError;
+use crate::ir::graph::{Graph, NodeId, Operation};
+use crate::passes::pipeline::PipelineScheduler;
+use std::collections::{BTreeSet, HashMap};
+
+/// Placements tried per node before the scheduler gives up on an II
+const BUDGET_PER_NODE: usize = 8;
+
+/// Modulo scheduler for a fixed initiation interval
+pub struct ModuloScheduler {
+    pub ii: usize,
+    /// Resource type -> unit count, as for `PipelineScheduler`
+    pub resource_constraints: HashMap<String, usize>,
+}
+
+/// Kernel of a modulo-scheduled loop
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuloSchedule {
+    pub ii: usize,
+    /// Cycle within one iteration that each node starts in
+    pub kernel_schedule: HashMap<NodeId, usize>,
+    /// Stages filled before the first iteration completes
+    pub prologue_stages: usize,
+    /// Stages drained after the last iteration starts
+    pub epilogue_stages: usize,
+}
+
+impl ModuloSchedule {
+    /// Iterations in flight at once in steady state
+    pub fn stage_count(&self) -> usize {
+        self.prologue_stages + 1
+    }
+
+    /// Kernel stage a node runs in, `cycle / II`
+    pub fn kernel_stage(&self, node_id: NodeId) -> Option<usize> {
+        self.kernel_schedule.get(&node_id).map(|cycle| cycle / self.ii)
+    }
+
+    /// Row of the reservation table a node occupies, `cycle mod II`
+    pub fn slot(&self, node_id: NodeId) -> Option<usize> {
+        self.kernel_schedule.get(&node_id).map(|cycle| cycle % self.ii)
+    }
+}
+
+/// Dependence from one node to another, by index into `graph.nodes`
+///
+/// The consumer starts at least `latency - II * distance` cycles after the
+/// producer, where `distance` is how many iterations later it reads.
+#[derive(Debug, Clone, Copy)]
+struct Dependence {
+    from: usize,
+    to: usize,
+    latency: i64,
+    distance: i64,
+}
+
+impl ModuloScheduler {
+    /// Scheduler for `ii` with the default Alveo U50 resource limits
+    pub fn new(ii: usize) -> Self {
+        Self { ii, resource_constraints: PipelineScheduler::new().resource_constraints }
+    }
+
+    /// Modulo-schedule `graph` at `self.ii`, then build its pipeline stages and registers
+    ///
+    /// Operations are not chained, except that a register update commits the
+    /// single-cycle result computed in its own cycle. Fails when the
+    /// resources or a recurrence need a larger II.
+    pub fn schedule(&self, graph: &mut Graph) -> Result<ModuloSchedule, HlsError> {
+        let scheduler = PipelineScheduler { resource_constraints: self.resource_constraints.clone(), ..PipelineScheduler::new() };
+        self.schedule_with(graph, &scheduler)
+    }
+
+    /// `schedule`, with the operation latencies and resource limits of `scheduler`
+    pub(crate) fn schedule_with(&self, graph: &mut Graph, scheduler: &PipelineScheduler) -> Result<ModuloSchedule, HlsError> {
+        graph.validate()?;
+        let ii = self.ii.max(1);
+        let min_ii = scheduler.min_initiation_interval(graph)?;
+        if min_ii > ii {
+            return Err(HlsError::SchedulingError(format!(
+                "II={} cannot be met with the available resources; the graph needs II >= {}", ii, min_ii
+            )));
+        }
+        let dependences = dependences(graph, scheduler);
+        let (rec_mii, bottleneck_path) = recurrence_bound(graph, &dependences);
+        if rec_mii > ii {
+            return Err(HlsError::IINotAchievable { requested: ii, achievable: rec_mii, bottleneck_path });
+        }
+
+        let cycles = self.place(graph, scheduler, &dependences, ii)?;
+        let mut schedule: HashMap<NodeId, usize> = graph.nodes.iter().zip(&cycles).map(|(node, &cycle)| (node.id, cycle)).collect();
+        let length = graph.nodes.iter()
+            .map(|node| schedule[&node.id] + scheduler.operation_latency(graph, &node.op).max(1))
+            .max()
+            .unwrap_or(1);
+        let stages = length.div_ceil(ii);
+
+        graph.pipeline_config.enable = true;
+        graph.pipeline_config.initiation_interval = ii;
+        scheduler.insert_pipeline_registers(graph, &mut schedule)?;
+        graph.pipeline_stages = scheduler.generate_pipeline_stages(&schedule, graph);
+        Ok(ModuloSchedule { ii, kernel_schedule: schedule, prologue_stages: stages - 1, epilogue_stages: stages - 1 })
+    }
+
+    /// Iterative modulo scheduling: the start cycle of every node, indexed like `graph.nodes`
+    ///
+    /// Nodes are taken in graph (ASAP) order. Each goes into the first of the
+    /// II cycles from its earliest start with a free unit; if there is none,
+    /// it takes one from the node holding it. Scheduled consumers whose
+    /// dependence the placement breaks are unscheduled too.
+    fn place(&self, graph: &Graph, scheduler: &PipelineScheduler, dependences: &[Dependence], ii: usize) -> Result<Vec<usize>, HlsError> {
+        let count = graph.nodes.len();
+        let resources: Vec<Option<(String, usize)>> = graph.nodes.iter()
+            .map(|node| scheduler.constrained_resource(graph, &node.op))
+            .collect();
+        let mut cycles: Vec<Option<i64>> = vec![None; count];
+        let mut last_tried: Vec<Option<i64>> = vec![None; count];
+        // (cycle mod II, resource) -> nodes using one of its units
+        let mut table: HashMap<(usize, String), Vec<usize>> = HashMap::new();
+        let mut unscheduled: BTreeSet<usize> = (0..count).collect();
+        let ii_cycles = ii as i64;
+
+        let mut budget = count * BUDGET_PER_NODE;
+        while let Some(node) = unscheduled.pop_first() {
+            if budget == 0 {
+                return Err(HlsError::SchedulingError(format!("no modulo schedule found at II={}", ii)));
+            }
+            budget -= 1;
+
+            let earliest = dependences.iter()
+                .filter(|dependence| dependence.to == node)
+                .filter_map(|dependence| Some(cycles[dependence.from]? + dependence.latency - ii_cycles * dependence.distance))
+                .fold(0, i64::max);
+            let row = |cycle: i64| cycle.rem_euclid(ii_cycles) as usize;
+            let free = |cycle: i64| match &resources[node] {
+                Some((resource, limit)) => table.get(&(row(cycle), resource.clone())).map_or(0, Vec::len) < *limit,
+                None => true,
+            };
+            let cycle = (earliest..earliest + ii_cycles).find(|&cycle| free(cycle)).unwrap_or(match last_tried[node] {
+                Some(previous) if previous >= earliest => previous + 1,
+                _ => earliest,
+            });
+
+            let mut evict = |victim: usize, cycles: &mut Vec<Option<i64>>, table: &mut HashMap<(usize, String), Vec<usize>>| {
+                if let (Some(victim_cycle), Some((resource, _))) = (cycles[victim].take(), &resources[victim]) {
+                    if let Some(users) = table.get_mut(&(row(victim_cycle), resource.clone())) {
+                        users.retain(|&user| user != victim);
+                    }
+                }
+                unscheduled.insert(victim);
+            };
+            if let Some((resource, limit)) = &resources[node] {
+                let users = table.get(&(row(cycle), resource.clone())).cloned().unwrap_or_default();
+                for &victim in users.iter().take((users.len() + 1).saturating_sub(*limit)) {
+                    evict(victim, &mut cycles, &mut table);
+                }
+            }
+            for dependence in dependences.iter().filter(|dependence| dependence.from == node) {
+                let broken = cycles[dependence.to]
+                    .is_some_and(|start| start < cycle + dependence.latency - ii_cycles * dependence.distance);
+                if broken {
+                    evict(dependence.to, &mut cycles, &mut table);
+                }
+            }
+
+            cycles[node] = Some(cycle);
+            last_tried[node] = Some(cycle);
+            if let Some((resource, _)) = &resources[node] {
+                table.entry((row(cycle), resource.clone())).or_default().push(node);
+            }
+        }
+
+        let first = cycles.iter().flatten().copied().min().unwrap_or(0);
+        Ok(cycles.into_iter().map(|cycle| (cycle.expect("every node is placed") - first) as usize).collect())
+    }
+}
+
+/// Data dependences, plus a distance-1 dependence from every register update to the reads it feeds
+///
+/// A register update commits the value computed in its cycle, so it can
+/// share that cycle with the single-cycle operation producing it.
+fn dependences(graph: &Graph, scheduler: &PipelineScheduler) -> Vec<Dependence> {
+    let index: HashMap<NodeId, usize> = graph.nodes.iter().enumerate().map(|(index, node)| (node.id, index)).collect();
+    let latency = |node: usize| scheduler.operation_latency(graph, &graph.nodes[node].op) as i64;
+    let updates = |op: &Operation| matches!(op, Operation::RegisterStore(_, _) | Operation::LoopNext(_, _));
+    let mut dependences = Vec::new();
+    for (to, node) in graph.nodes.iter().enumerate() {
+        for operand in node.op.operands() {
+            let Some(&from) = graph.value_map.get(&operand).and_then(|producer| index.get(producer)) else { continue };
+            let chained = updates(&node.op) && scheduler.chains(graph, &graph.nodes[from].op);
+            dependences.push(Dependence { from, to, latency: if chained { 0 } else { latency(from) }, distance: 0 });
+        }
+    }
+    for (from, update) in graph.nodes.iter().enumerate() {
+        for (to, read) in graph.nodes.iter().enumerate() {
+            let carried = match (&update.op, &read.op) {
+                (Operation::RegisterStore(written, _), Operation::RegisterLoad(name)) => written == name,
+                (Operation::LoopNext(written, _), Operation::LoopCarry(name, _)) => written == name,
+                _ => false,
+            };
+            if carried {
+                dependences.push(Dependence { from, to, latency: latency(from), distance: 1 });
+            }
+        }
+    }
+    dependences
+}
+
+/// Smallest II every recurrence allows (RecMII), and the nodes of the one that needs the most
+///
+/// Every cycle of the dependence graph runs through a register update, so
+/// an II is feasible when no cycle is longer than II times its distance.
+/// The longest path is relaxed over the graph, which is in topological
+/// order apart from the carried dependences, and a positive cycle shows up
+/// as a path still growing after as many rounds as there are nodes.
+fn recurrence_bound(graph: &Graph, dependences: &[Dependence]) -> (usize, Vec<NodeId>) {
+    let feasible = |ii: i64| {
+        let mut start = vec![0i64; graph.nodes.len()];
+        for _ in 0..=graph.nodes.len() {
+            let mut changed = false;
+            for dependence in dependences {
+                let bound = start[dependence.from] + dependence.latency - ii * dependence.distance;
+                if bound > start[dependence.to] {
+                    start[dependence.to] = bound;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return true;
+            }
+        }
+        false
+    };
+    let longest = dependences.iter().map(|dependence| dependence.latency.max(0)).sum::<i64>() + 1;
+    let Some(rec_mii) = (1..=longest).find(|&ii| feasible(ii)) else { return (1, Vec::new()) };
+    if rec_mii == 1 {
+        return (1, Vec::new());
+    }
+
+    // The carried dependence closing the longest single recurrence
+    let path = dependences.iter()
+        .filter(|carried| carried.distance > 0)
+        .filter_map(|carried| {
+            let (length, path) = longest_path(graph, dependences, carried.to, carried.from)?;
+            Some((length + carried.latency, path))
+        })
+        .max_by_key(|(length, _)| *length)
+        .map(|(_, path)| path.into_iter().map(|node| graph.nodes[node].id).collect())
+        .unwrap_or_default();
+    (rec_mii as usize, path)
+}
+
+/// Longest same-iteration dependence path from node `from` to node `to`, if any
+fn longest_path(graph: &Graph, dependences: &[Dependence], from: usize, to: usize) -> Option<(i64, Vec<usize>)> {
+    let mut best: Vec<Option<(i64, usize)>> = vec![None; graph.nodes.len()];
+    best[from] = Some((0, from));
+    for node in 0..graph.nodes.len() {
+        let Some((length, _)) = best[node] else { continue };
+        for dependence in dependences.iter().filter(|dependence| dependence.from == node && dependence.distance == 0) {
+            let candidate = length + dependence.latency;
+            if best[dependence.to].is_none_or(|(current, _)| candidate > current) {
+                best[dependence.to] = Some((candidate, node));
+            }
+        }
+    }
+    let (length, _) = best[to]?;
+    let mut path = vec![to];
+    while *path.last()? != from {
+        path.push(best[*path.last()?]?.1);
+    }
+    path.reverse();
+    Some((length, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::verilog::generate_verilog_module;
+
+    /// acc += a * b + c * d, one iteration per transaction
+    fn accumulator() -> (Graph, Vec<NodeId>) {
+        let mut graph = Graph::new();
+        graph.declare_state_register("acc", Some(32), 0, false);
+        let load = |graph: &mut Graph, name: &str| graph.add_node_with_output_width(Operation::Load(name.to_string()), 16);
+        let (a, b, c, d) = (load(&mut graph, "a"), load(&mut graph, "b"), load(&mut graph, "c"), load(&mut graph, "d"));
+        let ab = graph.add_node_with_output_width(Operation::Mul(a, b), 32);
+        let cd = graph.add_node_with_output_width(Operation::Mul(c, d), 32);
+        let products = graph.add_node_with_output_width(Operation::Add(ab, cd), 32);
+        let acc = graph.add_node_with_output_width(Operation::RegisterLoad("acc".to_string()), 32);
+        let sum = graph.add_node_with_output_width(Operation::Add(acc, products), 32);
+        graph.add_node(Operation::RegisterStore("acc".to_string(), sum));
+        graph.add_node(Operation::Store("acc_out".to_string(), sum));
+        let multipliers = vec![graph.value_map[&ab], graph.value_map[&cd]];
+        (graph, multipliers)
+    }
+
+    #[test]
+    fn test_accumulator_kernel_repeats_every_ii_cycles() {
+        let (mut graph, multipliers) = accumulator();
+        let mut scheduler = ModuloScheduler::new(2);
+        scheduler.resource_constraints.insert("multiplier".to_string(), 1);
+        let schedule = scheduler.schedule(&mut graph).unwrap();
+
+        // The two products share one DSP, so they take different rows of the table
+        assert_ne!(schedule.slot(multipliers[0]), schedule.slot(multipliers[1]));
+        // Port loads, the delayed second multiply, both adds and the output: nine cycles in five kernel stages
+        assert_eq!((schedule.prologue_stages, schedule.epilogue_stages), (4, 4));
+        assert_eq!(graph.pipeline_config.initiation_interval, 2);
+
+        // Overlapping iterations started every II cycles never need a second multiplier
+        let starts: Vec<usize> = multipliers.iter().map(|node_id| schedule.kernel_schedule[node_id]).collect();
+        let mut busy: HashMap<usize, usize> = HashMap::new();
+        for iteration in 0..8 {
+            for start in &starts {
+                *busy.entry(iteration * schedule.ii + start).or_default() += 1;
+            }
+        }
+        assert!(busy.values().all(|&units| units == 1));
+        // In steady state every window of II cycles issues the same operations
+        let row = |cycle: usize| busy.get(&cycle).copied().unwrap_or(0);
+        assert!((8..12).all(|cycle| row(cycle) == row(cycle + schedule.ii)));
+
+        // The next iteration reads the accumulator II cycles later, after this one's update
+        let acc = graph.nodes.iter().find(|node| matches!(node.op, Operation::RegisterLoad(_))).unwrap().id;
+        let update = graph.nodes.iter().find(|node| matches!(node.op, Operation::RegisterStore(_, _))).unwrap().id;
+        assert!(schedule.kernel_schedule[&acc] + schedule.ii > schedule.kernel_schedule[&update]);
+
+        let verilog = generate_verilog_module(&graph, "mac_acc", None).unwrap();
+        assert!(verilog.contains("    // Stage 2\n    // Kernel stage 1 (mod II=2), slot 0\n"), "{}", verilog);
+        assert!(verilog.contains("    // Stage 5\n    // Kernel stage 2 (mod II=2), slot 1\n"));
+    }
+
+    #[test]
+    fn test_ii_below_the_minimum_is_rejected() {
+        let (graph, _) = accumulator();
+        let mut scheduler = ModuloScheduler::new(1);
+        scheduler.resource_constraints.insert("multiplier".to_string(), 1);
+        assert!(matches!(scheduler.schedule(&mut graph.clone()), Err(HlsError::SchedulingError(_))));
+
+        // acc = acc * x: the next read waits for the three-cycle multiply
+        let mut graph = Graph::new();
+        graph.declare_state_register("acc", Some(16), 1, false);
+        let x = graph.add_node_with_output_width(Operation::Load("x".to_string()), 16);
+        let acc = graph.add_node_with_output_width(Operation::RegisterLoad("acc".to_string()), 16);
+        let product = graph.add_node_with_output_width(Operation::Mul(acc, x), 16);
+        let update = graph.add_node(Operation::RegisterStore("acc".to_string(), product));
+        match ModuloScheduler::new(3).schedule(&mut graph.clone()) {
+            Err(HlsError::IINotAchievable { requested, achievable, bottleneck_path }) => {
+                assert_eq!((requested, achievable), (3, 4));
+                assert_eq!(bottleneck_path, vec![graph.value_map[&acc], graph.value_map[&product], update]);
+            }
+            other => panic!("expected IINotAchievable, got {:?}", other),
+        }
+        assert!(ModuloScheduler::new(4).schedule(&mut graph).is_ok());
+    }
+}
@@ -6,36 +6,78 @@
 //! - Pipeline register insertion
 //! - Initiation interval optimization
 
-use crate::ir::graph::{Graph, NodeId, Operation, PipelineStage};
-use std::collections::{HashMap, VecDeque};
+use crate::error::HlsError;
+use crate::ir::graph::{ArrayKind, Graph, Node, NodeId, Operation, PipelineStage};
+use crate::passes::device::DeviceProfile;
+use crate::passes::modulo_sched::ModuloScheduler;
+use crate::passes::report::ResourceReport;
+use crate::passes::timing::ROUTING_OVERHEAD;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 
 /// Pipeline scheduler for HLS operations
+#[derive(Debug, Clone)]
 pub struct PipelineScheduler {
     pub max_stages: usize,
     pub resource_constraints: HashMap<String, usize>, // Resource type -> max count
+    /// Multiply latency override, e.g. `VerilogEmitOptions::mul_latency` for explicit DSP48E2s
+    pub mul_latency: Option<usize>,
+    /// Square root latency override, in place of `SQRT_LATENCY`
+    pub sqrt_latency: Option<usize>,
+    /// Raise `initiation_interval` to the smallest one the resources allow instead of failing
+    pub auto_ii: bool,
+    /// Chain dependent single-cycle operations into one stage while their
+    /// combined delay fits in `clock_period_ns`, less routing
+    pub chaining: bool,
+    /// Target clock period for chaining
+    pub clock_period_ns: f64,
+    /// Device the limits were derived from, for utilization in reports
+    pub device: DeviceProfile,
+}
+
+impl Default for PipelineScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PipelineScheduler {
+    /// Scheduler for the whole of an AMD Alveo U50
     pub fn new() -> Self {
-        let mut resource_constraints = HashMap::new();
-        // AMD Alveo U50 resource constraints (approximate)
-        resource_constraints.insert("adder".to_string(), 100);
-        resource_constraints.insert("multiplier".to_string(), 12); // DSP48E2 slices
-        resource_constraints.insert("divider".to_string(), 4);
-        resource_constraints.insert("memory".to_string(), 8);
-        
+        Self::with_device(DeviceProfile::alveo_u50())
+    }
+
+    /// Scheduler whose resource limits and clock come from `device`, within its kernel budget
+    pub fn with_device(device: DeviceProfile) -> Self {
+        let resource_constraints = device.resource_constraints().into_iter()
+            .map(|(resource, limit)| (resource.to_string(), limit))
+            .collect();
         Self {
             max_stages: 16, // Reasonable pipeline depth
             resource_constraints,
+            mul_latency: None,
+            sqrt_latency: None,
+            auto_ii: false,
+            chaining: true,
+            clock_period_ns: device.clock_period_ns(),
+            device,
         }
     }
 
-    /// Schedule operations into pipeline stages using ASAP scheduling
-    pub fn schedule_pipeline(&mut self, graph: &mut Graph) -> Result<(), String> {
+    /// Schedule operations into pipeline stages by resource-constrained list scheduling
+    ///
+    /// The body of a rolled loop is modulo-scheduled by `ModuloScheduler` instead.
+    pub fn schedule_pipeline(&mut self, graph: &mut Graph) -> Result<(), HlsError> {
         if !graph.pipeline_config.enable {
             return Ok(()); // No pipelining requested
         }
 
+        // Units are bound to the old schedule
+        graph.functional_units.clear();
+        if graph.loop_trip_count.is_some() {
+            return self.schedule_loop(graph);
+        }
+        graph.validate()?;
+
         println!("🔄 Scheduling pipeline with II={}, depth={}", 
                 graph.pipeline_config.initiation_interval,
                 graph.pipeline_config.pipeline_depth);
@@ -49,11 +91,33 @@ impl PipelineScheduler {
         // Step 3: Calculate ALAP (As Late As Possible) schedule  
         let alap_schedule = self.calculate_alap_schedule(graph, &dependencies, &asap_schedule)?;
         
-        // Step 4: Resource-constrained scheduling
-        let final_schedule = self.resource_constrained_schedule(graph, &asap_schedule, &alap_schedule)?;
+        // Step 4: Resource-constrained modulo scheduling at the requested II
+        let requested_ii = graph.pipeline_config.initiation_interval.max(1);
+        let mut ii = match self.min_initiation_interval(graph)? {
+            min_ii if min_ii > requested_ii && self.auto_ii => {
+                println!("⚠️  Raising II from {} to {} to fit the resource limits", requested_ii, min_ii);
+                min_ii
+            }
+            _ => requested_ii,
+        };
+        let mut final_schedule = self.resource_constrained_schedule(graph, &dependencies, &alap_schedule, ii)?;
+        // The next transaction may only read a state register once this one has updated it
+        loop {
+            let (distance, bottleneck_path) = self.recurrence_distance(graph, &mut final_schedule);
+            if distance <= ii {
+                break;
+            }
+            if !self.auto_ii {
+                return Err(HlsError::IINotAchievable { requested: ii, achievable: distance, bottleneck_path });
+            }
+            println!("⚠️  Raising II from {} to {} for the state register dependency", ii, distance);
+            ii = distance;
+            final_schedule = self.resource_constrained_schedule(graph, &dependencies, &alap_schedule, ii)?;
+        }
+        graph.pipeline_config.initiation_interval = ii;
         
         // Step 5: Insert pipeline registers
-        self.insert_pipeline_registers(graph, &final_schedule)?;
+        self.insert_pipeline_registers(graph, &mut final_schedule)?;
         
         // Step 6: Generate pipeline stages
         graph.pipeline_stages = self.generate_pipeline_stages(&final_schedule, graph);
@@ -62,6 +126,102 @@ impl PipelineScheduler {
         Ok(())
     }
 
+    /// Modulo-schedule the body of a rolled loop, so a new iteration starts every II cycles
+    ///
+    /// With `auto_ii` the II is raised until the resources and the loop-carried
+    /// recurrences allow it.
+    fn schedule_loop(&self, graph: &mut Graph) -> Result<(), HlsError> {
+        let requested_ii = graph.pipeline_config.initiation_interval.max(1);
+        let mut ii = match self.min_initiation_interval(graph)? {
+            min_ii if min_ii > requested_ii && self.auto_ii => {
+                println!("⚠️  Raising II from {} to {} to fit the resource limits", requested_ii, min_ii);
+                min_ii
+            }
+            _ => requested_ii,
+        };
+        loop {
+            match ModuloScheduler::new(ii).schedule_with(graph, self) {
+                Ok(schedule) => {
+                    println!("✅ Loop modulo-scheduled at II={} with {} stages in flight", ii, schedule.stage_count());
+                    return Ok(());
+                }
+                Err(HlsError::IINotAchievable { achievable, .. }) if self.auto_ii && achievable > ii => {
+                    println!("⚠️  Raising II from {} to {} for the loop-carried dependency", ii, achievable);
+                    ii = achievable;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// `schedule_pipeline`, then report resources and latency at `target_mhz`
+    pub fn schedule_pipeline_with_report(&mut self, graph: &mut Graph, target_mhz: f64) -> Result<ResourceReport, HlsError> {
+        let requested_ii = graph.pipeline_config.initiation_interval.max(1);
+        self.schedule_pipeline(graph)?;
+        Ok(ResourceReport { requested_ii, ..ResourceReport::new(graph, self, target_mhz) })
+    }
+
+    /// Smallest II at which every constrained resource has enough units:
+    /// each of its operations issues once per II cycles, so n operations
+    /// sharing k units need II >= n / k
+    pub fn min_initiation_interval(&self, graph: &Graph) -> Result<usize, HlsError> {
+        let mut usage: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for node in &graph.nodes {
+            if let Some((resource, limit)) = self.constrained_resource(graph, &node.op) {
+                if limit == 0 {
+                    return Err(HlsError::SchedulingError(format!("node {} needs a {} but none are available", node.id.0, resource)));
+                }
+                usage.entry(resource).or_insert((0, limit)).0 += 1;
+            }
+        }
+        Ok(usage.values()
+            .map(|&(count, limit)| count.div_ceil(limit))
+            .fold(1, usize::max))
+    }
+
+    /// Smallest II the graph's state registers allow under `schedule`, and the recurrence setting it
+    ///
+    /// A register read in cycle `load` and updated at the end of cycle
+    /// `store` is next read `II` cycles later, so `II >= store - load + 1`.
+    /// Reads first move to the cycle of their earliest consumer, to see the
+    /// freshest value. The path runs from the register's read to its update.
+    fn recurrence_distance(&self, graph: &Graph, schedule: &mut HashMap<NodeId, usize>) -> (usize, Vec<NodeId>) {
+        for node in &graph.nodes {
+            let (Operation::RegisterLoad(_), Some(output)) = (&node.op, node.output) else { continue };
+            let first_use = graph.nodes.iter()
+                .filter(|consumer| consumer.op.operands().contains(&output))
+                .filter_map(|consumer| schedule.get(&consumer.id).copied())
+                .min();
+            if let Some(cycle) = first_use {
+                schedule.insert(node.id, cycle);
+            }
+        }
+
+        let scheduled = |matches: &dyn Fn(&Operation) -> bool| graph.nodes.iter()
+            .filter(|node| matches(&node.op))
+            .filter_map(|node| Some((*schedule.get(&node.id)?, node.id)))
+            .collect::<Vec<_>>();
+        graph.state_registers.iter()
+            .filter_map(|register| {
+                let load = scheduled(&|op| matches!(op, Operation::RegisterLoad(name) if *name == register.name))
+                    .into_iter().min_by_key(|&(cycle, _)| cycle)?;
+                let store = scheduled(&|op| matches!(op, Operation::RegisterStore(name, _) if *name == register.name))
+                    .into_iter().max_by_key(|&(cycle, _)| cycle)?;
+                Some(((store.0 + 1).saturating_sub(load.0), load.1, store.1))
+            })
+            .max_by_key(|&(distance, _, _)| distance)
+            .filter(|&(distance, _, _)| distance > 1)
+            .map_or((1, Vec::new()), |(distance, load, store)| (distance, recurrence_path(graph, schedule, load, store)))
+    }
+
+    /// Cycles each node can move without lengthening the schedule (`ALAP - ASAP`)
+    pub fn mobility(&self, graph: &Graph) -> Result<HashMap<NodeId, usize>, HlsError> {
+        let dependencies = self.build_dependency_graph(graph);
+        let asap = self.calculate_asap_schedule(graph, &dependencies)?;
+        let alap = self.calculate_alap_schedule(graph, &dependencies, &asap)?;
+        Ok(asap.iter().map(|(node_id, &start)| (*node_id, alap[node_id] - start)).collect())
+    }
+
     /// Build dependency graph for scheduling
     fn build_dependency_graph(&self, graph: &Graph) -> HashMap<NodeId, Vec<NodeId>> {
         let mut dependencies = HashMap::new();
@@ -70,39 +230,12 @@ impl PipelineScheduler {
             let mut deps = Vec::new();
             
             // Find dependencies based on value usage
-            match &node.op {
-                Operation::Add(a, b) | Operation::Sub(a, b) | Operation::Mul(a, b) | 
-                Operation::Div(a, b) | Operation::And(a, b) | Operation::Or(a, b) |
-                Operation::CmpLt(a, b) | Operation::CmpEq(a, b) => {
-                    if let Some(producer_a) = graph.value_map.get(a) {
-                        deps.push(*producer_a);
-                    }
-                    if let Some(producer_b) = graph.value_map.get(b) {
-                        deps.push(*producer_b);
-                    }
-                }
-                Operation::Not(a) | Operation::PipelineRegister(a) => {
-                    if let Some(producer) = graph.value_map.get(a) {
+            for operand in node.op.operands() {
+                if let Some(producer) = graph.value_map.get(&operand) {
+                    if !deps.contains(producer) {
                         deps.push(*producer);
                     }
                 }
-                Operation::Mux(sel, a, b) => {
-                    if let Some(producer_sel) = graph.value_map.get(sel) {
-                        deps.push(*producer_sel);
-                    }
-                    if let Some(producer_a) = graph.value_map.get(a) {
-                        deps.push(*producer_a);
-                    }
-                    if let Some(producer_b) = graph.value_map.get(b) {
-                        deps.push(*producer_b);
-                    }
-                }
-                Operation::Store(_, val) => {
-                    if let Some(producer) = graph.value_map.get(val) {
-                        deps.push(*producer);
-                    }
-                }
-                _ => {} // No dependencies for Load, Const, etc.
             }
             
             dependencies.insert(node.id, deps);
@@ -113,7 +246,7 @@ impl PipelineScheduler {
 
     /// Calculate ASAP (As Soon As Possible) schedule
     fn calculate_asap_schedule(&self, graph: &Graph, dependencies: &HashMap<NodeId, Vec<NodeId>>) 
-        -> Result<HashMap<NodeId, usize>, String> {
+        -> Result<HashMap<NodeId, usize>, HlsError> {
         let mut schedule = HashMap::new();
         let mut ready_queue = VecDeque::new();
         let mut dependency_count = HashMap::new();
@@ -132,7 +265,7 @@ impl PipelineScheduler {
         // Schedule nodes using topological sort
         while let Some((node_id, earliest_cycle)) = ready_queue.pop_front() {
             let node = graph.nodes.iter().find(|n| n.id == node_id).unwrap();
-            let latency = graph.get_operation_latency(&node.op);
+            let latency = self.operation_latency(graph, &node.op);
             let finish_cycle = earliest_cycle + latency;
             
             schedule.insert(node_id, earliest_cycle);
@@ -152,108 +285,243 @@ impl PipelineScheduler {
             }
         }
         
+        // Validation allows cycles through pipeline registers, but they never become ready
+        if schedule.len() < graph.nodes.len() {
+            let unscheduled = graph.nodes.len() - schedule.len();
+            return Err(HlsError::SchedulingError(format!("{} nodes depend on their own results", unscheduled)));
+        }
+        
         Ok(schedule)
     }
 
     /// Calculate ALAP (As Late As Possible) schedule
-    fn calculate_alap_schedule(&self, graph: &Graph, _dependencies: &HashMap<NodeId, Vec<NodeId>>, 
-                              asap: &HashMap<NodeId, usize>) -> Result<HashMap<NodeId, usize>, String> {
-        // Find critical path length
-        let max_cycle = asap.values().max().copied().unwrap_or(0);
-        let target_cycles = max_cycle.min(graph.pipeline_config.pipeline_depth);
-        
-        let mut schedule = HashMap::new();
-        
-        // Work backwards from target
+    ///
+    /// Sinks finish at the critical-path length; every other node starts
+    /// early enough for its latest-starting consumer. `ALAP - ASAP` is the
+    /// node's mobility.
+    fn calculate_alap_schedule(&self, graph: &Graph, dependencies: &HashMap<NodeId, Vec<NodeId>>,
+                              asap: &HashMap<NodeId, usize>) -> Result<HashMap<NodeId, usize>, HlsError> {
+        let latency = |node: &Node| self.operation_latency(graph, &node.op);
+        let critical_path = graph.nodes.iter().map(|node| asap[&node.id] + latency(node)).max().unwrap_or(0);
+
+        let mut consumers: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
         for node in &graph.nodes {
-            let asap_time = asap.get(&node.id).copied().unwrap_or(0);
-            let slack = target_cycles.saturating_sub(asap_time);
-            let alap_time = asap_time + slack;
-            
-            schedule.insert(node.id, alap_time);
+            for dep in dependencies.get(&node.id).into_iter().flatten() {
+                consumers.entry(*dep).or_default().push(node.id);
+            }
         }
-        
+
+        let mut schedule: HashMap<NodeId, usize> = graph.nodes.iter()
+            .map(|node| (node.id, critical_path - latency(node)))
+            .collect();
+        // Pull producers earlier until every one finishes before its consumers start
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for node in &graph.nodes {
+                let Some(latest_finish) = consumers.get(&node.id).into_iter().flatten().map(|consumer| schedule[consumer]).min() else {
+                    continue;
+                };
+                let alap = latest_finish.saturating_sub(latency(node)).max(asap[&node.id]);
+                if alap < schedule[&node.id] {
+                    schedule.insert(node.id, alap);
+                    changed = true;
+                }
+            }
+        }
+
         Ok(schedule)
     }
 
-    /// Resource-constrained scheduling
-    fn resource_constrained_schedule(&self, graph: &Graph, asap: &HashMap<NodeId, usize>, 
-                                   alap: &HashMap<NodeId, usize>) -> Result<HashMap<NodeId, usize>, String> {
-        let mut final_schedule = HashMap::new();
-        let mut resource_usage: HashMap<usize, HashMap<String, usize>> = HashMap::new();
-        
-        // Sort nodes by mobility (ALAP - ASAP)
-        let mut nodes_by_mobility: Vec<_> = graph.nodes.iter().collect();
-        nodes_by_mobility.sort_by_key(|node| {
-            let asap_time = asap.get(&node.id).copied().unwrap_or(0);
-            let alap_time = alap.get(&node.id).copied().unwrap_or(0);
-            alap_time.saturating_sub(asap_time) // Lower mobility = higher priority
-        });
-        
-        for node in nodes_by_mobility {
-            let asap_time = asap.get(&node.id).copied().unwrap_or(0);
-            let alap_time = alap.get(&node.id).copied().unwrap_or(0);
-            let resource_type = self.get_resource_type(&node.op);
-            
-            // Find earliest feasible slot within [ASAP, ALAP] window
-            let mut scheduled_cycle = asap_time;
-            for cycle in asap_time..=alap_time {
-                let cycle_usage = resource_usage.entry(cycle).or_insert_with(HashMap::new);
-                let current_usage = cycle_usage.get(&resource_type).copied().unwrap_or(0);
-                let max_usage = self.resource_constraints.get(&resource_type).copied().unwrap_or(1);
-                
-                if current_usage < max_usage {
-                    scheduled_cycle = cycle;
-                    cycle_usage.insert(resource_type.clone(), current_usage + 1);
+    /// Resource-constrained modulo list scheduling
+    ///
+    /// Walks the cycles in order, starting every operation whose operands
+    /// are ready. Iterations overlap every `ii` cycles, so an operation in
+    /// cycle c occupies its unit in every cycle congruent to c mod `ii`.
+    /// When more operations compete for a resource than it has units left,
+    /// those with the least slack (`ALAP - cycle`) go first and the rest wait
+    /// for a later cycle.
+    fn resource_constrained_schedule(&self, graph: &Graph, dependencies: &HashMap<NodeId, Vec<NodeId>>,
+                                   alap: &HashMap<NodeId, usize>, ii: usize)
+        -> Result<HashMap<NodeId, usize>, HlsError> {
+        let min_ii = self.min_initiation_interval(graph)?;
+        if min_ii > ii {
+            return Err(HlsError::SchedulingError(format!(
+                "II={} cannot be met with the available resources; the graph needs II >= {}", ii, min_ii
+            )));
+        }
+
+        let nodes: HashMap<NodeId, &Node> = graph.nodes.iter().map(|node| (node.id, node)).collect();
+        let mut final_schedule: HashMap<NodeId, usize> = HashMap::new();
+        let finish = |schedule: &HashMap<NodeId, usize>, node_id: &NodeId| {
+            schedule.get(node_id).map(|&cycle| cycle + self.operation_latency(graph, &nodes[node_id].op))
+        };
+        // Nanoseconds into its cycle at which each chained result settles
+        let mut settled: HashMap<NodeId, f64> = HashMap::new();
+        let budget = self.clock_period_ns * (1.0 - ROUTING_OVERHEAD);
+        let delay = |node: &Node| graph.get_operation_delay_ns(&node.op, node.output_width.unwrap_or(32));
+
+        // Modulo reservation table: (cycle mod II, resource) -> units in use
+        let mut usage: HashMap<(usize, String), usize> = HashMap::new();
+        let mut cycle = 0;
+        while final_schedule.len() < graph.nodes.len() {
+            // Zero-latency results are usable in the cycle they are scheduled, so repeat until nothing changes
+            loop {
+                // A node starts once its operands are registered, or settles within
+                // the cycle behind the single-cycle operations it chains onto
+                let start_ns = |node: &Node| -> Option<f64> {
+                    dependencies[&node.id].iter().try_fold(0.0f64, |start, dep| {
+                        if finish(&final_schedule, dep).is_some_and(|done| done <= cycle) {
+                            return Some(start);
+                        }
+                        let chained = self.chaining && self.chains(graph, &node.op) && self.chains(graph, &nodes[dep].op)
+                            && final_schedule.get(dep) == Some(&cycle);
+                        chained.then(|| start.max(settled[dep]))
+                    }).filter(|start| *start == 0.0 || start + delay(node) <= budget)
+                };
+                let mut ready: Vec<_> = graph.nodes.iter().enumerate()
+                    .filter(|(_, node)| !final_schedule.contains_key(&node.id))
+                    .filter_map(|(index, node)| start_ns(node).map(|start| (index, node, start)))
+                    .collect();
+                ready.sort_by_key(|(index, node, _)| (alap[&node.id].saturating_sub(cycle), *index));
+
+                let mut progress = false;
+                for (_, node, start) in ready {
+                    if let Some((resource, limit)) = self.constrained_resource(graph, &node.op) {
+                        let used = usage.entry((cycle % ii, resource)).or_default();
+                        if *used >= limit {
+                            continue;
+                        }
+                        *used += 1;
+                    }
+                    final_schedule.insert(node.id, cycle);
+                    settled.insert(node.id, start + delay(node));
+                    progress = true;
+                }
+                if !progress {
                     break;
                 }
             }
-            
-            final_schedule.insert(node.id, scheduled_cycle);
+            cycle += 1;
         }
-        
+
         Ok(final_schedule)
     }
 
+    /// Whether an operation is single-cycle logic that can share a stage with its neighbours
+    ///
+    /// Multi-cycle operations, ports, BRAMs and explicit registers always
+    /// start from registered operands.
+    pub(crate) fn chains(&self, graph: &Graph, op: &Operation) -> bool {
+        !matches!(op, Operation::Load(_) | Operation::Const(_) | Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) |
+                      Operation::PipelineRegister(_) | Operation::PipelineBarrier | Operation::Nop)
+            && self.operation_latency(graph, op) == 1
+    }
+
+    /// Resource type and unit count, for operations whose type has a limit
+    ///
+    /// Top-level `Load`s and `Store`s are ports rather than shared memory
+    /// ports, and types without an entry in `resource_constraints` are
+    /// unlimited. Every block RAM has its own `memory` ports, shared by its
+    /// reads and writes; an interface array has one read and one write port.
+    pub(crate) fn constrained_resource(&self, graph: &Graph, op: &Operation) -> Option<(String, usize)> {
+        match op {
+            Operation::Load(_) | Operation::Store(_, _) => return None,
+            Operation::ArrayLoad(name, _) | Operation::ArrayStore(name, _, _) => {
+                let limit = *self.resource_constraints.get("memory")?;
+                return Some(match graph.array(name).map(|array| array.kind) {
+                    Some(ArrayKind::Interface) if matches!(op, Operation::ArrayLoad(_, _)) => (format!("memory:{}:read", name), 1),
+                    Some(ArrayKind::Interface) => (format!("memory:{}:write", name), 1),
+                    _ => (format!("memory:{}", name), limit),
+                });
+            }
+            _ => {}
+        }
+        let resource = self.get_resource_type(op);
+        let limit = *self.resource_constraints.get(&resource)?;
+        Some((resource, limit))
+    }
+
+    /// Latency of an operation, honouring `mul_latency` and `sqrt_latency`
+    pub(crate) fn operation_latency(&self, graph: &Graph, op: &Operation) -> usize {
+        match (op, self.mul_latency, self.sqrt_latency) {
+            (Operation::Mul(_, _) | Operation::SMul(_, _), Some(latency), _) => latency,
+            (Operation::Sqrt(_), _, Some(latency)) => latency,
+            _ => graph.get_operation_latency(op),
+        }
+    }
+
+    /// Count the hardware resources each type of operation in the graph needs
+    pub fn estimate_resources(&self, graph: &Graph) -> HashMap<String, usize> {
+        let mut usage: HashMap<String, usize> = HashMap::new();
+        for node in &graph.nodes {
+            *usage.entry(self.get_resource_type(&node.op)).or_default() += 1;
+        }
+        usage
+    }
+
     /// Get resource type for operation
-    fn get_resource_type(&self, op: &Operation) -> String {
+    pub(crate) fn get_resource_type(&self, op: &Operation) -> String {
         match op {
-            Operation::Add(_, _) | Operation::Sub(_, _) => "adder".to_string(),
-            Operation::Mul(_, _) => "multiplier".to_string(),
+            Operation::Add(_, _) | Operation::Sub(_, _) | Operation::SAdd(_, _) | Operation::SSub(_, _) |
+            Operation::AddSat(_, _, _) | Operation::SubSat(_, _, _) |
+            Operation::Shl(_, _) | Operation::Shr(_, _) => "adder".to_string(),
+            // The DSP48E2 post-adder makes a fused multiply-add a single slice
+            Operation::Mul(_, _) | Operation::SMul(_, _) | Operation::Fma(_, _, _) => "multiplier".to_string(),
             Operation::Div(_, _) => "divider".to_string(),
-            Operation::Load(_) | Operation::Store(_, _) => "memory".to_string(),
+            Operation::Load(_) | Operation::Store(_, _) |
+            Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) => "memory".to_string(),
             _ => "logic".to_string(),
         }
     }
 
-    /// Insert pipeline registers between stages
-    fn insert_pipeline_registers(&self, graph: &mut Graph, schedule: &HashMap<NodeId, usize>) 
-        -> Result<(), String> {
-        let mut registers_to_insert = Vec::new();
-        
-        // Find values that cross stage boundaries
-        for node in &graph.nodes {
-            let node_stage = schedule.get(&node.id).copied().unwrap_or(0);
-            
-            if let Some(output_val) = node.output {
-                // Check all consumers of this value
-                for consumer in &graph.nodes {
-                    let consumer_stage = schedule.get(&consumer.id).copied().unwrap_or(0);
-                    
-                    if consumer_stage > node_stage + 1 {
-                        // Insert pipeline registers for multi-cycle delays
-                        let stages_between = consumer_stage - node_stage - 1;
-                        registers_to_insert.push((output_val, stages_between));
-                    }
-                }
+    /// Insert pipeline registers between stages and rewire consumers to read them
+    ///
+    /// A value is available `latency` cycles after its producer starts; a
+    /// consumer starting `n` cycles after that reads it through `n` registers.
+    /// Each value gets one chain as long as its latest consumer needs, and
+    /// every consumer taps it at its own depth, so consumers in the same stage
+    /// share registers. Registers are scheduled in the cycle they load in.
+    pub(crate) fn insert_pipeline_registers(&self, graph: &mut Graph, schedule: &mut HashMap<NodeId, usize>)
+        -> Result<(), HlsError> {
+        // (value, cycle it is available in, late consumers with their register depth)
+        let mut taps = Vec::new();
+        for producer in &graph.nodes {
+            let (Some(output), Some(&start)) = (producer.output, schedule.get(&producer.id)) else { continue };
+            if matches!(producer.op, Operation::Const(_)) {
+                continue; // Constants are the same in every stage
+            }
+            let available = start + self.operation_latency(graph, &producer.op);
+            let consumers: Vec<(NodeId, usize)> = graph.nodes.iter()
+                .filter(|consumer| consumer.op.operands().contains(&output))
+                .filter_map(|consumer| {
+                    let depth = schedule.get(&consumer.id)?.checked_sub(available)?;
+                    (depth > 0).then_some((consumer.id, depth))
+                })
+                .collect();
+            if !consumers.is_empty() {
+                taps.push((output, available, consumers));
             }
         }
         
-        // Insert the pipeline registers
-        for (value, stages) in registers_to_insert {
+        for (value, available, consumers) in taps {
+            let longest = consumers.iter().map(|&(_, depth)| depth).max().unwrap_or(0);
+            let mut chain = Vec::with_capacity(longest);
             let mut current_value = value;
-            for _ in 0..stages {
+            for stage in 0..longest {
                 current_value = graph.insert_pipeline_register(current_value);
+                let register = graph.value_map[&current_value];
+                schedule.insert(register, available + stage);
+                chain.push(current_value);
+            }
+            for (consumer_id, depth) in consumers {
+                let consumer = graph.nodes.iter_mut().find(|node| node.id == consumer_id)
+                    .ok_or_else(|| HlsError::SchedulingError(format!("node {} disappeared while registering its operands", consumer_id.0)))?;
+                for operand in consumer.op.operands_mut() {
+                    if *operand == value {
+                        *operand = chain[depth - 1];
+                    }
+                }
             }
         }
         
@@ -261,26 +529,302 @@ impl PipelineScheduler {
     }
 
     /// Generate pipeline stages from schedule
-    fn generate_pipeline_stages(&self, schedule: &HashMap<NodeId, usize>, _graph: &Graph) -> Vec<PipelineStage> {
-        let mut stages = HashMap::new();
+    ///
+    /// There is one stage per cycle until the last operation finishes, so the
+    /// cycles a multi-cycle operation is still in flight are reserved as
+    /// stages of their own, possibly with no operation starting in them.
+    pub(crate) fn generate_pipeline_stages(&self, schedule: &HashMap<NodeId, usize>, graph: &Graph) -> Vec<PipelineStage> {
+        let finish = graph.nodes.iter()
+            .filter_map(|node| Some(schedule.get(&node.id)? + self.operation_latency(graph, &node.op).max(1)))
+            .max()
+            .unwrap_or(0);
+        let mut stages: Vec<_> = (0..finish)
+            .map(|cycle| PipelineStage { stage: cycle, cycle, operations: Vec::new() })
+            .collect();
         
-        for (node_id, &cycle) in schedule {
-            let stage = stages.entry(cycle).or_insert_with(|| PipelineStage {
-                stage: cycle,
-                cycle,
-                operations: Vec::new(),
-            });
-            stage.operations.push(*node_id);
+        for node in &graph.nodes {
+            if let Some(&cycle) = schedule.get(&node.id) {
+                stages[cycle].operations.push(node.id);
+            }
         }
-        
-        let mut result: Vec<_> = stages.into_values().collect();
-        result.sort_by_key(|stage| stage.stage);
-        result
+        for stage in &mut stages {
+            stage.operations.sort();
+        }
+        stages
     }
 }
 
 /// Public interface to run pipeline scheduling on a graph
-pub fn run_pipeline_pass(graph: &mut Graph) -> Result<(), String> {
+pub fn run_pipeline_pass(graph: &mut Graph) -> Result<(), HlsError> {
     let mut scheduler = PipelineScheduler::new();
     scheduler.schedule_pipeline(graph)
 }
+
+/// Dataflow path from `load` to `store`, following the latest-scheduled operand back from the store
+///
+/// Falls back to just the two nodes when the update does not depend on the read.
+fn recurrence_path(graph: &Graph, schedule: &HashMap<NodeId, usize>, load: NodeId, store: NodeId) -> Vec<NodeId> {
+    // Nodes reached from the read, in graph (and so topological) order
+    let mut reached = vec![load];
+    for node in &graph.nodes {
+        let depends = node.op.operands().iter()
+            .any(|operand| graph.value_map.get(operand).is_some_and(|producer| reached.contains(producer)));
+        if depends && !reached.contains(&node.id) {
+            reached.push(node.id);
+        }
+    }
+    if !reached.contains(&store) {
+        return vec![load, store];
+    }
+
+    let mut path = vec![store];
+    let mut cursor = store;
+    while cursor != load {
+        let node = graph.nodes.iter().find(|node| node.id == cursor).expect("path nodes are in the graph");
+        cursor = node.op.operands().iter()
+            .filter_map(|operand| graph.value_map.get(operand).copied())
+            .filter(|producer| reached.contains(producer))
+            .max_by_key(|producer| schedule.get(producer).copied().unwrap_or(0))
+            .expect("every reached node but the read has a reached operand");
+        path.push(cursor);
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::passes::resource_estimate::{ResourceEstimate, U50_CAPACITY};
+
+    #[test]
+    fn test_feedback_through_a_register_cannot_be_scheduled() {
+        // acc = a + reg(acc)
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let feedback = graph.add_node_with_output(Operation::PipelineRegister(a));
+        let sum = graph.add_node_with_output(Operation::Add(a, feedback));
+        graph.nodes[1].op = Operation::PipelineRegister(sum);
+        graph.add_node(Operation::Store("acc".to_string(), sum));
+        graph.enable_pipeline(1, 3, 1);
+
+        assert!(graph.validate().is_ok());
+        assert!(matches!(run_pipeline_pass(&mut graph), Err(HlsError::SchedulingError(_))));
+    }
+
+    /// A U50 cut down to 12 DSP48E2s
+    fn twelve_dsps() -> PipelineScheduler {
+        PipelineScheduler::with_device(DeviceProfile::custom("12 DSPs", ResourceEstimate { dsps: 12, ..U50_CAPACITY }, 300.0))
+    }
+
+    #[test]
+    fn test_multiplies_beyond_the_dsp_budget_move_to_a_later_cycle() {
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+        let b = graph.add_node_with_output(Operation::Load("b".to_string()));
+        let products: Vec<_> = (0..14).map(|_| graph.add_node_with_output(Operation::Mul(a, b))).collect();
+        // A slow add chain behind the first product leaves the others slack
+        let mut total = products[0];
+        for &product in &products[1..] {
+            total = graph.add_node_with_output(Operation::Add(total, product));
+        }
+        graph.add_node(Operation::Store("total".to_string(), total));
+        graph.enable_pipeline(2, 16, 1);
+
+        let mut scheduler = twelve_dsps();
+        let mobility = scheduler.mobility(&graph).unwrap();
+        let mul_ids: Vec<NodeId> = graph.nodes.iter().filter(|node| matches!(node.op, Operation::Mul(_, _))).map(|node| node.id).collect();
+        assert_eq!(mobility[&mul_ids[0]], 0);
+        assert_eq!(mobility[&mul_ids[1]], 0);
+        assert_eq!(mobility[&mul_ids[13]], 12);
+
+        scheduler.schedule_pipeline(&mut graph).unwrap();
+        let cycle_of = |node_id: NodeId| graph.pipeline_stages.iter().find(|stage| stage.operations.contains(&node_id)).unwrap().cycle;
+        let cycles: Vec<usize> = mul_ids.iter().map(|&node_id| cycle_of(node_id)).collect();
+        // 12 DSP48E2s: the two multiplies with the most slack wait a cycle
+        assert_eq!(cycles.iter().filter(|&&cycle| cycle == 2).count(), 12);
+        assert_eq!(&cycles[12..], &[3, 3]);
+        assert_eq!(graph.pipeline_config.initiation_interval, 2);
+    }
+
+    #[test]
+    fn test_modulo_resources_decide_the_initiation_interval() {
+        let fourteen_products = |ii: usize| {
+            let mut graph = Graph::new();
+            let a = graph.add_node_with_output(Operation::Load("a".to_string()));
+            let b = graph.add_node_with_output(Operation::Load("b".to_string()));
+            for i in 0..14 {
+                let product = graph.add_node_with_output(Operation::Mul(a, b));
+                graph.add_node(Operation::Store(format!("p{}", i), product));
+            }
+            graph.enable_pipeline(ii, 8, 1);
+            graph
+        };
+
+        // Every iteration needs 14 multiplies, but only 12 DSP48E2s start one per cycle
+        let mut graph = fourteen_products(1);
+        assert_eq!(twelve_dsps().min_initiation_interval(&graph).unwrap(), 2);
+        assert!(matches!(twelve_dsps().schedule_pipeline(&mut graph), Err(HlsError::SchedulingError(_))));
+        // The whole U50 starts them all at once
+        assert_eq!(PipelineScheduler::new().min_initiation_interval(&graph).unwrap(), 1);
+
+        let mut graph = fourteen_products(2);
+        twelve_dsps().schedule_pipeline(&mut graph).unwrap();
+        let per_slot = |slot: usize| graph.nodes.iter()
+            .filter(|node| matches!(node.op, Operation::Mul(_, _)))
+            .filter(|node| graph.pipeline_stages.iter().any(|stage| stage.cycle % 2 == slot && stage.operations.contains(&node.id)))
+            .count();
+        assert_eq!((per_slot(0), per_slot(1)), (12, 2));
+
+        let mut graph = fourteen_products(1);
+        let mut scheduler = PipelineScheduler { auto_ii: true, ..twelve_dsps() };
+        scheduler.schedule_pipeline(&mut graph).unwrap();
+        assert_eq!(graph.pipeline_config.initiation_interval, 2);
+    }
+
+    #[test]
+    fn test_each_array_has_its_own_memory_ports() {
+        let mut graph = Graph::new();
+        graph.declare_array("a", 16, None, ArrayKind::Input);
+        graph.declare_array("b", 16, None, ArrayKind::Input);
+        graph.declare_array("ext", 16, None, ArrayKind::Interface);
+        let i = graph.add_node_with_output(Operation::Load("i".to_string()));
+        for (array, reads) in [("a", 4), ("b", 2), ("ext", 1)] {
+            for _ in 0..reads {
+                let value = graph.add_node_with_output(Operation::ArrayLoad(array.to_string(), i));
+                graph.add_node(Operation::Store(format!("{}{}", array, value.0), value));
+            }
+        }
+        graph.enable_pipeline(1, 8, 1);
+
+        // Four reads of `a` need two cycles of its two ports; `b` and `ext` fit in one
+        let scheduler = PipelineScheduler::new();
+        assert_eq!(scheduler.min_initiation_interval(&graph).unwrap(), 2);
+        graph.add_node(Operation::ArrayStore("ext".to_string(), i, i));
+        graph.add_node(Operation::ArrayLoad("ext".to_string(), i));
+        assert_eq!(scheduler.min_initiation_interval(&graph).unwrap(), 2);
+        graph.add_node(Operation::ArrayLoad("ext".to_string(), i));
+        assert_eq!(scheduler.min_initiation_interval(&graph).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_state_register_dependency_bounds_the_ii() {
+        // acc = acc * x: read in the multiply's cycle, updated three cycles later
+        let mut graph = Graph::new();
+        graph.declare_state_register("acc", Some(16), 1, false);
+        let x = graph.add_node_with_output_width(Operation::Load("x".to_string()), 16);
+        let acc = graph.add_node_with_output_width(Operation::RegisterLoad("acc".to_string()), 16);
+        let product = graph.add_node_with_output_width(Operation::Mul(acc, x), 16);
+        graph.add_node(Operation::RegisterStore("acc".to_string(), product));
+        graph.add_node(Operation::Store("acc_out".to_string(), product));
+        graph.enable_pipeline(1, 8, 1);
+
+        assert!(matches!(run_pipeline_pass(&mut graph.clone()), Err(HlsError::IINotAchievable { requested: 1, achievable: 4, .. })));
+        let mut scheduler = PipelineScheduler { auto_ii: true, ..PipelineScheduler::new() };
+        scheduler.schedule_pipeline(&mut graph).unwrap();
+        assert_eq!(graph.pipeline_config.initiation_interval, 4);
+        let cycle_of = |node_id: NodeId| graph.pipeline_stages.iter().find(|stage| stage.operations.contains(&node_id)).unwrap().cycle;
+        assert_eq!(cycle_of(graph.value_map[&acc]), cycle_of(graph.value_map[&product]));
+    }
+
+    #[test]
+    fn test_three_multiplier_recurrence_misses_ii_1() {
+        // acc = ((acc * x) * y) * z: three DSP latencies between the read and the update
+        let mut graph = Graph::new();
+        graph.declare_state_register("acc", Some(16), 1, false);
+        let inputs: Vec<_> = ["x", "y", "z"].iter()
+            .map(|name| graph.add_node_with_output_width(Operation::Load(name.to_string()), 16))
+            .collect();
+        let acc = graph.add_node_with_output_width(Operation::RegisterLoad("acc".to_string()), 16);
+        let products = inputs.iter().fold(vec![acc], |mut products, &input| {
+            let product = graph.add_node_with_output_width(Operation::Mul(*products.last().unwrap(), input), 16);
+            products.push(product);
+            products
+        });
+        let update = graph.add_node(Operation::RegisterStore("acc".to_string(), products[3]));
+        graph.enable_pipeline(1, 16, 1);
+
+        match run_pipeline_pass(&mut graph) {
+            Err(HlsError::IINotAchievable { requested, achievable, bottleneck_path }) => {
+                assert_eq!((requested, achievable), (1, 10));
+                let mut expected: Vec<NodeId> = products.iter().map(|product| graph.value_map[product]).collect();
+                expected.push(update);
+                assert_eq!(bottleneck_path, expected);
+            }
+            other => panic!("expected IINotAchievable, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_hft_decision_graph_meets_the_ultra_low_latency_preset() {
+        let (ii, depth, _) = crate::backend::pipeline_integration::PipelinePresets::hft_ultra_low_latency();
+        let mut graph = crate::hft::ZeroPlusStrategy::to_fpga_graph();
+        graph.enable_pipeline(ii, depth, 1);
+        run_pipeline_pass(&mut graph).unwrap();
+        assert_eq!(graph.pipeline_config.initiation_interval, 1);
+    }
+
+    #[test]
+    fn test_cross_stage_reads_go_through_registers() {
+        // s = a + b, t = s & a, u = t ^ b and v = t | b, one stage each without chaining
+        let mut graph = Graph::new();
+        let a = graph.add_node_with_output_width(Operation::Load("a".to_string()), 8);
+        let b = graph.add_node_with_output_width(Operation::Load("b".to_string()), 8);
+        let s = graph.add_node_with_output(Operation::Add(a, b));
+        let t = graph.add_node_with_output(Operation::And(s, a));
+        let u = graph.add_node_with_output(Operation::Xor(t, b));
+        let v = graph.add_node_with_output(Operation::Or(t, b));
+        graph.add_node(Operation::Store("u".to_string(), u));
+        graph.add_node(Operation::Store("v".to_string(), v));
+        graph.enable_pipeline(1, 8, 1);
+        PipelineScheduler { chaining: false, ..PipelineScheduler::new() }.schedule_pipeline(&mut graph).unwrap();
+
+        let cycle = crate::passes::timing::schedule_of(&graph);
+        let registers_on = |mut value| {
+            let mut registers = 0;
+            while let Operation::PipelineRegister(inner) = graph.producer(value).unwrap().op {
+                registers += 1;
+                value = inner;
+            }
+            (graph.producer(value).unwrap(), registers)
+        };
+        for consumer in graph.nodes.iter().filter(|node| !matches!(node.op, Operation::PipelineRegister(_))) {
+            for operand in consumer.op.operands() {
+                let (producer, registers) = registers_on(operand);
+                let available = cycle[&producer.id] + graph.get_operation_latency(&producer.op);
+                assert_eq!(registers, cycle[&consumer.id] - available, "{:?} -> {:?}", producer.op, consumer.op);
+            }
+        }
+
+        // a is read one cycle late by t and b two cycles late by both u and v
+        let registers = graph.nodes.iter().filter(|node| matches!(node.op, Operation::PipelineRegister(_))).count();
+        assert_eq!(registers, 3);
+        graph.validate().unwrap();
+        let mut sim = crate::backend::sim::Simulator::new();
+        sim.set_input("a", 6, &graph);
+        sim.set_input("b", 3, &graph);
+        // s = 9, t = 9 & 6 = 0, u = 0 ^ 3
+        assert_eq!(sim.simulate(&graph).unwrap()["u"], 3);
+    }
+
+    #[test]
+    fn test_chaining_packs_the_hft_logic_into_few_stages() {
+        let schedule = |chaining: bool| {
+            let mut graph = crate::hft::ZeroPlusStrategy::to_fpga_graph();
+            graph.enable_pipeline(1, 8, 1);
+            PipelineScheduler { chaining, ..PipelineScheduler::new() }.schedule_pipeline(&mut graph).unwrap();
+            graph.pipeline_stages.len()
+        };
+
+        let (chained, unchained) = (schedule(true), schedule(false));
+        // Two of them are reserved for the input loads
+        assert!((3..=4).contains(&chained), "{} stages", chained);
+        assert!(unchained >= 6, "{} stages", unchained);
+
+        // A shorter clock fits fewer compares per cycle
+        let mut graph = crate::hft::ZeroPlusStrategy::to_fpga_graph();
+        graph.enable_pipeline(1, 8, 1);
+        PipelineScheduler { clock_period_ns: 1.0, ..PipelineScheduler::new() }.schedule_pipeline(&mut graph).unwrap();
+        assert!(graph.pipeline_stages.len() > chained);
+    }
+}
@@ -0,0 +1,210 @@
+//! Resource and latency report for a scheduled graph
+//!
+//! Combines the per-operation estimates of `resource_estimate` with the
+//! schedule: how many stages the pipeline has, how long they take at the
+//! target clock, and which initiation interval the resource limits allow.
+
+use crate::ir::graph::{Graph, Operation};
+use crate::passes::pipeline::PipelineScheduler;
+use crate::passes::resource_estimate::{self, ResourceEstimate};
+use serde::Serialize;
+use std::fmt;
+
+/// Alveo kernel clock, used by `build_report`
+pub const DEFAULT_TARGET_MHZ: f64 = 300.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceReport {
+    /// Width-aware LUT/FF/DSP/BRAM/URAM estimate
+    pub resources: ResourceEstimate,
+    /// Physical multipliers, mapped onto `resources.dsps` slices; multiplies sharing a functional unit count once
+    pub multipliers: usize,
+    /// Adders, subtractors and shifters
+    pub adders: usize,
+    /// `PipelineRegister` nodes that feed another node
+    pub pipeline_registers: usize,
+    /// Declared arrays
+    pub memories: usize,
+    pub stages: usize,
+    pub target_mhz: f64,
+    /// `stages` clock periods at `target_mhz`
+    pub latency_ns: f64,
+    pub requested_ii: usize,
+    /// Requested II, raised where more operations of one type compete for a limited resource
+    pub achieved_ii: usize,
+    /// Name of the scheduler's `DeviceProfile`
+    pub device: String,
+    /// Percentage of the device used, per resource kind
+    pub utilization: Vec<(&'static str, f64)>,
+    /// Whether the estimate stays within the share of the device one kernel may use
+    pub fits_budget: bool,
+}
+
+impl ResourceReport {
+    /// Report on `graph` as scheduled by `scheduler`, at `target_mhz`
+    pub fn new(graph: &Graph, scheduler: &PipelineScheduler, target_mhz: f64) -> Self {
+        let usage = scheduler.estimate_resources(graph);
+        let count = |resource: &str| usage.get(resource).copied().unwrap_or(0);
+
+        // Every operation of a pipelined kernel runs once per II cycles, so a
+        // resource shared by n operations with k units needs II >= n / k
+        let requested_ii = graph.pipeline_config.initiation_interval.max(1);
+        let achieved_ii = scheduler.min_initiation_interval(graph).unwrap_or(requested_ii).max(requested_ii);
+
+        // A shared multiplier is one unit however many multiplies take turns on it
+        let multiplies_sharing_a_unit: usize = graph.functional_units.iter().map(|unit| unit.operations.len().saturating_sub(1)).sum();
+        let used: Vec<_> = graph.nodes.iter().flat_map(|node| node.op.operands()).collect();
+        let pipeline_registers = graph.nodes.iter()
+            .filter(|node| matches!(node.op, Operation::PipelineRegister(_)))
+            .filter(|node| node.output.is_some_and(|output| used.contains(&output)))
+            .count();
+        let stages = graph.pipeline_stages.len();
+
+        let resources = resource_estimate::estimate_resources(graph);
+        Self {
+            utilization: resources.utilization(&scheduler.device.capacity),
+            fits_budget: resources.fits(&scheduler.device.budget()),
+            device: scheduler.device.name.clone(),
+            resources,
+            multipliers: count("multiplier").saturating_sub(multiplies_sharing_a_unit),
+            adders: count("adder"),
+            pipeline_registers,
+            memories: graph.arrays.len(),
+            stages,
+            target_mhz,
+            latency_ns: stages as f64 * 1000.0 / target_mhz,
+            requested_ii,
+            achieved_ii,
+        }
+    }
+
+    /// Machine-readable form of the report
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("reports always serialize to JSON")
+    }
+}
+
+impl fmt::Display for ResourceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "=== HLS RESOURCE REPORT ===")?;
+        writeln!(f, "Stages:   {} ({:.2} ns at {} MHz)", self.stages, self.latency_ns, self.target_mhz)?;
+        writeln!(f, "II:       {} (requested {})", self.achieved_ii, self.requested_ii)?;
+        writeln!(f, "DSP48E2:  {} ({} multipliers)", self.resources.dsps, self.multipliers)?;
+        writeln!(f, "LUT:      {} ({} adders)", self.resources.luts, self.adders)?;
+        writeln!(f, "FF:       {} ({} pipeline registers)", self.resources.ffs, self.pipeline_registers)?;
+        writeln!(f, "BRAM36:   {}, URAM: {} ({} memories)", self.resources.brams, self.resources.uram_blocks, self.memories)?;
+        let utilization: Vec<String> = self.utilization.iter().map(|(name, percent)| format!("{} {:.2}%", name, percent)).collect();
+        write!(f, "Device:   {}: {}", self.device, utilization.join(", "))?;
+        if !self.fits_budget {
+            write!(f, " (over the kernel budget)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Report on an already scheduled graph with the default scheduler limits at 300 MHz
+pub fn build_report(graph: &Graph) -> ResourceReport {
+    ResourceReport::new(graph, &PipelineScheduler::new(), DEFAULT_TARGET_MHZ)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::passes::device::DeviceProfile;
+    use crate::ir::graph::{FunctionalUnit, NodeId, PipelineStage};
+    use crate::ir::lower::lowered_mac;
+    use crate::passes::pipeline::run_pipeline_pass;
+
+    fn mac() -> Graph {
+        let mut graph = lowered_mac();
+        graph.enable_pipeline(1, 4, 1);
+        graph
+    }
+
+    #[test]
+    fn test_pipelined_mac_report() {
+        let mut graph = mac();
+        let report = PipelineScheduler::new().schedule_pipeline_with_report(&mut graph, 250.0).unwrap();
+
+        assert_eq!((report.resources.dsps, report.multipliers, report.adders), (2, 2, 2));
+        // Two cycles of loads, three of multiplies, then the chained adds
+        assert_eq!(report.stages, 6);
+        assert_eq!((report.requested_ii, report.achieved_ii), (1, 1));
+        assert!((report.latency_ns - 24.0).abs() < 1e-9);
+        assert!(report.to_string().contains("Stages:   6 (24.00 ns at 250 MHz)"));
+
+        let json = report.to_json();
+        assert_eq!(json["resources"]["dsps"], 2);
+        assert_eq!(json["stages"], 6);
+        assert_eq!(json["achieved_ii"], 1);
+    }
+
+    #[test]
+    fn test_stale_units_cannot_underflow_the_multiplier_count() {
+        // Units left over from a graph the multiplies have since been folded out of
+        let mut graph = mac();
+        run_pipeline_pass(&mut graph).unwrap();
+        graph.functional_units = vec![FunctionalUnit { operations: (0..5).map(NodeId).collect() }];
+        assert_eq!(build_report(&graph).multipliers, 0);
+    }
+
+    #[test]
+    fn test_shared_multiplier_raises_the_ii() {
+        let mut graph = mac();
+        let mut scheduler = PipelineScheduler { auto_ii: true, ..PipelineScheduler::new() };
+        scheduler.resource_constraints.insert("multiplier".to_string(), 1);
+        let report = scheduler.schedule_pipeline_with_report(&mut graph, DEFAULT_TARGET_MHZ).unwrap();
+
+        assert_eq!(report.achieved_ii, 2);
+        assert!(report.to_string().contains("II:       2 (requested 1)"));
+    }
+
+    #[test]
+    fn test_utilization_is_a_share_of_the_device_profile() {
+        let artix = DeviceProfile::artix7_100t();
+        let report = PipelineScheduler::with_device(artix.clone()).schedule_pipeline_with_report(&mut mac(), 100.0).unwrap();
+        assert_eq!(report.device, "Artix-7 100T");
+        assert_eq!(report.utilization[2].0, "DSP48E2");
+        assert!((report.utilization[2].1 - 100.0 * 2.0 / 240.0).abs() < 1e-9);
+        assert!(report.fits_budget);
+        assert!(report.to_string().contains("Device:   Artix-7 100T: LUT "));
+        assert!(report.to_string().contains("DSP48E2 0.83%"));
+
+        // Two DSPs are over a budget of one, which also keeps the pipeline to one multiplier per cycle
+        let mut scheduler = PipelineScheduler { auto_ii: true, ..PipelineScheduler::with_device(artix.with_kernel_fraction(0.005)) };
+        let report = scheduler.schedule_pipeline_with_report(&mut mac(), 100.0).unwrap();
+        assert!(!report.fits_budget);
+        assert_eq!(report.achieved_ii, 2);
+        assert!(report.to_string().ends_with("(over the kernel budget)"));
+    }
+
+    #[test]
+    fn test_chaining_merges_the_mac_adds_into_one_stage() {
+        let unchained = PipelineScheduler { chaining: false, ..PipelineScheduler::new() }.schedule_pipeline_with_report(&mut mac(), 250.0).unwrap();
+        let mut graph = mac();
+        let chained = PipelineScheduler::new().schedule_pipeline_with_report(&mut graph, 250.0).unwrap();
+
+        // Both adds and the store fit in the cycle after the multiplies
+        assert_eq!(chained.stages, unchained.stages - 2);
+        let last = graph.pipeline_stages.last().unwrap();
+        let ops: Vec<_> = last.operations.iter().map(|id| &graph.nodes.iter().find(|node| node.id == *id).unwrap().op).collect();
+        assert_eq!(ops.iter().filter(|op| matches!(op, Operation::Add(..))).count(), 2);
+        assert!(ops.iter().any(|op| matches!(op, Operation::Store(..))));
+    }
+
+    #[test]
+    fn test_unchained_mac_report_reserves_the_multiply_cycles() {
+        // Without chaining this is the schedule the report was first written
+        // against: 2 DSPs and operations starting in 5 cycles. Each cycle the
+        // 3-cycle multiplies occupy is now a stage of its own
+        let mut graph = mac();
+        let report = PipelineScheduler { chaining: false, ..PipelineScheduler::new() }.schedule_pipeline_with_report(&mut graph, 250.0).unwrap();
+        assert_eq!(report.resources.dsps, 2);
+
+        let starts = |stage: &PipelineStage| stage.operations.iter()
+            .any(|id| graph.nodes.iter().any(|node| node.id == *id && !matches!(node.op, Operation::PipelineRegister(_))));
+        let cycles: Vec<_> = graph.pipeline_stages.iter().filter(|stage| starts(stage)).map(|stage| stage.cycle).collect();
+        assert_eq!(cycles, vec![0, 2, 5, 6, 7]);
+        assert_eq!(report.stages, 8);
+    }
+}
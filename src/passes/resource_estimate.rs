@@ -0,0 +1,275 @@
+//! Pre-synthesis resource estimates for AMD Alveo U50 kernels
+//!
+//! Vivado synthesis of even a small kernel takes tens of minutes, so this pass
+//! sums rough per-operation costs taken from Vivado utilization reports to
+//! tell early whether a design fits. The numbers are estimates: they ignore
+//! cross-operation LUT packing and the control FSM.
+
+use crate::ir::graph::{ArrayKind, Graph, Node, NodeId, Operation, ValueId, DEFAULT_WIDTH};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Bits in one BRAM36 block
+const BRAM36_BITS: u64 = 36 * 1024;
+
+/// Bits in one UltraRAM block
+const URAM_BITS: u64 = 288 * 1024;
+
+/// Arrays this shallow are built from LUTRAM rather than block RAM
+const LUTRAM_MAX_DEPTH: usize = 64;
+
+/// Arrays needing more BRAM36 blocks than this go to UltraRAM instead
+const URAM_THRESHOLD_BRAMS: u64 = 32;
+
+/// Device resources, used both for estimates and for device capacities
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ResourceEstimate {
+    pub luts: u32,
+    pub ffs: u32,
+    pub dsps: u32,
+    pub brams: u32,
+    pub uram_blocks: u32,
+}
+
+/// Resources available on the AMD Alveo U50 (XCU50)
+pub const U50_CAPACITY: ResourceEstimate = ResourceEstimate {
+    luts: 872_000,
+    ffs: 1_743_000,
+    dsps: 5_952,
+    brams: 1_344,
+    uram_blocks: 640,
+};
+
+impl ResourceEstimate {
+    /// (name, used, available) for every resource kind
+    fn rows(&self, capacity: &ResourceEstimate) -> [(&'static str, u32, u32); 5] {
+        [
+            ("LUT", self.luts, capacity.luts),
+            ("FF", self.ffs, capacity.ffs),
+            ("DSP48E2", self.dsps, capacity.dsps),
+            ("BRAM36", self.brams, capacity.brams),
+            ("URAM", self.uram_blocks, capacity.uram_blocks),
+        ]
+    }
+
+    /// Percentage of `capacity` used, per resource kind
+    pub fn utilization(&self, capacity: &ResourceEstimate) -> Vec<(&'static str, f64)> {
+        self.rows(capacity).iter()
+            .map(|&(name, used, available)| (name, percent(used, available)))
+            .collect()
+    }
+
+    /// Whether every resource kind fits in `capacity`
+    pub fn fits(&self, capacity: &ResourceEstimate) -> bool {
+        self.rows(capacity).iter().all(|&(_, used, available)| used <= available)
+    }
+
+    /// Print usage next to the device capacity, e.g. `print_summary(&U50_CAPACITY)`
+    pub fn print_summary(&self, capacity: &ResourceEstimate) {
+        println!("\n=== RESOURCE ESTIMATE ===");
+        for (name, used, available) in self.rows(capacity) {
+            println!("{:<8} {:>9} / {:<9} ({:.2}%)", name, used, available, percent(used, available));
+        }
+        if !self.fits(capacity) {
+            println!("Design does not fit the target device");
+        }
+    }
+}
+
+impl std::ops::AddAssign for ResourceEstimate {
+    fn add_assign(&mut self, other: Self) {
+        self.luts += other.luts;
+        self.ffs += other.ffs;
+        self.dsps += other.dsps;
+        self.brams += other.brams;
+        self.uram_blocks += other.uram_blocks;
+    }
+}
+
+/// Sum the estimated cost of every node, on-chip array and state register in the graph
+///
+/// Scheduled graphs also pay `width` flip-flops for every stage boundary a
+/// value crosses. `PipelineRegister` nodes count only while something reads
+/// them, so the unused ones left behind by scheduling are free. Multiplies
+/// sharing a functional unit pay for its DSPs once, plus its input muxes.
+pub fn estimate_resources(graph: &Graph) -> ResourceEstimate {
+    let used: HashSet<ValueId> = graph.nodes.iter().flat_map(|node| node.op.operands()).collect();
+    let shared: HashSet<NodeId> = graph.functional_units.iter()
+        .filter(|unit| unit.is_shared())
+        .flat_map(|unit| unit.operations.iter().copied())
+        .collect();
+    let mut total = ResourceEstimate { ffs: stage_register_ffs(graph), ..Default::default() };
+    for node in &graph.nodes {
+        let dead_register = matches!(node.op, Operation::PipelineRegister(_)) && !node.output.is_some_and(|output| used.contains(&output));
+        if !dead_register && !shared.contains(&node.id) {
+            total += node_cost(node, graph);
+        }
+    }
+    for unit in graph.functional_units.iter().filter(|unit| unit.is_shared()) {
+        let width = unit.operations.iter()
+            .filter_map(|node_id| graph.nodes.iter().find(|node| node.id == *node_id))
+            .flat_map(|node| node.op.operands())
+            .map(|value| graph.value_width(value).unwrap_or(DEFAULT_WIDTH))
+            .max()
+            .unwrap_or(DEFAULT_WIDTH);
+        // An n:1 mux in front of each operand, built from n - 1 two-input ones
+        let muxes = 2 * (unit.operations.len() as u32 - 1);
+        total += ResourceEstimate { dsps: multiplier_dsps(width), luts: muxes * (width / 4).max(1), ..Default::default() };
+    }
+    for array in graph.arrays.iter().filter(|array| array.kind != ArrayKind::Interface) {
+        let width = array.width.unwrap_or(DEFAULT_WIDTH);
+        total += memory_cost(array.depth, width);
+    }
+    for register in &graph.state_registers {
+        total.ffs += register.width.unwrap_or(DEFAULT_WIDTH);
+    }
+    total
+}
+
+/// Empirical cost of a single operation
+fn node_cost(node: &Node, graph: &Graph) -> ResourceEstimate {
+    let width = node.output_width.unwrap_or(DEFAULT_WIDTH);
+    let operand_width = node.op.operands().into_iter()
+        .map(|value| graph.value_width(value).unwrap_or(DEFAULT_WIDTH))
+        .max()
+        .unwrap_or(width);
+    let luts = |luts: u32| ResourceEstimate { luts: luts.max(1), ..Default::default() };
+
+    match &node.op {
+        // One DSP48E2 takes a 27x18 product; 32-bit operands need three
+        Operation::Mul(_, _) | Operation::SMul(_, _) | Operation::Fma(_, _, _) => {
+            ResourceEstimate { dsps: multiplier_dsps(operand_width), ..Default::default() }
+        }
+        // Carry chains pack six bits per LUT
+        Operation::Add(_, _) | Operation::Sub(_, _) | Operation::SAdd(_, _) | Operation::SSub(_, _) |
+        Operation::CmpLt(_, _) | Operation::CmpGt(_, _) | Operation::CmpEq(_, _) |
+        Operation::CmpGe(_, _) | Operation::CmpLe(_, _) | Operation::CmpNe(_, _) => luts(operand_width / 6),
+        Operation::Mux(_, _, _) => luts(width / 4),
+        // N - 1 two-input adders or compare-and-selects
+        Operation::ReduceAdd(values) => luts(values.len().saturating_sub(1) as u32 * (width / 6).max(1)),
+        Operation::ReduceMax(values) | Operation::ReduceMin(values) => {
+            luts(values.len().saturating_sub(1) as u32 * (width / 6 + width / 4).max(1))
+        }
+        // Compare plus select, or conditional negate
+        Operation::Min(_, _) | Operation::Max(_, _) | Operation::Abs(_) => luts(width / 6 + width / 4),
+        // Adder, bound compare and clamp select
+        Operation::AddSat(_, _, _) | Operation::SubSat(_, _, _) => luts(operand_width / 6 + width / 6 + width / 4),
+        Operation::And(_, _) | Operation::Or(_, _) | Operation::Xor(_, _) | Operation::Not(_) |
+        Operation::Nand(_, _) | Operation::Nor(_, _) | Operation::Xnor(_, _) => luts(width / 2),
+        // Barrel shifter: one mux level per shift-amount bit
+        Operation::Shl(_, _) | Operation::Shr(_, _) => luts(width * u32::BITS.saturating_sub(width.leading_zeros()) / 4),
+        // Array divider: one subtract-and-select row per quotient bit
+        Operation::Div(_, _) => luts(width * operand_width / 6),
+        // One add-or-subtract row per result bit, each as wide as the partial remainder
+        Operation::Sqrt(_) => luts(width * (width + 2) / 6),
+        Operation::PipelineRegister(_) => ResourceEstimate { ffs: width, ..Default::default() },
+        // Counter register and incrementer; carried register with its first-iteration select
+        Operation::LoopIndex => ResourceEstimate { luts: (width / 6).max(1), ffs: width, ..Default::default() },
+        Operation::LoopCarry(_, _) => ResourceEstimate { luts: (width / 4).max(1), ffs: width, ..Default::default() },
+        // A whole instance of the callee; its results are wires from the instance ports
+        Operation::Call(name, _) => graph.callee(name).map(estimate_resources).unwrap_or_default(),
+        Operation::CallResult(_, _) => ResourceEstimate::default(),
+        // Wiring, ports, BRAM ports and state register accesses (counted per array
+        // or register) cost nothing themselves
+        Operation::Concat(_, _) | Operation::Slice(_, _, _) | Operation::Load(_) | Operation::Store(_, _) |
+        Operation::Const(_) | Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) | Operation::LoopNext(_, _) |
+        Operation::RegisterLoad(_) | Operation::RegisterStore(_, _) | Operation::PipelineBarrier | Operation::Nop => ResourceEstimate::default(),
+    }
+}
+
+/// Flip-flops holding values between the stages they are produced and read in
+///
+/// Constants are never registered, and reads by a `PipelineRegister` and
+/// the register's own output are that register's cost.
+fn stage_register_ffs(graph: &Graph) -> u32 {
+    let stage_of: HashMap<NodeId, usize> = graph.pipeline_stages.iter()
+        .flat_map(|stage| stage.operations.iter().map(move |node_id| (*node_id, stage.stage)))
+        .collect();
+    let mut last_read: HashMap<ValueId, usize> = HashMap::new();
+    for node in &graph.nodes {
+        let Some(&stage) = stage_of.get(&node.id) else { continue };
+        if matches!(node.op, Operation::PipelineRegister(_)) {
+            continue;
+        }
+        for operand in node.op.operands() {
+            let latest = last_read.entry(operand).or_insert(stage);
+            *latest = (*latest).max(stage);
+        }
+    }
+
+    graph.nodes.iter()
+        .filter(|node| !matches!(node.op, Operation::Const(_) | Operation::PipelineRegister(_)))
+        .filter_map(|node| {
+            let produced = *stage_of.get(&node.id)?;
+            let read = *last_read.get(&node.output?)?;
+            let width = node.output_width.unwrap_or(DEFAULT_WIDTH);
+            Some(width * read.saturating_sub(produced) as u32)
+        })
+        .sum()
+}
+
+/// DSP48E2 slices for a multiplier whose widest operand is `width` bits
+fn multiplier_dsps(width: u32) -> u32 {
+    if width <= 18 {
+        1
+    } else {
+        3 * width.div_ceil(32).pow(2)
+    }
+}
+
+/// LUTRAM for shallow arrays, BRAM36 for medium ones and URAM for large ones
+fn memory_cost(depth: usize, width: u32) -> ResourceEstimate {
+    if depth <= LUTRAM_MAX_DEPTH {
+        return ResourceEstimate { luts: width, ..Default::default() };
+    }
+    let bits = depth as u64 * width as u64;
+    let brams = bits.div_ceil(BRAM36_BITS);
+    if brams > URAM_THRESHOLD_BRAMS {
+        ResourceEstimate { uram_blocks: bits.div_ceil(URAM_BITS) as u32, ..Default::default() }
+    } else {
+        ResourceEstimate { brams: brams as u32, ..Default::default() }
+    }
+}
+
+fn percent(used: u32, available: u32) -> f64 {
+    if available == 0 {
+        0.0
+    } else {
+        100.0 * used as f64 / available as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+
+    #[test]
+    fn test_operation_costs_scale_with_width() {
+        let narrow = estimate_resources(&lower_expr_to_graph(&output("r", mul(input("a", 16), input("b", 16)))));
+        let wide = estimate_resources(&lower_expr_to_graph(&output("r", mul(input("a", 32), input("b", 32)))));
+        assert_eq!((narrow.dsps, wide.dsps), (1, 3));
+
+        let adder = estimate_resources(&lower_expr_to_graph(&output("r", add(input("a", 24), input("b", 24)))));
+        assert_eq!(adder, ResourceEstimate { luts: 4, ..Default::default() });
+        let select = estimate_resources(&lower_expr_to_graph(&output("r", select(input("c", 1), input("a", 32), input("b", 32)))));
+        assert_eq!(select.luts, 8);
+    }
+
+    #[test]
+    fn test_arrays_map_to_lutram_bram_or_uram() {
+        let mut graph = Graph::new();
+        graph.declare_array("small", 16, Some(8), ArrayKind::Input);
+        graph.declare_array("medium", 1024, Some(32), ArrayKind::Input);
+        graph.declare_array("large", 65536, Some(64), ArrayKind::Input);
+
+        let estimate = estimate_resources(&graph);
+        assert_eq!(estimate, ResourceEstimate { luts: 8, ffs: 0, dsps: 0, brams: 1, uram_blocks: 15 });
+        assert!(estimate.fits(&U50_CAPACITY));
+
+        let utilization = estimate.utilization(&U50_CAPACITY);
+        assert_eq!(utilization[4].0, "URAM");
+        assert!((utilization[4].1 - 100.0 * 15.0 / 640.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,380 @@
+//! Register retiming for scheduled pipelines
+//!
+//! The scheduler chains operations into the earliest cycle they fit, which
+//! can leave one stage holding most of the logic and the next nearly empty.
+//! Retiming (Leiserson and Saxe) moves the stage boundaries along the
+//! dataflow graph instead: an operation at the end of a path longer than
+//! the target period starts a cycle later, taking the register in front of
+//! it along, until every stage fits. The number of stages, and so the
+//! latency and what the pipeline computes, stays the same.
+
+use crate::ir::graph::{Graph, NodeId, Operation, ValueId, DEFAULT_WIDTH};
+use crate::passes::pipeline::PipelineScheduler;
+use crate::passes::timing::{schedule_of, ROUTING_OVERHEAD};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Stage delays before and after `run_retiming_pass`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetimingReport {
+    /// Longest chain of logic within one stage, before routing
+    pub critical_path_before_ns: f64,
+    pub critical_path_after_ns: f64,
+    pub target_period_ns: f64,
+    /// Stage boundaries crossed by moved operations
+    pub register_moves: usize,
+}
+
+impl RetimingReport {
+    pub fn meets_target(&self) -> bool {
+        self.critical_path_after_ns <= self.target_period_ns * (1.0 - ROUTING_OVERHEAD)
+    }
+}
+
+impl fmt::Display for RetimingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Retiming: critical path {:.2} ns -> {:.2} ns (target {:.2} ns), {} register moves",
+            self.critical_path_before_ns, self.critical_path_after_ns, self.target_period_ns, self.register_moves
+        )
+    }
+}
+
+/// Retime a scheduled graph so every stage fits in `target_period_ns`, less routing
+///
+/// Follows the FEAS relaxation of Leiserson and Saxe: every operation whose
+/// chained delay exceeds the budget, or whose operand is no longer ready,
+/// moves one cycle later, until none is left. Operations never move past
+/// the last stage, side-effecting operations never move at all, and no slot
+/// of the initiation interval gets more of a limited resource than it had,
+/// so when the target cannot be met the graph is left untouched. The
+/// pipeline registers are then rebuilt for the new cycles.
+pub fn run_retiming_pass(graph: &mut Graph, target_period_ns: f64) -> RetimingReport {
+    let budget = target_period_ns * (1.0 - ROUTING_OVERHEAD);
+    retime(graph, target_period_ns, |retiming| retiming.feasible(budget))
+}
+
+/// Retime a scheduled graph to the smallest stage delay its stages allow
+///
+/// Bisects the logic budget between the slowest single operation and the
+/// current critical path, keeping the tightest budget `run_retiming_pass`
+/// could meet. `target_period_ns` is only what the report is checked
+/// against; the stages are balanced as far as they go either way.
+pub fn run_min_period_retiming_pass(graph: &mut Graph, target_period_ns: f64) -> RetimingReport {
+    retime(graph, target_period_ns, |retiming| {
+        let (mut low, mut high) = (retiming.slowest_operation_ns(), retiming.before.critical_path_ns);
+        let mut best = None;
+        for _ in 0..BISECTION_STEPS {
+            if high - low < 1e-6 {
+                break;
+            }
+            let budget = (low + high) / 2.0;
+            match retiming.feasible(budget) {
+                Some(cycles) => {
+                    high = budget;
+                    best = Some(cycles);
+                }
+                None => low = budget,
+            }
+        }
+        best
+    })
+}
+
+/// Bisection steps of `run_min_period_retiming_pass`, enough for picosecond resolution
+const BISECTION_STEPS: usize = 40;
+
+/// Run `search` over the graph's schedule and apply the cycles it finds, if they move anything
+fn retime(graph: &mut Graph, target_period_ns: f64, search: impl Fn(&Retiming) -> Option<HashMap<NodeId, usize>>) -> RetimingReport {
+    let retiming = Retiming::new(graph);
+    let mut report = RetimingReport {
+        critical_path_before_ns: retiming.before.critical_path_ns,
+        critical_path_after_ns: retiming.before.critical_path_ns,
+        target_period_ns,
+        register_moves: 0,
+    };
+    let Some(mut retimed) = search(&retiming).filter(|retimed| *retimed != retiming.cycles) else { return report };
+
+    report.register_moves = retimed.iter().map(|(node_id, cycle)| cycle - retiming.cycles[node_id]).sum();
+    let scheduler = retiming.scheduler;
+    rebuild_pipeline_registers(graph, &scheduler, &mut retimed);
+    report.critical_path_after_ns = stage_delays(graph, &scheduler, &retimed, f64::INFINITY).critical_path_ns;
+    report
+}
+
+/// A scheduled graph's cycles, with what retiming must preserve
+struct Retiming<'a> {
+    graph: &'a Graph,
+    scheduler: PipelineScheduler,
+    latency: HashMap<NodeId, usize>,
+    cycles: HashMap<NodeId, usize>,
+    before: StageDelays,
+    depth: usize,
+    /// Units used and available of each limited resource per slot of the initiation interval, as scheduled
+    usage: HashMap<(usize, String), (usize, usize)>,
+}
+
+impl<'a> Retiming<'a> {
+    fn new(graph: &'a Graph) -> Self {
+        let scheduler = PipelineScheduler::new();
+        let latency: HashMap<NodeId, usize> = graph.nodes.iter()
+            .filter(|node| !matches!(node.op, Operation::PipelineRegister(_)))
+            .map(|node| (node.id, scheduler.operation_latency(graph, &node.op).max(1)))
+            .collect();
+        let mut cycles: HashMap<NodeId, usize> = schedule_of(graph);
+        cycles.retain(|node_id, _| latency.contains_key(node_id));
+        let before = stage_delays(graph, &scheduler, &cycles, f64::INFINITY);
+        let mut retiming = Self { graph, scheduler, latency, cycles, before, depth: graph.pipeline_stages.len(), usage: HashMap::new() };
+        retiming.usage = retiming.resource_usage(&retiming.cycles);
+        retiming
+    }
+
+    /// Cycles meeting `budget` by the FEAS relaxation, if there are any
+    fn feasible(&self, budget: f64) -> Option<HashMap<NodeId, usize>> {
+        let mut retimed = self.cycles.clone();
+        let mut delays = stage_delays(self.graph, &self.scheduler, &retimed, budget);
+        for _ in 0..=self.graph.nodes.len() {
+            if delays.late.is_empty() {
+                break;
+            }
+            for node_id in &delays.late {
+                let node = self.graph.nodes.iter().find(|node| node.id == *node_id).expect("late nodes are in the graph");
+                let cycle = retimed.get_mut(node_id).expect("only scheduled nodes are late");
+                *cycle += 1;
+                if *cycle + self.latency[node_id] > self.depth || has_side_effects(&node.op) {
+                    return None;
+                }
+            }
+            delays = stage_delays(self.graph, &self.scheduler, &retimed, budget);
+        }
+        let overused = self.resource_usage(&retimed).iter().any(|(slot, &(used, limit))| {
+            used > limit.max(self.usage.get(slot).map_or(0, |&(scheduled, _)| scheduled))
+        });
+        (delays.late.is_empty() && !overused).then_some(retimed)
+    }
+
+    /// Units used and available of every limited resource, per slot of the initiation interval under `cycles`
+    fn resource_usage(&self, cycles: &HashMap<NodeId, usize>) -> HashMap<(usize, String), (usize, usize)> {
+        let ii = self.graph.pipeline_config.initiation_interval.max(1);
+        let mut usage = HashMap::new();
+        for node in &self.graph.nodes {
+            let (Some(&cycle), Some((resource, limit))) = (cycles.get(&node.id), self.scheduler.constrained_resource(self.graph, &node.op)) else {
+                continue;
+            };
+            usage.entry((cycle % ii, resource)).or_insert((0, limit)).0 += 1;
+        }
+        usage
+    }
+
+    /// Per-cycle delay of the slowest operation, which no budget can go below
+    fn slowest_operation_ns(&self) -> f64 {
+        self.graph.nodes.iter()
+            .filter(|node| self.cycles.contains_key(&node.id))
+            .map(|node| {
+                let delay = self.graph.get_operation_delay_ns(&node.op, node.output_width.unwrap_or(DEFAULT_WIDTH));
+                delay / self.latency[&node.id] as f64
+            })
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Operations that commit in their scheduled stage: ports, memories, state registers and instances
+fn has_side_effects(op: &Operation) -> bool {
+    matches!(op, Operation::Store(_, _) | Operation::ArrayLoad(_, _) | Operation::ArrayStore(_, _, _) |
+                 Operation::RegisterLoad(_) | Operation::RegisterStore(_, _) | Operation::Call(_, _))
+}
+
+/// Chained logic delays under a cycle assignment
+struct StageDelays {
+    critical_path_ns: f64,
+    /// Operations that must start a cycle later
+    late: Vec<NodeId>,
+}
+
+/// Delay of every chain of operations in `cycles`, and the operations that break `budget`
+///
+/// Mirrors the scheduler: an operand is ready once its producer's latency
+/// has passed, or within the cycle when both operations chain. Multi-cycle
+/// operations spread their delay over their latency.
+fn stage_delays(graph: &Graph, scheduler: &PipelineScheduler, cycles: &HashMap<NodeId, usize>, budget: f64) -> StageDelays {
+    let mut settled: HashMap<NodeId, f64> = HashMap::new();
+    let mut late = Vec::new();
+    for node in &graph.nodes {
+        let Some(&cycle) = cycles.get(&node.id) else { continue };
+        let mut start: f64 = 0.0;
+        let mut ready = true;
+        for operand in node.op.operands() {
+            let Some(producer) = graph.producer(resolve(operand, graph)) else { continue };
+            let Some(&produced) = cycles.get(&producer.id) else { continue };
+            if produced + scheduler.operation_latency(graph, &producer.op) <= cycle {
+                continue;
+            }
+            if produced == cycle && scheduler.chains(graph, &producer.op) && scheduler.chains(graph, &node.op) {
+                start = start.max(settled[&producer.id]);
+            } else {
+                ready = false;
+            }
+        }
+
+        let latency = scheduler.operation_latency(graph, &node.op).max(1);
+        let settles = start + graph.get_operation_delay_ns(&node.op, node.output_width.unwrap_or(DEFAULT_WIDTH)) / latency as f64;
+        if !ready || (start > 0.0 && settles > budget) {
+            late.push(node.id);
+        }
+        settled.insert(node.id, settles);
+    }
+
+    StageDelays { critical_path_ns: settled.values().copied().fold(0.0, f64::max), late }
+}
+
+/// Replace the graph's pipeline registers with ones for the cycles in `cycles`
+fn rebuild_pipeline_registers(graph: &mut Graph, scheduler: &PipelineScheduler, cycles: &mut HashMap<NodeId, usize>) {
+    let registers: HashMap<ValueId, ValueId> = graph.nodes.iter()
+        .filter_map(|node| match node.op {
+            Operation::PipelineRegister(inner) => Some((node.output?, inner)),
+            _ => None,
+        })
+        .collect();
+    for node in &mut graph.nodes {
+        for operand in node.op.operands_mut() {
+            while let Some(&inner) = registers.get(operand) {
+                *operand = inner;
+            }
+        }
+    }
+    graph.nodes.retain(|node| !matches!(node.op, Operation::PipelineRegister(_)));
+    // Multiplies may have changed slots, so they need binding again
+    graph.functional_units.clear();
+    graph.value_map.retain(|value, _| !registers.contains_key(value));
+
+    scheduler.insert_pipeline_registers(graph, cycles)
+        .expect("retiming removes no consumers, so every one can be rewired");
+    graph.pipeline_stages = scheduler.generate_pipeline_stages(cycles, graph);
+}
+
+/// Follow pipeline registers back to the value they carry
+fn resolve(value: ValueId, graph: &Graph) -> ValueId {
+    match graph.producer(value).map(|producer| &producer.op) {
+        Some(Operation::PipelineRegister(inner)) => resolve(*inner, graph),
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+
+    /// Adds per stage, in stage order
+    fn adds_per_stage(graph: &Graph) -> Vec<usize> {
+        graph.pipeline_stages.iter()
+            .map(|stage| stage.operations.iter()
+                .filter(|node_id| graph.nodes.iter().any(|node| node.id == **node_id && matches!(node.op, Operation::Add(_, _))))
+                .count())
+            .filter(|&adds| adds > 0)
+            .collect()
+    }
+
+    #[test]
+    fn test_imbalanced_adder_chain_is_rebalanced() {
+        let chain = add(add(add(add(input("a", 8), input("b", 8)), input("c", 8)), input("d", 8)), input("e", 8));
+        let mut graph = lower_expr_to_graph(&output("sum", chain));
+        graph.enable_pipeline(1, 8, 1);
+        // 0.48 + 0.50 + 0.52 ns of carry chain fit in 2.5 ns less routing, the last 0.54 ns does not
+        let mut scheduler = PipelineScheduler { clock_period_ns: 2.5, ..PipelineScheduler::new() };
+        scheduler.schedule_pipeline(&mut graph).unwrap();
+        assert_eq!(adds_per_stage(&graph), vec![3, 1]);
+        let stages = graph.pipeline_stages.len();
+
+        let report = run_retiming_pass(&mut graph, 2.0);
+        assert_eq!(adds_per_stage(&graph), vec![2, 2]);
+        assert_eq!(graph.pipeline_stages.len(), stages);
+        assert_eq!(report.register_moves, 1);
+        assert!((report.critical_path_before_ns - 1.50).abs() < 1e-9);
+        assert!((report.critical_path_after_ns - 1.06).abs() < 1e-9);
+        assert!(report.meets_target());
+        assert!(report.to_string().contains("critical path 1.50 ns -> 1.06 ns (target 2.00 ns), 1 register moves"));
+
+        let mut sim = Simulator::new();
+        for (name, value) in [("a", 1), ("b", 2), ("c", 3), ("d", 4), ("e", 5)] {
+            sim.set_input(name, value, &graph);
+        }
+        assert_eq!(sim.simulate(&graph).unwrap()["sum"], 15);
+        // The register now sits after the second add
+        let verilog = crate::backend::verilog::generate_verilog_module(&graph, "sum5", None).unwrap();
+        assert!(verilog.contains("node_4_r2 <= node_4;"));
+        assert!(verilog.contains("assign node_7 = node_4_r2 + d_r2;"));
+    }
+
+    #[test]
+    fn test_unreachable_target_leaves_the_schedule_alone() {
+        let mut graph = lower_expr_to_graph(&output("sum", add(add(input("a", 8), input("b", 8)), input("c", 8))));
+        graph.enable_pipeline(1, 8, 1);
+        PipelineScheduler::new().schedule_pipeline(&mut graph).unwrap();
+        let before = schedule_of(&graph);
+
+        // Both adds share the last stage, so neither can move later
+        let report = run_retiming_pass(&mut graph, 1.0);
+        assert_eq!(schedule_of(&graph), before);
+        assert_eq!(report.register_moves, 0);
+        assert!(!report.meets_target());
+    }
+
+    #[test]
+    fn test_min_period_retiming_balances_chained_stages() {
+        // All four adds chain into one stage, leaving the compare alone in the next
+        let sum = add(add(add(add(input("a", 8), input("b", 8)), input("c", 8)), input("d", 8)), input("e", 8));
+        let mut graph = lower_expr_to_graph(&output("over", gt(sum, input("limit", 12))));
+        graph.enable_pipeline(1, 8, 1);
+        let mut scheduler = PipelineScheduler { clock_period_ns: 3.0, ..PipelineScheduler::new() };
+        scheduler.schedule_pipeline(&mut graph).unwrap();
+        let stages = graph.pipeline_stages.len();
+        let mut meets_target = graph.clone();
+
+        let report = run_min_period_retiming_pass(&mut graph, 2.0);
+        assert!((report.critical_path_before_ns - 2.04).abs() < 1e-9);
+        assert!((report.critical_path_after_ns - 1.38).abs() < 1e-9);
+        assert!(report.meets_target());
+        assert_eq!(graph.pipeline_stages.len(), stages);
+        // Meeting the target alone leaves a heavier stage than balancing does
+        assert!(run_retiming_pass(&mut meets_target, 2.0).critical_path_after_ns >= report.critical_path_after_ns);
+
+        let mut sim = Simulator::new();
+        for (name, value) in [("a", 100), ("b", 200), ("c", 250), ("d", 250), ("e", 5), ("limit", 804)] {
+            sim.set_input(name, value, &graph);
+        }
+        assert_eq!(sim.simulate(&graph).unwrap()["over"], 1);
+    }
+
+    #[test]
+    fn test_retiming_keeps_state_register_updates_in_their_stage() {
+        // acc' = acc + (a + b + c + d), committed in the stage of the last add
+        let mut graph = Graph::new();
+        graph.declare_state_register("acc", Some(16), 0, false);
+        let [a, b, c, d] = ["a", "b", "c", "d"]
+            .map(|name| graph.add_node_with_output_type(Operation::Load(name.to_string()), Some(16), false));
+        let mut terms = a;
+        for term in [b, c, d] {
+            terms = graph.add_node_with_output_type(Operation::Add(terms, term), Some(16), false);
+        }
+        let acc = graph.add_node_with_output(Operation::RegisterLoad("acc".to_string()));
+        let total = graph.add_node_with_output_type(Operation::Add(acc, terms), Some(16), false);
+        graph.add_node(Operation::RegisterStore("acc".to_string(), total));
+        graph.enable_pipeline(1, 8, 1);
+        PipelineScheduler { clock_period_ns: 3.0, ..PipelineScheduler::new() }.schedule_pipeline(&mut graph).unwrap();
+        let before = schedule_of(&graph);
+
+        // The adds rebalance around the update, which stays where it reads and commits acc
+        let report = run_min_period_retiming_pass(&mut graph, 1.0);
+        let after = schedule_of(&graph);
+        assert!(report.critical_path_after_ns < report.critical_path_before_ns);
+        for node in &graph.nodes {
+            if matches!(node.op, Operation::RegisterLoad(_) | Operation::RegisterStore(_, _)) {
+                assert_eq!(after[&node.id], before[&node.id], "{:?} moved", node.op);
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Strength reduction for HLS graphs
+//!
+//! Multiplying by a constant power of two is just wiring on an FPGA, so such
+//! `Mul` nodes are rewritten as left shifts and no longer claim a DSP48E2 slice.
+
+use crate::ir::graph::{Graph, Node, NodeId, Operation, ValueId};
+
+/// Replace `x * 2^k` (either operand order) with `x << k`
+///
+/// The rewritten node keeps the width and signedness of the product. Returns
+/// the number of replaced multiplications.
+pub fn run_strength_reduce_pass(graph: &mut Graph) -> usize {
+    let mut replaced = 0;
+    let mut index = 0;
+
+    while index < graph.nodes.len() {
+        if let Operation::Mul(left, right) = graph.nodes[index].op {
+            let reduction = match (power_of_two(graph, left), power_of_two(graph, right)) {
+                (_, Some(shift)) => Some((left, shift)),
+                (Some(shift), None) => Some((right, shift)),
+                (None, None) => None,
+            };
+
+            if let Some((value, shift)) = reduction {
+                // The shift amount must be defined before the shift itself
+                let amount = insert_const_before(graph, index, shift as i64);
+                index += 1;
+                graph.nodes[index].op = Operation::Shl(value, amount);
+                replaced += 1;
+            }
+        }
+        index += 1;
+    }
+
+    replaced
+}
+
+/// Exponent `k` if `value` is produced by `Const(2^k)`
+fn power_of_two(graph: &Graph, value: ValueId) -> Option<u32> {
+    match graph.producer(value)?.op {
+        Operation::Const(val) if val > 0 && val.count_ones() == 1 => Some(val.trailing_zeros()),
+        _ => None,
+    }
+}
+
+/// Insert a minimally sized unsigned constant at `index`, keeping graph order topological
+fn insert_const_before(graph: &mut Graph, index: usize, value: i64) -> ValueId {
+    let output = graph.new_value();
+    let node = Node {
+        id: NodeId(graph.next_node),
+        op: Operation::Const(value),
+        output: Some(output),
+        output_width: Some((64 - value.leading_zeros()).max(1)),
+        signed: false,
+        fixed_point: None,
+    };
+
+    graph.next_node += 1;
+    graph.value_map.insert(output, node.id);
+    graph.nodes.insert(index, node);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::sim::Simulator;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+    use crate::passes::dce::run_dce_pass;
+    use crate::passes::pipeline::PipelineScheduler;
+
+    #[test]
+    fn test_multiply_by_eight_becomes_shift() {
+        let mut graph = lower_expr_to_graph(&output("result", mul(input("a", 16), const_val(8, 16))));
+        let scheduler = PipelineScheduler::new();
+        assert_eq!(scheduler.estimate_resources(&graph).get("multiplier"), Some(&1));
+
+        assert_eq!(run_strength_reduce_pass(&mut graph), 1);
+        run_dce_pass(&mut graph);
+
+        let shift = graph.nodes.iter().find(|n| matches!(n.op, Operation::Shl(_, _))).expect("no shift");
+        let Operation::Shl(value, amount) = shift.op else { unreachable!() };
+        assert!(matches!(graph.producer(value).unwrap().op, Operation::Load(ref name) if name == "a"));
+        assert_eq!(graph.producer(amount).unwrap().op, Operation::Const(3));
+        assert_eq!(shift.output_width, Some(32));
+        assert_eq!(scheduler.estimate_resources(&graph).get("multiplier"), None);
+
+        let mut sim = Simulator::new();
+        sim.set_input("a", 1234, &graph);
+        assert_eq!(sim.simulate(&graph).unwrap()["result"], 1234 * 8);
+    }
+
+    #[test]
+    fn test_other_multiplies_are_kept() {
+        let mut graph = lower_expr_to_graph(&output("result", mul(input("a", 16), const_val(6, 16))));
+        assert_eq!(run_strength_reduce_pass(&mut graph), 0);
+        assert!(graph.nodes.iter().any(|n| matches!(n.op, Operation::Mul(_, _))));
+    }
+}
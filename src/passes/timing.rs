@@ -0,0 +1,400 @@
+//! Critical path analysis for scheduled graphs
+//!
+//! Finds the longest chain of operation latencies from an input to an output
+//! and estimates the clock period from rough UltraScale+ logic delays. The
+//! kernel targets 500 MHz, and routing is assumed to take 30% of each cycle,
+//! leaving 1.4 ns of logic per stage.
+//!
+//! `analyze_timing` goes stage by stage instead: the longest chain of
+//! operations within each stage, its slack against a clock period, and the
+//! worst paths overall, from a `DelayTable` that can be calibrated.
+
+use crate::ir::graph::{DelayTable, Graph, NodeId, Operation, ValueId, DEFAULT_WIDTH};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Clock period of the 500 MHz target
+pub const TARGET_PERIOD_NS: f64 = 2.0;
+
+/// Share of each cycle lost to routing
+pub const ROUTING_OVERHEAD: f64 = 0.3;
+
+/// The slowest stage, when it cannot meet the target period
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bottleneck {
+    pub stage: usize,
+    pub node: NodeId,
+    /// Logic delay of `node` within one cycle, before routing
+    pub logic_delay_ns: f64,
+}
+
+/// Longest input-to-output path and the clock period it allows
+#[derive(Debug, Clone, PartialEq)]
+pub struct CriticalPathReport {
+    /// Nodes from the `Load` to the `Store`, in dataflow order
+    pub path: Vec<NodeId>,
+    /// `Load(a)`, `Mul`, ..., one per node on `path`
+    pub labels: Vec<String>,
+    /// Logic delay accumulated up to and including each node on `path`
+    pub cumulative_ns: Vec<f64>,
+    /// Sum of the operation latencies along `path`
+    pub latency_cycles: usize,
+    /// Worst per-stage logic delay plus routing overhead
+    pub estimated_period_ns: f64,
+    pub target_period_ns: f64,
+    pub bottleneck: Option<Bottleneck>,
+}
+
+impl CriticalPathReport {
+    pub fn meets_timing(&self) -> bool {
+        self.bottleneck.is_none()
+    }
+}
+
+impl fmt::Display for CriticalPathReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps: Vec<String> = self.labels.iter().zip(&self.cumulative_ns)
+            .map(|(label, ns)| format!("{} ({:.2} ns)", label, ns))
+            .collect();
+        writeln!(f, "Critical path ({} cycles): {}", self.latency_cycles, steps.join(" -> "))?;
+        write!(f, "Estimated clock period: {:.2} ns (target {:.2} ns)", self.estimated_period_ns, self.target_period_ns)?;
+        if let Some(bottleneck) = &self.bottleneck {
+            write!(
+                f,
+                "\nBottleneck: stage {} (node {}, {:.2} ns of logic) misses the target",
+                bottleneck.stage, bottleneck.node.0, bottleneck.logic_delay_ns
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Cycle of every node in the graph's pipeline schedule
+pub fn schedule_of(graph: &Graph) -> HashMap<NodeId, usize> {
+    graph.pipeline_stages.iter()
+        .flat_map(|stage| stage.operations.iter().map(move |node_id| (*node_id, stage.cycle)))
+        .collect()
+}
+
+/// Analyze the longest `Load`-to-`Store` chain of `graph` under `schedule`
+///
+/// Ports take no time themselves, so a chain of three multipliers is 9
+/// cycles long. Nodes missing from `schedule` are assumed to start in the
+/// cycle their operands finish.
+pub fn analyze_critical_path(graph: &Graph, schedule: &HashMap<NodeId, usize>) -> CriticalPathReport {
+    let budget = TARGET_PERIOD_NS * (1.0 - ROUTING_OVERHEAD);
+
+    // Longest (cycles, delay) reaching each value, and the node producing it
+    let mut longest: HashMap<ValueId, (usize, f64, usize)> = HashMap::new();
+    let mut predecessor: HashMap<usize, Option<usize>> = HashMap::new();
+    let mut best_store: Option<(usize, f64, usize)> = None;
+    let mut bottleneck: Option<Bottleneck> = None;
+    let mut worst_stage_delay: f64 = 0.0;
+
+    for (index, node) in graph.nodes.iter().enumerate() {
+        let (cycles, delay, from) = node.op.operands().into_iter()
+            .filter_map(|value| longest.get(&value).copied())
+            .fold(None, |best: Option<(usize, f64, usize)>, candidate| longer(candidate, best))
+            .map(|(cycles, delay, producer)| (cycles, delay, Some(producer)))
+            .unwrap_or((0, 0.0, None));
+        predecessor.insert(index, from);
+
+        let latency = path_latency(&node.op, graph);
+        let node_delay = graph.get_operation_delay_ns(&node.op, node.output_width.unwrap_or(DEFAULT_WIDTH));
+        let reached = (cycles + latency, delay + node_delay, index);
+
+        // Multi-cycle operations spread their delay over their latency
+        let per_cycle = node_delay / latency.max(1) as f64;
+        worst_stage_delay = worst_stage_delay.max(per_cycle);
+        if per_cycle > budget && bottleneck.as_ref().is_none_or(|worst| per_cycle > worst.logic_delay_ns) {
+            let stage = schedule.get(&node.id).copied().unwrap_or(cycles);
+            bottleneck = Some(Bottleneck { stage, node: node.id, logic_delay_ns: per_cycle });
+        }
+
+        match (&node.op, node.output) {
+            (Operation::Store(_, _), _) => best_store = longer(reached, best_store),
+            (_, Some(output)) => {
+                longest.insert(output, reached);
+            }
+            _ => {}
+        }
+    }
+
+    let mut path = Vec::new();
+    let mut cursor = best_store.map(|(_, _, index)| index);
+    while let Some(index) = cursor {
+        path.push(index);
+        cursor = predecessor.get(&index).copied().flatten();
+    }
+    path.reverse();
+
+    let mut cumulative = 0.0;
+    let mut cumulative_ns = Vec::new();
+    for &index in &path {
+        let node = &graph.nodes[index];
+        cumulative += graph.get_operation_delay_ns(&node.op, node.output_width.unwrap_or(DEFAULT_WIDTH));
+        cumulative_ns.push(cumulative);
+    }
+
+    CriticalPathReport {
+        labels: path.iter().map(|&index| label(&graph.nodes[index].op)).collect(),
+        path: path.iter().map(|&index| graph.nodes[index].id).collect(),
+        cumulative_ns,
+        latency_cycles: best_store.map_or(0, |(cycles, _, _)| cycles),
+        estimated_period_ns: worst_stage_delay / (1.0 - ROUTING_OVERHEAD),
+        target_period_ns: TARGET_PERIOD_NS,
+        bottleneck,
+    }
+}
+
+/// `candidate` if it is strictly longer than `best`, so ties keep the earlier path
+fn longer(candidate: (usize, f64, usize), best: Option<(usize, f64, usize)>) -> Option<(usize, f64, usize)> {
+    match best {
+        Some(best) if (candidate.0, candidate.1) <= (best.0, best.1) => Some(best),
+        _ => Some(candidate),
+    }
+}
+
+/// Cycles an operation adds to a path; ports are free
+fn path_latency(op: &Operation, graph: &Graph) -> usize {
+    match op {
+        Operation::Load(_) | Operation::Store(_, _) => 0,
+        _ => graph.get_operation_latency(op),
+    }
+}
+
+/// `Load(a)`, `Store(result)` or the operation's name
+fn label(op: &Operation) -> String {
+    match op {
+        Operation::Load(name) => format!("Load({})", name),
+        Operation::Store(name, _) => format!("Store({})", name),
+        Operation::Const(value) => format!("Const({})", value),
+        Operation::ArrayLoad(name, _) => format!("ArrayLoad({})", name),
+        Operation::ArrayStore(name, _, _) => format!("ArrayStore({})", name),
+        op => op.name().to_string(),
+    }
+}
+
+/// Worst paths `analyze_timing` lists
+pub const WORST_PATHS: usize = 5;
+
+/// A chain of operations settling within one stage
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimingPath {
+    pub stage: usize,
+    /// Nodes along the chain, in dataflow order; empty for an idle stage
+    pub nodes: Vec<NodeId>,
+    /// `Load(a)`, `Add`, ..., one per node
+    pub labels: Vec<String>,
+    /// Logic delay of the chain, before routing
+    pub delay_ns: f64,
+    /// Logic budget left after `delay_ns`, negative when the chain misses the clock
+    pub slack_ns: f64,
+}
+
+/// Per-stage timing of a graph against a clock period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimingReport {
+    pub clock_period_ns: f64,
+    /// `clock_period_ns` less the `ROUTING_OVERHEAD` share
+    pub logic_budget_ns: f64,
+    /// Longest path of every stage, indexed by stage
+    pub stages: Vec<TimingPath>,
+    /// Longest paths overall, worst first
+    pub worst_paths: Vec<TimingPath>,
+}
+
+impl TimingReport {
+    /// Least slack of any stage
+    pub fn worst_slack_ns(&self) -> f64 {
+        self.stages.iter().map(|path| path.slack_ns).fold(self.logic_budget_ns, f64::min)
+    }
+
+    pub fn meets_timing(&self) -> bool {
+        self.worst_slack_ns() >= 0.0
+    }
+}
+
+impl fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps = |path: &TimingPath| if path.labels.is_empty() { "-".to_string() } else { path.labels.join(" -> ") };
+        writeln!(f, "Timing at {:.2} ns ({:.2} ns of logic per stage)", self.clock_period_ns, self.logic_budget_ns)?;
+        writeln!(f, "{:<6} {:>10} {:>10}  Path", "Stage", "Delay (ns)", "Slack (ns)")?;
+        for path in &self.stages {
+            writeln!(f, "{:<6} {:>10.2} {:>10.2}  {}", path.stage, path.delay_ns, path.slack_ns, steps(path))?;
+        }
+        write!(f, "Worst paths:")?;
+        for path in &self.worst_paths {
+            write!(f, "\n  stage {}: {:.2} ns, slack {:.2} ns: {}", path.stage, path.delay_ns, path.slack_ns, steps(path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Time every stage of `graph` at `clock_ns` with the default delays, listing the `WORST_PATHS` worst paths
+pub fn analyze_timing(graph: &Graph, clock_ns: f64) -> TimingReport {
+    analyze_timing_with(graph, clock_ns, &DelayTable::default(), WORST_PATHS)
+}
+
+/// Time every stage of `graph` at `clock_ns` with `delays`, listing the `worst_paths` worst paths
+///
+/// Within a stage, an operation chains onto an operand produced in the same
+/// cycle whose latency has not passed; anything else comes from a register.
+/// Multi-cycle operations spread their delay over their latency. An
+/// unscheduled graph is one combinational stage.
+pub fn analyze_timing_with(graph: &Graph, clock_ns: f64, delays: &DelayTable, worst_paths: usize) -> TimingReport {
+    let budget = clock_ns * (1.0 - ROUTING_OVERHEAD);
+    let schedule = schedule_of(graph);
+    let scheduled = !schedule.is_empty();
+    let depth = graph.pipeline_stages.len().max(schedule.values().map(|cycle| cycle + 1).max().unwrap_or(1));
+
+    let index: HashMap<NodeId, usize> = graph.nodes.iter().enumerate().map(|(index, node)| (node.id, index)).collect();
+    // Delay settled at each node's output and the operand it chains from
+    let mut settled: HashMap<usize, (f64, Option<usize>)> = HashMap::new();
+    for (position, node) in graph.nodes.iter().enumerate() {
+        if matches!(node.op, Operation::PipelineRegister(_)) {
+            continue;
+        }
+        let cycle = schedule.get(&node.id).copied().unwrap_or(0);
+        let (start, from) = node.op.operands().into_iter()
+            .filter_map(|operand| graph.producer(operand))
+            .filter_map(|producer| {
+                let &(delay, _) = settled.get(&index[&producer.id])?;
+                let produced = schedule.get(&producer.id).copied().unwrap_or(0);
+                let chained = !scheduled || (produced == cycle && produced + graph.get_operation_latency(&producer.op) > cycle);
+                chained.then_some((delay, index[&producer.id]))
+            })
+            .fold((0.0, None), |best: (f64, Option<usize>), (delay, producer)| {
+                if best.1.is_none() || delay > best.0 { (delay, Some(producer)) } else { best }
+            });
+        let latency = if scheduled { graph.get_operation_latency(&node.op).max(1) } else { 1 };
+        let delay = delays.delay_ns(&node.op, node.output_width.unwrap_or(DEFAULT_WIDTH)) / latency as f64;
+        settled.insert(position, (start + delay, from));
+    }
+
+    let path_to = |end: usize| {
+        let mut nodes = vec![end];
+        while let Some(from) = settled[nodes.last().expect("paths are never empty")].1 {
+            nodes.push(from);
+        }
+        nodes.reverse();
+        let delay_ns = settled[&end].0;
+        TimingPath {
+            stage: schedule.get(&graph.nodes[end].id).copied().unwrap_or(0),
+            labels: nodes.iter().map(|&position| label(&graph.nodes[position].op)).collect(),
+            nodes: nodes.iter().map(|&position| graph.nodes[position].id).collect(),
+            delay_ns,
+            slack_ns: budget - delay_ns,
+        }
+    };
+
+    // A chain ends where nothing in its stage chains on from it
+    let chained_from: HashSet<usize> = settled.values().filter_map(|&(_, from)| from).collect();
+    let mut ends: Vec<usize> = (0..graph.nodes.len())
+        .filter(|position| settled.get(position).is_some_and(|&(delay, _)| delay > 0.0) && !chained_from.contains(position))
+        .collect();
+    ends.sort_by(|a, b| settled[b].0.total_cmp(&settled[a].0));
+    let paths: Vec<TimingPath> = ends.into_iter().map(path_to).collect();
+
+    let stages = (0..depth)
+        .map(|stage| paths.iter().find(|path| path.stage == stage).cloned().unwrap_or(TimingPath {
+            stage,
+            nodes: Vec::new(),
+            labels: Vec::new(),
+            delay_ns: 0.0,
+            slack_ns: budget,
+        }))
+        .collect();
+    TimingReport {
+        clock_period_ns: clock_ns,
+        logic_budget_ns: budget,
+        stages,
+        worst_paths: paths.into_iter().take(worst_paths).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::ast::*;
+    use crate::ir::lower::lower_expr_to_graph;
+    use crate::passes::dce::run_dce_pass;
+    use crate::passes::pipeline::{run_pipeline_pass, PipelineScheduler};
+
+    #[test]
+    fn test_three_multiplier_chain_is_nine_cycles() {
+        let chain = mul(mul(mul(input("a", 8), input("b", 8)), input("c", 8)), input("d", 8));
+        let mut graph = lower_expr_to_graph(&output("result", chain));
+        graph.enable_pipeline(1, 10, 1);
+        run_pipeline_pass(&mut graph).unwrap();
+        run_dce_pass(&mut graph);
+
+        let report = analyze_critical_path(&graph, &schedule_of(&graph));
+        assert_eq!(report.latency_cycles, 9);
+        assert_eq!(report.labels, vec!["Load(a)", "Mul", "Mul", "Mul", "Store(result)"]);
+        assert!(report.meets_timing());
+        // One DSP stage per cycle: 1 ns of logic plus routing
+        assert!((report.estimated_period_ns - 1.0 / 0.7).abs() < 1e-9);
+        assert!(report.to_string().starts_with(
+            "Critical path (9 cycles): Load(a) (0.00 ns) -> Mul (3.00 ns) -> Mul (6.00 ns) -> Mul (9.00 ns) -> Store(result) (9.00 ns)"
+        ));
+    }
+
+    #[test]
+    fn test_wide_adder_is_flagged_as_the_bottleneck() {
+        let graph = lower_expr_to_graph(&output("result", mul(add(input("a", 64), input("b", 64)), input("c", 8))));
+        let report = analyze_critical_path(&graph, &HashMap::new());
+
+        // 0.3 + 0.02 * 65 ns of carry chain does not fit in 1.4 ns
+        let bottleneck = report.bottleneck.clone().unwrap();
+        assert_eq!((bottleneck.stage, bottleneck.node), (0, NodeId(2)));
+        assert!(report.estimated_period_ns > TARGET_PERIOD_NS);
+        assert!(report.to_string().contains("Bottleneck: stage 0 (node 2, 1.60 ns of logic) misses the target"));
+    }
+
+    #[test]
+    fn test_timing_report_finds_the_heavy_stage_of_a_pipeline() {
+        // The four adds chain into one stage and the compare gets the next to itself
+        let sum = add(add(add(add(input("a", 8), input("b", 8)), input("c", 8)), input("d", 8)), input("e", 8));
+        let mut graph = lower_expr_to_graph(&output("over", gt(sum, input("limit", 12))));
+        graph.enable_pipeline(1, 8, 1);
+        PipelineScheduler { clock_period_ns: 3.0, ..PipelineScheduler::new() }.schedule_pipeline(&mut graph).unwrap();
+
+        let report = analyze_timing(&graph, 3.0);
+        assert_eq!(report.stages.len(), graph.pipeline_stages.len());
+        let heavy = &report.stages[2];
+        assert_eq!(heavy.labels, ["Add", "Add", "Add", "Add"]);
+        // 0.3 ns plus 0.02 ns per bit, on sums of 9 to 12 bits
+        assert!((heavy.delay_ns - 2.04).abs() < 1e-9);
+        assert!((heavy.slack_ns - 0.06).abs() < 1e-9);
+        assert!(report.stages[1].nodes.is_empty());
+        assert_eq!(report.worst_paths[0], *heavy);
+        assert_eq!(report.worst_paths[1].labels, ["CmpLt", "Store(over)"]);
+        assert!(report.meets_timing());
+
+        let tight = analyze_timing(&graph, 2.5);
+        assert!(!tight.meets_timing());
+        assert!((tight.worst_slack_ns() - (1.75 - 2.04)).abs() < 1e-9);
+        let table = tight.to_string();
+        assert!(table.starts_with("Timing at 2.50 ns (1.75 ns of logic per stage)\nStage  Delay (ns) Slack (ns)  Path\n"));
+        assert!(table.contains("2            2.04      -0.29  Add -> Add -> Add -> Add\n"));
+    }
+
+    #[test]
+    fn test_calibrated_delays_change_the_report() {
+        let graph = lower_expr_to_graph(&output("result", mul(add(input("a", 8), input("b", 8)), input("c", 8))));
+        let mut delays = DelayTable { multiply_ns: 2.0, ..DelayTable::default() };
+        delays.overrides.insert("Add".to_string(), 0.25);
+
+        // Unscheduled, the whole expression settles in one stage
+        let report = analyze_timing_with(&graph, 4.0, &delays, 1);
+        assert_eq!(report.stages[0].labels, ["Load(a)", "Add", "Mul", "Store(result)"]);
+        assert!((report.stages[0].delay_ns - 2.25).abs() < 1e-9);
+        assert_eq!(report.worst_paths.len(), 1);
+        assert!((analyze_timing(&graph, 4.0).stages[0].delay_ns - 3.48).abs() < 1e-9);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["stages"][0]["labels"][1], "Add");
+    }
+}
@@ -0,0 +1,45 @@
+use rust_hls::backend::dsp::{build_fir_graph, generate_fir_filter};
+use rust_hls::backend::sim::Simulator;
+use rust_hls::ir::graph::Operation;
+use rust_hls::passes::report::build_report;
+
+/// y[n] = Σ c_k * x[n - k], with zeros before the first sample
+fn convolve(coefficients: &[i32], samples: &[i64]) -> Vec<i64> {
+    (0..samples.len())
+        .map(|n| coefficients.iter().enumerate().filter(|&(k, _)| k <= n).map(|(k, &c)| i64::from(c) * samples[n - k]).sum())
+        .collect()
+}
+
+#[test]
+fn moving_average_step_response_matches_convolution() {
+    let coefficients = [1, 1, 1, 1];
+    let graph = build_fir_graph(&coefficients);
+    assert_eq!(graph.nodes.iter().filter(|node| matches!(node.op, Operation::Const(_))).count(), 5);
+    assert!(graph.type_check().is_ok());
+
+    // One transaction per sample; the delay line carries over in state registers
+    let step = [8i64; 6];
+    let mut sim = Simulator::new();
+    let outputs: Vec<i64> = step.iter().map(|&sample| {
+        sim.set_input("x", sample, &graph);
+        sim.simulate(&graph).unwrap()["y"]
+    }).collect();
+    assert_eq!(outputs[..4], [8, 16, 24, 32]);
+    assert_eq!(outputs, convolve(&coefficients, &step));
+}
+
+#[test]
+fn fir_graph_and_verilog_use_one_dsp_per_tap() {
+    let coefficients = [5, -3, 0, 7, 2];
+    assert_eq!(build_report(&build_fir_graph(&coefficients)).multipliers, 5);
+    assert_eq!(generate_fir_filter("fir5", &coefficients, 16, 8).matches("DSP48E2 #(").count(), 5);
+
+    let samples = [3i64, -1, 4, 1, -5, 9, 2, -6];
+    let graph = build_fir_graph(&coefficients);
+    let mut sim = Simulator::new();
+    let outputs: Vec<i64> = samples.iter().map(|&sample| {
+        sim.set_input("x", sample, &graph);
+        sim.simulate(&graph).unwrap()["y"]
+    }).collect();
+    assert_eq!(outputs, convolve(&coefficients, &samples));
+}
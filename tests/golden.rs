@@ -0,0 +1,78 @@
+//! Golden-file regression tests for generated Verilog
+//!
+//! Each test compares a module against `tests/golden/<name>.v`, so any
+//! change to the emitted text shows up as a failing diff. Run with
+//! `UPDATE_GOLDEN=1` to rewrite the files after an intended change.
+
+use rust_hls::backend::verilog::generate_verilog_module;
+use rust_hls::dsl::ast::*;
+use rust_hls::hft::ZeroPlusStrategy;
+use rust_hls::ir::lower::lower_expr_to_graph;
+use rust_hls::passes::pipeline::PipelineScheduler;
+use std::path::PathBuf;
+
+/// Compare `verilog` against the checked-in golden file `name`, or rewrite it when `UPDATE_GOLDEN=1`
+fn assert_matches_golden(name: &str, verilog: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.v", name));
+    if std::env::var("UPDATE_GOLDEN").is_ok_and(|value| value == "1") {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, verilog).unwrap();
+        return;
+    }
+
+    let golden = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read {}: {} (run with UPDATE_GOLDEN=1 to create it)", path.display(), e));
+    let (expected, actual) = (normalize(&golden), normalize(verilog));
+    if let Some(line) = (0..expected.len().max(actual.len())).find(|&line| expected.get(line) != actual.get(line)) {
+        panic!(
+            "{} differs from {} at line {}:\n  golden:    {}\n  generated: {}\n(run with UPDATE_GOLDEN=1 if the change is intended)",
+            name,
+            path.display(),
+            line + 1,
+            expected.get(line).copied().unwrap_or("<end of file>"),
+            actual.get(line).copied().unwrap_or("<end of file>"),
+        );
+    }
+}
+
+/// Lines without trailing whitespace, the leading comment header or trailing blank lines
+///
+/// The header holds free-form notes such as a generation timestamp;
+/// `// synthesis` pragmas are part of the design and stay.
+fn normalize(verilog: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = verilog.lines()
+        .map(str::trim_end)
+        .skip_while(|line| line.starts_with("//") && !line.starts_with("// synthesis"))
+        .collect();
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    lines
+}
+
+#[test]
+fn golden_pipelined_mac() {
+    let mac = add(add(mul(input("a", 16), input("b", 16)), mul(input("c", 16), input("d", 16))), input("e", 16));
+    let mut graph = lower_expr_to_graph(&output("result", mac));
+    graph.enable_pipeline(1, 4, 1);
+    PipelineScheduler::new().schedule_pipeline(&mut graph).unwrap();
+    assert_matches_golden("pipelined_mac", &generate_verilog_module(&graph, "pipelined_mac", None).unwrap());
+}
+
+#[test]
+fn golden_simple_adder() {
+    let graph = lower_expr_to_graph(&output("result", add(input("a", 32), input("b", 32))));
+    assert_matches_golden("simple_adder", &generate_verilog_module(&graph, "adder", None).unwrap());
+}
+
+#[test]
+fn golden_hft_decision() {
+    assert_matches_golden("hft_decision", &generate_verilog_module(&ZeroPlusStrategy::to_fpga_graph(), "hft_decision", None).unwrap());
+}
+
+#[test]
+fn normalize_ignores_header_and_trailing_whitespace() {
+    let stamped = "// Generated 2026-01-01 12:00\n// synthesis translate_off\nmodule m;  \nendmodule\n\n";
+    assert_eq!(normalize(stamped), normalize("// synthesis translate_off\nmodule m;\nendmodule"));
+    assert_ne!(normalize("module m;\n"), normalize("module  m;\n"));
+}
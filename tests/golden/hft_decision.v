@@ -0,0 +1,154 @@
+// Generated for AMD Alveo U50 - SIMPLE VERSION
+// synthesis translate_off
+`timescale 1ns / 1ps
+// synthesis translate_on
+
+module hft_decision #(
+    parameter integer DATA_WIDTH = 32,
+    parameter integer ADDR_WIDTH = 16
+) (
+    // Clock and Reset
+    input  wire                    ap_clk,
+    input  wire                    ap_rst_n,
+    
+    // Control signals (HLS-style)
+    input  wire                    ap_start,
+    output reg                     ap_done,
+    output wire                    ap_idle,
+    output wire                    ap_ready,
+    
+    // Data inputs
+    input  wire [0:0]  ask_queue_strong,
+    input  wire [31:0]  best_ask_price,
+    input  wire [31:0]  best_ask_qty,
+    input  wire [31:0]  best_bid_price,
+    input  wire [31:0]  best_bid_qty,
+    input  wire [0:0]  bid_queue_strong,
+    input  wire signed [31:0]  current_position,
+    input  wire [31:0]  last_fill_price,
+    input  wire [7:0]  last_fill_side,
+    
+    // Data outputs
+    output wire [7:0]  action,
+    output wire [31:0]  price,
+    output wire [31:0]  quantity
+);
+
+    // Simple control state machine
+    (* DONT_TOUCH = "yes" *) reg [1:0] state;
+    localparam IDLE = 2'b00, COMPUTE = 2'b01, DONE = 2'b10;
+    
+    // Intermediate computation wires
+    wire [31:0] node_9;
+    wire [0:0] node_14;
+    wire [0:0] node_15;
+    wire [0:0] node_16;
+    wire [0:0] node_17;
+    wire [0:0] node_18;
+    wire [0:0] node_19;
+    wire [0:0] node_20;
+    wire [0:0] node_21;
+    wire [0:0] node_22;
+    wire [0:0] node_23;
+    wire [0:0] node_24;
+    wire [0:0] node_25;
+    wire [0:0] node_26;
+    wire [7:0] node_27;
+    wire [31:0] node_28;
+    wire [31:0] node_29;
+    wire [0:0] node_30;
+    wire [0:0] node_32;
+    wire [0:0] node_33;
+    wire [0:0] node_35;
+    wire [0:0] node_36;
+    wire [0:0] node_37;
+    wire [0:0] node_38;
+    wire [0:0] node_39;
+    wire [0:0] node_40;
+    wire [0:0] node_41;
+    wire [7:0] node_42;
+    wire [7:0] node_43;
+    wire [7:0] node_44;
+    wire [31:0] node_45;
+    wire [31:0] node_46;
+    wire [31:0] node_47;
+    wire [31:0] node_49;
+    wire [31:0] node_50;
+
+    // Combinational logic for all operations
+    assign node_9 = (best_ask_price < best_bid_price) ? 32'd0 : best_ask_price - best_bid_price;  // Saturating subtraction
+    assign node_14 = ($signed(current_position) != 32'd0) ? 1'd1 : 1'd0;  // Not equal
+    assign node_15 = (last_fill_side == 8'd1) ? 1'd1 : 1'd0;  // Equality
+    assign node_16 = (last_fill_side == 8'd2) ? 1'd1 : 1'd0;  // Equality
+    assign node_17 = ~bid_queue_strong;  // Bitwise NOT
+    assign node_18 = (best_bid_price < last_fill_price) ? 1'd1 : 1'd0;  // Less than
+    assign node_19 = node_17 | node_18;  // Bitwise OR
+    assign node_20 = node_15 & node_19;  // Bitwise AND
+    assign node_21 = ~ask_queue_strong;  // Bitwise NOT
+    assign node_22 = (best_ask_price > last_fill_price) ? 1'd1 : 1'd0;  // Greater than
+    assign node_23 = node_21 | node_22;  // Bitwise OR
+    assign node_24 = node_16 & node_23;  // Bitwise AND
+    assign node_25 = node_20 | node_24;  // Bitwise OR
+    assign node_26 = node_14 & node_25;  // Bitwise AND
+    assign node_27 = (node_15 != 0) ? 8'd2 : 8'd1;  // Multiplexer
+    assign node_28 = (node_15 != 0) ? best_bid_price : best_ask_price;  // Multiplexer
+    assign node_29 = (current_position[31]) ? (~current_position + 1) : current_position;  // Absolute value
+    assign node_30 = ($signed(current_position) == 32'd0) ? 1'd1 : 1'd0;  // Equality
+    assign node_32 = (node_9 == 32'd1) ? 1'd1 : 1'd0;  // Equality
+    assign node_33 = node_30 & node_32;  // Bitwise AND
+    assign node_35 = (best_bid_qty >= 32'd100) ? 1'd1 : 1'd0;  // Greater than or equal
+    assign node_36 = bid_queue_strong & node_35;  // Bitwise AND
+    assign node_37 = node_33 & node_36;  // Bitwise AND
+    assign node_38 = (best_ask_qty >= 32'd100) ? 1'd1 : 1'd0;  // Greater than or equal
+    assign node_39 = ask_queue_strong & node_38;  // Bitwise AND
+    assign node_40 = node_33 & node_39;  // Bitwise AND
+    assign node_41 = node_37 | node_40;  // Bitwise OR
+    assign node_42 = (node_40 != 0) ? 8'd2 : 8'd0;  // Multiplexer
+    assign node_43 = (node_37 != 0) ? 8'd1 : node_42;  // Multiplexer
+    assign node_44 = (node_26 != 0) ? node_27 : node_43;  // Multiplexer
+    assign node_45 = (node_40 != 0) ? best_ask_price : 32'd0;  // Multiplexer
+    assign node_46 = (node_37 != 0) ? best_bid_price : node_45;  // Multiplexer
+    assign node_47 = (node_26 != 0) ? node_28 : node_46;  // Multiplexer
+    assign node_49 = (node_41 != 0) ? 32'd50 : 32'd0;  // Multiplexer
+    assign node_50 = (node_26 != 0) ? node_29 : node_49;  // Multiplexer
+
+    // Output registers
+    reg [7:0] action_reg;
+    reg [31:0] price_reg;
+    reg [31:0] quantity_reg;
+    
+    always @(posedge ap_clk) begin
+        if (!ap_rst_n) begin
+            state <= IDLE;
+            ap_done <= 1'b0;
+            action_reg <= 8'd0;
+            price_reg <= 32'd0;
+            quantity_reg <= 32'd0;
+        end else begin
+            case (state)
+                IDLE: begin
+                    ap_done <= 1'b0;
+                    if (ap_start) state <= COMPUTE;
+                end
+                COMPUTE: begin
+                    state <= DONE;
+                    ap_done <= 1'b1;
+                    action_reg <= node_44;
+                    price_reg <= node_47;
+                    quantity_reg <= node_50;
+                end
+                default: begin
+                    state <= IDLE;
+                    ap_done <= 1'b0;
+                end
+            endcase
+        end
+    end
+    
+    assign ap_idle = (state == IDLE);
+    assign ap_ready = (state == IDLE);
+    assign action = action_reg;
+    assign price = price_reg;
+    assign quantity = quantity_reg;
+
+endmodule
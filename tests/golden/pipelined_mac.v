@@ -0,0 +1,140 @@
+// Generated for AMD Alveo U50 - PIPELINED VERSION (CLEAN)
+// Pipeline: 6-stage MAC implementation
+// synthesis translate_off
+`timescale 1ns / 1ps
+// synthesis translate_on
+
+module pipelined_mac #(
+    parameter integer DATA_WIDTH = 32,
+    parameter integer ADDR_WIDTH = 16
+) (
+    // Clock and Reset
+    input  wire                    ap_clk,
+    input  wire                    ap_rst_n,
+    
+    // Control signals (HLS-style)
+    input  wire                    ap_start,
+    output reg                     ap_done,
+    output wire                    ap_idle,
+    output wire                    ap_ready,
+    
+    // Data inputs - MAC: result = (a * b) + (c * d) + e
+    input  wire [15:0]  a,
+    input  wire [15:0]  b,
+    input  wire [15:0]  c,
+    input  wire [15:0]  d,
+    input  wire [15:0]  e,
+    
+    // Data outputs
+    output wire [33:0]  result
+);
+
+    // Pipeline control signals
+    reg [5:0] stage_valid;  // 6-stage pipeline
+    
+    // Pipeline registers for Stage 0 (Input Registration)
+    reg [15:0] a_reg0;
+    reg [15:0] b_reg0;
+    reg [15:0] c_reg0;
+    reg [15:0] d_reg0;
+    reg [15:0] e_reg0;
+    
+    // Pipeline registers for Stage 1 (Multiplication)
+    reg [31:0] prod0_reg1;
+    reg [31:0] prod1_reg1;
+    reg [15:0] e_reg1;
+    
+    // Pipeline registers for Stage 2 (Sum of Products)
+    reg [33:0] products_reg2;
+    reg [15:0] e_reg2;
+    
+    // Pipeline registers for Stages 3-4 (Accumulation and Output)
+    reg [33:0] result_reg3;
+    reg [33:0] result_reg4;
+    reg [33:0] result_reg5;
+    
+    // Pipeline Stage 0: Input Registration
+    always @(posedge ap_clk) begin
+        if (!ap_rst_n) begin
+            a_reg0 <= 16'd0;
+            b_reg0 <= 16'd0;
+            c_reg0 <= 16'd0;
+            d_reg0 <= 16'd0;
+            e_reg0 <= 16'd0;
+        end else if (ap_start) begin
+            a_reg0 <= a;
+            b_reg0 <= b;
+            c_reg0 <= c;
+            d_reg0 <= d;
+            e_reg0 <= e;
+        end
+    end
+    
+    // Pipeline Stage 1: Parallel Multiplications (DSP48E2 inferred)
+    always @(posedge ap_clk) begin
+        if (!ap_rst_n) begin
+            prod0_reg1 <= 32'd0;
+            prod1_reg1 <= 32'd0;
+            e_reg1 <= 16'd0;
+        end else if (stage_valid[0]) begin
+            prod0_reg1 <= a_reg0 * b_reg0;
+            prod1_reg1 <= c_reg0 * d_reg0;
+            e_reg1 <= e_reg0;
+        end
+    end
+    
+    // Pipeline Stage 2: Sum of Products
+    always @(posedge ap_clk) begin
+        if (!ap_rst_n) begin
+            products_reg2 <= 34'd0;
+            e_reg2 <= 16'd0;
+        end else if (stage_valid[1]) begin
+            products_reg2 <= prod0_reg1 + prod1_reg1;
+            e_reg2 <= e_reg1;
+        end
+    end
+    
+    // Pipeline Stage 3: Accumulation
+    always @(posedge ap_clk) begin
+        if (!ap_rst_n) begin
+            result_reg3 <= 34'd0;
+        end else if (stage_valid[2]) begin
+            result_reg3 <= products_reg2 + e_reg2;
+        end
+    end
+    
+    // Pipeline Stage 4: Output Register
+    always @(posedge ap_clk) begin
+        if (!ap_rst_n) begin
+            result_reg4 <= 34'd0;
+        end else if (stage_valid[3]) begin
+            result_reg4 <= result_reg3;
+        end
+    end
+    
+    // Pipeline Stage 5: Output Delay, to the scheduled latency
+    always @(posedge ap_clk) begin
+        if (!ap_rst_n) begin
+            result_reg5 <= 34'd0;
+        end else if (stage_valid[4]) begin
+            result_reg5 <= result_reg4;
+        end
+    end
+    
+    assign result = result_reg5;
+    
+    // Valid bits travel alongside the data
+    always @(posedge ap_clk) begin
+        if (!ap_rst_n) begin
+            stage_valid <= 6'b0;
+            ap_done <= 1'b0;
+        end else begin
+            stage_valid <= {stage_valid[4:0], ap_start};
+            ap_done <= stage_valid[4];
+        end
+    end
+
+    assign ap_idle = ~|stage_valid;
+    assign ap_ready = 1'b1;  // A new input is accepted every cycle
+
+endmodule
@@ -0,0 +1,69 @@
+// Generated for AMD Alveo U50 - SIMPLE VERSION
+// synthesis translate_off
+`timescale 1ns / 1ps
+// synthesis translate_on
+
+module adder #(
+    parameter integer DATA_WIDTH = 32,
+    parameter integer ADDR_WIDTH = 16
+) (
+    // Clock and Reset
+    input  wire                    ap_clk,
+    input  wire                    ap_rst_n,
+    
+    // Control signals (HLS-style)
+    input  wire                    ap_start,
+    output reg                     ap_done,
+    output wire                    ap_idle,
+    output wire                    ap_ready,
+    
+    // Data inputs
+    input  wire [31:0]  a,
+    input  wire [31:0]  b,
+    
+    // Data outputs
+    output wire [32:0]  result
+);
+
+    // Simple control state machine
+    (* DONT_TOUCH = "yes" *) reg [1:0] state;
+    localparam IDLE = 2'b00, COMPUTE = 2'b01, DONE = 2'b10;
+    
+    // Intermediate computation wires
+    wire [32:0] node_2;
+
+    // Combinational logic for all operations
+    assign node_2 = a + b;  // Addition
+
+    // Output registers
+    reg [32:0] result_reg;
+    
+    always @(posedge ap_clk) begin
+        if (!ap_rst_n) begin
+            state <= IDLE;
+            ap_done <= 1'b0;
+            result_reg <= 33'd0;
+        end else begin
+            case (state)
+                IDLE: begin
+                    ap_done <= 1'b0;
+                    if (ap_start) state <= COMPUTE;
+                end
+                COMPUTE: begin
+                    state <= DONE;
+                    ap_done <= 1'b1;
+                    result_reg <= node_2;
+                end
+                default: begin
+                    state <= IDLE;
+                    ap_done <= 1'b0;
+                end
+            endcase
+        end
+    end
+    
+    assign ap_idle = (state == IDLE);
+    assign ap_ready = (state == IDLE);
+    assign result = result_reg;
+
+endmodule
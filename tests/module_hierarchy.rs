@@ -0,0 +1,226 @@
+use rust_hls::backend::sim::Simulator;
+use rust_hls::backend::systemverilog::generate_systemverilog_module;
+use rust_hls::backend::testbench::TestbenchRunner;
+use rust_hls::backend::verilog::generate_verilog_module;
+use rust_hls::error::HlsError;
+use rust_hls::dsl::ast::*;
+use rust_hls::dsl::hls::HLSFunction;
+use rust_hls::ir::graph::{Graph, Operation, ValueId};
+use rust_hls::ir::lower::lower_expr_to_graph;
+use rust_hls::passes::pipeline::PipelineScheduler;
+use std::collections::HashMap;
+
+/// `(a_re + j a_im) * (b_re + j b_im)` on 16-bit signed parts, wrapping to 16 bits
+fn complex_multiply() -> HLSFunction {
+    let mut cmul = HLSFunction::new("cmul");
+    cmul.pipeline_advanced(1, 4, 1);
+    let graph = &mut cmul.graph;
+    let [a_re, a_im, b_re, b_im] = ["a_re", "a_im", "b_re", "b_im"]
+        .map(|name| graph.add_node_with_output_type(Operation::Load(name.to_string()), Some(16), true));
+    let mut product = |a, b| graph.add_node_with_output(Operation::SMul(a, b));
+    let (rr, ii, ri, ir) = (product(a_re, b_re), product(a_im, b_im), product(a_re, b_im), product(a_im, b_re));
+    let re = graph.add_node_with_output_type(Operation::SSub(rr, ii), Some(16), true);
+    let im = graph.add_node_with_output_type(Operation::SAdd(ri, ir), Some(16), true);
+    graph.add_node(Operation::Store("re".to_string(), re));
+    graph.add_node(Operation::Store("im".to_string(), im));
+    graph.set_port_order(&["a_re", "a_im", "b_re", "b_im", "re", "im"]);
+    cmul
+}
+
+fn reference_multiply((a_re, a_im): (i16, i16), (b_re, b_im): (i16, i16)) -> (i16, i16) {
+    let re = a_re.wrapping_mul(b_re).wrapping_sub(a_im.wrapping_mul(b_im));
+    let im = a_re.wrapping_mul(b_im).wrapping_add(a_im.wrapping_mul(b_re));
+    (re, im)
+}
+
+/// Four complex multiplies: `p = x * w`, `q = p * w`, `r = x * x` and `s = q * r`
+fn twiddle_chain() -> (HLSFunction, [ValueId; 4]) {
+    let cmul = complex_multiply();
+    let mut parent = HLSFunction::new("twiddle");
+    parent.pipeline_advanced(1, 16, 1);
+    let [x_re, x_im, w_re, w_im] = ["x_re", "x_im", "w_re", "w_im"]
+        .map(|name| parent.graph.add_node_with_output_type(Operation::Load(name.to_string()), Some(16), true));
+    let p = parent.call(&cmul, &[x_re, x_im, w_re, w_im]).unwrap();
+    let q = parent.call(&cmul, &[p[0], p[1], w_re, w_im]).unwrap();
+    let r = parent.call(&cmul, &[x_re, x_im, x_re, x_im]).unwrap();
+    let s = parent.call(&cmul, &[q[0], q[1], r[0], r[1]]).unwrap();
+    for (name, value) in [("r_re", r[0]), ("r_im", r[1]), ("s_re", s[0]), ("s_im", s[1])] {
+        parent.graph.add_node(Operation::Store(name.to_string(), value));
+    }
+    (parent, [r[0], r[1], s[0], s[1]])
+}
+
+fn calls(graph: &Graph) -> Vec<&rust_hls::ir::graph::Node> {
+    graph.nodes.iter().filter(|node| matches!(node.op, Operation::Call(_, _))).collect()
+}
+
+#[test]
+fn four_calls_simulate_like_the_complex_multiplies() {
+    let (parent, _) = twiddle_chain();
+    assert!(parent.graph.validate().is_ok());
+    assert_eq!(calls(&parent.graph).len(), 4);
+    assert_eq!(parent.graph.callees.len(), 1);
+
+    for (x, w) in [((3, -2), (5, 7)), ((-120, 45), (99, -13)), ((32767, -32768), (-1, 2))] {
+        let mut sim = Simulator::new();
+        for (name, value) in [("x_re", x.0), ("x_im", x.1), ("w_re", w.0), ("w_im", w.1)] {
+            sim.set_input(name, i64::from(value), &parent.graph);
+        }
+        let outputs = sim.simulate(&parent.graph).unwrap();
+
+        let q = reference_multiply(reference_multiply(x, w), w);
+        let r = reference_multiply(x, x);
+        let s = reference_multiply(q, r);
+        assert_eq!((outputs["r_re"], outputs["r_im"]), (i64::from(r.0), i64::from(r.1)));
+        assert_eq!((outputs["s_re"], outputs["s_im"]), (i64::from(s.0), i64::from(s.1)));
+    }
+}
+
+#[test]
+fn scheduler_gives_a_call_the_callee_pipeline_depth() {
+    let (mut parent, results) = twiddle_chain();
+    let depth = parent.graph.callee("cmul").unwrap().scheduled_latency();
+    assert!(depth > 1);
+    PipelineScheduler::new().schedule_pipeline(&mut parent.graph).unwrap();
+
+    let cycle = |value: ValueId| {
+        let node = parent.graph.producer(value).unwrap().id;
+        parent.graph.pipeline_stages.iter().find(|stage| stage.operations.contains(&node)).unwrap().cycle
+    };
+    let call_cycles: Vec<usize> = calls(&parent.graph).iter()
+        .map(|node| cycle(node.output.unwrap()))
+        .collect();
+    // p and r start together, q once p is out and s once q is
+    let [p, q, r, s] = call_cycles[..] else { panic!("four calls expected") };
+    assert_eq!((r, q - p, s - q), (p, depth, depth));
+    assert_eq!(cycle(results[2]), s + depth);
+}
+
+#[test]
+fn verilog_emits_the_callee_once_and_instantiates_it_per_call() {
+    let (mut parent, _) = twiddle_chain();
+    let verilog = parent.generate_verilog().unwrap();
+
+    assert_eq!(verilog.matches("module cmul").count(), 1);
+    assert_eq!(verilog.matches("module twiddle").count(), 1);
+    for instance in 0..4 {
+        assert!(verilog.contains(&format!("cmul inst_{} (", instance)), "missing inst_{}", instance);
+    }
+    // The first call starts with the transaction, on the inputs in the callee's port order
+    let first = &verilog[verilog.find("cmul inst_0 (").unwrap()..];
+    let first = &first[..first.find(");").unwrap()];
+    let ports: Vec<&str> = first.lines().skip(1).filter(|line| !line.trim().is_empty()).map(|line| line.trim().split('(').next().unwrap()).collect();
+    assert_eq!(ports, [".ap_clk", ".ap_rst_n", ".ap_start", ".ap_done", ".ap_idle", ".ap_ready", ".a_re", ".a_im", ".b_re", ".b_im", ".re", ".im"]);
+    // Each call is started by the valid bit of its stage, on that stage's copy of the inputs
+    let stage = verilog[..verilog.find("cmul inst_0 (").unwrap()].rfind("// Stage ").unwrap();
+    let stage: usize = verilog[stage + 9..].split_whitespace().next().unwrap().parse().unwrap();
+    assert!(first.contains(&format!(".ap_start(stage_valid[{}])", stage - 1)));
+    assert!(first.contains(&format!(".a_re(x_re_r{})", stage - 1)));
+}
+
+#[test]
+fn systemverilog_callees_are_written_in_systemverilog() {
+    let (mut parent, _) = twiddle_chain();
+    parent.generate_verilog().unwrap();
+    let sv = generate_systemverilog_module(&parent.graph, "twiddle").unwrap();
+
+    assert_eq!(sv.matches("module cmul").count(), 1);
+    assert!(sv.contains("cmul inst_3 ("));
+    // Each module gets its own typedefs
+    assert_eq!(sv.matches("typedef logic [DATA_WIDTH-1:0] data_t;").count(), 2);
+    assert!(!sv.contains(" wire ") && !sv.contains(" reg "));
+}
+
+#[test]
+fn calls_need_a_pipelined_caller_and_callee() {
+    let mut cmul = complex_multiply();
+    cmul.graph.pipeline_config.enable = false;
+    let mut parent = HLSFunction::new("twiddle");
+    parent.pipeline_advanced(1, 16, 1);
+    let inputs: Vec<ValueId> = ["a", "b", "c", "d"].iter().map(|name| parent.input(name).value).collect();
+    let results = parent.call(&cmul, &inputs).unwrap();
+    parent.graph.add_node(Operation::Store("re".to_string(), results[0]));
+    assert!(parent.generate_verilog().is_err());
+
+    let mut flat = parent.graph.clone();
+    flat.pipeline_config.enable = false;
+    assert!(generate_verilog_module(&flat, "twiddle", None).is_err());
+}
+
+/// `result = a * b + c * d + e` on 16-bit inputs, pipelined at II=1
+///
+/// Lowered like the crate's own `lowered_mac` test fixture, which integration tests cannot see.
+fn pipelined_mac() -> Graph {
+    let mac = add(add(mul(input("a", 16), input("b", 16)), mul(input("c", 16), input("d", 16))), input("e", 16));
+    let mut graph = lower_expr_to_graph(&output("result", mac));
+    graph.enable_pipeline(1, 8, 1);
+    graph
+}
+
+/// The MAC with its two multiplies moved into `mac_products`, both scheduled
+fn hierarchical_mac() -> Graph {
+    let mut graph = pipelined_mac();
+    let multiplies: Vec<_> = graph.nodes.iter().filter(|node| matches!(node.op, Operation::Mul(_, _))).map(|node| node.id).collect();
+    let products = graph.extract_module(&multiplies, "mac_products").unwrap();
+    assert_eq!(products.len(), 2);
+    PipelineScheduler::new().schedule_pipeline(&mut graph.callees[0].graph).unwrap();
+    PipelineScheduler::new().schedule_pipeline(&mut graph).unwrap();
+    graph
+}
+
+fn mac_inputs() -> Vec<HashMap<String, u64>> {
+    [[3, 5, 7, 11, 13], [65535, 65535, 65535, 65535, 65535], [0, 1234, 4321, 0, 9], [200, 300, 400, 500, 600]].iter()
+        .map(|values| ["a", "b", "c", "d", "e"].iter().map(|port| port.to_string()).zip(values.iter().copied()).collect())
+        .collect()
+}
+
+#[test]
+fn extracted_multiplies_simulate_like_the_flat_mac() {
+    let mut flat = pipelined_mac();
+    PipelineScheduler::new().schedule_pipeline(&mut flat).unwrap();
+    let hierarchical = hierarchical_mac();
+    assert!(hierarchical.validate().is_ok());
+    assert_eq!(calls(&hierarchical).len(), 1);
+    let core = hierarchical.callee("mac_products").unwrap();
+    assert_eq!(core.input_names(), ["a", "b", "c", "d"]);
+    assert_eq!(core.output_names().len(), 2);
+
+    let flat_verilog = generate_verilog_module(&flat, "mac", None).unwrap();
+    let verilog = generate_verilog_module(&hierarchical, "mac", None).unwrap();
+    assert!(!flat_verilog.contains("module mac_products"));
+    assert_eq!(verilog.matches("module mac_products").count(), 1);
+    assert!(verilog.contains("mac_products inst_0 ("));
+
+    for inputs in mac_inputs() {
+        let outputs: Vec<HashMap<String, i64>> = [&flat, &hierarchical].iter()
+            .map(|graph| {
+                let mut sim = Simulator::new();
+                for (port, value) in &inputs {
+                    sim.set_input(port, *value as i64, graph);
+                }
+                sim.simulate(graph).unwrap()
+            })
+            .collect();
+        let expected = inputs["a"] * inputs["b"] + inputs["c"] * inputs["d"] + inputs["e"];
+        assert_eq!(outputs[0]["result"] as u64, expected);
+        assert_eq!(outputs[1], outputs[0]);
+    }
+
+    // The Verilated modules agree too
+    let mut results: Vec<Vec<u64>> = Vec::new();
+    for (name, graph) in [("flat_mac", &flat), ("hierarchical_mac", &hierarchical)] {
+        let mut runner = TestbenchRunner::new(name);
+        match runner.prepare(graph) {
+            Ok(()) => {
+                let testbench = runner.create_testbench().expect("failed to load the MAC testbench");
+                results.push(mac_inputs().iter().map(|inputs| testbench.run_test_timed(inputs, graph).unwrap().0["result"]).collect());
+            }
+            Err(HlsError::CompilerNotFound(_)) => {
+                println!("Skipping hierarchical MAC simulation - Verilator not installed");
+                return;
+            }
+            Err(e) => panic!("Unexpected error building {}: {}", name, e),
+        }
+    }
+    assert_eq!(results[1], results[0]);
+}
@@ -0,0 +1,22 @@
+use rust_hls::dsl::ast::*;
+use rust_hls::ir::lower::lower_expr_to_graph;
+use rust_hls::passes::dce::run_dce_pass;
+use rust_hls::passes::pipeline::run_pipeline_pass;
+use rust_hls::passes::resource_estimate::{estimate_resources, U50_CAPACITY};
+
+#[test]
+fn five_stage_mac_fits_in_a_handful_of_dsps() {
+    let mac = add(add(mul(input("a", 16), input("b", 16)), mul(input("c", 16), input("d", 16))), input("e", 16));
+    let mut graph = lower_expr_to_graph(&output("result", mac));
+    graph.enable_pipeline(1, 5, 1);
+    run_pipeline_pass(&mut graph).unwrap();
+    run_dce_pass(&mut graph);
+
+    let estimate = estimate_resources(&graph);
+    assert!((2..=6).contains(&estimate.dsps), "{:?}", estimate);
+
+    // Both 32-bit products cross one stage boundary, and `e` waits three stages for the final add
+    assert!(estimate.ffs >= 2 * 32 + 3 * 16, "{:?}", estimate);
+    assert!(estimate.fits(&U50_CAPACITY));
+    estimate.print_summary(&U50_CAPACITY);
+}
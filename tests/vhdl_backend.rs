@@ -0,0 +1,30 @@
+use regex::Regex;
+use rust_hls::backend::vhdl::generate_vhdl_module;
+use rust_hls::dsl::ast::*;
+use rust_hls::hft::ZeroPlusStrategy;
+use rust_hls::ir::lower::lower_expr_to_graph;
+
+fn assert_entity_architecture_pair(vhdl: &str, module_name: &str) {
+    let entity = Regex::new(&format!(r"(?m)^entity {} is$[\s\S]*^end entity {};$", module_name, module_name)).unwrap();
+    let architecture = Regex::new(&format!(r"(?m)^architecture rtl of {} is$[\s\S]*^begin$[\s\S]*^end architecture rtl;$", module_name)).unwrap();
+    let packages = Regex::new(r"(?m)^use ieee\.std_logic_1164\.all;\s*^use ieee\.numeric_std\.all;").unwrap();
+
+    assert!(entity.is_match(vhdl), "missing entity:\n{}", vhdl);
+    assert!(architecture.is_match(vhdl), "missing architecture:\n{}", vhdl);
+    assert!(packages.is_match(vhdl), "missing packages:\n{}", vhdl);
+}
+
+#[test]
+fn vhdl_module_for_simple_adder() {
+    let graph = lower_expr_to_graph(&output("result", add(input("a", 32), input("b", 32))));
+    assert_entity_architecture_pair(&generate_vhdl_module(&graph, "adder"), "adder");
+}
+
+#[test]
+fn vhdl_module_for_hft_decision_graph() {
+    // Exercises comparisons, logic, muxes, a saturating subtract and an absolute value
+    let vhdl = generate_vhdl_module(&ZeroPlusStrategy::to_fpga_graph(), "hft_decision");
+    assert_entity_architecture_pair(&vhdl, "hft_decision");
+    assert!(vhdl.contains("best_bid_price : in  std_logic_vector(31 downto 0)"));
+    assert!(vhdl.contains("bid_queue_strong : in  std_logic_vector(0 downto 0)"));
+}